@@ -0,0 +1,40 @@
+/*
+ * C ABI of the vayload_kit library, built with `make ffi`.
+ *
+ * Arguments are NUL-terminated UTF-8. Every function returns a JSON document
+ * to free with vk_string_free():
+ *
+ *   {"ok": true, ..., "messages": [{"level": "step", "message": "..."}]}
+ *   {"ok": false, "error": {"code": "manifest-invalid", "message": "..."}, "messages": [...]}
+ */
+#ifndef VAYLOAD_KIT_H
+#define VAYLOAD_KIT_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Parses and checks plugin.json5 source; the manifest is under "manifest". */
+char *vk_manifest_parse(const char *source);
+
+/* Writes a manifest given as JSON as plugin.json5 source, under "source". */
+char *vk_manifest_serialize(const char *manifest);
+
+/* Checks the plugin in dir like `vk validate`. */
+char *vk_validate(const char *dir);
+
+/* Lists the files the package of the plugin in dir would contain, under
+ * "files" as {"name": ..., "size": ...}. */
+char *vk_pack_preview(const char *dir);
+
+/* Builds the package of the plugin in dir like `vk pack`, into out, or into
+ * the plugin's .vk/ directory when out is NULL. */
+char *vk_pack(const char *dir, const char *out);
+
+void vk_string_free(char *answer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
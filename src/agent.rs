@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::i18n::t;
+
+/// The key the agent holds, if one is running.
+#[cfg(unix)]
+pub fn fetch(socket: &Path) -> Option<[u8; 32]> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    // Whoever could place the socket could hand out any key
+    socket.parent().and_then(|dir| private_dir(dir).ok())?;
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(2))).ok()?;
+    stream.write_all(b"get\n").ok()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    hex::decode(line.trim()).ok()?.try_into().ok()
+}
+
+/// Starts an agent holding `key` for `timeout`, replacing any running one.
+/// Like `ssh-agent`, it keeps the key derived from the credentials
+/// passphrase in memory so the passphrase is asked once per session rather
+/// than by every command. It is a `vk auth agent` process listening on a
+/// socket in a directory only the user can enter, and exits once its time is up or when
+/// `vk auth lock` stops it. Windows has no agent.
+#[cfg(unix)]
+pub fn start(socket: &Path, key: &[u8; 32], timeout: Duration) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    stop(socket);
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["auth", "agent", "--timeout", &timeout.as_secs().to_string(), "--socket"])
+        .arg(socket)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Outlives the command, and Ctrl-C on it
+        .process_group(0)
+        .spawn()?;
+
+    // The key goes through a pipe, never the command line
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", hex::encode(key))?;
+    }
+    Ok(())
+}
+
+/// Stops the running agent. Returns whether there was one.
+#[cfg(unix)]
+pub fn stop(socket: &Path) -> bool {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    let stopped = UnixStream::connect(socket).and_then(|mut stream| stream.write_all(b"stop\n")).is_ok();
+    let _ = std::fs::remove_file(socket);
+    stopped
+}
+
+/// Runs the agent: reads the key from the standard input and hands it to
+/// whoever connects until `timeout` is up.
+#[cfg(unix)]
+pub fn serve(socket: &Path, timeout: Duration) -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    use std::os::unix::net::UnixListener;
+
+    let mut key = String::new();
+    std::io::stdin().read_line(&mut key)?;
+    let key = key.trim().to_string();
+
+    // The socket is only reachable through a directory no one else can
+    // enter, so nobody can connect between the bind and any chmod
+    let dir = socket.parent().expect("the agent socket lives in a directory");
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(err) if err.kind() != std::io::ErrorKind::AlreadyExists => return Err(err.into()),
+        _ => private_dir(dir)?,
+    }
+    if let Ok(metadata) = std::fs::symlink_metadata(socket) {
+        if metadata.uid() != current_uid() {
+            anyhow::bail!(t!("credentials.agent_socket_foreign", path = socket.display()));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+
+    let expired = socket.to_path_buf();
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+        let _ = std::fs::remove_file(&expired);
+        std::process::exit(0);
+    });
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        let mut request = String::new();
+        if BufReader::new(&stream).read_line(&mut request).is_err() {
+            continue;
+        }
+        match request.trim() {
+            "get" => {
+                let _ = writeln!(stream, "{}", key);
+            },
+            "stop" => break,
+            _ => {},
+        }
+    }
+
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+/// Fails unless `dir` is a directory of the user's that only they can use.
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        anyhow::bail!(t!("credentials.agent_dir_unsafe", path = dir.display()));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(not(unix))]
+pub fn fetch(_socket: &Path) -> Option<[u8; 32]> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn start(_socket: &Path, _key: &[u8; 32], _timeout: Duration) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn stop(_socket: &Path) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+pub fn serve(_socket: &Path, _timeout: Duration) -> Result<()> {
+    anyhow::bail!(crate::i18n::t!("credentials.agent_unsupported"))
+}
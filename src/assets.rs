@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use reqwest::blocking::multipart::Form;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::error::VkError;
+use crate::http_client::{HttpClient, UploadProgress};
+use crate::i18n::t;
+use crate::manifest::Asset;
+use crate::project::Project;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::utils::{EntryKind, PackageFile, format_bytes, sha256_hex};
+
+/// Size from which a binary file is worth moving out of the archive.
+pub const LARGE_FILE_SIZE: u64 = 1024 * 1024;
+/// Bytes read to tell binary files from text, as git does.
+const SNIFF_LEN: usize = 8000;
+
+/// Packaged files large enough to weigh on every install that look binary:
+/// candidates for the manifest's `assets`.
+pub fn large_binaries(files: &[PackageFile]) -> Vec<&PackageFile> {
+    files
+        .iter()
+        .filter(|f| f.kind == EntryKind::File && f.size >= LARGE_FILE_SIZE && is_binary(&f.path))
+        .collect()
+}
+
+/// Whether the file has a NUL byte near its start.
+fn is_binary(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head).is_ok() && head.contains(&0)
+}
+
+/// Whether `path` stays inside the plugin: relative, without `..`.
+fn is_contained(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Checks the manifest's assets against the project's files and returns them
+/// as they are published: with the hash and size of the files found.
+///
+/// Assets without a URL are uploaded, so their file must exist. Those with
+/// one need a hash, which a local copy must match.
+pub fn prepare(project: &Project, assets: &[Asset]) -> Result<Vec<Asset>> {
+    let mut prepared = Vec::with_capacity(assets.len());
+    for asset in assets {
+        if !is_contained(&asset.path) {
+            return Err(VkError::ManifestInvalid(t!("assets.invalid_path", path = asset.path)).into());
+        }
+
+        let path = project.root().join(&asset.path);
+        let mut asset = asset.clone();
+        if path.is_file() {
+            let data = fs::read(&path).with_context(|| t!("common.read_failed", path = path.display()))?;
+            let actual = sha256_hex(&data);
+            if asset.url.is_some()
+                && let Some(expected) = asset.sha256.as_deref().filter(|e| !e.eq_ignore_ascii_case(&actual))
+            {
+                return Err(VkError::ManifestInvalid(t!(
+                    "assets.hash_mismatch",
+                    path = asset.path,
+                    expected = expected,
+                    actual = actual
+                ))
+                .into());
+            }
+            asset.sha256 = Some(actual);
+            asset.size = Some(data.len() as u64);
+        } else if asset.url.is_none() {
+            return Err(VkError::ManifestInvalid(t!("assets.missing", path = asset.path)).into());
+        } else if asset.sha256.is_none() {
+            return Err(VkError::ManifestInvalid(t!("assets.hash_required", path = asset.path)).into());
+        }
+        prepared.push(asset);
+    }
+    Ok(prepared)
+}
+
+/// Uploads the assets published with the package, the ones without a URL,
+/// under the version being published.
+pub fn upload(
+    project: &Project,
+    name: &str,
+    version: &str,
+    assets: &[Asset],
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    for asset in assets.iter().filter(|a| a.url.is_none()) {
+        let path = project.root().join(&asset.path);
+        let data = fs::read(&path).with_context(|| t!("common.read_failed", path = path.display()))?;
+        reporter.step(&t!(
+            "assets.uploading",
+            path = asset.path.cyan(),
+            size = format_bytes(data.len())
+        ));
+
+        let checksum = sha256_hex(&data);
+        let progress = UploadProgress::default();
+        let file_name = asset.path.rsplit('/').next().unwrap_or(&asset.path).to_string();
+        let form = Form::new()
+            .part("file", progress.part(data).file_name(file_name))
+            .text("version", version.to_string())
+            .text("path", asset.path.clone())
+            .text("checksum", checksum);
+        RegistryClient::new(http_client).upload_asset(name, form, &progress)?;
+    }
+    Ok(())
+}
+
+/// Fetches the assets listed by the manifest of the plugin extracted at
+/// `dir`, `id` at `version`, and writes them in place once their hash is
+/// checked.
+pub fn fetch(id: &str, version: &str, dir: &Path, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let Some(manifest) = Project::at(dir).and_then(|p| p.read_manifest().ok()) else {
+        return Ok(());
+    };
+
+    for asset in &manifest.assets {
+        if !is_contained(&asset.path) {
+            return Err(VkError::ManifestInvalid(t!("assets.invalid_path", path = asset.path)).into());
+        }
+        let Some(expected) = &asset.sha256 else {
+            return Err(VkError::ManifestInvalid(t!("assets.hash_required", path = asset.path)).into());
+        };
+
+        let mut response = match &asset.url {
+            Some(url) => http_client.fetch(url)?,
+            None => RegistryClient::new(http_client).asset(id, version, &asset.path)?.response,
+        };
+        let pb = reporter.progress(
+            response.content_length().or(asset.size),
+            &t!("assets.downloading", path = asset.path),
+        );
+        let mut data = Vec::new();
+        let mut chunk = vec![0u8; 32 * 1024];
+        loop {
+            match response.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    data.extend_from_slice(&chunk[..n]);
+                    pb.inc(n as u64);
+                },
+                Err(e) => return Err(http_client.read_error(e).into()),
+            }
+        }
+        pb.finish();
+
+        let actual = sha256_hex(&data);
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(VkError::ChecksumMismatch {
+                package: format!("{}@{} ({})", id, version, asset.path),
+                expected: expected.clone(),
+                actual,
+            }
+            .into());
+        }
+
+        let target = dir.join(&asset.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+        }
+        fs::write(&target, &data).with_context(|| t!("common.write_failed", path = target.display()))?;
+        reporter.success(&t!(
+            "assets.fetched",
+            path = asset.path.cyan(),
+            size = format_bytes(data.len())
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_contained() {
+        assert!(is_contained("models/small.bin"));
+        assert!(!is_contained("../outside.bin"));
+        assert!(!is_contained("models/../../outside.bin"));
+        assert!(!is_contained("/etc/passwd"));
+        assert!(!is_contained(""));
+    }
+}
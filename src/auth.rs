@@ -1,18 +1,23 @@
 use anyhow::{Context, Result};
 use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use colored::Colorize;
-use dialoguer::{Input, Password};
+use dialoguer::{Input, Password, Select};
 use rand::distr::Alphanumeric;
 use rand::{RngExt, rng};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::net::TcpListener;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use url::Url;
 
-use crate::credentials_manager::{CredentialManager, RawCredentials};
-use crate::http_client::HttpClient;
+use crate::credentials_manager::{CredentialBundle, CredentialManager, KDF_COST, RawCredentials};
+use crate::error::VkError;
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::reporter::Reporter;
 
 #[derive(Debug, Clone, Default)]
 pub enum ClientType {
@@ -22,6 +27,7 @@ pub enum ClientType {
 }
 
 impl ClientType {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self> {
         match s {
             "web" => Ok(ClientType::Web),
@@ -91,6 +97,9 @@ pub struct OAuthAuthorizationResponse {
 pub struct OAuthCallbackRequest {
     pub code: String,
     pub state: String,
+    /// The PKCE secret whose hash went with the authorization request, so the
+    /// server can tell the code was redeemed by whoever started the login.
+    pub code_verifier: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -112,35 +121,179 @@ pub struct User {
     pub provider_id: String,
 }
 
+/// An organization's identity provider, as the registry describes it at
+/// `/orgs/<org>/sso`.
+#[derive(Debug, Deserialize)]
+pub struct SsoProvider {
+    /// OpenID Connect issuer, whose discovery document names the endpoints
+    /// when they are not given.
+    pub issuer: String,
+    pub client_id: String,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    /// Audience of the tokens requested; the registry URL by default.
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// The endpoints of an OpenID Connect discovery document.
+#[derive(Debug, Deserialize)]
+struct OidcDiscovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+/// Tokens the identity provider issues for the authorization code.
+#[derive(Debug, Deserialize)]
+struct IdpTokens {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+/// Scopes asked for when the organization names none.
+const SSO_SCOPES: &str = "openid profile email";
+
 const CALLBACK_PATH: &str = "/callback";
-const CALLBACK_PORT: u16 = 53682;
+/// Passphrase of credential bundles, for provisioning without a terminal.
+const BUNDLE_PASSPHRASE_VAR: &str = "VK_AUTH_PASSPHRASE";
+/// Passphrase of the stored credentials, for `vk auth set-passphrase`
+/// without a terminal.
+const CREDENTIALS_PASSPHRASE_VAR: &str = "VK_CREDENTIALS_PASSPHRASE";
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Trades the stored refresh token for new tokens, returning the access
+/// token. `http_client` must not authenticate requests itself.
+pub fn refresh_tokens(cm: &CredentialManager, http_client: &HttpClient) -> Result<String> {
+    let refresh_token = cm.get_refresh_token()?;
+    let response = http_client.post::<OAuthDataResponse, _>(
+        "/auth/refresh-token",
+        &serde_json::json!({ "refresh_token": refresh_token }),
+    )?;
+
+    cm.store_tokens(RawCredentials {
+        // Refreshed tokens keep the scope of the login
+        organization: cm.organization(),
+        ..RawCredentials::new(
+            response.access_token.clone(),
+            response.refresh_token,
+            response.expires_in,
+        )
+    })?;
+    Ok(response.access_token)
+}
+
+/// The PKCE `S256` challenge for a verifier: its SHA-256, base64url-encoded
+/// without padding (RFC 7636).
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Reads the authorization code and the encoded state from the URL the
+/// provider redirected to, checking the state is the one the login started
+/// with.
+fn parse_callback(url: &Url, expected_state: &str) -> Result<(String, String)> {
+    let mut code = None;
+    let mut state = None;
+
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "code" => code = Some(value.to_string()),
+            "state" => state = Some(value.to_string()),
+            "error" => anyhow::bail!(t!("auth.oauth_error", error = value)),
+            _ => {},
+        }
+    }
+
+    let code = code.with_context(|| t!("auth.callback_missing_code"))?;
+    let state_str = state.with_context(|| t!("auth.callback_missing_state"))?;
+    // The registry hands back its own encoding of the state; identity
+    // providers hand it back as sent
+    if state_str != expected_state && OAuthState::from_base64(&state_str)?.state != expected_state {
+        anyhow::bail!(t!("auth.state_mismatch"));
+    }
+
+    Ok((code, state_str))
+}
+
+/// The callback URL from what the user pasted: the whole URL the browser
+/// ended on, or just its query (`code=...&state=...`).
+fn pasted_callback_url(pasted: &str) -> Result<Url> {
+    let pasted = pasted.trim();
+    let url = if pasted.starts_with("http://") || pasted.starts_with("https://") {
+        pasted.to_string()
+    } else {
+        format!("http://localhost{CALLBACK_PATH}?{}", pasted.trim_start_matches('?'))
+    };
+    Url::parse(&url).with_context(|| t!("auth.callback_invalid_request"))
+}
 
 pub struct AuthCommands {
     cm: Arc<CredentialManager>,
     http_client: HttpClient,
+    reporter: Arc<dyn Reporter>,
+    /// Ports the OAuth callback may listen on; any free port when unset.
+    callback_ports: Option<RangeInclusive<u16>>,
 }
 
 impl AuthCommands {
-    pub fn new(credentials_manager: Arc<CredentialManager>, http_client: HttpClient) -> Self {
-        AuthCommands { cm: credentials_manager, http_client }
+    pub fn new(
+        credentials_manager: Arc<CredentialManager>,
+        http_client: HttpClient,
+        reporter: Arc<dyn Reporter>,
+    ) -> Self {
+        AuthCommands {
+            cm: credentials_manager,
+            http_client,
+            reporter,
+            callback_ports: None,
+        }
+    }
+
+    pub fn with_callback_ports(mut self, ports: Option<RangeInclusive<u16>>) -> Self {
+        self.callback_ports = ports;
+        self
+    }
+
+    /// Listens for the OAuth callback on the first free port allowed.
+    fn bind_callback(&self) -> Option<TcpListener> {
+        match &self.callback_ports {
+            Some(ports) => ports.clone().find_map(|port| TcpListener::bind(("localhost", port)).ok()),
+            None => TcpListener::bind(("localhost", 0)).ok(),
+        }
+    }
+
+    fn callback_ports_label(&self) -> String {
+        match &self.callback_ports {
+            Some(ports) if ports.start() == ports.end() => ports.start().to_string(),
+            Some(ports) => format!("{}-{}", ports.start(), ports.end()),
+            None => t!("auth.any_port"),
+        }
     }
 
     /// Login with username and password
     pub fn login_with_password(&self, username: Option<String>, password: Option<String>) -> Result<()> {
         let username = match username {
             Some(u) => u,
-            None => Input::new().with_prompt("Username").interact_text().context("Failed to read username")?,
+            None => Input::new()
+                .with_prompt(t!("auth.prompt_username"))
+                .interact_text()
+                .with_context(|| t!("auth.read_username_failed"))?,
         };
 
         let password = match password {
             Some(p) => p,
-            None => Password::new().with_prompt("Password").interact().context("Failed to read password")?,
+            None => Password::new()
+                .with_prompt(t!("auth.prompt_password"))
+                .interact()
+                .with_context(|| t!("auth.read_password_failed"))?,
         };
 
-        println!("{}", "🔐 Authenticating...".cyan());
+        self.reporter.title(&t!("auth.authenticating"));
 
         let login_response = self
             .http_client
+            .without_auth()
             .post::<LoginPasswordResponse, _>("/auth/login", &LoginPasswordRequest { username, password })?;
 
         let credentials = RawCredentials::new(
@@ -151,70 +304,116 @@ impl AuthCommands {
 
         self.cm.store_tokens(credentials)?;
 
-        println!("{}", "✓ Login successful!".green().bold());
+        self.reporter.done(&t!("auth.login_success"));
 
         Ok(())
     }
 
+    /// Starts listening for the redirect back from the browser, returning
+    /// the listener and the URL to redirect to. Where it cannot run (e.g. a
+    /// firewalled container), the user pastes the redirect instead.
+    fn callback(&self) -> Result<(Option<TcpListener>, String)> {
+        let listener = match self.bind_callback() {
+            Some(listener) => Some(listener),
+            None if self.reporter.is_interactive() => {
+                self.reporter.warn(&t!("auth.callback_unavailable", port = self.callback_ports_label()));
+                None
+            },
+            None => {
+                self.reporter.warn(&t!("auth.port_in_use_hint", port = self.callback_ports_label()));
+                return Err(anyhow::anyhow!(t!(
+                    "auth.port_in_use",
+                    port = self.callback_ports_label()
+                )));
+            },
+        };
+
+        // The server redirects to whichever port was picked. Without a
+        // listener, the first allowed one still tells the user where the
+        // browser was sent.
+        let port = match &listener {
+            Some(listener) => listener.local_addr()?.port(),
+            None => self.callback_ports.as_ref().map_or(80, |ports| *ports.start()),
+        };
+        Ok((listener, format!("http://localhost:{port}{CALLBACK_PATH}")))
+    }
+
+    /// Sends the user to `authorization_uri` and waits for the redirect back,
+    /// returning the authorization code and the state.
+    fn authorize(
+        &self,
+        listener: Option<&TcpListener>,
+        authorization_uri: &str,
+        state: &str,
+    ) -> Result<(String, String)> {
+        let callback = match listener {
+            Some(listener) => {
+                self.reporter.step(&t!("auth.opening_browser"));
+
+                if let Err(e) = open::that(authorization_uri) {
+                    self.reporter.warn(&t!("auth.open_browser_failed", error = e));
+                    self.reporter.output(&format!(
+                        "{}: {}",
+                        t!("auth.open_url_manually").yellow(),
+                        authorization_uri.bright_blue()
+                    ));
+                }
+
+                self.reporter.step(&t!("auth.waiting"));
+                self.receive_oauth_callback(listener, state)?
+            },
+            None => {
+                self.reporter.output(&format!(
+                    "{}: {}",
+                    t!("auth.open_url_manually").yellow(),
+                    authorization_uri.bright_blue()
+                ));
+                self.reporter.detail(&t!("auth.paste_hint"));
+
+                let pasted: String = Input::new()
+                    .with_prompt(t!("auth.prompt_redirect"))
+                    .interact_text()
+                    .with_context(|| t!("auth.read_redirect_failed"))?;
+                parse_callback(&pasted_callback_url(&pasted)?, state)?
+            },
+        };
+
+        self.reporter.success(&t!("auth.authorization_received"));
+        Ok(callback)
+    }
+
     /// Login with OAuth (Google or GitHub)
     /// The server handles all OAuth logic, we just open the browser and receive the callback
     pub fn login_with_oauth(&self, provider: &str) -> Result<()> {
-        println!("{} Starting OAuth login with {}...", "🔐".bold(), provider.cyan());
-
-        // Start the server to listen for the callback
-        let listener = match TcpListener::bind(format!("localhost:{CALLBACK_PORT}")) {
-            Ok(listener) => listener,
-            Err(_) => {
-                println!(
-                    "Port {} is already in use. Please close the conflicting app or try again.",
-                    CALLBACK_PORT
-                );
-                return Err(anyhow::anyhow!("Port {} is already in use", CALLBACK_PORT));
-            },
-        };
+        self.reporter.title(&t!("auth.oauth_start", provider = provider.cyan()));
 
-        let callback_url = format!("http://localhost:{CALLBACK_PORT}{CALLBACK_PATH}");
+        let (listener, callback_url) = self.callback()?;
 
         let state = self.random_string(16);
         let code_verifier = self.random_string(64);
-
-        let mut hasher = Sha256::new();
-        hasher.update(code_verifier.as_bytes());
-        let code_challenge = URL_SAFE_NO_PAD.encode(hasher.finalize());
+        let code_challenge = code_challenge(&code_verifier);
 
         let request_url = format!("auth/oauth/{provider}");
         let request_body = serde_json::json!({
             "state": state,
             "code_challenge": code_challenge,
+            "code_challenge_method": "S256",
             "redirect_uri": callback_url,
             "origin_uri": "http://localhost:8080",
             "client_type": "cli"
         });
 
-        let auth_response = self.http_client.post::<OAuthAuthorizationResponse, _>(&request_url, &request_body)?;
-
-        println!("\n{}", "Opening browser for authentication...".cyan());
-
-        if let Err(e) = open::that(&auth_response.authorization_uri) {
-            eprintln!("{} Failed to open browser: {}", "⚠".yellow(), e);
-            println!(
-                "{}: {}",
-                "Please open the URL manually".yellow(),
-                auth_response.authorization_uri.bright_blue()
-            );
-        }
-
-        println!("{}", "Waiting for authorization...".cyan());
+        let auth_response =
+            self.http_client.without_auth().post::<OAuthAuthorizationResponse, _>(&request_url, &request_body)?;
 
-        let (code, state) = self.receive_oauth_callback(&listener, &state)?;
+        let (code, state) = self.authorize(listener.as_ref(), &auth_response.authorization_uri, &state)?;
 
-        println!("{}", "✓ Authorization received!".green());
-        println!("{}", "Exchanging code for tokens...".cyan());
+        self.reporter.step(&t!("auth.exchanging"));
 
         let oauth_url = format!("auth/oauth/{provider}/exchange");
-        let oauth_body = OAuthCallbackRequest { code, state };
+        let oauth_body = OAuthCallbackRequest { code, state, code_verifier };
 
-        let oauth_response = self.http_client.post::<OAuthDataResponse, _>(&oauth_url, &oauth_body)?;
+        let oauth_response = self.http_client.without_auth().post::<OAuthDataResponse, _>(&oauth_url, &oauth_body)?;
 
         self.cm
             .store_tokens(RawCredentials {
@@ -222,14 +421,143 @@ impl AuthCommands {
                 access_expires_in: oauth_response.expires_in,
                 refresh_token: oauth_response.refresh_token,
                 refresh_expires_in: oauth_response.expires_in,
+                organization: None,
             })
-            .context("Failed to store tokens in keyring")?;
+            .with_context(|| t!("auth.store_tokens_failed"))?;
 
-        println!("{}", "✓ OAuth login successful!".green().bold());
+        self.reporter.done(&t!("auth.oauth_success"));
 
         Ok(())
     }
 
+    /// Logs in through the identity provider of `org`. The registry names
+    /// the provider; the browser authorizes with it (OpenID Connect, with
+    /// PKCE), asking for tokens whose audience is the registry. The registry
+    /// trades those for tokens scoped to the organization, which are stored.
+    pub fn login_with_sso(&self, org: &str) -> Result<()> {
+        self.reporter.title(&t!("auth.sso_start", org = org.cyan()));
+
+        let http = self.http_client.without_auth();
+        let provider: SsoProvider = match http.get(&format!("/orgs/{}/sso", org)) {
+            Ok(provider) => provider,
+            Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => {
+                anyhow::bail!(t!("auth.sso_not_configured", org = org))
+            },
+            Err(err) => return Err(err.into()),
+        };
+        let (authorization_endpoint, token_endpoint) =
+            match (&provider.authorization_endpoint, &provider.token_endpoint) {
+                (Some(authorization), Some(token)) => (authorization.clone(), token.clone()),
+                _ => {
+                    let url = format!(
+                        "{}/.well-known/openid-configuration",
+                        provider.issuer.trim_end_matches('/')
+                    );
+                    let discovery: OidcDiscovery = HttpClient::parse_json(http.fetch(&url)?)
+                        .with_context(|| t!("auth.sso_discovery_failed", issuer = provider.issuer.as_str()))?;
+                    (discovery.authorization_endpoint, discovery.token_endpoint)
+                },
+            };
+        self.reporter.step(&t!(
+            "auth.sso_provider",
+            issuer = provider.issuer.as_str().bright_black()
+        ));
+
+        let (listener, callback_url) = self.callback()?;
+        let state = self.random_string(16);
+        let code_verifier = self.random_string(64);
+        let audience = provider.audience.clone().unwrap_or_else(|| self.http_client.base_url().to_string());
+        let scope = match provider.scopes.is_empty() {
+            true => SSO_SCOPES.to_string(),
+            false => provider.scopes.join(" "),
+        };
+
+        let mut authorization_uri = Url::parse(&authorization_endpoint)
+            .with_context(|| t!("auth.sso_discovery_failed", issuer = provider.issuer.as_str()))?;
+        authorization_uri
+            .query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &callback_url)
+            .append_pair("scope", &scope)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("audience", &audience);
+
+        let (code, _) = self.authorize(listener.as_ref(), authorization_uri.as_str(), &state)?;
+
+        self.reporter.step(&t!("auth.exchanging"));
+        let idp_tokens: IdpTokens = http.post_form_url(
+            &token_endpoint,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", callback_url.as_str()),
+                ("client_id", provider.client_id.as_str()),
+                ("code_verifier", code_verifier.as_str()),
+            ],
+        )?;
+        let tokens = http.post::<OAuthDataResponse, _>(
+            &format!("/orgs/{}/sso/exchange", org),
+            &serde_json::json!({ "access_token": idp_tokens.access_token, "id_token": idp_tokens.id_token }),
+        )?;
+
+        self.cm
+            .store_tokens(RawCredentials {
+                organization: Some(org.to_string()),
+                ..RawCredentials::new(tokens.access_token, tokens.refresh_token, tokens.expires_in)
+            })
+            .with_context(|| t!("auth.store_tokens_failed"))?;
+
+        self.reporter.done(&t!("auth.sso_success", org = org));
+
+        Ok(())
+    }
+
+    /// Refreshes the session tokens now, instead of when they next expire.
+    pub fn refresh_session(&self) -> Result<()> {
+        if !self.cm.has_credentials() {
+            return Err(VkError::AuthRequired.into());
+        }
+        if self.cm.is_refresh_token_expired() {
+            return Err(VkError::AuthExpired.into());
+        }
+
+        self.reporter.step(&t!("auth.refreshing"));
+        refresh_tokens(&self.cm, &self.http_client.without_auth())?;
+        self.reporter.done(&t!("auth.refresh_success"));
+
+        Ok(())
+    }
+
+    /// Offers to log in again when the session can no longer be refreshed in
+    /// the middle of a command. Returns whether the user logged in.
+    pub fn reauthenticate(&self) -> Result<bool> {
+        self.reporter.warn(&t!("auth.session_expired"));
+        if !self.reporter.is_interactive() {
+            self.reporter.detail(&t!("auth.session_expired_hint"));
+            return Ok(false);
+        }
+        if !self.reporter.confirm(&t!("auth.relogin_prompt"))? {
+            return Ok(false);
+        }
+
+        let methods = [t!("auth.method_password"), "GitHub".to_string(), "Google".to_string()];
+        let method = Select::new()
+            .with_prompt(t!("auth.method_prompt"))
+            .items(&methods)
+            .default(0)
+            .interact()
+            .with_context(|| t!("init.read_input_failed"))?;
+        match method {
+            0 => self.login_with_password(None, None)?,
+            1 => self.login_with_oauth("github")?,
+            _ => self.login_with_oauth("google")?,
+        }
+        Ok(true)
+    }
+
     fn random_string(&self, len: usize) -> String {
         rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect()
     }
@@ -242,7 +570,7 @@ impl AuthCommands {
 
         loop {
             if start.elapsed() > timeout {
-                anyhow::bail!("OAuth login timed out after 120 seconds");
+                anyhow::bail!(t!("auth.timeout", seconds = timeout.as_secs()));
             }
 
             match listener.accept() {
@@ -250,44 +578,29 @@ impl AuthCommands {
                     let mut reader = BufReader::new(&stream);
                     let mut request_line = String::new();
 
-                    reader.read_line(&mut request_line).context("Failed to read OAuth callback request")?;
+                    reader.read_line(&mut request_line).with_context(|| t!("auth.callback_read_failed"))?;
 
-                    let path = request_line.split_whitespace().nth(1).context("Invalid HTTP request format")?;
+                    let path =
+                        request_line.split_whitespace().nth(1).with_context(|| t!("auth.callback_invalid_request"))?;
 
                     if !path.starts_with(CALLBACK_PATH) {
-                        self.send_error_response(&mut stream, "Invalid callback path")?;
-                        anyhow::bail!("Invalid callback path");
+                        self.send_error_response(&mut stream, &t!("auth.callback_invalid_path"))?;
+                        anyhow::bail!(t!("auth.callback_invalid_path"));
                     }
 
                     let full_url = format!("http://localhost{}", path);
-                    let parsed = Url::parse(&full_url).context("Failed to parse callback URL")?;
-
-                    let mut code = None;
-                    let mut state = None;
-
-                    for (key, value) in parsed.query_pairs() {
-                        match key.as_ref() {
-                            "code" => code = Some(value.to_string()),
-                            "state" => state = Some(value.to_string()),
-                            "error" => {
-                                self.send_error_response(&mut stream, &value)?;
-                                anyhow::bail!("OAuth error: {}", value);
-                            },
-                            _ => {},
-                        }
-                    }
-
-                    let code = code.context("No authorization code received")?;
-                    let state_str = state.context("No state parameter received")?;
-                    let state = OAuthState::from_base64(&state_str)?;
-
-                    if expected_state != state.state {
-                        self.send_error_response(&mut stream, "State mismatch - possible CSRF attack")?;
-                        anyhow::bail!("State mismatch - possible CSRF attack");
-                    }
-
-                    self.send_success_response(&mut stream)?;
-                    return Ok((code, state_str));
+                    let parsed = Url::parse(&full_url).with_context(|| t!("auth.callback_invalid_request"))?;
+
+                    return match parse_callback(&parsed, expected_state) {
+                        Ok(callback) => {
+                            self.send_success_response(&mut stream)?;
+                            Ok(callback)
+                        },
+                        Err(err) => {
+                            self.send_error_response(&mut stream, &err.to_string())?;
+                            Err(err)
+                        },
+                    };
                 },
 
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -301,16 +614,17 @@ impl AuthCommands {
 
     /// Send success HTML response to browser
     fn send_success_response(&self, stream: &mut std::net::TcpStream) -> Result<()> {
-        let response = "HTTP/1.1 200 OK\r\n\
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n\
             Content-Type: text/html; charset=utf-8\r\n\
             \r\n\
             <!DOCTYPE html>\
             <html lang='en'>\
             <head>\
                 <meta charset='UTF-8'>\
-                <title>Authentication Successful</title>\
+                <title>{}</title>\
                 <style>\
-                    :root {\
+                    :root {{\
                         --color-vayload-bg: #050505;\
                         --color-vayload-bg-light: #0a0a0a;\
                         --color-vayload-bg-card: #0f0f10;\
@@ -319,45 +633,49 @@ impl AuthCommands {
                         --color-accent-light: #ffc947;\
                         --color-text: #ffffff;\
                         --color-text-muted: #bbbbbb;\
-                    }\
-                    body {\
+                    }}\
+                    body {{\
                         font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;\
                         background-color: var(--color-vayload-bg);\
                         color: var(--color-text);\
                         text-align: center;\
                         padding: 50px;\
-                    }\
-                    .container {\
+                    }}\
+                    .container {{\
                         background-color: var(--color-vayload-bg-card);\
                         padding: 40px;\
                         border-radius: 12px;\
                         box-shadow: 0 4px 20px rgba(0,0,0,0.5);\
                         max-width: 500px;\
                         margin: 0 auto;\
-                    }\
-                    h1 {\
+                    }}\
+                    h1 {{\
                         color: var(--color-accent);\
                         margin: 0 0 20px 0;\
-                    }\
-                    .icon {\
+                    }}\
+                    .icon {{\
                         font-size: 64px;\
                         margin-bottom: 20px;\
                         color: var(--color-accent-light);\
-                    }\
-                    p {\
+                    }}\
+                    p {{\
                         color: var(--color-text-muted);\
                         margin: 10px 0;\
-                    }\
+                    }}\
                 </style>\
             </head>\
             <body>\
                 <div class='container'>\
                     <div class='icon'>✓</div>\
-                    <h1>Authentication Successful!</h1>\
-                    <p>You can close this window and return to the terminal.</p>\
+                    <h1>{}</h1>\
+                    <p>{}</p>\
                 </div>\
             </body>\
-            </html>";
+            </html>",
+            t!("auth.page_success_title"),
+            t!("auth.page_success_heading"),
+            t!("auth.page_success_message")
+        );
 
         stream.write_all(response.as_bytes()).context("Failed to send success response")?;
         Ok(())
@@ -373,7 +691,7 @@ impl AuthCommands {
             <html lang='en'>\
             <head>\
                 <meta charset='UTF-8'>\
-                <title>Authentication Failed</title>\
+                <title>{title}</title>\
                 <style>\
                     :root {{\
                         --color-vayload-bg: #050505;\
@@ -423,13 +741,15 @@ impl AuthCommands {
             <body>\
                 <div class='container'>\
                     <div class='icon'>✗</div>\
-                    <h1>Authentication Failed</h1>\
-                    <p class='error'>{}</p>\
-                    <p>Please close this window and try again.</p>\
+                    <h1>{title}</h1>\
+                    <p class='error'>{error}</p>\
+                    <p>{message}</p>\
                 </div>\
             </body>\
             </html>",
-            error
+            title = t!("auth.page_error_title"),
+            error = error,
+            message = t!("auth.page_error_message")
         );
 
         stream.write_all(response.as_bytes()).context("Failed to send error response")?;
@@ -438,49 +758,232 @@ impl AuthCommands {
 
     /// Get current user information
     pub fn whoami(&self) -> Result<()> {
+        self.cm.unlock()?;
         if !self.cm.is_authenticated() {
-            return Err(anyhow::anyhow!(
-                "Not authenticated. Please login first with 'vayload-kit auth -u <username> -p <password>' or 'vayload-kit auth -o <provider>'"
-            ));
+            return Err(VkError::AuthRequired.into());
         }
 
         let whoami_response = self.http_client.get::<User>("/auth/me")?;
 
-        println!("{}", "Current User:".green().bold());
+        self.reporter.title(&t!("auth.current_user"));
         self.print_user_info(&whoami_response);
+        if let Some(org) = self.cm.organization() {
+            self.reporter.output(&format!(
+                "{} {}",
+                t!("auth.user_organization").bright_black(),
+                org.cyan()
+            ));
+        }
 
         Ok(())
     }
 
     /// Logout and clear stored tokens
     pub fn logout(&self) -> Result<()> {
-        if !self.cm.is_authenticated() {
-            println!("{}", "Already logged out".yellow());
+        // Logging out needs no passphrase
+        if !self.cm.has_credentials() {
+            self.reporter.info(&t!("auth.already_logged_out"));
             return Ok(());
         }
 
-        self.cm.clear_all().context("Failed to clear tokens from keyring")?;
+        self.cm.clear_all().with_context(|| t!("auth.clear_tokens_failed"))?;
+
+        self.reporter.done(&t!("auth.logout_success"));
+        self.reporter.detail(&t!("auth.tokens_removed"));
+
+        Ok(())
+    }
+
+    /// Writes the stored credentials to `file`, encrypted with a passphrase,
+    /// so another machine can import them instead of logging in.
+    pub fn export_credentials(&self, file: &str, registry: &str) -> Result<()> {
+        if !self.cm.has_credentials() {
+            return Err(VkError::AuthRequired.into());
+        }
+        if self.cm.is_refresh_token_expired() {
+            self.reporter.warn(&t!("auth.export_expired"));
+        }
+
+        let passphrase = self.read_passphrase(BUNDLE_PASSPHRASE_VAR, true)?;
+        let bundle = CredentialBundle::seal(registry, &passphrase, &self.cm.export_plaintext()?, KDF_COST)?;
+
+        fs::write(file, serde_json::to_string_pretty(&bundle)? + "\n")
+            .with_context(|| t!("common.write_failed", path = file))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(file, fs::Permissions::from_mode(0o600))?;
+        }
+
+        self.reporter.done(&t!("auth.exported", path = file.cyan()));
+        self.reporter.detail(&t!("auth.export_hint"));
+        Ok(())
+    }
+
+    /// Stores the credentials of a bundle written by `export_credentials`.
+    pub fn import_credentials(&self, file: &str, registry: &str) -> Result<()> {
+        let content = fs::read_to_string(file).with_context(|| t!("common.read_failed", path = file))?;
+        let bundle: CredentialBundle =
+            serde_json::from_str(&content).with_context(|| t!("credentials.bundle_invalid"))?;
+
+        let passphrase = self.read_passphrase(BUNDLE_PASSPHRASE_VAR, false)?;
+        self.cm.import_plaintext(&bundle.open(&passphrase)?)?;
+
+        self.reporter.done(&t!("auth.imported", path = file.cyan()));
+        if bundle.registry.trim_end_matches('/') != registry.trim_end_matches('/') {
+            self.reporter.warn(&t!(
+                "auth.import_other_registry",
+                registry = bundle.registry.cyan(),
+                current = registry.cyan()
+            ));
+        }
+        if !self.cm.is_authenticated() {
+            self.reporter.warn(&t!("auth.export_expired"));
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts the stored credentials under a new key.
+    pub fn rotate_key(&self) -> Result<()> {
+        let id = self.cm.rotate_key()?;
+        self.reporter.done(&t!("auth.key_rotated", id = id.cyan()));
+        Ok(())
+    }
+
+    /// Derives the credentials key from a passphrase from now on, instead of
+    /// keeping it in the key file.
+    pub fn set_passphrase(&self) -> Result<()> {
+        let passphrase = self.read_passphrase(CREDENTIALS_PASSPHRASE_VAR, true)?;
+        let id = self.cm.set_passphrase(&passphrase)?;
+        self.reporter.done(&t!("auth.passphrase_set", id = id.cyan()));
+        self.reporter.detail(&t!("auth.passphrase_set_hint"));
+        Ok(())
+    }
 
-        println!("{}", "✓ Logged out successfully!".green().bold());
-        println!("{}", "All tokens have been removed from keyring.".bright_black());
+    pub fn remove_passphrase(&self) -> Result<()> {
+        if !self.cm.uses_passphrase()? {
+            self.reporter.info(&t!("auth.passphrase_not_set"));
+            return Ok(());
+        }
+        let id = self.cm.remove_passphrase()?;
+        self.reporter.done(&t!("auth.passphrase_removed", id = id.cyan()));
+        Ok(())
+    }
 
+    /// Forgets the key derived from the passphrase until it is entered again.
+    pub fn lock(&self) -> Result<()> {
+        if self.cm.lock() {
+            self.reporter.done(&t!("auth.locked"));
+        } else {
+            self.reporter.info(&t!("auth.not_unlocked"));
+        }
         Ok(())
     }
 
+    /// A new passphrase, from `var` or a prompt.
+    fn read_passphrase(&self, var: &str, confirm: bool) -> Result<String> {
+        let passphrase = match std::env::var(var) {
+            Ok(passphrase) => passphrase,
+            Err(_) if self.reporter.is_interactive() => {
+                let mut prompt = Password::new().with_prompt(t!("auth.prompt_passphrase"));
+                if confirm {
+                    prompt =
+                        prompt.with_confirmation(t!("auth.prompt_passphrase_again"), t!("auth.passphrase_mismatch"));
+                }
+                prompt.interact().with_context(|| t!("auth.read_password_failed"))?
+            },
+            Err(_) => anyhow::bail!(t!("auth.passphrase_required", var = var)),
+        };
+
+        if confirm && passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+            anyhow::bail!(t!("auth.passphrase_too_short", min = MIN_PASSPHRASE_LEN));
+        }
+        Ok(passphrase)
+    }
+
     /// Helper to print user information
     fn print_user_info(&self, user: &User) {
-        println!("{} {}", "Username:".bright_black(), user.username.cyan());
-        println!("{} {}", "Email:".bright_black(), user.email);
+        let out = &self.reporter;
+        out.output(&format!(
+            "{} {}",
+            t!("auth.user_username").bright_black(),
+            user.username.cyan()
+        ));
+        out.output(&format!("{} {}", t!("auth.user_email").bright_black(), user.email));
 
         if let Some(name) = &user.name {
-            println!("{} {}", "Name:".bright_black(), name);
+            out.output(&format!("{} {}", t!("auth.user_name").bright_black(), name));
         }
 
         if let Some(avatar) = &user.avatar_url {
-            println!("{} {}", "Avatar:".bright_black(), avatar.bright_black());
+            out.output(&format!(
+                "{} {}",
+                t!("auth.user_avatar").bright_black(),
+                avatar.bright_black()
+            ));
+        }
+
+        out.output(&format!(
+            "{} {}",
+            t!("auth.user_provider").bright_black(),
+            user.provider
+        ));
+        out.output(&format!(
+            "{} {}",
+            t!("auth.user_provider_id").bright_black(),
+            user.provider_id
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded_state(state: &str) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{state}|http://localhost:8080||challenge|cli"))
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // SHA-256 of "abc" is ba7816bf...f20015ad
+        assert_eq!(code_challenge("abc"), "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0");
+    }
+
+    #[test]
+    fn test_pkce_handshake() {
+        // The challenge travels in the state the provider hands back; the
+        // exchange must carry the verifier it was derived from
+        let verifier = "x".repeat(64);
+        let state = URL_SAFE_NO_PAD.encode(format!(
+            "abc123|http://localhost:8080||{}|cli",
+            code_challenge(&verifier)
+        ));
+        let url = pasted_callback_url(&format!("code=xyz&state={state}")).unwrap();
+        let (code, state) = parse_callback(&url, "abc123").unwrap();
+
+        let body =
+            serde_json::to_value(OAuthCallbackRequest { code, state: state.clone(), code_verifier: verifier.clone() })
+                .unwrap();
+        assert_eq!(body["code_verifier"], verifier.as_str());
+
+        let challenge = OAuthState::from_base64(&state).unwrap().code_challenge;
+        assert_eq!(code_challenge(body["code_verifier"].as_str().unwrap()), challenge);
+    }
+
+    #[test]
+    fn test_parse_pasted_callback() {
+        let state = encoded_state("abc123");
+        let full = format!("http://localhost:49152{CALLBACK_PATH}?code=xyz&state={state}");
+        let query = format!("code=xyz&state={state}");
+
+        for pasted in [full.as_str(), query.as_str()] {
+            let (code, returned) = parse_callback(&pasted_callback_url(pasted).unwrap(), "abc123").unwrap();
+            assert_eq!((code.as_str(), returned.as_str()), ("xyz", state.as_str()));
         }
 
-        println!("{} {}", "Provider:".bright_black(), user.provider);
-        println!("{} {}", "Provider ID:".bright_black(), user.provider_id);
+        assert!(parse_callback(&pasted_callback_url(&query).unwrap(), "other").is_err());
+        assert!(parse_callback(&pasted_callback_url("error=access_denied").unwrap(), "abc123").is_err());
+        assert!(parse_callback(&pasted_callback_url(&format!("state={state}")).unwrap(), "abc123").is_err());
     }
 }
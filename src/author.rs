@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use crate::config::AppUser;
+use crate::git;
+use crate::i18n::t;
+
+/// What `vk init` wrote as the author before it read any identity; a
+/// manifest still carrying it has no real author.
+const PLACEHOLDER: &str = "author";
+
+/// The author new manifests get, as `Name <email>`: the `[user]` identity of
+/// `config.toml`, each part falling back to `git config` as seen from `dir`.
+pub fn default_author(user: &AppUser, dir: &Path) -> Option<String> {
+    let git_config = |key: &str| git::run(dir, &["config", "--get", key]).ok().filter(|value| !value.is_empty());
+    let name = user.name.clone().or_else(|| git_config("user.name"));
+    let email = user.email.clone().or_else(|| git_config("user.email"));
+    match (name, email) {
+        (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+        (Some(name), None) => Some(name),
+        (None, Some(email)) => Some(format!("<{}>", email)),
+        (None, None) => None,
+    }
+}
+
+/// Why `author` is not a usable `Name <email> (url)`, where the email and URL
+/// are optional but the name is not.
+pub fn check(author: &str) -> Result<(), String> {
+    let author = author.trim();
+    if author.is_empty() || author == PLACEHOLDER {
+        return Err(t!("author.missing"));
+    }
+
+    let (rest, url) = match author.strip_suffix(')').and_then(|a| a.rsplit_once('(')) {
+        Some((rest, url)) => (rest.trim_end(), Some(url)),
+        None => (author, None),
+    };
+    let (name, email) = match rest.strip_suffix('>').and_then(|r| r.rsplit_once('<')) {
+        Some((name, email)) => (name.trim(), Some(email)),
+        None => (rest, None),
+    };
+
+    if name.is_empty() {
+        return Err(t!("author.no_name"));
+    }
+    if name.contains(['<', '>', '(', ')']) {
+        return Err(t!("author.malformed"));
+    }
+    if let Some(email) = email
+        && !is_email(email)
+    {
+        return Err(t!("author.invalid_email", email = email));
+    }
+    if let Some(url) = url
+        && !(url.starts_with("https://") || url.starts_with("http://"))
+    {
+        return Err(t!("author.invalid_url", url = url));
+    }
+    Ok(())
+}
+
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.contains(char::is_whitespace)
+        && !domain.contains('@')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_author() {
+        for author in [
+            "Ana",
+            "Ana Lima <ana@example.com>",
+            "Ana <ana@example.com> (https://ana.dev)",
+            "Ana (https://ana.dev)",
+        ] {
+            assert_eq!(check(author), Ok(()), "{}", author);
+        }
+        for author in [
+            "",
+            "author",
+            "<ana@example.com>",
+            "Ana <ana>",
+            "Ana <ana@example.com",
+            "Ana (ana.dev)",
+            "A <b@c.d> <e@f.g>",
+        ] {
+            assert!(check(author).is_err(), "{}", author);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use globset::Glob;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::i18n::t;
+use crate::lua;
+use crate::manifest::{FileSystemScope, Permissions};
+
+/// What a plugin does that its permissions have to allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capability {
+    Outbound,
+    Inbound,
+    Read,
+    Write,
+}
+
+/// Host modules that only work with a permission.
+const MODULES: &[(&str, Capability)] = &[
+    ("vhost:http", Capability::Outbound),
+    ("vhost:net", Capability::Outbound),
+    ("vhost:websocket", Capability::Outbound),
+    ("vhost:server", Capability::Inbound),
+    ("vhost:fs", Capability::Read),
+];
+
+/// Lua library functions that touch the filesystem.
+const CALLS: &[(&str, Capability)] = &[
+    ("io.open", Capability::Read),
+    ("io.lines", Capability::Read),
+    ("io.input", Capability::Read),
+    ("io.output", Capability::Write),
+    ("os.remove", Capability::Write),
+    ("os.rename", Capability::Write),
+    ("os.tmpname", Capability::Write),
+];
+
+/// Something a Lua source does that the manifest `permissions` do not cover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+/// Checks `source`, the Lua file `file` of a package, for host modules,
+/// filesystem calls and URLs its `permissions` do not allow. Only the first
+/// use of each is reported. Sources that do not parse are left to the syntax
+/// check.
+pub fn scan(file: &str, source: &str, permissions: Option<&Permissions>) -> Vec<Finding> {
+    let Ok(outline) = lua::outline(source) else {
+        return Vec::new();
+    };
+    let allowed = Allowed::new(permissions);
+
+    let mut findings = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut report = |subject: &str, line: usize, message: String| {
+        if seen.insert(subject.to_string()) {
+            findings.push(Finding { file: file.to_string(), line, message });
+        }
+    };
+
+    for (module, line) in &outline.requires {
+        if let Some((_, capability)) = MODULES.iter().find(|(name, _)| name == module)
+            && !allowed.grants(*capability)
+        {
+            report(module, *line, allowed.missing(module, *capability));
+        }
+    }
+    for (name, line) in &outline.names {
+        if let Some((_, capability)) = CALLS.iter().find(|(call, _)| call == name)
+            && !allowed.grants(*capability)
+        {
+            report(name, *line, allowed.missing(name, *capability));
+        }
+    }
+    for (string, line) in &outline.strings {
+        if let Some(host) = url_host(string)
+            && !allowed.reaches(&host)
+        {
+            report(&host, *line, t!("capabilities.host", host = host));
+        }
+    }
+    findings
+}
+
+/// Scans every Lua file under `dir`, an extracted package.
+pub fn scan_dir(dir: &Path, permissions: Option<&Permissions>) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.path().extension().is_none_or(|ext| ext != "lua") {
+            continue;
+        }
+        let file = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+        let source = String::from_utf8_lossy(&fs::read(entry.path())?).into_owned();
+        findings.extend(scan(&file, &source, permissions));
+    }
+    Ok(findings)
+}
+
+/// The host of an `http(s)://` or `ws(s)://` URL.
+fn url_host(string: &str) -> Option<String> {
+    let (scheme, rest) = string.split_once("://")?;
+    if !matches!(scheme, "http" | "https" | "ws" | "wss") {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?.to_ascii_lowercase();
+    (!host.is_empty()).then_some(host)
+}
+
+/// What the manifest permissions allow, in the terms of `Capability`.
+struct Allowed {
+    scope: FileSystemScope,
+    outbound: Vec<globset::GlobMatcher>,
+    inbound: bool,
+}
+
+impl Allowed {
+    fn new(permissions: Option<&Permissions>) -> Self {
+        let fs = permissions.and_then(|p| p.filesystem.as_ref());
+        let net = permissions.and_then(|p| p.network.as_ref());
+        Allowed {
+            scope: fs.map(|fs| fs.scope.clone()).unwrap_or_default(),
+            outbound: net
+                .map(|net| net.allow_outbound.iter())
+                .into_iter()
+                .flatten()
+                .filter_map(|host| Glob::new(&host.to_ascii_lowercase()).ok())
+                .map(|glob| glob.compile_matcher())
+                .collect(),
+            inbound: net.is_some_and(|net| net.allow_inbound),
+        }
+    }
+
+    fn grants(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Outbound => !self.outbound.is_empty(),
+            Capability::Inbound => self.inbound,
+            Capability::Read => !matches!(self.scope, FileSystemScope::None),
+            Capability::Write => matches!(self.scope, FileSystemScope::ReadWrite),
+        }
+    }
+
+    fn reaches(&self, host: &str) -> bool {
+        self.outbound.iter().any(|pattern| pattern.is_match(host))
+    }
+
+    /// Why using `what` needs more than is allowed.
+    fn missing(&self, what: &str, capability: Capability) -> String {
+        match capability {
+            Capability::Outbound => t!("capabilities.outbound", what = what),
+            Capability::Inbound => t!("capabilities.inbound", what = what),
+            Capability::Read => t!("capabilities.read", what = what),
+            Capability::Write if matches!(self.scope, FileSystemScope::ReadOnly) => {
+                t!("capabilities.write_read_only", what = what)
+            },
+            Capability::Write => t!("capabilities.write", what = what),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{FileSystemPermission, Limits, NetworkPermission};
+
+    #[test]
+    fn test_scan_undeclared() {
+        let source = "local http = require('vhost:http')\n\
+                      local fs = require('vhost:fs')\n\
+                      http.get('https://api.example.com/v1')\n\
+                      http.get('https://user@evil.test:8443/x')\n\
+                      os.remove('cache')\n\
+                      os.remove('other')";
+        let lines = |findings: Vec<Finding>| findings.iter().map(|f| f.line).collect::<Vec<_>>();
+
+        // Nothing declared: every capability is missing, each reported once
+        assert_eq!(lines(scan("init.lua", source, None)), [1, 2, 5, 3, 4]);
+
+        let permissions = Permissions::new(
+            FileSystemPermission { scope: FileSystemScope::ReadOnly, ..Default::default() },
+            NetworkPermission::new(vec!["*.example.com".to_string()], false),
+            Limits::default(),
+        );
+        let findings = scan("init.lua", source, Some(&permissions));
+        assert_eq!(lines(findings.clone()), [5, 4]);
+        assert!(findings[1].message.contains("evil.test"));
+    }
+}
@@ -1,81 +1,164 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
 use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
 
+use crate::commands::info::fetch_versions;
+use crate::commands::install::{download_plugin, verify_checksum};
+use crate::dist_tag;
 use crate::encoding::json5;
+use crate::error::VkError;
 use crate::http_client::HttpClient;
-use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
-use crate::utils::parse_package;
+use crate::i18n::t;
+use crate::manifest::{Dependency, MANIFEST_FILENAME, PluginManifest};
+use crate::policy::{self, Policy};
+use crate::project::{LEGACY_MANIFEST_FILENAMES, Project};
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::utils::{parse_package, read_zip_file};
 
-pub fn add_dependency(package: &str, is_dev: bool, http_client: &HttpClient) -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
+/// The manifest section a dependency is added to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Dependencies,
+    DevDependencies,
+    OptionalDependencies,
+}
 
-    let (id, version) = parse_package(package);
-    print!("{} Adding {}", "📦".bold(), id.cyan());
-    if let Some(v) = &version {
-        print!("@{}", v.yellow());
-    }
-    if is_dev {
-        print!(" as dev dependency");
+impl Section {
+    fn name(self) -> &'static str {
+        match self {
+            Section::Dependencies => "dependencies",
+            Section::DevDependencies => "dev-dependencies",
+            Section::OptionalDependencies => "optional-dependencies",
+        }
     }
-    println!();
 
-    let content = fs::read_to_string(manifest_path)?;
-    let mut manifest: PluginManifest = json5::from_str(&content)?;
+    fn entries(self, manifest: &mut PluginManifest) -> &mut HashMap<String, Dependency> {
+        match self {
+            Section::Dependencies => &mut manifest.dependencies,
+            Section::DevDependencies => manifest.dev_dependencies.get_or_insert_with(HashMap::new),
+            Section::OptionalDependencies => &mut manifest.optional_dependencies,
+        }
+    }
+}
 
-    let deps: &mut HashMap<String, String> = if is_dev {
-        manifest.dev_dependencies.get_or_insert_with(HashMap::new)
-    } else {
-        &mut manifest.dependencies
+pub fn add_dependency(
+    project: &Project,
+    package: &str,
+    section: Section,
+    include_pre: bool,
+    source: Option<Dependency>,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let (id, version) = parse_package(package);
+    let spec = match &version {
+        Some(v) => format!("{}@{}", id.cyan(), v.yellow()),
+        None => id.cyan().to_string(),
     };
+    match section {
+        Section::Dependencies => reporter.title(&t!("add.title", package = spec)),
+        Section::DevDependencies => reporter.title(&t!("add.title_dev", package = spec)),
+        Section::OptionalDependencies => reporter.title(&t!("add.title_optional", package = spec)),
+    }
+
+    let mut manifest = project.read_manifest()?;
+    let deps = section.entries(&mut manifest);
 
     #[allow(clippy::collapsible_if)]
-    if let Some(existing_version) = deps.get(&id) {
+    if let Some(existing_version) = deps.get(&id).and_then(Dependency::version) {
         if let Some(ref req) = version {
             if existing_version == req {
-                println!("Dependency already up to date.");
+                reporter.info(&t!("add.already_up_to_date"));
                 return Ok(());
             }
         }
     }
 
+    // Git and path sources are written as given; there is nothing to resolve
+    if let Some(source) = source {
+        reporter.step(&t!("add.source", source = source.to_string().bright_black()));
+        deps.insert(id.clone(), source);
+        project.write_manifest(&manifest)?;
+
+        reporter.done(&t!("add.added", id = id.cyan(), section = section.name()));
+        return Ok(());
+    }
+
     let final_version = match version {
+        Some(tag) if dist_tag::is_dist_tag(&tag) => {
+            let resolved = dist_tag::resolve(&id, &tag, http_client)?;
+            reporter.step(&t!("add.resolved_tag", tag = tag, version = resolved));
+            resolved
+        },
         Some(v) => v,
+        None if include_pre => {
+            let versions = fetch_versions(&id, http_client)?;
+            let latest = resolver::latest(versions.iter().map(String::as_str), true)
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "*".to_string());
+            reporter.step(&t!("add.latest_version", version = latest));
+            latest
+        },
         None => {
             let latest = fetch_latest_version(&id, http_client)?;
-            println!("Latest version: {}", latest);
+            reporter.step(&t!("add.latest_version", version = latest));
             latest
         },
     };
 
-    deps.insert(id.clone(), final_version);
+    if let Some(policy) = policy::load(Some(project))? {
+        check_policy(&policy, &id, &final_version, include_pre, http_client, reporter)?;
+    }
+
+    deps.insert(id.clone(), Dependency::Version(final_version));
 
-    fs::write(manifest_path, json5::to_string_pretty(&manifest)?)?;
+    project.write_manifest(&manifest)?;
 
-    println!(
-        "{} Added {} to {}",
-        "✅".green(),
-        id.cyan(),
-        if is_dev {
-            "dev-dependencies".green()
-        } else {
-            "dependencies".green()
-        }
-    );
+    reporter.done(&t!("add.added", id = id.cyan(), section = section.name()));
 
     Ok(())
 }
 
-fn fetch_latest_version(id: &str, http_client: &HttpClient) -> Result<String> {
-    #[derive(serde::Deserialize)]
-    struct PackageInfo {
-        #[serde(rename = "latestVersion")]
-        latest_version: String,
-    }
+/// Downloads the version about to be added and checks the permissions it
+/// declares against the policy, before anything is written.
+fn check_policy(
+    policy: &Policy,
+    id: &str,
+    spec: &str,
+    include_pre: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let version = match resolver::parse_req(spec) {
+        _ if resolver::parse_version(spec).is_some() => Some(spec.to_string()),
+        Some(req) if spec != "*" => {
+            let versions = fetch_versions(id, http_client)?;
+            resolver::max_satisfying(versions.iter().map(String::as_str), &req, include_pre).map(|v| v.to_string())
+        },
+        _ => None,
+    };
+
+    reporter.step(&t!("add.checking_policy"));
+    let (data, meta) = download_plugin(id, version.as_deref(), http_client, reporter)?;
+    verify_checksum(&data, &meta, reporter)?;
+
+    let manifest = std::iter::once(MANIFEST_FILENAME)
+        .chain(LEGACY_MANIFEST_FILENAMES.iter().copied())
+        .find_map(|name| read_zip_file(&data, name).ok().flatten());
+    let Some(manifest) = manifest else {
+        return Ok(());
+    };
+    let manifest: PluginManifest = json5::from_str(&String::from_utf8_lossy(&manifest))
+        .map_err(|e| VkError::ManifestInvalid(e.to_string()))
+        .with_context(|| t!("manifest.parse_failed"))?;
 
-    match http_client.get::<PackageInfo>(&format!("/packages/{}", id)) {
+    policy.check(id, manifest.permissions.as_ref(), reporter)
+}
+
+fn fetch_latest_version(id: &str, http_client: &HttpClient) -> Result<String> {
+    match RegistryClient::new(http_client).package(id) {
         Ok(info) => Ok(info.latest_version),
         Err(_) => Ok("*".to_string()),
     }
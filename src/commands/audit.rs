@@ -1,122 +1,443 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::Colorize;
-use std::fs;
-use std::path::Path;
 
-use crate::encoding::json5;
+use crate::encoding::json5::DateTime;
+use crate::error::VkError;
 use crate::http_client::HttpClient;
-use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
+use crate::i18n::t;
+use crate::lockfile::Lockfile;
+use crate::manifest::PluginManifest;
+use crate::policy::{Enforcement, Policy};
+use crate::project::Project;
+use crate::registry::RegistryClient;
+use crate::report::{FindingEntry, Recorder, VulnerabilityEntry};
+use crate::reporter::Reporter;
 
-pub fn audit_dependencies(http_client: &HttpClient) -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
+/// Points each finding takes off the score of 100.
+const LICENSE_VIOLATION_PENALTY: u32 = 10;
+const MISSING_LICENSE_PENALTY: u32 = 3;
+const PERMISSION_VIOLATION_PENALTY: u32 = 10;
+const YANKED_PENALTY: u32 = 15;
+const DEPRECATED_PENALTY: u32 = 5;
+const UNMAINTAINED_PENALTY: u32 = 3;
 
-    println!("{}", "🔍 Scanning for vulnerabilities...".bold().cyan());
-    println!();
+const SECS_PER_MONTH: i64 = 30 * 86_400;
 
-    let content = fs::read_to_string(manifest_path).context("Failed to read manifest file")?;
-    let manifest: PluginManifest = json5::from_str(&content).context("Failed to parse manifest file")?;
+/// Which sections of the report `vk audit` runs.
+#[derive(Debug, Clone, Copy)]
+pub struct AuditOptions {
+    /// Known advisories against the declared dependencies.
+    pub vulnerabilities: bool,
+    /// Licenses of the locked packages against the policy.
+    pub licenses: bool,
+    /// Permissions of the installed plugins against the policy.
+    pub permissions: bool,
+    /// Locked versions their publisher yanked or deprecated.
+    pub yanked: bool,
+    /// Locked packages without a release for `stale_months`.
+    pub unmaintained: bool,
+    pub stale_months: u32,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        AuditOptions {
+            vulnerabilities: true,
+            licenses: true,
+            permissions: true,
+            yanked: true,
+            unmaintained: true,
+            stale_months: 12,
+        }
+    }
+}
+
+/// What each section found, for the summary and the score.
+#[derive(Default)]
+struct Summary {
+    sections: Vec<(String, usize)>,
+    penalty: u32,
+    /// Packages over an enforced policy, licenses or permissions.
+    violations: Vec<String>,
+}
+
+impl Summary {
+    fn section(&mut self, name: String, issues: usize) {
+        self.sections.push((name, issues));
+    }
+
+    fn score(&self) -> u32 {
+        100u32.saturating_sub(self.penalty)
+    }
+}
+
+/// Reports on the supply chain of the project: vulnerabilities, licenses,
+/// permissions, yanked or deprecated versions and unmaintained packages, as
+/// `options` selects, ending with a score out of 100, which is returned. An
+/// enforced policy fails the audit once every section ran.
+pub fn audit_dependencies(
+    project: &Project,
+    plugins_dir: &str,
+    policy: Option<&Policy>,
+    options: &AuditOptions,
+    http_client: &HttpClient,
+    recorder: &Recorder,
+    reporter: &dyn Reporter,
+) -> Result<u32> {
+    let manifest = project.read_manifest()?;
+    let lockfile = Lockfile::load(project)?;
+    let registry = RegistryClient::new(http_client);
+    let mut summary = Summary::default();
+
+    if options.vulnerabilities {
+        audit_vulnerabilities(&manifest, &registry, recorder, reporter, &mut summary);
+    }
+    if options.licenses {
+        audit_licenses(
+            project,
+            &lockfile,
+            plugins_dir,
+            policy,
+            &registry,
+            recorder,
+            reporter,
+            &mut summary,
+        );
+    }
+    if options.permissions {
+        audit_permissions(
+            project,
+            &manifest,
+            plugins_dir,
+            policy,
+            recorder,
+            reporter,
+            &mut summary,
+        );
+    }
+    if options.yanked || options.unmaintained {
+        audit_releases(&lockfile, options, &registry, recorder, reporter, &mut summary);
+    }
+
+    let score = summary.score();
+    recorder.score(score);
+    reporter.step(&t!("audit.summary"));
+    for (section, issues) in &summary.sections {
+        let result = match issues {
+            0 => t!("audit.section_ok").green().to_string(),
+            _ => t!("audit.section_issues", count = issues).yellow().to_string(),
+        };
+        reporter.output(&format!("  {:<16} {}", section, result));
+    }
+    let score_line = t!("audit.score", score = score);
+    match score {
+        100 => reporter.success(&score_line),
+        70.. => reporter.warn(&score_line),
+        _ => reporter.error(&score_line),
+    }
+
+    if summary.violations.is_empty() || policy.is_none_or(|p| p.enforcement == Enforcement::Warn) {
+        return Ok(score);
+    }
+    summary.violations.sort();
+    summary.violations.dedup();
+    Err(VkError::PolicyViolation(summary.violations.join(", ")).into())
+}
+
+fn audit_vulnerabilities(
+    manifest: &PluginManifest,
+    registry: &RegistryClient,
+    recorder: &Recorder,
+    reporter: &dyn Reporter,
+    summary: &mut Summary,
+) {
+    reporter.title(&t!("audit.title"));
 
     let mut all_deps: Vec<(String, String, bool)> = Vec::new();
 
-    for (name, version) in manifest.dependencies {
-        all_deps.push((name.clone(), version.clone(), false));
+    // Advisories are only published for registry packages
+    for (name, dep) in &manifest.dependencies {
+        if let Some(version) = dep.version() {
+            all_deps.push((name.clone(), version.to_string(), false));
+        }
     }
 
-    if let Some(dev_deps) = manifest.dev_dependencies {
-        for (name, version) in dev_deps {
-            all_deps.push((name.clone(), version.clone(), true));
+    if let Some(dev_deps) = &manifest.dev_dependencies {
+        for (name, dep) in dev_deps {
+            if let Some(version) = dep.version() {
+                all_deps.push((name.clone(), version.to_string(), true));
+            }
         }
     }
 
     if all_deps.is_empty() {
-        println!("{} No dependencies to audit", "✅".green());
-        return Ok(());
+        reporter.done(&t!("audit.no_dependencies"));
+        summary.section(t!("audit.section_vulnerabilities"), 0);
+        return;
     }
 
-    println!("{} Checking {} packages...", "📋".bold(), all_deps.len());
-    println!();
+    reporter.step(&t!("audit.checking", count = all_deps.len()));
 
-    let mut vulnerabilities_found = false;
+    let mut findings = Vec::new();
     let mut checked = 0;
 
+    let pb = reporter.progress(Some(all_deps.len() as u64), &t!("audit.progress"));
     for (name, version, is_dev) in &all_deps {
         checked += 1;
 
-        match check_vulnerability(name, http_client) {
-            Ok(Some(vulns)) => {
-                vulnerabilities_found = true;
-                println!(
-                    "{} {}@{} ( {})",
-                    "⚠️".red().bold(),
-                    name.cyan(),
-                    version.yellow(),
-                    if *is_dev { "dev" } else { "prod" }
-                );
-
-                for vuln in vulns {
-                    println!(
-                        "{}",
-                        format!("  [{}] {}", vuln.severity.to_uppercase().red(), vuln.title).red()
-                    );
-                    println!("{}", format!("    ID: {}", vuln.id).bright_black());
-                    if let Some(desc) = &vuln.description {
-                        println!("{}", format!("    {}", desc).bright_black());
-                    }
-                    if let Some(patched) = &vuln.patched_versions {
-                        println!("{}", format!("    Patched in: {}", patched).green());
-                    }
-                    println!();
-                }
-            },
-            Ok(None) => {
-                print!(".");
-            },
-            Err(_) => {
-                print!("?");
-            },
+        if let Ok(vulns) = registry.advisories(name)
+            && !vulns.is_empty()
+        {
+            findings.push((name, version, *is_dev, vulns));
         }
+        pb.inc(1);
     }
+    pb.finish();
 
-    println!();
-    println!();
+    for (name, version, is_dev, vulns) in &findings {
+        reporter.warn(&format!(
+            "{}@{} ({})",
+            name.cyan(),
+            version.yellow(),
+            if *is_dev { "dev" } else { "prod" }
+        ));
 
-    if vulnerabilities_found {
-        println!("{}", "❌ Vulnerabilities found!".red().bold());
-        println!("{}", "Please update your dependencies using 'vk update'".yellow());
+        for vuln in vulns {
+            summary.penalty += severity_penalty(&vuln.severity);
+            recorder.vulnerability(VulnerabilityEntry {
+                package: name.to_string(),
+                version: version.to_string(),
+                id: vuln.id.clone(),
+                severity: vuln.severity.clone(),
+                title: vuln.title.clone(),
+            });
+            reporter.output(&format!(
+                "  [{}] {}",
+                vuln.severity.to_uppercase().red(),
+                vuln.title.red()
+            ));
+            reporter.output(&format!("    ID: {}", vuln.id).bright_black().to_string());
+            if let Some(desc) = &vuln.description {
+                reporter.output(&format!("    {}", desc).bright_black().to_string());
+            }
+            if let Some(patched) = &vuln.patched_versions {
+                reporter.output(&format!("    {}", t!("audit.patched_in", versions = patched)).green().to_string());
+            }
+        }
+    }
+
+    if !findings.is_empty() {
+        reporter.error(&t!("audit.found"));
+        reporter.info(&t!("audit.update_hint"));
     } else {
-        println!("{} No vulnerabilities found!", "✅".green().bold());
-        println!("{} {} packages audited successfully", "✓".green(), checked);
+        reporter.done(&t!("audit.clean"));
+        reporter.success(&t!("audit.audited", count = checked));
     }
+    let count = findings.iter().map(|(.., vulns)| vulns.len()).sum();
+    summary.section(t!("audit.section_vulnerabilities"), count);
+}
 
-    Ok(())
+fn severity_penalty(severity: &str) -> u32 {
+    match severity.to_ascii_lowercase().as_str() {
+        "critical" => 25,
+        "high" => 15,
+        "moderate" | "medium" => 8,
+        "low" => 3,
+        _ => 5,
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct VulnerabilityResponse {
-    vulnerabilities: Vec<Vulnerability>,
+/// Checks the license of every locked package, read from its installed
+/// manifest or else from the registry, against the policy.
+#[allow(clippy::too_many_arguments)]
+fn audit_licenses(
+    project: &Project,
+    lockfile: &Lockfile,
+    plugins_dir: &str,
+    policy: Option<&Policy>,
+    registry: &RegistryClient,
+    recorder: &Recorder,
+    reporter: &dyn Reporter,
+    summary: &mut Summary,
+) {
+    reporter.step(&t!("audit.checking_licenses", count = lockfile.packages.len()));
+
+    let mut issues = 0;
+    for package in &lockfile.packages {
+        let dir = project.root().join(plugins_dir).join(&package.id);
+        let license = Project::at(&dir)
+            .and_then(|p| p.read_manifest().ok())
+            .map(|m| m.license)
+            .filter(|license| !license.trim().is_empty())
+            .or_else(|| {
+                package
+                    .is_registry()
+                    .then(|| registry.package(&package.id).ok())
+                    .flatten()
+                    .and_then(|details| details.license)
+            });
+
+        let message = match &license {
+            None => {
+                summary.penalty += MISSING_LICENSE_PENALTY;
+                t!("audit.license_missing")
+            },
+            Some(license) if policy.is_some_and(|p| !p.licenses.permits(license)) => {
+                summary.penalty += LICENSE_VIOLATION_PENALTY;
+                summary.violations.push(package.id.clone());
+                t!("audit.license_denied", license = license)
+            },
+            Some(_) => continue,
+        };
+        issues += 1;
+        reporter.warn(&format!(
+            "{}@{}: {}",
+            package.id.cyan(),
+            package.version.yellow(),
+            message
+        ));
+        recorder.finding(FindingEntry {
+            section: "licenses",
+            package: package.id.clone(),
+            version: Some(package.version.clone()),
+            message,
+        });
+    }
+
+    if issues == 0 {
+        reporter.success(&t!("audit.licenses_ok"));
+    }
+    summary.section(t!("audit.section_licenses"), issues);
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct Vulnerability {
-    id: String,
-    title: String,
-    #[serde(rename = "severity")]
-    severity: String,
-    #[serde(rename = "description")]
-    description: Option<String>,
-    #[serde(rename = "patched_versions")]
-    patched_versions: Option<String>,
+/// Checks the installed copy of every dependency against the permission
+/// policy. All violations are listed before an enforced policy fails.
+fn audit_permissions(
+    project: &Project,
+    manifest: &PluginManifest,
+    plugins_dir: &str,
+    policy: Option<&Policy>,
+    recorder: &Recorder,
+    reporter: &dyn Reporter,
+    summary: &mut Summary,
+) {
+    let Some(policy) = policy else {
+        reporter.detail(&t!("audit.no_policy"));
+        return;
+    };
+    reporter.step(&t!("audit.checking_policy"));
+
+    let names = manifest.dependencies.keys().chain(manifest.dev_dependencies.iter().flat_map(|d| d.keys()));
+    let mut issues = 0;
+
+    for name in names {
+        let dir = project.root().join(plugins_dir).join(name);
+        let Some(installed) = Project::at(&dir).and_then(|p| p.read_manifest().ok()) else {
+            continue;
+        };
+        let permissions = installed.permissions.as_ref();
+        let violations = policy.violations(permissions);
+        if violations.is_empty() {
+            continue;
+        }
+        // Only reported here; an enforced policy fails once every section ran
+        let _ = policy.check(name, permissions, reporter);
+        issues += 1;
+        summary.penalty += PERMISSION_VIOLATION_PENALTY;
+        summary.violations.push(name.clone());
+        recorder.finding(FindingEntry {
+            section: "permissions",
+            package: name.clone(),
+            version: Some(installed.version.clone()),
+            message: violations.join("; "),
+        });
+    }
+
+    if issues == 0 {
+        reporter.success(&t!("audit.policy_ok"));
+    }
+    summary.section(t!("audit.section_permissions"), issues);
 }
 
-fn check_vulnerability(package: &str, http_client: &HttpClient) -> Result<Option<Vec<Vulnerability>>> {
-    match http_client.get::<VulnerabilityResponse>(&format!("/audit/{}", package)) {
-        Ok(response) => {
-            if response.vulnerabilities.is_empty() {
-                Ok(None)
-            } else {
-                Ok(Some(response.vulnerabilities))
+/// Looks up the releases of every locked registry package, for versions
+/// withdrawn since they were locked and packages nobody releases anymore.
+fn audit_releases(
+    lockfile: &Lockfile,
+    options: &AuditOptions,
+    registry: &RegistryClient,
+    recorder: &Recorder,
+    reporter: &dyn Reporter,
+    summary: &mut Summary,
+) {
+    let packages: Vec<_> = lockfile.packages.iter().filter(|p| p.is_registry()).collect();
+    reporter.step(&t!("audit.checking_releases", count = packages.len()));
+
+    let stale_before = DateTime::now().unix_timestamp() - i64::from(options.stale_months) * SECS_PER_MONTH;
+    let (mut withdrawn, mut stale) = (0, 0);
+    let report = |package: &str, version: Option<&str>, section: &'static str, message: String| {
+        let at = version.map(|v| format!("@{}", v.yellow())).unwrap_or_default();
+        reporter.warn(&format!("{}{}: {}", package.cyan(), at, message));
+        recorder.finding(FindingEntry {
+            section,
+            package: package.to_string(),
+            version: version.map(str::to_string),
+            message,
+        });
+    };
+
+    let pb = reporter.progress(Some(packages.len() as u64), &t!("audit.progress"));
+    for package in &packages {
+        pb.inc(1);
+        let versions = match registry.versions(&package.id) {
+            Ok(versions) => versions,
+            Err(err) => {
+                reporter.detail(&t!("audit.releases_failed", package = package.id.cyan(), error = err));
+                continue;
+            },
+        };
+
+        if options.yanked
+            && let Some(locked) = versions.iter().find(|v| v.version == package.version)
+        {
+            if locked.yanked {
+                withdrawn += 1;
+                summary.penalty += YANKED_PENALTY;
+                report(&package.id, Some(&package.version), "yanked", t!("audit.yanked"));
+            } else if let Some(reason) = &locked.deprecated {
+                withdrawn += 1;
+                summary.penalty += DEPRECATED_PENALTY;
+                report(
+                    &package.id,
+                    Some(&package.version),
+                    "yanked",
+                    t!("audit.deprecated", reason = reason),
+                );
             }
-        },
-        Err(_) => Ok(None),
+        }
+
+        if options.unmaintained
+            && let Some(last) = versions.iter().filter_map(|v| v.published_at).max()
+            && last.unix_timestamp() < stale_before
+        {
+            stale += 1;
+            summary.penalty += UNMAINTAINED_PENALTY;
+            report(
+                &package.id,
+                None,
+                "unmaintained",
+                t!("audit.unmaintained", date = last.date()),
+            );
+        }
+    }
+    pb.finish();
+
+    if options.yanked {
+        summary.section(t!("audit.section_yanked"), withdrawn);
+    }
+    if options.unmaintained {
+        summary.section(t!("audit.section_unmaintained"), stale);
+    }
+    if withdrawn + stale == 0 {
+        reporter.success(&t!("audit.releases_ok"));
     }
 }
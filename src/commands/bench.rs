@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Instant;
+
+use crate::encoding::json5;
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::manifest::PluginManifest;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::scripts::{self, Suite};
+
+pub struct BenchOptions {
+    /// Only run bench files whose path contains this.
+    pub filter: Option<String>,
+    /// Command to run each bench file with, instead of the `bench` script or
+    /// the host runtime.
+    pub interpreter: Option<String>,
+    /// Untimed iterations before measuring.
+    pub warmup: u32,
+    /// Timed iterations.
+    pub iterations: u32,
+    /// Results to compare against.
+    pub baseline: Option<String>,
+    /// Where to save these results as a baseline.
+    pub save_baseline: Option<String>,
+    /// Slowdown of the median, in percent, that counts as a regression.
+    pub threshold: f64,
+}
+
+/// Timing of one bench file, in nanoseconds per iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub mean_ns: u64,
+    pub median_ns: u64,
+    pub p95_ns: u64,
+    pub samples: usize,
+}
+
+/// Saved results, keyed by bench file.
+type Baseline = BTreeMap<String, Stats>;
+
+/// Runs every Lua file under `benches/` and reports the mean, median and 95th
+/// percentile time of an iteration. With a baseline, fails when a median got
+/// slower than the threshold allows.
+///
+/// The runner gets the iteration counts in `VK_BENCH_WARMUP` and
+/// `VK_BENCH_ITERATIONS` and writes the duration of each timed iteration, in
+/// nanoseconds, one per line, to `VK_BENCH_OUTPUT`. When it writes nothing,
+/// vk times whole runs of the file instead.
+pub fn run_benches(project: &Project, options: &BenchOptions, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+
+    let files: Vec<String> = Suite::Bench
+        .files(project)
+        .into_iter()
+        .filter(|f| options.filter.as_deref().is_none_or(|filter| f.contains(filter)))
+        .collect();
+
+    if files.is_empty() {
+        reporter.info(&t!("bench.none", dir = Suite::Bench.dir()));
+        return Ok(());
+    }
+
+    let baseline = options.baseline.as_deref().map(load_baseline).transpose()?;
+
+    reporter.title(&t!(
+        "bench.title",
+        count = files.len(),
+        name = manifest.name.cyan(),
+        version = manifest.version.yellow()
+    ));
+
+    let mut results = Baseline::new();
+    let mut regressions = Vec::new();
+
+    for file in &files {
+        reporter.step(&t!(
+            "bench.running",
+            file = file.cyan(),
+            warmup = options.warmup,
+            iterations = options.iterations
+        ));
+
+        let samples = measure(project, &manifest, file, options)?;
+        let stats = stats(&samples);
+        reporter.success(&t!(
+            "bench.result",
+            file = file,
+            mean = format_nanos(stats.mean_ns),
+            median = format_nanos(stats.median_ns).bold(),
+            p95 = format_nanos(stats.p95_ns),
+            samples = stats.samples
+        ));
+
+        if let Some(before) = baseline.as_ref().and_then(|b| b.get(file)) {
+            let change = change_percent(before.median_ns, stats.median_ns);
+            let label = format!("{:+.1}%", change);
+            if change > options.threshold {
+                reporter.error(&t!(
+                    "bench.regression",
+                    change = label.red().bold(),
+                    before = format_nanos(before.median_ns),
+                    threshold = options.threshold
+                ));
+                regressions.push(file.clone());
+            } else {
+                let label = if change < 0.0 { label.green() } else { label.normal() };
+                reporter.detail(&t!(
+                    "bench.compared",
+                    change = label,
+                    before = format_nanos(before.median_ns)
+                ));
+            }
+        }
+
+        results.insert(file.clone(), stats);
+    }
+
+    if let Some(path) = &options.save_baseline {
+        // Keep the entries of benches that were filtered out this time
+        let mut saved = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| json5::from_str::<Baseline>(&content).ok())
+            .unwrap_or_default();
+        saved.extend(results);
+        fs::write(path, serde_json::to_string_pretty(&saved)? + "\n")
+            .with_context(|| t!("common.write_failed", path = path))?;
+        reporter.detail(&t!("bench.baseline_saved", path = path));
+    }
+
+    if !regressions.is_empty() {
+        return Err(VkError::BenchRegression(regressions.join(", ")).into());
+    }
+
+    reporter.done(&t!("bench.done", count = files.len()));
+    Ok(())
+}
+
+fn load_baseline(path: &str) -> Result<Baseline> {
+    let content = fs::read_to_string(path).with_context(|| t!("common.read_failed", path = path))?;
+    json5::from_str(&content).with_context(|| t!("bench.baseline_invalid", path = path))
+}
+
+/// Per-iteration durations of `file`, from the runner when it reports them,
+/// else from timing whole runs.
+fn measure(project: &Project, manifest: &PluginManifest, file: &str, options: &BenchOptions) -> Result<Vec<u64>> {
+    let output = tempfile::NamedTempFile::new()?;
+    let warmup = options.warmup.to_string();
+    let iterations = options.iterations.to_string();
+    let env = [
+        ("VK_BENCH_WARMUP", warmup.as_ref()),
+        ("VK_BENCH_ITERATIONS", iterations.as_ref()),
+        ("VK_BENCH_OUTPUT", output.path().as_os_str()),
+    ];
+
+    let run = || -> Result<u64> {
+        let started = Instant::now();
+        let status = scripts::run_suite_file(
+            project,
+            manifest,
+            Suite::Bench,
+            file,
+            options.interpreter.as_deref(),
+            &env,
+        )?;
+        if !status.success() {
+            return Err(VkError::ScriptFailed { name: file.to_string(), status: status.code() }.into());
+        }
+        Ok(started.elapsed().as_nanos() as u64)
+    };
+
+    run()?;
+    let reported: Vec<u64> = fs::read_to_string(output.path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+    if !reported.is_empty() {
+        return Ok(reported);
+    }
+
+    // The first run already served as a warmup
+    for _ in 1..options.warmup {
+        run()?;
+    }
+    (0..options.iterations.max(1)).map(|_| run()).collect()
+}
+
+fn stats(samples: &[u64]) -> Stats {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let n = sorted.len();
+    if n == 0 {
+        return Stats { mean_ns: 0, median_ns: 0, p95_ns: 0, samples: 0 };
+    }
+
+    let median = if n.is_multiple_of(2) {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+    } else {
+        sorted[n / 2]
+    };
+    // Nearest-rank percentile
+    let p95 = sorted[(n * 95).div_ceil(100) - 1];
+
+    Stats {
+        mean_ns: (sorted.iter().map(|&s| s as u128).sum::<u128>() / n as u128) as u64,
+        median_ns: median,
+        p95_ns: p95,
+        samples: n,
+    }
+}
+
+/// How much slower `after` is than `before`, in percent.
+fn change_percent(before: u64, after: u64) -> f64 {
+    if before == 0 {
+        return 0.0;
+    }
+    (after as f64 - before as f64) * 100.0 / before as f64
+}
+
+fn format_nanos(nanos: u64) -> String {
+    match nanos {
+        n if n < 1_000 => format!("{} ns", n),
+        n if n < 1_000_000 => format!("{:.2} µs", n as f64 / 1e3),
+        n if n < 1_000_000_000 => format!("{:.2} ms", n as f64 / 1e6),
+        n => format!("{:.2} s", n as f64 / 1e9),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let samples: Vec<u64> = (1..=20).rev().map(|n| n * 10).collect();
+        let stats = stats(&samples);
+        assert_eq!(stats.samples, 20);
+        assert_eq!(stats.mean_ns, 105);
+        assert_eq!(stats.median_ns, 105);
+        assert_eq!(stats.p95_ns, 190);
+
+        let single = super::stats(&[42]);
+        assert_eq!((single.mean_ns, single.median_ns, single.p95_ns), (42, 42, 42));
+    }
+
+    #[test]
+    fn test_change_and_format() {
+        assert_eq!(change_percent(100, 112), 12.0);
+        assert_eq!(change_percent(200, 150), -25.0);
+        assert_eq!(format_nanos(999), "999 ns");
+        assert_eq!(format_nanos(1_500), "1.50 µs");
+        assert_eq!(format_nanos(2_345_000), "2.35 ms");
+    }
+}
@@ -0,0 +1,341 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+
+use crate::encoding::json5::DateTime;
+use crate::git;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+
+pub const CHANGELOG_FILENAME: &str = "CHANGELOG.md";
+
+const HEADER: &str = "# Changelog\n\nAll notable changes to this plugin are documented in this file.\n";
+const UNRELEASED: &str = "Unreleased";
+
+/// Sections of a generated entry, in the order they are written, with the
+/// conventional commit types that go in each.
+const GROUPS: &[(&str, &[&str])] = &[
+    ("Features", &["feat"]),
+    ("Bug Fixes", &["fix"]),
+    ("Performance", &["perf"]),
+    ("Reverts", &["revert"]),
+];
+const BREAKING: &str = "Breaking Changes";
+/// Where commits that do not follow the convention go, when none does.
+const OTHER: &str = "Changes";
+
+pub struct AddOptions<'a> {
+    /// Version of the entry; the manifest version when `None`.
+    pub version: Option<&'a str>,
+    /// Commit or tag after which commits are collected; the latest tag when
+    /// `None`.
+    pub since: Option<&'a str>,
+    /// Replace an entry the changelog already has for the version.
+    pub force: bool,
+    /// Print the entry instead of writing it.
+    pub dry_run: bool,
+}
+
+/// A commit as `git log` reports it.
+#[derive(Debug, PartialEq)]
+struct Commit {
+    subject: String,
+    body: String,
+}
+
+/// What a conventional commit subject (`type(scope)!: description`) says.
+#[derive(Debug, PartialEq)]
+struct Conventional<'a> {
+    kind: &'a str,
+    scope: Option<&'a str>,
+    breaking: bool,
+    description: &'a str,
+}
+
+/// Adds the entry of a version to `CHANGELOG.md`, creating the file when
+/// needed. An `Unreleased` section with content becomes the entry; otherwise
+/// it is generated from the conventional commits since the latest tag.
+pub fn add_entry(project: &Project, options: &AddOptions, reporter: &dyn Reporter) -> Result<()> {
+    let version = match options.version {
+        Some(version) => version.to_string(),
+        None => project.read_manifest()?.version,
+    };
+    let path = project.root().join(CHANGELOG_FILENAME);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => HEADER.to_string(),
+    };
+
+    if section(&content, &version).is_some() && !options.force {
+        anyhow::bail!(t!(
+            "changelog.entry_exists",
+            version = version,
+            file = CHANGELOG_FILENAME
+        ));
+    }
+
+    let unreleased = section(&content, UNRELEASED).filter(|notes| !notes.is_empty());
+    let notes = match unreleased {
+        Some(notes) => {
+            reporter.step(&t!("changelog.promoting", version = version.yellow()));
+            notes
+        },
+        None => {
+            let commits = collect_commits(project, options.since, reporter)?;
+            reporter.step(&t!("changelog.collected", count = commits.len()));
+            render_notes(&commits)
+        },
+    };
+    if notes.is_empty() {
+        reporter.warn(&t!("changelog.empty", version = version.yellow()));
+    }
+
+    let entry = format!("## [{}] - {}\n\n{}", version, DateTime::now().date(), notes);
+    if options.dry_run {
+        reporter.output(entry.trim_end());
+        return Ok(());
+    }
+
+    let updated = insert(&remove_section(&remove_section(&content, UNRELEASED), &version), &entry);
+    fs::write(&path, updated).with_context(|| t!("common.write_failed", path = path.display()))?;
+    reporter.done(&t!(
+        "changelog.added",
+        version = version.yellow(),
+        file = CHANGELOG_FILENAME.cyan()
+    ));
+    Ok(())
+}
+
+/// Prints the entry of a version, the manifest version when `None`.
+pub fn show_entry(project: &Project, version: Option<&str>, reporter: &dyn Reporter) -> Result<()> {
+    let version = match version {
+        Some(version) => version.to_string(),
+        None => project.read_manifest()?.version,
+    };
+    match release_notes(project, &version) {
+        Some(notes) => reporter.output(&notes),
+        None => anyhow::bail!(t!("changelog.no_entry", version = version, file = CHANGELOG_FILENAME)),
+    }
+    Ok(())
+}
+
+/// The notes `CHANGELOG.md` has for `version`, if any.
+pub fn release_notes(project: &Project, version: &str) -> Option<String> {
+    let content = fs::read_to_string(project.root().join(CHANGELOG_FILENAME)).ok()?;
+    section(&content, version).filter(|notes| !notes.is_empty())
+}
+
+/// Commits after `since`, or after the latest tag, oldest first. Outside a
+/// git repository there are none.
+fn collect_commits(project: &Project, since: Option<&str>, reporter: &dyn Reporter) -> Result<Vec<Commit>> {
+    let root = project.root();
+    if git::run(root, &["rev-parse", "--git-dir"]).is_err() {
+        reporter.warn(&t!("changelog.no_git"));
+        return Ok(Vec::new());
+    }
+
+    let since = match since {
+        Some(since) => Some(since.to_string()),
+        None => git::run(root, &["describe", "--tags", "--abbrev=0"]).ok(),
+    };
+    let range = since.map(|since| format!("{}..HEAD", since)).unwrap_or_else(|| "HEAD".to_string());
+    // Fields are split by unit separators, commits by record separators
+    let log = git::run(root, &["log", "--reverse", "--format=%s%x1f%b%x1e", &range, "--", "."])?;
+
+    Ok(log
+        .split('\x1e')
+        .filter_map(|record| {
+            let (subject, body) = record.trim().split_once('\x1f')?;
+            Some(Commit {
+                subject: subject.trim().to_string(),
+                body: body.trim().to_string(),
+            })
+        })
+        .collect())
+}
+
+fn parse_conventional(subject: &str) -> Option<Conventional<'_>> {
+    let (head, description) = subject.split_once(": ")?;
+    let (head, breaking) = match head.strip_suffix('!') {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let (kind, scope) = match head.split_once('(') {
+        Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?)),
+        None => (head, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(Conventional { kind, scope, breaking, description: description.trim() })
+}
+
+/// Groups the commits into the sections of an entry. Commit types with no
+/// section (`chore`, `docs`, `test`, ...) are left out. When no commit is
+/// conventional, every subject is listed as is.
+fn render_notes(commits: &[Commit]) -> String {
+    let parsed: Vec<_> = commits.iter().map(|c| (c, parse_conventional(&c.subject))).collect();
+
+    if parsed.iter().all(|(_, conventional)| conventional.is_none()) {
+        return render_group(OTHER, commits.iter().map(|c| c.subject.clone()).collect());
+    }
+
+    let line = |c: &Conventional| match c.scope {
+        Some(scope) => format!("**{}:** {}", scope, c.description),
+        None => c.description.to_string(),
+    };
+    let mut notes = render_group(
+        BREAKING,
+        parsed
+            .iter()
+            .filter_map(|(commit, c)| {
+                let c = c.as_ref()?;
+                (c.breaking || commit.body.contains("BREAKING CHANGE")).then(|| line(c))
+            })
+            .collect(),
+    );
+    for (title, kinds) in GROUPS {
+        notes.push_str(&render_group(
+            title,
+            parsed
+                .iter()
+                .filter_map(|(_, c)| c.as_ref().filter(|c| kinds.contains(&c.kind)).map(line))
+                .collect(),
+        ));
+    }
+    notes
+}
+
+fn render_group(title: &str, lines: Vec<String>) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut group = format!("### {}\n\n", title);
+    for line in lines {
+        group.push_str(&format!("- {}\n", line));
+    }
+    group.push('\n');
+    group
+}
+
+/// Whether a `## ` heading is the one of `version`: `## [1.2.0] - date`,
+/// `## 1.2.0`, `## v1.2.0 (date)`...
+fn is_heading_of(line: &str, version: &str) -> bool {
+    let Some(title) = line.strip_prefix("## ") else {
+        return false;
+    };
+    let title = title.trim_start_matches('[');
+    let title = title.strip_prefix('v').unwrap_or(title);
+    title.strip_prefix(version).is_some_and(|rest| rest.is_empty() || rest.starts_with([']', ' ']))
+}
+
+/// Byte range of the section of `version`, from its heading to the next
+/// `## ` heading.
+fn section_range(content: &str, version: &str) -> Option<(usize, usize, usize)> {
+    let mut offset = 0;
+    let mut start = None;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("## ") {
+            if let Some((heading, body)) = start {
+                return Some((heading, body, offset));
+            }
+            if is_heading_of(line.trim_end(), version) {
+                start = Some((offset, offset + line.len()));
+            }
+        }
+        offset += line.len();
+    }
+    start.map(|(heading, body)| (heading, body, content.len()))
+}
+
+/// The body of the section of `version`, trimmed.
+fn section(content: &str, version: &str) -> Option<String> {
+    let (_, body, end) = section_range(content, version)?;
+    Some(content[body..end].trim().to_string())
+}
+
+fn remove_section(content: &str, version: &str) -> String {
+    match section_range(content, version) {
+        Some((heading, _, end)) => format!("{}{}", &content[..heading], &content[end..]),
+        None => content.to_string(),
+    }
+}
+
+/// Inserts `entry` above the first version section, below the header.
+fn insert(content: &str, entry: &str) -> String {
+    let at = content.match_indices("## ").find(|(i, _)| *i == 0 || content[..*i].ends_with('\n')).map(|(i, _)| i);
+    let entry = format!("{}\n\n", entry.trim_end());
+    match at {
+        Some(at) => format!("{}{}{}", &content[..at], entry, &content[at..]),
+        None => format!("{}\n\n{}", content.trim_end(), entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(subject: &str, body: &str) -> Commit {
+        Commit { subject: subject.into(), body: body.into() }
+    }
+
+    #[test]
+    fn test_parse_conventional() {
+        assert_eq!(
+            parse_conventional("feat(api)!: drop v1 routes"),
+            Some(Conventional {
+                kind: "feat",
+                scope: Some("api"),
+                breaking: true,
+                description: "drop v1 routes"
+            })
+        );
+        assert_eq!(
+            parse_conventional("fix: typo").map(|c| (c.kind, c.scope)),
+            Some(("fix", None))
+        );
+        assert_eq!(parse_conventional("Update README"), None);
+        assert_eq!(parse_conventional("Merge branch 'main': conflicts"), None);
+    }
+
+    #[test]
+    fn test_render_notes() {
+        let commits = [
+            commit("feat: add retries", ""),
+            commit("chore: bump deps", ""),
+            commit("fix(io): close files", "BREAKING CHANGE: handles are no longer reused"),
+        ];
+        assert_eq!(
+            render_notes(&commits),
+            "### Breaking Changes\n\n- **io:** close files\n\n\
+             ### Features\n\n- add retries\n\n\
+             ### Bug Fixes\n\n- **io:** close files\n\n"
+        );
+        assert_eq!(
+            render_notes(&[commit("Initial import", "")]),
+            "### Changes\n\n- Initial import\n\n"
+        );
+        assert_eq!(render_notes(&[]), "");
+    }
+
+    #[test]
+    fn test_sections() {
+        let content =
+            "# Changelog\n\n## [Unreleased]\n\n- next\n\n## [1.1.0] - 2026-01-02\n\n- one\n\n## v1.0.0\n\n- first\n";
+        assert_eq!(section(content, "1.1.0").as_deref(), Some("- one"));
+        assert_eq!(section(content, "1.0.0").as_deref(), Some("- first"));
+        assert_eq!(section(content, UNRELEASED).as_deref(), Some("- next"));
+        assert_eq!(section(content, "1.1"), None);
+
+        let updated = insert(
+            &remove_section(content, UNRELEASED),
+            "## [1.2.0] - 2026-03-04\n\n- two\n",
+        );
+        assert_eq!(
+            updated,
+            "# Changelog\n\n## [1.2.0] - 2026-03-04\n\n- two\n\n## [1.1.0] - 2026-01-02\n\n- one\n\n## v1.0.0\n\n- first\n"
+        );
+        assert_eq!(insert(HEADER, "## [0.1.0]\n"), format!("{}\n## [0.1.0]\n\n", HEADER));
+    }
+}
@@ -1,55 +1,190 @@
-use anyhow::{Context, Result};
+use crate::project::{CACHE_DIR, Project};
+use anyhow::Result;
 use colored::Colorize;
 use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
-pub fn clean_cache() -> Result<()> {
-    println!("{}", "🧹 Cleaning Vayload cache and artifacts...".bold().cyan());
-    println!();
+use crate::i18n::t;
+use crate::policy::POLICY_FILE;
+use crate::reporter::Reporter;
+use crate::utils::format_bytes;
 
-    let mut cleaned_items: Vec<(String, String)> = Vec::new();
-    let current_dir = std::env::current_dir().context("Failed to get current directory")?;
+/// Cache directory used by older releases, cleaned along with `.vk/`.
+const LEGACY_CACHE_DIR: &str = ".vkcache";
 
-    let paths_to_clean =
-        vec![(".vk", "Cache directory"), ("target", "Build artifacts"), ("node_modules", "Node modules")];
+pub struct CleanOptions {
+    pub cache: bool,
+    pub artifacts: bool,
+    pub lockfile: bool,
+    pub all: bool,
+    pub dry_run: bool,
+    /// Delete the lockfile without asking.
+    pub yes: bool,
+}
 
-    for (path_name, description) in paths_to_clean {
-        let path = current_dir.join(path_name);
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// Everything under `.vk/` except packed archives and the policy file.
+    Cache,
+    /// Archives written by `vk pack` into `.vk/`.
+    Artifacts,
+    Lockfile,
+}
 
-        if path.exists() {
-            match fs::remove_dir_all(&path) {
-                Ok(_) => {
-                    cleaned_items.push((path_name.to_string(), description.to_string()));
-                    println!("{} Removed {}", "✓".green(), path_name.cyan());
-                },
-                Err(e) => {
-                    println!("{} Failed to remove {}: {}", "⚠".yellow(), path_name.cyan(), e);
-                },
-            }
+impl Target {
+    fn label(self) -> String {
+        match self {
+            Target::Cache => t!("clean.target_cache"),
+            Target::Artifacts => t!("clean.target_artifacts"),
+            Target::Lockfile => t!("clean.target_lockfile"),
         }
     }
 
-    let lockfile = current_dir.join("vayload.lock");
-    if lockfile.exists() {
-        if let Err(e) = fs::remove_file(&lockfile) {
-            println!("{} Failed to remove lockfile: {}", "⚠".yellow(), e);
-        } else {
-            cleaned_items.push(("vayload.lock".to_string(), "Lock file".to_string()));
-            println!("{}", "✓ Removed vaload.lock".green());
+    /// The existing paths this target would remove. Only paths vk itself
+    /// creates are listed, never directories other tooling may own.
+    fn paths(self, project: &Project) -> Vec<PathBuf> {
+        let cache_entries = || {
+            fs::read_dir(project.cache_dir())
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .collect::<Vec<_>>()
+        };
+
+        let mut paths = match self {
+            Target::Cache => {
+                // The project's permission policy is configuration, not cache
+                let mut paths: Vec<_> =
+                    cache_entries().into_iter().filter(|p| !is_archive(p) && !p.ends_with(POLICY_FILE)).collect();
+                paths.push(project.root().join(LEGACY_CACHE_DIR));
+                paths
+            },
+            Target::Artifacts => cache_entries().into_iter().filter(|p| is_archive(p)).collect(),
+            Target::Lockfile => vec![project.lockfile_path()],
+        };
+        paths.retain(|p| p.symlink_metadata().is_ok());
+        paths.sort();
+        paths
+    }
+}
+
+/// Removes what vk keeps in the project: the cache, packed archives and, on
+/// request, the lockfile. Cleans the cache and archives when no target is
+/// given.
+pub fn clean_cache(project: &Project, options: &CleanOptions, reporter: &dyn Reporter) -> Result<()> {
+    reporter.title(&t!("clean.title"));
+
+    let mut targets = Vec::new();
+    if options.all || options.cache || !(options.artifacts || options.lockfile) {
+        targets.push(Target::Cache);
+    }
+    if options.all || options.artifacts || !(options.cache || options.lockfile) {
+        targets.push(Target::Artifacts);
+    }
+    if options.all || options.lockfile {
+        targets.push(Target::Lockfile);
+    }
+
+    let mut total = 0u64;
+    let mut cleaned = 0usize;
+
+    for target in targets {
+        let paths = target.paths(project);
+        if paths.is_empty() {
+            reporter.detail(&t!("clean.target_empty", target = target.label()));
+            continue;
         }
+
+        if target == Target::Lockfile && !options.dry_run && !options.yes && !confirm_lockfile(reporter)? {
+            continue;
+        }
+
+        let mut freed = 0u64;
+        for path in &paths {
+            let relative = path.strip_prefix(project.root()).unwrap_or(path).display().to_string();
+            let size = disk_size(path);
+
+            if options.dry_run {
+                reporter.info(&t!(
+                    "clean.would_remove",
+                    path = relative.cyan(),
+                    size = format_bytes(size as usize)
+                ));
+            } else if let Err(e) = remove(path) {
+                reporter.warn(&t!("clean.remove_failed", path = relative.cyan(), error = e));
+                continue;
+            } else {
+                reporter.success(&t!(
+                    "clean.removed",
+                    path = relative.cyan(),
+                    size = format_bytes(size as usize)
+                ));
+            }
+
+            freed += size;
+            cleaned += 1;
+        }
+
+        reporter.detail(&t!(
+            "clean.target_freed",
+            target = target.label(),
+            size = format_bytes(freed as usize)
+        ));
+        total += freed;
     }
 
-    println!();
+    // Leave no empty `.vk/` behind
+    if !options.dry_run {
+        let _ = fs::remove_dir(project.root().join(CACHE_DIR));
+    }
 
-    if cleaned_items.is_empty() {
-        println!("{} Nothing to clean", "📭".yellow());
+    if cleaned == 0 {
+        reporter.info(&t!("clean.nothing"));
+    } else if options.dry_run {
+        reporter.done(&t!("clean.dry_run", size = format_bytes(total as usize).bold()));
     } else {
-        let total_size: usize = cleaned_items.iter().len();
-        println!(
-            "{} Cleaned {} item(s)",
-            "✅".green(),
-            total_size.to_string().green().bold()
-        );
+        reporter.done(&t!("clean.done", size = format_bytes(total as usize).bold()));
     }
 
     Ok(())
 }
+
+/// Deleting the lockfile unpins every dependency, so it needs a yes from the
+/// user; without a terminal it takes `--yes`.
+fn confirm_lockfile(reporter: &dyn Reporter) -> Result<bool> {
+    if !reporter.is_interactive() {
+        reporter.warn(&t!("clean.lockfile_needs_yes"));
+        return Ok(false);
+    }
+
+    let confirmed = reporter.confirm(&t!("clean.confirm_lockfile"))?;
+    if !confirmed {
+        reporter.detail(&t!("clean.lockfile_kept"));
+    }
+    Ok(confirmed)
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|e| e == "zip")
+}
+
+/// Bytes taken by the files under `path`, without following symlinks.
+fn disk_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
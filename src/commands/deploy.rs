@@ -0,0 +1,157 @@
+use anyhow::Result;
+use colored::Colorize;
+use reqwest::blocking::multipart::Form;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::commands::publish::archive_spec;
+use crate::error::VkError;
+use crate::http_client::{HttpClient, UploadProgress};
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::{create_zip, format_bytes, sha256_hex};
+
+const DEFAULT_HEALTH_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct DeployOptions<'a> {
+    /// Name of the environment, from the `[deploy.<name>]` table.
+    pub environment: &'a str,
+    /// Go back to the version deployed before the current one instead of
+    /// uploading.
+    pub rollback: bool,
+    pub allow_path_deps: bool,
+    /// How long the host may take to report the plugin healthy.
+    pub health_timeout: Option<Duration>,
+}
+
+/// What the host answers to a deploy or a rollback.
+#[derive(Debug, Deserialize)]
+struct Deployed {
+    version: String,
+    #[serde(default)]
+    previous: Option<String>,
+}
+
+/// State of a plugin on the host.
+#[derive(Debug, Deserialize)]
+struct Health {
+    status: String,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Packs the plugin and uploads it to the host `http_client` points at, or
+/// rolls it back to its previous version, then waits for the host to report
+/// it healthy.
+pub fn deploy(
+    project: &Project,
+    options: &DeployOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let manifest = project.read_manifest()?;
+    let name = manifest.name.clone();
+
+    let deployed = if options.rollback {
+        reporter.title(&t!(
+            "deploy.rolling_back",
+            name = name.cyan(),
+            environment = options.environment.cyan(),
+            url = http_client.base_url().bright_black()
+        ));
+        http_client.post::<Deployed, _>(&format!("/plugins/{}/rollback", name), &json!({}))?
+    } else {
+        reporter.title(&t!(
+            "deploy.title",
+            name = name.cyan(),
+            version = manifest.version.yellow(),
+            environment = options.environment.cyan(),
+            url = http_client.base_url().bright_black()
+        ));
+        let spec = archive_spec(project, &manifest, options.allow_path_deps, reporter)?;
+        let archive = create_zip(project.root(), &spec, reporter)?;
+
+        reporter.step(&t!("deploy.uploading", size = format_bytes(archive.data.len())));
+        let progress = UploadProgress::default();
+        let form = Form::new()
+            .part(
+                "file",
+                progress.part(archive.data.clone()).file_name(format!("{}.zip", name)).mime_str("application/zip")?,
+            )
+            .text("name", name.clone())
+            .text("version", manifest.version.clone())
+            .text("checksum", sha256_hex(&archive.data));
+        http_client.post_multipart::<Deployed>("/plugins", form, HeaderMap::new(), &progress)?
+    };
+
+    match &deployed.previous {
+        Some(previous) => reporter.success(&t!(
+            "deploy.accepted_replacing",
+            version = deployed.version.yellow(),
+            previous = previous.bright_black()
+        )),
+        None => reporter.success(&t!("deploy.accepted", version = deployed.version.yellow())),
+    }
+
+    wait_healthy(&name, &deployed.version, options, http_client, reporter)?;
+    reporter.done(&t!(
+        "deploy.healthy",
+        name = name.cyan(),
+        version = deployed.version.yellow(),
+        environment = options.environment.cyan()
+    ));
+    Ok(())
+}
+
+/// Polls the plugin's health until the host reports `version` healthy. Fails
+/// when it reports the plugin failed, or when the timeout runs out.
+fn wait_healthy(
+    name: &str,
+    version: &str,
+    options: &DeployOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let timeout = options.health_timeout.unwrap_or(DEFAULT_HEALTH_TIMEOUT);
+    let unhealthy = |reason: String| VkError::DeployUnhealthy {
+        plugin: format!("{}@{}", name, version),
+        environment: options.environment.to_string(),
+        reason,
+    };
+
+    reporter.step(&t!("deploy.waiting", seconds = timeout.as_secs()));
+    let started = Instant::now();
+    let mut last_status = String::new();
+    loop {
+        let health: Health = http_client.get(&format!("/plugins/{}/health", name))?;
+        let current = health.version.as_deref().is_none_or(|v| v == version);
+
+        match health.status.as_str() {
+            "healthy" if current => return Ok(()),
+            "failed" | "unhealthy" if current => {
+                return Err(unhealthy(health.message.unwrap_or(health.status)).into());
+            },
+            status if status != last_status => {
+                reporter.detail(&t!("deploy.status", status = status));
+                last_status = status.to_string();
+            },
+            _ => {},
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(unhealthy(t!(
+                "deploy.timed_out",
+                seconds = timeout.as_secs(),
+                status = health.status
+            ))
+            .into());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::commands::install::{download_plugin, verify_checksum};
+use crate::diff;
+use crate::dist_tag;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::extract_zip;
+
+/// Lines of context around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// One side of the comparison.
+pub enum DiffSide {
+    Published(Option<String>),
+    Local(PathBuf),
+}
+
+/// Compares two versions of `package` file by file and prints a unified diff
+/// of the text files that changed.
+pub fn diff_package(
+    package: &str,
+    from: DiffSide,
+    to: DiffSide,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let (from_dir, from_label, _from_tmp) = materialize(package, from, http_client, reporter)?;
+    let (to_dir, to_label, _to_tmp) = materialize(package, to, http_client, reporter)?;
+
+    reporter.title(&t!(
+        "diff.title",
+        package = package.cyan(),
+        from = from_label.yellow(),
+        to = to_label.green()
+    ));
+
+    let old = list_files(&from_dir);
+    let new = list_files(&to_dir);
+
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let (mut added, mut removed, mut modified) = (Vec::new(), Vec::new(), Vec::new());
+    for path in paths {
+        match (old.get(path), new.get(path)) {
+            (None, Some(_)) => added.push(path),
+            (Some(_), None) => removed.push(path),
+            (Some(a), Some(b)) if fs::read(a)? != fs::read(b)? => modified.push(path),
+            _ => {},
+        }
+    }
+
+    if added.is_empty() && removed.is_empty() && modified.is_empty() {
+        reporter.done(&t!("diff.identical"));
+        return Ok(());
+    }
+
+    for path in &added {
+        reporter.output(&format!("{} {}", "A".green().bold(), path));
+    }
+    for path in &removed {
+        reporter.output(&format!("{} {}", "D".red().bold(), path));
+    }
+    for path in &modified {
+        reporter.output(&format!("{} {}", "M".yellow().bold(), path));
+    }
+
+    for path in added.iter().chain(&removed).chain(&modified) {
+        let before = old.get(*path).map(fs::read).transpose()?.unwrap_or_default();
+        let after = new.get(*path).map(fs::read).transpose()?.unwrap_or_default();
+        print_file_diff(path, &before, &after, reporter);
+    }
+
+    reporter.info(&t!(
+        "diff.summary",
+        files = added.len() + removed.len() + modified.len(),
+        added = added.len(),
+        removed = removed.len(),
+        modified = modified.len()
+    ));
+
+    Ok(())
+}
+
+/// The installed copy of `package` in the project, for `vk diff <package>`.
+pub fn installed_dir(project: &Project, package: &str, plugins_dir: &str) -> Result<PathBuf> {
+    let path = project.root().join(plugins_dir).join(package);
+    if !path.is_dir() {
+        anyhow::bail!(t!("diff.not_installed", package = package));
+    }
+    Ok(path)
+}
+
+/// Gets a directory with the contents of `side`, downloading it into a
+/// temporary directory (kept alive by the returned guard) when needed.
+fn materialize(
+    package: &str,
+    side: DiffSide,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<(PathBuf, String, Option<tempfile::TempDir>)> {
+    match side {
+        DiffSide::Local(path) => {
+            let label = path.display().to_string();
+            Ok((path, label, None))
+        },
+        DiffSide::Published(version) => {
+            let version = match version {
+                Some(tag) if dist_tag::is_dist_tag(&tag) => Some(dist_tag::resolve(package, &tag, http_client)?),
+                other => other,
+            };
+
+            let (data, meta) = download_plugin(package, version.as_deref(), http_client, reporter)?;
+            verify_checksum(&data, &meta, reporter)?;
+
+            let tmp =
+                tempfile::Builder::new().prefix("vk-diff-").tempdir().with_context(|| t!("diff.tempdir_failed"))?;
+            extract_zip(&data, tmp.path(), false).with_context(|| t!("install.extract_failed"))?;
+
+            Ok((tmp.path().to_path_buf(), meta.version, Some(tmp)))
+        },
+    }
+}
+
+/// Files under `dir` keyed by their `/`-separated relative path.
+fn list_files(dir: &Path) -> BTreeMap<String, PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(dir).ok()?.to_string_lossy().replace('\\', "/");
+            Some((relative, e.into_path()))
+        })
+        .collect()
+}
+
+fn print_file_diff(path: &str, before: &[u8], after: &[u8], reporter: &dyn Reporter) {
+    reporter.output(&format!("diff a/{} b/{}", path, path).bold().to_string());
+
+    let (Ok(before), Ok(after)) = (std::str::from_utf8(before), std::str::from_utf8(after)) else {
+        reporter.output(&t!("diff.binary"));
+        return;
+    };
+
+    reporter.output(&format!("--- a/{}", path).red().to_string());
+    reporter.output(&format!("+++ b/{}", path).green().to_string());
+
+    for line in diff::unified(before, after, CONTEXT_LINES).lines() {
+        let line = if line.starts_with("@@") {
+            line.cyan().to_string()
+        } else if line.starts_with('+') {
+            line.green().to_string()
+        } else if line.starts_with('-') {
+            line.red().to_string()
+        } else {
+            line.to_string()
+        };
+        reporter.output(&line);
+    }
+}
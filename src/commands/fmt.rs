@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::json5::document::{Container, KeyQuotes, Node, Quotes, Style};
+use crate::encoding::json5::{self, Document};
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::manifest::{MANIFEST_FIELDS, MANIFEST_FILENAME, VKIGNORE_FILENAME};
+use crate::reporter::Reporter;
+use crate::utils::FilteredWalker;
+
+/// Formatting settings, read from the closest one up from each file.
+pub const CONFIG_FILENAME: &str = ".vkfmt.json5";
+
+/// Manifest sections vk writes sorted by key.
+const SORTED_SECTIONS: &[&str] = &[
+    "dependencies",
+    "dev_dependencies",
+    "host_dependencies",
+    "optional_dependencies",
+    "features",
+    "overrides",
+    "scripts",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyOrder {
+    /// Keys stay where they are.
+    Preserve,
+    /// Keys of every object sorted.
+    Sorted,
+    /// Manifests in the order vk writes them; other files as they are.
+    Manifest,
+}
+
+/// The contents of `.vkfmt.json5`. The defaults are the layout vk writes
+/// manifests in, so formatting a manifest vk wrote changes nothing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FmtConfig {
+    /// Spaces per level.
+    pub indent: usize,
+    /// Indents with tabs instead of spaces.
+    pub tabs: bool,
+    pub quotes: Quotes,
+    pub quote_keys: KeyQuotes,
+    pub trailing_commas: bool,
+    pub key_order: KeyOrder,
+}
+
+impl Default for FmtConfig {
+    fn default() -> Self {
+        FmtConfig {
+            indent: 4,
+            tabs: false,
+            quotes: Quotes::Double,
+            quote_keys: KeyQuotes::AsNeeded,
+            trailing_commas: false,
+            key_order: KeyOrder::Preserve,
+        }
+    }
+}
+
+impl FmtConfig {
+    /// The settings of the closest `.vkfmt.json5` in `dir` or its parents.
+    pub fn find(dir: &Path) -> Result<Self> {
+        let Some(path) = dir.ancestors().map(|d| d.join(CONFIG_FILENAME)).find(|p| p.is_file()) else {
+            return Ok(FmtConfig::default());
+        };
+        let content = fs::read_to_string(&path).with_context(|| t!("common.read_failed", path = path.display()))?;
+        json5::from_str(&content).with_context(|| t!("fmt.config_invalid", path = path.display()))
+    }
+
+    pub fn style(&self) -> Style {
+        Style {
+            indent: if self.tabs {
+                "\t".to_string()
+            } else {
+                " ".repeat(self.indent)
+            },
+            quotes: self.quotes,
+            key_quotes: self.quote_keys,
+            trailing_commas: self.trailing_commas,
+        }
+    }
+
+    /// Puts the keys of `document` in the configured order. `manifest` is
+    /// whether the document is a plugin manifest.
+    pub fn order_keys(&self, document: &mut Document, manifest: bool) {
+        match (self.key_order, &mut document.root.value) {
+            (KeyOrder::Sorted, root) => sort_all(root),
+            (KeyOrder::Manifest, Node::Object(root)) if manifest => {
+                let rank =
+                    |name: &str| MANIFEST_FIELDS.iter().position(|k| *k == name).unwrap_or(MANIFEST_FIELDS.len());
+                root.items.sort_by_key(|item| item.key.as_ref().map_or(MANIFEST_FIELDS.len(), |k| rank(&k.name)));
+                for item in &mut root.items {
+                    if let (Some(key), Node::Object(section)) = (&item.key, &mut item.value)
+                        && SORTED_SECTIONS.contains(&key.name.as_str())
+                    {
+                        sort_keys(section);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn sort_keys(object: &mut Container) {
+    object.items.sort_by(|a, b| match (&a.key, &b.key) {
+        (Some(a), Some(b)) => a.name.cmp(&b.name),
+        _ => Ordering::Equal,
+    });
+}
+
+fn sort_all(node: &mut Node) {
+    match node {
+        Node::Object(object) => {
+            sort_keys(object);
+            object.items.iter_mut().for_each(|item| sort_all(&mut item.value));
+        },
+        Node::Array(array) => array.items.iter_mut().for_each(|item| sort_all(&mut item.value)),
+        _ => {},
+    }
+}
+
+/// Formats `paths`: JSON5 files, and the `.json5` files under directories
+/// that no ignore file excludes. With `check`, files are left as they are
+/// and the command fails when any would change.
+pub fn format_files(paths: &[PathBuf], check: bool, reporter: &dyn Reporter) -> Result<()> {
+    let files = collect_files(paths)?;
+
+    let (mut changed, mut unparsable) = (0, 0);
+    for file in &files {
+        let source = fs::read_to_string(file).with_context(|| t!("common.read_failed", path = file.display()))?;
+        let mut document = match Document::parse(&source) {
+            Ok(document) => document,
+            Err(e) => {
+                let at = e.offset().map(|offset| line_and_column(&source, offset)).unwrap_or_default();
+                reporter.error(&format!("{}{}: {}", file.display().to_string().cyan(), at, e));
+                unparsable += 1;
+                continue;
+            },
+        };
+
+        let dir = file.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let config = FmtConfig::find(dir)?;
+        let is_manifest = file.file_name().is_some_and(|name| name == MANIFEST_FILENAME);
+        config.order_keys(&mut document, is_manifest);
+
+        let formatted = document.format(&config.style());
+        if formatted == source {
+            continue;
+        }
+        changed += 1;
+        if check {
+            reporter.warn(&t!("fmt.would_format", path = file.display().to_string().cyan()));
+        } else {
+            fs::write(file, formatted).with_context(|| t!("common.write_failed", path = file.display()))?;
+            reporter.step(&t!("fmt.formatted", path = file.display().to_string().cyan()));
+        }
+    }
+
+    if unparsable > 0 {
+        return Err(VkError::ManifestInvalid(t!("fmt.unparsable", count = unparsable)).into());
+    }
+    if check && changed > 0 {
+        return Err(VkError::Unformatted { count: changed }.into());
+    }
+    if check {
+        reporter.success(&t!("fmt.check_ok", count = files.len()));
+    } else {
+        reporter.done(&t!("fmt.done", changed = changed, count = files.len()));
+    }
+    Ok(())
+}
+
+/// The files `paths` name, directories expanded to their `.json5` files.
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            if !path.exists() {
+                anyhow::bail!(t!("fmt.not_found", path = path.display()));
+            }
+            files.push(path.clone());
+            continue;
+        }
+        let mut walker = FilteredWalker::new(path);
+        walker.add_ignore_file_name(".gitignore").add_ignore_file_name(VKIGNORE_FILENAME);
+        files.extend(
+            walker
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .filter(|file| file.extension().is_some_and(|ext| ext == "json5")),
+        );
+    }
+    Ok(files)
+}
+
+/// `:line:column` of byte `offset` of `source`, both from 1.
+fn line_and_column(source: &str, offset: usize) -> String {
+    let Some(before) = source.get(..offset) else {
+        return String::new();
+    };
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    format!(
+        ":{}:{}",
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_key_order() {
+        let source = "{\n    // deps\n    dependencies: { b: \"1\", a: \"2\" },\n    custom: 1,\n    name: \"x\"\n}\n";
+        let config = FmtConfig { key_order: KeyOrder::Manifest, ..FmtConfig::default() };
+
+        let mut document = Document::parse(source).unwrap();
+        config.order_keys(&mut document, true);
+        assert_eq!(
+            document.format(&config.style()),
+            "{\n    name: \"x\",\n    // deps\n    dependencies: {\n        a: \"2\",\n        b: \"1\"\n    },\n    custom: 1\n}\n"
+        );
+
+        // Only manifests have an order
+        let mut document = Document::parse(source).unwrap();
+        config.order_keys(&mut document, false);
+        assert!(document.format(&config.style()).starts_with("{\n    // deps\n    dependencies"));
+    }
+}
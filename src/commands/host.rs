@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config;
+use crate::encoding::json5;
+use crate::error::VkError;
+use crate::host::{self, Running};
+use crate::http_client::{Download, HttpClient};
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::scripts;
+use crate::utils::{format_bytes, sha256_hex};
+
+const LOG_FILENAME: &str = "host.log";
+
+/// Downloads the host runtime `version` (the latest one when omitted) for
+/// this platform into the hosts directory. The first one installed becomes
+/// the default version.
+pub fn install(version: Option<&str>, force: bool, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    if let Some(version) = version {
+        if resolver::parse_version(version).is_none() {
+            anyhow::bail!(t!("host.invalid_version", version = version));
+        }
+        if !force && host::binary_path(version)?.is_file() {
+            reporter.info(&t!("host.already_installed", version = version.cyan()));
+            return Ok(());
+        }
+    }
+
+    let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+    reporter.title(&t!(
+        "host.installing",
+        version = version.unwrap_or("latest").cyan(),
+        platform = platform
+    ));
+
+    let path = format!("/host/releases/{}/{}", version.unwrap_or("latest"), platform);
+    let Download { headers, response } = http_client.download(&path)?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    let Some(version) = header("X-Host-Version").or_else(|| version.map(String::from)) else {
+        anyhow::bail!(t!("host.version_unknown"));
+    };
+    // The version names a directory the binary is written to
+    if resolver::parse_version(&version).is_none() {
+        anyhow::bail!(t!("host.invalid_version", version = version));
+    }
+    // Checked before the download, so nothing unverifiable is fetched
+    let Some(expected) = header("X-Checksum") else {
+        anyhow::bail!(t!("host.checksum_missing", version = version));
+    };
+
+    let pb = reporter.progress(response.content_length(), &t!("install.downloading"));
+    let mut binary = Vec::new();
+    let mut reader = response;
+    let mut chunk = vec![0u8; 32 * 1024];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                binary.extend_from_slice(&chunk[..n]);
+                pb.inc(n as u64);
+            },
+            Err(e) => return Err(http_client.read_error(e).into()),
+        }
+    }
+    pb.finish();
+
+    let actual = sha256_hex(&binary);
+    if !actual.eq_ignore_ascii_case(&expected) {
+        return Err(VkError::ChecksumMismatch { package: format!("vayload@{}", version), expected, actual }.into());
+    }
+    reporter.success(&t!("install.checksum_verified", checksum = expected.bright_black()));
+
+    let target = host::binary_path(&version)?;
+    write_binary(&target, &binary)?;
+    reporter.done(&t!(
+        "host.installed",
+        version = version.cyan(),
+        size = format_bytes(binary.len()),
+        path = target.display().to_string().bright_black()
+    ));
+
+    if host::pinned(None).is_none() {
+        host::pin(None, &version)?;
+        reporter.detail(&t!("host.default_set", version = version.cyan()));
+    }
+    Ok(())
+}
+
+/// Writes the runtime next to its final path, then moves it there, so an
+/// interrupted download never leaves a truncated binary behind.
+fn write_binary(target: &Path, binary: &[u8]) -> Result<()> {
+    let dir = target.parent().expect("runtime binaries live in a version directory");
+    fs::create_dir_all(dir).with_context(|| t!("common.write_failed", path = dir.display()))?;
+
+    let partial = target.with_extension("partial");
+    fs::write(&partial, binary).with_context(|| t!("common.write_failed", path = partial.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&partial, fs::Permissions::from_mode(0o755))
+            .with_context(|| t!("common.write_failed", path = partial.display()))?;
+    }
+    fs::rename(&partial, target).with_context(|| t!("common.write_failed", path = target.display()))
+}
+
+/// Lists the installed runtimes, marking the one commands of the current
+/// project use.
+pub fn list(project: Option<&Project>, reporter: &dyn Reporter) -> Result<()> {
+    let installed = host::installed();
+    if installed.is_empty() {
+        reporter.info(&t!("host.none_installed"));
+        return Ok(());
+    }
+
+    let active = host::managed_runtime(project);
+    let pinned = host::pinned(project);
+    reporter.title(&t!(
+        "host.list_title",
+        dir = config::hosts_dir()?.display().to_string().bright_black()
+    ));
+    for version in installed {
+        let version = version.to_string();
+        let marker = if active.as_deref() == Some(host::binary_path(&version)?.as_path()) {
+            "*"
+        } else {
+            " "
+        };
+        let note = if pinned.as_deref() == Some(version.as_str()) {
+            t!("host.pinned_note").bright_black().to_string()
+        } else {
+            String::new()
+        };
+        reporter.output(format!("{} {} {}", marker.green(), version.cyan(), note).trim_end());
+    }
+    if let Some(pinned) = pinned.filter(|v| !host::binary_path(v).is_ok_and(|path| path.is_file())) {
+        reporter.warn(&t!("host.pin_not_installed", version = pinned));
+    }
+    Ok(())
+}
+
+/// Pins the runtime version of the project, or the default version when
+/// `project` is None.
+pub fn pin(project: Option<&Project>, version: &str, reporter: &dyn Reporter) -> Result<()> {
+    if resolver::parse_version(version).is_none() {
+        anyhow::bail!(t!("host.invalid_version", version = version));
+    }
+    host::pin(project, version)?;
+
+    let path = host::pin_path(project)?;
+    reporter.done(&t!(
+        "host.pinned",
+        version = version.cyan(),
+        path = path.display().to_string().bright_black()
+    ));
+    if !host::binary_path(version)?.is_file() {
+        reporter.warn(&t!("host.pin_not_installed", version = version));
+    }
+    Ok(())
+}
+
+/// Starts the project's runtime in the background, serving the plugins
+/// directory on `port`. Its output goes to `.vk/host.log`.
+pub fn start(project: &Project, port: u16, plugins_dir: &str, reporter: &dyn Reporter) -> Result<()> {
+    if let Some(running) = host::running(project).filter(|r| is_alive(r.pid)) {
+        reporter.info(&t!("host.already_running", pid = running.pid, port = running.port));
+        return Ok(());
+    }
+
+    if std::env::var_os(scripts::HOST_RUNTIME_VAR).is_none()
+        && let Some(version) = host::pinned(Some(project))
+        && !host::binary_path(&version)?.is_file()
+    {
+        anyhow::bail!(t!("host.pin_not_installed", version = version));
+    }
+    let runtime = scripts::host_runtime();
+    let manifest = project.read_manifest()?;
+
+    let cache_dir = project.cache_dir();
+    fs::create_dir_all(&cache_dir).with_context(|| t!("common.write_failed", path = cache_dir.display()))?;
+    let log_path = cache_dir.join(LOG_FILENAME);
+    let log = fs::File::create(&log_path).with_context(|| t!("common.write_failed", path = log_path.display()))?;
+
+    let mut command = Command::new(&runtime);
+    command
+        .arg("serve")
+        .arg("--port")
+        .arg(port.to_string())
+        .arg("--plugins")
+        .arg(project.root().join(plugins_dir))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    scripts::configure(&mut command, project, &manifest);
+
+    let mut child = match command.spawn() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!(t!("host.runtime_missing", runtime = runtime))
+        },
+        result => result.with_context(|| t!("scripts.spawn_failed", name = runtime))?,
+    };
+
+    // A runtime that cannot bind the port or read the plugins quits at once
+    std::thread::sleep(Duration::from_millis(300));
+    if let Some(status) = child.try_wait()? {
+        return Err(VkError::ScriptFailed { name: format!("{} serve", runtime), status: status.code() })
+            .with_context(|| t!("host.see_log", path = log_path.display()));
+    }
+
+    let running = Running { pid: child.id(), port, runtime };
+    let state_path = host::running_path(project);
+    fs::write(&state_path, json5::to_string_pretty(&running)?)
+        .with_context(|| t!("common.write_failed", path = state_path.display()))?;
+
+    reporter.done(&t!(
+        "host.started",
+        port = port.to_string().cyan(),
+        pid = running.pid,
+        log = log_path.display().to_string().bright_black()
+    ));
+    Ok(())
+}
+
+/// Stops the runtime `vk host start` left running for the project.
+pub fn stop(project: &Project, reporter: &dyn Reporter) -> Result<()> {
+    let state_path = host::running_path(project);
+    let Some(running) = host::running(project) else {
+        reporter.info(&t!("host.not_running"));
+        return Ok(());
+    };
+
+    if is_alive(running.pid) {
+        kill(running.pid).with_context(|| t!("host.stop_failed", pid = running.pid))?;
+        reporter.done(&t!("host.stopped", pid = running.pid, port = running.port));
+    } else {
+        reporter.info(&t!("host.not_running"));
+    }
+    fs::remove_file(&state_path).with_context(|| t!("common.write_failed", path = state_path.display()))
+}
+
+fn is_alive(pid: u32) -> bool {
+    if cfg!(windows) {
+        let filter = format!("PID eq {}", pid);
+        Command::new("tasklist")
+            .args(["/FI", &filter, "/NH"])
+            .output()
+            .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+}
+
+fn kill(pid: u32) -> Result<()> {
+    let status = if cfg!(windows) {
+        Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]).status()?
+    } else {
+        Command::new("kill").arg(pid.to_string()).status()?
+    };
+    if !status.success() {
+        anyhow::bail!(t!("host.stop_failed", pid = pid));
+    }
+    Ok(())
+}
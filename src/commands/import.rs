@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::encoding::json5;
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::manifest::{Dependency, GitSource, MANIFEST_FILENAME, PathSource, PluginManifest, Repository};
+use crate::project::LEGACY_MANIFEST_FILENAMES;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::resolver;
+
+/// `package.json` fields read by the import; others are reported.
+const MAPPED_FIELDS: &[&str] = &[
+    "name",
+    "displayName",
+    "version",
+    "description",
+    "license",
+    "keywords",
+    "homepage",
+    "repository",
+    "author",
+    "contributors",
+    "main",
+    "engines",
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "files",
+    "scripts",
+];
+
+pub struct ImportOptions {
+    /// Overwrite an existing `plugin.json5`.
+    pub force: bool,
+    /// Print the manifest instead of writing it.
+    pub dry_run: bool,
+}
+
+/// Writes a `plugin.json5` next to `source`, an npm `package.json` or a
+/// legacy manifest, mapping what has an equivalent and reporting the rest.
+/// Registry dependencies are kept only when the registry has them.
+pub fn import_manifest(
+    source: &Path,
+    options: &ImportOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let file_name = source.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    reporter.title(&t!("import.title", file = file_name.cyan()));
+
+    let dir = source.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let target = dir.join(MANIFEST_FILENAME);
+    if target.exists() && !options.force && !options.dry_run {
+        anyhow::bail!(t!("import.exists", path = target.display()));
+    }
+
+    let content = fs::read_to_string(source).with_context(|| t!("common.read_failed", path = source.display()))?;
+    let (manifest, unmapped) = if LEGACY_MANIFEST_FILENAMES.contains(&file_name) {
+        let manifest: PluginManifest =
+            json5::from_str(&content).with_context(|| t!("import.invalid", path = source.display()))?;
+        (manifest, 0)
+    } else {
+        let package: Map<String, Value> =
+            serde_json::from_str(&content).with_context(|| t!("import.invalid", path = source.display()))?;
+        let mut notes = Vec::new();
+        let mut manifest = map_package(&package, &mut notes);
+        check_registry(&mut manifest, http_client, &mut notes, reporter);
+        for note in &notes {
+            reporter.warn(note);
+        }
+        (manifest, notes.len())
+    };
+
+    let rendered = json5::to_string_pretty(&manifest)?;
+    if options.dry_run {
+        reporter.output(&rendered);
+    } else {
+        fs::write(&target, rendered).with_context(|| t!("manifest.write_failed"))?;
+        reporter.success(&t!(
+            "import.written",
+            path = target.display().to_string().bright_black()
+        ));
+    }
+
+    let dependencies = manifest.dependencies.len()
+        + manifest.dev_dependencies.as_ref().map_or(0, HashMap::len)
+        + manifest.optional_dependencies.len();
+    reporter.done(&t!("import.done", dependencies = dependencies, unmapped = unmapped));
+    Ok(())
+}
+
+/// Maps the fields of a `package.json` onto a manifest, noting in `notes`
+/// what could not be mapped.
+fn map_package(package: &Map<String, Value>, notes: &mut Vec<String>) -> PluginManifest {
+    let text = |field: &str| package.get(field).and_then(Value::as_str).map(str::to_string);
+    let mut manifest = PluginManifest::default();
+
+    let name = text("name").unwrap_or_default();
+    // The registry has no scopes
+    manifest.name = match name.strip_prefix('@').and_then(|n| n.split_once('/')) {
+        Some((_, bare)) => {
+            notes.push(t!("import.scope_dropped", from = name, to = bare));
+            bare.to_string()
+        },
+        None => name,
+    };
+    manifest.display_name = text("displayName").unwrap_or_else(|| display_name(&manifest.name));
+    manifest.version = text("version").unwrap_or(manifest.version);
+    manifest.description = text("description").unwrap_or_default();
+    manifest.license = match package.get("license") {
+        Some(Value::String(license)) => license.clone(),
+        Some(Value::Object(license)) => license.get("type").and_then(Value::as_str).unwrap_or_default().to_string(),
+        _ => manifest.license,
+    };
+    manifest.keywords = strings(package.get("keywords"));
+    manifest.homepage = text("homepage");
+    manifest.repository = package.get("repository").and_then(repository);
+    manifest.author = package.get("author").and_then(person).unwrap_or_default();
+    let contributors: Vec<String> = package
+        .get("contributors")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(person)
+        .collect();
+    manifest.contributors = (!contributors.is_empty()).then_some(contributors);
+
+    match text("main") {
+        Some(main) if main.ends_with(".lua") => manifest.main = main,
+        Some(main) => notes.push(t!("import.main_not_lua", main = main, default = manifest.main)),
+        None => {},
+    }
+
+    if let Some(engines) = package.get("engines").and_then(Value::as_object) {
+        for (engine, range) in engines {
+            let range = range.as_str().unwrap_or_default();
+            match (engine.as_str(), convert_range(range)) {
+                ("lua", Some(range)) => manifest.engines.lua = range,
+                ("vayload" | "host", Some(range)) => manifest.engines.host = range,
+                _ => notes.push(t!("import.unmapped_field", field = format!("engines.{}", engine))),
+            }
+        }
+    }
+
+    manifest.dependencies = dependencies(package.get("dependencies"), notes);
+    let dev = dependencies(package.get("devDependencies"), notes);
+    manifest.dev_dependencies = (!dev.is_empty()).then_some(dev);
+    manifest.optional_dependencies = dependencies(package.get("optionalDependencies"), notes);
+
+    if package.contains_key("files") {
+        manifest.files = Some(strings(package.get("files")));
+    }
+    if let Some(scripts) = package.get("scripts").and_then(Value::as_object) {
+        manifest.scripts = scripts
+            .iter()
+            .filter_map(|(name, command)| Some((name.clone(), command.as_str()?.to_string())))
+            .collect();
+    }
+
+    for field in package.keys().filter(|field| !MAPPED_FIELDS.contains(&field.as_str())) {
+        notes.push(t!("import.unmapped_field", field = field));
+    }
+    manifest
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// `my-plugin` as `My Plugin`.
+fn display_name(name: &str) -> String {
+    name.split(['-', '_', '.'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A person, written `Name <email> (url)` as npm also accepts it.
+fn person(value: &Value) -> Option<String> {
+    match value {
+        Value::String(person) => Some(person.clone()),
+        Value::Object(person) => {
+            let mut text = person.get("name")?.as_str()?.to_string();
+            if let Some(email) = person.get("email").and_then(Value::as_str) {
+                text.push_str(&format!(" <{}>", email));
+            }
+            if let Some(url) = person.get("url").and_then(Value::as_str) {
+                text.push_str(&format!(" ({})", url));
+            }
+            Some(text)
+        },
+        _ => None,
+    }
+}
+
+fn repository(value: &Value) -> Option<Repository> {
+    let (kind, url) = match value {
+        Value::String(url) => ("git", url.as_str()),
+        Value::Object(repository) => (
+            repository.get("type").and_then(Value::as_str).unwrap_or("git"),
+            repository.get("url")?.as_str()?,
+        ),
+        _ => return None,
+    };
+    Some(Repository { r#type: kind.to_string(), url: expand_shorthand(url) })
+}
+
+/// The URL of an npm repository shorthand: `github:user/repo`, `user/repo`,
+/// `gitlab:user/repo` or `bitbucket:user/repo`.
+fn expand_shorthand(url: &str) -> String {
+    let (host, path) = match url.split_once(':') {
+        Some(("github", path)) => ("github.com", path),
+        Some(("gitlab", path)) => ("gitlab.com", path),
+        Some(("bitbucket", path)) => ("bitbucket.org", path),
+        None if url.matches('/').count() == 1 && !url.starts_with(['.', '/', '@']) => ("github.com", url),
+        _ => return url.to_string(),
+    };
+    format!("https://{}/{}", host, path)
+}
+
+fn dependencies(value: Option<&Value>, notes: &mut Vec<String>) -> HashMap<String, Dependency> {
+    let mut mapped = HashMap::new();
+    for (name, spec) in value.and_then(Value::as_object).into_iter().flatten() {
+        let spec = spec.as_str().unwrap_or_default();
+        match convert_dependency(spec) {
+            Some(dependency) => {
+                mapped.insert(name.clone(), dependency);
+            },
+            None => notes.push(t!("import.unsupported_spec", name = name, spec = spec)),
+        }
+    }
+    mapped
+}
+
+/// The manifest entry for an npm dependency spec: a range, a dist-tag, a
+/// git URL or a `file:` path. Aliases, workspaces and ranges joined with
+/// `||` have no equivalent.
+fn convert_dependency(spec: &str) -> Option<Dependency> {
+    let spec = spec.trim();
+    if let Some(path) = spec.strip_prefix("file:") {
+        return Some(Dependency::Path(PathSource { path: path.to_string() }));
+    }
+    if spec.starts_with("npm:") || spec.starts_with("workspace:") || spec.contains("||") {
+        return None;
+    }
+
+    let is_git = spec.starts_with("git+") || spec.starts_with("git://") || spec.ends_with(".git");
+    let is_shorthand = ["github:", "gitlab:", "bitbucket:"].iter().any(|p| spec.starts_with(p))
+        || (spec.matches('/').count() == 1 && !spec.contains(':') && !spec.starts_with(['.', '/']));
+    if is_git || is_shorthand {
+        let (url, reference) = spec.split_once('#').map_or((spec, None), |(url, r)| (url, Some(r.to_string())));
+        let url = expand_shorthand(url.strip_prefix("git+").unwrap_or(url));
+        let is_commit = reference.as_ref().is_some_and(|r| r.len() >= 7 && r.chars().all(|c| c.is_ascii_hexdigit()));
+        let (rev, branch) = if is_commit {
+            (reference, None)
+        } else {
+            (None, reference)
+        };
+        return Some(Dependency::Git(GitSource { git: url, rev, branch, tag: None }));
+    }
+
+    convert_range(spec).map(Dependency::Version)
+}
+
+/// An npm range as vk writes it: comparators separated by commas rather
+/// than spaces, and hyphen ranges spelled out. Dist-tags are kept.
+fn convert_range(spec: &str) -> Option<String> {
+    let spec = spec.trim();
+    if spec.is_empty() || spec == "*" || spec == "x" || spec == "latest" {
+        return Some("*".to_string());
+    }
+    if spec.starts_with(|c: char| c.is_ascii_alphabetic()) && !spec.starts_with(['x', 'X']) {
+        return Some(spec.to_string());
+    }
+
+    let words: Vec<&str> = spec.split_whitespace().collect();
+    let converted = match words.as_slice() {
+        [from, "-", to] => format!(">={}, <={}", from, to),
+        _ => {
+            // `>= 1.0` is one comparator
+            let mut comparators: Vec<String> = Vec::new();
+            for word in words {
+                match comparators.last_mut() {
+                    Some(last) if last.chars().all(|c| "<>=~^".contains(c)) => last.push_str(word),
+                    _ => comparators.push(word.trim_end_matches(',').to_string()),
+                }
+            }
+            comparators.join(", ")
+        },
+    };
+    resolver::parse_req(&converted).map(|_| converted)
+}
+
+/// Drops the registry dependencies the registry does not have, noting them.
+/// Dependencies that could not be checked are kept.
+fn check_registry(
+    manifest: &mut PluginManifest,
+    http_client: &HttpClient,
+    notes: &mut Vec<String>,
+    reporter: &dyn Reporter,
+) {
+    let registry = RegistryClient::new(http_client);
+    let sections = [
+        Some(&mut manifest.dependencies),
+        manifest.dev_dependencies.as_mut(),
+        Some(&mut manifest.optional_dependencies),
+    ];
+    for deps in sections.into_iter().flatten() {
+        let mut names: Vec<String> =
+            deps.iter().filter(|(_, d)| d.version().is_some()).map(|(n, _)| n.clone()).collect();
+        names.sort();
+        for name in names {
+            match registry.versions(&name) {
+                Ok(_) => reporter.step(&t!(
+                    "import.dependency",
+                    name = name.cyan(),
+                    spec = deps[&name].to_string()
+                )),
+                Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => {
+                    deps.remove(&name);
+                    notes.push(t!("import.not_in_registry", name = name));
+                },
+                Err(err) => reporter.warn(&t!("import.unchecked", name = name, error = err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_range() {
+        assert_eq!(convert_range("^1.2.3").as_deref(), Some("^1.2.3"));
+        assert_eq!(convert_range(">=1.0.0 <2.0.0").as_deref(), Some(">=1.0.0, <2.0.0"));
+        assert_eq!(convert_range(">= 1.0.0 < 2").as_deref(), Some(">=1.0.0, <2"));
+        assert_eq!(convert_range("1.2.3 - 2.0.0").as_deref(), Some(">=1.2.3, <=2.0.0"));
+        assert_eq!(convert_range("1.x").as_deref(), Some("1.x"));
+        assert_eq!(convert_range("latest").as_deref(), Some("*"));
+        assert_eq!(convert_range("beta").as_deref(), Some("beta"));
+    }
+
+    #[test]
+    fn test_convert_dependency() {
+        assert_eq!(convert_dependency("^1 || ^2"), None);
+        assert_eq!(convert_dependency("npm:other@1.0.0"), None);
+        assert_eq!(
+            convert_dependency("file:../ui"),
+            Some(Dependency::Path(PathSource { path: "../ui".into() }))
+        );
+        assert_eq!(
+            convert_dependency("github:acme/ui#dev"),
+            Some(Dependency::Git(GitSource {
+                git: "https://github.com/acme/ui".into(),
+                rev: None,
+                branch: Some("dev".into()),
+                tag: None,
+            }))
+        );
+        let Some(Dependency::Git(source)) = convert_dependency("git+https://git.example/ui.git#abc1234") else {
+            panic!("not a git dependency");
+        };
+        assert_eq!(
+            (source.git.as_str(), source.rev.as_deref()),
+            ("https://git.example/ui.git", Some("abc1234"))
+        );
+    }
+
+    #[test]
+    fn test_map_package() {
+        let package = serde_json::from_str(
+            r#"{
+                "name": "@acme/cool-plugin",
+                "version": "1.2.0",
+                "author": { "name": "Ana", "email": "ana@example.com" },
+                "repository": "acme/cool-plugin",
+                "main": "index.js",
+                "engines": { "node": ">=18", "lua": "5.4" },
+                "dependencies": { "ui": "~2.1.0", "left": "^1 || ^2" },
+                "scripts": { "test": "busted" },
+                "private": true
+            }"#,
+        )
+        .unwrap();
+        let mut notes = Vec::new();
+        let manifest = map_package(&package, &mut notes);
+
+        assert_eq!(manifest.name, "cool-plugin");
+        assert_eq!(manifest.display_name, "Cool Plugin");
+        assert_eq!(manifest.author, "Ana <ana@example.com>");
+        assert_eq!(manifest.repository.unwrap().url, "https://github.com/acme/cool-plugin");
+        assert_eq!(manifest.main, "src/init.lua");
+        assert_eq!(manifest.engines.lua, "5.4");
+        assert_eq!(manifest.dependencies.keys().collect::<Vec<_>>(), ["ui"]);
+        assert_eq!(manifest.scripts["test"], "busted");
+        // Scope, main, engines.node, the `||` range and `private`
+        assert_eq!(notes.len(), 5);
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::commands::update::show_release_notes;
+use crate::dist_tag;
+use crate::error::VkError;
+use crate::git::BuildInfo;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::registry::{PackageVersion, RegistryClient};
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::utils::parse_package;
+
+pub fn show_info(package: &str, include_pre: bool, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let (id, _) = parse_package(package);
+
+    let details = RegistryClient::new(http_client).package(&id).map_err(|e| VkError::package_lookup(e, &id))?;
+
+    reporter.title(&format!("{}@{}", details.name.cyan(), details.latest_version.yellow()));
+
+    if let Some(description) = &details.description {
+        reporter.output(description);
+    }
+    if let Some(license) = &details.license {
+        reporter.output(&format!("{} {}", t!("info.license").bright_black(), license));
+    }
+    if let Some(homepage) = &details.homepage {
+        reporter.output(&format!("{} {}", t!("info.homepage").bright_black(), homepage));
+    }
+
+    if let Ok(tags) = dist_tag::fetch(&id, http_client)
+        && !tags.is_empty()
+    {
+        reporter.output("");
+        reporter.output(&t!("info.dist_tags").bold().to_string());
+        for (tag, version) in &tags {
+            reporter.output(&format!("  {}: {}", tag.cyan(), version.yellow()));
+        }
+    }
+
+    let entries = fetch_entries(&id, http_client)?;
+    let mut versions: Vec<String> = entries.iter().map(|v| v.version.clone()).collect();
+    resolver::sort_versions(&mut versions);
+
+    let hidden = versions.iter().filter(|v| !include_pre && is_pre_release(v)).count();
+
+    reporter.output("");
+    reporter.output(&t!("info.versions").bold().to_string());
+    for version in versions.iter().rev().filter(|v| include_pre || !is_pre_release(v)) {
+        let published = entries.iter().find(|v| v.version == *version).and_then(|v| v.published_at);
+        match published {
+            Some(at) => reporter.output(&format!("  {} {}", version, at.date().bright_black())),
+            None => reporter.output(&format!("  {}", version)),
+        }
+    }
+
+    if hidden > 0 {
+        reporter.info(&t!("info.pre_hidden", count = hidden));
+    }
+
+    // Notes of the newest version listed, as published with it
+    let listed: Vec<&String> = versions.iter().filter(|v| include_pre || !is_pre_release(v)).collect();
+    if let Some((newest, older)) = listed.split_last() {
+        let build_info = entries.iter().find(|v| v.version == **newest).and_then(|v| v.build_info.as_ref());
+        if let Some(info) = build_info {
+            reporter.output("");
+            reporter.output(&format!(
+                "{} {}",
+                t!("info.built_from", version = newest).bright_black(),
+                describe_build(info)
+            ));
+        }
+        let previous = older.last().map_or("", |v| v.as_str());
+        show_release_notes(&id, previous, newest, http_client, reporter);
+    }
+
+    Ok(())
+}
+
+/// `commit (tag)`, flagged when the tree had uncommitted changes.
+fn describe_build(info: &BuildInfo) -> String {
+    let mut description = info.commit.yellow().to_string();
+    if let Some(tag) = &info.tag {
+        description.push_str(&format!(" ({})", tag.cyan()));
+    }
+    if info.dirty {
+        description.push_str(&format!(" {}", t!("info.built_dirty").red()));
+    }
+    description
+}
+
+pub fn fetch_entries(id: &str, http_client: &HttpClient) -> Result<Vec<PackageVersion>> {
+    RegistryClient::new(http_client).versions(id).map_err(|e| VkError::package_lookup(e, id))
+}
+
+/// Every published version of `id`, in registry order.
+pub fn fetch_versions(id: &str, http_client: &HttpClient) -> Result<Vec<String>> {
+    Ok(fetch_entries(id, http_client)?.into_iter().map(|v| v.version).collect())
+}
+
+fn is_pre_release(version: &str) -> bool {
+    resolver::parse_version(version).is_some_and(|v| !v.pre.is_empty())
+}
@@ -1,15 +1,89 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 use dialoguer::Input;
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    encoding::json5,
+    author,
+    config::AppUser,
+    encoding::json5::{self, DateTime},
+    git,
+    i18n::t,
     manifest::{FileSystemPermission, Limits, MANIFEST_FILENAME, NetworkPermission, Permissions, PluginManifest},
+    project::Project,
+    reporter::Reporter,
 };
 
-pub fn init_project(yes: bool, directory: &Option<String>) -> Result<()> {
-    let dir_path = if let Some(dir) = directory {
+/// CI pipeline templates embedded in the binary.
+const GITHUB_TEMPLATE: &str = include_str!("../../templates/ci/github.yml");
+const GITLAB_TEMPLATE: &str = include_str!("../../templates/ci/gitlab.yml");
+
+/// License texts `vk init` can write, by SPDX id.
+const LICENSES: &[(&str, &str)] = &[
+    ("MIT", include_str!("../../templates/licenses/MIT.txt")),
+    ("Apache-2.0", include_str!("../../templates/licenses/Apache-2.0.txt")),
+    (
+        "BSD-2-Clause",
+        include_str!("../../templates/licenses/BSD-2-Clause.txt"),
+    ),
+    (
+        "BSD-3-Clause",
+        include_str!("../../templates/licenses/BSD-3-Clause.txt"),
+    ),
+    ("ISC", include_str!("../../templates/licenses/ISC.txt")),
+    ("MPL-2.0", include_str!("../../templates/licenses/MPL-2.0.txt")),
+    ("Unlicense", include_str!("../../templates/licenses/Unlicense.txt")),
+];
+
+/// Files git leaves out of a new project: installed plugins, build output
+/// and local state.
+const GITIGNORE: &str = "plugins/\ntarget/\n.vk/\n.env\n";
+
+/// CI services `vk init --ci` can write a pipeline for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CiProvider {
+    Github,
+    Gitlab,
+}
+
+impl CiProvider {
+    fn template(self) -> &'static str {
+        match self {
+            CiProvider::Github => GITHUB_TEMPLATE,
+            CiProvider::Gitlab => GITLAB_TEMPLATE,
+        }
+    }
+
+    /// Where the provider looks for the pipeline, relative to the project.
+    fn path(self) -> &'static str {
+        match self {
+            CiProvider::Github => ".github/workflows/vayload.yml",
+            CiProvider::Gitlab => ".gitlab-ci.yml",
+        }
+    }
+}
+
+pub struct InitOptions {
+    /// Skip prompts and use defaults.
+    pub yes: bool,
+    pub directory: Option<String>,
+    /// Also write a CI pipeline for this provider.
+    pub ci: Option<CiProvider>,
+    /// Registry the pipeline publishes to.
+    pub registry_url: String,
+    /// SPDX id of the license, offered as the default when prompting.
+    pub license: String,
+    /// Make the project a git repository with a first commit.
+    pub git: bool,
+    /// Identity the author defaults to.
+    pub user: AppUser,
+}
+
+pub fn init_project(options: &InitOptions, reporter: &dyn Reporter) -> Result<()> {
+    let dir_path = if let Some(dir) = &options.directory {
         Path::new(dir).to_path_buf()
     } else {
         std::env::current_dir()?
@@ -17,11 +91,22 @@ pub fn init_project(yes: bool, directory: &Option<String>) -> Result<()> {
 
     // If current directory already has a manifest file, skip initialization
     let manifest_path = dir_path.join(MANIFEST_FILENAME);
-    if manifest_path.exists() {
-        return Err(anyhow::anyhow!("Plugin manifest already exists, skipping"));
+    if let Some(existing) = Project::at(&dir_path) {
+        // `--ci` on an existing project only adds the pipeline
+        let Some(ci) = options.ci else {
+            return Err(anyhow::anyhow!(t!("init.manifest_exists")));
+        };
+        let manifest = existing.read_manifest()?;
+        if let Some(path) = write_pipeline(&dir_path, ci, &manifest.name, &options.registry_url, reporter)? {
+            reporter.success(&t!("init.created", path = path.display().to_string().cyan()));
+        }
+        return Ok(());
     }
 
-    println!("{}", "🚀 Initializing Vayload plugin...".cyan().bold());
+    reporter.title(&t!("init.title"));
+
+    // Prompts need a terminal; fall back to the defaults otherwise
+    let yes = options.yes || !reporter.is_interactive();
 
     let plugin_name = dir_path.file_name().and_then(|n| n.to_str()).unwrap_or("my-project").to_string();
 
@@ -29,53 +114,77 @@ pub fn init_project(yes: bool, directory: &Option<String>) -> Result<()> {
         plugin_name.clone()
     } else {
         Input::new()
-            .with_prompt("Plugin name")
+            .with_prompt(t!("init.prompt_name"))
             .default(plugin_name)
             .interact_text()
-            .context("Failed to read plugin name")?
+            .with_context(|| t!("init.read_input_failed"))?
     };
 
     let description: String = if yes {
         "A Vayload plugin".to_string()
     } else {
         Input::new()
-            .with_prompt("Description")
+            .with_prompt(t!("init.prompt_description"))
             .default("A Vayload plugin".to_string())
             .interact_text()
-            .context("Failed to read description")?
+            .with_context(|| t!("init.read_input_failed"))?
     };
 
+    // The directory may not exist yet; git reads the identity from there
+    let identity_dir = dir_path.ancestors().find(|dir| dir.is_dir()).unwrap_or(Path::new("."));
+    let default_author = author::default_author(&options.user, identity_dir);
     let author: String = if yes {
-        "author".to_string()
+        default_author.unwrap_or_default()
+    } else {
+        let mut input = Input::new().with_prompt(t!("init.prompt_author")).allow_empty(true);
+        if let Some(default_author) = default_author {
+            input = input.default(default_author);
+        }
+        input.interact_text().with_context(|| t!("init.read_input_failed"))?
+    };
+    if let Err(reason) = author::check(&author) {
+        reporter.warn(&t!("init.author_invalid", reason = reason));
+    }
+
+    let license: String = if yes {
+        options.license.clone()
     } else {
         Input::new()
-            .with_prompt("Author")
-            .default("author".to_string())
+            .with_prompt(t!("init.prompt_license"))
+            .default(options.license.clone())
             .interact_text()
-            .context("Failed to read author")?
+            .with_context(|| t!("init.read_input_failed"))?
     };
 
     let mut project = PluginManifest::default();
     project.set_name(name.clone());
     project.description = description.clone();
-    project.author = author;
+    // Spelled as SPDX does when there is a template for it
+    let license = LICENSES
+        .iter()
+        .find(|(id, _)| id.eq_ignore_ascii_case(&license))
+        .map_or(license, |(id, _)| id.to_string());
+    project.license = license.clone();
+    project.author = author.clone();
     project.permissions = Some(Permissions::new(
         FileSystemPermission::default(),
         NetworkPermission::new(vec!["jsonplaceholder.typicode.com".to_string()], false),
         Limits::default(),
     ));
 
-    fs::write(&manifest_path, json5::to_string_pretty(&project)?).context("Failed to write manifest file")?;
+    fs::write(&manifest_path, json5::to_string_pretty(&project)?).with_context(|| t!("manifest.write_failed"))?;
 
     let src_dir = dir_path.join("src");
-    fs::create_dir_all(&src_dir).context("Failed to create src directory")?;
+    fs::create_dir_all(&src_dir).with_context(|| t!("common.write_failed", path = src_dir.display()))?;
 
     let readme_content = format!(
         "# {}\n\n{}\n\n## Getting Started\n\n1. Run `vk install` to install dependencies\n2. Build your plugin\n3. Publish with `vk publish`\n",
         name, description
     );
-    fs::write(dir_path.join("README.md"), readme_content).context("Failed to write README.md")?;
-    fs::write(dir_path.join(".vkignore"), "target/\n*.lock\n.vk/\n.env\n").context("Failed to write .vkignore")?;
+    fs::write(dir_path.join("README.md"), readme_content)
+        .with_context(|| t!("common.write_failed", path = "README.md"))?;
+    fs::write(dir_path.join(".vkignore"), "target/\n*.lock\n.vk/\n.env\n")
+        .with_context(|| t!("common.write_failed", path = ".vkignore"))?;
 
     let entry_content = r#"
        	local kernel = require("vhost:kernel")
@@ -97,27 +206,102 @@ pub fn init_project(yes: bool, directory: &Option<String>) -> Result<()> {
 
     fs::write(src_dir.join("init.lua"), entry_content)?;
 
-    println!("\n{}", "✅ Project initialized successfully!".green().bold());
-    println!(
-        "{} Created {}",
-        "📄".green(),
-        manifest_path.display().to_string().cyan()
-    );
-    println!("{} Created {}", "📁".green(), src_dir.display().to_string().cyan());
-    println!(
-        "{} Created {}",
-        "📝".green(),
-        dir_path.join("README.md").display().to_string().cyan()
-    );
-    println!(
-        "{} Created {}",
-        "📝".green(),
-        dir_path.join(".vkignore").display().to_string().cyan()
-    );
-    println!(
-        "Created Entry file in {}",
-        dir_path.join("src/main.lua").display().to_string().cyan()
-    );
+    let mut created = vec![
+        manifest_path,
+        src_dir.clone(),
+        dir_path.join("README.md"),
+        dir_path.join(".vkignore"),
+        src_dir.join("init.lua"),
+    ];
+    match license_text(&license, &author) {
+        Some(text) => {
+            let path = dir_path.join("LICENSE");
+            fs::write(&path, text).with_context(|| t!("common.write_failed", path = "LICENSE"))?;
+            created.push(path);
+        },
+        None => reporter.warn(&t!("init.license_unknown", license = license.as_str())),
+    }
+    if let Some(ci) = options.ci {
+        created.extend(write_pipeline(
+            &dir_path,
+            ci,
+            &project.name,
+            &options.registry_url,
+            reporter,
+        )?);
+    }
+
+    if options.git {
+        created.extend(init_git(&dir_path, reporter)?);
+    }
+
+    reporter.done(&t!("init.done"));
+    for path in created {
+        reporter.success(&t!("init.created", path = path.display().to_string().cyan()));
+    }
 
     Ok(())
 }
+
+/// The text of the `license` template with its copyright line filled in, if
+/// there is a template for that SPDX id. The line names the author without
+/// their email.
+fn license_text(license: &str, author: &str) -> Option<String> {
+    let (_, template) = LICENSES.iter().find(|(id, _)| *id == license)?;
+    let year = &DateTime::now().date()[..4];
+    let holder = author.split(['<', '(']).next().unwrap_or_default().trim();
+    Some(template.replace("{{year}}", year).replace("{{author}}", holder))
+}
+
+/// Makes `dir` a git repository holding the new project as its first commit,
+/// returning the `.gitignore` it writes. A project inside an existing
+/// repository is left to it. Git failing, as when it is not installed or has
+/// no identity set, only warns: the project is there either way.
+fn init_git(dir: &Path, reporter: &dyn Reporter) -> Result<Option<PathBuf>> {
+    if git::run(dir, &["rev-parse", "--is-inside-work-tree"]).is_ok() {
+        reporter.detail(&t!("init.git_existing"));
+        return Ok(None);
+    }
+    let gitignore = dir.join(".gitignore");
+    let written = !gitignore.exists();
+    if written {
+        fs::write(&gitignore, GITIGNORE).with_context(|| t!("common.write_failed", path = ".gitignore"))?;
+    }
+
+    let result = git::run(dir, &["init", "--quiet"])
+        .and_then(|_| git::run(dir, &["add", "--all"]))
+        .and_then(|_| git::run(dir, &["commit", "--quiet", "--message", "Initial commit"]));
+    match result {
+        Ok(_) => reporter.step(&t!("init.git_committed")),
+        Err(err) => reporter.warn(&t!("init.git_failed", reason = err)),
+    }
+    Ok(written.then_some(gitignore))
+}
+
+/// Writes the pipeline of `ci` into `dir`, filled in for the plugin `name`
+/// and `registry_url`. An existing pipeline is left alone.
+fn write_pipeline(
+    dir: &Path,
+    ci: CiProvider,
+    name: &str,
+    registry_url: &str,
+    reporter: &dyn Reporter,
+) -> Result<Option<PathBuf>> {
+    let path = dir.join(ci.path());
+    if path.exists() {
+        reporter.warn(&t!("init.ci_exists", path = ci.path()));
+        return Ok(None);
+    }
+
+    let content = ci
+        .template()
+        .replace("{{name}}", name)
+        .replace("{{registry_url}}", registry_url)
+        .replace("{{vk_version}}", &format!("v{}", env!("CARGO_PKG_VERSION")));
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+    }
+    fs::write(&path, content).with_context(|| t!("common.write_failed", path = path.display()))?;
+    Ok(Some(path))
+}
@@ -1,121 +1,1476 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
-use crate::http_client::HttpClient;
+use crate::assets;
+use crate::capabilities;
+use crate::commands::info::{fetch_entries, fetch_versions};
+use crate::commands::{validate, vendor};
+use crate::config;
+use crate::delta;
+use crate::dist_tag;
+use crate::error::VkError;
+use crate::features::{self, Enabled};
+use crate::git;
+use crate::http_client::{Download, HttpClient};
+use crate::i18n::t;
+use crate::inspect;
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::manifest::{Dependency, GitSource, PathSource, PluginManifest};
+use crate::permissions;
+use crate::policy::{self, Policy};
+use crate::project::Project;
+use crate::provenance;
+use crate::registry::RegistryClient;
+use crate::report::{self, PackageEntry, Recorder};
+use crate::reporter::{DeferredReporter, Progress, Reporter};
+use crate::resolver::{self, Candidate, Conflict, Resolved, Strategy, Unsolvable};
+use crate::scripts::{self, Hook};
+use crate::store::{self, Store, StoreMode};
+use crate::target::{Engine, Target};
+use crate::trust::Trust;
 use crate::types::DownloadMeta;
-use crate::utils::{extract_zip, format_bytes, parse_package};
+use crate::utils::{
+    extract_zip, format_bytes, hash_dir, link_or_copy_dir, parse_package, remove_installed, sha256_hex,
+};
 
-pub fn install_plugin(package: &str, plugins_dir: &str, http_client: &HttpClient) -> Result<()> {
-    let (id, version) = parse_package(package);
+pub struct InstallOptions<'a> {
+    /// Allow pre-release versions when resolving a range.
+    pub include_pre: bool,
+    /// Never run plugin `postinstall` hooks.
+    pub ignore_scripts: bool,
+    /// Install upgrades that ask for more permissions without asking.
+    pub accept_permissions: bool,
+    /// Never reach the network: registry packages come from `vendor/`.
+    pub offline: bool,
+    /// Restore the symlinks packages store, as long as they point inside the
+    /// package. They are left out otherwise.
+    pub allow_symlinks: bool,
+    /// Only install registry packages published with provenance the registry
+    /// verified, for the exact archive downloaded.
+    pub require_provenance: bool,
+    /// Which version of a range to pick.
+    pub strategy: Strategy,
+    /// Features to enable instead of the ones in the lockfile.
+    pub features: Option<Vec<String>>,
+    /// Where the plugins will run, for target dependencies and the
+    /// `engines` ranges of each plugin.
+    pub target: Target,
+    /// Warn about plugins whose `engines` ranges exclude the target instead
+    /// of refusing them.
+    pub ignore_engines: bool,
+    /// Permission ceilings every installed plugin must respect.
+    pub policy: Option<Policy>,
+    /// Registry archives downloaded and extracted at once by bulk installs.
+    pub jobs: usize,
+    /// How registry packages are shared with other projects.
+    pub store: StoreMode,
+    /// Publishers whose packages may be installed; any when unset.
+    pub trust: Option<Trust>,
+    /// Show what each registry package holds and ask before installing it.
+    pub inspect: bool,
+    /// Notes what was installed, for `--report`.
+    pub recorder: &'a Recorder,
+}
 
-    print!("{} Installing {}", "📦".bold(), id.cyan());
-    if let Some(v) = &version {
-        print!("@{}", v.yellow());
+impl InstallOptions<'_> {
+    /// The stored copy of `locked`, when installs use the store. Provenance
+    /// is checked on the archive, so installs requiring it download.
+    fn stored(&self, locked: &LockedPackage) -> Option<PathBuf> {
+        if self.store == StoreMode::Off || self.require_provenance {
+            return None;
+        }
+        Store::open().ok()?.get(&locked.id, &locked.version, locked.checksum.as_deref())
     }
-    println!();
+}
+
+pub fn install_plugin(
+    project: &Project,
+    package: Option<&str>,
+    plugins_dir: &str,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let plugins_path = project.root().join(plugins_dir);
+    fs::create_dir_all(&plugins_path).with_context(|| t!("install.create_plugins_dir_failed"))?;
 
-    let plugins_path = Path::new(plugins_dir);
-    fs::create_dir_all(plugins_path).context("Failed to create plugins directory")?;
+    let manifest = project.read_manifest()?;
+    let mut lockfile = Lockfile::load(project)?;
 
-    let (zip_data, meta) = download_plugin(&id, version.as_deref(), http_client)?;
+    if let Some(requested) = &options.features {
+        let mut requested: Vec<String> =
+            requested.iter().map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect();
+        requested.sort();
+        requested.dedup();
+        lockfile.features = requested;
+    }
+    let enabled = features::enable(&manifest, &lockfile.features)
+        .map_err(|name| VkError::ManifestInvalid(t!("install.unknown_feature", feature = name)))?;
 
-    println!(
-        "{} Downloaded {}@{} ({})",
-        "✓".green(),
-        meta.id.cyan(),
-        meta.version.yellow(),
-        format_bytes(zip_data.len())
-    );
+    let Some(package) = package else {
+        return install_all(
+            project,
+            &manifest,
+            &enabled,
+            &mut lockfile,
+            &plugins_path,
+            options,
+            http_client,
+            reporter,
+        );
+    };
 
-    if let Some(checksum) = &meta.checksum {
-        println!("{} Checksum verified: {}", "✓".green(), checksum.bright_black());
+    let (id, mut version) = parse_package(package);
+    if let Some(pinned) = lockfile.pinned(&id) {
+        if version.as_deref().is_some_and(|v| v != pinned) {
+            anyhow::bail!(t!("pin.install_pinned", package = id.cyan(), version = pinned));
+        }
+        version = Some(pinned.to_string());
     }
+    let declared = declared_dependencies(&manifest, &lockfile.features, Some(&options.target)).remove(&id);
 
     let plugin_path = plugins_path.join(&id);
+    if is_linked(&plugin_path) {
+        reporter.warn(&t!("install.linked_skipped", id = id.cyan()));
+        options.recorder.skipped(&id, "linked");
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let locked = match (&version, &declared) {
+        (None, Some(Dependency::Git(source))) => {
+            install_git(&id, source, lockfile.get(&id), &plugins_path, options, reporter)?
+        },
+        (None, Some(Dependency::Path(source))) => install_path(project, &id, source, &plugins_path, options, reporter)?,
+        _ if options.offline => {
+            let locked = lockfile
+                .get(&id)
+                .filter(|l| l.is_registry() && version.as_deref().is_none_or(|v| satisfies(v, &l.version)));
+            match locked.and_then(|l| Some((l, vendored_archive(project, l, true)?))) {
+                Some((locked, archive)) => {
+                    install_vendored(locked, &archive, &plugins_path, options, http_client, reporter)?
+                },
+                None => return Err(VkError::NotVendored(package.to_string()).into()),
+            }
+        },
+        _ => {
+            let version = resolve_version(
+                &id,
+                version,
+                options.include_pre,
+                options.strategy,
+                http_client,
+                reporter,
+            )?;
+            check_publishers(
+                [(id.as_str(), version.as_deref())],
+                &lockfile,
+                options,
+                http_client,
+                reporter,
+            )?;
+            install_registry(
+                project,
+                &id,
+                version.as_deref(),
+                None,
+                &plugins_path,
+                options,
+                http_client,
+                reporter,
+            )?
+        },
+    };
+
+    scripts::run_plugin_hook(
+        project,
+        &plugin_path,
+        Hook::PostInstall,
+        options.ignore_scripts,
+        reporter,
+    )?;
+    record_install(options.recorder, lockfile.get(&id), &locked, started);
+
+    // Only manifest dependencies are pinned, so the lockfile mirrors the manifest
+    if declared.is_some() {
+        let overridden = manifest.overrides.contains_key(&id);
+        lockfile.upsert(LockedPackage { overridden, ..locked });
+        lockfile.save(project)?;
+    }
+
+    Ok(())
+}
+
+/// Installs every dependency declared in the manifest, reusing the versions
+/// and commits pinned in the lockfile while they still satisfy the manifest.
+/// Optional dependencies no enabled feature asks for are uninstalled.
+#[allow(clippy::too_many_arguments)]
+fn install_all(
+    project: &Project,
+    manifest: &PluginManifest,
+    enabled: &Enabled,
+    lockfile: &mut Lockfile,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let declared = declared_dependencies(manifest, &lockfile.features, None);
+    let deps = declared_dependencies(manifest, &lockfile.features, Some(&options.target));
+
+    if declared.is_empty() {
+        reporter.info(&t!("list.empty"));
+        return Ok(());
+    }
 
-    if plugin_path.exists() {
-        fs::remove_dir_all(&plugin_path).context("Failed to remove old version")?;
+    reporter.title(&t!("install.title_all", count = deps.len()));
+    if !enabled.features.is_empty() {
+        let names: Vec<&str> = enabled.features.iter().map(String::as_str).collect();
+        reporter.step(&t!("install.features", features = names.join(", ").cyan()));
     }
+    for id in declared.keys().filter(|id| !deps.contains_key(*id)) {
+        reporter.detail(&t!("install.target_skipped", id = id.cyan()));
+        options.recorder.skipped(id, "target");
+    }
+
+    let resolved = match options.offline {
+        true => BTreeMap::new(),
+        false => resolve_registry(
+            project,
+            manifest,
+            lockfile,
+            plugins_path,
+            options,
+            http_client,
+            reporter,
+        )?,
+    };
+
+    // Registry archives are downloaded and extracted ahead, several at a time,
+    // leaving prompts, hooks and the lockfile to the loop below
+    let plan: Vec<(&str, String)> = deps
+        .iter()
+        .filter(|(id, _)| !options.offline && !is_linked(&plugins_path.join(id)))
+        .filter_map(|(id, dep)| {
+            let Dependency::Version(spec) = dep else {
+                return None;
+            };
+            let resolved = resolved.get(id).map(|r| r.version.to_string());
+            let locked = reusable_lock(lockfile, id, spec, resolved.as_deref());
+            if locked.is_some_and(|l| vendored_archive(project, l, false).is_some() || options.stored(l).is_some()) {
+                return None;
+            }
+            Some((id.as_str(), resolved.or_else(|| locked.map(|l| l.version.clone()))?))
+        })
+        .collect();
+    check_publishers(
+        plan.iter().map(|(id, version)| (*id, Some(version.as_str()))),
+        lockfile,
+        options,
+        http_client,
+        reporter,
+    )?;
+    let mut prefetched = match options.jobs {
+        0 | 1 => Prefetched::none(plugins_path),
+        jobs => prefetch(project, &plan, plugins_path, jobs, options, http_client, reporter),
+    };
+
+    for (id, dep) in &deps {
+        if is_linked(&plugins_path.join(id)) {
+            reporter.warn(&t!("install.linked_skipped", id = id.cyan()));
+            options.recorder.skipped(id, "linked");
+            continue;
+        }
+
+        let started = Instant::now();
+        let version = resolved.get(id).map(|r| r.version.to_string());
+        let mut locked = install_declared(
+            project,
+            id,
+            dep,
+            version.as_deref(),
+            prefetched.take(id),
+            lockfile,
+            plugins_path,
+            options,
+            http_client,
+            reporter,
+        )?;
 
-    fs::create_dir_all(&plugin_path).context("Failed to create plugin directory")?;
+        let plugin_path = plugins_path.join(id);
+        scripts::run_plugin_hook(
+            project,
+            &plugin_path,
+            Hook::PostInstall,
+            options.ignore_scripts,
+            reporter,
+        )?;
+        record_install(options.recorder, lockfile.get(id), &locked, started);
+        locked.overridden = manifest.overrides.contains_key(id);
+        if let Some(resolved) = resolved.get(id) {
+            locked.dependencies = resolved.dependencies.iter().map(|(d, v)| (d.clone(), v.to_string())).collect();
+        }
+        lockfile.upsert(locked);
+    }
 
-    extract_zip(&zip_data, &plugin_path).context("Failed to extract plugin")?;
+    for id in manifest.optional_dependencies.keys().filter(|id| !deps.contains_key(*id)) {
+        let plugin_path = plugins_path.join(id);
+        if lockfile.get(id).is_some() && !is_linked(&plugin_path) && plugin_path.exists() {
+            remove_installed(&plugin_path)?;
+            reporter.detail(&t!("install.optional_removed", id = id.cyan()));
+        }
+    }
 
-    println!(
-        "{} Installed to {}",
-        "✅".green(),
-        plugin_path.display().to_string().bright_black()
-    );
+    // Entries for other targets stay for the machines they apply to
+    lockfile.packages.retain(|p| declared.contains_key(&p.id));
+    lockfile.save(project)?;
+    features::write_metadata(plugins_path, enabled)?;
+
+    reporter.done(&t!("install.all_done", count = deps.len()));
 
     Ok(())
 }
 
-fn download_plugin(id: &str, version: Option<&str>, http_client: &HttpClient) -> Result<(Vec<u8>, DownloadMeta)> {
-    let mut url = format!("/plugins/{id}/download");
-    if let Some(v) = version {
-        url.push_str(&format!("?version={}", v));
+/// Notes an install as an update when it changed the locked version.
+fn record_install(recorder: &Recorder, previous: Option<&LockedPackage>, locked: &LockedPackage, started: Instant) {
+    let from = previous.map(|p| p.version.clone()).filter(|v| *v != locked.version);
+    let entry = PackageEntry {
+        id: locked.id.clone(),
+        version: Some(locked.version.clone()),
+        duration_ms: Some(report::millis(started.elapsed())),
+        ..PackageEntry::default()
+    };
+    match from {
+        Some(from) => recorder.updated(PackageEntry { from: Some(from), ..entry }),
+        None => recorder.installed(entry),
     }
+}
+
+/// Whether an installed plugin is a `vk link` into the global links directory.
+/// Linked plugins are left alone by installs until `vk unlink`.
+pub fn is_linked(plugin_path: &Path) -> bool {
+    let Ok(links_dir) = config::links_dir() else {
+        return false;
+    };
+    fs::read_link(plugin_path).is_ok_and(|target| target.starts_with(links_dir))
+}
 
-    let response = http_client.get_raw(&url)?;
-    let checksum = response.headers().get("X-Checksum").and_then(|v| v.to_str().ok()).map(String::from);
+/// Reinstalls the given manifest dependencies exactly as locked, e.g. to
+/// repair installs that `vk verify` found modified.
+pub fn reinstall(
+    project: &Project,
+    ids: &[String],
+    plugins_dir: &str,
+    allow_symlinks: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let recorder = Recorder::new("verify");
+    let plugins_path = project.root().join(plugins_dir);
+    let mut lockfile = Lockfile::load(project)?;
+    let deps = declared_dependencies(&project.read_manifest()?, &lockfile.features, None);
 
-    let plugin_version = response
-        .headers()
-        .get("X-Plugin-Version")
-        .and_then(|v| v.to_str().ok())
-        .map(String::from)
-        .or_else(|| version.map(String::from))
-        .unwrap_or_else(|| "unknown".to_string());
+    // Locked versions were already accepted when first installed
+    let options = InstallOptions {
+        include_pre: false,
+        ignore_scripts: true,
+        accept_permissions: true,
+        policy: policy::load(Some(project))?,
+        offline: false,
+        allow_symlinks,
+        require_provenance: false,
+        strategy: Strategy::Highest,
+        features: None,
+        target: Target::local(),
+        ignore_engines: true,
+        jobs: 1,
+        // What the store holds may be what was modified
+        store: StoreMode::Off,
+        trust: None,
+        inspect: false,
+        recorder: &recorder,
+    };
 
-    let meta = DownloadMeta { id: id.to_string(), version: plugin_version, checksum };
+    for id in ids {
+        let dep = deps.get(id).ok_or_else(|| VkError::DependencyNotFound(id.clone()))?;
+        let locked = install_declared(
+            project,
+            id,
+            dep,
+            None,
+            None,
+            &lockfile,
+            &plugins_path,
+            &options,
+            http_client,
+            reporter,
+        )?;
+        lockfile.upsert(locked);
+    }
 
-    let total_size = response.content_length();
+    lockfile.save(project)
+}
 
-    let pb = if let Some(size) = total_size {
-        let pb = ProgressBar::new(size);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{msg} [{bar:30.cyan/blue}] {percent}% ({bytes}/{total_bytes}) {elapsed}")
-                .unwrap()
-                .progress_chars("█░"),
-        );
-        pb.set_message("Downloading");
-        Some(pb)
-    } else {
-        println!("Downloading (unknown size)...");
-        None
+/// Installs a manifest dependency. Registry ones get the `resolved` version
+/// when there is one, else the locked version while it satisfies the range,
+/// from the archive already `fetched` for it if any.
+#[allow(clippy::too_many_arguments)]
+fn install_declared(
+    project: &Project,
+    id: &str,
+    dep: &Dependency,
+    resolved: Option<&str>,
+    fetched: Option<Fetched>,
+    lockfile: &Lockfile,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    Ok(match dep {
+        Dependency::Git(source) => install_git(id, source, lockfile.get(id), plugins_path, options, reporter)?,
+        Dependency::Path(source) => install_path(project, id, source, plugins_path, options, reporter)?,
+        Dependency::Version(spec) => {
+            let locked = reusable_lock(lockfile, id, spec, resolved);
+            if let Some(locked) = locked {
+                if let Some(archive) = vendored_archive(project, locked, options.offline) {
+                    return install_vendored(locked, &archive, plugins_path, options, http_client, reporter);
+                }
+                if let Some(archive) = locked.vendored() {
+                    reporter.warn(&t!("install.vendored_missing", id = id.cyan(), path = archive));
+                }
+                if let Some(entry) = options.stored(locked) {
+                    return install_stored(locked, &entry, plugins_path, options, reporter);
+                }
+            }
+            if options.offline {
+                return Err(VkError::NotVendored(id.to_string()).into());
+            }
+
+            let version = match (resolved, locked) {
+                (Some(version), _) => Some(version.to_string()),
+                (None, Some(locked)) => Some(locked.version.clone()),
+                (None, None) if spec.is_empty() || spec == "*" => None,
+                (None, None) => resolve_version(
+                    id,
+                    Some(spec.clone()),
+                    options.include_pre,
+                    options.strategy,
+                    http_client,
+                    reporter,
+                )?,
+            };
+            install_registry(
+                project,
+                id,
+                version.as_deref(),
+                fetched,
+                plugins_path,
+                options,
+                http_client,
+                reporter,
+            )?
+        },
+    })
+}
+
+/// The locked version of a registry dependency still to install: the one
+/// that satisfies `spec` and is the `resolved` version, if any.
+fn reusable_lock<'a>(
+    lockfile: &'a Lockfile,
+    id: &str,
+    spec: &str,
+    resolved: Option<&str>,
+) -> Option<&'a LockedPackage> {
+    lockfile
+        .get(id)
+        .filter(|l| l.is_registry() && satisfies(spec, &l.version) && resolved.is_none_or(|v| v == l.version))
+}
+
+/// Checks who publishes the registry packages about to be installed, except
+/// at their locked version, whose publishers were checked when it was first
+/// installed.
+fn check_publishers<'a>(
+    packages: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    lockfile: &Lockfile,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let Some(trust) = &options.trust else {
+        return Ok(());
     };
+    for (id, version) in packages {
+        let locked = lockfile.get(id).filter(|l| l.is_registry()).map(|l| l.version.as_str());
+        if version.is_none() || version != locked {
+            trust.check(id, http_client, reporter)?;
+        }
+    }
+    Ok(())
+}
 
-    let start = Instant::now();
-    let mut buffer = Vec::new();
+/// Picks the version of every registry dependency, checking the ranges the
+/// packages place on each other. Fails with the chain of requirements behind
+/// the conflict when they cannot all hold.
+fn resolve_registry(
+    project: &Project,
+    manifest: &PluginManifest,
+    lockfile: &Lockfile,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<BTreeMap<String, Resolved>> {
+    let mut roots = BTreeMap::new();
+    let mut preferred = BTreeMap::new();
+    for (id, dep) in &declared_dependencies(manifest, &lockfile.features, Some(&options.target)) {
+        let Dependency::Version(spec) = dep else {
+            continue;
+        };
+        if is_linked(&plugins_path.join(id)) {
+            continue;
+        }
 
-    use std::io::Read;
-    let mut reader = response;
-    let mut chunk = vec![0u8; 32 * 1024]; // 32KB chunks
+        let locked = lockfile.get(id).filter(|l| l.is_registry() && satisfies(spec, &l.version));
+        if let Some(version) = locked.and_then(|l| resolver::parse_version(&l.version)) {
+            preferred.insert(id.clone(), version);
+        }
+        // A dist-tag stands for the version it points at
+        let range = match locked {
+            Some(locked) if dist_tag::is_dist_tag(spec) => format!("={}", locked.version),
+            None if dist_tag::is_dist_tag(spec) => {
+                let version = dist_tag::resolve(id, spec, http_client)?;
+                format!("={}", version)
+            },
+            _ => spec.clone(),
+        };
+        roots.insert(id.clone(), range);
+    }
+    if roots.is_empty() {
+        return Ok(BTreeMap::new());
+    }
 
-    loop {
-        match reader.read(&mut chunk) {
-            Ok(0) => break,
-            Ok(n) => {
-                buffer.extend_from_slice(&chunk[..n]);
-                if let Some(ref pb) = pb {
-                    pb.inc(n as u64);
+    // Packages overridden with a git or local source leave the registry tree
+    let mut overrides = BTreeMap::new();
+    for (id, dep) in &manifest.overrides {
+        if let Dependency::Version(range) = dep {
+            overrides.insert(id.clone(), range.clone());
+        }
+    }
+    // A pin holds its package at one version, whatever asks for it
+    for (id, pin) in &lockfile.pins {
+        overrides.insert(id.clone(), format!("={}", pin.version));
+    }
+
+    reporter.step(&t!("install.resolving", count = roots.len()));
+    let fetch = |id: &str| -> Result<Vec<Candidate>> {
+        Ok(fetch_entries(id, http_client)?
+            .into_iter()
+            .filter_map(|entry| {
+                let conditional = entry
+                    .target_dependencies
+                    .into_iter()
+                    .filter(|set| options.target.satisfies(&set.when))
+                    .flat_map(|set| set.dependencies);
+                let dependencies = conditional
+                    .chain(entry.dependencies)
+                    .filter_map(|(id, dep)| match dep {
+                        Dependency::Version(range)
+                            if !manifest.overrides.contains_key(&id) || overrides.contains_key(&id) =>
+                        {
+                            Some((id, range))
+                        },
+                        _ => None,
+                    })
+                    .collect();
+                Some(Candidate {
+                    version: resolver::parse_version(&entry.version)?,
+                    dependencies,
+                })
+            })
+            .collect())
+    };
+
+    match resolver::solve(
+        &roots,
+        &preferred,
+        &overrides,
+        options.strategy,
+        options.include_pre,
+        fetch,
+    ) {
+        Ok(solved) => Ok(solved),
+        Err(Unsolvable::Failed(err)) => Err(err),
+        Err(Unsolvable::Conflict(conflict)) => {
+            Err(VkError::DependencyConflict(explain_conflict(&conflict, project.manifest_filename())).into())
+        },
+    }
+}
+
+/// The requirements behind a conflict, as a tree: each range on the package,
+/// then the packages that led to it up to the manifest.
+fn explain_conflict(conflict: &Conflict, manifest_file: &str) -> String {
+    let required_by = |via: &[resolver::Dependent]| {
+        via.last().map_or_else(|| manifest_file.to_string(), |d| format!("{} {}", d.id, d.version))
+    };
+
+    let mut lines = vec![t!("install.conflict", id = conflict.package)];
+    for (i, requirement) in conflict.requirements.iter().enumerate() {
+        let last = i + 1 == conflict.requirements.len();
+        lines.push(format!(
+            "  {} {}",
+            if last { "└─" } else { "├─" },
+            t!(
+                "install.conflict_required",
+                range = requirement.range,
+                by = required_by(&requirement.via)
+            )
+        ));
+
+        let margin = if last { "     " } else { "  │  " };
+        for (depth, dependent) in requirement.via.iter().enumerate().rev() {
+            let indent = "   ".repeat(requirement.via.len() - 1 - depth);
+            lines.push(format!(
+                "{}{}└─ {}",
+                margin,
+                indent,
+                t!(
+                    "install.conflict_required_dependent",
+                    id = dependent.id,
+                    range = dependent.range,
+                    by = required_by(&requirement.via[..depth])
+                )
+            ));
+        }
+    }
+
+    let available: Vec<String> = conflict.available.iter().map(|v| v.to_string()).collect();
+    lines.push(match available.is_empty() {
+        true => t!("install.conflict_unpublished"),
+        false => t!("install.conflict_available", versions = available.join(", ")),
+    });
+    lines.join("\n")
+}
+
+/// The dependencies and dev-dependencies of the manifest, plus the optional
+/// dependencies `features` enable, with the entry of `overrides` in place of
+/// theirs for the packages it names. Target dependencies are included when
+/// they apply to `target`, or all of them without one.
+pub fn declared_dependencies(
+    manifest: &PluginManifest,
+    features: &[String],
+    target: Option<&Target>,
+) -> BTreeMap<String, Dependency> {
+    let optional = features::enable(manifest, features).unwrap_or_default().optional_dependencies;
+    let conditional = manifest
+        .target_dependencies
+        .iter()
+        .filter(|set| target.is_none_or(|t| t.satisfies(&set.when)))
+        .flat_map(|set| &set.dependencies);
+
+    // Unconditional entries come last, so they win
+    conditional
+        .chain(&manifest.dependencies)
+        .chain(manifest.dev_dependencies.iter().flatten())
+        .chain(manifest.optional_dependencies.iter().filter(|(id, _)| optional.contains(*id)))
+        .map(|(id, dep)| (id.clone(), manifest.overrides.get(id).unwrap_or(dep).clone()))
+        .collect()
+}
+
+/// Whether a locked version still satisfies the manifest entry. Dist-tags move,
+/// so a pinned version is kept for them until `vk update`.
+pub fn satisfies(spec: &str, version: &str) -> bool {
+    if dist_tag::is_dist_tag(spec) {
+        return true;
+    }
+    match (resolver::parse_req(spec), resolver::parse_version(version)) {
+        (Some(req), Some(version)) => resolver::matches(&req, &version, true),
+        _ => spec == version,
+    }
+}
+
+/// Turns a dist-tag or range into the exact version to download.
+pub fn resolve_version(
+    id: &str,
+    version: Option<String>,
+    include_pre: bool,
+    strategy: Strategy,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<Option<String>> {
+    Ok(match version {
+        Some(tag) if dist_tag::is_dist_tag(&tag) => {
+            let resolved = dist_tag::resolve(id, &tag, http_client)?;
+            reporter.step(&t!(
+                "install.resolved_tag",
+                tag = tag.cyan(),
+                version = resolved.yellow()
+            ));
+            Some(resolved)
+        },
+        Some(range) if resolver::parse_version(&range).is_none() => {
+            let req = resolver::parse_req(&range)
+                .ok_or_else(|| VkError::ManifestInvalid(t!("install.invalid_range", range = range)))?;
+            let versions = fetch_versions(id, http_client)?;
+            let resolved = resolver::pick(versions.iter().map(String::as_str), &req, include_pre, strategy)
+                .ok_or_else(|| VkError::PackageNotFound(format!("{}@{}", id, range)))?
+                .to_string();
+            reporter.step(&t!(
+                "install.resolved_range",
+                range = range.cyan(),
+                version = resolved.yellow()
+            ));
+            Some(resolved)
+        },
+        other => other,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn install_registry(
+    project: &Project,
+    id: &str,
+    version: Option<&str>,
+    fetched: Option<Fetched>,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    match version {
+        Some(v) => reporter.title(&t!("install.title_version", id = id.cyan(), version = v.yellow())),
+        None => reporter.title(&t!("install.title", id = id.cyan())),
+    }
+
+    let staged = match fetched {
+        Some(Fetched { log, archive }) => {
+            log.replay(reporter);
+            archive?
+        },
+        None => fetch_staged(
+            project,
+            id,
+            version,
+            plugins_path,
+            options.allow_symlinks,
+            options.require_provenance,
+            http_client,
+            reporter,
+            None,
+        )?,
+    };
+    let Staged { zip_data, meta, skipped } = staged;
+    options.recorder.downloaded(zip_data.len());
+
+    let plugin_path = install_staged(
+        id,
+        &meta.version,
+        &skipped,
+        plugins_path,
+        options,
+        http_client,
+        reporter,
+    )?;
+    if let Err(err) = delta::store(project, &meta.id, &meta.version, &zip_data) {
+        reporter.detail(&format!("{:#}", err));
+    }
+    if let Some(checksum) = meta.checksum.as_deref().filter(|_| options.store != StoreMode::Off) {
+        Store::open()?.adopt(&meta.id, &meta.version, checksum, &plugin_path, options.store)?;
+    }
+
+    reporter.done(&t!(
+        "install.installed_to",
+        path = plugin_path.display().to_string().bright_black()
+    ));
+
+    Ok(LockedPackage {
+        id: meta.id,
+        version: meta.version,
+        source: None,
+        checksum: meta.checksum,
+        integrity: Some(hash_dir(&plugin_path)?),
+        dependencies: BTreeMap::new(),
+        overridden: false,
+    })
+}
+
+/// A registry archive downloaded, checked and extracted into its staging
+/// directory, ready to replace the installed copy.
+struct Staged {
+    zip_data: Vec<u8>,
+    meta: DownloadMeta,
+    /// Symlinks left out of the extraction.
+    skipped: Vec<String>,
+}
+
+/// An archive [`prefetch`] staged, or failed to, with what it reported
+/// meanwhile.
+struct Fetched {
+    log: DeferredReporter,
+    archive: Result<Staged>,
+}
+
+/// The archives [`prefetch`] staged. Staging directories never installed are
+/// removed with it.
+struct Prefetched {
+    plugins_path: PathBuf,
+    fetched: BTreeMap<String, Fetched>,
+}
+
+impl Prefetched {
+    fn none(plugins_path: &Path) -> Self {
+        Self {
+            plugins_path: plugins_path.to_path_buf(),
+            fetched: BTreeMap::new(),
+        }
+    }
+
+    fn take(&mut self, id: &str) -> Option<Fetched> {
+        self.fetched.remove(id)
+    }
+}
+
+impl Drop for Prefetched {
+    fn drop(&mut self) {
+        for id in self.fetched.keys() {
+            fs::remove_dir_all(staging_dir(&self.plugins_path, id)).ok();
+        }
+    }
+}
+
+/// Downloads, checks and extracts the registry archives of `plan` on up to
+/// `jobs` threads, so that one package extracts while others download.
+/// Everything needing the user, the lockfile or the installed copy is left
+/// to the install of each package.
+fn prefetch(
+    project: &Project,
+    plan: &[(&str, String)],
+    plugins_path: &Path,
+    jobs: usize,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Prefetched {
+    let mut prefetched = Prefetched::none(plugins_path);
+    if plan.len() < 2 {
+        return prefetched;
+    }
+
+    let workers = jobs.min(plan.len());
+    reporter.step(&t!("install.prefetching", count = plan.len(), jobs = workers));
+    let (allow_symlinks, require_provenance) = (options.allow_symlinks, options.require_provenance);
+    let pb = reporter.progress(None, &t!("install.downloading"));
+    let next = AtomicUsize::new(0);
+    let fetched = Mutex::new(BTreeMap::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((id, version)) = plan.get(index) else { break };
+
+                    let log = DeferredReporter::new(reporter.mode());
+                    let archive = fetch_staged(
+                        project,
+                        id,
+                        Some(version),
+                        plugins_path,
+                        allow_symlinks,
+                        require_provenance,
+                        http_client,
+                        &log,
+                        Some(&pb),
+                    );
+                    fetched.lock().unwrap().insert(id.to_string(), Fetched { log, archive });
                 }
+            });
+        }
+    });
+    pb.finish();
+
+    prefetched.fetched = fetched.into_inner().unwrap();
+    prefetched
+}
+
+/// Downloads `id` at `version` (the latest when `None`), checks it and
+/// extracts it into its staging directory. Downloads count towards `pb` when
+/// given, else they show their own progress.
+#[allow(clippy::too_many_arguments)]
+fn fetch_staged(
+    project: &Project,
+    id: &str,
+    version: Option<&str>,
+    plugins_path: &Path,
+    allow_symlinks: bool,
+    require_provenance: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+    pb: Option<&Progress>,
+) -> Result<Staged> {
+    // Patching the archive of the installed version saves most of the download
+    let (zip_data, meta) = match delta::download(project, id, version, http_client, reporter) {
+        Some(patched) => patched,
+        None => match pb {
+            Some(pb) => {
+                let (mut meta, response) = request_archive(id, version, http_client)?;
+                let data = read_archive(response, &mut meta, pb, http_client)?;
+                (data, meta)
+            },
+            None => download_plugin(id, version, http_client, reporter)?,
+        },
+    };
+
+    reporter.success(&t!(
+        "install.downloaded",
+        id = meta.id.cyan(),
+        version = meta.version.yellow(),
+        size = format_bytes(zip_data.len())
+    ));
+
+    verify_checksum(&zip_data, &meta, reporter)?;
+    if require_provenance {
+        provenance::verify(&meta.id, &meta.version, &zip_data, http_client, reporter)?;
+    }
+    let skipped = extract_staged(id, &zip_data, plugins_path, allow_symlinks)?;
+    Ok(Staged { zip_data, meta, skipped })
+}
+
+/// The vendored archive to install `locked` from: the one the lockfile points
+/// at or, offline, one `vk vendor` left in `vendor/` for the locked version.
+fn vendored_archive(project: &Project, locked: &LockedPackage, offline: bool) -> Option<PathBuf> {
+    let path = match locked.vendored() {
+        Some(archive) => project.root().join(archive),
+        None if offline => project.vendor_dir().join(vendor::archive_name(&locked.id, &locked.version)),
+        None => return None,
+    };
+    path.is_file().then_some(path)
+}
+
+/// Installs a registry package from the archive `vk vendor` saved, checked
+/// against the checksum in the lockfile.
+fn install_vendored(
+    locked: &LockedPackage,
+    archive: &Path,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    reporter.title(&t!(
+        "install.title_version",
+        id = locked.id.cyan(),
+        version = locked.version.yellow()
+    ));
+
+    let zip_data = fs::read(archive).with_context(|| t!("common.read_failed", path = archive.display()))?;
+    reporter.success(&t!(
+        "install.vendored",
+        path = archive.display().to_string().bright_black(),
+        size = format_bytes(zip_data.len())
+    ));
+
+    let meta = DownloadMeta {
+        id: locked.id.clone(),
+        version: locked.version.clone(),
+        checksum: locked.checksum.clone(),
+        sha256: None,
+    };
+    verify_checksum(&zip_data, &meta, reporter)?;
+    if options.require_provenance {
+        provenance::verify(&locked.id, &locked.version, &zip_data, http_client, reporter)?;
+    }
+    let plugin_path = unpack(
+        &locked.id,
+        &locked.version,
+        &zip_data,
+        plugins_path,
+        options,
+        http_client,
+        reporter,
+    )?;
+
+    reporter.done(&t!(
+        "install.installed_to",
+        path = plugin_path.display().to_string().bright_black()
+    ));
+
+    Ok(LockedPackage { integrity: Some(hash_dir(&plugin_path)?), ..locked.clone() })
+}
+
+/// Installs a registry package from the shared store, which holds the
+/// locked version extracted from the locked archive.
+fn install_stored(
+    locked: &LockedPackage,
+    entry: &Path,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    reporter.title(&t!(
+        "install.title_version",
+        id = locked.id.cyan(),
+        version = locked.version.yellow()
+    ));
+    reporter.success(&t!(
+        "install.from_store",
+        path = entry.display().to_string().bright_black()
+    ));
+
+    let plugin_path = plugins_path.join(&locked.id);
+    let staging = staging_dir(plugins_path, &locked.id);
+    remove_installed(&staging).with_context(|| t!("install.remove_old_failed"))?;
+    fs::create_dir_all(plugins_path).with_context(|| t!("install.create_plugin_dir_failed"))?;
+    store::link(entry, &staging, options.store)?;
+
+    let checked = check_engine(&staging, options, reporter)
+        .and_then(|_| check_permissions(&plugin_path, &staging, options, reporter))
+        .and_then(|_| inspect_staged(&locked.id, &locked.version, &staging, options, reporter));
+    if let Err(err) = checked {
+        remove_installed(&staging).ok();
+        return Err(err);
+    }
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+    fs::rename(&staging, &plugin_path).with_context(|| t!("install.create_plugin_dir_failed"))?;
+
+    reporter.done(&t!(
+        "install.installed_to",
+        path = plugin_path.display().to_string().bright_black()
+    ));
+
+    Ok(LockedPackage { integrity: Some(hash_dir(&plugin_path)?), ..locked.clone() })
+}
+
+/// Extracts an archive into the plugins directory, with the assets its
+/// manifest lists, and returns where the plugin was installed.
+fn unpack(
+    id: &str,
+    version: &str,
+    zip_data: &[u8],
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<PathBuf> {
+    let skipped = extract_staged(id, zip_data, plugins_path, options.allow_symlinks)?;
+    install_staged(id, version, &skipped, plugins_path, options, http_client, reporter)
+}
+
+/// Where a plugin is extracted before it replaces the installed copy.
+fn staging_dir(plugins_path: &Path, id: &str) -> PathBuf {
+    plugins_path.join(format!(".{}.partial", id))
+}
+
+/// Extracts an archive into the staging directory of `id`. Returns the
+/// symlinks left out.
+fn extract_staged(id: &str, zip_data: &[u8], plugins_path: &Path, allow_symlinks: bool) -> Result<Vec<String>> {
+    // Extract next to the final location so the old copy stays until the new one is accepted
+    let staging = staging_dir(plugins_path, id);
+    if staging.exists() {
+        fs::remove_dir_all(&staging).with_context(|| t!("install.remove_old_failed"))?;
+    }
+    fs::create_dir_all(&staging).with_context(|| t!("install.create_plugin_dir_failed"))?;
+
+    extract_zip(zip_data, &staging, allow_symlinks)
+        .with_context(|| t!("install.extract_failed"))
+        .inspect_err(|_| {
+            fs::remove_dir_all(&staging).ok();
+        })
+}
+
+/// Checks the plugin extracted into the staging directory of `id`, fetches
+/// its assets and moves it in place of the installed copy. Returns where the
+/// plugin was installed.
+fn install_staged(
+    id: &str,
+    version: &str,
+    skipped: &[String],
+    plugins_path: &Path,
+    options: &InstallOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<PathBuf> {
+    let plugin_path = plugins_path.join(id);
+    let staging = staging_dir(plugins_path, id);
+
+    if !skipped.is_empty() {
+        reporter.warn(&t!(
+            "install.symlinks_skipped",
+            id = id.cyan(),
+            links = skipped.join(", ")
+        ));
+    }
+    let staged = check_engine(&staging, options, reporter)
+        .and_then(|_| check_permissions(&plugin_path, &staging, options, reporter))
+        .and_then(|_| assets::fetch(id, version, &staging, http_client, reporter))
+        .and_then(|_| inspect_staged(id, version, &staging, options, reporter));
+    if let Err(err) = staged {
+        fs::remove_dir_all(&staging).ok();
+        return Err(err);
+    }
+
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+    fs::rename(&staging, &plugin_path).with_context(|| t!("install.create_plugin_dir_failed"))?;
+    Ok(plugin_path)
+}
+
+/// Checks out a git dependency into the plugins directory, at the commit
+/// pinned in the lockfile when there is one.
+fn install_git(
+    id: &str,
+    source: &GitSource,
+    locked: Option<&LockedPackage>,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    reporter.title(&t!(
+        "install.title_git",
+        id = id.cyan(),
+        source = source.to_string().bright_black()
+    ));
+
+    if options.offline {
+        return Err(VkError::NotVendored(id.to_string()).into());
+    }
+
+    let pinned = locked.and_then(|l| l.git_commit(source));
+    if let Some(commit) = pinned {
+        reporter.step(&t!("install.git_pinned", commit = commit.yellow()));
+    }
+
+    // Check out next to the final location so a failed clone keeps the old copy
+    let staging = staging_dir(plugins_path, id);
+    if staging.exists() {
+        fs::remove_dir_all(&staging).with_context(|| t!("install.remove_old_failed"))?;
+    }
+
+    let commit = match git::checkout(source, pinned, &staging)
+        .and_then(|commit| check_permissions(&plugins_path.join(id), &staging, options, reporter).map(|_| commit))
+    {
+        Ok(commit) => commit,
+        Err(err) => {
+            fs::remove_dir_all(&staging).ok();
+            return Err(err);
+        },
+    };
+    reporter.success(&t!("install.git_checked_out", commit = commit.yellow()));
+
+    let plugin_path = plugins_path.join(id);
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+    fs::rename(&staging, &plugin_path).with_context(|| t!("install.create_plugin_dir_failed"))?;
+
+    let version = Project::at(&plugin_path)
+        .and_then(|p| p.read_manifest().ok())
+        .map(|m| m.version)
+        .unwrap_or_else(|| commit.chars().take(12).collect());
+
+    reporter.done(&t!(
+        "install.installed_to",
+        path = plugin_path.display().to_string().bright_black()
+    ));
+
+    let mut locked = LockedPackage::git(id, version, source, &commit);
+    locked.integrity = Some(hash_dir(&plugin_path)?);
+    Ok(locked)
+}
+
+/// Refuses a plugin whose `engines` ranges exclude the host runtime or its
+/// Lua version, or only warns with `--ignore-engines`. An engine whose
+/// version is unknown is not checked.
+fn check_engine(staged: &Path, options: &InstallOptions, reporter: &dyn Reporter) -> Result<()> {
+    let Some(manifest) = Project::at(staged).and_then(|p| p.read_manifest().ok()) else {
+        return Ok(());
+    };
+
+    for (engine, range) in [(Engine::Host, &manifest.engines.host), (Engine::Lua, &manifest.engines.lua)] {
+        let range = range.trim();
+        if range.is_empty() || range == "*" {
+            continue;
+        }
+        match options.target.supports(engine, range) {
+            Some(true) => {},
+            Some(false) => {
+                let mismatch = VkError::EngineMismatch {
+                    package: format!("{}@{}", manifest.name, manifest.version),
+                    engine: engine.name().to_string(),
+                    range: range.to_string(),
+                    version: options.target.version(engine).map(|v| v.to_string()).unwrap_or_default(),
+                };
+                if !options.ignore_engines {
+                    return Err(mismatch.into());
+                }
+                reporter.warn(&mismatch.to_string());
+            },
+            None => {
+                let id = manifest.name.cyan();
+                reporter.detail(&t!(
+                    "install.engine_unknown",
+                    id = id,
+                    engine = engine.name(),
+                    range = range
+                ));
             },
-            Err(e) => return Err(e.into()),
         }
     }
+    Ok(())
+}
 
-    if let Some(pb) = pb {
-        pb.finish_and_clear();
+/// Checks the plugin staged to replace `installed` against the permission
+/// policy, warns about what its Lua sources do beyond its permissions, then
+/// compares its permissions with those of the installed copy. Anything new
+/// is shown and needs the user's confirmation, or `--accept-permissions`.
+fn check_permissions(installed: &Path, staged: &Path, options: &InstallOptions, reporter: &dyn Reporter) -> Result<()> {
+    let read = |dir: &Path| Project::at(dir).and_then(|p| p.read_manifest().ok());
+    let Some(new) = read(staged) else {
+        return Ok(());
+    };
+
+    let undeclared = capabilities::scan_dir(staged, new.permissions.as_ref())?;
+    validate::warn_undeclared(&new.name, &undeclared, reporter);
+
+    if let Some(policy) = &options.policy {
+        policy.check(&new.name, new.permissions.as_ref(), reporter)?;
+    }
+
+    let Some(old) = read(installed) else {
+        return Ok(());
+    };
+
+    let added = permissions::escalations(old.permissions.as_ref(), new.permissions.as_ref());
+    if added.is_empty() {
+        return Ok(());
+    }
+
+    reporter.warn(&t!(
+        "install.permissions_escalated",
+        id = new.name.cyan(),
+        version = new.version.yellow()
+    ));
+    for line in &added {
+        reporter.output(&format!("  {}", format!("+ {}", line).yellow().bold()));
+    }
+
+    if options.accept_permissions {
+        reporter.detail(&t!("install.permissions_accepted"));
+        return Ok(());
+    }
+    if reporter.confirm(&t!("install.confirm_permissions"))? {
+        return Ok(());
+    }
+    Err(VkError::PermissionsNotAccepted(new.name).into())
+}
+
+/// With `--inspect`, shows what the package staged at `staged` holds and
+/// what looks suspicious in it, and only goes on once the user agrees.
+fn inspect_staged(
+    id: &str,
+    version: &str,
+    staged: &Path,
+    options: &InstallOptions,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if !options.inspect {
+        return Ok(());
+    }
+    let inspection = inspect::inspect(staged)?;
+    let none = || t!("inspect.none").bright_black().to_string();
+
+    reporter.step(&t!("inspect.title", id = id.cyan(), version = version.yellow()));
+    reporter.output(&format!(
+        "  {}",
+        t!(
+            "inspect.files",
+            count = inspection.files,
+            size = format_bytes(inspection.size as usize)
+        )
+    ));
+    for (label, items) in [
+        (t!("inspect.permissions"), &inspection.permissions),
+        (t!("inspect.hooks"), &inspection.hooks),
+        (t!("inspect.binaries"), &inspection.binaries),
+    ] {
+        if items.is_empty() {
+            reporter.output(&format!("  {} {}", label, none()));
+            continue;
+        }
+        reporter.output(&format!("  {}", label));
+        for item in items {
+            reporter.output(&format!("    {}", item));
+        }
+    }
+    for flag in &inspection.red_flags {
+        reporter.warn(flag);
+    }
+
+    if reporter.confirm(&t!("inspect.confirm", id = id, version = version))? {
+        return Ok(());
+    }
+    Err(VkError::InstallDeclined(format!("{}@{}", id, version)).into())
+}
+
+/// Links a local dependency into the plugins directory so edits to it are
+/// picked up without reinstalling. Falls back to a copy where links are not
+/// available.
+fn install_path(
+    project: &Project,
+    id: &str,
+    source: &PathSource,
+    plugins_path: &Path,
+    options: &InstallOptions,
+    reporter: &dyn Reporter,
+) -> Result<LockedPackage> {
+    reporter.title(&t!(
+        "install.title_path",
+        id = id.cyan(),
+        path = source.path.bright_black()
+    ));
+
+    let target = project.root().join(&source.path);
+    let dependency =
+        Project::at(&target).ok_or_else(|| VkError::DependencyNotFound(format!("{} ({})", id, target.display())))?;
+    let manifest = dependency.read_manifest()?;
+    if let Some(policy) = &options.policy {
+        policy.check(&manifest.name, manifest.permissions.as_ref(), reporter)?;
+    }
+    let target = fs::canonicalize(dependency.root()).unwrap_or_else(|_| dependency.root().to_path_buf());
+
+    let plugin_path = plugins_path.join(id);
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+
+    if link_or_copy_dir(&target, &plugin_path).with_context(|| t!("install.create_plugin_dir_failed"))? {
+        reporter.done(&t!(
+            "install.path_linked",
+            path = plugin_path.display().to_string().bright_black(),
+            target = target.display().to_string().bright_black()
+        ));
+    } else {
+        reporter.done(&t!(
+            "install.path_copied",
+            path = plugin_path.display().to_string().bright_black()
+        ));
+    }
+
+    Ok(LockedPackage::path(id, manifest.version, source))
+}
+
+/// Checks a downloaded archive against the checksum the registry sent with it.
+pub fn verify_checksum(data: &[u8], meta: &DownloadMeta, reporter: &dyn Reporter) -> Result<()> {
+    if let Some(expected) = &meta.checksum {
+        let actual = meta.sha256.clone().unwrap_or_else(|| sha256_hex(data));
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(
+                VkError::ChecksumMismatch { package: meta.id.clone(), expected: expected.clone(), actual }.into(),
+            );
+        }
+        reporter.success(&t!("install.checksum_verified", checksum = expected.bright_black()));
     }
+    Ok(())
+}
+
+/// Where to fetch an archive from, when the registry does not serve it.
+#[derive(Deserialize)]
+struct DownloadLink {
+    #[serde(alias = "downloadUrl")]
+    url: String,
+    checksum: Option<String>,
+    version: Option<String>,
+}
+
+pub fn download_plugin(
+    id: &str,
+    version: Option<&str>,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<(Vec<u8>, DownloadMeta)> {
+    let (mut meta, response) = request_archive(id, version, http_client)?;
+    let pb = reporter.progress(response.content_length(), &t!("install.downloading"));
+
+    let start = Instant::now();
+    let buffer = read_archive(response, &mut meta, &pb, http_client)?;
+    pb.finish();
 
     let elapsed = start.elapsed().as_secs_f64();
-    println!("{} Download completed in {:.2}s", "✓".green(), elapsed);
+    reporter.success(&t!("install.download_completed", seconds = format!("{:.2}", elapsed)));
 
     Ok((buffer, meta))
 }
+
+/// Asks the registry for an archive, following the link it may answer with.
+fn request_archive(
+    id: &str,
+    version: Option<&str>,
+    http_client: &HttpClient,
+) -> Result<(DownloadMeta, reqwest::blocking::Response)> {
+    let Download { headers, response } =
+        RegistryClient::new(http_client).download(id, version).map_err(|e| VkError::package_lookup(e, id))?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    let mut checksum = header("X-Checksum");
+    let mut plugin_version = header("X-Plugin-Version");
+
+    // The registry may answer with a link to the archive, e.g. a pre-signed
+    // CDN URL, instead of the archive itself
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let response = if is_json {
+        let link: DownloadLink = HttpClient::parse_json(response)?;
+        checksum = link.checksum.or(checksum);
+        plugin_version = link.version.or(plugin_version);
+        http_client.fetch(&link.url)?
+    } else {
+        response
+    };
+
+    let plugin_version = plugin_version.or_else(|| version.map(String::from)).unwrap_or_else(|| "unknown".to_string());
+
+    Ok((
+        DownloadMeta {
+            id: id.to_string(),
+            version: plugin_version,
+            checksum,
+            sha256: None,
+        },
+        response,
+    ))
+}
+
+/// Reads an archive as it arrives, hashing each chunk on the way so the
+/// checksum is ready when the download ends.
+fn read_archive(
+    mut response: reqwest::blocking::Response,
+    meta: &mut DownloadMeta,
+    pb: &Progress,
+    http_client: &HttpClient,
+) -> Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(response.content_length().unwrap_or_default() as usize);
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0u8; 32 * 1024]; // 32KB chunks
+
+    loop {
+        match response.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                buffer.extend_from_slice(&chunk[..n]);
+                hasher.update(&chunk[..n]);
+                pb.inc(n as u64);
+            },
+            Err(e) => return Err(http_client.read_error(e).into()),
+        }
+    }
+
+    meta.sha256 = Some(hex::encode(hasher.finalize()));
+    Ok(buffer)
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+
+use crate::commands::install::is_linked;
+use crate::config;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::{remove_installed, symlink_dir};
+
+/// Without a name, registers the current plugin in the global links directory.
+/// With a name, links that registered plugin into the project's plugins
+/// directory, where it shadows the registry version until `vk unlink`.
+pub fn link(project: &Project, name: Option<&str>, plugins_dir: &str, reporter: &dyn Reporter) -> Result<()> {
+    let links_dir = config::links_dir()?;
+
+    let Some(name) = name else {
+        let manifest = project.read_manifest()?;
+        reporter.title(&t!("link.title_register", name = manifest.name.cyan()));
+
+        fs::create_dir_all(&links_dir).with_context(|| t!("common.write_failed", path = links_dir.display()))?;
+        let entry = links_dir.join(&manifest.name);
+        remove_installed(&entry).with_context(|| t!("common.write_failed", path = entry.display()))?;
+        symlink_dir(project.root(), &entry).with_context(|| t!("link.symlink_failed", path = entry.display()))?;
+
+        reporter.done(&t!(
+            "link.registered",
+            name = manifest.name.cyan(),
+            path = project.root().display().to_string().bright_black()
+        ));
+        return Ok(());
+    };
+
+    reporter.title(&t!("link.title", name = name.cyan()));
+
+    let entry = links_dir.join(name);
+    let Ok(target) = fs::read_link(&entry) else {
+        anyhow::bail!(t!("link.not_registered", name = name));
+    };
+
+    let plugins_path = project.root().join(plugins_dir);
+    fs::create_dir_all(&plugins_path).with_context(|| t!("install.create_plugins_dir_failed"))?;
+
+    // Point at the registry entry rather than the plugin itself, so running
+    // `vk link` again from a moved checkout updates every consumer
+    let plugin_path = plugins_path.join(name);
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+    symlink_dir(&entry, &plugin_path).with_context(|| t!("link.symlink_failed", path = plugin_path.display()))?;
+
+    reporter.done(&t!(
+        "link.linked",
+        path = plugin_path.display().to_string().bright_black(),
+        target = target.display().to_string().bright_black()
+    ));
+
+    Ok(())
+}
+
+/// Reverses [`link`]: removes the global registration of the current plugin,
+/// or the named link from the project's plugins directory.
+pub fn unlink(project: &Project, name: Option<&str>, plugins_dir: &str, reporter: &dyn Reporter) -> Result<()> {
+    let links_dir = config::links_dir()?;
+
+    let Some(name) = name else {
+        let manifest = project.read_manifest()?;
+        reporter.title(&t!("link.title_unregister", name = manifest.name.cyan()));
+
+        let entry = links_dir.join(&manifest.name);
+        if fs::symlink_metadata(&entry).is_err() {
+            reporter.info(&t!("link.not_registered", name = manifest.name));
+            return Ok(());
+        }
+        remove_installed(&entry).with_context(|| t!("common.write_failed", path = entry.display()))?;
+
+        reporter.done(&t!("link.unregistered", name = manifest.name.cyan()));
+        return Ok(());
+    };
+
+    reporter.title(&t!("link.title_unlink", name = name.cyan()));
+
+    let plugin_path = project.root().join(plugins_dir).join(name);
+    if !is_linked(&plugin_path) {
+        anyhow::bail!(t!("link.not_linked", name = name));
+    }
+    remove_installed(&plugin_path).with_context(|| t!("install.remove_old_failed"))?;
+
+    reporter.done(&t!("link.unlinked", name = name.cyan()));
+    reporter.info(&t!("link.reinstall_hint"));
+
+    Ok(())
+}
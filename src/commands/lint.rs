@@ -0,0 +1,426 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use semver::Version;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::commands::fmt::FmtConfig;
+use crate::encoding::json5::document::{Container, Item, Key, Node};
+use crate::encoding::json5::{self, Document};
+use crate::error::VkError;
+use crate::git;
+use crate::i18n::t;
+use crate::manifest::{DEPRECATED_FIELDS, Dependency, MANIFEST_FIELDS, PluginManifest};
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::resolver;
+
+/// Rule settings, read from the project root.
+pub const CONFIG_FILENAME: &str = ".vklint.json5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Warn,
+    /// Fails `vk lint`.
+    Error,
+}
+
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+}
+
+/// Every rule, with the severity it has unless `.vklint.json5` says otherwise.
+pub const RULES: &[Rule] = &[
+    Rule { id: "missing-description", severity: Severity::Warn },
+    Rule { id: "missing-keywords", severity: Severity::Warn },
+    Rule { id: "missing-repository", severity: Severity::Warn },
+    Rule { id: "missing-homepage", severity: Severity::Warn },
+    Rule { id: "wildcard-version", severity: Severity::Error },
+    Rule { id: "broad-network", severity: Severity::Error },
+    Rule { id: "deprecated-field", severity: Severity::Warn },
+    Rule { id: "unknown-field", severity: Severity::Warn },
+    Rule { id: "legacy-manifest", severity: Severity::Warn },
+];
+
+/// The contents of `.vklint.json5`: a severity for each rule to change,
+/// e.g. `{ rules: { "missing-homepage": "off" } }`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub rules: BTreeMap<String, Severity>,
+}
+
+impl LintConfig {
+    pub fn load(project: &Project) -> Result<Self> {
+        let path = project.root().join(CONFIG_FILENAME);
+        if !path.is_file() {
+            return Ok(LintConfig::default());
+        }
+        let content = fs::read_to_string(&path).with_context(|| t!("common.read_failed", path = path.display()))?;
+        let config: LintConfig =
+            json5::from_str(&content).with_context(|| t!("lint.config_invalid", path = path.display()))?;
+        if let Some(unknown) = config.rules.keys().find(|id| !RULES.iter().any(|rule| rule.id == *id)) {
+            anyhow::bail!(t!("lint.unknown_rule", rule = unknown, path = path.display()));
+        }
+        Ok(config)
+    }
+
+    pub fn severity(&self, rule: &str) -> Severity {
+        self.rules
+            .get(rule)
+            .copied()
+            .or_else(|| RULES.iter().find(|r| r.id == rule).map(|r| r.severity))
+            .unwrap_or(Severity::Off)
+    }
+}
+
+/// A change to the manifest that settles a finding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    /// Renames a top-level field, keeping its value and comments.
+    RenameField { from: String, to: String },
+    /// Adds a git `repository` with this URL.
+    SetRepository(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl Finding {
+    fn new(rule: &'static str, message: String) -> Self {
+        Finding { rule, message, fix: None }
+    }
+}
+
+/// Findings on the top-level field names of `document`. These are checked
+/// before the manifest is parsed, so `--fix` can repair fields it needs.
+pub fn check_fields(document: &Document) -> Vec<Finding> {
+    let Node::Object(root) = &document.root.value else {
+        return Vec::new();
+    };
+    let names: Vec<&str> = root.items.iter().filter_map(|item| item.key.as_ref()).map(|k| k.name.as_str()).collect();
+
+    let mut findings = Vec::new();
+    for name in &names {
+        if let Some((_, replacement)) = DEPRECATED_FIELDS.iter().find(|(from, _)| from == name) {
+            let mut finding = Finding::new(
+                "deprecated-field",
+                t!("lint.deprecated_field", field = name, replacement = replacement),
+            );
+            // Renaming onto a field that is there would lose one of them
+            if !names.contains(replacement) {
+                finding.fix = Some(Fix::RenameField { from: name.to_string(), to: replacement.to_string() });
+            }
+            findings.push(finding);
+        } else if !MANIFEST_FIELDS.contains(name) {
+            findings.push(Finding::new("unknown-field", t!("lint.unknown_field", field = name)));
+        }
+    }
+    findings
+}
+
+/// Findings on the contents of `manifest`. `origin` is the URL of the
+/// project's git remote, offered as the fix for a missing repository.
+pub fn check_manifest(manifest: &PluginManifest, origin: Option<&str>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if manifest.description.trim().is_empty() {
+        findings.push(Finding::new(
+            "missing-description",
+            t!("lint.missing_field", field = "description"),
+        ));
+    }
+    if manifest.keywords.is_empty() {
+        findings.push(Finding::new(
+            "missing-keywords",
+            t!("lint.missing_field", field = "keywords"),
+        ));
+    }
+    if manifest.repository.as_ref().is_none_or(|repository| repository.url.is_empty()) {
+        findings.push(Finding {
+            rule: "missing-repository",
+            message: t!("lint.missing_field", field = "repository"),
+            fix: origin.filter(|_| manifest.repository.is_none()).map(|url| Fix::SetRepository(url.to_string())),
+        });
+    }
+    if manifest.homepage.as_deref().is_none_or(str::is_empty) {
+        findings.push(Finding::new(
+            "missing-homepage",
+            t!("lint.missing_field", field = "homepage"),
+        ));
+    }
+
+    let mut sections: Vec<(&str, Vec<(&String, &str)>)> = vec![
+        ("dependencies", versions(manifest.dependencies.iter())),
+        ("dev_dependencies", versions(manifest.dev_dependencies.iter().flatten())),
+        ("optional_dependencies", versions(manifest.optional_dependencies.iter())),
+        ("overrides", versions(manifest.overrides.iter())),
+        (
+            "host_dependencies",
+            manifest.host_dependencies.iter().flatten().map(|(name, spec)| (name, spec.as_str())).collect(),
+        ),
+    ];
+    for target in &manifest.target_dependencies {
+        sections.push(("target_dependencies", versions(target.dependencies.iter())));
+    }
+    for (section, mut specs) in sections {
+        specs.sort();
+        for (name, spec) in specs.into_iter().filter(|(_, spec)| is_wildcard(spec)) {
+            findings.push(Finding::new(
+                "wildcard-version",
+                t!("lint.wildcard_version", name = name, spec = spec, section = section),
+            ));
+        }
+    }
+
+    let outbound = manifest.permissions.iter().filter_map(|p| p.network.as_ref()).flat_map(|n| &n.allow_outbound);
+    for host in outbound.filter(|host| is_any_host(host)) {
+        findings.push(Finding::new("broad-network", t!("lint.broad_network", host = host)));
+    }
+
+    findings
+}
+
+fn versions<'a>(deps: impl Iterator<Item = (&'a String, &'a Dependency)>) -> Vec<(&'a String, &'a str)> {
+    deps.filter_map(|(name, dep)| dep.version().map(|spec| (name, spec))).collect()
+}
+
+/// Whether `spec` is a range that takes any version at all. Dist-tags such
+/// as `beta` are not ranges.
+fn is_wildcard(spec: &str) -> bool {
+    resolver::parse_req(spec).is_some_and(|req| {
+        resolver::matches(&req, &Version::new(0, 0, 0), false)
+            && resolver::matches(&req, &Version::new(u64::MAX, 0, 0), false)
+    })
+}
+
+/// Whether the outbound pattern `host` matches every host, like `*` or `*.*`.
+fn is_any_host(host: &str) -> bool {
+    host.contains('*') && host.chars().all(|c| c == '*' || c == '.')
+}
+
+/// Applies `fix` to the manifest `document`.
+fn apply(document: &mut Document, fix: &Fix) {
+    let Node::Object(root) = &mut document.root.value else {
+        return;
+    };
+    match fix {
+        Fix::RenameField { from, to } => {
+            if let Some(key) = root.items.iter_mut().filter_map(|item| item.key.as_mut()).find(|k| k.name == *from) {
+                key.name = to.clone();
+            }
+        },
+        Fix::SetRepository(url) => {
+            let entry = |name: &str, value: Node| Item {
+                comments: Vec::new(),
+                key: Some(Key { name: name.to_string(), quote: None }),
+                value,
+                trailing: None,
+                blank_before: false,
+            };
+            let string = |value: &str| Node::String { value: value.to_string(), quote: b'"' };
+            let repository = entry(
+                "repository",
+                Node::Object(Container {
+                    items: vec![entry("type", string("git")), entry("url", string(url))],
+                    dangling: Vec::new(),
+                }),
+            );
+
+            // After the last field vk writes before it
+            let rank = |item: &Item| {
+                item.key
+                    .as_ref()
+                    .and_then(|k| MANIFEST_FIELDS.iter().position(|f| *f == k.name))
+                    .unwrap_or(usize::MAX)
+            };
+            let own = rank(&repository);
+            let at = root.items.iter().rposition(|item| rank(item) < own).map_or(0, |i| i + 1);
+            root.items.insert(at, repository);
+        },
+    }
+}
+
+/// Drops the findings of rules that are off and, given a `document` to
+/// fix, fixes what can be, moving those findings to `fixed`. Returns the
+/// findings left.
+fn settle(
+    findings: Vec<Finding>,
+    config: &LintConfig,
+    mut document: Option<&mut Document>,
+    fixed: &mut Vec<Finding>,
+) -> Vec<Finding> {
+    let mut remaining = Vec::new();
+    for finding in findings.into_iter().filter(|f| config.severity(f.rule) != Severity::Off) {
+        match (&mut document, &finding.fix) {
+            (Some(document), Some(change)) => {
+                apply(document, change);
+                fixed.push(finding);
+            },
+            _ => remaining.push(finding),
+        }
+    }
+    remaining
+}
+
+/// Checks the project's manifest against the lint rules, failing when any
+/// rule set to `error` finds something. With `fix`, the findings that can
+/// be fixed safely are fixed in place, keeping the file's comments.
+pub fn lint_project(project: &Project, fix: bool, reporter: &dyn Reporter) -> Result<()> {
+    let config = LintConfig::load(project)?;
+    let path = project.manifest_path();
+    reporter.title(&t!("lint.title", file = project.manifest_filename().cyan()));
+
+    let source = fs::read_to_string(path).with_context(|| t!("manifest.read_failed"))?;
+    let mut document = Document::parse(&source)
+        .map_err(|e| VkError::ManifestInvalid(e.to_string()))
+        .with_context(|| t!("manifest.parse_failed"))?;
+    // Legacy manifests are plain JSON, which fixes would not keep
+    let fix = fix && !project.is_legacy();
+
+    let mut findings = check_fields(&document);
+    if project.is_legacy() {
+        findings.push(Finding::new(
+            "legacy-manifest",
+            t!("lint.legacy_manifest", file = project.manifest_filename()),
+        ));
+    }
+    let mut fixed = Vec::new();
+    let mut remaining = settle(findings, &config, fix.then_some(&mut document), &mut fixed);
+
+    let fmt_config = FmtConfig::find(project.root())?;
+    let text = if fixed.is_empty() {
+        source.clone()
+    } else {
+        document.format(&fmt_config.style())
+    };
+    let manifest: PluginManifest = match json5::from_str(&text) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            // What was found so far may be why it does not parse
+            report(&remaining, &config, reporter);
+            return Err(
+                anyhow::Error::from(VkError::ManifestInvalid(e.to_string())).context(t!("manifest.parse_failed"))
+            );
+        },
+    };
+
+    // Only asked for when there is no repository to fill in
+    let origin = manifest
+        .repository
+        .is_none()
+        .then(|| git::run(project.root(), &["remote", "get-url", "origin"]).ok())
+        .flatten()
+        .filter(|url| !url.is_empty());
+    let findings = check_manifest(&manifest, origin.as_deref());
+    remaining.extend(settle(findings, &config, fix.then_some(&mut document), &mut fixed));
+
+    if !fixed.is_empty() {
+        fs::write(path, document.format(&fmt_config.style())).with_context(|| t!("manifest.write_failed"))?;
+        for finding in &fixed {
+            reporter.step(&t!(
+                "lint.fixed",
+                rule = finding.rule.dimmed(),
+                message = finding.message
+            ));
+        }
+    }
+
+    let errors = report(&remaining, &config, reporter);
+    if errors > 0 {
+        return Err(VkError::LintFailed { count: errors }.into());
+    }
+    if remaining.is_empty() {
+        reporter.done(&t!("lint.clean"));
+    } else {
+        reporter.done(&t!("lint.done", warnings = remaining.len()));
+    }
+    Ok(())
+}
+
+/// Reports `findings` at the severity of their rules, returning how many
+/// are errors.
+fn report(findings: &[Finding], config: &LintConfig, reporter: &dyn Reporter) -> usize {
+    let mut errors = 0;
+    for finding in findings {
+        let mut line = format!("{} {}", finding.rule.dimmed(), finding.message);
+        if finding.fix.is_some() {
+            line.push_str(&format!(" {}", t!("lint.fixable").dimmed()));
+        }
+        if config.severity(finding.rule) == Severity::Error {
+            errors += 1;
+            reporter.error(&line);
+        } else {
+            reporter.warn(&line);
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(findings: &[Finding]) -> Vec<&str> {
+        findings.iter().map(|f| f.rule).collect()
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(is_wildcard("*"));
+        assert!(is_wildcard(""));
+        assert!(is_wildcard(">=0.0.0"));
+        assert!(!is_wildcard("^1.2.0"));
+        assert!(!is_wildcard(">=1.0.0"));
+        assert!(!is_wildcard("beta"));
+
+        assert!(is_any_host("*"));
+        assert!(is_any_host("*.*"));
+        assert!(!is_any_host("*.example.com"));
+    }
+
+    #[test]
+    fn test_fields() {
+        let document =
+            Document::parse("{ name: \"x\", displayName: \"X\", devDependencies: {}, dev_dependencies: {}, extra: 1 }")
+                .unwrap();
+        let findings = check_fields(&document);
+        assert_eq!(
+            rules(&findings),
+            ["deprecated-field", "deprecated-field", "unknown-field"]
+        );
+        assert_eq!(
+            findings[0].fix,
+            Some(Fix::RenameField { from: "displayName".into(), to: "display_name".into() })
+        );
+        // `dev_dependencies` is already there
+        assert_eq!(findings[1].fix, None);
+    }
+
+    #[test]
+    fn test_set_repository() {
+        let mut document =
+            Document::parse("{\n    name: \"x\",\n    // where\n    homepage: \"h\",\n    main: \"m\"\n}\n").unwrap();
+        apply(&mut document, &Fix::SetRepository("https://git.example/x".into()));
+        assert_eq!(
+            document.format(&Default::default()),
+            "{\n    name: \"x\",\n    // where\n    homepage: \"h\",\n    repository: {\n        type: \"git\",\n        url: \"https://git.example/x\"\n    },\n    main: \"m\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_config_severity() {
+        let config: LintConfig = json5::from_str("{ rules: { \"missing-homepage\": \"off\" } }").unwrap();
+        assert_eq!(config.severity("missing-homepage"), Severity::Off);
+        assert_eq!(config.severity("wildcard-version"), Severity::Error);
+        assert!(json5::from_str::<LintConfig>("{ rule: {} }").is_err());
+    }
+}
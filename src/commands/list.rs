@@ -1,33 +1,46 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::Colorize;
 use std::path::Path;
 use std::{collections::HashMap, fs};
 
 use crate::encoding::json5;
-use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
+use crate::i18n::t;
+use crate::manifest::Dependency;
+use crate::project::Project;
+use crate::reporter::Reporter;
 
-pub fn list_dependencies(depth: Option<usize>) -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
-    let content = fs::read_to_string(manifest_path).context("Failed to read manifest file")?;
-    let manifest: PluginManifest = json5::from_str(&content).context("Failed to parse manifest file")?;
+pub fn list_dependencies(project: &Project, depth: Option<usize>, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
 
-    println!("{}", "📦 Dependencies".bold().cyan());
-    println!("{}", "═".repeat(40).bright_black());
-    println!();
+    reporter.title(&t!("list.title"));
 
     let max_depth = depth.unwrap_or(usize::MAX);
 
-    let has_deps = print_dependencies_section(&manifest.dependencies, "", max_depth)?;
-    let has_dev_deps = print_dependencies_section(&manifest.dev_dependencies.unwrap_or_default(), "dev ", max_depth)?;
+    let lock_path = project.lockfile_path();
+
+    let has_deps = print_dependencies_section(&manifest.dependencies, "", max_depth, &lock_path, reporter)?;
+    let has_dev_deps = print_dependencies_section(
+        &manifest.dev_dependencies.unwrap_or_default(),
+        "dev ",
+        max_depth,
+        &lock_path,
+        reporter,
+    )?;
 
     if !has_deps && !has_dev_deps {
-        println!("{} No dependencies found", "📭".yellow());
+        reporter.info(&t!("list.empty"));
     }
 
     Ok(())
 }
 
-fn print_dependencies_section(deps: &HashMap<String, String>, prefix: &str, max_depth: usize) -> Result<bool> {
+fn print_dependencies_section(
+    deps: &HashMap<String, Dependency>,
+    prefix: &str,
+    max_depth: usize,
+    lock_path: &Path,
+    reporter: &dyn Reporter,
+) -> Result<bool> {
     let mut has_any = false;
 
     if !deps.is_empty() {
@@ -36,34 +49,45 @@ fn print_dependencies_section(deps: &HashMap<String, String>, prefix: &str, max_
         } else {
             format!("{}dependencies", prefix)
         };
-        println!("{}", title.bold().green());
-
-        for (name, version) in deps {
-            let version_str = version.as_str();
-            println!(
-                "{} {}",
-                format!("{}{}", prefix, name).cyan(),
-                format!("@{}", version_str).yellow()
-            );
+        reporter.output(&title.bold().green().to_string());
+
+        for (name, dep) in deps {
+            match dep {
+                Dependency::Version(version) => reporter.output(&format!(
+                    "{} {}",
+                    format!("{}{}", prefix, name).cyan(),
+                    format!("@{}", version).yellow()
+                )),
+                Dependency::Git(source) => reporter.output(&format!(
+                    "{} {} {}",
+                    format!("{}{}", prefix, name).cyan(),
+                    "(git)".magenta(),
+                    source.to_string().bright_black()
+                )),
+                Dependency::Path(source) => reporter.output(&format!(
+                    "{} {} {}",
+                    format!("{}{}", prefix, name).cyan(),
+                    "(path)".blue(),
+                    source.path.bright_black()
+                )),
+            }
 
             if max_depth > 1 {
-                print_transitive_deps(name, max_depth - 1, "  ");
+                print_transitive_deps(lock_path, name, max_depth - 1, "  ", reporter);
             }
 
             has_any = true;
         }
-        println!();
     }
 
     Ok(has_any)
 }
 
-fn print_transitive_deps(package: &str, depth: usize, indent: &str) {
+fn print_transitive_deps(lock_path: &Path, package: &str, depth: usize, indent: &str, reporter: &dyn Reporter) {
     if depth == 0 {
         return;
     }
 
-    let lock_path = Path::new("vayload.lock");
     if !lock_path.exists() {
         return;
     }
@@ -76,15 +100,21 @@ fn print_transitive_deps(package: &str, depth: usize, indent: &str) {
                     if pkg.get("id").and_then(|i| i.as_str()) == Some(package) {
                         if let Some(deps) = pkg.get("dependencies").and_then(|d| d.as_object()) {
                             for (name, version) in deps {
-                                println!(
+                                reporter.output(&format!(
                                     "{}{}{} @ {}",
                                     indent,
                                     "├─ ".bright_black(),
                                     name.cyan(),
                                     version.as_str().unwrap_or("*").yellow()
-                                );
+                                ));
                                 if depth > 1 {
-                                    print_transitive_deps(name, depth - 1, &format!("{}  ", indent));
+                                    print_transitive_deps(
+                                        lock_path,
+                                        name,
+                                        depth - 1,
+                                        &format!("{}  ", indent),
+                                        reporter,
+                                    );
                                 }
                             }
                         }
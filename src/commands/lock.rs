@@ -0,0 +1,308 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+
+use crate::commands::install::{declared_dependencies, satisfies};
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::manifest::Dependency;
+use crate::project::{LOCKFILE_NAME, Project};
+use crate::reporter::Reporter;
+use crate::target::Target;
+
+/// Formats `vk lock export` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// The lockfile as plain JSON, for tools without a JSON5 parser.
+    Json,
+}
+
+/// A package as an npm or yarn lockfile records it.
+#[derive(Debug, PartialEq)]
+struct ForeignPackage {
+    name: String,
+    version: String,
+    resolved: Option<String>,
+    /// Ranges the entry was resolved for (yarn only).
+    ranges: Vec<String>,
+}
+
+/// Writes the lockfile in another format, to `output` or the standard output.
+pub fn export_lockfile(
+    project: &Project,
+    format: ExportFormat,
+    output: Option<&str>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if !project.lockfile_path().exists() {
+        anyhow::bail!(t!("lock.export_missing", file = LOCKFILE_NAME));
+    }
+    let lockfile = Lockfile::load(project)?;
+
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&lockfile)?,
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, content + "\n").with_context(|| t!("common.write_failed", path = path))?;
+            reporter.success(&t!(
+                "lock.exported",
+                count = lockfile.packages.len(),
+                path = path.cyan()
+            ));
+        },
+        None => reporter.output(&content),
+    }
+    Ok(())
+}
+
+/// Pins the manifest dependencies at the versions an npm `package-lock.json`
+/// or a `yarn.lock` records, e.g. when moving a project over to vk. Entries
+/// for other packages are left alone.
+pub fn import_lockfile(project: &Project, file: &str, reporter: &dyn Reporter) -> Result<()> {
+    let content = fs::read_to_string(file).with_context(|| t!("common.read_failed", path = file))?;
+    let foreign = if content.trim_start().starts_with('{') {
+        parse_npm(&content).with_context(|| t!("lock.import_invalid", path = file))?
+    } else {
+        parse_yarn(&content)
+    };
+
+    let mut lockfile = Lockfile::load(project)?;
+    let deps = declared_dependencies(&project.read_manifest()?, &lockfile.features, None);
+
+    reporter.title(&t!("lock.import_title", path = file.cyan()));
+
+    let mut imported = 0;
+    for (id, dep) in &deps {
+        let Some(locked) = pick(id, dep, &foreign) else {
+            reporter.warn(&t!("lock.import_missing", id = id.cyan()));
+            continue;
+        };
+        reporter.success(&t!(
+            "lock.import_pinned",
+            id = id.cyan(),
+            version = locked.version.yellow()
+        ));
+        lockfile.upsert(locked);
+        imported += 1;
+    }
+
+    lockfile.save(project)?;
+    reporter.done(&t!("lock.import_done", count = imported, total = deps.len()));
+    Ok(())
+}
+
+/// The lock entry for a manifest dependency, from the foreign package that
+/// matches it: the one resolved for the same range, else one satisfying it.
+fn pick(id: &str, dep: &Dependency, foreign: &[ForeignPackage]) -> Option<LockedPackage> {
+    let mut candidates = foreign.iter().filter(|p| p.name == id);
+
+    match dep {
+        Dependency::Version(spec) => {
+            let all: Vec<_> = candidates.collect();
+            let package = all
+                .iter()
+                .find(|p| p.ranges.contains(spec))
+                .or_else(|| all.iter().find(|p| satisfies(spec, &p.version)))?;
+            Some(LockedPackage {
+                id: id.to_string(),
+                version: package.version.clone(),
+                source: None,
+                checksum: None,
+                integrity: None,
+                dependencies: Default::default(),
+                overridden: false,
+            })
+        },
+        // Only a resolved commit pins a git dependency
+        Dependency::Git(source) => candidates.find_map(|p| {
+            let (_, commit) = p.resolved.as_deref()?.rsplit_once('#')?;
+            let is_commit = commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit());
+            is_commit.then(|| LockedPackage::git(id, p.version.clone(), source, commit))
+        }),
+        Dependency::Path(source) => candidates.next().map(|p| LockedPackage::path(id, p.version.clone(), source)),
+    }
+}
+
+/// Reads the top-level packages of a `package-lock.json`: the `packages` map
+/// of lockfile versions 2 and 3, or the `dependencies` map of version 1.
+fn parse_npm(content: &str) -> Result<Vec<ForeignPackage>> {
+    let root: Value = serde_json::from_str(content)?;
+    let entry = |name: &str, value: &Value| {
+        Some(ForeignPackage {
+            name: name.to_string(),
+            version: value.get("version")?.as_str()?.to_string(),
+            resolved: value.get("resolved").and_then(Value::as_str).map(String::from),
+            ranges: Vec::new(),
+        })
+    };
+
+    if let Some(packages) = root.get("packages").and_then(Value::as_object) {
+        return Ok(packages
+            .iter()
+            .filter_map(|(path, value)| {
+                let name = path.strip_prefix("node_modules/").filter(|n| !n.contains("/node_modules/"))?;
+                entry(name, value)
+            })
+            .collect());
+    }
+
+    Ok(root
+        .get("dependencies")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| entry(name, value))
+        .collect())
+}
+
+/// Reads a classic `yarn.lock`: blocks headed by the `name@range` specifiers
+/// they resolve, with indented `version` and `resolved` fields.
+fn parse_yarn(content: &str) -> Vec<ForeignPackage> {
+    let mut packages = Vec::new();
+    let mut current: Option<ForeignPackage> = None;
+
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if !line.starts_with(' ') {
+            packages.extend(current.take().filter(|p| !p.version.is_empty()));
+            let mut name = String::new();
+            let mut ranges = Vec::new();
+            for specifier in line.trim_end_matches(':').split(',') {
+                let specifier = specifier.trim().trim_matches('"');
+                // Scoped names start with `@`, so split on the last one
+                if let Some(at) = specifier.rfind('@').filter(|&at| at > 0) {
+                    name = specifier[..at].to_string();
+                    ranges.push(specifier[at + 1..].to_string());
+                }
+            }
+            current = Some(ForeignPackage { name, version: String::new(), resolved: None, ranges });
+            continue;
+        }
+
+        let Some(package) = current.as_mut() else {
+            continue;
+        };
+        if let Some((key, value)) = line.trim().split_once(' ') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key {
+                "version" => package.version = value,
+                "resolved" => package.resolved = Some(value),
+                _ => {},
+            }
+        }
+    }
+
+    packages.extend(current.filter(|p| !p.version.is_empty()));
+    packages
+}
+
+/// Fails when the lockfile does not pin exactly the dependencies the manifest
+/// declares, e.g. after the manifest was edited without running `vk install`.
+pub fn check_lockfile(project: &Project, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+    let lockfile = Lockfile::load(project)?;
+    let deps = declared_dependencies(&manifest, &lockfile.features, None);
+    let here = declared_dependencies(&manifest, &lockfile.features, Some(&Target::local()));
+
+    let mut problems = Vec::new();
+    for (id, dep) in &deps {
+        let Some(locked) = lockfile.get(id) else {
+            // Installs elsewhere lock what only applies there
+            if here.contains_key(id) {
+                problems.push(t!("lock.check_missing", id = id.cyan()));
+            }
+            continue;
+        };
+        let in_sync = locked.overridden == manifest.overrides.contains_key(id)
+            && match dep {
+                Dependency::Version(spec) => locked.is_registry() && satisfies(spec, &locked.version),
+                Dependency::Git(source) => locked.git_commit(source).is_some(),
+                Dependency::Path(source) => locked.source == LockedPackage::path(id, String::new(), source).source,
+            };
+        if !in_sync {
+            problems.push(t!(
+                "lock.check_changed",
+                id = id.cyan(),
+                version = locked.version.yellow()
+            ));
+        }
+    }
+    for package in lockfile.packages.iter().filter(|p| !deps.contains_key(&p.id)) {
+        problems.push(t!("lock.check_extra", id = package.id.cyan()));
+    }
+
+    if problems.is_empty() {
+        reporter.success(&t!("lock.check_ok", count = deps.len()));
+        return Ok(());
+    }
+
+    for problem in &problems {
+        reporter.error(problem);
+    }
+    Err(VkError::LockOutOfSync {
+        file: project.manifest_filename().to_string(),
+        count: problems.len(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_npm() {
+        let v3 = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "app" },
+                "node_modules/hello": { "version": "1.2.0", "resolved": "https://r.example/hello-1.2.0.tgz" },
+                "node_modules/hello/node_modules/dep": { "version": "0.1.0" }
+            }
+        }"#;
+        let packages = parse_npm(v3).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(
+            (packages[0].name.as_str(), packages[0].version.as_str()),
+            ("hello", "1.2.0")
+        );
+
+        let v1 = r#"{ "lockfileVersion": 1, "dependencies": { "hello": { "version": "1.0.0" } } }"#;
+        assert_eq!(parse_npm(v1).unwrap()[0].version, "1.0.0");
+    }
+
+    #[test]
+    fn test_parse_yarn() {
+        let content = "# yarn lockfile v1\n\n\
+            hello@^1.0.0, hello@^1.1.0:\n  version \"1.1.4\"\n  resolved \"https://r.example/hello-1.1.4.tgz\"\n\n\
+            \"@scope/tools@~2.0.0\":\n  version \"2.0.3\"\n";
+        let packages = parse_yarn(content);
+        assert_eq!(
+            packages[0],
+            ForeignPackage {
+                name: "hello".into(),
+                version: "1.1.4".into(),
+                resolved: Some("https://r.example/hello-1.1.4.tgz".into()),
+                ranges: vec!["^1.0.0".into(), "^1.1.0".into()],
+            }
+        );
+        assert_eq!(packages[1].name, "@scope/tools");
+        assert_eq!(packages[1].ranges, ["~2.0.0"]);
+    }
+
+    #[test]
+    fn test_pick() {
+        let foreign = parse_yarn("hello@^1.0.0:\n  version \"1.3.0\"\nhello@^2.0.0:\n  version \"2.1.0\"\n");
+        let pick_version = |spec: &str| pick("hello", &Dependency::Version(spec.into()), &foreign).map(|l| l.version);
+        assert_eq!(pick_version("^2.0.0").as_deref(), Some("2.1.0"));
+        assert_eq!(pick_version("~1.3.0").as_deref(), Some("1.3.0"));
+        assert_eq!(pick_version("^3.0.0"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::events;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::reporter::Reporter;
+
+/// Environment variable holding the token sent to the host, for hosts that
+/// require one.
+pub const HOST_TOKEN_VAR: &str = "VK_HOST_TOKEN";
+
+pub struct LogsOptions<'a> {
+    /// Keep the connection open and print lines as the host writes them.
+    pub follow: bool,
+    /// Only show the lines of this plugin.
+    pub plugin: Option<&'a str>,
+    /// A duration (`10m`, `2h`) or a timestamp the host understands.
+    pub since: Option<&'a str>,
+    /// Print the entries as the host sends them, one JSON object per line.
+    pub json: bool,
+}
+
+/// One line of the host log, as sent by its `/logs` endpoint.
+#[derive(Debug, Deserialize)]
+struct Entry {
+    #[serde(default, alias = "timestamp")]
+    time: Option<String>,
+    #[serde(default)]
+    level: Option<String>,
+    #[serde(default)]
+    plugin: Option<String>,
+    #[serde(alias = "msg")]
+    message: String,
+}
+
+/// Prints the log of the host `http_client` points at, read from its
+/// `/logs` endpoint as server-sent events or newline-delimited JSON. Lines
+/// that are not JSON are printed as they come, unless filtering by plugin.
+/// Followed event streams resume where they left off when the connection
+/// drops.
+pub fn show_logs(http_client: &HttpClient, options: &LogsOptions, reporter: &dyn Reporter) -> Result<()> {
+    let since = options.since.map(|since| match parse_duration(since) {
+        Some(seconds) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+            now.saturating_sub(seconds).to_string()
+        },
+        None => since.to_string(),
+    });
+
+    let mut query = Vec::new();
+    if options.follow {
+        query.push(("follow".to_string(), "true".to_string()));
+    }
+    if let Some(plugin) = options.plugin {
+        query.push(("plugin".to_string(), plugin.to_string()));
+    }
+    if let Some(since) = since {
+        query.push(("since".to_string(), since));
+    }
+
+    if options.follow {
+        reporter.detail(&t!("logs.following", host = http_client.base_url().cyan()));
+    }
+
+    for event in events::subscribe(http_client, "/logs", query, options.follow) {
+        let line = event?.data;
+
+        let entry = serde_json::from_str::<Entry>(&line).ok();
+        let shown = match (&entry, options.plugin) {
+            (_, None) => true,
+            (Some(entry), Some(plugin)) => entry.plugin.as_deref() == Some(plugin),
+            (None, Some(_)) => false,
+        };
+        if !shown {
+            continue;
+        }
+
+        match entry {
+            Some(entry) if !options.json => reporter.output(&render(&entry)),
+            _ => reporter.output(&line),
+        }
+    }
+
+    if options.follow {
+        reporter.info(&t!("logs.closed"));
+    }
+    Ok(())
+}
+
+fn render(entry: &Entry) -> String {
+    let level = entry.level.as_deref().unwrap_or("info").to_ascii_lowercase();
+    let label = format!("{:<5}", level.to_ascii_uppercase());
+    let label = match level.as_str() {
+        "error" | "fatal" => label.red().bold(),
+        "warn" | "warning" => label.yellow(),
+        "debug" | "trace" => label.bright_black(),
+        _ => label.green(),
+    };
+
+    let mut parts = Vec::new();
+    if let Some(time) = &entry.time {
+        parts.push(time.bright_black().to_string());
+    }
+    parts.push(label.to_string());
+    if let Some(plugin) = &entry.plugin {
+        parts.push(format!("[{}]", plugin).cyan().to_string());
+    }
+    parts.push(entry.message.clone());
+    parts.join(" ")
+}
+
+/// Parses a duration such as `90s`, `10m`, `2h` or `1d` into seconds.
+fn parse_duration(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let unit = match value.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 3_600,
+        'd' => 86_400,
+        _ => return None,
+    };
+    let amount: u64 = value[..value.len() - 1].parse().ok()?;
+    amount.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("10m"), Some(600));
+        assert_eq!(parse_duration(" 2h "), Some(7_200));
+        assert_eq!(parse_duration("1d"), Some(86_400));
+        assert_eq!(parse_duration("2026-10-16T10:00:00Z"), None);
+        assert_eq!(parse_duration("m"), None);
+    }
+}
@@ -0,0 +1,300 @@
+//! `vk lsp`: a language server for plugin manifests, over the standard input
+//! and output. It reports syntax and schema errors and dependencies the
+//! registry does not have, completes dependency names and versions, and
+//! describes dependencies on hover.
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{self, BufWriter};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::commands::install::satisfies;
+use crate::commands::publish::check_manifest;
+use crate::encoding::json5;
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::lsp::{self, Member, Slot};
+use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
+use crate::project::LEGACY_MANIFEST_FILENAMES;
+use crate::registry::RegistryClient;
+use crate::resolver;
+
+/// Sections whose keys are plugin names.
+const DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "dev_dependencies", "optional_dependencies"];
+/// How long the versions of a package are trusted before asking again.
+const CACHE_TTL: Duration = Duration::from_secs(300);
+const COMPLETION_LIMIT: usize = 20;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const SEVERITY_ERROR: u8 = 1;
+
+/// What the registry knows of a package.
+#[derive(Clone)]
+enum Published {
+    Versions(Vec<String>),
+    Missing,
+}
+
+struct Server<'a> {
+    registry: RegistryClient<'a>,
+    /// Open manifests by URI.
+    documents: HashMap<String, String>,
+    cache: HashMap<String, (Instant, Published)>,
+}
+
+/// Serves editors until they close the connection or send `exit`.
+pub fn serve(http_client: &HttpClient) -> Result<()> {
+    let mut server = Server {
+        registry: RegistryClient::new(http_client),
+        documents: HashMap::new(),
+        cache: HashMap::new(),
+    };
+    let mut input = io::stdin().lock();
+    let mut output = BufWriter::new(io::stdout().lock());
+
+    while let Some(message) = lsp::read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            if method == "exit" {
+                break;
+            }
+            for reply in server.notify(method, params) {
+                lsp::write_message(&mut output, &reply)?;
+            }
+            continue;
+        };
+        let reply = match server.request(method, params) {
+            Some(result) => lsp::response(id, result),
+            None => lsp::error_response(id, METHOD_NOT_FOUND, &t!("lsp.unknown_method", method = method)),
+        };
+        lsp::write_message(&mut output, &reply)?;
+    }
+    Ok(())
+}
+
+impl Server<'_> {
+    /// The result of a request, or `None` for methods it does not serve.
+    fn request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": { "triggerCharacters": ["\"", "'", ":"] },
+                    "hoverProvider": true,
+                },
+                "serverInfo": { "name": "vk", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Some(Value::Null),
+            "textDocument/completion" => Some(self.completion(params)),
+            "textDocument/hover" => Some(self.hover(params)),
+            _ => None,
+        }
+    }
+
+    /// Handles a notification, returning the ones to send back.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        if !is_manifest(&uri) {
+            return Vec::new();
+        }
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+            },
+            "textDocument/didChange" => {
+                // Full sync: the last change is the whole document
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes.and_then(|c| c.last()).and_then(|c| c["text"].as_str()) else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), text.to_string());
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            },
+            _ => return Vec::new(),
+        }
+
+        let text = self.documents[&uri].clone();
+        vec![publish_diagnostics(&uri, self.diagnostics(&text))]
+    }
+
+    fn diagnostics(&mut self, text: &str) -> Vec<Value> {
+        let mut diagnostics = Vec::new();
+        match json5::parse_value(text) {
+            Err(err) => {
+                let at = err.offset().unwrap_or(text.len());
+                diagnostics.push(diagnostic(text, &(at..at + 1), err.to_string()));
+            },
+            Ok(_) => {
+                let schema = json5::from_str::<PluginManifest>(text)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|manifest| check_manifest(&manifest));
+                if let Err(err) = schema {
+                    diagnostics.push(diagnostic(text, &(0..0), format!("{:#}", err)));
+                }
+            },
+        }
+
+        for member in lsp::members(text).iter().filter(|m| is_dependency(&m.path)) {
+            if let Some(problem) = self.check_dependency(text, member) {
+                diagnostics.push(problem);
+            }
+        }
+        diagnostics
+    }
+
+    /// An error for a dependency on a package or version the registry does
+    /// not have. Git and path dependencies are not checked.
+    fn check_dependency(&mut self, text: &str, member: &Member) -> Option<Value> {
+        let (spec, value_span) = (member.value.as_deref()?, member.value_span.as_ref()?);
+        match self.published(&member.key)? {
+            Published::Missing => Some(diagnostic(
+                text,
+                &member.key_span,
+                t!("lsp.unknown_package", name = member.key),
+            )),
+            Published::Versions(versions) if !versions.iter().any(|v| satisfies(spec, v)) => Some(diagnostic(
+                text,
+                value_span,
+                t!("lsp.no_matching_version", name = member.key, spec = spec),
+            )),
+            Published::Versions(_) => None,
+        }
+    }
+
+    /// The published versions of `id`, newest first, or `None` when the
+    /// registry cannot be asked.
+    fn published(&mut self, id: &str) -> Option<Published> {
+        if let Some((fetched, published)) = self.cache.get(id)
+            && fetched.elapsed() < CACHE_TTL
+        {
+            return Some(published.clone());
+        }
+        let published = match self.registry.versions(id) {
+            Ok(entries) => {
+                let mut versions: Vec<String> = entries.into_iter().map(|v| v.version).collect();
+                resolver::sort_versions(&mut versions);
+                versions.reverse();
+                Published::Versions(versions)
+            },
+            Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => Published::Missing,
+            Err(_) => return None,
+        };
+        self.cache.insert(id.to_string(), (Instant::now(), published.clone()));
+        Some(published)
+    }
+
+    fn completion(&mut self, params: &Value) -> Value {
+        let Some((_, slot)) = self.slot(params) else {
+            return json!([]);
+        };
+        match slot {
+            Slot::Key { path, prefix, .. } if is_dependency(&path) => {
+                let results = self.registry.search(&prefix, COMPLETION_LIMIT).unwrap_or_default();
+                let items: Vec<Value> = results
+                    .iter()
+                    .map(|result| {
+                        json!({
+                            "label": result.name,
+                            "kind": 9,
+                            "detail": result.latest_version,
+                            "documentation": result.description,
+                        })
+                    })
+                    .collect();
+                json!(items)
+            },
+            Slot::Value { path, key, .. } if is_dependency(&path) => {
+                let Some(Published::Versions(versions)) = self.published(&key) else {
+                    return json!([]);
+                };
+                let stable = versions.iter().filter(|v| !is_pre_release(v)).take(COMPLETION_LIMIT);
+                let ranges = stable.clone().map(|v| format!("^{}", v));
+                let items: Vec<Value> = ranges
+                    .chain(versions.iter().take(COMPLETION_LIMIT).cloned())
+                    .enumerate()
+                    .map(|(i, label)| json!({ "label": label, "kind": 12, "sortText": format!("{:04}", i) }))
+                    .collect();
+                json!(items)
+            },
+            _ => json!([]),
+        }
+    }
+
+    fn hover(&mut self, params: &Value) -> Value {
+        let Some((text, slot)) = self.slot(params) else {
+            return Value::Null;
+        };
+        let (Slot::Key { path, key, .. } | Slot::Value { path, key, .. }) = slot else {
+            return Value::Null;
+        };
+        if !is_dependency(&path) || key.is_empty() {
+            return Value::Null;
+        }
+        let Ok(details) = self.registry.package(&key) else {
+            return Value::Null;
+        };
+
+        let mut lines = vec![format!("**{}** {}", details.name, details.latest_version), String::new()];
+        if let Some(description) = &details.description {
+            lines.extend([description.clone(), String::new()]);
+        }
+        if let Some(license) = &details.license {
+            lines.push(format!("{} {}  ", t!("info.license"), license));
+        }
+        if let Some(homepage) = &details.homepage {
+            lines.push(format!("{} {}", t!("info.homepage"), homepage));
+        }
+        let span = lsp::members(&text).into_iter().find(|m| m.path == path && m.key == key).map(|m| m.key_span);
+
+        let mut hover = json!({ "contents": { "kind": "markdown", "value": lines.join("\n").trim_end() } });
+        if let Some(span) = span {
+            hover["range"] = lsp::range(&text, &span);
+        }
+        hover
+    }
+
+    /// The document a position request is about and what is at the position.
+    fn slot(&self, params: &Value) -> Option<(String, Slot)> {
+        let text = self.documents.get(params["textDocument"]["uri"].as_str()?)?;
+        let position = &params["position"];
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        let offset = lsp::offset(text, line, character);
+        Some((text.clone(), lsp::slot(text, offset)))
+    }
+}
+
+fn is_manifest(uri: &str) -> bool {
+    let name = uri.rsplit('/').next().unwrap_or_default();
+    name == MANIFEST_FILENAME || LEGACY_MANIFEST_FILENAMES.contains(&name)
+}
+
+fn is_dependency(path: &[String]) -> bool {
+    matches!(path, [section] if DEPENDENCY_SECTIONS.contains(&section.as_str()))
+}
+
+fn is_pre_release(version: &str) -> bool {
+    resolver::parse_version(version).is_some_and(|v| !v.pre.is_empty())
+}
+
+fn diagnostic(text: &str, span: &Range<usize>, message: String) -> Value {
+    json!({
+        "range": lsp::range(text, span),
+        "severity": SEVERITY_ERROR,
+        "source": "vk",
+        "message": message,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    lsp::notification(
+        "textDocument/publishDiagnostics",
+        json!({ "uri": uri, "diagnostics": diagnostics }),
+    )
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+
+use crate::commands::fmt::FmtConfig;
+use crate::encoding::json5::document::{Container, Item, Key, Node, Style};
+use crate::encoding::json5::{self, Document};
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::manifest::{DEPRECATED_FIELDS, MANIFEST_FILENAME, MANIFEST_VERSION, PluginManifest};
+use crate::pre;
+use crate::reporter::Reporter;
+
+/// The step from each manifest schema to the next: the entry at index `n`
+/// upgrades version `n + 1`. Each returns what it changed.
+const MIGRATIONS: &[fn(&mut Container) -> Vec<String>] = &[to_v2];
+
+/// Upgrades the manifest to the current schema (`MANIFEST_VERSION`), keeping
+/// its comments, and rewrites a legacy manifest (`plugin.json`) as
+/// `plugin.json5`, removing the old file.
+pub fn migrate_project(reporter: &dyn Reporter) -> Result<()> {
+    let project = pre::ensure_manifest_exists()?;
+    let version = project.manifest_version()?;
+
+    if version > MANIFEST_VERSION {
+        return Err(VkError::ManifestUnsupported { version, supported: MANIFEST_VERSION }.into());
+    }
+    if !project.is_legacy() && version == MANIFEST_VERSION {
+        reporter.info(&t!("migrate.up_to_date", file = MANIFEST_FILENAME, version = version));
+        return Ok(());
+    }
+
+    let source = fs::read_to_string(project.manifest_path()).with_context(|| t!("manifest.read_failed"))?;
+    let mut document = Document::parse(&source)
+        .map_err(|e| VkError::ManifestInvalid(e.to_string()))
+        .with_context(|| t!("manifest.parse_failed"))?;
+    let changes = upgrade(&mut document, version);
+
+    let legacy_name = project.manifest_filename().to_string();
+    if project.is_legacy() {
+        reporter.title(&t!(
+            "migrate.title",
+            from = legacy_name.cyan(),
+            to = MANIFEST_FILENAME.cyan()
+        ));
+    } else {
+        reporter.title(&t!(
+            "migrate.schema_title",
+            file = MANIFEST_FILENAME.cyan(),
+            from = version,
+            to = MANIFEST_VERSION
+        ));
+    }
+    for change in &changes {
+        reporter.output(&format!("  - {}", change));
+    }
+
+    let target = project.root().join(MANIFEST_FILENAME);
+    let content = if project.is_legacy() {
+        // Legacy manifests are plain JSON, written anew in the vk layout
+        let manifest = parse(&document.format(&Style::default()))?;
+        json5::to_string_pretty(&manifest)?
+    } else {
+        let content = document.format(&FmtConfig::find(project.root())?.style());
+        parse(&content)?;
+        content
+    };
+
+    fs::write(&target, content).with_context(|| t!("manifest.write_failed"))?;
+    reporter.success(&t!(
+        "migrate.written",
+        path = target.display().to_string().bright_black()
+    ));
+
+    if project.is_legacy() {
+        fs::remove_file(project.manifest_path())
+            .with_context(|| t!("migrate.remove_failed", file = legacy_name.as_str()))?;
+        reporter.success(&t!("migrate.removed", file = legacy_name.as_str()));
+    }
+
+    reporter.done(&t!("migrate.done"));
+
+    Ok(())
+}
+
+/// Checks the upgraded manifest still reads before it replaces the old one.
+fn parse(content: &str) -> Result<PluginManifest> {
+    json5::from_str(content)
+        .map_err(|e| VkError::ManifestInvalid(e.to_string()))
+        .with_context(|| t!("manifest.parse_failed"))
+}
+
+/// Runs the migrations from schema `from` on, then records the current
+/// version in the manifest.
+fn upgrade(document: &mut Document, from: u32) -> Vec<String> {
+    let Node::Object(root) = &mut document.root.value else {
+        return Vec::new();
+    };
+
+    let mut changes: Vec<String> = MIGRATIONS
+        .iter()
+        .skip(from.saturating_sub(1) as usize)
+        .flat_map(|migration| migration(root))
+        .collect();
+
+    let version = Node::Literal(MANIFEST_VERSION.to_string());
+    match root.items.iter_mut().find(|item| item.key.as_ref().is_some_and(|k| k.name == "manifest_version")) {
+        Some(item) => item.value = version,
+        None => root.items.insert(
+            0,
+            Item {
+                comments: Vec::new(),
+                key: Some(Key { name: "manifest_version".to_string(), quote: None }),
+                value: version,
+                trailing: None,
+                blank_before: false,
+            },
+        ),
+    }
+    changes.push(t!("migrate.version_set", version = MANIFEST_VERSION));
+    changes
+}
+
+/// Version 2 reads no npm-style field names: they are renamed to the ones vk
+/// reads, unless that field is set too.
+fn to_v2(root: &mut Container) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (from, to) in DEPRECATED_FIELDS {
+        let has =
+            |root: &Container, name: &str| root.items.iter().any(|i| i.key.as_ref().is_some_and(|k| k.name == name));
+        if !has(root, from) {
+            continue;
+        }
+        if has(root, to) {
+            changes.push(t!("migrate.field_kept", field = from, replacement = to));
+            continue;
+        }
+        if let Some(key) = root.items.iter_mut().filter_map(|i| i.key.as_mut()).find(|k| k.name == *from) {
+            key.name = to.to_string();
+            key.quote = None;
+            changes.push(t!("migrate.field_renamed", field = from, replacement = to));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_from_v1() {
+        let mut document = Document::parse(
+            "{\n    // The plugin\n    name: \"x\",\n    displayName: \"X\",\n    installDir: \"a\",\n    install_dir: \"b\"\n}\n",
+        )
+        .unwrap();
+        let changes = upgrade(&mut document, 1);
+        assert_eq!(changes.len(), 3, "{:?}", changes);
+
+        let text = document.format(&Style::default());
+        assert!(text.starts_with("{\n    manifest_version: 2,\n    // The plugin\n    name: \"x\",\n    display_name: \"X\",\n    installDir: \"a\","));
+
+        // An up-to-date manifest only gets its version set
+        let mut document = Document::parse("{ manifest_version: 1, name: \"x\" }").unwrap();
+        assert_eq!(upgrade(&mut document, MANIFEST_VERSION).len(), 1);
+        assert!(document.format(&Style::default()).contains("manifest_version: 2"));
+    }
+}
@@ -1,14 +1,45 @@
 pub mod audit;
+pub mod bench;
+pub mod changelog;
+pub mod deploy;
+pub mod diff;
+pub mod fmt;
+pub mod import;
+pub mod info;
 pub mod install;
+pub mod lint;
 pub mod list;
+pub mod lock;
+pub mod logs;
+pub mod migrate;
+pub mod outdated;
+pub mod pack;
+pub mod pin;
 pub mod publish;
+pub mod run;
+pub mod stats;
+pub mod tag;
+pub mod test;
+pub mod token;
 pub mod update;
+pub mod validate;
+pub mod vendor;
+pub mod verify;
+pub mod watch_registry;
 
 #[cfg(feature = "full")]
 pub mod add;
 #[cfg(feature = "full")]
 pub mod clean;
 #[cfg(feature = "full")]
+pub mod host;
+#[cfg(feature = "full")]
 pub mod init;
 #[cfg(feature = "full")]
+pub mod link;
+#[cfg(feature = "full")]
+pub mod lsp;
+#[cfg(feature = "full")]
 pub mod remove;
+#[cfg(feature = "full")]
+pub mod telemetry;
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::BTreeMap;
+
+use crate::commands::info::fetch_versions;
+use crate::commands::update::show_release_notes;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::resolver;
+
+/// Lists dependencies with a newer version available: `wanted` is the newest
+/// version allowed by the declared range, `latest` the newest overall. With
+/// `notes`, the release notes of the versions up to `latest` follow each row.
+pub fn list_outdated(
+    project: &Project,
+    include_pre: bool,
+    notes: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let manifest = project.read_manifest()?;
+
+    // Only registry dependencies have versions to compare
+    let deps: BTreeMap<String, String> = manifest
+        .dependencies
+        .into_iter()
+        .chain(manifest.dev_dependencies.unwrap_or_default())
+        .filter_map(|(name, dep)| dep.version().map(|v| (name, v.to_string())))
+        .collect();
+
+    reporter.title(&t!("outdated.title"));
+
+    if deps.is_empty() {
+        reporter.info(&t!("list.empty"));
+        return Ok(());
+    }
+
+    let mut rows = Vec::new();
+    let pb = reporter.progress(Some(deps.len() as u64), &t!("outdated.progress"));
+
+    for (name, spec) in &deps {
+        pb.inc(1);
+
+        let Some(req) = resolver::parse_req(spec) else {
+            reporter.warn(&t!("outdated.invalid_range", package = name.cyan(), range = spec));
+            continue;
+        };
+
+        let versions = match fetch_versions(name, http_client) {
+            Ok(versions) => versions,
+            Err(_) => {
+                reporter.warn(&t!("update.fetch_latest_failed", package = name.cyan()));
+                continue;
+            },
+        };
+
+        let wanted = resolver::max_satisfying(versions.iter().map(String::as_str), &req, include_pre);
+        let latest = resolver::latest(versions.iter().map(String::as_str), include_pre);
+        let current = resolver::parse_version(spec);
+
+        let behind_wanted = matches!((&current, &wanted), (Some(c), Some(w)) if c < w);
+        let behind_latest = match (&wanted, &latest) {
+            (Some(w), Some(l)) => w < l,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+
+        if behind_wanted || behind_latest {
+            rows.push((name, spec, wanted, latest));
+        }
+    }
+    pb.finish();
+
+    if rows.is_empty() {
+        reporter.done(&t!("outdated.up_to_date"));
+        return Ok(());
+    }
+
+    reporter.output(
+        &format!(
+            "{:<30} {:<15} {:<15} {:<15}",
+            t!("outdated.package"),
+            t!("outdated.current"),
+            t!("outdated.wanted"),
+            t!("outdated.latest")
+        )
+        .bold()
+        .to_string(),
+    );
+
+    for (name, spec, wanted, latest) in rows {
+        // Notes start after the version in use: the pinned one, else the wanted one
+        let from = resolver::parse_version(spec).or_else(|| wanted.clone()).map(|v| v.to_string()).unwrap_or_default();
+        let wanted = wanted.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        let latest = latest.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        reporter.output(&format!(
+            "{} {} {} {}",
+            format!("{:<30}", name).cyan(),
+            format!("{:<15}", spec).yellow(),
+            format!("{:<15}", wanted).green(),
+            format!("{:<15}", latest).magenta()
+        ));
+        if notes && latest != "-" {
+            show_release_notes(name, &from, &latest, http_client, reporter);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::commands::publish::archive_spec;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::{PackedArchive, create_zip, format_bytes, package_files};
+
+/// Entries listed in the "largest files" section of `--analyze`.
+const LARGEST_FILES: usize = 10;
+
+/// Path components that almost never belong in a published plugin.
+const SUSPICIOUS: &[&str] = &[".env", "node_modules", ".git"];
+
+pub struct PackOptions {
+    /// Where to write the archive; `.vk/<name>-<version>.zip` when `None`, so
+    /// it never ends up in the next package.
+    pub out: Option<String>,
+    pub analyze: bool,
+    /// Only print the files that would be packaged.
+    pub list: bool,
+    pub allow_path_deps: bool,
+}
+
+/// Builds the archive `vk publish` would upload and writes it to disk.
+pub fn pack_plugin(project: &Project, options: &PackOptions, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+
+    reporter.title(&t!(
+        "pack.title",
+        name = manifest.name.cyan(),
+        version = manifest.version.yellow()
+    ));
+
+    let spec = archive_spec(project, &manifest, options.allow_path_deps, reporter)?;
+
+    if options.list {
+        let files = package_files(project.root(), &spec)?;
+        for file in &files {
+            reporter.output(&format!(
+                "{} {}",
+                format!("{:<70}", file.name).cyan(),
+                format!("{:>10}", format_bytes(file.size as usize)).bright_black()
+            ));
+        }
+        let total: u64 = files.iter().map(|f| f.size).sum();
+        reporter.info(&t!(
+            "pack.list_total",
+            files = files.len(),
+            size = format_bytes(total as usize)
+        ));
+        return Ok(());
+    }
+    let archive = create_zip(project.root(), &spec, reporter)?;
+
+    if options.analyze {
+        analyze(project.root(), &archive, reporter);
+    }
+
+    let out = match &options.out {
+        Some(path) => Path::new(path).to_path_buf(),
+        None => project.cache_dir().join(format!("{}-{}.zip", manifest.name, manifest.version)),
+    };
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+    }
+    fs::write(&out, &archive.data).with_context(|| t!("common.write_failed", path = out.display()))?;
+
+    reporter.done(&t!(
+        "pack.written",
+        path = out.display().to_string().bright_black(),
+        size = format_bytes(archive.data.len())
+    ));
+
+    Ok(())
+}
+
+/// Prints where the bytes of the archive go, what was left out, and anything
+/// that looks like it was included by mistake.
+fn analyze(root: &Path, archive: &PackedArchive, reporter: &dyn Reporter) {
+    let total = archive.total_size();
+
+    reporter.title(&t!("pack.analyze_largest"));
+    let mut largest: Vec<_> = archive.entries.iter().collect();
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    for entry in largest.into_iter().take(LARGEST_FILES) {
+        reporter.output(&format!(
+            "  {} {} {}",
+            format!("{:<60}", entry.name).cyan(),
+            format!("{:>10}", format_bytes(entry.size as usize)).yellow(),
+            format!("{:>10}", format_bytes(entry.compressed as usize)).bright_black()
+        ));
+    }
+
+    reporter.title(&t!("pack.analyze_directories"));
+    let mut directories: BTreeMap<&str, (u64, usize)> = BTreeMap::new();
+    for entry in &archive.entries {
+        let directory = entry.name.split_once('/').map_or(".", |(first, _)| first);
+        let stats = directories.entry(directory).or_default();
+        stats.0 += entry.size;
+        stats.1 += 1;
+    }
+    let mut directories: Vec<_> = directories.into_iter().collect();
+    directories.sort_by_key(|(_, (size, _))| std::cmp::Reverse(*size));
+    for (directory, (size, files)) in directories {
+        let share = if total == 0 {
+            0.0
+        } else {
+            size as f64 * 100.0 / total as f64
+        };
+        reporter.output(&format!(
+            "  {} {} {}",
+            format!("{:<40}", directory).cyan(),
+            format!("{:>10}", format_bytes(size as usize)).yellow(),
+            t!("pack.analyze_share", share = format!("{:.1}", share), files = files).bright_black()
+        ));
+    }
+
+    // Vendored dependencies live outside the root, so only root files are compared
+    let included: HashSet<&str> = archive.entries.iter().map(|e| e.name.as_str()).collect();
+    let (mut ignored, mut ignored_size) = (0usize, 0u64);
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        if !included.contains(relative.to_string_lossy().replace('\\', "/").as_str()) {
+            ignored += 1;
+            ignored_size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+
+    reporter.title(&t!("pack.analyze_summary"));
+    reporter.output(&format!(
+        "  {}",
+        t!(
+            "pack.analyze_included",
+            files = archive.entries.len(),
+            size = format_bytes(total as usize)
+        )
+    ));
+    reporter.output(&format!(
+        "  {}",
+        t!(
+            "pack.analyze_ignored",
+            files = ignored,
+            size = format_bytes(ignored_size as usize)
+        )
+    ));
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        archive.data.len() as f64 * 100.0 / total as f64
+    };
+    reporter.output(&format!(
+        "  {}",
+        t!("pack.analyze_ratio", ratio = format!("{:.1}", ratio))
+    ));
+
+    for entry in &archive.entries {
+        let suspicious =
+            entry.name.split('/').find(|part| SUSPICIOUS.iter().any(|s| part == s) || part.starts_with(".env."));
+        if let Some(part) = suspicious {
+            reporter.warn(&t!(
+                "pack.analyze_suspicious",
+                path = entry.name.as_str(),
+                reason = part
+            ));
+        }
+    }
+}
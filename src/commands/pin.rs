@@ -0,0 +1,107 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::commands::info::fetch_versions;
+use crate::commands::install::{declared_dependencies, satisfies};
+use crate::dist_tag;
+use crate::error::VkError;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::lockfile::{Lockfile, Pin};
+use crate::manifest::Dependency;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::utils::parse_package;
+
+/// Holds `package` (`<id>[@<version>]`) at a published version, or at the
+/// locked one when none is given, so installs and updates leave it there.
+/// Transitive packages can be pinned too; a declared dependency can only be
+/// pinned inside its range.
+pub fn pin_package(
+    project: &Project,
+    package: &str,
+    reason: Option<&str>,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let (id, version) = parse_package(package);
+    let mut lockfile = Lockfile::load(project)?;
+    let locked = lockfile.get(&id).filter(|l| l.is_registry());
+
+    let version = match version.or_else(|| locked.map(|l| l.version.clone())) {
+        Some(version) => version,
+        None => anyhow::bail!(t!("pin.not_locked", package = id.cyan())),
+    };
+    if resolver::parse_version(&version).is_none() {
+        anyhow::bail!(t!("pin.not_exact", version = version));
+    }
+
+    let manifest = project.read_manifest()?;
+    match declared_dependencies(&manifest, &lockfile.features, None).get(&id) {
+        Some(Dependency::Version(spec)) if !dist_tag::is_dist_tag(spec) && !satisfies(spec, &version) => {
+            anyhow::bail!(t!(
+                "pin.outside_range",
+                package = id.cyan(),
+                version = version,
+                range = spec
+            ));
+        },
+        Some(Dependency::Version(_)) => {},
+        Some(_) => anyhow::bail!(t!("pin.not_registry", package = id.cyan())),
+        None if locked.is_none() => return Err(VkError::DependencyNotFound(id).into()),
+        None => {},
+    }
+
+    if !fetch_versions(&id, http_client)?.contains(&version) {
+        return Err(VkError::PackageNotFound(format!("{}@{}", id, version)).into());
+    }
+
+    let installed = locked.map(|l| l.version.clone());
+    lockfile.pins.insert(
+        id.clone(),
+        Pin { version: version.clone(), reason: reason.map(str::to_string) },
+    );
+    lockfile.save(project)?;
+
+    reporter.success(&t!("pin.pinned", package = id.cyan(), version = version.yellow()));
+    if installed.as_deref() != Some(version.as_str()) {
+        reporter.info(&t!("pin.install_hint", command = "vk install".cyan()));
+    }
+    Ok(())
+}
+
+/// Lets installs and updates move `id` again.
+pub fn unpin_package(project: &Project, id: &str, reporter: &dyn Reporter) -> Result<()> {
+    let mut lockfile = Lockfile::load(project)?;
+    if lockfile.pins.remove(id).is_none() {
+        anyhow::bail!(t!("pin.not_pinned", package = id.cyan()));
+    }
+    lockfile.save(project)?;
+
+    reporter.success(&t!("pin.unpinned", package = id.cyan()));
+    Ok(())
+}
+
+pub fn list_pins(project: &Project, reporter: &dyn Reporter) -> Result<()> {
+    let lockfile = Lockfile::load(project)?;
+    if lockfile.pins.is_empty() {
+        reporter.info(&t!("pin.none"));
+        return Ok(());
+    }
+
+    for (id, pin) in &lockfile.pins {
+        let mut line = format!("{} {}", id.cyan(), pin.version.yellow());
+        if let Some(locked) = lockfile.get(id).filter(|l| l.version != pin.version) {
+            line.push_str(&format!(
+                " {}",
+                t!("pin.locked_at", version = locked.version).bright_black()
+            ));
+        }
+        if let Some(reason) = &pin.reason {
+            line.push_str(&format!(" - {}", reason));
+        }
+        reporter.output(&line);
+    }
+    Ok(())
+}
@@ -1,102 +1,560 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use reqwest::blocking::multipart::{Form, Part};
-use serde::Deserialize;
-use std::fs;
 use std::path::Path;
 
+use crate::assets;
+use crate::author;
+use crate::commands::audit::{self, AuditOptions};
+use crate::commands::changelog::{self, CHANGELOG_FILENAME};
+use crate::commands::test::{self, TestOptions};
+use crate::commands::validate;
+use crate::dist_tag;
 use crate::encoding::json5;
-use crate::http_client::HttpClient;
-use crate::manifest::{MANIFEST_FILENAME, PluginAccess, PluginManifest};
-use crate::utils::{create_zip, format_bytes};
-
-pub fn publish_plugin(
-    directory: &Option<String>,
-    access: Option<PluginAccess>,
-    dry_run: bool,
-    http_client: &HttpClient,
-) -> Result<()> {
-    let dir_path = if let Some(dir) = directory {
-        Path::new(dir).to_path_buf()
-    } else {
-        std::env::current_dir()?
+use crate::error::VkError;
+use crate::git;
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::interpolate;
+use crate::limits::{self, PublishLimits};
+use crate::manifest::{Dependency, InstallDir, MANIFEST_FILENAME, PluginAccess, PluginManifest};
+use crate::policy;
+use crate::pre;
+use crate::project::Project;
+use crate::provenance;
+use crate::registry::{RegistryClient, Role, Upload, VERSION_EXISTS};
+use crate::report::Recorder;
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::scripts::{self, Suite};
+use crate::utils::{ArchiveSpec, create_zip, format_bytes, package_files, sha256_hex};
+
+const PREPUBLISH_SCRIPT: &str = "prepublish";
+/// File added to the archive with the git state the package was built from.
+pub const BUILD_INFO_FILENAME: &str = "BUILD_INFO.json5";
+
+/// Version parts `vk publish --bump` increments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BumpLevel {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl BumpLevel {
+    /// The next version at this level. A pre-release is released by a patch
+    /// bump (`1.0.0-beta.1` becomes `1.0.0`).
+    pub fn apply(self, version: &semver::Version) -> semver::Version {
+        let mut next = version.clone();
+        next.build = semver::BuildMetadata::EMPTY;
+        let released = !version.pre.is_empty();
+        next.pre = semver::Prerelease::EMPTY;
+        match self {
+            BumpLevel::Major if released && version.minor == 0 && version.patch == 0 => {},
+            BumpLevel::Major => (next.major, next.minor, next.patch) = (version.major + 1, 0, 0),
+            BumpLevel::Minor if released && version.patch == 0 => {},
+            BumpLevel::Minor => (next.minor, next.patch) = (version.minor + 1, 0),
+            BumpLevel::Patch if released => {},
+            BumpLevel::Patch => next.patch += 1,
+        }
+        next
+    }
+}
+
+pub struct PublishOptions {
+    /// Directory of the plugin; the enclosing project when `None`.
+    pub directory: Option<String>,
+    pub access: Option<PluginAccess>,
+    pub tag: String,
+    pub dry_run: bool,
+    /// Skip the `prepublish` script.
+    pub ignore_scripts: bool,
+    /// Bundle path dependencies under `vendor/` instead of refusing to publish.
+    pub allow_path_deps: bool,
+    /// Syntax-check the Lua sources before anything is uploaded.
+    pub verify: bool,
+    /// Refuse to upload unless an audit of the dependencies finds nothing.
+    pub require_clean_audit: bool,
+    /// Refuse to upload unless the tests exist and pass.
+    pub require_tests: bool,
+    /// Replace the archive of a version that is already published, where the
+    /// registry allows it.
+    pub force_republish: bool,
+    /// Bump the manifest version at this level when the registry already
+    /// has it.
+    pub bump: Option<BumpLevel>,
+    /// Upload a provenance statement signed with the publisher key.
+    pub provenance: bool,
+    /// Limits from `config.toml`, in place of the registry's.
+    pub limits: PublishLimits,
+    /// The configured plugins directory, where the audit finds the installed
+    /// dependencies.
+    pub install_dir: Option<InstallDir>,
+}
+
+pub fn publish_plugin(options: &PublishOptions, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let tag = options.tag.as_str();
+    if !dist_tag::is_dist_tag(tag) {
+        anyhow::bail!(t!("tag.invalid", tag = tag));
+    }
+
+    let project = match &options.directory {
+        Some(dir) => {
+            let dir_path = Path::new(dir);
+            let dir_path =
+                dir_path.canonicalize().with_context(|| t!("common.invalid_directory", path = dir_path.display()))?;
+
+            Project::at(&dir_path).ok_or_else(|| VkError::ManifestMissing {
+                file: dir_path.join(MANIFEST_FILENAME).display().to_string(),
+            })?
+        },
+        None => pre::ensure_manifest_exists()?,
     };
 
-    let dir_path = dir_path.canonicalize().context("Failed to canonicalize directory path")?;
+    if project.is_legacy() {
+        reporter.warn(&t!("project.legacy_manifest", file = project.manifest_filename()));
+    }
 
-    // Check if manifest file exists
-    let manifest_path = dir_path.join(MANIFEST_FILENAME);
-    if !manifest_path.exists() {
-        anyhow::bail!(
-            "Is not possible to publish a plugin without a manifest file: {}, {}",
-            MANIFEST_FILENAME,
-            "Please create a manifest file before publishing or verify the directory path."
-        );
+    let dir_path = project.root();
+    let mut manifest = read_manifest(&project)?;
+    if let Err(reason) = author::check(&manifest.author) {
+        return Err(VkError::ManifestInvalid(t!("publish.invalid_author", reason = reason)).into());
     }
+    // A missing key should not surface after the package is built
+    let publisher_key = (options.provenance && !options.dry_run).then(provenance::publisher_key).transpose()?;
 
-    let manifest = read_manifest(&manifest_path)?;
+    reporter.title(&t!(
+        "publish.title",
+        name = manifest.name.cyan(),
+        version = manifest.version.yellow()
+    ));
+    if tag != dist_tag::DEFAULT_TAG {
+        reporter.step(&t!("publish.tag", tag = tag.cyan()));
+    }
 
-    println!(
-        "{} Publishing {}@{}",
-        "📦".bold(),
-        manifest.name.cyan(),
-        manifest.version.yellow()
-    );
+    check_role(&manifest.name, options, http_client, reporter)?;
+    if !options.force_republish {
+        check_version_free(&project, &mut manifest, options, http_client, reporter)?;
+    }
 
-    let (zip_data, _checksum) = create_zip(&dir_path).context("Failed to create ZIP archive")?;
+    if !options.ignore_scripts && manifest.scripts.contains_key(PREPUBLISH_SCRIPT) {
+        reporter.step(&t!("publish.running_prepublish", name = PREPUBLISH_SCRIPT.cyan()));
+        scripts::run_lifecycle(&project, &manifest, PREPUBLISH_SCRIPT)?;
+        // The script may have rebuilt files or bumped the manifest
+        let version = manifest.version.clone();
+        manifest = read_manifest(&project)?;
+        if manifest.version != version && !options.force_republish {
+            check_version_free(&project, &mut manifest, options, http_client, reporter)?;
+        }
+    }
 
-    println!("{} Package created ({})", "✓".green(), format_bytes(zip_data.len()));
+    let mut spec = archive_spec(&project, &manifest, options.allow_path_deps, reporter)?;
+    let build_info = git::build_info(dir_path);
+    match &build_info {
+        Some(info) => {
+            let commit = &info.commit[..info.commit.len().min(12)];
+            match &info.tag {
+                Some(tag) => reporter.step(&t!("publish.build_info_tag", commit = commit.cyan(), tag = tag.cyan())),
+                None => reporter.step(&t!("publish.build_info", commit = commit.cyan())),
+            }
+            if info.dirty {
+                reporter.warn(&t!("publish.dirty"));
+            }
+            // Canonical, so rebuilding the same commit reproduces the archive
+            spec.replace.push((
+                BUILD_INFO_FILENAME.to_string(),
+                json5::to_string_canonical(info)?.into_bytes(),
+            ));
+        },
+        None => reporter.detail(&t!("publish.no_git")),
+    }
+    if options.verify {
+        validate::check_sources(&project, &manifest, &spec, reporter)?;
+    }
+    if options.require_tests {
+        require_tests(&project, reporter)?;
+    }
+    if options.require_clean_audit {
+        require_clean_audit(&project, options, http_client, reporter)?;
+    }
+
+    // Checked before packing, so a package the registry would refuse is not built
+    let limits = limits::fetch(http_client, &options.limits, reporter);
+    let mut problems = limits.check_files(&package_files(dir_path, &spec)?);
+    problems.extend(limits.check_manifest_version(manifest.schema_version()));
+    if !problems.is_empty() {
+        return Err(VkError::PackageLimit(problems.join("; ")).into());
+    }
+
+    let archive = create_zip(dir_path, &spec, reporter).with_context(|| t!("publish.archive_failed"))?;
+    if let Some(problem) = limits.check_archive(archive.data.len(), &archive.entries, reporter) {
+        return Err(VkError::PackageLimit(problem).into());
+    }
+    let zip_data = archive.data;
+
+    reporter.success(&t!("publish.package_created", size = format_bytes(zip_data.len())));
 
-    if dry_run {
-        println!("{} Dry run mode enabled, skipping upload, only intent", "⚠".yellow());
+    let mut attestation = None;
+    if options.provenance {
+        let statement = provenance::statement(&project, &manifest, &zip_data, build_info.as_ref())?;
+        let (builder, _) = provenance::builder();
+        reporter.step(&t!("publish.provenance", builder = builder.bright_black()));
+        if provenance::is_local_builder(&builder) {
+            reporter.warn(&t!("publish.provenance_local"));
+        }
+        if let Some(key) = &publisher_key {
+            attestation = Some(serde_json::to_string(&provenance::sign(&statement, key)?)?);
+        }
+    }
+
+    if options.dry_run {
+        reporter.warn(&t!("publish.dry_run"));
     } else {
-        upload_plugin(&manifest.name, &zip_data, access.unwrap_or_default(), http_client)?;
-        println!("{} Published successfully!", "✅".green());
+        let notes = changelog::release_notes(&project, &manifest.version);
+        if notes.is_some() {
+            reporter.step(&t!("publish.release_notes", file = CHANGELOG_FILENAME.cyan()));
+        }
+        let upload = Upload {
+            name: &manifest.name,
+            version: &manifest.version,
+            data: &zip_data,
+            notes: notes.as_deref(),
+            build_info: build_info.as_ref(),
+            provenance: attestation.as_deref(),
+            access: options.access.clone().unwrap_or_default(),
+            tag,
+            force: options.force_republish,
+        };
+        // Uploaded first, so installs of the version find them
+        assets::upload(
+            &project,
+            &manifest.name,
+            &manifest.version,
+            &manifest.assets,
+            http_client,
+            reporter,
+        )?;
+        upload_plugin(&upload, http_client, reporter)?;
+        reporter.done(&t!("publish.done"));
     }
 
     Ok(())
 }
 
-fn read_manifest(path: &Path) -> Result<PluginManifest> {
-    let content = fs::read_to_string(path).context("Plugin need manifest file for publishing")?;
+/// Runs the tests, which have to exist and pass for the publish to go on.
+fn require_tests(project: &Project, reporter: &dyn Reporter) -> Result<()> {
+    if Suite::Test.files(project).is_empty() {
+        return Err(VkError::TestsFailed(t!("publish.no_tests", dir = Suite::Test.dir())).into());
+    }
+    let options = TestOptions {
+        filter: None,
+        interpreter: None,
+        junit: None,
+        coverage: false,
+    };
+    test::run_tests(project, &options, reporter).with_context(|| t!("publish.gate_failed"))
+}
+
+/// Audits the dependencies, every section of it, which have to come out
+/// without a finding for the publish to go on.
+fn require_clean_audit(
+    project: &Project,
+    options: &PublishOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let policy = policy::load(Some(project))?;
+    let plugins_dir = project.plugins_dir(None, options.install_dir.as_ref())?;
+    let recorder = Recorder::new("audit");
+    let score = audit::audit_dependencies(
+        project,
+        &plugins_dir,
+        policy.as_ref(),
+        &AuditOptions::default(),
+        http_client,
+        &recorder,
+        reporter,
+    )
+    .with_context(|| t!("publish.gate_failed"))?;
+    if score < 100 {
+        return Err(VkError::AuditNotClean { score }.into());
+    }
+    Ok(())
+}
+
+/// Asks the registry what the user may do with the package, so a publish it
+/// would refuse stops before the upload, naming the owners to ask. A package
+/// nobody published yet is the user's to create; a registry that cannot
+/// answer is left to the upload to deal with.
+fn check_role(name: &str, options: &PublishOptions, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let package_role = match RegistryClient::new(http_client).role(name) {
+        Ok(role) => role,
+        Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => {
+            reporter.step(&t!("publish.role_new", name = name.cyan()));
+            return Ok(());
+        },
+        Err(err) => {
+            reporter.detail(&t!("publish.role_skipped", reason = err));
+            return Ok(());
+        },
+    };
+    let role = match package_role.role {
+        Role::Owner => t!("publish.role_owner"),
+        Role::Maintainer => t!("publish.role_maintainer"),
+        Role::None => {
+            let err = VkError::NotPublisher { package: name.to_string(), owners: package_role.owners };
+            if options.dry_run {
+                reporter.warn(&err.to_string());
+                return Ok(());
+            }
+            return Err(err.into());
+        },
+    };
+    reporter.step(&t!("publish.role", role = role.cyan()));
+    Ok(())
+}
+
+/// Asks the registry whether the manifest version is already published, so
+/// nothing is built or uploaded for nothing. A taken version is bumped with
+/// `--bump` (or after asking, in a terminal) until a free one is found;
+/// otherwise the publish stops. A registry that cannot answer is left to the
+/// upload to deal with.
+fn check_version_free(
+    project: &Project,
+    manifest: &mut PluginManifest,
+    options: &PublishOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let published: Vec<String> = match RegistryClient::new(http_client).versions(&manifest.name) {
+        Ok(versions) => versions.into_iter().map(|v| v.version).collect(),
+        Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => return Ok(()),
+        Err(err) => {
+            reporter.detail(&t!("publish.preflight_skipped", reason = err));
+            return Ok(());
+        },
+    };
+    if !published.contains(&manifest.version) {
+        return Ok(());
+    }
+
+    let package = format!("{}@{}", manifest.name, manifest.version);
+    if options.dry_run && options.bump.is_none() {
+        reporter.warn(&t!("publish.version_exists_dry_run", package = package.cyan()));
+        return Ok(());
+    }
+    let level = match options.bump {
+        Some(level) => level,
+        None if reporter.is_interactive()
+            && reporter.confirm(&t!("publish.bump_prompt", package = package.cyan())).unwrap_or(false) =>
+        {
+            BumpLevel::Patch
+        },
+        None => return Err(VkError::VersionExists(package).into()),
+    };
+
+    let current = semver::Version::parse(&manifest.version)
+        .map_err(|e| VkError::ManifestInvalid(t!("publish.invalid_version", version = manifest.version, reason = e)))?;
+    let mut next = level.apply(&current);
+    while published.contains(&next.to_string()) {
+        next = level.apply(&next);
+    }
+
+    reporter.step(&t!(
+        "publish.bumped",
+        from = manifest.version.yellow(),
+        to = next.to_string().yellow()
+    ));
+    manifest.version = next.to_string();
+    if options.dry_run {
+        return Ok(());
+    }
+    // The manifest on disk gets the new version, as with a manual bump
+    let mut on_disk = project.read_manifest()?;
+    on_disk.version = manifest.version.clone();
+    project.write_manifest(&on_disk)
+}
 
-    let manifest: PluginManifest = json5::from_str(&content).context("Failed to parse manifest file")?;
+/// Reads the manifest, requiring the fields the registry needs and engine
+/// ranges installs can check.
+pub fn read_manifest(project: &Project) -> Result<PluginManifest> {
+    let manifest = project.read_manifest()?;
+    check_manifest(&manifest)?;
+    Ok(manifest)
+}
 
+/// Fails on a manifest missing fields the registry needs, or with engine
+/// ranges installs cannot check.
+pub fn check_manifest(manifest: &PluginManifest) -> Result<()> {
     if manifest.version.is_empty() {
-        anyhow::bail!("Manifest missing required field: version");
+        return Err(VkError::ManifestInvalid(t!("manifest.missing_field", field = "version")).into());
     }
     if manifest.name.is_empty() {
-        anyhow::bail!("Manifest missing required field: name");
+        return Err(VkError::ManifestInvalid(t!("manifest.missing_field", field = "name")).into());
     }
 
-    Ok(manifest)
+    let conditions = manifest.target_dependencies.iter().filter_map(|t| t.when.host.as_ref());
+    let ranges = [("engines.host", &manifest.engines.host), ("engines.lua", &manifest.engines.lua)]
+        .into_iter()
+        .chain(conditions.map(|range| ("target_dependencies.when.host", range)));
+    for (field, range) in ranges {
+        if resolver::parse_req(range).is_none() {
+            return Err(VkError::ManifestInvalid(t!("manifest.invalid_range", field = field, range = range)).into());
+        }
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-pub struct PluginResponse {
-    pub name: String,
-    pub slug: String,
+/// Describes the archive for `manifest`: its `files`/`exclude` selection and
+/// how path dependencies are handled.
+///
+/// Path dependencies only exist on the author's machine. They are refused
+/// unless `allow_path_deps` is set, in which case each one is bundled under
+/// `vendor/<id>/` and dropped from the published manifest.
+///
+/// `${VAR}` references in `homepage` and `repository.url` are expanded in the
+/// published manifest; the one on disk keeps them.
+///
+/// Assets are left out of the archive, and listed in the published manifest
+/// with their hash.
+pub fn archive_spec(
+    project: &Project,
+    manifest: &PluginManifest,
+    allow_path_deps: bool,
+    reporter: &dyn Reporter,
+) -> Result<ArchiveSpec> {
+    let mut published = manifest.clone();
+    let mut spec = ArchiveSpec {
+        files: manifest.files.clone(),
+        exclude: manifest.exclude.clone(),
+        symlinks: manifest.packaging.symlinks,
+        empty_dirs: manifest.packaging.empty_dirs,
+        ..Default::default()
+    };
+    let mut rewritten = expand_links(&mut published)?;
+    let mut local = Vec::new();
+
+    let sections = [
+        Some(&mut published.dependencies),
+        published.dev_dependencies.as_mut(),
+        Some(&mut published.optional_dependencies),
+    ];
+    for deps in sections.into_iter().flatten() {
+        let ids: Vec<String> = deps
+            .iter()
+            .filter(|(_, dep)| matches!(dep, Dependency::Path(_)))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in ids {
+            if let Some(Dependency::Path(source)) = deps.remove(&id) {
+                local.push((id, source));
+            }
+        }
+    }
+
+    if !local.is_empty() {
+        local.sort_by(|a, b| a.0.cmp(&b.0));
+        let names = local.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>().join(", ");
+
+        if !allow_path_deps {
+            return Err(VkError::ManifestInvalid(t!("publish.path_deps_refused", packages = names)).into());
+        }
+
+        for (id, source) in local {
+            let dir = project.root().join(&source.path);
+            let Some(dependency) = Project::at(&dir) else {
+                return Err(VkError::DependencyNotFound(format!("{} ({})", id, dir.display())).into());
+            };
+            let dir = dependency.root().canonicalize().unwrap_or(dir);
+            spec.directories.push((dir, format!("vendor/{}/", id)));
+        }
+
+        reporter.warn(&t!("publish.path_deps_vendored", packages = names));
+        rewritten = true;
+    }
+
+    if !manifest.assets.is_empty() {
+        published.assets = assets::prepare(project, &manifest.assets)?;
+        spec.exclude.extend(manifest.assets.iter().map(|a| format!("/{}", a.path)));
+        rewritten = true;
+    }
+
+    if rewritten {
+        spec.replace.push((
+            project.manifest_filename().to_string(),
+            project.render_manifest(&published)?.into_bytes(),
+        ));
+    }
+
+    Ok(spec)
 }
 
-fn upload_plugin(id: &str, zip_data: &[u8], access: PluginAccess, http_client: &HttpClient) -> Result<()> {
-    let form = Form::new()
-        .part(
-            "file",
-            Part::bytes(zip_data.to_vec()).file_name(format!("{}.zip", id)).mime_str("application/zip")?,
-        )
-        .part("access", Part::bytes(access.as_str().to_string().into_bytes()));
+/// Expands environment variables in the manifest's links. Returns whether
+/// anything changed.
+fn expand_links(manifest: &mut PluginManifest) -> Result<bool> {
+    let mut changed = false;
+    let links = [
+        ("homepage", manifest.homepage.as_mut()),
+        ("repository.url", manifest.repository.as_mut().map(|r| &mut r.url)),
+    ];
+
+    for (field, value) in links.into_iter().filter_map(|(field, value)| Some((field, value?))) {
+        let expanded = interpolate::expand(value).with_context(|| t!("interpolate.in_field", field = field))?;
+        if expanded != *value {
+            *value = expanded;
+            changed = true;
+        }
+    }
+
+    Ok(changed)
+}
 
-    let response = http_client.post_multipart::<PluginResponse>("/plugins/publish", form);
+/// Uploads the archive. When the registry already has the version, the
+/// publish counts as done if it holds this very archive (an earlier run got
+/// through but never saw the answer), and fails otherwise.
+fn upload_plugin(upload: &Upload, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let checksum = sha256_hex(upload.data);
 
-    match response {
+    let package = format!("{}@{}", upload.name, upload.version);
+    match RegistryClient::new(http_client).publish(upload) {
         Ok(data) => {
-            println!(
-                "Plugin '{}' published successfuly with id: {}",
-                data.name.bold().blue(),
-                data.slug.cyan()
-            );
+            reporter.success(&t!(
+                "publish.published",
+                name = data.name.bold().blue(),
+                slug = data.slug.cyan()
+            ));
             Ok(())
         },
-        Err(e) => Err(e.into()),
+        Err(ClientError::Api { status: 409, payload, .. }) if payload.error.code == VERSION_EXISTS => {
+            let published = payload.error.details.as_ref().and_then(|d| d.get("checksum")).and_then(|c| c.as_str());
+            if published.is_some_and(|c| c.eq_ignore_ascii_case(&checksum)) {
+                reporter.success(&t!("publish.already_published", package = package.cyan()));
+                return Ok(());
+            }
+            Err(VkError::VersionExists(package).into())
+        },
+        // The registry may have taken the upload without the answer making it back
+        Err(err @ (ClientError::Transport(_) | ClientError::Serialization(_) | ClientError::Io(_))) => {
+            Err(anyhow::Error::from(err).context(t!("publish.retry_safe", package = package)))
+        },
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_level() {
+        let bump = |level: BumpLevel, version: &str| level.apply(&semver::Version::parse(version).unwrap()).to_string();
+        assert_eq!(bump(BumpLevel::Patch, "1.2.3"), "1.2.4");
+        assert_eq!(bump(BumpLevel::Minor, "1.2.3"), "1.3.0");
+        assert_eq!(bump(BumpLevel::Major, "1.2.3+build.5"), "2.0.0");
+        assert_eq!(bump(BumpLevel::Patch, "1.3.0-beta.1"), "1.3.0");
+        assert_eq!(bump(BumpLevel::Minor, "1.3.0-beta.1"), "1.3.0");
+        assert_eq!(bump(BumpLevel::Minor, "1.3.1-rc.1"), "1.4.0");
+        assert_eq!(bump(BumpLevel::Major, "2.0.0-rc.1"), "2.0.0");
     }
 }
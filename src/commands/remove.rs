@@ -1,49 +1,72 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::Colorize;
 use std::fs;
-use std::path::Path;
 
-use crate::{
-    encoding::json5,
-    manifest::{MANIFEST_FILENAME, PluginManifest},
-};
+use crate::scripts::{self, Hook};
+use crate::utils::remove_installed;
+use crate::{error::VkError, i18n::t, lockfile::Lockfile, project::Project, reporter::Reporter};
 
-pub fn remove_dependency(package: &str) -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
-
-    println!("{} Removing package {}", "🗑️".bold(), package.cyan());
-    let content = fs::read_to_string(manifest_path).context("Failed to read manifest file")?;
-    let mut manifest: PluginManifest = json5::from_str(&content).context("Failed to parse manifest file")?;
+pub fn remove_dependency(
+    project: &Project,
+    package: &str,
+    plugins_dir: &str,
+    ignore_scripts: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    reporter.title(&t!("remove.title", package = package.cyan()));
+    let mut manifest = project.read_manifest()?;
 
     let mut removed = false;
 
     if manifest.dependencies.remove(package).is_some() {
         removed = true;
-        println!("{} Removed from dependencies", "✓".green());
+        reporter.success(&t!("remove.removed_from_dependencies"));
     }
 
     #[allow(clippy::collapsible_if)]
     if let Some(deps) = manifest.dev_dependencies.as_mut() {
         if deps.remove(package).is_some() {
             removed = true;
-            println!("{} Removed from dev-dependencies", "✓".green());
+            reporter.success(&t!("remove.removed_from_dev_dependencies"));
         }
     }
 
+    if manifest.optional_dependencies.remove(package).is_some() {
+        removed = true;
+        reporter.success(&t!("remove.removed_from_optional_dependencies"));
+    }
+
     if !removed {
-        anyhow::bail!("Package {} not found in dependencies", package);
+        return Err(VkError::DependencyNotFound(package.to_string()).into());
     }
 
-    fs::write(manifest_path, json5::to_string_pretty(&manifest)?).context("Failed to write manifest file")?;
+    // The hook needs the plugin still in place, so it runs before anything is deleted
+    let plugin_path = project.root().join(plugins_dir).join(package);
+    scripts::run_plugin_hook(project, &plugin_path, Hook::PreRemove, ignore_scripts, reporter)?;
+
+    project.write_manifest(&manifest)?;
+
+    if plugin_path.symlink_metadata().is_ok() {
+        remove_installed(&plugin_path)?;
+        reporter.success(&t!(
+            "remove.removed_installed",
+            path = plugin_path.display().to_string().bright_black()
+        ));
+    }
+
+    let mut lockfile = Lockfile::load(project)?;
+    if lockfile.remove(package).is_some() {
+        lockfile.save(project)?;
+    }
 
     // TODO: Remove package from cache directory, API is unstable
-    let cache_dir = Path::new(".vk").join("modules").join(package);
+    let cache_dir = project.cache_dir().join("modules").join(package);
     if cache_dir.exists() {
         fs::remove_dir_all(&cache_dir).ok();
-        println!("{} Removed cached files", "✓".green());
+        reporter.success(&t!("remove.removed_cache"));
     }
 
-    println!("{} Package {} removed successfully!", "✅".green(), package.cyan());
+    reporter.done(&t!("remove.done", package = package.cyan()));
 
     Ok(())
 }
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::scripts;
+
+/// Runs a manifest script, or lists the available ones when `name` is omitted.
+pub fn run_script(project: &Project, name: Option<&str>, args: &[String], reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+
+    let Some(name) = name else {
+        if manifest.scripts.is_empty() {
+            reporter.info(&t!("run.no_scripts"));
+            return Ok(());
+        }
+
+        reporter.title(&t!("run.available"));
+        for (name, script) in &manifest.scripts {
+            reporter.output(&format!("  {}", name.cyan()));
+            reporter.output(&format!("    {}", script.bright_black()));
+        }
+        return Ok(());
+    };
+
+    if let Some(script) = manifest.scripts.get(name) {
+        reporter.step(&t!("run.running", name = name.cyan(), script = script.bright_black()));
+    }
+
+    scripts::run_script(project, &manifest, name, args)
+}
+
+pub fn exec_command(project: &Project, command: &[String], reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+
+    let Some((program, args)) = command.split_first() else {
+        anyhow::bail!(t!("run.exec_missing_command"));
+    };
+
+    reporter.step(&t!("run.executing", command = command.join(" ").bright_black()));
+
+    scripts::exec(project, &manifest, program, args)
+}
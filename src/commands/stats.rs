@@ -0,0 +1,144 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::error::VkError;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::resolver;
+use crate::utils::parse_package;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const BAR_WIDTH: usize = 30;
+/// Versions shown in the adoption chart; the rest are summed on one line.
+const MAX_VERSIONS: usize = 10;
+
+/// Shows the downloads of `package` over the last `days` days, their split
+/// across versions and its dependent count. With `json`, prints the
+/// registry's answer instead of charts.
+pub fn show_stats(
+    package: &str,
+    days: u32,
+    json: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let (id, _) = parse_package(package);
+    let stats = RegistryClient::new(http_client).stats(&id, days).map_err(|e| VkError::package_lookup(e, &id))?;
+
+    if json {
+        reporter.output(&serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let total: u64 = stats.downloads.iter().map(|d| d.count).sum();
+    reporter.title(&t!("stats.title", package = id.cyan(), days = days));
+    reporter.output(&format!(
+        "{} {}",
+        t!("stats.downloads").bright_black(),
+        total.to_string().yellow()
+    ));
+    reporter.output(&format!(
+        "{} {}",
+        t!("stats.dependents").bright_black(),
+        stats.dependents.to_string().yellow()
+    ));
+
+    if let (Some(first), Some(last)) = (stats.downloads.first(), stats.downloads.last()) {
+        let counts: Vec<u64> = stats.downloads.iter().map(|d| d.count).collect();
+        reporter.output("");
+        reporter.output(&t!("stats.daily").bold().to_string());
+        reporter.output(&format!("  {}", sparkline(&counts).green()));
+        reporter.output(&format!(
+            "  {} → {}  {}",
+            first.date,
+            last.date,
+            t!("stats.peak", count = counts.iter().max().copied().unwrap_or_default()).bright_black()
+        ));
+    }
+
+    let mut versions: Vec<(&String, u64)> = stats.versions.iter().map(|(v, c)| (v, *c)).collect();
+    if !versions.is_empty() {
+        // Most downloaded first, newest first among equals
+        versions.sort_by(|a, b| {
+            b.1.cmp(&a.1).then_with(|| resolver::parse_version(b.0).cmp(&resolver::parse_version(a.0)))
+        });
+        let version_total: u64 = versions.iter().map(|(_, c)| c).sum();
+        let max = versions[0].1;
+        let width = versions.iter().take(MAX_VERSIONS).map(|(v, _)| v.len()).max().unwrap_or_default();
+
+        reporter.output("");
+        reporter.output(&t!("stats.adoption").bold().to_string());
+        for (version, count) in versions.iter().take(MAX_VERSIONS) {
+            reporter.output(&format!(
+                "  {:<width$}  {}  {:>5.1}%  {}",
+                version,
+                format!("{:<bar$}", bar(*count, max), bar = BAR_WIDTH).cyan(),
+                percent(*count, version_total),
+                count.to_string().bright_black(),
+                width = width
+            ));
+        }
+        let rest = &versions[versions.len().min(MAX_VERSIONS)..];
+        if !rest.is_empty() {
+            let count: u64 = rest.iter().map(|(_, c)| c).sum();
+            reporter.info(&t!(
+                "stats.other_versions",
+                versions = rest.len(),
+                percent = format!("{:.1}", percent(count, version_total))
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// One block per value, as high as the value relative to the largest.
+fn sparkline(values: &[u64]) -> String {
+    let max = values.iter().max().copied().unwrap_or_default();
+    values
+        .iter()
+        .map(|&v| match max {
+            0 => SPARKS[0],
+            max => SPARKS[((v * (SPARKS.len() as u64 - 1)) / max) as usize],
+        })
+        .collect()
+}
+
+/// A bar of up to [`BAR_WIDTH`] blocks, as long as `value` relative to `max`.
+/// Non-zero values get at least one block.
+fn bar(value: u64, max: u64) -> String {
+    if max == 0 || value == 0 {
+        return String::new();
+    }
+    let len = ((value * BAR_WIDTH as u64) / max).max(1) as usize;
+    "█".repeat(len)
+}
+
+fn percent(value: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charts() {
+        assert_eq!(sparkline(&[0, 7, 14, 7]), "▁▄█▄");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+
+        assert_eq!(bar(100, 100).chars().count(), BAR_WIDTH);
+        assert_eq!(bar(50, 100).chars().count(), BAR_WIDTH / 2);
+        assert_eq!(bar(1, 1_000).chars().count(), 1);
+        assert_eq!(bar(0, 100), "");
+        assert_eq!(percent(1, 4), 25.0);
+        assert_eq!(percent(1, 0), 0.0);
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::dist_tag::{self, DEFAULT_TAG};
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::pre;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::utils::parse_package;
+
+/// Points `tag` at an already published version (`vk tag add foo@1.2.0 beta`).
+pub fn add_tag(package: &str, tag: &str, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    ensure_valid_tag(tag)?;
+
+    let (id, version) = parse_package(package);
+    let Some(version) = version.filter(|v| !dist_tag::is_dist_tag(v)) else {
+        anyhow::bail!(t!("tag.version_required", package = id));
+    };
+
+    reporter.title(&t!(
+        "tag.adding",
+        tag = tag.cyan(),
+        id = id.cyan(),
+        version = version.yellow()
+    ));
+
+    RegistryClient::new(http_client).set_dist_tag(&id, tag, &version)?;
+
+    reporter.done(&t!(
+        "tag.added",
+        tag = tag.cyan(),
+        id = id.cyan(),
+        version = version.yellow()
+    ));
+
+    Ok(())
+}
+
+pub fn remove_tag(package: &str, tag: &str, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    ensure_valid_tag(tag)?;
+
+    if tag == DEFAULT_TAG {
+        anyhow::bail!(t!("tag.cannot_remove_latest", tag = DEFAULT_TAG));
+    }
+
+    let (id, _) = parse_package(package);
+    reporter.title(&t!("tag.removing", tag = tag.cyan(), id = id.cyan()));
+
+    RegistryClient::new(http_client).remove_dist_tag(&id, tag)?;
+
+    reporter.done(&t!("tag.removed", tag = tag.cyan(), id = id.cyan()));
+
+    Ok(())
+}
+
+/// Lists the dist-tags of `package`, or of the current project when omitted.
+pub fn list_tags(package: Option<&str>, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let id = match package {
+        Some(package) => parse_package(package).0,
+        None => pre::ensure_manifest_exists()?.read_manifest()?.name,
+    };
+
+    reporter.title(&t!("tag.list_title", id = id.cyan()));
+
+    let tags = dist_tag::fetch(&id, http_client)?;
+    if tags.is_empty() {
+        reporter.info(&t!("tag.none"));
+        return Ok(());
+    }
+
+    for (tag, version) in &tags {
+        reporter.output(&format!("{}: {}", tag.cyan(), version.yellow()));
+    }
+
+    Ok(())
+}
+
+fn ensure_valid_tag(tag: &str) -> Result<()> {
+    if !dist_tag::is_dist_tag(tag) {
+        anyhow::bail!(t!("tag.invalid", tag = tag));
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::i18n::t;
+use crate::reporter::Reporter;
+use crate::telemetry;
+
+/// Turns the sending of anonymous usage metrics on or off. Turning it off
+/// also drops the events not sent yet.
+pub fn set_enabled(enabled: bool, reporter: &dyn Reporter) -> Result<()> {
+    telemetry::save_settings(&telemetry::Settings { enabled, asked: true })?;
+
+    if enabled {
+        reporter.success(&t!("telemetry.enabled"));
+        if let Some(var) = telemetry::disabled_by_env() {
+            reporter.warn(&t!("telemetry.env_override", var = var));
+        }
+    } else {
+        telemetry::clear_queue()?;
+        reporter.success(&t!("telemetry.disabled"));
+    }
+    Ok(())
+}
+
+pub fn show_status(endpoint: &str, reporter: &dyn Reporter) -> Result<()> {
+    let settings = telemetry::load_settings();
+
+    let state = match (telemetry::disabled_by_env(), settings.enabled) {
+        (Some(var), _) => t!("telemetry.status_env", var = var),
+        (None, true) => t!("telemetry.status_on").green().to_string(),
+        (None, false) if settings.asked => t!("telemetry.status_off"),
+        (None, false) => t!("telemetry.status_not_asked"),
+    };
+    reporter.output(&state);
+    reporter.output(&t!("telemetry.status_endpoint", endpoint = endpoint.cyan()));
+    reporter.output(&t!("telemetry.status_queued", count = telemetry::queued()));
+    Ok(())
+}
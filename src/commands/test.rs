@@ -0,0 +1,282 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::coverage::{self, Coverage};
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::scripts::{self, Suite};
+
+/// Merged coverage trace, under the project cache directory.
+const COVERAGE_FILE: &str = "coverage/lcov.info";
+
+pub struct TestOptions {
+    /// Only run test files whose path contains this.
+    pub filter: Option<String>,
+    /// Command to run each test file with, instead of the `test` script or
+    /// the host runtime.
+    pub interpreter: Option<String>,
+    /// Where to write a JUnit XML report.
+    pub junit: Option<String>,
+    /// Ask the runner for line coverage and report it.
+    pub coverage: bool,
+}
+
+/// Outcome of one test file.
+struct TestResult {
+    file: String,
+    passed: bool,
+    /// Exit code; `None` when the process was killed by a signal.
+    status: Option<i32>,
+    duration: Duration,
+}
+
+/// Runs every Lua file under `tests/`, one process per file, and fails when
+/// any of them exits with an error.
+pub fn run_tests(project: &Project, options: &TestOptions, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = project.read_manifest()?;
+    let files = Suite::Test.files(project);
+
+    let (selected, filtered): (Vec<_>, Vec<_>) =
+        files.into_iter().partition(|f| options.filter.as_deref().is_none_or(|filter| f.contains(filter)));
+
+    if selected.is_empty() {
+        reporter.info(&t!("test.none", dir = Suite::Test.dir()));
+        return Ok(());
+    }
+
+    reporter.title(&t!(
+        "test.title",
+        count = selected.len(),
+        name = manifest.name.cyan(),
+        version = manifest.version.yellow()
+    ));
+
+    // Each run writes its own trace, merged once it exits
+    let traces = match options.coverage {
+        true => Some(tempfile::Builder::new().prefix("vk-coverage-").tempdir()?),
+        false => None,
+    };
+    let mut coverage = Coverage::default();
+
+    let started = Instant::now();
+    let mut results = Vec::new();
+
+    for (index, file) in selected.iter().enumerate() {
+        reporter.step(&t!("test.running", file = file.cyan()));
+
+        let trace = traces.as_ref().map(|dir| dir.path().join(format!("{}.lcov", index)));
+        let file_started = Instant::now();
+        let env: Vec<_> = trace.iter().map(|path| ("VK_COVERAGE_FILE", path.as_os_str())).collect();
+        let status = scripts::run_suite_file(
+            project,
+            &manifest,
+            Suite::Test,
+            file,
+            options.interpreter.as_deref(),
+            &env,
+        )?;
+        let result = TestResult {
+            file: file.clone(),
+            passed: status.success(),
+            status: status.code(),
+            duration: file_started.elapsed(),
+        };
+
+        let time = format_duration(result.duration).bright_black();
+        if result.passed {
+            reporter.success(&t!("test.passed", file = file, time = time));
+        } else {
+            reporter.error(&t!("test.failed", file = file, time = time));
+        }
+
+        if let Some(content) = trace.and_then(|path| fs::read_to_string(path).ok()) {
+            coverage.add_lcov(&content);
+        }
+        results.push(result);
+    }
+
+    let elapsed = started.elapsed();
+    print_table(&results, reporter);
+
+    if let Some(path) = &options.junit {
+        let xml = junit_report(&manifest.name, &results, elapsed);
+        fs::write(path, xml).with_context(|| t!("common.write_failed", path = path))?;
+        reporter.detail(&t!("test.junit_written", path = path));
+    }
+
+    if options.coverage {
+        report_coverage(project, &coverage, reporter)?;
+    }
+
+    let failed: Vec<&str> = results.iter().filter(|r| !r.passed).map(|r| r.file.as_str()).collect();
+    let summary = t!(
+        "test.summary",
+        passed = results.len() - failed.len(),
+        failed = failed.len(),
+        filtered = filtered.len(),
+        time = format_duration(elapsed)
+    );
+
+    if !failed.is_empty() {
+        reporter.info(&summary);
+        return Err(VkError::TestsFailed(failed.join(", ")).into());
+    }
+
+    reporter.done(&summary);
+    Ok(())
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
+
+/// One row per test file, slowest first.
+fn print_table(results: &[TestResult], reporter: &dyn Reporter) {
+    let mut rows: Vec<&TestResult> = results.iter().collect();
+    rows.sort_by_key(|r| Reverse(r.duration));
+    let width = rows.iter().map(|r| r.file.len()).max().unwrap_or(0);
+
+    reporter.output("");
+    for row in rows {
+        let outcome = if row.passed {
+            format!("{:<6}", t!("test.table_passed")).green()
+        } else {
+            format!("{:<6}", t!("test.table_failed")).red()
+        };
+        reporter.output(&format!(
+            "  {}  {:<width$}  {}",
+            outcome,
+            row.file,
+            format!("{:>8}", format_duration(row.duration)).bright_black()
+        ));
+    }
+    reporter.output("");
+}
+
+/// Writes the merged trace to `.vk/coverage/lcov.info` and prints the line
+/// coverage of every source file.
+fn report_coverage(project: &Project, coverage: &Coverage, reporter: &dyn Reporter) -> Result<()> {
+    if coverage.is_empty() {
+        reporter.warn(&t!("test.coverage_unavailable"));
+        return Ok(());
+    }
+
+    for file in coverage.files() {
+        let path = Path::new(file.path);
+        let path = path.strip_prefix(project.root()).unwrap_or(path).display().to_string();
+        reporter.output(&format!(
+            "  {:>6.1}%  {}  {}",
+            coverage::percent(file.covered, file.lines),
+            path,
+            format!("{}/{}", file.covered, file.lines).bright_black()
+        ));
+    }
+
+    let out = project.cache_dir().join(COVERAGE_FILE);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+    }
+    fs::write(&out, coverage.to_lcov()).with_context(|| t!("common.write_failed", path = out.display()))?;
+
+    let (covered, lines) = coverage.totals();
+    reporter.info(&t!(
+        "test.coverage_total",
+        percent = format!("{:.1}", coverage::percent(covered, lines)),
+        covered = covered,
+        lines = lines,
+        path = out.strip_prefix(project.root()).unwrap_or(&out).display()
+    ));
+    Ok(())
+}
+
+/// A JUnit XML report with one test case per test file, as read by CI
+/// dashboards.
+fn junit_report(suite: &str, results: &[TestResult], elapsed: Duration) -> String {
+    let failures = results.iter().filter(|r| !r.passed).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"vk test\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failures,
+        elapsed.as_secs_f64()
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        xml_escape(suite),
+        results.len(),
+        failures,
+        elapsed.as_secs_f64()
+    );
+
+    for result in results {
+        let attributes = format!(
+            "name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.file),
+            xml_escape(suite),
+            result.duration.as_secs_f64()
+        );
+        if result.passed {
+            let _ = writeln!(xml, "    <testcase {}/>", attributes);
+            continue;
+        }
+
+        let message = match result.status {
+            Some(status) => t!("test.junit_exit_status", status = status),
+            None => t!("test.junit_killed"),
+        };
+        let _ = writeln!(xml, "    <testcase {}>", attributes);
+        let _ = writeln!(xml, "      <failure message=\"{}\"/>", xml_escape(&message));
+        let _ = writeln!(xml, "    </testcase>");
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report() {
+        let results = [
+            TestResult {
+                file: "tests/a.lua".into(),
+                passed: true,
+                status: Some(0),
+                duration: Duration::from_millis(15),
+            },
+            TestResult {
+                file: "tests/<b>.lua".into(),
+                passed: false,
+                status: Some(1),
+                duration: Duration::from_millis(1500),
+            },
+        ];
+        let xml = junit_report("my-plugin", &results, Duration::from_secs(2));
+
+        assert!(xml.contains("<testsuites name=\"vk test\" tests=\"2\" failures=\"1\" time=\"2.000\">"));
+        assert!(xml.contains("<testcase name=\"tests/a.lua\" classname=\"my-plugin\" time=\"0.015\"/>"));
+        assert!(xml.contains("<testcase name=\"tests/&lt;b&gt;.lua\" classname=\"my-plugin\" time=\"1.500\">"));
+        assert_eq!(xml.matches("<failure ").count(), 1);
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+
+/// Lists the API tokens of the logged-in user, every page of them.
+pub fn list_tokens(http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    reporter.title(&t!("token.list_title"));
+
+    let tokens = RegistryClient::new(http_client).tokens()?;
+    if tokens.is_empty() {
+        reporter.info(&t!("token.none"));
+        return Ok(());
+    }
+
+    for token in &tokens {
+        let created = token.created_at.map(|at| at.date()).unwrap_or_else(|| "-".to_string());
+        let last_used = match token.last_used_at {
+            Some(at) => t!("token.last_used", date = at.date()),
+            None => t!("token.never_used"),
+        };
+        reporter.output(&format!(
+            "{} {}  {}",
+            token.name.cyan(),
+            token.id.bright_black(),
+            t!("token.details", created = created, last_used = last_used).bright_black()
+        ));
+    }
+
+    Ok(())
+}
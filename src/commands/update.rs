@@ -1,137 +1,701 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::Colorize;
-use std::fs;
-use std::path::Path;
+use semver::Version;
+use serde::Serialize;
+use std::time::Duration;
 
-use crate::encoding::json5;
+use crate::commands::info::fetch_versions;
+use crate::error::VkError;
+use crate::git;
 use crate::http_client::HttpClient;
-use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
+use crate::i18n::t;
+use crate::lockfile::Lockfile;
+use crate::manifest::{Dependency, PluginManifest};
+use crate::project::{LOCKFILE_NAME, Project};
+use crate::registry::RegistryClient;
+use crate::report::{PackageEntry, Recorder};
+use crate::reporter::Reporter;
+use crate::resolver;
 use crate::utils::parse_package;
 
-pub fn update_dependencies(package: Option<&str>, http_client: &HttpClient) -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
+/// Release notes longer than this many lines are cut.
+const MAX_NOTE_LINES: usize = 12;
 
-    let content = fs::read_to_string(manifest_path).context("Failed to read manifest file")?;
-    let mut manifest: PluginManifest = json5::from_str(&content).context("Failed to parse manifest file")?;
+/// Release notes are a nicety; a slow registry should not hold up the update.
+const RELEASE_NOTES_TIMEOUT: Duration = Duration::from_secs(5);
 
-    if let Some(pkg) = package {
-        update_single_package(&mut manifest, pkg, http_client)?;
+pub struct UpdateOptions<'a> {
+    /// Report what would change without touching the manifest or lockfile.
+    pub dry_run: bool,
+    /// Ask which of the proposed changes to apply.
+    pub interactive: bool,
+    /// Move to the newest release even when the declared range excludes it,
+    /// rewriting the range.
+    pub latest: bool,
+    /// Notes the applied and skipped changes, for `--report`.
+    pub recorder: &'a Recorder,
+    /// Never prompts, and prints a JSON summary of the changes for tools
+    /// that open pull requests from it.
+    pub bot: bool,
+    /// With `bot`, commits the changes on git branches instead of leaving
+    /// them in the working tree.
+    pub branches: Option<BranchMode>,
+}
+
+/// How `vk update --bot` spreads the updates over branches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BranchMode {
+    /// A branch per updated package.
+    Each,
+    /// One branch with every update.
+    Grouped,
+}
+
+/// Branches made by `--branches` are named with this prefix.
+const BRANCH_PREFIX: &str = "vk-update/";
+
+/// What `vk update --bot` prints.
+#[derive(Debug, Serialize)]
+struct BotSummary {
+    updates: Vec<BotUpdate>,
+    /// Files changed in the working tree, when no branches were made.
+    changed_files: Vec<String>,
+    branches: Vec<BotBranch>,
+}
+
+#[derive(Debug, Serialize)]
+struct BotUpdate {
+    package: String,
+    dev: bool,
+    /// `bump`, or `unpin` for a git dependency.
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    /// The range written to the manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spec: Option<String>,
+    release_notes: Vec<ReleaseNote>,
+}
+
+#[derive(Debug, Serialize)]
+struct BotBranch {
+    name: String,
+    /// The commit with the updates.
+    commit: String,
+    packages: Vec<String>,
+    changed_files: Vec<String>,
+}
+
+/// The release notes of one version.
+#[derive(Debug, Serialize)]
+pub struct ReleaseNote {
+    pub version: String,
+    pub notes: String,
+}
+
+/// A change `vk update` proposes for one dependency.
+enum Change {
+    /// A registry dependency moving to a newer version.
+    Bump {
+        id: String,
+        dev: bool,
+        /// Installed version, or the declared one when nothing is locked.
+        from: String,
+        to: String,
+        /// Constraint written back to the manifest.
+        spec: String,
+    },
+    /// A git dependency whose pinned commit gets dropped, so the next install
+    /// fetches the current head of the requested ref.
+    Unpin { id: String, source: String },
+}
+
+impl Change {
+    fn id(&self) -> &str {
+        match self {
+            Change::Bump { id, .. } | Change::Unpin { id, .. } => id,
+        }
+    }
+
+    fn entry(&self) -> PackageEntry {
+        match self {
+            Change::Bump { id, from, to, .. } => PackageEntry {
+                id: id.clone(),
+                from: Some(from.clone()),
+                version: Some(to.clone()),
+                ..PackageEntry::default()
+            },
+            Change::Unpin { id, .. } => PackageEntry {
+                id: id.clone(),
+                reason: Some("git-unpinned"),
+                ..PackageEntry::default()
+            },
+        }
+    }
+
+    fn summary(&self, http_client: &HttpClient) -> BotUpdate {
+        match self {
+            Change::Bump { id, dev, from, to, spec } => BotUpdate {
+                package: id.clone(),
+                dev: *dev,
+                kind: "bump",
+                from: Some(from.clone()),
+                to: Some(to.clone()),
+                spec: Some(spec.clone()),
+                release_notes: release_notes(id, from, to, http_client),
+            },
+            Change::Unpin { id, .. } => BotUpdate {
+                package: id.clone(),
+                dev: false,
+                kind: "unpin",
+                from: None,
+                to: None,
+                spec: None,
+                release_notes: Vec::new(),
+            },
+        }
+    }
+
+    /// The subject of the commit `--branches` makes for this change alone.
+    fn commit_subject(&self) -> String {
+        match self {
+            Change::Bump { id, to, .. } => format!("Update {} to {}", id, to),
+            Change::Unpin { id, .. } => format!("Update {} to the head of its git ref", id),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Change::Bump { id, dev: false, from, to, .. } => {
+                t!(
+                    "update.bumped",
+                    package = id.cyan(),
+                    from = from.yellow(),
+                    to = to.green()
+                )
+            },
+            Change::Bump { id, dev: true, from, to, .. } => {
+                t!(
+                    "update.bumped_dev",
+                    package = id.cyan(),
+                    from = from.yellow(),
+                    to = to.green()
+                )
+            },
+            Change::Unpin { id, source } => {
+                t!(
+                    "update.git_unpinned",
+                    package = id.cyan(),
+                    source = source.bright_black()
+                )
+            },
+        }
+    }
+}
+
+pub fn update_dependencies(
+    project: &Project,
+    package: Option<&str>,
+    options: &UpdateOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut manifest = project.read_manifest()?;
+    let mut lockfile = Lockfile::load(project)?;
+
+    let changes = if let Some(pkg) = package {
+        plan_single_package(&manifest, &lockfile, pkg, options.latest, http_client, reporter)?
+    } else {
+        plan_all_packages(&manifest, &lockfile, options.latest, http_client, reporter)?
+    };
+
+    if options.bot {
+        return run_bot(project, manifest, lockfile, changes, options, http_client, reporter);
+    }
+    if changes.is_empty() {
+        reporter.done(&t!("update.nothing_to_do"));
+        return Ok(());
+    }
+
+    for change in &changes {
+        if options.dry_run || options.interactive {
+            reporter.info(&change.label());
+        } else {
+            reporter.success(&change.label());
+        }
+        if let Change::Bump { id, from, to, .. } = change {
+            show_release_notes(id, from, to, http_client, reporter);
+        }
+    }
+
+    if options.dry_run {
+        for change in &changes {
+            options.recorder.skipped(change.id(), "dry-run");
+        }
+        reporter.done(&t!("update.dry_run", count = changes.len()));
+        return Ok(());
+    }
+
+    let planned: Vec<String> = changes.iter().map(|c| c.id().to_string()).collect();
+    let changes = if options.interactive {
+        select_changes(changes, reporter)?
     } else {
-        update_all_packages(&mut manifest, http_client)?;
+        changes
+    };
+    for id in planned.iter().filter(|id| !changes.iter().any(|c| c.id() == id.as_str())) {
+        options.recorder.skipped(id, "not-selected");
+    }
+    if changes.is_empty() {
+        reporter.done(&t!("update.nothing_selected"));
+        return Ok(());
     }
 
-    fs::write(manifest_path, json5::to_string_pretty(&manifest)?).context("Failed to write manifest file")?;
+    for change in &changes {
+        apply(change, &mut manifest, &mut lockfile);
+        options.recorder.updated(change.entry());
+    }
+    write_changes(project, &manifest, &mut lockfile)?;
 
-    println!("{} Dependencies updated successfully!", "✅".green());
+    reporter.done(&t!("update.done"));
 
     Ok(())
 }
 
-fn update_single_package(manifest: &mut PluginManifest, package: &str, http_client: &HttpClient) -> Result<()> {
-    let (id, _) = parse_package(package);
+/// Writes the updated manifest and lockfile, returning the names of the
+/// files written. Projects without a lockfile do not get one.
+fn write_changes(project: &Project, manifest: &PluginManifest, lockfile: &mut Lockfile) -> Result<Vec<String>> {
+    project.write_manifest(manifest)?;
+    let mut files = vec![project.manifest_filename().to_string()];
+    if project.lockfile_path().exists() {
+        lockfile.save(project)?;
+        files.push(LOCKFILE_NAME.to_string());
+    }
+    Ok(files)
+}
 
-    println!("{} Updating {}", "🔄".bold(), id.cyan());
+/// `vk update --bot`: applies every change without asking, to the working
+/// tree or to branches, and prints what changed as JSON.
+fn run_bot(
+    project: &Project,
+    manifest: PluginManifest,
+    lockfile: Lockfile,
+    changes: Vec<Change>,
+    options: &UpdateOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut summary = BotSummary {
+        updates: changes.iter().map(|change| change.summary(http_client)).collect(),
+        changed_files: Vec::new(),
+        branches: Vec::new(),
+    };
 
-    let latest = fetch_latest_version(&id, http_client)?;
+    if options.dry_run {
+        for change in &changes {
+            options.recorder.skipped(change.id(), "dry-run");
+        }
+    } else if !changes.is_empty() {
+        match options.branches {
+            None => {
+                let (mut manifest, mut lockfile) = (manifest, lockfile);
+                for change in &changes {
+                    apply(change, &mut manifest, &mut lockfile);
+                }
+                summary.changed_files = write_changes(project, &manifest, &mut lockfile)?;
+            },
+            Some(mode) => summary.branches = commit_branches(project, &manifest, &lockfile, &changes, mode)?,
+        }
+        for change in &changes {
+            options.recorder.updated(change.entry());
+        }
+    }
 
-    let mut updated = false;
+    reporter.output(&serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
 
-    // ---- dependencies ----
-    if let Some(old_version) = manifest.dependencies.get_mut(&id) {
-        let previous = old_version.clone();
-        *old_version = latest.clone();
+/// Commits `changes` on branches made from the current commit, one per
+/// change or one for all of them, and goes back to where it started. A
+/// branch that already exists is reset, so a bot rerun refreshes it.
+fn commit_branches(
+    project: &Project,
+    manifest: &PluginManifest,
+    lockfile: &Lockfile,
+    changes: &[Change],
+    mode: BranchMode,
+) -> Result<Vec<BotBranch>> {
+    let root = project.root();
+    let tracked = [project.manifest_filename(), LOCKFILE_NAME];
+    let status = git::run(root, &["status", "--porcelain", "--", tracked[0], tracked[1]])?;
+    if !status.is_empty() {
+        anyhow::bail!(t!("update.bot_dirty"));
+    }
+    let base = match git::run(root, &["rev-parse", "--abbrev-ref", "HEAD"])? {
+        head if head == "HEAD" => git::run(root, &["rev-parse", "HEAD"])?,
+        branch => branch,
+    };
 
-        println!(
-            "{} {}: {} -> {}",
-            "✓".green(),
-            id.cyan(),
-            previous.yellow(),
-            latest.green()
-        );
+    let groups: Vec<(String, Vec<&Change>, String)> = match mode {
+        BranchMode::Each => changes
+            .iter()
+            .map(|change| (branch_name(change.id()), vec![change], change.commit_subject()))
+            .collect(),
+        BranchMode::Grouped => {
+            let mut message = "Update dependencies\n".to_string();
+            for change in changes {
+                message.push_str(&format!("\n- {}", change.commit_subject()));
+            }
+            vec![(branch_name("all"), changes.iter().collect(), message)]
+        },
+    };
+
+    let mut branches = Vec::new();
+    for (name, group, message) in groups {
+        git::run(root, &["checkout", "-q", "-B", &name])?;
+        let committed = (|| {
+            let (mut manifest, mut lockfile) = (manifest.clone(), lockfile.clone());
+            for change in &group {
+                apply(change, &mut manifest, &mut lockfile);
+            }
+            let files = write_changes(project, &manifest, &mut lockfile)?;
+            let mut add = vec!["add", "--"];
+            add.extend(files.iter().map(String::as_str));
+            git::run(root, &add)?;
+            git::run(root, &["commit", "-q", "-m", &message])?;
+            Ok::<_, anyhow::Error>(files)
+        })();
+        if committed.is_err() {
+            // Put the files back so the checkout below is not refused
+            let _ = git::run(root, &["checkout", "-q", "HEAD", "--", tracked[0]]);
+            let _ = git::run(root, &["checkout", "-q", "HEAD", "--", tracked[1]]);
+        }
+        let commit = git::run(root, &["rev-parse", "HEAD"]);
+        git::run(root, &["checkout", "-q", &base])?;
 
-        updated = true;
+        branches.push(BotBranch {
+            name,
+            commit: commit?,
+            packages: group.iter().map(|change| change.id().to_string()).collect(),
+            changed_files: committed?,
+        });
     }
+    Ok(branches)
+}
 
-    // ---- dev_dependencies ----
-    #[allow(clippy::collapsible_if)]
-    if let Some(dev_deps) = manifest.dev_dependencies.as_mut() {
-        if let Some(old_version) = dev_deps.get_mut(&id) {
-            let previous = old_version.clone();
-            *old_version = latest.clone();
+/// The branch for `id`, with characters git refuses in names replaced.
+fn branch_name(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{}{}", BRANCH_PREFIX, id.trim_matches(['-', '.']))
+}
 
-            println!(
-                "{} {} (dev): {} -> {}",
-                "✓".green(),
-                id.cyan(),
-                previous.yellow(),
-                latest.green()
-            );
+fn plan_single_package(
+    manifest: &PluginManifest,
+    lockfile: &Lockfile,
+    package: &str,
+    latest: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<Vec<Change>> {
+    let (id, _) = parse_package(package);
 
-            updated = true;
-        }
+    reporter.title(&t!("update.title", package = id.cyan()));
+
+    let declared = [
+        (false, manifest.dependencies.get(&id)),
+        (true, manifest.dev_dependencies.as_ref().and_then(|d| d.get(&id))),
+    ];
+    if declared.iter().all(|(_, dep)| dep.is_none()) {
+        return Err(VkError::DependencyNotFound(id).into());
     }
 
-    if !updated {
-        anyhow::bail!("Package {} not found in dependencies", id);
+    let mut changes = Vec::new();
+    for (dev, dep) in declared {
+        let Some(dep) = dep else {
+            continue;
+        };
+        if let Some(version) = lockfile.pinned(&id).filter(|_| matches!(dep, Dependency::Version(_))) {
+            reporter.warn(&t!("update.pinned", package = id.cyan(), version = version));
+            continue;
+        }
+        match dep {
+            Dependency::Version(spec) => {
+                let versions = fetch_versions(&id, http_client)?;
+                changes.extend(plan_version(&id, dev, spec, &versions, lockfile, latest, reporter)?);
+            },
+            _ => plan_other(&id, dep, lockfile, &mut changes, reporter),
+        }
     }
 
-    Ok(())
+    Ok(changes)
 }
 
-fn update_all_packages(manifest: &mut PluginManifest, http_client: &HttpClient) -> Result<()> {
-    println!("{} Updating all dependencies...", "🔄".bold());
+fn plan_all_packages(
+    manifest: &PluginManifest,
+    lockfile: &Lockfile,
+    latest: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<Vec<Change>> {
+    reporter.title(&t!("update.title_all"));
+
+    let all_deps = manifest
+        .dependencies
+        .iter()
+        .map(|dep| (false, dep))
+        .chain(manifest.dev_dependencies.iter().flatten().map(|dep| (true, dep)));
 
-    for (pkg, version) in manifest.dependencies.iter_mut() {
-        update_version(pkg, version, http_client)?;
+    let mut changes = Vec::new();
+    for (dev, (pkg, dep)) in all_deps {
+        if let Some(version) = lockfile.pinned(pkg).filter(|_| matches!(dep, Dependency::Version(_))) {
+            reporter.detail(&t!("update.pinned", package = pkg.cyan(), version = version));
+            continue;
+        }
+        match dep {
+            Dependency::Version(spec) => match fetch_versions(pkg, http_client) {
+                Ok(versions) => changes.extend(plan_version(pkg, dev, spec, &versions, lockfile, latest, reporter)?),
+                Err(_) => reporter.warn(&t!("update.fetch_latest_failed", package = pkg.cyan())),
+            },
+            _ => plan_other(pkg, dep, lockfile, &mut changes, reporter),
+        }
     }
 
-    if let Some(dev_deps) = manifest.dev_dependencies.as_mut() {
-        for (pkg, version) in dev_deps.iter_mut() {
-            update_version(pkg, version, http_client)?;
+    Ok(changes)
+}
+
+/// Picks the newest published version the declared range allows, or the
+/// newest overall with `latest`, and compares it with what is installed.
+fn plan_version(
+    id: &str,
+    dev: bool,
+    spec: &str,
+    versions: &[String],
+    lockfile: &Lockfile,
+    latest: bool,
+    reporter: &dyn Reporter,
+) -> Result<Option<Change>> {
+    let req =
+        resolver::parse_req(spec).ok_or_else(|| VkError::ManifestInvalid(t!("install.invalid_range", range = spec)))?;
+
+    let newest = resolver::latest(versions.iter().map(String::as_str), false);
+    let target = if latest {
+        newest.clone()
+    } else {
+        resolver::max_satisfying(versions.iter().map(String::as_str), &req, false)
+    };
+    let Some(target) = target else {
+        reporter.warn(&t!("update.no_matching_version", package = id.cyan(), range = spec));
+        return Ok(None);
+    };
+
+    // What is installed now: the locked version, else the declared one
+    let declared = spec.trim().trim_start_matches(['^', '~', '=']);
+    let from = lockfile
+        .get(id)
+        .filter(|p| p.is_registry())
+        .map(|p| p.version.as_str())
+        .or(resolver::parse_version(declared).map(|_| declared))
+        .unwrap_or(spec);
+    let new_spec = rewrite_spec(spec, &target, latest);
+
+    let behind = match resolver::parse_version(from) {
+        Some(current) => current < target,
+        None => new_spec != spec,
+    };
+    if !behind {
+        match newest.filter(|n| *n > target) {
+            Some(newest) => reporter.detail(&t!(
+                "update.latest_outside_range",
+                package = id.cyan(),
+                latest = newest.to_string().yellow(),
+                range = spec
+            )),
+            None => reporter.detail(&t!("update.already_latest", package = id.cyan())),
         }
+        return Ok(None);
     }
 
-    Ok(())
+    Ok(Some(Change::Bump {
+        id: id.to_string(),
+        dev,
+        from: from.to_string(),
+        to: target.to_string(),
+        spec: new_spec,
+    }))
 }
 
-fn update_version(pkg: &str, version: &mut String, http_client: &HttpClient) -> Result<()> {
-    let current = version.clone();
+/// The constraint to write back after moving to `target`. Single-version
+/// specs keep their operator (`^1.0.0` becomes `^1.2.0`) so the manifest
+/// records the new minimum; other ranges are kept as they are unless `latest`
+/// has to step outside them.
+fn rewrite_spec(spec: &str, target: &Version, latest: bool) -> String {
+    let trimmed = spec.trim();
+    if trimmed.is_empty() || trimmed == "*" {
+        return spec.to_string();
+    }
 
-    if current == "*" {
-        return Ok(());
+    let operator = ["^", "~", "="].into_iter().find(|op| trimmed.starts_with(op)).unwrap_or("");
+    if resolver::parse_version(&trimmed[operator.len()..]).is_some() {
+        return format!("{}{}", operator, target);
     }
 
-    match fetch_latest_version(pkg, http_client) {
-        Ok(latest) => {
-            if current != latest {
-                *version = latest.clone();
+    let req = resolver::parse_req(spec);
+    if latest && !req.is_some_and(|r| resolver::matches(&r, target, false)) {
+        format!("^{}", target)
+    } else {
+        spec.to_string()
+    }
+}
 
-                println!(
-                    "{} {}: {} -> {}",
-                    "✓".green(),
-                    pkg.cyan(),
-                    current.yellow(),
-                    latest.green()
-                );
+/// Git and path dependencies have no versions to bump: git ones get their
+/// pinned commit dropped, path ones are always current.
+fn plan_other(id: &str, dep: &Dependency, lockfile: &Lockfile, changes: &mut Vec<Change>, reporter: &dyn Reporter) {
+    match dep {
+        Dependency::Git(source) => {
+            if lockfile.get(id).is_some() {
+                changes.push(Change::Unpin { id: id.to_string(), source: source.to_string() });
             } else {
-                println!("{} {}: already at latest", "-".yellow(), pkg.cyan());
+                reporter.detail(&t!("update.git_not_pinned", package = id.cyan()));
             }
         },
-        Err(_) => {
-            println!("{} {}: could not fetch latest version", "⚠".yellow(), pkg.cyan());
+        Dependency::Path(source) => reporter.detail(&t!(
+            "update.path_skipped",
+            package = id.cyan(),
+            path = source.path.bright_black()
+        )),
+        Dependency::Version(_) => {},
+    }
+}
+
+fn apply(change: &Change, manifest: &mut PluginManifest, lockfile: &mut Lockfile) {
+    match change {
+        Change::Bump { id, dev, spec, .. } => {
+            let deps = if *dev {
+                manifest.dev_dependencies.as_mut()
+            } else {
+                Some(&mut manifest.dependencies)
+            };
+            if let Some(Dependency::Version(version)) = deps.and_then(|d| d.get_mut(id)) {
+                *version = spec.clone();
+            }
+            // Dropping the pin lets the next install resolve the new version
+            lockfile.remove(id);
+        },
+        Change::Unpin { id, .. } => {
+            lockfile.remove(id);
         },
     }
+}
 
-    Ok(())
+#[cfg(feature = "full")]
+fn select_changes(changes: Vec<Change>, reporter: &dyn Reporter) -> Result<Vec<Change>> {
+    use anyhow::Context;
+    use dialoguer::MultiSelect;
+
+    if !reporter.is_interactive() {
+        anyhow::bail!(t!("update.interactive_unavailable"));
+    }
+
+    let labels: Vec<String> = changes.iter().map(Change::label).collect();
+    let picked = MultiSelect::new()
+        .with_prompt(t!("update.prompt_select"))
+        .items(&labels)
+        .defaults(&vec![true; labels.len()])
+        .interact()
+        .with_context(|| t!("init.read_input_failed"))?;
+
+    Ok(changes.into_iter().enumerate().filter(|(i, _)| picked.contains(i)).map(|(_, c)| c).collect())
 }
 
-fn fetch_latest_version(id: &str, http_client: &HttpClient) -> Result<String> {
-    #[derive(serde::Deserialize)]
-    struct PackageInfo {
-        #[serde(rename = "latestVersion")]
-        latest_version: String,
+#[cfg(not(feature = "full"))]
+fn select_changes(_changes: Vec<Change>, _reporter: &dyn Reporter) -> Result<Vec<Change>> {
+    anyhow::bail!(t!("update.interactive_unavailable"))
+}
+
+/// The registry's release notes for every version after `from` up to and
+/// including `to`, newest first. Versions without notes are left out.
+pub fn release_notes(id: &str, from: &str, to: &str, http_client: &HttpClient) -> Vec<ReleaseNote> {
+    let http_client = http_client.with_request_timeout(RELEASE_NOTES_TIMEOUT);
+    let Ok(mut entries) = RegistryClient::new(&http_client).versions(id) else {
+        return Vec::new();
+    };
+
+    let from = resolver::parse_version(from);
+    let Some(to) = resolver::parse_version(to) else {
+        return Vec::new();
+    };
+    entries.retain(|e| {
+        resolver::parse_version(&e.version).is_some_and(|v| v <= to && from.as_ref().is_none_or(|f| v > *f))
+    });
+    entries.sort_by_cached_key(|e| std::cmp::Reverse(resolver::parse_version(&e.version)));
+
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let notes = entry.changelog.as_deref().map(str::trim).filter(|n| !n.is_empty())?.to_string();
+            Some(ReleaseNote { version: entry.version, notes })
+        })
+        .collect()
+}
+
+/// Prints the registry's release notes for every version after `from` up to
+/// and including `to`, newest first. Packages without notes print nothing.
+pub fn show_release_notes(id: &str, from: &str, to: &str, http_client: &HttpClient, reporter: &dyn Reporter) {
+    for note in release_notes(id, from, to, http_client) {
+        reporter.output(&format!(
+            "    {}",
+            t!("update.release_notes", version = note.version.yellow()).bold()
+        ));
+
+        let lines: Vec<&str> = note.notes.lines().collect();
+        for line in lines.iter().take(MAX_NOTE_LINES) {
+            reporter.output(&format!("      {}", line.bright_black()));
+        }
+        if lines.len() > MAX_NOTE_LINES {
+            reporter.output(&format!(
+                "      {}",
+                t!("update.release_notes_truncated", count = lines.len() - MAX_NOTE_LINES).bright_black()
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        resolver::parse_version(s).unwrap()
+    }
+
+    #[test]
+    fn test_rewrite_spec_keeps_operator() {
+        assert_eq!(rewrite_spec("^1.0.0", &v("1.2.0"), false), "^1.2.0");
+        assert_eq!(rewrite_spec("~1.0.0", &v("1.0.3"), false), "~1.0.3");
+        assert_eq!(rewrite_spec("1.0.0", &v("1.2.0"), false), "1.2.0");
+        assert_eq!(rewrite_spec("*", &v("2.0.0"), true), "*");
     }
 
-    let info = http_client.get::<PackageInfo>(&format!("/packages/{}", id))?;
-    Ok(info.latest_version)
+    #[test]
+    fn test_rewrite_spec_ranges() {
+        assert_eq!(rewrite_spec(">=1.0.0, <2.0.0", &v("1.4.0"), false), ">=1.0.0, <2.0.0");
+        assert_eq!(rewrite_spec(">=1.0.0, <2.0.0", &v("1.4.0"), true), ">=1.0.0, <2.0.0");
+        assert_eq!(rewrite_spec(">=1.0.0, <2.0.0", &v("2.1.0"), true), "^2.1.0");
+    }
+
+    #[test]
+    fn test_branch_name() {
+        assert_eq!(branch_name("ui-kit"), "vk-update/ui-kit");
+        assert_eq!(branch_name("@acme/ui kit"), "vk-update/acme-ui-kit");
+    }
 }
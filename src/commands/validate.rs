@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+
+use crate::capabilities::{self, Finding};
+use crate::commands::publish::{archive_spec, read_manifest};
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::lua;
+use crate::manifest::PluginManifest;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::{ArchiveSpec, package_files};
+
+/// Checks the plugin the way `vk publish` would see it: the manifest fields
+/// the registry requires and the syntax of every Lua file in the package.
+pub fn validate_package(project: &Project, allow_path_deps: bool, reporter: &dyn Reporter) -> Result<()> {
+    let manifest = read_manifest(project)?;
+
+    reporter.title(&t!(
+        "validate.title",
+        name = manifest.name.cyan(),
+        version = manifest.version.yellow()
+    ));
+
+    let spec = archive_spec(project, &manifest, allow_path_deps, reporter)?;
+    check_sources(project, &manifest, &spec, reporter)?;
+
+    reporter.done(&t!("validate.done"));
+    Ok(())
+}
+
+/// Syntax-checks `main` and every other `.lua` file the package would
+/// contain, reporting each error with its file and line.
+pub fn check_sources(
+    project: &Project,
+    manifest: &PluginManifest,
+    spec: &ArchiveSpec,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let files = package_files(project.root(), spec)?;
+
+    let main = manifest.main.trim_start_matches("./").replace('\\', "/");
+    if !main.is_empty() && !files.iter().any(|f| f.name == main) {
+        return Err(VkError::ManifestInvalid(t!("validate.main_not_packaged", main = manifest.main)).into());
+    }
+
+    let sources: Vec<_> = files.iter().filter(|f| f.name.ends_with(".lua")).collect();
+    reporter.step(&t!("validate.checking_lua", count = sources.len()));
+
+    let mut failed = Vec::new();
+    let mut findings = Vec::new();
+    for file in &sources {
+        let content = fs::read(&file.path).with_context(|| t!("common.read_failed", path = file.path.display()))?;
+        let source = String::from_utf8_lossy(&content);
+        if let Err(e) = lua::check(&source) {
+            reporter.error(&format!("{}:{}: {}", file.name.cyan(), e.line, e.message));
+            failed.push(file.name.clone());
+        }
+        findings.extend(capabilities::scan(&file.name, &source, manifest.permissions.as_ref()));
+    }
+
+    if !failed.is_empty() {
+        return Err(VkError::LuaSyntax(failed.join(", ")).into());
+    }
+
+    reporter.success(&t!("validate.lua_ok", count = sources.len()));
+    warn_undeclared(&manifest.name, &findings, reporter);
+    Ok(())
+}
+
+/// Warns about what the Lua sources of `plugin` do beyond its permissions.
+pub fn warn_undeclared(plugin: &str, findings: &[Finding], reporter: &dyn Reporter) {
+    if findings.is_empty() {
+        return;
+    }
+    reporter.warn(&t!("capabilities.undeclared", plugin = plugin.cyan()));
+    for finding in findings {
+        reporter.output(&format!(
+            "  {} {}",
+            format!("{}:{}", finding.file, finding.line).bright_black(),
+            finding.message
+        ));
+    }
+}
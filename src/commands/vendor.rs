@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::commands::install::{declared_dependencies, download_plugin, resolve_version, satisfies, verify_checksum};
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::manifest::Dependency;
+use crate::project::{Project, VENDOR_DIR};
+use crate::reporter::Reporter;
+use crate::resolver::Strategy;
+use crate::utils::{format_bytes, sha256_hex};
+
+/// File name of the vendored archive of a package version.
+pub fn archive_name(id: &str, version: &str) -> String {
+    format!("{}-{}.zip", id, version)
+}
+
+/// Downloads the archive of every registry dependency into `vendor/` and
+/// points the lockfile at it, so `vk install --offline` can run where the
+/// registry cannot be reached. Archives no dependency uses anymore are
+/// removed.
+pub fn vendor_dependencies(project: &Project, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+    let mut lockfile = Lockfile::load(project)?;
+    let deps = declared_dependencies(&project.read_manifest()?, &lockfile.features, None);
+
+    if deps.is_empty() {
+        reporter.info(&t!("list.empty"));
+        return Ok(());
+    }
+
+    let vendor_dir = project.vendor_dir();
+    fs::create_dir_all(&vendor_dir).with_context(|| t!("vendor.create_dir_failed", path = vendor_dir.display()))?;
+
+    reporter.title(&t!("vendor.title", count = deps.len(), path = VENDOR_DIR.cyan()));
+
+    let mut kept = BTreeSet::new();
+    for (id, dep) in &deps {
+        let spec = match dep {
+            Dependency::Version(spec) => spec,
+            Dependency::Git(_) => {
+                reporter.warn(&t!("vendor.git_skipped", id = id.cyan()));
+                continue;
+            },
+            Dependency::Path(_) => {
+                reporter.detail(&t!("vendor.path_skipped", id = id.cyan()));
+                continue;
+            },
+        };
+
+        let locked = lockfile.get(id).filter(|l| l.is_registry() && satisfies(spec, &l.version)).cloned();
+
+        // An archive vendored earlier for the locked version is kept as is
+        if let Some(locked) = &locked
+            && let (Some(archive), Some(checksum)) = (locked.vendored(), &locked.checksum)
+            && fs::read(project.root().join(archive)).is_ok_and(|data| sha256_hex(&data).eq_ignore_ascii_case(checksum))
+        {
+            reporter.success(&t!(
+                "vendor.up_to_date",
+                id = id.cyan(),
+                version = locked.version.yellow()
+            ));
+            kept.insert(archive_name(id, &locked.version));
+            continue;
+        }
+
+        let version = match &locked {
+            Some(locked) => Some(locked.version.clone()),
+            None if spec.is_empty() || spec == "*" => None,
+            None => resolve_version(id, Some(spec.clone()), false, Strategy::Highest, http_client, reporter)?,
+        };
+
+        let (data, meta) = download_plugin(id, version.as_deref(), http_client, reporter)?;
+        verify_checksum(&data, &meta, reporter)?;
+
+        let name = archive_name(id, &meta.version);
+        let path = vendor_dir.join(&name);
+        fs::write(&path, &data).with_context(|| t!("common.write_failed", path = path.display()))?;
+        reporter.success(&t!(
+            "vendor.saved",
+            id = id.cyan(),
+            version = meta.version.yellow(),
+            size = format_bytes(data.len())
+        ));
+
+        // What was installed only holds while the version does
+        let installed = locked.filter(|l| l.version == meta.version);
+        lockfile.upsert(LockedPackage {
+            id: id.clone(),
+            source: Some(format!("vendor+{}/{}", VENDOR_DIR, name)),
+            checksum: Some(meta.checksum.unwrap_or_else(|| sha256_hex(&data))),
+            version: meta.version,
+            integrity: installed.as_ref().and_then(|l| l.integrity.clone()),
+            dependencies: installed.as_ref().map(|l| l.dependencies.clone()).unwrap_or_default(),
+            overridden: installed.is_some_and(|l| l.overridden),
+        });
+        kept.insert(name);
+    }
+
+    for entry in fs::read_dir(&vendor_dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".zip") && !kept.contains(&name) {
+            fs::remove_file(entry.path()).with_context(|| t!("vendor.remove_failed", path = name))?;
+            reporter.detail(&t!("vendor.removed", path = format!("{}/{}", VENDOR_DIR, name)));
+        }
+    }
+
+    lockfile.save(project)?;
+    reporter.done(&t!("vendor.done", count = kept.len(), path = VENDOR_DIR));
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::commands::install::{self, is_linked};
+use crate::error::VkError;
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::lockfile::Lockfile;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::utils::hash_dir;
+
+/// Re-hashes every installed plugin and compares it with the hash recorded in
+/// the lockfile at install time. With `fix`, modified or missing plugins are
+/// reinstalled at their locked versions, restoring symlinks when
+/// `allow_symlinks` is set.
+pub fn verify_installs(
+    project: &Project,
+    plugins_dir: &str,
+    fix: bool,
+    allow_symlinks: bool,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let lockfile = Lockfile::load(project)?;
+    let plugins_path = project.root().join(plugins_dir);
+
+    if lockfile.packages.is_empty() {
+        reporter.info(&t!("verify.empty"));
+        return Ok(());
+    }
+
+    reporter.title(&t!("verify.title", count = lockfile.packages.len()));
+
+    let mut broken = Vec::new();
+
+    for package in &lockfile.packages {
+        let path = plugins_path.join(&package.id);
+        let label = format!("{}@{}", package.id, package.version);
+
+        let Some(expected) = &package.integrity else {
+            reporter.detail(&t!("verify.skipped", package = label.cyan()));
+            continue;
+        };
+
+        if is_linked(&path) {
+            reporter.detail(&t!("verify.skipped", package = label.cyan()));
+            continue;
+        }
+
+        if !path.is_dir() {
+            reporter.error(&t!("verify.missing", package = label.cyan()));
+            broken.push(package.id.clone());
+            continue;
+        }
+
+        if hash_dir(&path)? == *expected {
+            reporter.success(&t!("verify.ok", package = label.cyan()));
+        } else {
+            reporter.error(&t!("verify.modified", package = label.cyan()));
+            broken.push(package.id.clone());
+        }
+    }
+
+    if broken.is_empty() {
+        reporter.done(&t!("verify.all_ok"));
+        return Ok(());
+    }
+
+    if !fix {
+        return Err(VkError::IntegrityFailed(broken.join(", ")).into());
+    }
+
+    reporter.step(&t!("verify.repairing", count = broken.len()));
+    install::reinstall(project, &broken, plugins_dir, allow_symlinks, http_client, reporter)?;
+    reporter.done(&t!("verify.repaired", count = broken.len()));
+
+    Ok(())
+}
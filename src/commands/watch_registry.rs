@@ -0,0 +1,209 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+use crate::commands::install::{declared_dependencies, satisfies};
+use crate::events::{self, Event};
+use crate::http_client::HttpClient;
+use crate::i18n::t;
+use crate::lockfile::Lockfile;
+use crate::project::Project;
+use crate::reporter::Reporter;
+
+pub struct WatchOptions<'a> {
+    /// Print each event as a JSON object, one per line.
+    pub json: bool,
+    /// URL each event is posted to, as JSON.
+    pub webhook: Option<&'a str>,
+}
+
+/// What the registry sends about a package. Which fields are set depends on
+/// the event type.
+#[derive(Debug, Deserialize)]
+struct Notice {
+    package: String,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    severity: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    /// Versions an advisory affects.
+    #[serde(default)]
+    range: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// A package the project depends on: its manifest range, if declared there,
+/// and its locked version.
+#[derive(Debug, Default)]
+struct Watched {
+    range: Option<String>,
+    locked: Option<String>,
+}
+
+/// Subscribes to the registry's events about the project's dependencies,
+/// direct or locked: new versions, advisories and yanks. Runs until the
+/// registry ends the stream or the user stops it.
+pub fn watch_registry(
+    project: &Project,
+    options: &WatchOptions,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let manifest = project.read_manifest()?;
+    let lockfile = Lockfile::load(project)?;
+
+    let all_features: Vec<String> = manifest.optional_dependencies.keys().cloned().collect();
+    let mut watched: BTreeMap<String, Watched> = BTreeMap::new();
+    for (id, dep) in declared_dependencies(&manifest, &all_features, None) {
+        if let Some(range) = dep.version() {
+            watched.entry(id).or_default().range = Some(range.to_string());
+        }
+    }
+    for package in lockfile.packages.iter().filter(|p| p.is_registry()) {
+        watched.entry(package.id.clone()).or_default().locked = Some(package.version.clone());
+    }
+    if watched.is_empty() {
+        reporter.info(&t!("watch.nothing"));
+        return Ok(());
+    }
+
+    reporter.title(&t!(
+        "watch.title",
+        count = watched.len(),
+        registry = http_client.base_url().cyan()
+    ));
+    let packages = watched.keys().cloned().collect::<Vec<_>>().join(",");
+    let query = vec![("packages".to_string(), packages)];
+
+    for event in events::subscribe(http_client, "/events", query, true) {
+        let event = event?;
+        let Ok(notice) = serde_json::from_str::<Notice>(&event.data) else {
+            continue;
+        };
+        let Some(package) = watched.get(&notice.package) else {
+            continue;
+        };
+        let Some(text) = describe(&event, &notice, package) else {
+            continue;
+        };
+
+        if options.json {
+            reporter.output(&payload(&event, &text).to_string());
+        } else if event.event == "version" {
+            reporter.info(&text);
+        } else {
+            reporter.warn(&text);
+        }
+
+        if let Some(webhook) = options.webhook
+            && let Err(err) = http_client.post_url(webhook, &payload(&event, &text))
+        {
+            reporter.warn(&t!("watch.webhook_failed", error = err));
+        }
+    }
+
+    reporter.info(&t!("watch.closed"));
+    Ok(())
+}
+
+/// One-line summary of an event, or None for event types vk does not know.
+fn describe(event: &Event, notice: &Notice, package: &Watched) -> Option<String> {
+    let name = &notice.package;
+    let version = notice.version.as_deref().unwrap_or("?");
+    let locked = package.locked.as_deref();
+
+    let text = match event.event.as_str() {
+        "version" => match (&package.range, locked) {
+            (Some(range), Some(locked)) if satisfies(range, version) => {
+                t!(
+                    "watch.version_in_range",
+                    package = name,
+                    version = version,
+                    range = range,
+                    locked = locked
+                )
+            },
+            (Some(range), _) => t!(
+                "watch.version_out_of_range",
+                package = name,
+                version = version,
+                range = range
+            ),
+            (None, _) => t!("watch.version", package = name, version = version),
+        },
+        "advisory" => {
+            let range = notice.range.as_deref().unwrap_or("*");
+            let mut text = t!(
+                "watch.advisory",
+                package = name,
+                severity = notice.severity.as_deref().unwrap_or("unknown"),
+                id = notice.id.as_deref().unwrap_or("?"),
+                title = notice.title.as_deref().unwrap_or(""),
+                range = range
+            );
+            if let Some(locked) = locked.filter(|locked| satisfies(range, locked)) {
+                text = format!("{}. {}", text, t!("watch.affects_locked", locked = locked));
+            }
+            text
+        },
+        "yank" => {
+            let mut text = t!(
+                "watch.yank",
+                package = name,
+                version = version,
+                reason = notice.reason.as_deref().unwrap_or("-")
+            );
+            if locked == Some(version) {
+                text = format!("{}. {}", text, t!("watch.yank_locked"));
+            }
+            text
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// The event as forwarded to webhooks and printed by `--json`. `text`
+/// makes it readable by chat webhooks as is.
+fn payload(event: &Event, text: &str) -> Value {
+    let data: Value = serde_json::from_str(&event.data).unwrap_or(Value::Null);
+    json!({ "event": event.event, "id": event.id, "text": text, "data": data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let event = |kind: &str| Event { event: kind.to_string(), data: String::new(), id: None };
+        let notice = |version: Option<&str>, range: Option<&str>| Notice {
+            package: "hello".into(),
+            version: version.map(String::from),
+            id: Some("VK-1".into()),
+            severity: Some("high".into()),
+            title: Some("Bad".into()),
+            range: range.map(String::from),
+            reason: None,
+        };
+        let package = Watched { range: Some("^2.0.0".into()), locked: Some("2.0.0".into()) };
+
+        let in_range = describe(&event("version"), &notice(Some("2.1.0"), None), &package).unwrap();
+        let out_of_range = describe(&event("version"), &notice(Some("3.0.0"), None), &package).unwrap();
+        assert_ne!(in_range, out_of_range);
+        assert!(in_range.contains("2.1.0"));
+
+        let affected = describe(&event("advisory"), &notice(None, Some("<2.0.1")), &package).unwrap();
+        let unaffected = describe(&event("advisory"), &notice(None, Some("<2.0.0")), &package).unwrap();
+        assert!(affected.len() > unaffected.len());
+
+        assert!(describe(&event("ping"), &notice(None, None), &package).is_none());
+    }
+}
@@ -1,39 +1,335 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::http_client::Timeouts;
+use crate::i18n::t;
+use crate::interpolate;
+use crate::limits::PublishLimits;
+use crate::manifest::InstallDir;
+use crate::store::StoreMode;
 
 #[allow(unused)]
 const DEFAULT_CONFIG: &str = include_str!("../config.toml");
 
 #[cfg(debug_assertions)]
-pub fn default_config_path() -> PathBuf {
-    PathBuf::from("./config.toml")
+pub fn default_config_path() -> Result<PathBuf> {
+    Ok(PathBuf::from("./config.toml"))
 }
 
 #[cfg(not(debug_assertions))]
-pub fn default_config_path() -> PathBuf {
-    dirs::home_dir().expect("No home directory").join(".vayload-kit").join("config.toml")
+pub fn default_config_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("config.toml"))
+}
+
+/// `~/.vayload-kit`, where vk keeps its configuration and data.
+pub fn data_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().with_context(|| t!("common.no_home"))?;
+    Ok(home.join(".vayload-kit"))
+}
+
+/// Where `vk link` registers plugins under development, one symlink per
+/// plugin name. `VK_LINKS_DIR` overrides it.
+pub fn links_dir() -> Result<PathBuf> {
+    match std::env::var_os("VK_LINKS_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(data_dir()?.join("links")),
+    }
+}
+
+/// Where `vk host install` keeps host runtimes, one directory per version.
+/// `VK_HOSTS_DIR` overrides it.
+pub fn hosts_dir() -> Result<PathBuf> {
+    match std::env::var_os("VK_HOSTS_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(data_dir()?.join("hosts")),
+    }
+}
+
+/// Where installs share extracted registry packages, when `install.store`
+/// is on. `VK_STORE_DIR` overrides it.
+pub fn store_dir() -> Result<PathBuf> {
+    match std::env::var_os("VK_STORE_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(data_dir()?.join("store")),
+    }
+}
+
+/// Where checkouts of `git+` registries are kept, one directory per remote.
+/// `VK_REGISTRIES_DIR` overrides it.
+pub fn registries_dir() -> Result<PathBuf> {
+    match std::env::var_os("VK_REGISTRIES_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(data_dir()?.join("registries")),
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub server: AppServer,
+    #[serde(default)]
+    pub ui: AppUi,
+    #[serde(default)]
+    pub http: AppHttp,
+    #[serde(default)]
+    pub telemetry: AppTelemetry,
+    #[serde(default)]
+    pub install: AppInstall,
+    #[serde(default)]
+    pub auth: AppAuth,
+    #[serde(default)]
+    pub init: AppInit,
+    #[serde(default)]
+    pub user: AppUser,
+    /// Limits `vk publish` checks packages against, in place of the ones the
+    /// registry advertises.
+    #[serde(default)]
+    pub publish: PublishLimits,
+    /// Hosts `vk deploy` pushes to, by environment name.
+    #[serde(default)]
+    pub deploy: BTreeMap<String, DeployTarget>,
+}
+
+/// A Vayload host environment plugins are deployed to.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeployTarget {
+    pub url: String,
+    /// Token sent to the host, usually `${VAR}` so it stays out of the file.
+    pub token: Option<String>,
+    /// Seconds to wait for the host to report the plugin healthy.
+    pub health_timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AppAuth {
+    /// Ports the OAuth login callback may listen on: one (`53682`) or a
+    /// range (`53682-53690`). Any free port when unset.
+    pub callback_ports: Option<String>,
+    /// Seconds the key derived from the credentials passphrase is kept in
+    /// memory; 0 asks for the passphrase on every command.
+    pub passphrase_cache: Option<u64>,
+}
+
+impl AppAuth {
+    pub fn passphrase_cache(&self) -> Duration {
+        Duration::from_secs(self.passphrase_cache.unwrap_or(900))
+    }
+
+    pub fn callback_ports(&self) -> Result<Option<RangeInclusive<u16>>> {
+        let Some(ports) = &self.callback_ports else {
+            return Ok(None);
+        };
+        let invalid = || t!("auth.invalid_ports", ports = ports);
+        let (first, last) = ports.split_once('-').unwrap_or((ports, ports));
+        let first: u16 = first.trim().parse().with_context(invalid)?;
+        let last: u16 = last.trim().parse().with_context(invalid)?;
+        if first == 0 || first > last {
+            anyhow::bail!(invalid());
+        }
+        Ok(Some(first..=last))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AppInstall {
+    /// Plugins directory of projects whose manifest sets no `install_dir`.
+    pub dir: Option<InstallDir>,
+    /// Restore the symlinks packages store. Off by default, so a package can
+    /// only add regular files.
+    #[serde(default)]
+    pub symlinks: bool,
+    /// Refuse registry packages without verified provenance.
+    #[serde(default)]
+    pub require_provenance: bool,
+    /// Host runtime and Lua versions plugins are checked against, for
+    /// machines that install plugins without running them.
+    pub host_version: Option<String>,
+    pub lua_version: Option<String>,
+    /// Only warn about plugins whose `engines` ranges do not match.
+    #[serde(default)]
+    pub ignore_engines: bool,
+    /// How registry packages are shared between projects.
+    #[serde(default)]
+    pub store: StoreMode,
+    /// Publishers whose packages install without asking.
+    #[serde(default)]
+    pub trusted_publishers: Vec<String>,
+    /// Publishers whose packages are never installed.
+    #[serde(default)]
+    pub blocked_publishers: Vec<String>,
+    /// Refuse packages of publishers not in `trusted_publishers`.
+    #[serde(default)]
+    pub require_trusted: bool,
+    /// Inspect every registry package before installing it.
+    #[serde(default)]
+    pub inspect: bool,
+}
+
+/// Who the user is, for the author of the plugins they create. Either part
+/// unset is taken from `git config`.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AppUser {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Defaults of `vk init`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppInit {
+    /// SPDX id of the license new projects get.
+    pub license: String,
+    /// Make new projects git repositories.
+    pub git: bool,
+}
+
+impl Default for AppInit {
+    fn default() -> Self {
+        AppInit { license: "MIT".to_string(), git: true }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AppTelemetry {
+    /// Where usage metrics are sent, when the user opted in. Defaults to the
+    /// registry's `/telemetry/events`.
+    pub endpoint: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppServer {
     pub registry_url: String,
+    /// Read-only copies of the registry, tried in order when it is down.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// Status page of the registry, pointed to when it is down.
+    pub status_page: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AppUi {
+    /// Language of CLI messages (`en`, `es`). Defaults to `LANG`.
+    pub locale: Option<String>,
+}
+
+/// Registry request timeouts, in seconds.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppHttp {
+    /// Time to establish a connection.
+    pub connect_timeout: u64,
+    /// Overall limit of a metadata request (lookups, login, tags).
+    pub request_timeout: u64,
+    /// Time a download or upload may go without moving any data.
+    pub stall_timeout: u64,
+    /// Overall limit of an upload, including the registry's answer.
+    pub upload_timeout: u64,
+}
+
+impl Default for AppHttp {
+    fn default() -> Self {
+        AppHttp {
+            connect_timeout: 10,
+            request_timeout: 30,
+            stall_timeout: 60,
+            upload_timeout: 3600,
+        }
+    }
+}
+
+impl AppHttp {
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            connect: Duration::from_secs(self.connect_timeout),
+            request: Duration::from_secs(self.request_timeout),
+            stall: Duration::from_secs(self.stall_timeout),
+            upload: Duration::from_secs(self.upload_timeout),
+        }
+    }
 }
 
 impl AppConfig {
     pub fn load() -> Result<Self> {
         if let Ok(registry_url) = std::env::var("VK_REGISTRY_URL") {
-            return Ok(AppConfig { server: AppServer { registry_url } });
+            return Ok(AppConfig {
+                server: AppServer { registry_url, mirrors: Vec::new(), status_page: None },
+                ui: AppUi::default(),
+                http: AppHttp::default(),
+                telemetry: AppTelemetry::default(),
+                install: AppInstall::default(),
+                auth: AppAuth::default(),
+                init: AppInit::default(),
+                user: AppUser::default(),
+                publish: PublishLimits::default(),
+                deploy: BTreeMap::new(),
+            });
         }
 
+        let mut config = Self::read()?;
+        config.expand_env()?;
+        Ok(config)
+    }
+
+    /// Where usage metrics are sent when the user opted in.
+    #[cfg(feature = "full")]
+    pub fn telemetry_endpoint(&self) -> String {
+        match &self.telemetry.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!("{}/telemetry/events", self.server.registry_url.trim_end_matches('/')),
+        }
+    }
+
+    /// Expands `${VAR}` references in the settings, so one file can serve
+    /// several registries.
+    fn expand_env(&mut self) -> Result<()> {
+        self.server.registry_url = interpolate::expand(&self.server.registry_url)
+            .with_context(|| t!("interpolate.in_field", field = "server.registry_url"))?;
+        for (i, mirror) in self.server.mirrors.iter_mut().enumerate() {
+            *mirror = interpolate::expand(mirror)
+                .with_context(|| t!("interpolate.in_field", field = format!("server.mirrors[{}]", i)))?;
+        }
+        if let Some(endpoint) = &self.telemetry.endpoint {
+            self.telemetry.endpoint = Some(
+                interpolate::expand(endpoint)
+                    .with_context(|| t!("interpolate.in_field", field = "telemetry.endpoint"))?,
+            );
+        }
+        match &mut self.install.dir {
+            Some(InstallDir::Path(path)) => {
+                *path = interpolate::expand(path).with_context(|| t!("interpolate.in_field", field = "install.dir"))?;
+            },
+            Some(InstallDir::PerEnvironment(dirs)) => {
+                for (environment, path) in dirs.iter_mut() {
+                    *path = interpolate::expand(path)
+                        .with_context(|| t!("interpolate.in_field", field = format!("install.dir.{}", environment)))?;
+                }
+            },
+            None => {},
+        }
+        for (name, target) in self.deploy.iter_mut() {
+            target.url = interpolate::expand(&target.url)
+                .with_context(|| t!("interpolate.in_field", field = format!("deploy.{}.url", name)))?;
+            if let Some(token) = &target.token {
+                target.token = Some(
+                    interpolate::expand(token)
+                        .with_context(|| t!("interpolate.in_field", field = format!("deploy.{}.token", name)))?,
+                );
+            }
+        }
+        if let Some(locale) = &self.ui.locale {
+            self.ui.locale =
+                Some(interpolate::expand(locale).with_context(|| t!("interpolate.in_field", field = "ui.locale"))?);
+        }
+        Ok(())
+    }
+
+    fn read() -> Result<Self> {
         #[cfg(feature = "full")]
         {
-            let path = default_config_path();
+            let path = default_config_path()?;
 
             #[cfg(not(debug_assertions))]
             if !path.exists() {
@@ -60,3 +356,25 @@ impl AppConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callback_ports() {
+        let ports = |value: &str| {
+            AppAuth {
+                callback_ports: Some(value.to_string()),
+                ..Default::default()
+            }
+            .callback_ports()
+        };
+        assert_eq!(AppAuth::default().callback_ports().unwrap(), None);
+        assert_eq!(ports("53682").unwrap(), Some(53682..=53682));
+        assert_eq!(ports("53682 - 53690").unwrap(), Some(53682..=53690));
+        assert!(ports("53690-53682").is_err());
+        assert!(ports("0").is_err());
+        assert!(ports("http").is_err());
+    }
+}
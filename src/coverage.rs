@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Line hits per source file, merged from the LCOV traces the host runtime
+/// writes when it runs with coverage enabled.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: BTreeMap<String, BTreeMap<u32, u64>>,
+}
+
+/// Coverage of one source file.
+pub struct FileCoverage<'a> {
+    pub path: &'a str,
+    pub covered: usize,
+    pub lines: usize,
+}
+
+impl Coverage {
+    /// Adds the `SF:`/`DA:` records of an LCOV trace, summing the hits of
+    /// lines seen before. Other records are ignored.
+    pub fn add_lcov(&mut self, content: &str) {
+        let mut current: Option<&mut BTreeMap<u32, u64>> = None;
+
+        for line in content.lines().map(str::trim) {
+            if let Some(path) = line.strip_prefix("SF:") {
+                current = Some(self.files.entry(path.to_string()).or_default());
+            } else if line == "end_of_record" {
+                current = None;
+            } else if let (Some(data), Some(hits)) = (line.strip_prefix("DA:"), current.as_deref_mut()) {
+                let mut fields = data.split(',');
+                let (Some(Ok(number)), Some(Ok(count))) = (
+                    fields.next().map(str::parse::<u32>),
+                    fields.next().map(str::parse::<u64>),
+                ) else {
+                    continue;
+                };
+                *hits.entry(number).or_default() += count;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Covered and instrumented line counts per file, by path.
+    pub fn files(&self) -> impl Iterator<Item = FileCoverage<'_>> {
+        self.files.iter().map(|(path, hits)| FileCoverage {
+            path,
+            covered: hits.values().filter(|&&h| h > 0).count(),
+            lines: hits.len(),
+        })
+    }
+
+    /// Covered and instrumented lines over all files.
+    pub fn totals(&self) -> (usize, usize) {
+        self.files().fold((0, 0), |(covered, lines), f| (covered + f.covered, lines + f.lines))
+    }
+
+    /// The merged trace in LCOV format.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, hits) in &self.files {
+            let _ = writeln!(out, "SF:{}", path);
+            for (line, count) in hits {
+                let _ = writeln!(out, "DA:{},{}", line, count);
+            }
+            let _ = writeln!(out, "LH:{}", hits.values().filter(|&&h| h > 0).count());
+            let _ = writeln!(out, "LF:{}", hits.len());
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+}
+
+/// `covered` as a percentage of `lines`; a file without instrumented lines
+/// counts as fully covered.
+pub fn percent(covered: usize, lines: usize) -> f64 {
+    if lines == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / lines as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_are_merged() {
+        let mut coverage = Coverage::default();
+        coverage.add_lcov("TN:\nSF:src/init.lua\nDA:1,1\nDA:2,0\nDA:3,0\nend_of_record\n");
+        coverage
+            .add_lcov("SF:src/init.lua\nFN:1,f\nDA:2,4,abc\nend_of_record\nSF:src/util.lua\nDA:1,0\nend_of_record\n");
+
+        let files: Vec<_> = coverage.files().map(|f| (f.path.to_string(), f.covered, f.lines)).collect();
+        assert_eq!(
+            files,
+            vec![("src/init.lua".to_string(), 2, 3), ("src/util.lua".to_string(), 0, 1)]
+        );
+        assert_eq!(coverage.totals(), (2, 4));
+        assert!(coverage.to_lcov().contains("SF:src/init.lua\nDA:1,1\nDA:2,4\nDA:3,0\nLH:2\nLF:3\nend_of_record\n"));
+    }
+
+    #[test]
+    fn test_records_outside_a_file_are_ignored() {
+        let mut coverage = Coverage::default();
+        coverage.add_lcov("DA:1,1\nnot lcov at all\n");
+        assert!(coverage.is_empty());
+        assert_eq!(percent(0, 0), 100.0);
+        assert_eq!(percent(1, 4), 25.0);
+    }
+}
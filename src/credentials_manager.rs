@@ -1,12 +1,22 @@
 use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine, engine::general_purpose::STANDARD};
 use chacha20poly1305::{
     AeadCore, ChaCha20Poly1305, Nonce,
     aead::{Aead, KeyInit, OsRng},
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::agent;
+use crate::error::VkError;
+use crate::i18n::t;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Credentials {
@@ -14,6 +24,9 @@ pub struct Credentials {
     access_expires_at: u64,
     refresh_token: String,
     refresh_expires_at: u64,
+    /// Organization the tokens are scoped to, for SSO logins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    organization: Option<String>,
 }
 
 pub struct RawCredentials {
@@ -21,6 +34,7 @@ pub struct RawCredentials {
     pub access_expires_in: u64,
     pub refresh_token: String,
     pub refresh_expires_in: u64,
+    pub organization: Option<String>,
 }
 
 impl RawCredentials {
@@ -30,6 +44,7 @@ impl RawCredentials {
             access_expires_in,
             refresh_token,
             refresh_expires_in: (access_expires_in / 60) * 24 * 60 * 60,
+            organization: None,
         }
     }
 
@@ -41,29 +56,223 @@ impl RawCredentials {
             access_expires_at: now + self.access_expires_in,
             refresh_token: self.refresh_token.clone(),
             refresh_expires_at: now + self.refresh_expires_in * 60,
+            organization: self.organization.clone(),
         })
     }
 }
 
+/// Version of the credentials file envelope. Files without one predate it.
+const ENVELOPE_VERSION: u32 = 2;
+/// Version of the key file. Raw 32-byte key files predate it.
+const KEY_RING_VERSION: u32 = 1;
+
+/// The credentials file: the encrypted credentials, naming the key they
+/// were encrypted with.
 #[derive(Debug, Serialize, Deserialize)]
-struct EncryptedCredentials {
+struct Envelope {
+    version: u32,
+    key_id: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// The credentials file before envelopes were versioned, encrypted with
+/// the raw key file.
+#[derive(Debug, Deserialize)]
+struct LegacyEnvelope {
     ciphertext: Vec<u8>,
     nonce: Vec<u8>,
 }
 
+/// The key file. The last key is the current one; earlier ones only remain
+/// while a rotation is under way, so an interrupted one loses nothing.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyRing {
+    version: u32,
+    keys: Vec<StoredKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    id: String,
+    /// The key itself, for keys kept in the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    /// How the key is derived from the passphrase, for keys never stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    derived: Option<Derivation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Derivation {
+    kdf: String,
+    #[serde(flatten)]
+    cost: KdfCost,
+    salt: String,
+}
+
+impl StoredKey {
+    fn generate() -> Self {
+        Self::from_bytes(ChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    fn from_bytes(key: [u8; 32]) -> Self {
+        StoredKey { id: key_id(&key), key: Some(hex::encode(key)), derived: None }
+    }
+
+    /// A new key derived from `passphrase` at `cost`, with the key.
+    fn derive(passphrase: &str, cost: KdfCost) -> Result<(Self, [u8; 32])> {
+        let salt: [u8; 16] = rand::random();
+        let key = derive_key(passphrase, &salt, cost)?;
+        let derivation = Derivation { kdf: KDF.to_string(), cost, salt: hex::encode(salt) };
+        Ok((
+            StoredKey { id: key_id(&key), key: None, derived: Some(derivation) },
+            key,
+        ))
+    }
+}
+
+/// Names a key without revealing it, so the key a file was encrypted with
+/// can be found, and a wrong passphrase told apart.
+fn key_id(key: &[u8; 32]) -> String {
+    hex::encode(&Sha256::digest(key)[..4])
+}
+
+const KDF: &str = "argon2id";
+
+/// Argon2id parameters deriving a key from a passphrase, stored with what
+/// the key encrypts so it can be derived again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfCost {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// The cost keys are derived at: 64 MiB, 3 passes.
+pub const KDF_COST: KdfCost = KdfCost { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 };
+/// The costs a file may ask for. Files are not trusted: below the least, a
+/// tampered one would have the passphrase checked cheaply; above the most,
+/// it would stall the command.
+const MIN_KDF_COST: KdfCost = KdfCost { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 };
+const MAX_KDF_COST: KdfCost = KdfCost { memory_kib: 1024 * 1024, iterations: 16, parallelism: 16 };
+
+impl KdfCost {
+    fn is_accepted(&self) -> bool {
+        (MIN_KDF_COST.memory_kib..=MAX_KDF_COST.memory_kib).contains(&self.memory_kib)
+            && (MIN_KDF_COST.iterations..=MAX_KDF_COST.iterations).contains(&self.iterations)
+            && (MIN_KDF_COST.parallelism..=MAX_KDF_COST.parallelism).contains(&self.parallelism)
+    }
+}
+
+/// Passphrase of the stored credentials, for commands run without a terminal.
+const PASSPHRASE_VAR: &str = "VK_CREDENTIALS_PASSPHRASE";
+const PASSPHRASE_ATTEMPTS: usize = 3;
+
+const BUNDLE_FORMAT: &str = "vk-credentials";
+const BUNDLE_VERSION: u32 = 2;
+
+/// Credentials encrypted with a key derived from a passphrase, written by
+/// `vk auth export` and read back by `vk auth import` on another machine.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialBundle {
+    format: String,
+    version: u32,
+    /// Registry the credentials were issued by.
+    pub registry: String,
+    kdf: String,
+    #[serde(flatten)]
+    cost: KdfCost,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl CredentialBundle {
+    pub fn seal(registry: &str, passphrase: &str, plaintext: &[u8], cost: KdfCost) -> Result<Self> {
+        let salt: [u8; 16] = rand::random();
+        let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt, cost)?.into());
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| anyhow::anyhow!(t!("credentials.encrypt_failed", error = e)))?;
+
+        Ok(Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            registry: registry.to_string(),
+            kdf: KDF.to_string(),
+            cost,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    pub fn open(&self, passphrase: &str) -> Result<Vec<u8>> {
+        if self.format != BUNDLE_FORMAT || self.version != BUNDLE_VERSION || self.kdf != KDF {
+            anyhow::bail!(t!("credentials.bundle_unsupported", version = self.version));
+        }
+
+        let invalid = || t!("credentials.bundle_invalid");
+        let salt = hex::decode(&self.salt).with_context(invalid)?;
+        let nonce = hex::decode(&self.nonce).with_context(invalid)?;
+        let ciphertext = STANDARD.decode(&self.ciphertext).with_context(invalid)?;
+        if nonce.len() != 12 || !self.cost.is_accepted() {
+            anyhow::bail!(invalid());
+        }
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt, self.cost).with_context(invalid)?.into());
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow::anyhow!(t!("credentials.bundle_wrong_passphrase")))
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], cost: KdfCost) -> Result<[u8; 32]> {
+    let failed = |e| anyhow::anyhow!(t!("credentials.derive_failed", error = e));
+    let params = Params::new(cost.memory_kib, cost.iterations, cost.parallelism, Some(32)).map_err(failed)?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(failed)?;
+    Ok(key)
+}
+
 pub struct CredentialManager {
     config_dir: PathBuf,
+    /// Keys derived from the passphrase during this command, by id.
+    unlocked: Mutex<HashMap<String, [u8; 32]>>,
+    /// How long the agent keeps a derived key; zero to not start one.
+    agent_timeout: Duration,
+    /// Cost of deriving keys from a new passphrase.
+    kdf_cost: KdfCost,
 }
 
 impl CredentialManager {
     pub fn new() -> Result<Self> {
-        let config_dir = dirs::config_dir()
-            .context("No se pudo encontrar el directorio de configuración")?
-            .join("vayload-kit");
+        let config_dir = dirs::config_dir().with_context(|| t!("credentials.config_dir_missing"))?.join("vayload-kit");
+        Self::in_dir(config_dir)
+    }
 
-        fs::create_dir_all(&config_dir).context("Error al crear el directorio de configuración")?;
+    fn in_dir(config_dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&config_dir).with_context(|| t!("credentials.config_dir_create_failed"))?;
 
-        Ok(Self { config_dir })
+        let manager = Self {
+            config_dir,
+            unlocked: Mutex::default(),
+            agent_timeout: Duration::ZERO,
+            kdf_cost: KDF_COST,
+        };
+        manager.migrate_legacy()?;
+        Ok(manager)
+    }
+
+    /// Keeps keys derived from the passphrase in an agent for `timeout`.
+    pub fn with_agent_timeout(mut self, timeout: Duration) -> Self {
+        self.agent_timeout = timeout;
+        self
     }
 
     pub fn store_tokens(&self, credentials: RawCredentials) -> Result<()> {
@@ -89,12 +298,107 @@ impl CredentialManager {
         Ok(self.get_credentials()?.refresh_token)
     }
 
+    /// The organization an SSO login scoped the stored tokens to.
+    pub fn organization(&self) -> Option<String> {
+        self.get_credentials().ok().and_then(|c| c.organization)
+    }
+
+    /// The stored credentials, decrypted, for `vk auth export`.
+    pub fn export_plaintext(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&self.get_credentials()?)?)
+    }
+
+    /// Stores credentials exported on another machine, replacing the ones
+    /// stored here.
+    pub fn import_plaintext(&self, plaintext: &[u8]) -> Result<()> {
+        let credentials: Credentials =
+            serde_json::from_slice(plaintext).with_context(|| t!("credentials.bundle_invalid"))?;
+        self.encrypt_and_write(&serde_json::to_vec(&credentials)?)
+    }
+
+    /// Whether the key is derived from a passphrase instead of stored.
+    pub fn uses_passphrase(&self) -> Result<bool> {
+        Ok(self.key_ring()?.keys.last().is_some_and(|k| k.derived.is_some()))
+    }
+
+    /// Makes sure the key of the stored credentials is at hand, asking for
+    /// the passphrase if it has to.
+    pub fn unlock(&self) -> Result<()> {
+        if self.has_credentials()
+            && let Some(key) = self.key_ring()?.keys.last()
+        {
+            self.key_bytes(key)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the key derived from the passphrase. Returns whether an agent
+    /// was holding it.
+    pub fn lock(&self) -> bool {
+        agent::stop(&self.agent_socket())
+    }
+
+    /// Replaces the encryption key with a new one of the same kind and
+    /// re-encrypts the stored credentials under it. Returns the id of the
+    /// new key.
+    pub fn rotate_key(&self) -> Result<String> {
+        let current = self.key_ring()?.keys.last().cloned();
+        match current {
+            Some(current) if current.derived.is_some() => {
+                let passphrase = self.ask_passphrase(&current)?;
+                self.set_passphrase(&passphrase)
+            },
+            _ => self.rekey(StoredKey::generate()),
+        }
+    }
+
+    /// Derives the key from `passphrase` from now on. Returns the key id.
+    pub fn set_passphrase(&self, passphrase: &str) -> Result<String> {
+        let (stored, key) = StoredKey::derive(passphrase, self.kdf_cost)?;
+        self.remember(&stored.id, key);
+        self.rekey(stored)
+    }
+
+    /// Goes back to a key kept in the key file. Returns the key id.
+    pub fn remove_passphrase(&self) -> Result<String> {
+        let id = self.rekey(StoredKey::generate())?;
+        self.lock();
+        Ok(id)
+    }
+
+    /// Re-encrypts the stored credentials under `key`, which becomes the
+    /// current key.
+    fn rekey(&self, key: StoredKey) -> Result<String> {
+        let mut ring = self.key_ring()?;
+        let plaintext = match self.has_credentials() {
+            true => Some(self.decrypt_stored(&ring)?),
+            false => None,
+        };
+
+        ring.keys.push(key.clone());
+        self.write_key_ring(&ring)?;
+
+        if let Some(plaintext) = plaintext {
+            self.encrypt_and_write(&plaintext)?;
+        }
+
+        // Only once nothing is encrypted with them may the old keys go
+        ring.keys.retain(|k| k.id == key.id);
+        self.write_key_ring(&ring)?;
+        Ok(key.id)
+    }
+
     pub fn clear_all(&self) -> Result<()> {
         let _ = fs::remove_file(self.credentials_path());
         let _ = fs::remove_file(self.key_path());
         Ok(())
     }
 
+    /// Whether a login stored tokens, expired or not.
+    pub fn has_credentials(&self) -> bool {
+        self.credentials_path().exists()
+    }
+
     pub fn is_authenticated(&self) -> bool {
         !self.is_refresh_token_expired() || !self.is_access_token_expired()
     }
@@ -113,38 +417,135 @@ impl CredentialManager {
     }
 
     fn get_credentials(&self) -> Result<Credentials> {
-        let encrypted_json = fs::read(self.credentials_path()).context("No hay credenciales guardadas")?;
-
-        let encrypted: EncryptedCredentials = serde_json::from_slice(&encrypted_json)?;
-        let key = self.get_or_create_key()?;
+        let plaintext = self.decrypt_stored(&self.key_ring()?)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
 
-        let cipher = ChaCha20Poly1305::new(&key.into());
-        let nonce = Nonce::from_slice(&encrypted.nonce);
+    fn decrypt_stored(&self, ring: &KeyRing) -> Result<Vec<u8>> {
+        let data = fs::read(self.credentials_path()).with_context(|| t!("credentials.none_stored"))?;
+        let envelope: Envelope = serde_json::from_slice(&data).with_context(|| t!("credentials.file_invalid"))?;
+        if envelope.version != ENVELOPE_VERSION {
+            anyhow::bail!(t!("credentials.file_unsupported", version = envelope.version));
+        }
 
-        let plaintext = cipher
-            .decrypt(nonce, encrypted.ciphertext.as_ref())
-            .map_err(|e| anyhow::anyhow!("Error de descifrado: {}", e))?;
+        let key = ring
+            .keys
+            .iter()
+            .find(|k| k.id == envelope.key_id)
+            .with_context(|| t!("credentials.key_missing", id = envelope.key_id))?;
+        let nonce = hex::decode(&envelope.nonce).with_context(|| t!("credentials.file_invalid"))?;
+        let ciphertext = STANDARD.decode(&envelope.ciphertext).with_context(|| t!("credentials.file_invalid"))?;
+        if nonce.len() != 12 {
+            anyhow::bail!(t!("credentials.file_invalid"));
+        }
 
-        Ok(serde_json::from_str(&String::from_utf8(plaintext)?)?)
+        self.cipher(key)?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|e| anyhow::anyhow!(t!("credentials.decrypt_failed", error = e)))
     }
 
     fn encrypt_and_write(&self, plaintext: &[u8]) -> Result<()> {
-        let key = self.get_or_create_key()?;
-        let cipher = ChaCha20Poly1305::new(&key.into());
+        let ring = self.key_ring()?;
+        let key = ring.keys.last().with_context(|| t!("credentials.key_invalid"))?;
         let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
 
-        let ciphertext = cipher.encrypt(&nonce, plaintext).map_err(|e| anyhow::anyhow!("Cifrado fallido: {}", e))?;
+        let ciphertext = self
+            .cipher(key)?
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| anyhow::anyhow!(t!("credentials.encrypt_failed", error = e)))?;
 
-        let data = serde_json::to_vec(&EncryptedCredentials { ciphertext, nonce: nonce.to_vec() })?;
+        let envelope = Envelope {
+            version: ENVELOPE_VERSION,
+            key_id: key.id.clone(),
+            nonce: hex::encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        write_private(&self.credentials_path(), &serde_json::to_vec(&envelope)?)
+    }
 
-        let path = self.credentials_path();
-        fs::write(&path, data)?;
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    fn cipher(&self, key: &StoredKey) -> Result<ChaCha20Poly1305> {
+        Ok(ChaCha20Poly1305::new(&self.key_bytes(key)?.into()))
+    }
+
+    /// The key itself: from the key file, or derived from the passphrase.
+    /// Derived keys come from this command's memory, the agent, or else the
+    /// passphrase is asked.
+    fn key_bytes(&self, key: &StoredKey) -> Result<[u8; 32]> {
+        if let Some(stored) = &key.key {
+            return hex::decode(stored)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .with_context(|| t!("credentials.key_invalid"));
         }
-        Ok(())
+
+        if let Some(bytes) = self.unlocked.lock().unwrap_or_else(|e| e.into_inner()).get(&key.id) {
+            return Ok(*bytes);
+        }
+        if let Some(bytes) = agent::fetch(&self.agent_socket()).filter(|bytes| key_id(bytes) == key.id) {
+            self.unlocked.lock().unwrap_or_else(|e| e.into_inner()).insert(key.id.clone(), bytes);
+            return Ok(bytes);
+        }
+
+        let passphrase = self.ask_passphrase(key)?;
+        let bytes = self.derive_stored(key, &passphrase)?;
+        self.remember(&key.id, bytes);
+        Ok(bytes)
+    }
+
+    /// Asks for the passphrase of `key` until it is the right one, or takes
+    /// it from `VK_CREDENTIALS_PASSPHRASE`.
+    fn ask_passphrase(&self, key: &StoredKey) -> Result<String> {
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+            self.derive_stored(key, &passphrase)?;
+            return Ok(passphrase);
+        }
+        if !std::io::stdin().is_terminal() {
+            return Err(VkError::CredentialsLocked.into());
+        }
+
+        let mut prompt = t!("credentials.prompt_passphrase");
+        for _ in 0..PASSPHRASE_ATTEMPTS {
+            let passphrase = dialoguer::Password::new()
+                .with_prompt(&prompt)
+                .interact()
+                .with_context(|| t!("auth.read_password_failed"))?;
+            if self.derive_stored(key, &passphrase).is_ok() {
+                return Ok(passphrase);
+            }
+            prompt = t!("credentials.prompt_passphrase_retry");
+        }
+        anyhow::bail!(t!("credentials.wrong_passphrase"))
+    }
+
+    /// Derives `key` from `passphrase`, failing when it is not the right one.
+    fn derive_stored(&self, key: &StoredKey, passphrase: &str) -> Result<[u8; 32]> {
+        let derivation = key.derived.as_ref().with_context(|| t!("credentials.key_invalid"))?;
+        if derivation.kdf != KDF {
+            anyhow::bail!(t!("credentials.file_unsupported", version = derivation.kdf));
+        }
+        if !derivation.cost.is_accepted() {
+            anyhow::bail!(t!("credentials.key_invalid"));
+        }
+        let salt = hex::decode(&derivation.salt).with_context(|| t!("credentials.key_invalid"))?;
+
+        let bytes = derive_key(passphrase, &salt, derivation.cost).with_context(|| t!("credentials.key_invalid"))?;
+        if key_id(&bytes) != key.id {
+            anyhow::bail!(t!("credentials.wrong_passphrase"));
+        }
+        Ok(bytes)
+    }
+
+    /// Keeps a derived key for the rest of the command, and in the agent.
+    fn remember(&self, id: &str, key: [u8; 32]) {
+        self.unlocked.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string(), key);
+        if !self.agent_timeout.is_zero() {
+            // Without an agent the passphrase is only asked again next time
+            let _ = agent::start(&self.agent_socket(), &key, self.agent_timeout);
+        }
+    }
+
+    fn agent_socket(&self) -> PathBuf {
+        self.config_dir.join("agent").join("agent.sock")
     }
 
     fn credentials_path(&self) -> PathBuf {
@@ -154,22 +555,174 @@ impl CredentialManager {
         self.config_dir.join(".key")
     }
 
-    fn get_or_create_key(&self) -> Result<[u8; 32]> {
+    /// The key file, created with a new key when there is none.
+    fn key_ring(&self) -> Result<KeyRing> {
         let path = self.key_path();
-        if path.exists() {
-            let b = fs::read(&path)?;
-            let mut key = [0u8; 32];
-            key.copy_from_slice(&b);
-            Ok(key)
-        } else {
-            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
-            fs::write(&path, key)?;
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        if !path.exists() {
+            let ring = KeyRing { version: KEY_RING_VERSION, keys: vec![StoredKey::generate()] };
+            self.write_key_ring(&ring)?;
+            return Ok(ring);
+        }
+
+        let ring: KeyRing = serde_json::from_slice(&fs::read(&path)?).with_context(|| t!("credentials.key_invalid"))?;
+        if ring.version != KEY_RING_VERSION {
+            anyhow::bail!(t!("credentials.file_unsupported", version = ring.version));
+        }
+        Ok(ring)
+    }
+
+    fn write_key_ring(&self, ring: &KeyRing) -> Result<()> {
+        write_private(&self.key_path(), &serde_json::to_vec_pretty(ring)?)
+    }
+
+    /// Moves a raw key file and the credentials encrypted with it to the
+    /// versioned formats, keeping the key.
+    fn migrate_legacy(&self) -> Result<()> {
+        let Ok(raw) = fs::read(self.key_path()) else {
+            return Ok(());
+        };
+        let Ok(key) = <[u8; 32]>::try_from(raw.as_slice()) else {
+            return Ok(());
+        };
+
+        // Credentials that cannot be read are left for the next login to replace
+        let plaintext = fs::read(self.credentials_path())
+            .ok()
+            .and_then(|data| serde_json::from_slice::<LegacyEnvelope>(&data).ok())
+            .and_then(|legacy| {
+                ChaCha20Poly1305::new(&key.into())
+                    .decrypt(Nonce::from_slice(&legacy.nonce), legacy.ciphertext.as_ref())
+                    .ok()
+            });
+
+        self.write_key_ring(&KeyRing {
+            version: KEY_RING_VERSION,
+            keys: vec![StoredKey::from_bytes(key)],
+        })?;
+        if let Some(plaintext) = plaintext {
+            self.encrypt_and_write(&plaintext)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    fs::write(path, data)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cheapest accepted, so the tests derive keys quickly.
+    const TEST_COST: KdfCost = MIN_KDF_COST;
+
+    impl CredentialManager {
+        fn for_tests(dir: &Path) -> Self {
+            Self {
+                kdf_cost: TEST_COST,
+                ..Self::in_dir(dir.to_path_buf()).unwrap()
             }
-            Ok(key.into())
         }
     }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let bundle = CredentialBundle::seal("https://r.example", "correct horse", b"secret", TEST_COST).unwrap();
+        assert_eq!(bundle.open("correct horse").unwrap(), b"secret");
+        assert!(bundle.open("wrong horse").is_err());
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let read: CredentialBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.registry, "https://r.example");
+        assert_eq!(read.open("correct horse").unwrap(), b"secret");
+
+        // Costs outside the accepted range are refused before deriving
+        for cost in [
+            KdfCost { iterations: 1, ..TEST_COST },
+            KdfCost { iterations: u32::MAX, ..TEST_COST },
+            KdfCost { memory_kib: 4 * 1024 * 1024, ..TEST_COST },
+        ] {
+            let tampered = CredentialBundle { cost, ..serde_json::from_str(&json).unwrap() };
+            let err = tampered.open("correct horse").unwrap_err();
+            assert_eq!(err.to_string(), t!("credentials.bundle_invalid"));
+        }
+    }
+
+    fn store(cm: &CredentialManager) {
+        cm.store_tokens(RawCredentials::new("access".into(), "refresh".into(), 3600)).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let cm = CredentialManager::for_tests(dir.path());
+        store(&cm);
+        let before = cm.key_ring().unwrap().keys[0].id.clone();
+
+        let id = cm.rotate_key().unwrap();
+        assert_ne!(id, before);
+        let ring = cm.key_ring().unwrap();
+        assert_eq!(ring.keys.len(), 1);
+        assert_eq!(ring.keys[0].id, id);
+        assert_eq!(cm.get_access_token().unwrap(), "access");
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let cm = CredentialManager::for_tests(dir.path());
+        store(&cm);
+
+        let id = cm.set_passphrase("correct horse").unwrap();
+        assert!(cm.uses_passphrase().unwrap());
+        let ring = cm.key_ring().unwrap();
+        assert_eq!(ring.keys.len(), 1);
+        assert!(ring.keys[0].key.is_none());
+        assert_eq!(cm.get_access_token().unwrap(), "access");
+
+        // A new command has nothing unlocked yet
+        let cm = CredentialManager::for_tests(dir.path());
+        let key = &ring.keys[0];
+        assert!(cm.derive_stored(key, "wrong horse").is_err());
+        cm.remember(&id, cm.derive_stored(key, "correct horse").unwrap());
+        assert_eq!(cm.get_access_token().unwrap(), "access");
+
+        cm.remove_passphrase().unwrap();
+        assert!(!cm.uses_passphrase().unwrap());
+        assert_eq!(cm.get_access_token().unwrap(), "access");
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = [7u8; 32];
+        let nonce = [1u8; 12];
+        let ciphertext = ChaCha20Poly1305::new(&key.into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                br#"{"access_token":"old","access_expires_at":0,"refresh_token":"r","refresh_expires_at":0}"#.as_ref(),
+            )
+            .unwrap();
+        fs::write(dir.path().join(".key"), key).unwrap();
+        fs::write(
+            dir.path().join("credentials.enc"),
+            serde_json::json!({ "ciphertext": ciphertext, "nonce": nonce }).to_string(),
+        )
+        .unwrap();
+
+        let cm = CredentialManager::for_tests(dir.path());
+        assert_eq!(cm.get_access_token().unwrap(), "old");
+        assert_eq!(cm.key_ring().unwrap().keys[0].id, StoredKey::from_bytes(key).id);
+        let envelope: Envelope =
+            serde_json::from_slice(&fs::read(dir.path().join("credentials.enc")).unwrap()).unwrap();
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+    }
 }
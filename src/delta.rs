@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::http_client::{ClientError, Download, HttpClient};
+use crate::i18n::t;
+use crate::project::Project;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::types::DownloadMeta;
+use crate::utils::{format_bytes, sha256_hex};
+
+/// Directory of the project cache keeping the archive of each installed
+/// registry package, which the next version is patched from.
+const ARCHIVES_DIR: &str = "archives";
+
+fn archives_dir(project: &Project) -> PathBuf {
+    project.cache_dir().join(ARCHIVES_DIR)
+}
+
+fn archive_name(id: &str, version: &str) -> String {
+    format!("{}@{}.zip", id, version)
+}
+
+/// The archive kept for `id`, with its version.
+pub fn cached(project: &Project, id: &str) -> Option<(String, PathBuf)> {
+    let prefix = format!("{}@", id);
+    fs::read_dir(archives_dir(project)).ok()?.filter_map(|e| e.ok()).find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let version = name.strip_prefix(&prefix)?.strip_suffix(".zip")?.to_string();
+        Some((version, entry.path()))
+    })
+}
+
+/// Keeps the archive of `id` at `version`, in place of the one of the
+/// version it replaces.
+pub fn store(project: &Project, id: &str, version: &str, data: &[u8]) -> Result<()> {
+    if let Some((_, previous)) = cached(project, id) {
+        fs::remove_file(&previous).ok();
+    }
+    let dir = archives_dir(project);
+    fs::create_dir_all(&dir).with_context(|| t!("common.write_failed", path = dir.display()))?;
+    let path = dir.join(archive_name(id, version));
+    fs::write(&path, data).with_context(|| t!("common.write_failed", path = path.display()))
+}
+
+/// Downloads `id` at `version` (the latest when `None`) as a patch of the
+/// archive kept for the installed version, and applies it.
+///
+/// Patches are zstd frames compressed with the old archive as reference
+/// (`zstd --patch-from`), served by the registry's `/delta` endpoint. The
+/// patched archive must match the checksum sent with the patch. Returns
+/// `None` whenever that does not work out, for a full download instead.
+pub fn download(
+    project: &Project,
+    id: &str,
+    version: Option<&str>,
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Option<(Vec<u8>, DownloadMeta)> {
+    let (from, path) = cached(project, id)?;
+    if version == Some(from.as_str()) {
+        return None;
+    }
+    let base = fs::read(path).ok()?;
+
+    match apply(id, &from, version, &base, http_client) {
+        Ok(Some((data, meta, patch_size))) => {
+            reporter.success(&t!(
+                "delta.applied",
+                from = from.yellow(),
+                to = meta.version.yellow(),
+                size = format_bytes(patch_size),
+                full = format_bytes(data.len()).bright_black()
+            ));
+            Some((data, meta))
+        },
+        Ok(None) => None,
+        Err(err) => {
+            reporter.detail(&t!("delta.fallback", reason = format!("{:#}", err).bright_black()));
+            None
+        },
+    }
+}
+
+/// Fetches and applies the patch from `from` to `to`. `None` when the
+/// registry has none to offer.
+fn apply(
+    id: &str,
+    from: &str,
+    to: Option<&str>,
+    base: &[u8],
+    http_client: &HttpClient,
+) -> Result<Option<(Vec<u8>, DownloadMeta, usize)>> {
+    let Download { headers, response } = match RegistryClient::new(http_client).delta(id, from, to) {
+        Ok(download) => download,
+        Err(ClientError::Api { status: 404 | 501, .. } | ClientError::Status { status: 404 | 501, .. }) => {
+            return Ok(None);
+        },
+        Err(err) => return Err(err.into()),
+    };
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    let (Some(version), Some(checksum)) = (header("X-Plugin-Version"), header("X-Checksum")) else {
+        anyhow::bail!(t!("delta.no_checksum"));
+    };
+
+    let mut patch = Vec::new();
+    let mut response = response;
+    response.read_to_end(&mut patch).map_err(|e| http_client.read_error(e))?;
+
+    let data = patched(base, &patch)?;
+    let actual = sha256_hex(&data);
+    if !actual.eq_ignore_ascii_case(&checksum) {
+        anyhow::bail!(t!("delta.checksum_mismatch"));
+    }
+
+    let meta = DownloadMeta {
+        id: id.to_string(),
+        version,
+        checksum: Some(checksum),
+        sha256: Some(actual),
+    };
+    Ok(Some((data, meta, patch.len())))
+}
+
+/// `base` with a `zstd --patch-from` patch applied.
+fn patched(base: &[u8], patch: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    zstd::stream::read::Decoder::with_ref_prefix(patch, base)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_patch() {
+        let base: Vec<u8> = (0..64 * 1024).map(|i| (i * 7 % 251) as u8).collect();
+        let mut target = base.clone();
+        target[1000..1010].copy_from_slice(b"0123456789");
+        target.extend_from_slice(b"appended");
+
+        let mut encoder = zstd::stream::write::Encoder::with_ref_prefix(Vec::new(), 3, &base).unwrap();
+        encoder.write_all(&target).unwrap();
+        let delta = encoder.finish().unwrap();
+
+        assert!(delta.len() < target.len() / 10);
+        assert_eq!(patched(&base, &delta).unwrap(), target);
+    }
+}
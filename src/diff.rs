@@ -0,0 +1,167 @@
+/// A line-level edit turning `old` into `new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// Line present in both, with its index in `old` and in `new`.
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Shortest edit script between two line sequences (Myers' algorithm).
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+
+    // `v[k]` is the furthest x reached on diagonal k; one snapshot per step d
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk the snapshots backwards to recover the path
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// Renders a unified diff (`@@ -a,b +c,d @@` hunks) with `context` lines
+/// around each change. Returns an empty string when the inputs are equal.
+pub fn unified(old: &str, new: &str, context: usize) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = diff_lines(&old_lines, &new_lines);
+
+    let changes: Vec<usize> =
+        edits.iter().enumerate().filter(|(_, e)| !matches!(e, Edit::Equal(..))).map(|(i, _)| i).collect();
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < changes.len() {
+        // Group changes whose context windows overlap into one hunk
+        let start = changes[i].saturating_sub(context);
+        let mut end = (changes[i] + context + 1).min(edits.len());
+        while i + 1 < changes.len() && changes[i + 1] <= end + context {
+            i += 1;
+            end = (changes[i] + context + 1).min(edits.len());
+        }
+        i += 1;
+
+        let hunk = &edits[start..end];
+        let (old_start, new_start) = hunk_start(&edits[..start]);
+        let old_count = hunk.iter().filter(|e| !matches!(e, Edit::Insert(_))).count();
+        let new_count = hunk.iter().filter(|e| !matches!(e, Edit::Delete(_))).count();
+
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_count > 0),
+            old_count,
+            new_start + usize::from(new_count > 0),
+            new_count
+        ));
+        for edit in hunk {
+            match *edit {
+                Edit::Equal(a, _) => out.push_str(&format!(" {}\n", old_lines[a])),
+                Edit::Delete(a) => out.push_str(&format!("-{}\n", old_lines[a])),
+                Edit::Insert(b) => out.push_str(&format!("+{}\n", new_lines[b])),
+            }
+        }
+    }
+
+    out
+}
+
+/// Number of old and new lines consumed before a hunk.
+fn hunk_start(before: &[Edit]) -> (usize, usize) {
+    before.iter().fold((0, 0), |(old, new), edit| match edit {
+        Edit::Equal(..) => (old + 1, new + 1),
+        Edit::Delete(_) => (old + 1, new),
+        Edit::Insert(_) => (old, new + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines_roundtrip() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let edits = diff_lines(&old, &new);
+
+        let rebuilt: Vec<&str> = edits
+            .iter()
+            .filter_map(|e| match *e {
+                Edit::Equal(_, b) | Edit::Insert(b) => Some(new[b]),
+                Edit::Delete(_) => None,
+            })
+            .collect();
+        assert_eq!(rebuilt, new);
+        // Myers finds the minimal script: 5 edits for this classic example
+        assert_eq!(edits.iter().filter(|e| !matches!(e, Edit::Equal(..))).count(), 5);
+    }
+
+    #[test]
+    fn test_diff_lines_edge_cases() {
+        assert!(diff_lines(&[], &[]).is_empty());
+        assert_eq!(diff_lines(&[], &["x"]), vec![Edit::Insert(0)]);
+        assert_eq!(diff_lines(&["x"], &[]), vec![Edit::Delete(0)]);
+    }
+
+    #[test]
+    fn test_unified() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n";
+        assert_eq!(unified(old, new, 1), "@@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n");
+        assert_eq!(unified(old, old, 3), "");
+        assert_eq!(unified("", "a\n", 3), "@@ -0,0 +1,1 @@\n+a\n");
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::error::VkError;
+use crate::http_client::HttpClient;
+use crate::registry::RegistryClient;
+
+/// Channel every release is published under unless `--tag` says otherwise,
+/// and the one used when a dependency has no version.
+pub const DEFAULT_TAG: &str = "latest";
+
+/// Whether `spec` (the part after `@` in `package@spec`) names a dist-tag such
+/// as `latest`, `beta` or `next` rather than a version or range.
+pub fn is_dist_tag(spec: &str) -> bool {
+    let mut chars = spec.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Fetches the `tag -> version` map of a package.
+pub fn fetch(id: &str, http_client: &HttpClient) -> Result<BTreeMap<String, String>> {
+    RegistryClient::new(http_client).dist_tags(id).map_err(|e| VkError::package_lookup(e, id))
+}
+
+/// Resolves `tag` to the version it currently points to.
+pub fn resolve(id: &str, tag: &str, http_client: &HttpClient) -> Result<String> {
+    fetch(id, http_client)?
+        .remove(tag)
+        .ok_or_else(|| VkError::PackageNotFound(format!("{}@{}", id, tag)).into())
+}
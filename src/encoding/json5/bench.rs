@@ -0,0 +1,98 @@
+//! Parser and serializer throughput, to compare changes against. Ignored by
+//! default; run them optimized with `make bench`, i.e.
+//! `cargo test --release json5::bench -- --ignored --nocapture --test-threads 1`.
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::encoding::json5::{Value, from_path, from_str, parse_value, to_string_pretty, to_writer_pretty};
+
+/// Time each measurement runs for, after one warm-up pass.
+const BUDGET: Duration = Duration::from_secs(2);
+
+/// A lockfile of `packages` entries, with the comments, unquoted keys and
+/// trailing commas hand-edited files have.
+fn lockfile(packages: usize) -> String {
+    let mut out = String::from("// generated\n{\n    version: 1,\n    features: [\"hooks\",],\n    packages: [\n");
+    for i in 0..packages {
+        out.push_str(&format!(
+            "        {{\n            id: \"package-{i}\",\n            version: \"{}.{}.{}\",\n            \
+             checksum: \"{:064x}\",\n            integrity: 'sha256-{:x}',\n            \
+             dependencies: {{ \"dep-{}\": \"^1.0.0\", }},\n            overridden: false,\n            \
+             size: {},\n            ratio: {}.5e-3,\n        }},\n",
+            i % 7,
+            i % 13,
+            i % 29,
+            i * 7919,
+            i * 104729,
+            i % 97,
+            i * 1024,
+            i % 10,
+        ));
+    }
+    out.push_str("    ],\n}\n");
+    out
+}
+
+/// Runs `f` over `bytes` of input until the budget is spent and prints the
+/// throughput.
+fn measure(name: &str, bytes: usize, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while start.elapsed() < BUDGET {
+        f();
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    let throughput = bytes as f64 / per_run.as_secs_f64() / (1024.0 * 1024.0);
+    println!(
+        "{name:<28} {:>8.2} MiB  {:>10.2?}/run  {throughput:>8.1} MiB/s",
+        bytes as f64 / 1048576.0,
+        per_run
+    );
+}
+
+#[test]
+#[ignore]
+fn bench_parse() {
+    for packages in [100, 10_000, 100_000] {
+        let input = lockfile(packages);
+        measure(&format!("parse_value/{packages}"), input.len(), || {
+            black_box(parse_value(black_box(&input)).unwrap());
+        });
+        measure(&format!("from_str/{packages}"), input.len(), || {
+            black_box(from_str::<serde_json::Value>(black_box(&input)).unwrap());
+        });
+    }
+}
+
+#[test]
+#[ignore]
+fn bench_from_path() {
+    for packages in [100, 100_000] {
+        let input = lockfile(packages);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(input.as_bytes()).unwrap();
+        measure(&format!("from_path/{packages}"), input.len(), || {
+            black_box(from_path::<serde_json::Value>(file.path()).unwrap());
+        });
+    }
+}
+
+#[test]
+#[ignore]
+fn bench_serialize() {
+    for packages in [100, 100_000] {
+        let value: Value = parse_value(&lockfile(packages)).unwrap();
+        let bytes = to_string_pretty(&value).unwrap().len();
+        measure(&format!("to_string_pretty/{packages}"), bytes, || {
+            black_box(to_string_pretty(black_box(&value)).unwrap());
+        });
+        measure(&format!("to_writer_pretty/{packages}"), bytes, || {
+            let mut out = Vec::with_capacity(bytes);
+            to_writer_pretty(&mut out, black_box(&value)).unwrap();
+            black_box(out);
+        });
+    }
+}
@@ -0,0 +1,226 @@
+//! ISO-8601 timestamps, the way JSON5 documents store them: as strings in
+//! RFC 3339 form (`2026-10-16T09:30:00Z`). [`DateTime`] (de)serializes from
+//! and to that string, so fields like `published_at` only need its type.
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::encoding::json5::error::{Error, Result};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// An instant in UTC, with nanosecond precision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    /// Seconds since 1970-01-01T00:00:00Z.
+    secs: i64,
+    nanos: u32,
+}
+
+impl DateTime {
+    pub fn from_unix(secs: i64, nanos: u32) -> Self {
+        DateTime {
+            secs: secs + i64::from(nanos / 1_000_000_000),
+            nanos: nanos % 1_000_000_000,
+        }
+    }
+
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    pub fn unix_timestamp(&self) -> i64 {
+        self.secs
+    }
+
+    /// Parses an RFC 3339 timestamp, with any UTC offset, or a plain
+    /// `YYYY-MM-DD` date taken as midnight UTC.
+    pub fn parse(s: &str) -> Result<Self> {
+        parse(s.as_bytes()).ok_or_else(|| Error::InvalidDateTime(s.to_string()))
+    }
+
+    /// The calendar date, as `YYYY-MM-DD`.
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_from_days(self.secs.div_euclid(SECS_PER_DAY));
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// RFC 3339 in UTC, with as many fractional digits as the instant needs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.secs.rem_euclid(SECS_PER_DAY);
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}",
+            self.date(),
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        f.write_str("Z")
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => DateTime::from_unix(d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                match d.subsec_nanos() {
+                    0 => DateTime::from_unix(-(d.as_secs() as i64), 0),
+                    n => DateTime::from_unix(-(d.as_secs() as i64) - 1, 1_000_000_000 - n),
+                }
+            },
+        }
+    }
+}
+
+impl From<DateTime> for SystemTime {
+    fn from(time: DateTime) -> Self {
+        let nanos = Duration::from_nanos(u64::from(time.nanos));
+        match u64::try_from(time.secs) {
+            Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs) + nanos,
+            Err(_) => UNIX_EPOCH - Duration::from_secs(time.secs.unsigned_abs()) + nanos,
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for DateTime {
+    fn from(time: chrono::DateTime<chrono::Utc>) -> Self {
+        DateTime::from_unix(time.timestamp(), time.timestamp_subsec_nanos())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime> for chrono::DateTime<chrono::Utc> {
+    fn from(time: DateTime) -> Self {
+        chrono::DateTime::from_timestamp(time.secs, time.nanos).unwrap_or_default()
+    }
+}
+
+impl Serialize for DateTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DateTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DateTime::parse(&s).map_err(de::Error::custom)
+    }
+}
+
+/// `YYYY-MM-DD`, then optionally `THH:MM:SS[.fraction](Z|±HH:MM)`. A space
+/// or lowercase `t`/`z` are accepted too, as RFC 3339 allows.
+fn parse(s: &[u8]) -> Option<DateTime> {
+    let year = digits(s, 0, 4)?;
+    let month = digits(s, 5, 2)?;
+    let day = digits(s, 8, 2)?;
+    if s.get(4) != Some(&b'-') || s.get(7) != Some(&b'-') || !(1..=12).contains(&month) {
+        return None;
+    }
+    if day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if s.len() == 10 {
+        return Some(DateTime::from_unix(days * SECS_PER_DAY, 0));
+    }
+
+    if !matches!(s.get(10), Some(b'T' | b't' | b' ')) || s.get(13) != Some(&b':') || s.get(16) != Some(&b':') {
+        return None;
+    }
+    let (hour, minute, second) = (digits(s, 11, 2)?, digits(s, 14, 2)?, digits(s, 17, 2)?);
+    // 60 is a leap second
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut pos = 19;
+    let mut nanos = 0u32;
+    if s.get(pos) == Some(&b'.') {
+        let start = pos + 1;
+        pos = start;
+        while s.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        if pos == start {
+            return None;
+        }
+        // Digits past nanoseconds are dropped
+        for i in 0..9 {
+            let digit = if start + i < pos {
+                u32::from(s[start + i] - b'0')
+            } else {
+                0
+            };
+            nanos = nanos * 10 + digit;
+        }
+    }
+
+    let offset = match &s[pos..] {
+        b"Z" | b"z" => 0,
+        [sign @ (b'+' | b'-'), rest @ ..] if rest.len() == 5 && rest[2] == b':' => {
+            let (hours, minutes) = (digits(rest, 0, 2)?, digits(rest, 3, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' { -offset } else { offset }
+        },
+        _ => return None,
+    };
+
+    let secs = days * SECS_PER_DAY + hour * 3600 + minute * 60 + second - offset;
+    Some(DateTime::from_unix(secs, nanos))
+}
+
+/// The `len` ASCII digits of `s` at `at`, as a number.
+fn digits(s: &[u8], at: usize, len: usize) -> Option<i64> {
+    let field = s.get(at..at + len)?;
+    field.iter().try_fold(0i64, |n, b| b.is_ascii_digit().then(|| n * 10 + i64::from(b - b'0')))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to a calendar date (Howard Hinnant's
+/// days-from-civil algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The calendar date `days` after 1970-01-01, the inverse of
+/// [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -13,6 +13,7 @@ pub struct Deserializer<'de> {
 // !TODO undestand for what marked as unused
 #[allow(dead_code)]
 impl<'de> Deserializer<'de> {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(input: &'de str) -> Self {
         Self { parser: Parser::new(input) }
     }
@@ -102,6 +103,11 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
             Value::Number(Number::Int(n)) => visitor.visit_i64(n),
             Value::Number(Number::Uint(n)) => visitor.visit_u64(n),
             Value::Number(Number::Float(f)) => visitor.visit_f64(f),
+            Value::Number(Number::Big(s)) => match (s.parse::<i128>(), s.parse::<u128>()) {
+                (Ok(i), _) => visitor.visit_i128(i),
+                (_, Ok(u)) => visitor.visit_u128(u),
+                _ => visitor.visit_string(s),
+            },
             Value::Number(Number::NaN) => visitor.visit_f64(f64::NAN),
             Value::Number(Number::Infinity) => visitor.visit_f64(f64::INFINITY),
             Value::Number(Number::NegInfinity) => visitor.visit_f64(f64::NEG_INFINITY),
@@ -446,12 +452,18 @@ impl<'de> VariantAccess<'de> for ContentVariant {
 
 fn num_to_int<T>(val: &Value) -> Result<T>
 where
-    T: TryFrom<i64> + TryFrom<u64>,
+    T: TryFrom<i64> + TryFrom<u64> + TryFrom<i128>,
     <T as TryFrom<i64>>::Error: std::fmt::Debug,
     <T as TryFrom<u64>>::Error: std::fmt::Debug,
+    <T as TryFrom<i128>>::Error: std::fmt::Debug,
 {
     match val {
         Value::Number(Number::Int(n)) => T::try_from(*n).map_err(|_| Error::Custom(format!("integer overflow: {}", n))),
+        Value::Number(Number::Big(s)) => s
+            .parse::<i128>()
+            .ok()
+            .and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| Error::Custom(format!("integer overflow: {}", s))),
         Value::Number(Number::Uint(n)) => {
             T::try_from(*n).map_err(|_| Error::Custom(format!("integer overflow: {}", n)))
         },
@@ -465,11 +477,17 @@ where
 
 fn num_to_uint<T>(val: &Value) -> Result<T>
 where
-    T: TryFrom<u64> + TryFrom<i64>,
+    T: TryFrom<u64> + TryFrom<i64> + TryFrom<u128>,
     <T as TryFrom<u64>>::Error: std::fmt::Debug,
     <T as TryFrom<i64>>::Error: std::fmt::Debug,
+    <T as TryFrom<u128>>::Error: std::fmt::Debug,
 {
     match val {
+        Value::Number(Number::Big(s)) if !s.starts_with('-') => s
+            .parse::<u128>()
+            .ok()
+            .and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| Error::Custom(format!("integer overflow: {}", s))),
         Value::Number(Number::Uint(n)) => {
             T::try_from(*n).map_err(|_| Error::Custom(format!("integer overflow: {}", n)))
         },
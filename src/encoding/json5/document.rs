@@ -0,0 +1,382 @@
+/// A JSON5 document as written: values with the comments and blank lines
+/// around them, and strings with their quotes. `Value` drops all of that, so
+/// tools that rewrite files people edit (`vk fmt`) work on a `Document`.
+use crate::encoding::json5::error::{Error, Result};
+use crate::encoding::json5::parser::Parser;
+use crate::encoding::json5::ser::{is_valid_identifier, write_quoted};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    /// The root value, with the comments before it.
+    pub root: Item,
+    /// Comments after the root value.
+    pub trailing: Vec<Comment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The comment as written, with its `//` or `/* */`.
+    pub text: String,
+    /// Whether a blank line separates it from what comes before.
+    pub blank_before: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    String {
+        value: String,
+        quote: u8,
+    },
+    /// A number, boolean or null, as written.
+    Literal(String),
+    Array(Container),
+    Object(Container),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Container {
+    pub items: Vec<Item>,
+    /// Comments after the last item.
+    pub dangling: Vec<Comment>,
+}
+
+/// An element of an array or entry of an object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// Comments on the lines before it.
+    pub comments: Vec<Comment>,
+    /// The key of an object entry.
+    pub key: Option<Key>,
+    pub value: Node,
+    /// A comment after it on the same line.
+    pub trailing: Option<Comment>,
+    /// Whether a blank line separates it from what comes before.
+    pub blank_before: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub name: String,
+    /// The quote it was written with, `None` for an identifier.
+    pub quote: Option<u8>,
+}
+
+/// Which quotes strings are written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quotes {
+    Double,
+    Single,
+    /// The quotes each string was written with.
+    Preserve,
+}
+
+/// When object keys are quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyQuotes {
+    /// Only keys that are not identifiers.
+    AsNeeded,
+    Always,
+    /// Keys quoted where they were.
+    Preserve,
+}
+
+/// How [`Document::format`] writes a document. Containers always go one
+/// item per line; the defaults match `to_string_pretty`.
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub indent: String,
+    pub quotes: Quotes,
+    pub key_quotes: KeyQuotes,
+    /// Whether the last item of a container gets a comma too.
+    pub trailing_commas: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            indent: "    ".to_string(),
+            quotes: Quotes::Double,
+            key_quotes: KeyQuotes::AsNeeded,
+            trailing_commas: false,
+        }
+    }
+}
+
+impl Document {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut reader = Reader { input, parser: Parser::new(input), pos: 0 };
+        let (comments, blank_before) = reader.trivia();
+        let value = reader.node()?;
+        let trailing = reader.inline_comment();
+        let root = Item { comments, key: None, value, trailing, blank_before };
+
+        let (trailing, _) = reader.trivia();
+        if reader.pos < input.len() {
+            return Err(Error::TrailingData(reader.pos));
+        }
+        Ok(Document { root, trailing })
+    }
+
+    pub fn format(&self, style: &Style) -> String {
+        let mut printer = Printer { style, out: Vec::new(), depth: 0 };
+        for comment in &self.root.comments {
+            if comment.blank_before && !printer.out.is_empty() {
+                printer.out.push(b'\n');
+            }
+            printer.out.extend_from_slice(comment.text.as_bytes());
+            printer.out.push(b'\n');
+        }
+        if self.root.blank_before && !printer.out.is_empty() {
+            printer.out.push(b'\n');
+        }
+        printer.node(&self.root.value);
+        printer.trailing_comment(&self.root);
+        for comment in &self.trailing {
+            printer.out.extend_from_slice(if comment.blank_before { b"\n\n" } else { b"\n" });
+            printer.out.extend_from_slice(comment.text.as_bytes());
+        }
+        printer.out.push(b'\n');
+        // Only whole strings and comments were copied
+        String::from_utf8(printer.out).unwrap_or_default()
+    }
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    /// Reads the strings, keys and literals.
+    parser: Parser<'a>,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn unexpected(&self) -> Error {
+        match self.input[self.pos..].chars().next() {
+            Some(c) => Error::UnexpectedChar(c, self.pos),
+            None => Error::UnexpectedEof,
+        }
+    }
+
+    /// Skips to the next token, returning the comments on the way and
+    /// whether a blank line precedes the token.
+    fn trivia(&mut self) -> (Vec<Comment>, bool) {
+        let mut comments = Vec::new();
+        let mut newlines = 0;
+        while let Some(c) = self.input[self.pos..].chars().next() {
+            if c == '\n' {
+                newlines += 1;
+            } else if self.input[self.pos..].starts_with("//") || self.input[self.pos..].starts_with("/*") {
+                let text = self.comment();
+                comments.push(Comment { text, blank_before: newlines > 1 });
+                newlines = 0;
+                continue;
+            } else if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        (comments, newlines > 1)
+    }
+
+    /// A comment starting on the current line, after spaces.
+    fn inline_comment(&mut self) -> Option<Comment> {
+        let rest = self.input[self.pos..].trim_start_matches([' ', '\t']);
+        if !rest.starts_with("//") && !rest.starts_with("/*") {
+            return None;
+        }
+        self.pos = self.input.len() - rest.len();
+        Some(Comment { text: self.comment(), blank_before: false })
+    }
+
+    fn comment(&mut self) -> String {
+        let rest = &self.input[self.pos..];
+        let len = if rest.starts_with("//") {
+            rest.find(['\n', '\r']).unwrap_or(rest.len())
+        } else {
+            // Unclosed comments run to the end, as the parser allows
+            rest[2..].find("*/").map_or(rest.len(), |end| end + 4)
+        };
+        self.pos += len;
+        rest[..len].trim_end().to_string()
+    }
+
+    fn node(&mut self) -> Result<Node> {
+        match self.peek().ok_or(Error::UnexpectedEof)? {
+            b'{' => Ok(Node::Object(self.container(b'}', true)?)),
+            b'[' => Ok(Node::Array(self.container(b']', false)?)),
+            quote @ (b'"' | b'\'') => {
+                self.parser.seek(self.pos);
+                let value = self.parser.parse_string()?;
+                self.pos = self.parser.pos();
+                Ok(Node::String { value, quote })
+            },
+            _ => {
+                let start = self.pos;
+                self.parser.seek(start);
+                self.parser.parse_value()?;
+                self.pos = self.parser.pos();
+                Ok(Node::Literal(self.input[start..self.pos].to_string()))
+            },
+        }
+    }
+
+    fn container(&mut self, close: u8, object: bool) -> Result<Container> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        // Comments read that belong to the next item
+        let mut pending = Vec::new();
+        loop {
+            let (comments, blank_before) = self.trivia();
+            pending.extend(comments);
+            match self.peek() {
+                None => return Err(Error::UnexpectedEof),
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(Container { items, dangling: pending });
+                },
+                _ => {},
+            }
+
+            let key = if object {
+                let quote = self.peek().filter(|c| matches!(c, b'"' | b'\''));
+                self.parser.seek(self.pos);
+                let name = self.parser.parse_key()?;
+                self.pos = self.parser.pos();
+                // Comments around the colon go before the entry
+                pending.extend(self.trivia().0);
+                if self.peek() != Some(b':') {
+                    return Err(self.unexpected());
+                }
+                self.pos += 1;
+                pending.extend(self.trivia().0);
+                Some(Key { name, quote })
+            } else {
+                None
+            };
+
+            let value = self.node()?;
+            let mut trailing = self.inline_comment();
+            let (after, _) = self.trivia();
+            match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    if trailing.is_none() {
+                        trailing = self.inline_comment();
+                    }
+                },
+                Some(c) if c == close => {},
+                _ => return Err(self.unexpected()),
+            }
+            items.push(Item {
+                comments: std::mem::take(&mut pending),
+                key,
+                value,
+                trailing,
+                blank_before,
+            });
+            pending = after;
+        }
+    }
+}
+
+struct Printer<'a> {
+    style: &'a Style,
+    out: Vec<u8>,
+    depth: usize,
+}
+
+impl Printer<'_> {
+    fn newline(&mut self, blank: bool) {
+        self.out.extend_from_slice(if blank { b"\n\n" } else { b"\n" });
+        for _ in 0..self.depth {
+            self.out.extend_from_slice(self.style.indent.as_bytes());
+        }
+    }
+
+    /// Comments on lines of their own. `started` is whether anything was
+    /// written in the container yet: no blank line goes before the first.
+    fn comments(&mut self, comments: &[Comment], started: &mut bool) {
+        for comment in comments {
+            self.newline(*started && comment.blank_before);
+            self.out.extend_from_slice(comment.text.as_bytes());
+            *started = true;
+        }
+    }
+
+    fn trailing_comment(&mut self, item: &Item) {
+        if let Some(comment) = &item.trailing {
+            self.out.push(b' ');
+            self.out.extend_from_slice(comment.text.as_bytes());
+        }
+    }
+
+    fn quote(&self, original: Option<u8>) -> u8 {
+        match self.style.quotes {
+            Quotes::Double => b'"',
+            Quotes::Single => b'\'',
+            Quotes::Preserve => original.unwrap_or(b'"'),
+        }
+    }
+
+    fn string(&mut self, value: &str, quote: u8) {
+        // Writing to a Vec cannot fail
+        let _ = write_quoted(&mut self.out, value, quote);
+    }
+
+    fn key(&mut self, key: &Key) {
+        let bare = match self.style.key_quotes {
+            KeyQuotes::AsNeeded => is_valid_identifier(&key.name),
+            KeyQuotes::Always => false,
+            KeyQuotes::Preserve => key.quote.is_none(),
+        };
+        if bare {
+            self.out.extend_from_slice(key.name.as_bytes());
+        } else {
+            self.string(&key.name, self.quote(key.quote));
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::String { value, quote } => self.string(value, self.quote(Some(*quote))),
+            Node::Literal(raw) => self.out.extend_from_slice(raw.as_bytes()),
+            Node::Array(container) => self.container(container, b'[', b']'),
+            Node::Object(container) => self.container(container, b'{', b'}'),
+        }
+    }
+
+    fn container(&mut self, container: &Container, open: u8, close: u8) {
+        self.out.push(open);
+        if container.items.is_empty() && container.dangling.is_empty() {
+            self.out.push(close);
+            return;
+        }
+
+        self.depth += 1;
+        let mut started = false;
+        for (i, item) in container.items.iter().enumerate() {
+            self.comments(&item.comments, &mut started);
+            self.newline(started && item.blank_before);
+            started = true;
+            if let Some(key) = &item.key {
+                self.key(key);
+                self.out.extend_from_slice(b": ");
+            }
+            self.node(&item.value);
+            if i + 1 < container.items.len() || self.style.trailing_commas {
+                self.out.push(b',');
+            }
+            self.trailing_comment(item);
+        }
+        self.comments(&container.dangling, &mut started);
+        self.depth -= 1;
+        self.newline(false);
+        self.out.push(close);
+    }
+}
@@ -26,6 +26,12 @@ pub enum Error {
     Custom(String),
     /// Type mismatch during deserialization
     TypeMismatch { expected: &'static str, got: &'static str },
+    /// Input that is not UTF-8, valid up to the given position
+    InvalidUtf8(usize),
+    /// Failed to read the input
+    Io(String),
+    /// String that is not an ISO-8601 timestamp
+    InvalidDateTime(String),
 }
 
 impl fmt::Display for Error {
@@ -43,12 +49,31 @@ impl fmt::Display for Error {
             Error::TypeMismatch { expected, got } => {
                 write!(f, "Type mismatch: expected {}, got {}", expected, got)
             },
+            Error::InvalidUtf8(pos) => write!(f, "Invalid UTF-8 at position {}", pos),
+            Error::Io(s) => write!(f, "{}", s),
+            Error::InvalidDateTime(s) => write!(f, "Invalid ISO-8601 timestamp: {:?}", s),
+        }
+    }
+}
+
+impl Error {
+    /// Byte offset in the input the error was found at, when known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Error::UnexpectedChar(_, pos) | Error::TrailingData(pos) | Error::InvalidUtf8(pos) => Some(*pos),
+            _ => None,
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.to_string())
+    }
+}
+
 impl de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
         Error::Custom(msg.to_string())
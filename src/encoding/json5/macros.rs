@@ -0,0 +1,99 @@
+/// Builds a [`Value`](crate::encoding::json5::Value) from JSON5-like syntax,
+/// as `serde_json::json!` does for JSON:
+///
+/// ```ignore
+/// let body = json5!({ name: "hello", "dist-tag": "next", versions: [1, 2], yanked: null });
+/// ```
+///
+/// Keys are identifiers, string literals or `(expressions)`. Values are
+/// `null`, `true`, `false`, nested arrays and objects, or any expression with
+/// an `Into<Value>` conversion.
+#[allow(unused_macros)]
+macro_rules! json5 {
+    // Arrays: elements are collected as expressions, then put in a vec
+    (@array [$($elems:expr,)*]) => {
+        vec![$($elems,)*]
+    };
+    (@array [$($elems:expr),*]) => {
+        vec![$($elems),*]
+    };
+    (@array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!(null)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!(true)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!(false)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!([$($array)*])] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] {$($object:tt)*} $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!({$($object)*})] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!($next),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)* $crate::encoding::json5::json5!($last)])
+    };
+    (@array [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@array [$($elems,)*] $($rest)*)
+    };
+
+    // Objects: each key, then the tokens of its value up to the next comma
+    (@object $map:ident) => {};
+    (@object $map:ident , $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@object $map $($rest)*)
+    };
+    (@object $map:ident $key:ident : $($rest:tt)+) => {
+        $crate::encoding::json5::json5!(@value $map (stringify!($key)) () $($rest)+)
+    };
+    (@object $map:ident $key:literal : $($rest:tt)+) => {
+        $crate::encoding::json5::json5!(@value $map ($key) () $($rest)+)
+    };
+    (@object $map:ident ($key:expr) : $($rest:tt)+) => {
+        $crate::encoding::json5::json5!(@value $map ($key) () $($rest)+)
+    };
+    (@value $map:ident ($key:expr) ($($value:tt)+) , $($rest:tt)*) => {
+        $map.insert(::std::string::String::from($key), $crate::encoding::json5::json5!($($value)+));
+        $crate::encoding::json5::json5!(@object $map $($rest)*);
+    };
+    (@value $map:ident ($key:expr) ($($value:tt)+)) => {
+        $map.insert(::std::string::String::from($key), $crate::encoding::json5::json5!($($value)+));
+    };
+    (@value $map:ident ($key:expr) ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::encoding::json5::json5!(@value $map ($key) ($($value)* $next) $($rest)*)
+    };
+
+    (null) => {
+        $crate::encoding::json5::Value::Null
+    };
+    (true) => {
+        $crate::encoding::json5::Value::Bool(true)
+    };
+    (false) => {
+        $crate::encoding::json5::Value::Bool(false)
+    };
+    ([]) => {
+        $crate::encoding::json5::Value::Array(vec![])
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::encoding::json5::Value::Array($crate::encoding::json5::json5!(@array [] $($tt)+))
+    };
+    ({}) => {
+        $crate::encoding::json5::Value::Object($crate::encoding::json5::Map::new())
+    };
+    ({ $($tt:tt)+ }) => {{
+        let mut map = $crate::encoding::json5::Map::new();
+        $crate::encoding::json5::json5!(@object map $($tt)+);
+        $crate::encoding::json5::Value::Object(map)
+    }};
+    ($other:expr) => {
+        $crate::encoding::json5::Value::from($other)
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use json5;
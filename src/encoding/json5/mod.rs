@@ -1,17 +1,31 @@
 /// JSON5 implementation in Rust with serde support.
 /// Spec: https://spec.json5.org/
+pub mod datetime;
 pub mod de;
+pub mod document;
 pub mod error;
+mod macros;
 pub mod parser;
 pub mod ser;
 pub mod value;
 
+pub use datetime::DateTime;
+pub use document::Document;
 pub use error::{Error, Result};
+#[allow(unused_imports)]
+pub(crate) use macros::json5;
 pub use parser::Parser;
 #[allow(unused_imports)]
 pub use value::{Map, Number, Value};
 
+use memmap2::Mmap;
 use serde::{Serialize, de::DeserializeOwned};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Size from which [`from_path`] maps a file into memory instead of reading it.
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
 
 /// Deserialize a JSON5 string into a Rust type.
 pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T> {
@@ -19,6 +33,36 @@ pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T> {
     T::deserialize(de::ValueDeserializer::new(value))
 }
 
+/// Deserialize UTF-8 JSON5 bytes into a Rust type.
+pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T> {
+    let input = std::str::from_utf8(input).map_err(|e| Error::InvalidUtf8(e.valid_up_to()))?;
+    from_str(input)
+}
+
+/// Deserialize JSON5 read from `reader` into a Rust type. The input is
+/// buffered as bytes, without the copy into a `String`.
+pub fn from_reader<T: DeserializeOwned, R: Read>(mut reader: R) -> Result<T> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    from_slice(&input)
+}
+
+/// Deserialize a JSON5 file into a Rust type. Files from [`MMAP_THRESHOLD`]
+/// on are memory-mapped and parsed in place, so a large lockfile is never
+/// held in memory twice.
+pub fn from_path<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() < MMAP_THRESHOLD {
+        return from_reader(file);
+    }
+
+    // SAFETY: the map is only read while parsing, and what is parsed owns its
+    // data. A file truncated by another process meanwhile is the hazard of
+    // any mapped read, as it is for lockfiles written by a concurrent vk.
+    let map = unsafe { Mmap::map(&file)? };
+    from_slice(&map)
+}
+
 /// Serialize a Rust type into a JSON5 string.
 #[allow(dead_code)]
 pub fn to_string<T: Serialize>(value: &T) -> Result<String> {
@@ -30,6 +74,25 @@ pub fn to_string_pretty<T: Serialize>(value: &T) -> Result<String> {
     ser::serialize_with_formatter(value, &mut ser::PrettyFormatter::new("    ", false))
 }
 
+/// Serialize a Rust type into `writer` as it goes, without building a `Value`
+/// or a `String` first. Wrap files in a `BufWriter`.
+#[allow(dead_code)]
+pub fn to_writer<W: Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
+    ser::serialize_into(writer, value, ser::CompactFormatter::new(false, None))
+}
+
+/// Serialize with pretty-printing in canonical form: object keys sorted and
+/// numbers normalized, so the output only depends on the data. For files
+/// whose bytes are hashed, like those generated into package archives.
+pub fn to_string_canonical<T: Serialize>(value: &T) -> Result<String> {
+    ser::serialize_with_formatter(value, &mut ser::PrettyFormatter::new("    ", false).canonical())
+}
+
+/// [`to_writer`] with pretty-printing, laid out as [`to_string_pretty`].
+pub fn to_writer_pretty<W: Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
+    ser::serialize_into(writer, value, ser::PrettyFormatter::new("    ", false))
+}
+
 /// Parse a JSON5 string into a `Value`.
 pub fn parse_value(input: &str) -> Result<Value> {
     let mut parser = Parser::new(input);
@@ -44,3 +107,6 @@ pub fn parse_value(input: &str) -> Result<Value> {
 #[cfg(test)]
 #[cfg(not(clippy))]
 mod tests;
+
+#[cfg(test)]
+mod bench;
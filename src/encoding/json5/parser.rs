@@ -19,6 +19,12 @@ impl<'a> Parser<'a> {
         self.pos
     }
 
+    /// Moves to byte `pos` of the input.
+    #[inline]
+    pub fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
     #[inline]
     pub fn remaining(&self) -> usize {
         self.input.len() - self.pos
@@ -494,17 +500,16 @@ impl<'a> Parser<'a> {
             let f: f64 = s.parse().map_err(|_| Error::InvalidNumber(s.clone()))?;
             Ok(Value::Number(Number::Float(f)))
         } else if negative {
-            let i: i64 = s.parse().map_err(|_| Error::InvalidNumber(s.clone()))?;
-            Ok(Value::Number(Number::Int(i)))
+            match s.parse::<i64>() {
+                Ok(i) => Ok(Value::Number(Number::Int(i))),
+                Err(_) => big_integer(&s),
+            }
         } else {
             // Use Int for small positive numbers, Uint for large ones
             match s.parse::<u64>() {
                 Ok(n) if n <= i64::MAX as u64 => Ok(Value::Number(Number::Int(n as i64))),
                 Ok(n) => Ok(Value::Number(Number::Uint(n))),
-                Err(_) => {
-                    let f: f64 = s.parse().map_err(|_| Error::InvalidNumber(s.clone()))?;
-                    Ok(Value::Number(Number::Float(f)))
-                },
+                Err(_) => big_integer(&s),
             }
         }
     }
@@ -579,7 +584,7 @@ impl<'a> Parser<'a> {
 
     /// JSON5 keys can be quoted strings OR unquoted identifiers
     /// Supports to normal JSON
-    fn parse_key(&mut self) -> Result<String> {
+    pub fn parse_key(&mut self) -> Result<String> {
         match self.peek() {
             Some(b'"') | Some(b'\'') => self.parse_string(),
             Some(b) if is_id_start(b) => self.parse_identifier(),
@@ -623,6 +628,17 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// An integer literal past the range of `i64`/`u64`, kept as its digits so it
+/// round-trips instead of degrading to a float.
+fn big_integer(s: &str) -> Result<Value> {
+    let s = s.strip_prefix('+').unwrap_or(s);
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::InvalidNumber(s.to_string()));
+    }
+    Ok(Value::Number(Number::Big(s.to_string())))
+}
+
 #[inline(always)]
 fn hex_val(b: u8) -> Option<u8> {
     match b {
@@ -1,9 +1,36 @@
 use crate::encoding::json5::error::{Error, Result};
 use crate::encoding::json5::value::{Map, Number, Value};
 use serde::{Serialize, ser};
+use std::io::{self, Write};
 
 pub struct ValueSerializer;
 
+fn float(v: f64) -> Number {
+    if v.is_nan() {
+        Number::NaN
+    } else if v.is_infinite() {
+        if v > 0.0 { Number::Infinity } else { Number::NegInfinity }
+    } else {
+        Number::Float(v)
+    }
+}
+
+/// The narrowest variant holding `v` exactly, `Big` past 64 bits.
+fn int128(v: i128) -> Number {
+    match (i64::try_from(v), u64::try_from(v)) {
+        (Ok(i), _) => Number::Int(i),
+        (_, Ok(u)) => Number::Uint(u),
+        _ => Number::Big(v.to_string()),
+    }
+}
+
+fn uint128(v: u128) -> Number {
+    match u64::try_from(v) {
+        Ok(u) => Number::Uint(u),
+        Err(_) => Number::Big(v.to_string()),
+    }
+}
+
 impl ser::Serializer for ValueSerializer {
     type Ok = Value;
     type Error = Error;
@@ -32,7 +59,7 @@ impl ser::Serializer for ValueSerializer {
         Ok(Value::Number(Number::Int(v)))
     }
     fn serialize_i128(self, v: i128) -> Result<Value> {
-        Ok(Value::Number(Number::Float(v as f64)))
+        Ok(Value::Number(int128(v)))
     }
     fn serialize_u8(self, v: u8) -> Result<Value> {
         Ok(Value::Number(Number::Uint(v as u64)))
@@ -47,19 +74,13 @@ impl ser::Serializer for ValueSerializer {
         Ok(Value::Number(Number::Uint(v)))
     }
     fn serialize_u128(self, v: u128) -> Result<Value> {
-        Ok(Value::Number(Number::Float(v as f64)))
+        Ok(Value::Number(uint128(v)))
     }
     fn serialize_f32(self, v: f32) -> Result<Value> {
         self.serialize_f64(v as f64)
     }
     fn serialize_f64(self, v: f64) -> Result<Value> {
-        Ok(Value::Number(if v.is_nan() {
-            Number::NaN
-        } else if v.is_infinite() {
-            if v > 0.0 { Number::Infinity } else { Number::NegInfinity }
-        } else {
-            Number::Float(v)
-        }))
+        Ok(Value::Number(float(v)))
     }
     fn serialize_char(self, v: char) -> Result<Value> {
         Ok(Value::String(v.to_string()))
@@ -247,247 +268,618 @@ impl ser::SerializeStructVariant for StructVariantSerializer {
 /// Maximum depth for JSON serialization.
 const MAX_DEPTH: usize = 512;
 
+/// Lays out the output. Serializers call it for each piece as they reach it,
+/// so it writes straight into any `io::Write`.
 pub trait Formatter {
-    fn write_null(&mut self, out: &mut String) -> Result<()>;
-    fn write_bool(&mut self, out: &mut String, v: bool) -> Result<()>;
-    fn write_number(&mut self, out: &mut String, n: &Number) -> Result<()>;
-    fn write_string(&mut self, out: &mut String, s: &str) -> Result<()>;
-    fn write_array(&mut self, out: &mut String, arr: &[Value], depth: usize) -> Result<()>;
-    fn write_object(&mut self, out: &mut String, obj: &Map<String, Value>, depth: usize) -> Result<()>;
-    fn write_value(&mut self, out: &mut String, v: &Value, depth: usize) -> Result<()>;
-    fn write_object_key(&mut self, out: &mut String, k: &str) -> Result<()>;
+    /// Deepest nesting of arrays and objects written.
+    fn max_depth(&self) -> usize {
+        MAX_DEPTH
+    }
+
+    /// Whether object keys are sorted and numbers normalized, so the output
+    /// only depends on the data (see [`Value::canonicalize`]).
+    fn is_canonical(&self) -> bool {
+        false
+    }
+
+    fn write_null<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"null")
+    }
+
+    fn write_bool<W: ?Sized + Write>(&mut self, out: &mut W, v: bool) -> io::Result<()> {
+        out.write_all(if v { b"true" } else { b"false" })
+    }
+
+    fn write_number<W: ?Sized + Write>(&mut self, out: &mut W, n: &Number) -> io::Result<()> {
+        write!(out, "{}", n)
+    }
+
+    fn write_string<W: ?Sized + Write>(&mut self, out: &mut W, s: &str) -> io::Result<()> {
+        write_escaped_str(out, s)
+    }
+
+    fn write_object_key<W: ?Sized + Write>(&mut self, out: &mut W, k: &str) -> io::Result<()>;
+
+    fn begin_array<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"[")
+    }
+
+    /// Closes an array that got `len` elements.
+    fn end_array<W: ?Sized + Write>(&mut self, out: &mut W, _len: usize) -> io::Result<()> {
+        out.write_all(b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        if first { Ok(()) } else { out.write_all(b",") }
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"{")
+    }
+
+    /// Closes an object that got `len` entries.
+    fn end_object<W: ?Sized + Write>(&mut self, out: &mut W, _len: usize) -> io::Result<()> {
+        out.write_all(b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        if first { Ok(()) } else { out.write_all(b",") }
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        out.write_all(b":")
+    }
+}
+
+impl<F: Formatter + ?Sized> Formatter for &mut F {
+    fn max_depth(&self) -> usize {
+        (**self).max_depth()
+    }
+    fn is_canonical(&self) -> bool {
+        (**self).is_canonical()
+    }
+    fn write_null<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        (**self).write_null(out)
+    }
+    fn write_bool<W: ?Sized + Write>(&mut self, out: &mut W, v: bool) -> io::Result<()> {
+        (**self).write_bool(out, v)
+    }
+    fn write_number<W: ?Sized + Write>(&mut self, out: &mut W, n: &Number) -> io::Result<()> {
+        (**self).write_number(out, n)
+    }
+    fn write_string<W: ?Sized + Write>(&mut self, out: &mut W, s: &str) -> io::Result<()> {
+        (**self).write_string(out, s)
+    }
+    fn write_object_key<W: ?Sized + Write>(&mut self, out: &mut W, k: &str) -> io::Result<()> {
+        (**self).write_object_key(out, k)
+    }
+    fn begin_array<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        (**self).begin_array(out)
+    }
+    fn end_array<W: ?Sized + Write>(&mut self, out: &mut W, len: usize) -> io::Result<()> {
+        (**self).end_array(out, len)
+    }
+    fn begin_array_value<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        (**self).begin_array_value(out, first)
+    }
+    fn begin_object<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        (**self).begin_object(out)
+    }
+    fn end_object<W: ?Sized + Write>(&mut self, out: &mut W, len: usize) -> io::Result<()> {
+        (**self).end_object(out, len)
+    }
+    fn begin_object_key<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        (**self).begin_object_key(out, first)
+    }
+    fn begin_object_value<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        (**self).begin_object_value(out)
+    }
 }
 
 pub struct CompactFormatter {
     pub quote_keys: bool,
     max_depth: usize,
+    canonical: bool,
 }
 
 impl CompactFormatter {
     pub fn new(quote_keys: bool, max_depth: Option<usize>) -> Self {
-        Self { quote_keys, max_depth: max_depth.unwrap_or(MAX_DEPTH) }
+        Self {
+            quote_keys,
+            max_depth: max_depth.unwrap_or(MAX_DEPTH),
+            canonical: false,
+        }
+    }
+
+    /// Sorts object keys and normalizes numbers.
+    pub fn canonical(mut self) -> Self {
+        self.canonical = true;
+        self
     }
 }
 
 impl Formatter for CompactFormatter {
-    fn write_null(&mut self, out: &mut String) -> Result<()> {
-        out.push_str("null");
-        Ok(())
+    fn max_depth(&self) -> usize {
+        self.max_depth
     }
 
-    fn write_bool(&mut self, out: &mut String, v: bool) -> Result<()> {
-        out.push_str(if v { "true" } else { "false" });
-        Ok(())
+    fn is_canonical(&self) -> bool {
+        self.canonical
     }
 
-    fn write_number(&mut self, out: &mut String, n: &Number) -> Result<()> {
-        out.push_str(&n.to_string());
-        Ok(())
+    fn write_object_key<W: ?Sized + Write>(&mut self, out: &mut W, k: &str) -> io::Result<()> {
+        write_key(out, k, self.quote_keys)
     }
+}
 
-    fn write_string(&mut self, out: &mut String, s: &str) -> Result<()> {
-        write_escaped_str(out, s, true);
-        Ok(())
+pub struct PrettyFormatter<'a> {
+    indent_str: &'a str,
+    pub quote_keys: bool,
+    depth: usize,
+    canonical: bool,
+}
+
+impl<'a> PrettyFormatter<'a> {
+    pub fn new(indent_str: &'a str, quote_keys: bool) -> Self {
+        Self { indent_str, quote_keys, depth: 0, canonical: false }
     }
 
-    fn write_value(&mut self, out: &mut String, v: &Value, depth: usize) -> Result<()> {
-        if depth > self.max_depth {
-            return Err(Error::Custom("Recursion limit exceeded".into()));
-        }
-        match v {
-            Value::Null => self.write_null(out),
-            Value::Bool(b) => self.write_bool(out, *b),
-            Value::Number(n) => self.write_number(out, n),
-            Value::String(s) => self.write_string(out, s),
-            Value::Array(arr) => self.write_array(out, arr, depth),
-            Value::Object(map) => self.write_object(out, map, depth),
-        }
+    /// Sorts object keys and normalizes numbers.
+    pub fn canonical(mut self) -> Self {
+        self.canonical = true;
+        self
     }
 
-    fn write_array(&mut self, out: &mut String, arr: &[Value], depth: usize) -> Result<()> {
-        out.push('[');
-        for (i, v) in arr.iter().enumerate() {
-            if i > 0 {
-                out.push(',');
-            }
-            self.write_value(out, v, depth + 1)?;
+    fn write_indent<W: ?Sized + Write>(&self, out: &mut W) -> io::Result<()> {
+        for _ in 0..self.depth {
+            out.write_all(self.indent_str.as_bytes())?;
         }
-        out.push(']');
         Ok(())
     }
 
-    fn write_object(&mut self, out: &mut String, obj: &Map<String, Value>, depth: usize) -> Result<()> {
-        out.push('{');
-        for (i, (k, v)) in obj.iter().enumerate() {
-            if i > 0 {
-                out.push(',');
-            }
-            self.write_object_key(out, k)?;
-            out.push(':');
-            self.write_value(out, v, depth + 1)?;
+    /// Closes a container, on its own line unless it is empty.
+    fn end<W: ?Sized + Write>(&mut self, out: &mut W, len: usize, close: &[u8]) -> io::Result<()> {
+        self.depth -= 1;
+        if len > 0 {
+            out.write_all(b"\n")?;
+            self.write_indent(out)?;
         }
-        out.push('}');
-        Ok(())
+        out.write_all(close)
     }
 
-    fn write_object_key(&mut self, out: &mut String, k: &str) -> Result<()> {
-        if !self.quote_keys && is_valid_identifier(k) {
-            out.push_str(k);
-        } else {
-            write_escaped_str(out, k, true);
-        }
-        Ok(())
+    /// Starts an element or entry on its own line.
+    fn next_line<W: ?Sized + Write>(&self, out: &mut W, first: bool) -> io::Result<()> {
+        out.write_all(if first { b"\n" } else { b",\n" })?;
+        self.write_indent(out)
     }
 }
 
-pub struct PrettyFormatter<'a> {
-    indent_str: &'a str,
-    pub quote_keys: bool,
+impl<'a> Formatter for PrettyFormatter<'a> {
+    fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    fn write_object_key<W: ?Sized + Write>(&mut self, out: &mut W, k: &str) -> io::Result<()> {
+        write_key(out, k, self.quote_keys)
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.depth += 1;
+        out.write_all(b"[")
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, out: &mut W, len: usize) -> io::Result<()> {
+        self.end(out, len, b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        self.next_line(out, first)
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.depth += 1;
+        out.write_all(b"{")
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, out: &mut W, len: usize) -> io::Result<()> {
+        self.end(out, len, b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(&mut self, out: &mut W, first: bool) -> io::Result<()> {
+        self.next_line(out, first)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+        out.write_all(b": ")
+    }
 }
 
-impl<'a> PrettyFormatter<'a> {
-    pub fn new(indent_str: &'a str, quote_keys: bool) -> Self {
-        Self { indent_str, quote_keys }
+/// Writes an object key, bare when it is an identifier unless `quote` is set.
+fn write_key<W: ?Sized + Write>(out: &mut W, k: &str, quote: bool) -> io::Result<()> {
+    if !quote && is_valid_identifier(k) {
+        out.write_all(k.as_bytes())
+    } else {
+        write_escaped_str(out, k)
     }
+}
 
-    fn write_indent(&self, writer: &mut String, depth: usize) {
-        for _ in 0..depth {
-            writer.push_str(self.indent_str);
-        }
+/// Writes `s` in double quotes.
+fn write_escaped_str<W: ?Sized + Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write_quoted(out, s, b'"')
+}
+
+/// Writes `s` between `quote`s, copying the runs that need no escape in one
+/// go.
+pub(crate) fn write_quoted<W: ?Sized + Write>(out: &mut W, s: &str, quote: u8) -> io::Result<()> {
+    out.write_all(&[quote])?;
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let escape: &[u8] = match b {
+            b'"' if quote == b'"' => b"\\\"",
+            b'\'' if quote == b'\'' => b"\\'",
+            b'\\' => b"\\\\",
+            0x08 => b"\\b",
+            0x0c => b"\\f",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..0x20 => {
+                out.write_all(&bytes[start..i])?;
+                write!(out, "\\u{:04x}", b)?;
+                start = i + 1;
+                continue;
+            },
+            _ => continue,
+        };
+        out.write_all(&bytes[start..i])?;
+        out.write_all(escape)?;
+        start = i + 1;
     }
+    out.write_all(&bytes[start..])?;
+    out.write_all(&[quote])
 }
 
-impl<'a> Formatter for PrettyFormatter<'a> {
-    fn write_null(&mut self, out: &mut String) -> Result<()> {
-        out.push_str("null");
-        Ok(())
+#[inline]
+pub(crate) fn is_valid_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {},
+        _ => return false,
     }
-    fn write_bool(&mut self, out: &mut String, v: bool) -> Result<()> {
-        out.push_str(if v { "true" } else { "false" });
-        Ok(())
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+// -------------------------------------------------------------------------
+// Rust type → JSON5 writer, without an intermediate Value
+// -------------------------------------------------------------------------
+
+/// Serializes values straight into `writer`, laid out by `formatter`.
+pub struct Serializer<W, F> {
+    writer: W,
+    formatter: F,
+    depth: usize,
+}
+
+impl<W: Write, F: Formatter> Serializer<W, F> {
+    pub fn with_formatter(writer: W, formatter: F) -> Self {
+        Self { writer, formatter, depth: 0 }
     }
-    fn write_number(&mut self, out: &mut String, n: &Number) -> Result<()> {
-        out.push_str(&n.to_string());
+
+    /// Enters an array or object.
+    fn nest(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.formatter.max_depth() {
+            return Err(Error::Custom("Recursion limit exceeded".into()));
+        }
         Ok(())
     }
-    fn write_string(&mut self, out: &mut String, s: &str) -> Result<()> {
-        write_escaped_str(out, s, true);
-        Ok(())
+
+    fn begin_array(&mut self) -> Result<()> {
+        self.nest()?;
+        Ok(self.formatter.begin_array(&mut self.writer)?)
     }
 
-    fn write_value(&mut self, out: &mut String, v: &Value, depth: usize) -> Result<()> {
-        match v {
-            Value::Array(arr) => self.write_array(out, arr, depth),
-            Value::Object(map) => self.write_object(out, map, depth),
-            _ => {
-                // Para tipos simples no hay indentación extra aquí
-                match v {
-                    Value::Null => self.write_null(out),
-                    Value::Bool(b) => self.write_bool(out, *b),
-                    Value::Number(n) => self.write_number(out, n),
-                    Value::String(s) => self.write_string(out, s),
-                    _ => unreachable!(),
-                }
-            },
-        }
+    fn end_array(&mut self, len: usize) -> Result<()> {
+        self.depth -= 1;
+        Ok(self.formatter.end_array(&mut self.writer, len)?)
     }
 
-    fn write_array(&mut self, out: &mut String, arr: &[Value], depth: usize) -> Result<()> {
-        if arr.is_empty() {
-            out.push_str("[]");
-            return Ok(());
-        }
-        out.push_str("[\n");
-        for (i, v) in arr.iter().enumerate() {
-            self.write_indent(out, depth + 1);
-            self.write_value(out, v, depth + 1)?;
-            if i < arr.len() - 1 {
-                out.push(',');
-            }
-            out.push('\n');
+    fn begin_object(&mut self) -> Result<()> {
+        self.nest()?;
+        Ok(self.formatter.begin_object(&mut self.writer)?)
+    }
+
+    fn end_object(&mut self, len: usize) -> Result<()> {
+        self.depth -= 1;
+        Ok(self.formatter.end_object(&mut self.writer, len)?)
+    }
+
+    /// Writes the key of the entry after `len` others, up to its value.
+    fn key(&mut self, key: &str, len: usize) -> Result<()> {
+        self.formatter.begin_object_key(&mut self.writer, len == 0)?;
+        self.formatter.write_object_key(&mut self.writer, key)?;
+        Ok(self.formatter.begin_object_value(&mut self.writer)?)
+    }
+
+    fn number(&mut self, n: Number) -> Result<()> {
+        Ok(self.formatter.write_number(&mut self.writer, &n)?)
+    }
+}
+
+impl<'a, W: Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W, F>;
+    type SerializeTuple = Compound<'a, W, F>;
+    type SerializeTupleStruct = Compound<'a, W, F>;
+    type SerializeTupleVariant = Compound<'a, W, F>;
+    type SerializeMap = Compound<'a, W, F>;
+    type SerializeStruct = Compound<'a, W, F>;
+    type SerializeStructVariant = Compound<'a, W, F>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        Ok(self.formatter.write_bool(&mut self.writer, v)?)
+    }
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.number(Number::Int(v as i64))
+    }
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.number(Number::Int(v as i64))
+    }
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.number(Number::Int(v as i64))
+    }
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.number(Number::Int(v))
+    }
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.number(int128(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.number(Number::Uint(v as u64))
+    }
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.number(Number::Uint(v as u64))
+    }
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.number(Number::Uint(v as u64))
+    }
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.number(Number::Uint(v))
+    }
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.number(uint128(v))
+    }
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.number(float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, v: &str) -> Result<()> {
+        Ok(self.formatter.write_string(&mut self.writer, v)?)
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        use ser::SerializeSeq;
+        let mut seq = self.serialize_seq(Some(v.len()))?;
+        for b in v {
+            seq.serialize_element(b)?;
         }
-        self.write_indent(out, depth);
-        out.push(']');
+        seq.end()
+    }
+    fn serialize_none(self) -> Result<()> {
+        Ok(self.formatter.write_null(&mut self.writer)?)
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<()> {
+        self.serialize_none()
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_none()
+    }
+    fn serialize_unit_variant(self, _name: &'static str, _idx: u32, variant: &'static str) -> Result<()> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _idx: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.begin_object()?;
+        self.key(variant, 0)?;
+        value.serialize(&mut *self)?;
+        self.end_object(1)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, W, F>> {
+        self.begin_array()?;
+        Ok(Compound { ser: self, len: 0, variant: false })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, W, F>> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W, F>> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _idx: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W, F>> {
+        self.begin_object()?;
+        self.key(variant, 0)?;
+        self.begin_array()?;
+        Ok(Compound { ser: self, len: 0, variant: true })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, W, F>> {
+        self.begin_object()?;
+        Ok(Compound { ser: self, len: 0, variant: false })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W, F>> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _idx: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W, F>> {
+        self.begin_object()?;
+        self.key(variant, 0)?;
+        self.begin_object()?;
+        Ok(Compound { ser: self, len: 0, variant: true })
+    }
+}
+
+/// An array or object being written, with the elements or entries it got.
+/// Variants close the object wrapping them too.
+pub struct Compound<'a, W, F> {
+    ser: &'a mut Serializer<W, F>,
+    len: usize,
+    variant: bool,
+}
+
+impl<W: Write, F: Formatter> Compound<'_, W, F> {
+    fn element<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        self.ser.formatter.begin_array_value(&mut self.ser.writer, self.len == 0)?;
+        v.serialize(&mut *self.ser)?;
+        self.len += 1;
         Ok(())
     }
 
-    fn write_object(&mut self, out: &mut String, obj: &Map<String, Value>, depth: usize) -> Result<()> {
-        if obj.is_empty() {
-            out.push_str("{}");
-            return Ok(());
-        }
-        out.push_str("{\n");
-        for (i, (k, v)) in obj.iter().enumerate() {
-            self.write_indent(out, depth + 1);
-            self.write_object_key(out, k)?;
-            out.push_str(": ");
-            self.write_value(out, v, depth + 1)?;
-            if i < obj.len() - 1 {
-                out.push(',');
-            }
-            out.push('\n');
+    fn field<T: ?Sized + Serialize>(&mut self, k: &str, v: &T) -> Result<()> {
+        self.ser.key(k, self.len)?;
+        v.serialize(&mut *self.ser)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn end_array(self) -> Result<()> {
+        self.ser.end_array(self.len)?;
+        if self.variant {
+            self.ser.end_object(1)?;
         }
-        self.write_indent(out, depth);
-        out.push('}');
         Ok(())
     }
 
-    fn write_object_key(&mut self, out: &mut String, k: &str) -> Result<()> {
-        if !self.quote_keys && is_valid_identifier(k) {
-            out.push_str(k);
-        } else {
-            write_escaped_str(out, k, true);
+    fn end_object(self) -> Result<()> {
+        self.ser.end_object(self.len)?;
+        if self.variant {
+            self.ser.end_object(1)?;
         }
         Ok(())
     }
 }
 
-fn write_escaped_str(out: &mut String, s: &str, quote: bool) {
-    if quote {
-        out.push('"');
-    }
-    for ch in s.chars() {
-        match ch {
-            '"' => out.push_str("\\\""),
-            '\\' => out.push_str("\\\\"),
-            '\x08' => out.push_str("\\b"),
-            '\x0c' => out.push_str("\\f"),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            '\t' => out.push_str("\\t"),
-            c if c < '\x20' => {
-                let code = c as u32;
-                out.push_str("\\u");
-                out.push(hex_digit((code >> 12) as u8 & 0xF));
-                out.push(hex_digit((code >> 8) as u8 & 0xF));
-                out.push(hex_digit((code >> 4) as u8 & 0xF));
-                out.push(hex_digit(code as u8 & 0xF));
-            },
-            c => out.push(c),
-        }
+impl<W: Write, F: Formatter> ser::SerializeSeq for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
     }
-    if quote {
-        out.push('"');
+    fn end(self) -> Result<()> {
+        self.end_array()
     }
 }
-
-#[inline]
-fn is_valid_identifier(key: &str) -> bool {
-    let mut chars = key.chars();
-    match chars.next() {
-        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {},
-        _ => return false,
+impl<W: Write, F: Formatter> ser::SerializeTuple for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+    fn end(self) -> Result<()> {
+        self.end_array()
+    }
+}
+impl<W: Write, F: Formatter> ser::SerializeTupleStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+    fn end(self) -> Result<()> {
+        self.end_array()
+    }
+}
+impl<W: Write, F: Formatter> ser::SerializeTupleVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+    fn end(self) -> Result<()> {
+        self.end_array()
+    }
+}
+impl<W: Write, F: Formatter> ser::SerializeMap for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, k: &T) -> Result<()> {
+        // Keys that are not strings are written as they would print
+        let key = match k.serialize(ValueSerializer)? {
+            Value::String(s) => s,
+            other => other.to_string(),
+        };
+        self.ser.key(&key, self.len)
+    }
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<()> {
+        v.serialize(&mut *self.ser)?;
+        self.len += 1;
+        Ok(())
+    }
+    fn end(self) -> Result<()> {
+        self.end_object()
+    }
+}
+impl<W: Write, F: Formatter> ser::SerializeStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, k: &'static str, v: &T) -> Result<()> {
+        self.field(k, v)
+    }
+    fn end(self) -> Result<()> {
+        self.end_object()
+    }
+}
+impl<W: Write, F: Formatter> ser::SerializeStructVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, k: &'static str, v: &T) -> Result<()> {
+        self.field(k, v)
+    }
+    fn end(self) -> Result<()> {
+        self.end_object()
     }
-    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
 }
 
-#[inline]
-fn hex_digit(n: u8) -> char {
-    if n < 10 {
-        (b'0' + n) as char
-    } else {
-        (b'a' + n - 10) as char
+/// Serializes `value` into `writer` as laid out by `formatter`. Canonical
+/// formatters need the whole value to sort its keys, so it is built first.
+pub fn serialize_into<W, T, V>(writer: W, value: &V, formatter: T) -> Result<()>
+where
+    W: Write,
+    T: Formatter,
+    V: ?Sized + Serialize,
+{
+    if formatter.is_canonical() {
+        let mut value = value.serialize(ValueSerializer)?;
+        value.canonicalize();
+        return value.serialize(&mut Serializer::with_formatter(writer, formatter));
     }
+    value.serialize(&mut Serializer::with_formatter(writer, formatter))
 }
 
 // -------------------------------------------------------------------------
@@ -498,12 +890,7 @@ pub fn serialize<V>(value: &V) -> Result<String>
 where
     V: Serialize,
 {
-    let value = value.serialize(ValueSerializer)?;
-    let mut out = String::with_capacity(256);
-    let mut formatter = CompactFormatter::new(false, None);
-
-    formatter.write_value(&mut out, &value, 0)?;
-    Ok(out)
+    serialize_with_formatter(value, &mut CompactFormatter::new(false, None))
 }
 
 pub fn serialize_with_formatter<T, V>(value: &V, formatter: &mut T) -> Result<String>
@@ -511,9 +898,12 @@ where
     T: Formatter,
     V: Serialize,
 {
-    let internal_value = value.serialize(ValueSerializer)?;
+    let mut internal_value = value.serialize(ValueSerializer)?;
+    if formatter.is_canonical() {
+        internal_value.canonicalize();
+    }
 
-    let mut out = String::with_capacity(256);
-    formatter.write_value(&mut out, &internal_value, 0)?;
-    Ok(out)
+    let mut out = Vec::with_capacity(256);
+    internal_value.serialize(&mut Serializer::with_formatter(&mut out, formatter))?;
+    String::from_utf8(out).map_err(|e| Error::Custom(e.to_string()))
 }
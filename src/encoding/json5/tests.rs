@@ -1,6 +1,10 @@
-use crate::encoding::json5::ser::{PrettyFormatter, serialize_with_formatter};
-use crate::encoding::json5::value::{Number, Value};
-use crate::encoding::json5::{from_str, parse_value, to_string, to_string_pretty};
+use crate::encoding::json5::document::{KeyQuotes, Quotes, Style};
+use crate::encoding::json5::ser::{CompactFormatter, PrettyFormatter, serialize_into, serialize_with_formatter};
+use crate::encoding::json5::value::{Map, Number, Value};
+use crate::encoding::json5::{
+    DateTime, Document, Error, from_path, from_reader, from_slice, from_str, json5, parse_value, to_string,
+    to_string_canonical, to_string_pretty, to_writer, to_writer_pretty,
+};
 use serde::{Deserialize, Serialize};
 
 #[test]
@@ -54,6 +58,38 @@ fn test_hex_numbers() {
     assert_eq!(parse_value("-0x10").unwrap(), Value::Number(Number::Int(-16)));
 }
 
+#[test]
+fn test_big_integers() {
+    let big = "123456789012345678901234567890";
+    assert_eq!(parse_value(big).unwrap(), Value::Number(Number::Big(big.into())));
+    assert_eq!(
+        parse_value("-18446744073709551616").unwrap(),
+        Value::Number(Number::Big("-18446744073709551616".into()))
+    );
+    assert_eq!(
+        parse_value("+18_446_744_073_709_551_616").unwrap(),
+        Value::Number(Number::Big("18446744073709551616".into()))
+    );
+    assert_eq!(to_string(&parse_value(big).unwrap()).unwrap(), big);
+    assert_eq!(from_str::<u128>(big).unwrap(), 123456789012345678901234567890);
+    assert!(from_str::<u64>(big).is_err());
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Wide {
+        max: u128,
+        min: i128,
+        small: i128,
+    }
+    let wide = Wide { max: u128::MAX, min: i128::MIN, small: -3 };
+    let s = to_string(&wide).unwrap();
+    assert_eq!(s, format!("{{max:{},min:{},small:-3}}", u128::MAX, i128::MIN));
+    assert_eq!(from_str::<Wide>(&s).unwrap(), wide);
+    assert_eq!(
+        from_str::<serde_json::Value>("{n: 340282366920938463463374607431768211456}").unwrap()["n"],
+        "340282366920938463463374607431768211456"
+    );
+}
+
 #[test]
 fn test_leading_trailing_dot() {
     assert_eq!(parse_value(".5").unwrap(), Value::Number(Number::Float(0.5)));
@@ -712,3 +748,261 @@ fn parse_with_quoted_keys() {
     assert!(serialized.contains("\n    \"age\""));
     assert!(serialized.contains("\n    \"address\""));
 }
+
+#[test]
+fn test_from_path_and_reader() {
+    #[derive(Deserialize)]
+    struct Lock {
+        packages: Vec<String>,
+    }
+
+    // Large enough to be memory-mapped
+    let names: Vec<String> = (0..100_000).map(|i| format!("'package-{}'", i)).collect();
+    let input = format!("// lock\n{{ packages: [{}], }}", names.join(",\n"));
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), &input).unwrap();
+    let lock: Lock = from_path(file.path()).unwrap();
+    assert_eq!(lock.packages.len(), 100_000);
+    assert_eq!(lock.packages[99_999], "package-99999");
+
+    let lock: Lock = from_reader("{ packages: ['a'] }".as_bytes()).unwrap();
+    assert_eq!(lock.packages, ["a"]);
+    assert_eq!(
+        from_slice::<Lock>(b"{ packages: ['\xff'] }").err(),
+        Some(Error::InvalidUtf8(14))
+    );
+    assert!(matches!(
+        from_path::<Lock>("/nonexistent/vayload.lock"),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn test_to_writer_matches_to_string() {
+    #[derive(Serialize)]
+    enum Source {
+        Registry,
+        Git { url: String, rev: Option<String> },
+        Path(String),
+        Pair(u8, i64),
+    }
+
+    #[derive(Serialize)]
+    struct Lock {
+        version: u32,
+        features: Vec<String>,
+        empty: Vec<u8>,
+        sources: Vec<Source>,
+        sizes: std::collections::BTreeMap<u32, f64>,
+        #[serde(rename = "needs quotes")]
+        note: &'static str,
+    }
+
+    let lock = Lock {
+        version: 1,
+        features: vec!["hooks".into(), "line\nbreak \"quoted\" \u{1}".into()],
+        empty: Vec::new(),
+        sources: vec![
+            Source::Registry,
+            Source::Git { url: "https://example.com".into(), rev: None },
+            Source::Path("../local".into()),
+            Source::Pair(7, -3),
+        ],
+        sizes: [(1, 1.5), (2, f64::NAN), (3, 2.0)].into_iter().collect(),
+        note: "ünïcode",
+    };
+
+    let mut compact = Vec::new();
+    to_writer(&mut compact, &lock).unwrap();
+    assert_eq!(String::from_utf8(compact).unwrap(), to_string(&lock).unwrap());
+
+    let mut pretty = Vec::new();
+    to_writer_pretty(&mut pretty, &lock).unwrap();
+    let pretty = String::from_utf8(pretty).unwrap();
+    assert_eq!(pretty, to_string_pretty(&lock).unwrap());
+    assert!(pretty.contains("    empty: [],\n"));
+    assert!(pretty.contains("\"line\\nbreak \\\"quoted\\\" \\u0001\""));
+    assert!(pretty.contains("\"needs quotes\": \"ünïcode\""));
+}
+
+#[test]
+fn test_json5_macro() {
+    let name = "hello";
+    let versions = vec!["1.0.0", "2.0.0"];
+    let value = json5!({
+        name: name,
+        "dist-tag": "next",
+        (format!("{}-key", name)): 1 + 2,
+        size: -4096,
+        ratio: 0.5,
+        private: false,
+        yanked: null,
+        versions: versions,
+        deps: [{ id: "ui", optional: true }, [], {}, null, Some(7), None::<String>],
+        nested: { deep: { deeper: [1, 2,], }, },
+    });
+
+    assert_eq!(
+        value,
+        parse_value(
+            r#"{
+                name: "hello",
+                "dist-tag": "next",
+                "hello-key": 3,
+                size: -4096,
+                ratio: 0.5,
+                private: false,
+                yanked: null,
+                versions: ["1.0.0", "2.0.0"],
+                deps: [{ id: "ui", optional: true }, [], {}, null, 7, null],
+                nested: { deep: { deeper: [1, 2] } },
+            }"#
+        )
+        .unwrap()
+    );
+    assert_eq!(json5!([]), Value::Array(Vec::new()));
+    assert_eq!(json5!({}), Value::Object(Map::new()));
+    assert_eq!(json5!("text"), Value::String("text".into()));
+}
+
+#[test]
+fn test_canonical_output() {
+    let a = json5!({ zeta: 1.0, alpha: { b: [2.5, -0.0], a: 18446744073709551615u64 }, mid: 7u32 });
+    let b = json5!({ mid: 7, alpha: { a: 18446744073709551615u64, b: [2.5, 0] }, zeta: 1 });
+    assert_ne!(to_string(&a).unwrap(), to_string(&b).unwrap());
+
+    let canonical = to_string_canonical(&a).unwrap();
+    assert_eq!(canonical, to_string_canonical(&b).unwrap());
+    assert_eq!(
+        canonical,
+        "{\n    alpha: {\n        a: 18446744073709551615,\n        b: [\n            2.5,\n            0\n        ]\n    },\n    mid: 7,\n    zeta: 1\n}"
+    );
+
+    let compact = CompactFormatter::new(false, None).canonical();
+    let mut out = Vec::new();
+    serialize_into(&mut out, &a, compact).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{alpha:{a:18446744073709551615,b:[2.5,0]},mid:7,zeta:1}"
+    );
+}
+
+#[test]
+fn test_datetime() {
+    assert_eq!(DateTime::from_unix(0, 0).to_string(), "1970-01-01T00:00:00Z");
+    assert_eq!(DateTime::from_unix(11_016 * 86_400, 0).date(), "2000-02-29");
+    assert_eq!(
+        DateTime::from_unix(-1, 500_000_000).to_string(),
+        "1969-12-31T23:59:59.5Z"
+    );
+
+    let at = DateTime::parse("2026-10-16T09:30:05.120Z").unwrap();
+    assert_eq!(at.unix_timestamp(), 1_792_143_005);
+    assert_eq!(at.to_string(), "2026-10-16T09:30:05.12Z");
+    assert_eq!(DateTime::parse("2026-10-16T11:30:05.12+02:00").unwrap(), at);
+    assert_eq!(DateTime::parse("2026-10-16").unwrap().date(), "2026-10-16");
+    for invalid in ["2026-02-29T00:00:00Z", "2026-10-16T24:00:00Z", "2026-10-16T09:30:05", "16/10/2026"] {
+        assert!(
+            matches!(DateTime::parse(invalid), Err(Error::InvalidDateTime(_))),
+            "{}",
+            invalid
+        );
+    }
+
+    let time: std::time::SystemTime = at.into();
+    assert_eq!(DateTime::from(time), at);
+    assert_eq!(Value::from(at).as_datetime(), Some(at));
+    assert_eq!(Value::from(1).as_datetime(), None);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Release {
+        published_at: DateTime,
+        yanked_at: Option<DateTime>,
+    }
+    let release: Release = from_str("{published_at: '2026-10-16T09:30:05.12Z', yanked_at: null}").unwrap();
+    assert_eq!(release, Release { published_at: at, yanked_at: None });
+    assert_eq!(
+        to_string(&release).unwrap(),
+        r#"{published_at:"2026-10-16T09:30:05.12Z",yanked_at:null}"#
+    );
+    assert!(from_str::<Release>("{published_at: 'yesterday'}").is_err());
+}
+
+#[test]
+fn test_document_format() {
+    let input = r#"// plugin
+{'name': 'hello', // the id
+  "deps": {"b-lib": "^1.0.0", a: '2.x',},
+
+    /* kept */
+  list: [ 1,  0x1F,
+     .5 ], empty: {},
+  // dangling
+}
+"#;
+    let expected = r#"// plugin
+{
+    name: "hello", // the id
+    deps: {
+        "b-lib": "^1.0.0",
+        a: "2.x"
+    },
+
+    /* kept */
+    list: [
+        1,
+        0x1F,
+        .5
+    ],
+    empty: {}
+    // dangling
+}
+"#;
+    let document = Document::parse(input).unwrap();
+    assert_eq!(document.format(&Style::default()), expected);
+    assert_eq!(Document::parse(expected).unwrap().format(&Style::default()), expected);
+}
+
+#[test]
+fn test_document_style() {
+    let document = Document::parse(r#"{ "a b": "it's", c: ["x"] }"#).unwrap();
+    let style = Style {
+        indent: "\t".to_string(),
+        quotes: Quotes::Single,
+        key_quotes: KeyQuotes::Always,
+        trailing_commas: true,
+    };
+    assert_eq!(
+        document.format(&style),
+        "{\n\t'a b': 'it\\'s',\n\t'c': [\n\t\t'x',\n\t],\n}\n"
+    );
+
+    let style = Style {
+        quotes: Quotes::Preserve,
+        key_quotes: KeyQuotes::Preserve,
+        ..Style::default()
+    };
+    assert_eq!(
+        document.format(&style),
+        "{\n    \"a b\": \"it's\",\n    c: [\n        \"x\"\n    ]\n}\n"
+    );
+}
+
+#[test]
+fn test_document_matches_pretty() {
+    let value = json5!({
+        "name": "hello",
+        "tags": ["a", "b"],
+        "dependencies": { "ui-kit": "^1.0.0" },
+        "empty": [],
+    });
+    let pretty = to_string_pretty(&value).unwrap() + "\n";
+    assert_eq!(Document::parse(&pretty).unwrap().format(&Style::default()), pretty);
+}
+
+#[test]
+fn test_document_errors() {
+    assert_eq!(Document::parse("{ a: 1"), Err(Error::UnexpectedEof));
+    assert_eq!(Document::parse("{ a 1 }"), Err(Error::UnexpectedChar('1', 4)));
+    assert_eq!(Document::parse("[1] x"), Err(Error::TrailingData(4)));
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::encoding::json5::datetime::DateTime;
 use indexmap::{IndexMap, map::IntoIter as IndexMapIntoIter};
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 
@@ -12,6 +13,9 @@ pub enum Number {
     Int(i64),
     Uint(u64),
     Float(f64),
+    /// An integer outside the range of `i64` and `u64`, as its decimal digits
+    /// (with a leading `-` when negative), so it round-trips exactly
+    Big(String),
     /// JSON5: NaN
     NaN,
     /// JSON5: Infinity
@@ -26,6 +30,7 @@ impl Number {
             Number::Int(n) => *n as f64,
             Number::Uint(n) => *n as f64,
             Number::Float(f) => *f,
+            Number::Big(s) => s.parse().unwrap_or(f64::NAN),
             Number::NaN => f64::NAN,
             Number::Infinity => f64::INFINITY,
             Number::NegInfinity => f64::NEG_INFINITY,
@@ -56,6 +61,7 @@ impl fmt::Display for Number {
         match self {
             Number::Int(n) => write!(f, "{}", n),
             Number::Uint(n) => write!(f, "{}", n),
+            Number::Big(s) => f.write_str(s),
             Number::Float(n) => {
                 if n.fract() == 0.0 && n.is_finite() {
                     write!(f, "{:.1}", n)
@@ -125,7 +131,40 @@ impl fmt::Display for Value {
     }
 }
 
+/// Largest integer every float below it represents exactly: 2^53.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
 impl Value {
+    /// Puts the value in canonical form, so equal data always serializes the
+    /// same: object keys sorted, at every level, and integers written one way
+    /// (`1.0` and an unsigned `1` both become `1`, `-0.0` becomes `0`).
+    pub fn canonicalize(&mut self) {
+        match self {
+            Value::Number(n) => {
+                *n = match *n {
+                    Number::Uint(u) if u <= i64::MAX as u64 => Number::Int(u as i64),
+                    Number::Float(f) if f.fract() == 0.0 && f.abs() < MAX_SAFE_INTEGER => Number::Int(f as i64),
+                    ref other => other.clone(),
+                }
+            },
+            Value::Array(arr) => arr.iter_mut().for_each(Value::canonicalize),
+            Value::Object(map) => {
+                map.sort_unstable_keys();
+                map.values_mut().for_each(Value::canonicalize);
+            },
+            Value::Null | Value::Bool(_) | Value::String(_) => {},
+        }
+    }
+
+    /// The timestamp a string holds, if it is one in ISO-8601 form.
+    #[allow(dead_code)]
+    pub fn as_datetime(&self) -> Option<DateTime> {
+        match self {
+            Value::String(s) => DateTime::parse(s).ok(),
+            _ => None,
+        }
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self {
             Value::Null => "null",
@@ -158,6 +197,41 @@ impl From<f64> for Value {
         Value::Number(Number::Float(f))
     }
 }
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Number(Number::Int(n as i64))
+    }
+}
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Value::Number(Number::Uint(n as u64))
+    }
+}
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Value::Number(Number::Uint(n as u64))
+    }
+}
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Self {
+        Value::Array(v.into_iter().map(Into::into).collect())
+    }
+}
+impl From<Map<String, Value>> for Value {
+    fn from(map: Map<String, Value>) -> Self {
+        Value::Object(map)
+    }
+}
+impl From<DateTime> for Value {
+    fn from(time: DateTime) -> Self {
+        Value::String(time.to_string())
+    }
+}
 impl From<String> for Value {
     fn from(s: String) -> Self {
         Value::String(s)
@@ -181,6 +255,11 @@ impl Serialize for Value {
                 Number::Int(i) => serializer.serialize_i64(*i),
                 Number::Uint(u) => serializer.serialize_u64(*u),
                 Number::Float(f) => serializer.serialize_f64(*f),
+                Number::Big(s) => match (s.parse::<i128>(), s.parse::<u128>()) {
+                    (Ok(i), _) => serializer.serialize_i128(i),
+                    (_, Ok(u)) => serializer.serialize_u128(u),
+                    _ => serializer.serialize_str(s),
+                },
                 Number::NaN => serializer.serialize_f64(f64::NAN),
                 Number::Infinity => serializer.serialize_f64(f64::INFINITY),
                 Number::NegInfinity => serializer.serialize_f64(f64::NEG_INFINITY),
@@ -0,0 +1,373 @@
+use std::fmt;
+
+use crate::http_client::ClientError;
+use crate::i18n::t;
+
+/// Process exit codes, one per failure class, so wrappers and CI scripts can
+/// branch on the kind of failure. `2` is reserved for usage errors (clap).
+pub mod exit_code {
+    pub const INTERNAL: i32 = 1;
+    pub const MANIFEST: i32 = 3;
+    pub const AUTH: i32 = 4;
+    pub const NOT_FOUND: i32 = 5;
+    pub const INTEGRITY: i32 = 6;
+    pub const REGISTRY_UNAVAILABLE: i32 = 7;
+    pub const REGISTRY_REJECTED: i32 = 8;
+    pub const SCRIPT: i32 = 9;
+    pub const PERMISSIONS: i32 = 10;
+}
+
+/// Failures the CLI knows how to classify.
+///
+/// Each variant has a stable, machine-readable code (printed as
+/// `error[<code>]`) and maps to one of the `exit_code` classes.
+#[derive(Debug)]
+pub enum VkError {
+    ManifestMissing {
+        file: String,
+    },
+    ManifestInvalid(String),
+    /// The manifest declares a `manifest_version` newer than this vk knows.
+    ManifestUnsupported {
+        version: u32,
+        supported: u32,
+    },
+    AuthRequired,
+    AuthExpired,
+    /// The credentials key is derived from a passphrase nobody entered.
+    CredentialsLocked,
+    PackageNotFound(String),
+    DependencyNotFound(String),
+    /// A dependency `install --offline` has no vendored archive for.
+    NotVendored(String),
+    /// The registry already has this version with another archive.
+    VersionExists(String),
+    /// The user may not publish to a package others own.
+    NotPublisher {
+        package: String,
+        owners: Vec<String>,
+    },
+    ChecksumMismatch {
+        package: String,
+        expected: String,
+        actual: String,
+    },
+    /// Dependency ranges no set of published versions satisfies; holds the
+    /// explanation.
+    DependencyConflict(String),
+    /// A plugin whose `engines` range excludes the host runtime or its Lua.
+    EngineMismatch {
+        package: String,
+        engine: String,
+        range: String,
+        version: String,
+    },
+    /// A package over the limits the registry accepts; holds the problems.
+    PackageLimit(String),
+    /// A deployed plugin the host reported failed, or never reported healthy.
+    DeployUnhealthy {
+        plugin: String,
+        environment: String,
+        reason: String,
+    },
+    /// The lockfile does not pin what the manifest declares.
+    LockOutOfSync {
+        file: String,
+        count: usize,
+    },
+    /// Files `vk fmt --check` would reformat.
+    Unformatted {
+        count: usize,
+    },
+    /// Lint rules set to `error` found problems in the manifest.
+    LintFailed {
+        count: usize,
+    },
+    /// A version published without provenance, when it is required.
+    ProvenanceMissing(String),
+    ProvenanceInvalid {
+        package: String,
+        reason: String,
+    },
+    /// Installed plugins whose files no longer match the lockfile.
+    IntegrityFailed(String),
+    RegistryUnreachable(String),
+    /// The registry is down for announced maintenance; holds what it said.
+    RegistryMaintenance(String),
+    RegistryError {
+        status: u16,
+        message: String,
+    },
+    RegistryRejected {
+        status: u16,
+        message: String,
+    },
+    ScriptNotFound(String),
+    ScriptFailed {
+        name: String,
+        status: Option<i32>,
+    },
+    /// Lua sources of the package that do not parse.
+    LuaSyntax(String),
+    /// Test files of the plugin that failed.
+    TestsFailed(String),
+    /// Benchmarks slower than the saved baseline allows.
+    BenchRegression(String),
+    /// A plugin upgrade asks for permissions the user did not accept.
+    PermissionsNotAccepted(String),
+    /// Plugins requesting more than the permission policy allows.
+    PolicyViolation(String),
+    /// An audit that found something, when publishing requires a clean one.
+    AuditNotClean {
+        score: u32,
+    },
+    /// A package one of the `blocked_publishers` owns.
+    PublisherBlocked {
+        package: String,
+        publisher: String,
+    },
+    /// A package whose publishers nobody trusted.
+    PublisherNotTrusted {
+        package: String,
+        publishers: String,
+    },
+    /// A package the user turned down after `install --inspect`.
+    InstallDeclined(String),
+}
+
+impl VkError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VkError::ManifestMissing { .. } => "manifest-missing",
+            VkError::ManifestInvalid(_) => "manifest-invalid",
+            VkError::ManifestUnsupported { .. } => "manifest-unsupported",
+            VkError::AuthRequired => "auth-required",
+            VkError::AuthExpired => "auth-expired",
+            VkError::CredentialsLocked => "credentials-locked",
+            VkError::PackageNotFound(_) => "package-not-found",
+            VkError::DependencyNotFound(_) => "dependency-not-found",
+            VkError::NotVendored(_) => "not-vendored",
+            VkError::VersionExists(_) => "version-exists",
+            VkError::NotPublisher { .. } => "not-publisher",
+            VkError::ChecksumMismatch { .. } => "checksum-mismatch",
+            VkError::ProvenanceMissing(_) => "provenance-missing",
+            VkError::ProvenanceInvalid { .. } => "provenance-invalid",
+            VkError::DependencyConflict(_) => "dependency-conflict",
+            VkError::EngineMismatch { .. } => "engine-mismatch",
+            VkError::LockOutOfSync { .. } => "lock-out-of-sync",
+            VkError::Unformatted { .. } => "unformatted",
+            VkError::LintFailed { .. } => "lint-failed",
+            VkError::IntegrityFailed(_) => "integrity-failed",
+            VkError::RegistryUnreachable(_) => "registry-unreachable",
+            VkError::RegistryMaintenance(_) => "registry-maintenance",
+            VkError::RegistryError { .. } => "registry-error",
+            VkError::RegistryRejected { .. } => "registry-rejected",
+            VkError::PackageLimit(_) => "package-limit",
+            VkError::DeployUnhealthy { .. } => "deploy-unhealthy",
+            VkError::ScriptNotFound(_) => "script-not-found",
+            VkError::ScriptFailed { .. } => "script-failed",
+            VkError::LuaSyntax(_) => "lua-syntax",
+            VkError::TestsFailed(_) => "tests-failed",
+            VkError::BenchRegression(_) => "bench-regression",
+            VkError::PermissionsNotAccepted(_) => "permissions-not-accepted",
+            VkError::PolicyViolation(_) => "policy-violation",
+            VkError::AuditNotClean { .. } => "audit-not-clean",
+            VkError::PublisherBlocked { .. } => "publisher-blocked",
+            VkError::PublisherNotTrusted { .. } => "publisher-not-trusted",
+            VkError::InstallDeclined(_) => "install-declined",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VkError::ManifestMissing { .. }
+            | VkError::ManifestInvalid(_)
+            | VkError::ManifestUnsupported { .. }
+            | VkError::LockOutOfSync { .. }
+            | VkError::Unformatted { .. }
+            | VkError::LintFailed { .. }
+            | VkError::DependencyConflict(_)
+            | VkError::EngineMismatch { .. }
+            | VkError::LuaSyntax(_) => exit_code::MANIFEST,
+            VkError::AuthRequired | VkError::AuthExpired | VkError::CredentialsLocked => exit_code::AUTH,
+            VkError::PackageNotFound(_) | VkError::DependencyNotFound(_) | VkError::NotVendored(_) => {
+                exit_code::NOT_FOUND
+            },
+            VkError::ChecksumMismatch { .. }
+            | VkError::IntegrityFailed(_)
+            | VkError::ProvenanceMissing(_)
+            | VkError::ProvenanceInvalid { .. } => exit_code::INTEGRITY,
+            VkError::RegistryUnreachable(_) | VkError::RegistryMaintenance(_) | VkError::RegistryError { .. } => {
+                exit_code::REGISTRY_UNAVAILABLE
+            },
+            VkError::RegistryRejected { .. }
+            | VkError::VersionExists(_)
+            | VkError::NotPublisher { .. }
+            | VkError::PackageLimit(_)
+            | VkError::DeployUnhealthy { .. } => exit_code::REGISTRY_REJECTED,
+            VkError::ScriptNotFound(_) => exit_code::NOT_FOUND,
+            VkError::ScriptFailed { .. } | VkError::TestsFailed(_) | VkError::BenchRegression(_) => exit_code::SCRIPT,
+            VkError::PermissionsNotAccepted(_)
+            | VkError::PolicyViolation(_)
+            | VkError::AuditNotClean { .. }
+            | VkError::PublisherBlocked { .. }
+            | VkError::PublisherNotTrusted { .. }
+            | VkError::InstallDeclined(_) => exit_code::PERMISSIONS,
+        }
+    }
+
+    /// Maps a failed package lookup to `PackageNotFound` when the registry
+    /// answered 404, keeping any other failure as is.
+    pub fn package_lookup(err: ClientError, package: &str) -> anyhow::Error {
+        match err {
+            ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. } => {
+                VkError::PackageNotFound(package.to_string()).into()
+            },
+            other => other.into(),
+        }
+    }
+
+    /// Classifies a registry client failure.
+    pub fn from_client_error(err: &ClientError) -> Option<Self> {
+        match err {
+            ClientError::Transport(e) if e.is_connect() || e.is_timeout() => {
+                Some(VkError::RegistryUnreachable(e.to_string()))
+            },
+            ClientError::Stalled { .. } => Some(VkError::RegistryUnreachable(err.to_string())),
+            ClientError::Unavailable(unavailable) if unavailable.maintenance => {
+                Some(VkError::RegistryMaintenance(unavailable.to_string()))
+            },
+            ClientError::Unavailable(unavailable) => {
+                Some(VkError::RegistryError { status: 503, message: unavailable.to_string() })
+            },
+            ClientError::Api { status, message, .. } | ClientError::Status { status, message } => Some(match *status {
+                401 => VkError::AuthExpired,
+                s if s >= 500 => VkError::RegistryError { status: s, message: message.clone() },
+                s => VkError::RegistryRejected { status: s, message: message.clone() },
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            VkError::ManifestMissing { file } => t!("error.manifest_missing", file = file),
+            VkError::ManifestInvalid(reason) => t!("error.manifest_invalid", reason = reason),
+            VkError::ManifestUnsupported { version, supported } => {
+                t!("error.manifest_unsupported", version = version, supported = supported)
+            },
+            VkError::AuthRequired => t!("error.auth_required"),
+            VkError::AuthExpired => t!("error.auth_expired"),
+            VkError::CredentialsLocked => t!("error.credentials_locked"),
+            VkError::PackageNotFound(package) => t!("error.package_not_found", package = package),
+            VkError::DependencyNotFound(package) => t!("error.dependency_not_found", package = package),
+            VkError::NotVendored(package) => t!("error.not_vendored", package = package),
+            VkError::VersionExists(package) => t!("error.version_exists", package = package),
+            VkError::NotPublisher { package, owners } if owners.is_empty() => {
+                t!("error.not_publisher", package = package)
+            },
+            VkError::NotPublisher { package, owners } => {
+                t!("error.not_publisher_ask", package = package, owners = owners.join(", "))
+            },
+            VkError::ChecksumMismatch { package, expected, actual } => t!(
+                "error.checksum_mismatch",
+                package = package,
+                expected = expected,
+                actual = actual
+            ),
+            VkError::ProvenanceMissing(package) => t!("error.provenance_missing", package = package),
+            VkError::ProvenanceInvalid { package, reason } => {
+                t!("error.provenance_invalid", package = package, reason = reason)
+            },
+            VkError::DependencyConflict(explanation) => t!("error.dependency_conflict", explanation = explanation),
+            VkError::EngineMismatch { package, engine, range, version } => {
+                t!(
+                    "error.engine_mismatch",
+                    package = package,
+                    engine = engine,
+                    range = range,
+                    version = version
+                )
+            },
+            VkError::LockOutOfSync { file, count } => t!("error.lock_out_of_sync", file = file, count = count),
+            VkError::Unformatted { count } => t!("error.unformatted", count = count),
+            VkError::LintFailed { count } => t!("error.lint_failed", count = count),
+            VkError::IntegrityFailed(packages) => t!("error.integrity_failed", packages = packages),
+            VkError::RegistryUnreachable(reason) => t!("error.registry_unreachable", reason = reason),
+            VkError::RegistryMaintenance(details) => t!("error.registry_maintenance", details = details),
+            VkError::RegistryError { status, message } => {
+                t!("error.registry_error", status = status, message = message)
+            },
+            VkError::RegistryRejected { status, message } => {
+                t!("error.registry_rejected", status = status, message = message)
+            },
+            VkError::PackageLimit(problems) => t!("error.package_limit", problems = problems),
+            VkError::DeployUnhealthy { plugin, environment, reason } => {
+                t!(
+                    "error.deploy_unhealthy",
+                    plugin = plugin,
+                    environment = environment,
+                    reason = reason
+                )
+            },
+            VkError::ScriptNotFound(name) => t!("error.script_not_found", name = name),
+            VkError::ScriptFailed { name, status: Some(status) } => {
+                t!("error.script_failed", name = name, status = status)
+            },
+            VkError::ScriptFailed { name, status: None } => t!("error.script_killed", name = name),
+            VkError::LuaSyntax(files) => t!("error.lua_syntax", files = files),
+            VkError::TestsFailed(files) => t!("error.tests_failed", files = files),
+            VkError::BenchRegression(files) => t!("error.bench_regression", files = files),
+            VkError::PermissionsNotAccepted(package) => t!("error.permissions_not_accepted", package = package),
+            VkError::PolicyViolation(packages) => t!("error.policy_violation", packages = packages),
+            VkError::AuditNotClean { score } => t!("error.audit_not_clean", score = score),
+            VkError::PublisherBlocked { package, publisher } => {
+                t!("error.publisher_blocked", package = package, publisher = publisher)
+            },
+            VkError::PublisherNotTrusted { package, publishers } => {
+                t!(
+                    "error.publisher_not_trusted",
+                    package = package,
+                    publishers = publishers
+                )
+            },
+            VkError::InstallDeclined(package) => t!("error.install_declined", package = package),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for VkError {}
+
+/// Renders an error with its context chain, skipping causes whose text is
+/// already part of the message (transport errors repeat their sources).
+pub fn render(err: &anyhow::Error) -> String {
+    let mut message = String::new();
+    for cause in err.chain() {
+        let text = cause.to_string();
+        if message.contains(&text) {
+            continue;
+        }
+        if !message.is_empty() {
+            message.push_str(": ");
+        }
+        message.push_str(&text);
+    }
+    message
+}
+
+/// Resolves the `(code, exit code)` pair for any error returned by a command,
+/// looking through the whole context chain.
+pub fn classify(err: &anyhow::Error) -> (&'static str, i32) {
+    for cause in err.chain() {
+        if let Some(vk) = cause.downcast_ref::<VkError>() {
+            return (vk.code(), vk.exit_code());
+        }
+        if let Some(vk) = cause.downcast_ref::<ClientError>().and_then(VkError::from_client_error) {
+            return (vk.code(), vk.exit_code());
+        }
+    }
+    ("internal", exit_code::INTERNAL)
+}
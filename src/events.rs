@@ -0,0 +1,248 @@
+use reqwest::blocking::Response;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue};
+use std::io::{BufRead, BufReader, Lines};
+use std::time::Duration;
+
+use crate::http_client::{ClientError, HttpClient};
+
+const EVENT_STREAM: &str = "text/event-stream";
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+/// Wait before reconnecting, until the server sets another with `retry:`.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+/// One server-sent event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Event type; `message` unless the server names one.
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+impl Event {
+    fn message(data: String) -> Self {
+        Self { event: "message".to_string(), data, id: None }
+    }
+}
+
+/// Events of a streaming endpoint, read with the client's base URL and
+/// credentials.
+///
+/// Endpoints answering `text/event-stream` are read as server-sent events.
+/// When the connection drops, they are reconnected to, sending the id of the
+/// last event so the server resumes after it. Any other answer is read as
+/// one `message` event per non-empty line, such as newline-delimited JSON,
+/// and ends with the response.
+pub struct EventStream<'a> {
+    client: &'a HttpClient,
+    path: String,
+    query: Vec<(String, String)>,
+    reconnect: bool,
+    lines: Option<Lines<BufReader<Response>>>,
+    sse: bool,
+    parser: Parser,
+    done: bool,
+}
+
+/// Subscribes to the events of `path`. With `reconnect`, event streams are
+/// reconnected to when they end; otherwise the events stop with the first
+/// response.
+pub fn subscribe<'a>(
+    client: &'a HttpClient,
+    path: &str,
+    query: Vec<(String, String)>,
+    reconnect: bool,
+) -> EventStream<'a> {
+    EventStream {
+        client,
+        path: path.to_string(),
+        query,
+        reconnect,
+        lines: None,
+        sse: false,
+        parser: Parser::default(),
+        done: false,
+    }
+}
+
+impl EventStream<'_> {
+    /// Opens the stream. Returns false when the server has nothing to send
+    /// (204), which per the spec means not to reconnect.
+    fn connect(&mut self) -> Result<bool, ClientError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(EVENT_STREAM));
+        if let Some(id) = self.parser.last_id.as_deref().and_then(|id| HeaderValue::from_str(id).ok()) {
+            headers.insert(LAST_EVENT_ID, id);
+        }
+
+        let response = self.client.stream(&self.path, &self.query, headers)?;
+        if response.status().as_u16() == 204 {
+            return Ok(false);
+        }
+        self.sse = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with(EVENT_STREAM));
+        self.parser.pending = Pending::default();
+        self.lines = Some(BufReader::new(response).lines());
+        Ok(true)
+    }
+
+    /// Drops the connection, to reconnect on the next read when the stream
+    /// may resume. Returns whether it will.
+    fn disconnect(&mut self) -> bool {
+        self.lines = None;
+        if self.reconnect && self.sse {
+            std::thread::sleep(self.parser.retry);
+            return true;
+        }
+        self.done = true;
+        false
+    }
+}
+
+impl Iterator for EventStream<'_> {
+    type Item = Result<Event, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if self.lines.is_none() {
+                match self.connect() {
+                    Ok(true) => {},
+                    Ok(false) => {
+                        self.done = true;
+                        return None;
+                    },
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    },
+                }
+            }
+
+            let lines = self.lines.as_mut()?;
+            match lines.next() {
+                Some(Ok(line)) if self.sse => {
+                    if let Some(event) = self.parser.feed(&line) {
+                        return Some(Ok(event));
+                    }
+                },
+                Some(Ok(line)) => {
+                    if !line.trim().is_empty() {
+                        return Some(Ok(Event::message(line)));
+                    }
+                },
+                Some(Err(err)) => {
+                    if !self.disconnect() {
+                        return Some(Err(self.client.read_error(err)));
+                    }
+                },
+                None => {
+                    self.disconnect();
+                },
+            }
+        }
+    }
+}
+
+/// The event being received.
+#[derive(Debug, Default)]
+struct Pending {
+    event: Option<String>,
+    data: Option<String>,
+}
+
+/// Server-sent events parser, fed one line at a time.
+#[derive(Debug)]
+struct Parser {
+    pending: Pending,
+    last_id: Option<String>,
+    retry: Duration,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self {
+            pending: Pending::default(),
+            last_id: None,
+            retry: DEFAULT_RETRY,
+        }
+    }
+}
+
+impl Parser {
+    /// Takes a line without its terminator. Returns the event it completes,
+    /// if any: a blank line ends an event, and events without data are
+    /// dropped.
+    fn feed(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            return pending.data.map(|data| Event {
+                event: pending.event.unwrap_or_else(|| "message".to_string()),
+                data,
+                id: self.last_id.clone(),
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.pending.event = Some(value.to_string()),
+            "data" => match &mut self.pending.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                },
+                None => self.pending.data = Some(value.to_string()),
+            },
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Duration::from_millis(millis);
+                }
+            },
+            _ => {},
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut Parser, text: &str) -> Vec<Event> {
+        text.split('\n').filter_map(|line| parser.feed(line)).collect()
+    }
+
+    #[test]
+    fn test_parser() {
+        let mut parser = Parser::default();
+        let events = feed(
+            &mut parser,
+            ": keep-alive\n\ndata: first\ndata:  second\n\nevent: publish\nid: 7\nretry: 500\ndata: {\"name\":\"hello\"}\n\nid: 8\n\n",
+        );
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            Event {
+                event: "message".into(),
+                data: "first\n second".into(),
+                id: None
+            }
+        );
+        assert_eq!(events[1].event, "publish");
+        assert_eq!(events[1].data, "{\"name\":\"hello\"}");
+        assert_eq!(events[1].id.as_deref(), Some("7"));
+        assert_eq!(parser.last_id.as_deref(), Some("8"));
+        assert_eq!(parser.retry, Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::encoding::json5;
+use crate::i18n::t;
+use crate::manifest::PluginManifest;
+
+/// File `vk install` writes next to the installed plugins, telling the host
+/// runtime which features of the project are on.
+pub const METADATA_FILENAME: &str = ".vk-features.json5";
+
+/// The features turned on for an install, and the optional dependencies
+/// they bring in.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Enabled {
+    pub features: BTreeSet<String>,
+    pub optional_dependencies: BTreeSet<String>,
+}
+
+/// Expands the requested features: a feature enables the optional
+/// dependencies and the other features it lists, and the name of an optional
+/// dependency enables that dependency alone. Fails with the first name that
+/// is neither.
+pub fn enable(manifest: &PluginManifest, requested: &[String]) -> Result<Enabled, String> {
+    let mut enabled = Enabled::default();
+    let mut pending: Vec<&str> = requested.iter().map(String::as_str).filter(|f| !f.is_empty()).collect();
+
+    while let Some(name) = pending.pop() {
+        if let Some(entries) = manifest.features.get(name) {
+            if enabled.features.insert(name.to_string()) {
+                pending.extend(entries.iter().map(String::as_str));
+            }
+        } else if manifest.optional_dependencies.contains_key(name) {
+            enabled.optional_dependencies.insert(name.to_string());
+        } else {
+            return Err(name.to_string());
+        }
+    }
+    Ok(enabled)
+}
+
+/// Writes the enabled features for the host runtime, or removes the file
+/// when none are.
+pub fn write_metadata(plugins_path: &Path, enabled: &Enabled) -> Result<()> {
+    let path = plugins_path.join(METADATA_FILENAME);
+    if enabled.features.is_empty() && enabled.optional_dependencies.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| t!("common.write_failed", path = path.display()))?;
+        }
+        return Ok(());
+    }
+    fs::write(&path, json5::to_string_pretty(enabled)?)
+        .with_context(|| t!("common.write_failed", path = path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Dependency;
+
+    fn manifest() -> PluginManifest {
+        let mut manifest = PluginManifest::default();
+        for id in ["json-utils", "tls", "metrics"] {
+            manifest.optional_dependencies.insert(id.into(), Dependency::Version("^1.0.0".into()));
+        }
+        manifest.features.insert("json".into(), vec!["json-utils".into()]);
+        manifest.features.insert("full".into(), vec!["json".into(), "tls".into(), "full".into()]);
+        manifest
+    }
+
+    #[test]
+    fn test_enable() {
+        let manifest = manifest();
+        let enabled = enable(&manifest, &["full".into()]).unwrap();
+        assert_eq!(
+            enabled.features,
+            BTreeSet::from(["full".to_string(), "json".to_string()])
+        );
+        assert_eq!(
+            enabled.optional_dependencies,
+            BTreeSet::from(["json-utils".to_string(), "tls".to_string()])
+        );
+
+        let enabled = enable(&manifest, &["metrics".into(), String::new()]).unwrap();
+        assert!(enabled.features.is_empty());
+        assert_eq!(enabled.optional_dependencies.len(), 1);
+
+        assert_eq!(enable(&manifest, &["yaml".into()]), Err("yaml".to_string()));
+    }
+}
@@ -0,0 +1,169 @@
+//! C ABI over the manifest and packaging APIs, for editor extensions and
+//! other tools that validate `plugin.json5` or preview a package without
+//! running `vk`. Built as a shared library with `make ffi`; the declarations
+//! are in `include/vayload_kit.h`.
+//!
+//! Arguments are NUL-terminated UTF-8. Every function answers a JSON document
+//! the caller frees with [`vk_string_free`]: `{"ok": true, ...}`, or
+//! `{"ok": false, "error": {"code": ..., "message": ...}}` with the codes of
+//! `vk`'s own errors. `messages` holds what the operation reported on the way,
+//! as `{"level": ..., "message": ...}`.
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use std::ffi::{CStr, CString, c_char};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use crate::commands::pack::{PackOptions, pack_plugin};
+use crate::commands::publish::{archive_spec, check_manifest, read_manifest};
+use crate::commands::validate::validate_package;
+use crate::encoding::json5;
+use crate::error::{VkError, classify, render};
+use crate::manifest::{MANIFEST_FILENAME, PluginManifest};
+use crate::project::Project;
+use crate::reporter::{CallbackReporter, Report};
+use crate::utils::package_files;
+
+/// Parses and checks a manifest, answering it as JSON under `manifest`.
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_manifest_parse(source: *const c_char) -> *mut c_char {
+    answer(|_| {
+        let source = unsafe { argument(source, "source") }?;
+        let manifest: PluginManifest = json5::from_str(source).map_err(|e| VkError::ManifestInvalid(e.to_string()))?;
+        check_manifest(&manifest)?;
+        Ok(json!({ "manifest": manifest }))
+    })
+}
+
+/// Writes a manifest given as JSON the way `vk` writes `plugin.json5`,
+/// answering the text under `source`.
+///
+/// # Safety
+///
+/// `manifest` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_manifest_serialize(manifest: *const c_char) -> *mut c_char {
+    answer(|_| {
+        let manifest = unsafe { argument(manifest, "manifest") }?;
+        let manifest: PluginManifest =
+            serde_json::from_str(manifest).map_err(|e| VkError::ManifestInvalid(e.to_string()))?;
+        Ok(json!({ "source": json5::to_string_pretty(&manifest)? }))
+    })
+}
+
+/// Runs `vk validate` on the plugin in `dir`: its manifest and the syntax of
+/// every Lua file it would package.
+///
+/// # Safety
+///
+/// `dir` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_validate(dir: *const c_char) -> *mut c_char {
+    answer(|reporter| {
+        let project = open(unsafe { argument(dir, "dir") }?)?;
+        validate_package(&project, false, reporter)?;
+        Ok(json!({}))
+    })
+}
+
+/// Lists what the package of the plugin in `dir` would contain, under
+/// `files` as `{"name": ..., "size": ...}`, without building it.
+///
+/// # Safety
+///
+/// `dir` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_pack_preview(dir: *const c_char) -> *mut c_char {
+    answer(|reporter| {
+        let project = open(unsafe { argument(dir, "dir") }?)?;
+        let manifest = read_manifest(&project)?;
+        let spec = archive_spec(&project, &manifest, false, reporter)?;
+        let files: Vec<Value> = package_files(project.root(), &spec)?
+            .iter()
+            .map(|file| json!({ "name": file.name, "size": file.size }))
+            .collect();
+        Ok(json!({ "files": files }))
+    })
+}
+
+/// Runs `vk pack` on the plugin in `dir`, writing the archive to `out`, or
+/// to `.vk/` in the plugin when `out` is null.
+///
+/// # Safety
+///
+/// `dir` and `out` must be null or point to NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_pack(dir: *const c_char, out: *const c_char) -> *mut c_char {
+    answer(|reporter| {
+        let project = open(unsafe { argument(dir, "dir") }?)?;
+        let out = if out.is_null() {
+            None
+        } else {
+            Some(unsafe { argument(out, "out") }?.to_string())
+        };
+        let options = PackOptions { out, analyze: false, list: false, allow_path_deps: false };
+        pack_plugin(&project, &options, reporter)?;
+        Ok(json!({}))
+    })
+}
+
+/// Frees an answer of this library.
+///
+/// # Safety
+///
+/// `answer` must be null or a pointer one of these functions returned, not
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vk_string_free(answer: *mut c_char) {
+    if !answer.is_null() {
+        drop(unsafe { CString::from_raw(answer) });
+    }
+}
+
+/// Runs `f` with a reporter collecting its messages and turns the outcome
+/// into the JSON answer.
+fn answer(f: impl FnOnce(&CallbackReporter) -> Result<Value>) -> *mut c_char {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let collected = messages.clone();
+    let reporter = CallbackReporter::new(move |report| {
+        let message = match report {
+            Report::Message(level, message) => json!({ "level": level.as_str(), "message": message }),
+            Report::Output(line) => json!({ "level": "output", "message": line }),
+            Report::Progress { .. } => return,
+        };
+        collected.lock().unwrap().push(message);
+    });
+
+    let mut answer = match f(&reporter) {
+        Ok(Value::Object(mut fields)) => {
+            fields.insert("ok".to_string(), Value::Bool(true));
+            Value::Object(fields)
+        },
+        Ok(other) => json!({ "ok": true, "value": other }),
+        Err(err) => json!({ "ok": false, "error": { "code": classify(&err).0, "message": render(&err) } }),
+    };
+    answer["messages"] = Value::Array(messages.lock().unwrap().drain(..).collect());
+
+    // JSON escapes control characters, so the text has no NUL
+    CString::new(answer.to_string()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// # Safety
+///
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn argument<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    if value.is_null() {
+        return Err(anyhow!("{} is null", name));
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().with_context(|| format!("{} is not UTF-8", name))
+}
+
+fn open(dir: &str) -> Result<Project> {
+    Project::discover(Path::new(dir))
+        .ok_or_else(|| VkError::ManifestMissing { file: MANIFEST_FILENAME.to_string() }.into())
+}
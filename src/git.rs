@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::i18n::t;
+use crate::manifest::GitSource;
+
+/// Runs `git` in `cwd` and returns its trimmed stdout.
+pub fn run(cwd: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(cwd).output().with_context(|| t!("git.not_available"))?;
+
+    if !output.status.success() {
+        anyhow::bail!(t!(
+            "git.command_failed",
+            command = args.join(" "),
+            stderr = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Where a package was built from, as published with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub commit: String,
+    /// Whether files of the package had uncommitted changes.
+    pub dirty: bool,
+    /// Tag pointing at the commit, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// The commit `dir` is checked out at, or `None` outside a git repository.
+/// Only changes under `dir` make it dirty, so a plugin in a monorepo is not
+/// affected by its neighbours.
+pub fn build_info(dir: &Path) -> Option<BuildInfo> {
+    let commit = run(dir, &["rev-parse", "HEAD"]).ok()?;
+    let dirty = !run(dir, &["status", "--porcelain", "--", "."]).ok()?.is_empty();
+    let tag = run(dir, &["tag", "--points-at", "HEAD", "--sort=-creatordate"])
+        .ok()
+        .and_then(|tags| tags.lines().next().map(String::from));
+    Some(BuildInfo { commit, dirty, tag })
+}
+
+/// Checks out `source` into `dest` (which must not exist) and returns the
+/// commit that was checked out.
+///
+/// `pinned` (a commit from the lockfile) takes precedence over the ref in the
+/// manifest. Only that commit is fetched when the server allows it; otherwise
+/// the full history is fetched. The `.git` directory is removed afterwards so
+/// the result looks like any other installed plugin.
+pub fn checkout(source: &GitSource, pinned: Option<&str>, dest: &Path) -> Result<String> {
+    // Whatever the source came from, nothing user-supplied is read as an option
+    let reference = pinned.or(source.reference()).unwrap_or("HEAD");
+    if let Some(value) = source.check().err().or(reference.starts_with('-').then_some(reference)) {
+        anyhow::bail!(t!("git.option_like", value = value));
+    }
+
+    fs::create_dir_all(dest).with_context(|| t!("common.write_failed", path = dest.display()))?;
+
+    run(dest, &["init", "-q"])?;
+    run(dest, &["remote", "add", "--", "origin", &source.git])?;
+
+    if run(
+        dest,
+        &["fetch", "-q", "--depth", "1", "--end-of-options", "origin", reference],
+    )
+    .is_ok()
+    {
+        run(dest, &["checkout", "-q", "FETCH_HEAD"])?;
+    } else {
+        // Abbreviated hashes and servers that refuse fetching by commit
+        run(dest, &["fetch", "-q", "--tags", "--end-of-options", "origin"])?;
+        run(dest, &["checkout", "-q", reference, "--"])?;
+    }
+
+    let commit = run(dest, &["rev-parse", "HEAD"])?;
+
+    fs::remove_dir_all(dest.join(".git")).ok();
+
+    Ok(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkout_refuses_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(repo.join("init.lua"), "return {}").unwrap();
+        run(&repo, &["init", "-q", "-b", "main"]).unwrap();
+        run(&repo, &["add", "."]).unwrap();
+        run(
+            &repo,
+            &["-c", "user.name=vk", "-c", "user.email=vk@example.com", "commit", "-qm", "init"],
+        )
+        .unwrap();
+        run(&repo, &["tag", "v1.0.0"]).unwrap();
+        let url = format!("file://{}", repo.display());
+
+        let source = |rev: &str| GitSource {
+            git: url.clone(),
+            rev: Some(rev.into()),
+            branch: None,
+            tag: None,
+        };
+        let dest = dir.path().join("tagged");
+        let commit = checkout(&source("v1.0.0"), None, &dest).unwrap();
+        assert_eq!(commit, run(&repo, &["rev-parse", "HEAD"]).unwrap());
+        assert!(dest.join("init.lua").is_file());
+
+        // Nothing is fetched, nor the destination created, for option-like values
+        let dest = dir.path().join("refused");
+        assert!(checkout(&source("--upload-pack=touch pwned"), None, &dest).is_err());
+        assert!(checkout(&source("v1.0.0"), Some("-pinned"), &dest).is_err());
+        let evil = GitSource {
+            git: "--upload-pack=touch pwned".into(),
+            rev: None,
+            branch: None,
+            tag: None,
+        };
+        assert!(checkout(&evil, None, &dest).is_err());
+        assert!(!dest.exists());
+    }
+}
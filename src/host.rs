@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::encoding::json5;
+use crate::i18n::t;
+use crate::project::Project;
+use crate::resolver;
+
+/// File at the project root naming the host runtime version to develop
+/// against, like `.nvmrc`. Meant to be committed.
+pub const PIN_FILENAME: &str = ".vayload-host";
+/// File in the hosts directory naming the version used outside pinned
+/// projects.
+const DEFAULT_FILENAME: &str = "default";
+/// File in the project's cache directory describing the host `vk host start`
+/// left running.
+const RUNNING_FILENAME: &str = "host.json5";
+
+/// A host started by `vk host start`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Running {
+    pub pid: u32,
+    pub port: u16,
+    pub runtime: String,
+}
+
+impl Running {
+    /// URL the host serves on.
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
+
+/// Path of the runtime binary of a managed `version`.
+pub fn binary_path(version: &str) -> Result<PathBuf> {
+    let name = if cfg!(windows) { "vayload.exe" } else { "vayload" };
+    Ok(config::hosts_dir()?.join(version).join(name))
+}
+
+/// Managed runtime versions, oldest first.
+pub fn installed() -> Vec<Version> {
+    let Ok(Ok(entries)) = config::hosts_dir().map(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<Version> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| resolver::parse_version(&e.file_name().to_string_lossy()))
+        .filter(|v| binary_path(&v.to_string()).is_ok_and(|path| path.is_file()))
+        .collect();
+    versions.sort();
+    versions
+}
+
+/// Where the version is pinned: the project's pin file, or the default of
+/// the hosts directory.
+pub fn pin_path(project: Option<&Project>) -> Result<PathBuf> {
+    match project {
+        Some(project) => Ok(project.root().join(PIN_FILENAME)),
+        None => Ok(config::hosts_dir()?.join(DEFAULT_FILENAME)),
+    }
+}
+
+/// The pinned version: the project's, else the default one.
+pub fn pinned(project: Option<&Project>) -> Option<String> {
+    let read = |path: Result<PathBuf>| {
+        fs::read_to_string(path.ok()?).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+    };
+    project.and_then(|p| read(pin_path(Some(p)))).or_else(|| read(pin_path(None)))
+}
+
+pub fn pin(project: Option<&Project>, version: &str) -> Result<()> {
+    let path = pin_path(project)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+    }
+    fs::write(&path, format!("{}\n", version)).with_context(|| t!("common.write_failed", path = path.display()))
+}
+
+/// The managed runtime commands of `project` run with: the pinned version
+/// when it is installed, else the newest installed one. None when there is
+/// none, and the runtime on `PATH` is used.
+pub fn managed_runtime(project: Option<&Project>) -> Option<PathBuf> {
+    if let Some(version) = pinned(project)
+        && let Ok(path) = binary_path(&version)
+        && path.is_file()
+    {
+        return Some(path);
+    }
+    installed().last().and_then(|v| binary_path(&v.to_string()).ok())
+}
+
+pub fn running_path(project: &Project) -> PathBuf {
+    project.cache_dir().join(RUNNING_FILENAME)
+}
+
+/// The host `vk host start` left running for `project`, if it says so. The
+/// process may have exited since.
+pub fn running(project: &Project) -> Option<Running> {
+    let content = fs::read_to_string(running_path(project)).ok()?;
+    json5::from_str(&content).ok()
+}
+
+/// Same as [`managed_runtime`], for the project enclosing `dir`.
+pub fn managed_runtime_at(dir: &Path) -> Option<PathBuf> {
+    managed_runtime(Project::discover(dir).as_ref())
+}
@@ -1,11 +1,20 @@
 use anyhow::{Context, Result};
-use reqwest::blocking::{Client, Response, multipart};
+use reqwest::Url;
+use reqwest::blocking::{Client, RequestBuilder, Response, multipart};
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING, HeaderMap, LINK, LOCATION, RETRY_AFTER};
+use reqwest::redirect::Policy;
+use reqwest::{Method, StatusCode};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use std::time::Duration;
-use std::{io, sync::Arc};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+use crate::http_capture::{Captured, HttpCapture};
+use crate::i18n::t;
 use crate::types::{ErrorResponse, JsonResponse};
 
 #[derive(Debug, Error)]
@@ -20,36 +29,287 @@ pub enum ClientError {
     Io(#[from] io::Error),
 
     #[error("{message}")]
-    Api { message: String, payload: Box<ErrorResponse> },
+    Api { status: u16, message: String, payload: Box<ErrorResponse> },
+
+    #[error("HTTP {status}: {message}")]
+    Status { status: u16, message: String },
+
+    #[error("Transfer stalled: no data for {seconds}s")]
+    Stalled { seconds: u64 },
+
+    /// The registry is down, for maintenance or otherwise, rather than
+    /// refusing the request.
+    #[error("{0}")]
+    Unavailable(Box<Unavailable>),
+}
+
+impl ClientError {
+    /// Whether the registry could not take the request at all: it could not
+    /// be reached, or answered 503. Such requests were not processed and can
+    /// be sent again.
+    fn is_down(&self) -> bool {
+        match self {
+            ClientError::Transport(e) => e.is_connect(),
+            ClientError::Unavailable(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// What a registry answering 503 said about its downtime. A maintenance
+/// answer is an error envelope with the code `maintenance`, whose `details`
+/// may announce when it ends (`until`) and where to follow it (`status_url`).
+#[derive(Debug, Default)]
+pub struct Unavailable {
+    pub maintenance: bool,
+    pub message: Option<String>,
+    pub until: Option<String>,
+    pub status_url: Option<String>,
+    /// From the `Retry-After` header, when given in seconds.
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = match (&self.message, self.maintenance) {
+            (Some(message), true) => t!("http.maintenance_message", message = message),
+            (None, true) => t!("http.maintenance"),
+            (Some(message), false) => t!("http.unavailable_message", message = message),
+            (None, false) => t!("http.unavailable"),
+        };
+        if let Some(until) = &self.until {
+            text.push_str(&t!("http.back_by", until = until));
+        }
+        if let Some(url) = &self.status_url {
+            text.push_str(&t!("http.status_page", url = url));
+        }
+        f.write_str(&text)
+    }
+}
+
+/// How long requests may take. Metadata requests have an overall limit;
+/// downloads and uploads have none, but fail when no data moves for `stall`.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub request: Duration,
+    pub stall: Duration,
+    /// Overall limit of an upload, so a stalled server answer does not hang
+    /// forever once the body is sent.
+    pub upload: Duration,
+}
+
+/// Bytes of an upload read so far, to tell a slow upload from a stalled one.
+#[derive(Clone, Default)]
+pub struct UploadProgress {
+    sent: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+}
+
+impl UploadProgress {
+    /// A multipart part with `data`, whose upload is tracked here.
+    pub fn part(&self, data: Vec<u8>) -> multipart::Part {
+        let length = data.len() as u64;
+        self.total.fetch_add(length, Ordering::Relaxed);
+        multipart::Part::reader_with_length(
+            TrackedReader { inner: io::Cursor::new(data), progress: self.clone() },
+            length,
+        )
+    }
+
+    fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Whether every tracked byte was handed to the connection.
+    fn is_complete(&self) -> bool {
+        self.sent() >= self.total.load(Ordering::Relaxed)
+    }
+}
+
+struct TrackedReader<R> {
+    inner: R,
+    progress: UploadProgress,
+}
+
+impl<R: Read> Read for TrackedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.sent.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
 }
 
 type AuthFn = Arc<dyn Fn() -> Option<String> + Send + Sync>;
+type MirrorFn = Arc<dyn Fn(MirrorEvent) + Send + Sync>;
+type DeprecationFn = Arc<dyn Fn(&str) + Send + Sync>;
+type WaitFn = Arc<dyn Fn(&ClientError, Duration) + Send + Sync>;
 
+/// How long to keep retrying while the registry is down, for
+/// `--wait-for-registry`, and who to tell about each retry.
+#[derive(Clone)]
+struct Wait {
+    limit: Duration,
+    notify: WaitFn,
+}
+
+/// Where a read request ended up when the registry has mirrors.
+pub enum MirrorEvent<'a> {
+    /// `from` could not answer; the request moves on to `to`.
+    Failover { from: &'a str, to: &'a str, reason: String },
+    /// `url` answered after a failover and serves the following reads.
+    Serving { url: &'a str },
+}
+
+/// Idle connections kept per host. Resolving a dependency tree makes many
+/// small requests to the registry in a row.
+const POOL_MAX_IDLE_PER_HOST: usize = 8;
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Response encodings metadata requests accept, decoded by `read_body`.
+const ACCEPTED_ENCODINGS: &str = "zstd, gzip";
+
+/// The command a registry request is made for, such as `install`.
+const COMMAND_HEADER: &str = "X-VK-Command";
+/// Set by registries that will stop serving this version of vk, with a
+/// message saying what to do.
+const DEPRECATION_HEADER: &str = "X-VK-Deprecation";
+
+const MAX_REDIRECTS: usize = 10;
+
+/// Pages a list is followed for at most, against a registry that keeps
+/// naming another.
+const MAX_PAGES: usize = 1000;
+
+/// Pause between attempts while waiting for the registry, unless its
+/// `Retry-After` asks for another within these bounds.
+const WAIT_INTERVAL: Duration = Duration::from_secs(30);
+const MIN_WAIT_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_WAIT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A package archive being downloaded.
+pub struct Download {
+    /// Headers of the registry's answer, which describe the package even when
+    /// the archive comes from another host.
+    pub headers: HeaderMap,
+    /// Response to read the archive from.
+    pub response: Response,
+}
+
+/// Registry client. Clones share one connection pool, so connections are
+/// reused by every command and by the credential refresh.
 #[derive(Clone)]
 pub struct HttpClient {
     base_url: String,
     client: Client,
     auth_fn: Option<AuthFn>,
+    timeouts: Timeouts,
+    /// Registries serving the same packages, tried in order by reads when
+    /// the primary fails.
+    mirrors: Arc<[String]>,
+    /// Index into the primary followed by the mirrors of the last endpoint
+    /// that answered, where the next read starts.
+    serving: Arc<AtomicUsize>,
+    mirror_fn: Option<MirrorFn>,
+    /// Records every request, for `--debug-http`.
+    capture: Option<Arc<HttpCapture>>,
+    command: Option<Arc<str>>,
+    deprecation_fn: Option<DeprecationFn>,
+    deprecation_shown: Arc<AtomicBool>,
+    wait: Option<Wait>,
 }
 
 impl HttpClient {
-    pub fn new(base_url: impl Into<String>) -> Result<Self> {
-        let client =
-            Client::builder().timeout(Duration::from_secs(240)).build().context("Failed to build HTTP client")?;
-
-        Ok(Self { base_url: base_url.into(), client, auth_fn: None })
+    pub fn new(base_url: impl Into<String>, timeouts: Timeouts) -> Result<Self> {
+        Ok(Self {
+            base_url: base_url.into(),
+            client: Self::build_client(&timeouts)?,
+            auth_fn: None,
+            timeouts,
+            mirrors: Arc::from([]),
+            serving: Arc::default(),
+            mirror_fn: None,
+            capture: None,
+            command: None,
+            deprecation_fn: None,
+            deprecation_shown: Arc::default(),
+            wait: None,
+        })
     }
 
-    #[allow(dead_code)]
-    pub fn new_with_token(base_url: impl Into<String>, token: String) -> Result<Self> {
-        let client =
-            Client::builder().timeout(Duration::from_secs(240)).build().context("Failed to build HTTP client")?;
+    pub fn new_with_token(base_url: impl Into<String>, token: String, timeouts: Timeouts) -> Result<Self> {
+        let client = Self::build_client(&timeouts)?;
 
         let token = Arc::new(token);
         let token_clone = token.clone();
         let auth_fn: AuthFn = Arc::new(move || Some(token_clone.to_string()));
 
-        Ok(Self { base_url: base_url.into(), client, auth_fn: Some(auth_fn) })
+        Ok(Self {
+            base_url: base_url.into(),
+            client,
+            auth_fn: Some(auth_fn),
+            timeouts,
+            mirrors: Arc::from([]),
+            serving: Arc::default(),
+            mirror_fn: None,
+            capture: None,
+            command: None,
+            deprecation_fn: None,
+            deprecation_shown: Arc::default(),
+            wait: None,
+        })
+    }
+
+    /// The client-wide timeout bounds each wait of a blocking request: for
+    /// the response, then for every read of its body. Requests without their
+    /// own limit therefore fail only when data stops coming.
+    ///
+    /// HTTP/2 is negotiated through ALPN when the registry offers it.
+    fn build_client(timeouts: &Timeouts) -> Result<Client> {
+        Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.stall)
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .http2_adaptive_window(true)
+            .redirect(Self::redirect_policy())
+            .user_agent(user_agent())
+            .build()
+            .context("Failed to build HTTP client")
+    }
+
+    /// Follows redirects, except those of download endpoints: `download`
+    /// follows them itself so the registry's headers are not lost.
+    fn redirect_policy() -> Policy {
+        Policy::custom(|attempt| {
+            if attempt.previous().last().is_some_and(|url| url.path().ends_with("/download")) {
+                attempt.stop()
+            } else if attempt.previous().len() > MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        })
+    }
+
+    /// A client sharing this one's connections whose metadata requests may
+    /// take up to `timeout`.
+    pub fn with_request_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.timeouts.request = timeout;
+        client
+    }
+
+    /// Registry URL requests are made against.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// A copy sending no credentials, for the requests that obtain them.
+    pub fn without_auth(&self) -> Self {
+        Self { auth_fn: None, ..self.clone() }
     }
 
     pub fn set_auth_fn<F>(&mut self, f: F)
@@ -59,7 +319,162 @@ impl HttpClient {
         self.auth_fn = Some(Arc::new(f));
     }
 
-    fn with_auth(&self, rb: reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder {
+    /// Sets the mirrors reads fall back to. Writes always go to the primary.
+    pub fn set_mirrors(&mut self, mirrors: Vec<String>) {
+        self.mirrors = mirrors.into();
+        self.serving = Arc::default();
+    }
+
+    /// Called when a read fails over to a mirror.
+    pub fn set_mirror_fn<F>(&mut self, f: F)
+    where
+        F: Fn(MirrorEvent) + Send + Sync + 'static,
+    {
+        self.mirror_fn = Some(Arc::new(f));
+    }
+
+    /// Names the command requests to the registry are made for, sent as
+    /// `X-VK-Command` so registry operators can tell them apart.
+    pub fn set_command(&mut self, command: &str) {
+        self.command = Some(Arc::from(command));
+    }
+
+    /// Called with the `X-VK-Deprecation` notice the registry answers with,
+    /// once per command, when it announces the end of support for this vk.
+    pub fn set_deprecation_fn<F>(&mut self, f: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.deprecation_fn = Some(Arc::new(f));
+    }
+
+    /// Retries requests the registry could not take, because it is down or
+    /// under maintenance, for up to `limit`. `notify` is told why before each
+    /// pause.
+    pub fn set_wait<F>(&mut self, limit: Duration, notify: F)
+    where
+        F: Fn(&ClientError, Duration) + Send + Sync + 'static,
+    {
+        self.wait = Some(Wait { limit, notify: Arc::new(notify) });
+    }
+
+    /// Records the requests of this client and its clones in `capture`.
+    pub fn set_capture(&mut self, capture: Arc<HttpCapture>) {
+        self.capture = Some(capture);
+    }
+
+    /// Sends `request`, built on `client`, recording it when the requests are
+    /// captured. Every request goes out through here.
+    fn execute(&self, client: &Client, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = match &self.capture {
+            None => request.send()?,
+            Some(capture) => {
+                let request = request.build()?;
+                let entry = capture.request(&request);
+                let started = Instant::now();
+                let result = client.execute(request);
+                capture.response(entry, started.elapsed(), &result);
+
+                let mut response = result?;
+                response.extensions_mut().insert(Captured { capture: capture.clone(), entry });
+                response
+            },
+        };
+
+        if let Some(f) = &self.deprecation_fn
+            && let Some(notice) = response.headers().get(DEPRECATION_HEADER).and_then(|v| v.to_str().ok())
+            && !self.deprecation_shown.swap(true, Ordering::Relaxed)
+        {
+            f(notice.trim());
+        }
+        Ok(response)
+    }
+
+    /// Sends a read to the endpoint that last answered, then to the others in
+    /// order while they are unreachable or fail with a 5xx, until one is up.
+    fn read(
+        &self,
+        path: &str,
+        attempt: impl Fn(String) -> Result<Response, ClientError>,
+    ) -> Result<Response, ClientError> {
+        self.until_up(|| self.try_endpoints(path, &attempt))
+    }
+
+    fn try_endpoints(
+        &self,
+        path: &str,
+        attempt: &impl Fn(String) -> Result<Response, ClientError>,
+    ) -> Result<Response, ClientError> {
+        let endpoints: Vec<&str> =
+            std::iter::once(self.base_url.as_str()).chain(self.mirrors.iter().map(String::as_str)).collect();
+        let first = self.serving.load(Ordering::Relaxed).min(endpoints.len() - 1);
+        let mut failed_over = false;
+
+        for step in 0..endpoints.len() {
+            let index = (first + step) % endpoints.len();
+            let result = attempt(Self::join(endpoints[index], path));
+
+            let reason = match &result {
+                Err(ClientError::Transport(e)) if e.is_connect() || e.is_timeout() => e.to_string(),
+                Ok(response) if response.status().is_server_error() => response.status().to_string(),
+                _ => {
+                    if failed_over && let Some(f) = &self.mirror_fn {
+                        f(MirrorEvent::Serving { url: endpoints[index] });
+                    }
+                    self.serving.store(index, Ordering::Relaxed);
+                    return result;
+                },
+            };
+
+            if step + 1 == endpoints.len() {
+                return result;
+            }
+            if let Some(f) = &self.mirror_fn {
+                let to = endpoints[(index + 1) % endpoints.len()];
+                f(MirrorEvent::Failover { from: endpoints[index], to, reason });
+            }
+            failed_over = true;
+        }
+
+        unreachable!("there is always a primary endpoint")
+    }
+
+    /// Makes `attempt` again while the registry is down, with pauses, for as
+    /// long as `set_wait` allows. Without it, the first answer is returned.
+    fn until_up(&self, attempt: impl Fn() -> Result<Response, ClientError>) -> Result<Response, ClientError> {
+        let Some(wait) = &self.wait else {
+            return attempt();
+        };
+        let deadline = Instant::now() + wait.limit;
+        loop {
+            let err = match attempt() {
+                Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => {
+                    Self::error_from_response(response)
+                },
+                Err(err) if err.is_down() => err,
+                other => return other,
+            };
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(err);
+            }
+            let retry_after = match &err {
+                ClientError::Unavailable(unavailable) => unavailable.retry_after,
+                _ => None,
+            };
+            let delay = retry_after.unwrap_or(WAIT_INTERVAL).clamp(MIN_WAIT_INTERVAL, MAX_WAIT_INTERVAL).min(left);
+            (wait.notify)(&err, delay);
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Adds what requests to the registry carry: the command they are made
+    /// for and the credentials.
+    fn for_registry(&self, rb: reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder {
+        let rb = match &self.command {
+            Some(command) => rb.header(COMMAND_HEADER, command.as_ref()),
+            None => rb,
+        };
         if let Some(auth_fn) = &self.auth_fn
             && let Some(token) = auth_fn()
         {
@@ -68,34 +483,132 @@ impl HttpClient {
         rb
     }
 
-    pub fn get_raw(&self, path: &str) -> Result<Response, ClientError> {
-        let request = self.client.get(self.url(path));
-        let request = self.with_auth(request);
+    /// Sends a metadata request, bounded by the request timeout. The answer
+    /// may come compressed.
+    fn send(&self, rb: reqwest::blocking::RequestBuilder) -> Result<Response, ClientError> {
+        let rb = rb.header(ACCEPT_ENCODING, ACCEPTED_ENCODINGS).timeout(self.timeouts.request);
+        self.execute(&self.client, self.for_registry(rb))
+    }
 
-        let response = request.send()?;
-        let status = response.status();
+    /// Starts a download. When the registry redirects to storage, the
+    /// archive is fetched from there. Reading the body has no overall limit;
+    /// use `read_error` to report a read that timed out as a stall.
+    pub fn download(&self, path: &str) -> Result<Download, ClientError> {
+        let response = self.read(path, |url| {
+            self.execute(&self.client, self.for_registry(self.client.get(url)))
+        })?;
+        let headers = response.headers().clone();
 
-        if status.is_success() {
-            Ok(response)
+        let location = response.headers().get(LOCATION).and_then(|v| v.to_str().ok());
+        let response = match location {
+            Some(location) if response.status().is_redirection() => {
+                let target = response.url().join(location).map_err(io::Error::other)?;
+                self.fetch(target.as_str())?
+            },
+            _ => Self::check_status(response)?,
+        };
+
+        Ok(Download { headers, response })
+    }
+
+    /// Opens a response read as it arrives, such as logs being followed.
+    /// Only connecting is bounded: the server may stay silent for as long as
+    /// it likes between lines.
+    pub fn stream(&self, path: &str, query: &[(String, String)], headers: HeaderMap) -> Result<Response, ClientError> {
+        let client = Client::builder()
+            .connect_timeout(self.timeouts.connect)
+            .timeout(None)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()?;
+        let request = self.for_registry(client.get(Self::join(&self.base_url, path)).query(query).headers(headers));
+        Self::check_status(self.execute(&client, request)?)
+    }
+
+    /// A request to storage the registry's packages live in, such as the
+    /// bucket of an S3 registry. It shares the connections but carries no
+    /// registry credentials, and only data that stops moving times it out.
+    pub fn storage(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Sends a request made with `storage`.
+    pub fn send_storage(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        self.execute(&self.client, request)
+    }
+
+    /// Downloads from `url`, a location the registry handed out. The
+    /// credentials only go to the registry and its mirrors, never to storage
+    /// or CDN hosts.
+    pub fn fetch(&self, url: &str) -> Result<Response, ClientError> {
+        let request = self.client.get(url);
+        let request = if self.is_registry(url) {
+            self.for_registry(request)
         } else {
-            let body = response.text()?;
+            request
+        };
+        Self::check_status(self.execute(&self.client, request)?)
+    }
 
-            let parsed: ErrorResponse = serde_json::from_str(&body).map_err(ClientError::Serialization)?;
+    fn is_registry(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+        std::iter::once(&self.base_url)
+            .chain(self.mirrors.iter())
+            .filter_map(|endpoint| Url::parse(endpoint).ok())
+            .any(|endpoint| endpoint.origin() == url.origin())
+    }
 
-            Err(ClientError::Api {
-                message: parsed.error.message.clone(),
-                payload: Box::new(parsed),
-            })
+    fn check_status(response: Response) -> Result<Response, ClientError> {
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(Self::error_from_response(response))
         }
     }
 
+    /// The items of the list at `path`, fetched a page at a time as they are
+    /// iterated.
+    pub fn get_paginated<T>(&self, path: &str) -> Pages<'_, T>
+    where
+        T: DeserializeOwned,
+    {
+        Pages {
+            client: self,
+            first: Some(path.to_string()),
+            next: None,
+            items: Vec::new().into_iter(),
+            fetched: 0,
+        }
+    }
+
+    /// Every item of the list at `path`, across all its pages.
+    pub fn get_all<T>(&self, path: &str) -> Result<Vec<T>, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        self.get_paginated(path).collect()
+    }
+
+    /// Gets `url`, a full URL the registry handed out, such as the next page
+    /// of a list. Credentials only go to the registry and its mirrors.
+    fn get_url(&self, url: &str) -> Result<Response, ClientError> {
+        self.until_up(|| {
+            let request = self.client.get(url);
+            if self.is_registry(url) {
+                self.send(request)
+            } else {
+                let request = request.header(ACCEPT_ENCODING, ACCEPTED_ENCODINGS).timeout(self.timeouts.request);
+                self.execute(&self.client, request)
+            }
+        })
+    }
+
     pub fn get<T>(&self, path: &str) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
     {
-        let request = self.client.get(self.url(path));
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.read(path, |url| self.send(self.client.get(url)))?;
 
         Self::parse_json(response)
     }
@@ -105,46 +618,102 @@ impl HttpClient {
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.post(self.url(path)).json(body);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.post(self.url(path)).json(body)))?;
 
         Self::parse_json(response)
     }
 
+    /// Posts `body` as JSON to `url`, a service outside the registry such as
+    /// a webhook. No credentials are sent, and the answer is not read.
+    pub fn post_url<B>(&self, url: &str, body: &B) -> Result<(), ClientError>
+    where
+        B: Serialize,
+    {
+        let request = self.client.post(url).json(body).timeout(self.timeouts.request);
+        let response = self.execute(&self.client, request)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = Self::read_body(response)?;
+        Err(ClientError::Status {
+            status: status.as_u16(),
+            message: body.chars().take(200).collect(),
+        })
+    }
+
+    /// Posts `form` to `url`, a service outside the registry such as an
+    /// identity provider, and parses its JSON answer. No credentials are sent.
+    pub fn post_form_url<T, B>(&self, url: &str, form: &B) -> Result<T, ClientError>
+    where
+        T: DeserializeOwned,
+        B: Serialize,
+    {
+        let request = self.client.post(url).form(form).timeout(self.timeouts.request);
+        Self::parse_json(self.execute(&self.client, request)?)
+    }
+
     #[allow(dead_code)]
     pub fn post_form<T, B>(&self, path: &str, form: &B) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.post(self.url(path)).form(form);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.post(self.url(path)).form(form)))?;
 
         Self::parse_json(response)
     }
 
-    pub fn post_multipart<T>(&self, path: &str, form: multipart::Form) -> Result<T, ClientError>
+    /// Uploads `form`, failing when the parts tracked by `progress` stop
+    /// being read for the stall timeout. Once they are sent, the answer only
+    /// has to come within the upload timeout. The request runs on its own
+    /// thread so a stall can be noticed while it blocks.
+    pub fn post_multipart<T>(
+        &self,
+        path: &str,
+        form: multipart::Form,
+        headers: HeaderMap,
+        progress: &UploadProgress,
+    ) -> Result<T, ClientError>
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + Send + 'static,
     {
-        let request = self.client.post(self.url(path)).multipart(form);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let request = self.client.post(self.url(path)).headers(headers).multipart(form).timeout(self.timeouts.upload);
+        let request = self.for_registry(request);
 
-        Self::parse_json(response)
+        let (tx, rx) = mpsc::channel();
+        let sender = self.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(sender.execute(&sender.client, request).and_then(Self::parse_json));
+        });
+
+        let mut sent = progress.sent();
+        let mut last_progress = Instant::now();
+        loop {
+            match rx.recv_timeout(Duration::from_millis(250)) {
+                Ok(result) => return result,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(ClientError::Io(io::Error::other("upload thread exited")));
+                },
+                Err(mpsc::RecvTimeoutError::Timeout) => {},
+            }
+
+            let now = progress.sent();
+            if now != sent {
+                sent = now;
+                last_progress = Instant::now();
+            } else if !progress.is_complete() && last_progress.elapsed() >= self.timeouts.stall {
+                return Err(ClientError::Stalled { seconds: self.timeouts.stall.as_secs() });
+            }
+        }
     }
 
-    #[allow(dead_code)]
     pub fn put<T, B>(&self, path: &str, body: &B) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.put(self.url(path)).json(body);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.put(self.url(path)).json(body)))?;
 
         Self::parse_json(response)
     }
@@ -155,9 +724,7 @@ impl HttpClient {
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.put(self.url(path)).form(form);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.put(self.url(path)).form(form)))?;
 
         Self::parse_json(response)
     }
@@ -168,9 +735,7 @@ impl HttpClient {
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.patch(self.url(path)).json(body);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.patch(self.url(path)).json(body)))?;
 
         Self::parse_json(response)
     }
@@ -181,58 +746,306 @@ impl HttpClient {
         T: DeserializeOwned,
         B: Serialize,
     {
-        let request = self.client.patch(self.url(path)).form(form);
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.patch(self.url(path)).form(form)))?;
 
         Self::parse_json(response)
     }
 
-    #[allow(dead_code)]
     pub fn delete<T>(&self, path: &str) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
     {
-        let request = self.client.delete(self.url(path));
-        let request = self.with_auth(request);
-        let response = request.send()?;
+        let response = self.until_up(|| self.send(self.client.delete(self.url(path))))?;
 
         Self::parse_json(response)
     }
 
+    /// Classifies an error reading a download body.
+    pub fn read_error(&self, err: io::Error) -> ClientError {
+        let timed_out = err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+            .is_some_and(|e| e.is_timeout());
+        if timed_out || err.kind() == io::ErrorKind::TimedOut {
+            ClientError::Stalled { seconds: self.timeouts.stall.as_secs() }
+        } else {
+            ClientError::Io(err)
+        }
+    }
+
     fn url(&self, path: &str) -> String {
-        format!(
-            "{}/{}",
-            self.base_url.trim_end_matches('/'),
-            path.trim_start_matches('/')
-        )
+        Self::join(&self.base_url, path)
+    }
+
+    fn join(base_url: &str, path: &str) -> String {
+        format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'))
     }
 
-    fn parse_json<T>(response: Response) -> Result<T, ClientError>
+    /// Parses a JSON answer, unwrapping the registry's `data` envelope.
+    pub fn parse_json<T>(response: Response) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
     {
-        let status = response.status();
-        let body = response.text()?;
+        let response = Self::check_status(response)?;
+        let body = Self::read_body(response)?;
 
-        if status.is_success() {
-            if let Ok(wrapped) = serde_json::from_str::<JsonResponse<T>>(&body) {
-                return Ok(wrapped.data);
-            }
+        if let Ok(wrapped) = serde_json::from_str::<JsonResponse<T>>(&body) {
+            return Ok(wrapped.data);
+        }
 
-            if let Ok(direct) = serde_json::from_str::<T>(&body) {
-                return Ok(direct);
-            }
+        if let Ok(direct) = serde_json::from_str::<T>(&body) {
+            return Ok(direct);
+        }
 
-            let data = serde_json::from_str::<T>(&body).map_err(ClientError::Serialization)?;
+        let data = serde_json::from_str::<T>(&body).map_err(ClientError::Serialization)?;
 
-            Ok(data)
-        } else {
-            let parsed: ErrorResponse = serde_json::from_str(&body)?;
-            Err(ClientError::Api {
+        Ok(data)
+    }
+
+    /// Reads a response body as text, undoing its `Content-Encoding`.
+    fn read_body(response: Response) -> Result<String, ClientError> {
+        let captured = response.extensions().get::<Captured>().cloned();
+        let encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+        let raw = response.bytes()?;
+        let body = decode(encoding.as_deref(), &raw)?;
+        if let Some(Captured { capture, entry }) = captured {
+            capture.body(entry, &body);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Builds the error for a non-success response, reading its body.
+    fn error_from_response(response: Response) -> ClientError {
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        match Self::read_body(response) {
+            Ok(body) => Self::error_from_body(status, retry_after, body),
+            Err(err) => err,
+        }
+    }
+
+    /// Registry errors are JSON envelopes; anything else (proxies, load
+    /// balancers) keeps the raw body. A 503 or a `maintenance` error means
+    /// the registry is down rather than refusing the request.
+    fn error_from_body(status: u16, retry_after: Option<Duration>, body: String) -> ClientError {
+        let parsed = serde_json::from_str::<ErrorResponse>(&body).ok();
+        let maintenance = parsed.as_ref().is_some_and(|p| p.error.code == "maintenance");
+
+        if status == StatusCode::SERVICE_UNAVAILABLE.as_u16() || maintenance {
+            let details = parsed.as_ref().and_then(|p| p.error.details.as_ref());
+            let detail = |name: &str| details.and_then(|d| d.get(name)).and_then(|v| v.as_str()).map(str::to_string);
+            return ClientError::Unavailable(Box::new(Unavailable {
+                maintenance,
+                until: detail("until"),
+                status_url: detail("status_url"),
+                message: parsed.map(|p| p.error.message),
+                retry_after,
+            }));
+        }
+
+        match parsed {
+            Some(parsed) => ClientError::Api {
+                status,
                 message: parsed.error.message.clone(),
                 payload: Box::new(parsed),
-            })
+            },
+            None => ClientError::Status { status, message: body.chars().take(200).collect() },
         }
     }
 }
+
+/// Items of a list endpoint, from `HttpClient::get_paginated`. A page is a
+/// JSON array, bare or in the `data` envelope. The next one is named by a
+/// `Link` header with `rel="next"` or by the envelope's `meta.next_cursor`,
+/// sent back as the `cursor` query parameter. Iteration ends after the first
+/// error.
+pub struct Pages<'a, T> {
+    client: &'a HttpClient,
+    /// Path of the first page, until it is fetched.
+    first: Option<String>,
+    next: Option<Url>,
+    items: std::vec::IntoIter<T>,
+    fetched: usize,
+}
+
+impl<T: DeserializeOwned> Iterator for Pages<'_, T> {
+    type Item = Result<T, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.items.next() {
+                return Some(Ok(item));
+            }
+
+            let client = self.client;
+            let response = match self.first.take() {
+                Some(path) => client.read(&path, |url| client.send(client.client.get(url))),
+                None if self.fetched >= MAX_PAGES => return None,
+                None => client.get_url(self.next.take()?.as_str()),
+            };
+            self.fetched += 1;
+
+            match response.and_then(Self::page) {
+                Ok((items, next)) => {
+                    self.items = items.into_iter();
+                    self.next = next;
+                },
+                Err(err) => {
+                    self.next = None;
+                    return Some(Err(err));
+                },
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Pages<'_, T> {
+    /// The items of a page, and the URL of the next one.
+    fn page(response: Response) -> Result<(Vec<T>, Option<Url>), ClientError> {
+        let response = HttpClient::check_status(response)?;
+        let url = response.url().clone();
+        let link = next_link(response.headers()).and_then(|link| url.join(&link).ok());
+        let body = HttpClient::read_body(response)?;
+
+        let (items, next) = match serde_json::from_str::<JsonResponse<Vec<T>>>(&body) {
+            Ok(page) => {
+                let cursor = page.meta.and_then(|meta| meta.next_cursor).filter(|cursor| !cursor.is_empty());
+                (
+                    page.data,
+                    link.or_else(|| cursor.map(|cursor| with_cursor(&url, &cursor))),
+                )
+            },
+            Err(_) => (serde_json::from_str::<Vec<T>>(&body)?, link),
+        };
+        // A registry naming the same page again would never end
+        Ok((items, next.filter(|next| *next != url)))
+    }
+}
+
+/// The `rel="next"` target of a `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';');
+            let target = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+            parts
+                .any(|param| {
+                    param
+                        .trim()
+                        .strip_prefix("rel=")
+                        .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|r| r == "next"))
+                })
+                .then(|| target.to_string())
+        })
+}
+
+/// `url` asking for the page after `cursor`.
+fn with_cursor(url: &Url, cursor: &str) -> Url {
+    let mut next = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != "cursor")
+        .map(|(n, v)| (n.into_owned(), v.into_owned()))
+        .collect();
+    next.query_pairs_mut().clear().extend_pairs(pairs).append_pair("cursor", cursor);
+    next
+}
+
+/// `vk/<version> (<os>; <arch>)`, sent with every request.
+fn user_agent() -> String {
+    format!(
+        "vk/{} ({}; {})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )
+}
+
+/// Decompresses a body sent with `encoding`. Unknown encodings are passed
+/// through.
+fn decode(encoding: Option<&str>, raw: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        Some("gzip" | "x-gzip") => {
+            let mut body = Vec::new();
+            flate2::read::GzDecoder::new(raw).read_to_end(&mut body)?;
+            Ok(body)
+        },
+        Some("zstd") => zstd::decode_all(raw),
+        _ => Ok(raw.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_decode() {
+        let json = br#"{"data":{"name":"hello"}}"#;
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(json).unwrap();
+        assert_eq!(decode(Some("gzip"), &gzip.finish().unwrap()).unwrap(), json);
+
+        let zstd = zstd::encode_all(&json[..], 0).unwrap();
+        assert_eq!(decode(Some("zstd"), &zstd).unwrap(), json);
+
+        assert_eq!(decode(None, json).unwrap(), json);
+        assert!(decode(Some("gzip"), json).is_err());
+    }
+
+    #[test]
+    fn test_unavailable() {
+        let body = r#"{"error":{"code":"maintenance","message":"Database upgrade","details":{"until":"14:00 UTC","status_url":"https://status.example.com"}}}"#;
+        let err = HttpClient::error_from_body(503, Some(Duration::from_secs(120)), body.to_string());
+        let ClientError::Unavailable(unavailable) = &err else {
+            panic!("{:?}", err);
+        };
+        assert!(unavailable.maintenance && err.is_down());
+        assert_eq!(unavailable.until.as_deref(), Some("14:00 UTC"));
+        assert_eq!(unavailable.retry_after, Some(Duration::from_secs(120)));
+
+        // A load balancer page still means the registry is down
+        let err = HttpClient::error_from_body(503, None, "<html>Service Unavailable</html>".to_string());
+        assert!(matches!(&err, ClientError::Unavailable(u) if !u.maintenance && u.message.is_none()));
+
+        let err = HttpClient::error_from_body(400, None, r#"{"error":{"code":"invalid","message":"bad"}}"#.to_string());
+        assert!(matches!(err, ClientError::Api { status: 400, .. }) && !err.is_down());
+    }
+
+    #[test]
+    fn test_next_page() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            r#"<https://r.example.com/packages?page=1>; rel="prev", <https://r.example.com/packages?page=3>; rel="next""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            next_link(&headers).as_deref(),
+            Some("https://r.example.com/packages?page=3")
+        );
+        assert_eq!(next_link(&HeaderMap::new()), None);
+
+        let url = Url::parse("https://r.example.com/packages/x/versions?limit=50&cursor=old").unwrap();
+        assert_eq!(
+            with_cursor(&url, "a+b/c=").as_str(),
+            "https://r.example.com/packages/x/versions?limit=50&cursor=a%2Bb%2Fc%3D"
+        );
+    }
+}
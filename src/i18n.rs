@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::encoding::json5;
+
+/// Message catalogs shipped with the binary, keyed by `<area>.<message>`.
+const EN_CATALOG: &str = include_str!("../locales/en.json5");
+const ES_CATALOG: &str = include_str!("../locales/es.json5");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Es,
+}
+
+impl Locale {
+    /// Parses values like `es`, `es_AR.UTF-8` or `en-US`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let lang = value.split(['_', '-', '.']).next()?.to_ascii_lowercase();
+        match lang.as_str() {
+            "en" => Some(Locale::En),
+            "es" => Some(Locale::Es),
+            _ => None,
+        }
+    }
+
+    /// Locale requested by the environment (`LC_ALL`, `LC_MESSAGES`, then `LANG`).
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Locale::parse(&value))
+    }
+
+    fn catalog(&self) -> &'static HashMap<String, String> {
+        static EN: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static ES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+        match self {
+            Locale::En => EN.get_or_init(|| load_catalog(EN_CATALOG)),
+            Locale::Es => ES.get_or_init(|| load_catalog(ES_CATALOG)),
+        }
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_locale(locale: Locale) {
+    CURRENT.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn current_locale() -> Locale {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Locale::Es,
+        _ => Locale::En,
+    }
+}
+
+fn load_catalog(source: &str) -> HashMap<String, String> {
+    json5::from_str(source).expect("embedded message catalog must be valid JSON5")
+}
+
+/// Looks up `key` in the active catalog (falling back to English, then to the
+/// key itself) and replaces `{name}` placeholders with the given arguments.
+pub fn translate(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let template = current_locale()
+        .catalog()
+        .get(key)
+        .or_else(|| Locale::En.catalog().get(key))
+        .map(String::as_str)
+        .unwrap_or(key);
+
+    // One pass, so braces inside the values are never taken for placeholders
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let arg = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter().find(|(arg, _)| *arg == name).map(|(_, value)| (end, value))
+        });
+        match arg {
+            Some((end, value)) => {
+                message.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            },
+            None => {
+                message.push('{');
+                rest = after;
+            },
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// Translates a catalog key: `t!("install.title", id = name)`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::translate($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+}
+
+pub use crate::t;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_placeholders() {
+        // Keys missing from the catalogs are their own template
+        assert_eq!(translate("{a} and {b}", &[("a", &"{b}"), ("b", &1)]), "{b} and 1");
+        assert_eq!(translate("{a} {unknown} {", &[("a", &"x")]), "x {unknown} {");
+        assert_eq!(translate("{a}{a}", &[("a", &"ñ")]), "ññ");
+    }
+}
@@ -0,0 +1,172 @@
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Ignore rules with `.gitignore` semantics.
+///
+/// Rules are checked in the order they were added and the last match wins, so
+/// `!pattern` can re-include what an earlier rule excluded. Patterns with a
+/// slash before the end are anchored to the directory of the file that
+/// declared them; others match at any depth below it. A trailing slash
+/// restricts a rule to directories.
+#[derive(Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    /// Directory the rule was declared in, relative to the walk root, with a
+    /// trailing `/` (empty for the root).
+    base: String,
+    matcher: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRules {
+    /// Adds the rules of an ignore file found in `base` (relative to the walk
+    /// root, `""` for the root itself). Missing files are skipped.
+    pub fn add_file(&mut self, path: &Path, base: &str) {
+        if let Ok(content) = read_to_string(path) {
+            for line in content.lines() {
+                self.add_line(line, base);
+            }
+        }
+    }
+
+    /// Adds one line of an ignore file. Blank lines and comments are skipped.
+    pub fn add_line(&mut self, line: &str, base: &str) {
+        let line = line.trim_end_matches(['\r', ' ']);
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return;
+        }
+
+        // A slash anywhere but the end anchors the pattern to `base`
+        let glob = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+
+        let Ok(glob) = GlobBuilder::new(&glob).literal_separator(true).backslash_escape(true).build() else {
+            return;
+        };
+
+        let base = if base.is_empty() || base.ends_with('/') {
+            base.to_string()
+        } else {
+            format!("{}/", base)
+        };
+
+        self.rules.push(Rule { base, matcher: glob.compile_matcher(), negated, dir_only });
+    }
+
+    /// Whether `path` (relative to the walk root, `/`-separated) is ignored.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.decision(path, is_dir).unwrap_or(false)
+    }
+
+    /// The verdict of the last rule matching `path`, or `None` when no rule
+    /// matches it.
+    pub fn decision(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let mut decision = None;
+
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let Some(relative) = path.strip_prefix(rule.base.as_str()) else {
+                continue;
+            };
+            if rule.matcher.is_match(relative) {
+                decision = Some(!rule.negated);
+            }
+        }
+
+        decision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(lines: &[&str]) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        for line in lines {
+            rules.add_line(line, "");
+        }
+        rules
+    }
+
+    #[test]
+    fn test_unanchored_patterns_match_at_any_depth() {
+        let rules = rules(&["*.log", "build"]);
+        assert!(rules.is_ignored("debug.log", false));
+        assert!(rules.is_ignored("src/deep/debug.log", false));
+        assert!(rules.is_ignored("src/build", true));
+        assert!(!rules.is_ignored("src/main.lua", false));
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let rules = rules(&["/dist", "docs/*.md"]);
+        assert!(rules.is_ignored("dist", true));
+        assert!(!rules.is_ignored("src/dist", true));
+        assert!(rules.is_ignored("docs/intro.md", false));
+        assert!(!rules.is_ignored("docs/api/intro.md", false));
+        assert!(!rules.is_ignored("src/docs/intro.md", false));
+    }
+
+    #[test]
+    fn test_directory_only_rules() {
+        let rules = rules(&["cache/"]);
+        assert!(rules.is_ignored("cache", true));
+        assert!(rules.is_ignored("src/cache", true));
+        assert!(!rules.is_ignored("cache", false));
+    }
+
+    #[test]
+    fn test_negation_last_match_wins() {
+        let rules = rules(&["*.lua", "!init.lua", "# comment", "\\!bang"]);
+        assert!(rules.is_ignored("util.lua", false));
+        assert!(!rules.is_ignored("src/init.lua", false));
+        assert!(rules.is_ignored("!bang", false));
+
+        let rules = self::rules(&["!init.lua", "*.lua"]);
+        assert!(rules.is_ignored("init.lua", false));
+    }
+
+    #[test]
+    fn test_double_star() {
+        let rules = rules(&["assets/**/*.psd", "**/tmp"]);
+        assert!(rules.is_ignored("assets/a.psd", false));
+        assert!(rules.is_ignored("assets/x/y/a.psd", false));
+        assert!(rules.is_ignored("a/b/tmp", true));
+    }
+
+    #[test]
+    fn test_nested_file_rules_are_scoped() {
+        let mut rules = IgnoreRules::default();
+        rules.add_line("*.txt", "");
+        rules.add_line("!keep.txt", "docs");
+        rules.add_line("/local", "docs");
+        assert!(!rules.is_ignored("docs/keep.txt", false));
+        assert!(rules.is_ignored("keep.txt", false));
+        assert!(rules.is_ignored("docs/local", true));
+        assert!(!rules.is_ignored("local", true));
+    }
+}
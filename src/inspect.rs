@@ -0,0 +1,148 @@
+use anyhow::Result;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::i18n::t;
+use crate::lua;
+use crate::permissions;
+use crate::project::Project;
+
+/// Lua calls that reach outside the sandbox the permissions describe.
+const RISKY_CALLS: &[&str] = &["os.execute", "io.popen", "package.loadlib"];
+
+/// Lua calls that run code put together at runtime, which review cannot see.
+const DYNAMIC_CALLS: &[&str] = &["load", "loadstring", "dofile"];
+
+/// String literals this long, made only of base64 or hex digits, are data
+/// hidden from review more often than not.
+const ENCODED_LENGTH: usize = 512;
+
+/// What a package holds, shown by `vk install --inspect` before it replaces
+/// the installed copy.
+#[derive(Debug, Default)]
+pub struct Inspection {
+    pub files: usize,
+    pub size: u64,
+    /// The permissions the manifest requests, described.
+    pub permissions: Vec<String>,
+    /// Lifecycle hooks, as `<hook>: <script>`.
+    pub hooks: Vec<String>,
+    /// Native executables and libraries, and precompiled Lua.
+    pub binaries: Vec<String>,
+    /// Anything worth a closer look, described.
+    pub red_flags: Vec<String>,
+}
+
+/// Looks through the package extracted at `dir` without running any of it.
+pub fn inspect(dir: &Path) -> Result<Inspection> {
+    let mut inspection = Inspection::default();
+
+    if let Some(manifest) = Project::at(dir).and_then(|p| p.read_manifest().ok()) {
+        inspection.permissions = permissions::describe(manifest.permissions.as_ref());
+        if let Some(hooks) = &manifest.hooks {
+            for (hook, script) in [("postinstall", &hooks.postinstall), ("preremove", &hooks.preremove)] {
+                if let Some(script) = script {
+                    inspection.hooks.push(format!("{}: {}", hook, script));
+                }
+            }
+        }
+    }
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+        inspection.files += 1;
+        inspection.size += entry.metadata()?.len();
+
+        if let Some(kind) = binary_kind(entry.path())? {
+            inspection.red_flags.push(t!("inspect.flag_binary", file = path, kind = kind));
+            inspection.binaries.push(path);
+            continue;
+        }
+        if path.ends_with(".lua") {
+            let source = String::from_utf8_lossy(&fs::read(entry.path())?).into_owned();
+            scan_lua(&path, &source, &mut inspection.red_flags);
+        }
+    }
+    Ok(inspection)
+}
+
+/// What kind of binary the file at `path` is, going by its first bytes.
+fn binary_kind(path: &Path) -> Result<Option<&'static str>> {
+    let mut magic = [0u8; 4];
+    let read = fs::File::open(path)?.take(4).read(&mut magic)?;
+    Ok(match &magic[..read] {
+        [0x7f, b'E', b'L', b'F'] => Some("ELF"),
+        [b'M', b'Z', ..] => Some("PE"),
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf] | [0xce | 0xcf, 0xfa, 0xed, 0xfe] | [0xca, 0xfe, 0xba, 0xbe] => Some("Mach-O"),
+        [0x00, b'a', b's', b'm'] => Some("WebAssembly"),
+        [0x1b, b'L', b'u', b'a'] => Some("Lua bytecode"),
+        _ => None,
+    })
+}
+
+fn scan_lua(path: &str, source: &str, red_flags: &mut Vec<String>) {
+    let outline = match lua::outline(source) {
+        Ok(outline) => outline,
+        Err(err) => {
+            red_flags.push(t!("inspect.flag_unreadable", file = path, line = err.line));
+            return;
+        },
+    };
+
+    for (name, line) in &outline.names {
+        let at = format!("{}:{}", path, line);
+        if RISKY_CALLS.contains(&name.as_str()) {
+            red_flags.push(t!("inspect.flag_escapes", call = name, at = at));
+        } else if DYNAMIC_CALLS.contains(&name.as_str()) {
+            red_flags.push(t!("inspect.flag_dynamic", call = name, at = at));
+        }
+    }
+    for (string, line) in &outline.strings {
+        if is_encoded(string) {
+            red_flags.push(t!(
+                "inspect.flag_encoded",
+                at = format!("{}:{}", path, line),
+                length = string.len()
+            ));
+        }
+    }
+}
+
+fn is_encoded(string: &str) -> bool {
+    string.len() >= ENCODED_LENGTH
+        && string.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("init.lua"),
+            format!(
+                "local out = io.popen('id'):read('a')\nlocal blob = '{}'\nreturn load(blob)\n",
+                "QUJD".repeat(200)
+            ),
+        )
+        .unwrap();
+        fs::write(dir.path().join("helper.so"), b"\x7fELF\x02\x01").unwrap();
+        fs::write(dir.path().join("README.md"), "Text that mentions MZ").unwrap();
+
+        let inspection = inspect(dir.path()).unwrap();
+        assert_eq!(inspection.files, 3);
+        assert_eq!(inspection.binaries, ["helper.so"]);
+        assert_eq!(inspection.red_flags.len(), 4, "{:?}", inspection.red_flags);
+        assert!(inspection.red_flags[1].contains("io.popen") && inspection.red_flags[1].contains("init.lua:1"));
+        assert!(inspection.red_flags[2].contains("load") && inspection.red_flags[2].contains("init.lua:3"));
+        assert!(inspection.red_flags[3].contains("init.lua:2"));
+    }
+}
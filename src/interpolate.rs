@@ -0,0 +1,105 @@
+use anyhow::{Result, bail};
+
+use crate::i18n::t;
+
+/// Expands environment variable references in `input`:
+///
+/// - `${NAME}` or `${env:NAME}`: the value of `NAME`, which must be set;
+/// - `${NAME:-default}`: `default` when `NAME` is unset or empty;
+/// - `$${`: a literal `${`.
+///
+/// A `$` not followed by `{` is kept as is.
+pub fn expand(input: &str) -> Result<String> {
+    expand_with(input, |name| std::env::var(name).ok())
+}
+
+fn expand_with(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(escaped) = after.strip_prefix("${") {
+            out.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(body) = after.strip_prefix('{') else {
+            out.push('$');
+            rest = after;
+            continue;
+        };
+        let Some(end) = body.find('}') else {
+            bail!(t!("interpolate.unterminated", input = input));
+        };
+
+        let reference = &body[..end];
+        let reference = reference.strip_prefix("env:").unwrap_or(reference);
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if !is_variable_name(name) {
+            bail!(t!("interpolate.invalid_name", name = name));
+        }
+
+        match (lookup(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => bail!(t!("interpolate.not_set", name = name)),
+        }
+        rest = &body[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String> {
+        expand_with(input, |name| match name {
+            "HOST" => Some("registry.example.com".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(
+            expand("https://${HOST}/api").unwrap(),
+            "https://registry.example.com/api"
+        );
+        assert_eq!(expand("${env:HOST}").unwrap(), "registry.example.com");
+        assert_eq!(expand("${MISSING:-localhost}:${PORT:-8080}").unwrap(), "localhost:8080");
+        assert_eq!(expand("${EMPTY:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand("${EMPTY}").unwrap(), "");
+        assert_eq!(expand("${env:MISSING:-}").unwrap(), "");
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(expand("no references").unwrap(), "no references");
+        assert_eq!(expand("cost: $5, $HOST").unwrap(), "cost: $5, $HOST");
+        assert_eq!(expand("$${HOST}").unwrap(), "${HOST}");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(expand("${MISSING}").is_err());
+        assert!(expand("${HOST").is_err());
+        assert!(expand("${}").is_err());
+        assert!(expand("${1BAD}").is_err());
+    }
+}
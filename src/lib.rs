@@ -0,0 +1,144 @@
+//! The engine behind `vk`, for tools that work with Vayload plugins from
+//! Rust: editor plugins, bots, custom CI steps.
+//!
+//! The stable API is what this root exports: the operations `vk install`,
+//! `vk update`, `vk publish`, `vk pack` and `vk audit` run, their options,
+//! and what they need to run. They never print: every message and progress
+//! update goes to the [`Reporter`] they are given, so a [`CallbackReporter`]
+//! receives them as [`Report`]s. Failures are `anyhow` errors; those the CLI
+//! has an exit code for carry a [`VkError`]; [`classify`] gives their stable
+//! code and exit code.
+//!
+//! ```ignore
+//! use vayload_kit::{CallbackReporter, PackOptions, Project, Report};
+//!
+//! let project = Project::discover(&std::env::current_dir()?).expect("not in a plugin");
+//! let reporter = CallbackReporter::new(|report| match report {
+//!     Report::Message(level, message) => eprintln!("{:?}: {}", level, message),
+//!     Report::Output(line) => println!("{}", line),
+//!     Report::Progress { .. } => {},
+//! });
+//! let options = PackOptions { out: None, analyze: false, list: false, allow_path_deps: false };
+//! if let Err(err) = vayload_kit::pack(&project, &options, &reporter) {
+//!     let (code, _) = vayload_kit::classify(&err);
+//!     eprintln!("error[{}]: {:#}", code, err);
+//! }
+//! ```
+//!
+//! The modules are how `vk` itself is built. They are public for its
+//! binaries and change between releases without notice.
+
+#[doc(hidden)]
+pub mod assets;
+#[doc(hidden)]
+pub mod author;
+#[doc(hidden)]
+pub mod capabilities;
+#[doc(hidden)]
+pub mod commands;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod coverage;
+#[doc(hidden)]
+pub mod delta;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod dist_tag;
+#[doc(hidden)]
+pub mod encoding;
+#[doc(hidden)]
+pub mod error;
+#[doc(hidden)]
+pub mod events;
+#[doc(hidden)]
+pub mod features;
+#[doc(hidden)]
+pub mod git;
+#[doc(hidden)]
+pub mod host;
+#[doc(hidden)]
+pub mod http_capture;
+#[doc(hidden)]
+pub mod http_client;
+#[doc(hidden)]
+pub mod i18n;
+#[doc(hidden)]
+pub mod ignore;
+#[doc(hidden)]
+pub mod inspect;
+#[doc(hidden)]
+pub mod interpolate;
+#[doc(hidden)]
+pub mod limits;
+#[doc(hidden)]
+pub mod lockfile;
+#[doc(hidden)]
+pub mod lua;
+#[doc(hidden)]
+pub mod manifest;
+#[doc(hidden)]
+pub mod permissions;
+#[doc(hidden)]
+pub mod policy;
+#[doc(hidden)]
+pub mod pre;
+#[doc(hidden)]
+pub mod project;
+#[doc(hidden)]
+pub mod provenance;
+#[doc(hidden)]
+pub mod registry;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod reporter;
+#[doc(hidden)]
+pub mod resolver;
+#[doc(hidden)]
+pub mod scripts;
+#[doc(hidden)]
+pub mod store;
+#[doc(hidden)]
+pub mod target;
+#[doc(hidden)]
+pub mod trust;
+#[doc(hidden)]
+pub mod types;
+#[doc(hidden)]
+pub mod utils;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "full")]
+#[doc(hidden)]
+pub mod agent;
+#[cfg(feature = "full")]
+#[doc(hidden)]
+pub mod auth;
+#[cfg(feature = "full")]
+#[doc(hidden)]
+pub mod credentials_manager;
+#[cfg(feature = "full")]
+#[doc(hidden)]
+pub mod lsp;
+#[cfg(feature = "full")]
+#[doc(hidden)]
+pub mod telemetry;
+
+pub use commands::audit::{AuditOptions, audit_dependencies as audit};
+pub use commands::install::{InstallOptions, install_plugin as install};
+pub use commands::pack::{PackOptions, pack_plugin as pack};
+pub use commands::publish::{PublishOptions, publish_plugin as publish};
+pub use commands::update::{UpdateOptions, update_dependencies as update};
+pub use error::{VkError, classify};
+pub use http_client::{ClientError, HttpClient, Timeouts};
+pub use manifest::PluginManifest;
+pub use policy::Policy;
+pub use project::Project;
+pub use report::Recorder;
+pub use reporter::{CallbackReporter, Level, OutputMode, Progress, Report, Reporter};
+pub use resolver::Strategy;
+pub use target::Target;
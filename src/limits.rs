@@ -0,0 +1,188 @@
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+use crate::utils::{EntryKind, PackageFile, PackedEntry, format_bytes};
+
+/// Files named when an archive is over the size limit.
+const LARGEST_FILES: usize = 3;
+
+/// What the registry accepts from `vk publish`, as its `/limits` endpoint
+/// advertises it, or as the `[publish]` table of `config.toml` overrides
+/// it. Unset limits are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PublishLimits {
+    /// Largest archive accepted, in bytes.
+    #[serde(alias = "maxArchiveSize")]
+    pub max_archive_size: Option<u64>,
+    /// Archive size past which publishing warns, in bytes.
+    #[serde(alias = "warnArchiveSize")]
+    pub warn_archive_size: Option<u64>,
+    /// Most entries a package may hold.
+    #[serde(alias = "maxFiles")]
+    pub max_files: Option<usize>,
+    /// Extensions of the files the registry refuses (`exe`, `dll`).
+    #[serde(alias = "bannedExtensions")]
+    pub banned_extensions: Option<Vec<String>>,
+    /// Oldest manifest schema (`manifest_version`) accepted.
+    #[serde(alias = "minManifestVersion")]
+    pub min_manifest_version: Option<u32>,
+    /// Newest manifest schema accepted.
+    #[serde(alias = "maxManifestVersion")]
+    pub max_manifest_version: Option<u32>,
+}
+
+impl PublishLimits {
+    /// These limits, with the ones `overrides` sets taking their place.
+    pub fn overridden_by(self, overrides: &PublishLimits) -> Self {
+        Self {
+            max_archive_size: overrides.max_archive_size.or(self.max_archive_size),
+            warn_archive_size: overrides.warn_archive_size.or(self.warn_archive_size),
+            max_files: overrides.max_files.or(self.max_files),
+            banned_extensions: overrides.banned_extensions.clone().or(self.banned_extensions),
+            min_manifest_version: overrides.min_manifest_version.or(self.min_manifest_version),
+            max_manifest_version: overrides.max_manifest_version.or(self.max_manifest_version),
+        }
+    }
+
+    /// Checks the schema version of the manifest being published.
+    pub fn check_manifest_version(&self, version: u32) -> Option<String> {
+        if let Some(min) = self.min_manifest_version.filter(|min| version < *min) {
+            return Some(t!("limits.manifest_too_old", version = version, min = min));
+        }
+        if let Some(max) = self.max_manifest_version.filter(|max| version > *max) {
+            return Some(t!("limits.manifest_too_new", version = version, max = max));
+        }
+        None
+    }
+
+    /// Checks the files about to be packaged. Returns every problem found,
+    /// so they can all be fixed at once.
+    pub fn check_files(&self, files: &[PackageFile]) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(banned) = &self.banned_extensions {
+            for file in files.iter().filter(|f| f.kind == EntryKind::File) {
+                if let Some(extension) = banned_extension(&file.name, banned) {
+                    problems.push(t!("limits.banned_file", file = file.name, extension = extension));
+                }
+            }
+        }
+        if let Some(max) = self.max_files
+            && files.len() > max
+        {
+            problems.push(t!("limits.too_many_files", count = files.len(), max = max));
+        }
+
+        problems
+    }
+
+    /// Checks the size of the built archive, naming its largest entries when
+    /// it is over the limit. Warns past the warning threshold.
+    pub fn check_archive(&self, size: usize, entries: &[PackedEntry], reporter: &dyn Reporter) -> Option<String> {
+        if let Some(max) = self.max_archive_size
+            && size as u64 > max
+        {
+            let mut largest: Vec<&PackedEntry> = entries.iter().collect();
+            largest.sort_by_key(|e| std::cmp::Reverse(e.compressed));
+            let largest: Vec<String> = largest
+                .iter()
+                .take(LARGEST_FILES)
+                .map(|e| format!("{} ({})", e.name, format_bytes(e.compressed as usize)))
+                .collect();
+            return Some(t!(
+                "limits.archive_too_large",
+                size = format_bytes(size),
+                max = format_bytes(max as usize),
+                files = largest.join(", ")
+            ));
+        }
+        if let Some(threshold) = self.warn_archive_size
+            && size as u64 > threshold
+        {
+            reporter.warn(&t!(
+                "limits.archive_large",
+                size = format_bytes(size),
+                threshold = format_bytes(threshold as usize)
+            ));
+        }
+        None
+    }
+}
+
+/// Fetches the limits the registry advertises, overridden by `overrides`. A
+/// registry that advertises none leaves only the overrides.
+pub fn fetch(http_client: &HttpClient, overrides: &PublishLimits, reporter: &dyn Reporter) -> PublishLimits {
+    let advertised = match RegistryClient::new(http_client).limits() {
+        Ok(limits) => limits,
+        Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => PublishLimits::default(),
+        Err(err) => {
+            reporter.detail(&t!("limits.unavailable", reason = err.to_string().bright_black()));
+            PublishLimits::default()
+        },
+    };
+    advertised.overridden_by(overrides)
+}
+
+/// The entry of `banned` the extension of `name` matches, compared without
+/// case or leading dot.
+fn banned_extension<'a>(name: &str, banned: &'a [String]) -> Option<&'a str> {
+    let (_, extension) = name.rsplit_once('/').map_or(name, |(_, file)| file).rsplit_once('.')?;
+    banned.iter().map(|b| b.trim_start_matches('.')).find(|b| b.eq_ignore_ascii_case(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(name: &str) -> PackageFile {
+        PackageFile {
+            path: PathBuf::from(name),
+            name: name.to_string(),
+            size: 1,
+            executable: false,
+            kind: EntryKind::File,
+        }
+    }
+
+    #[test]
+    fn test_check_files() {
+        let registry = PublishLimits {
+            max_files: Some(2),
+            banned_extensions: Some(vec![".exe".into(), "DLL".into()]),
+            ..Default::default()
+        };
+        let files = [file("src/init.lua"), file("bin/tool.EXE"), file("lib/native.dll"), file("exe")];
+        let problems = registry.check_files(&files);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("bin/tool.EXE"));
+        assert!(problems[1].contains("lib/native.dll"));
+
+        let config = PublishLimits {
+            max_files: Some(10),
+            banned_extensions: Some(Vec::new()),
+            ..Default::default()
+        };
+        let limits = registry.overridden_by(&config);
+        assert_eq!(limits.max_files, Some(10));
+        assert!(limits.check_files(&files).is_empty());
+    }
+
+    #[test]
+    fn test_check_manifest_version() {
+        let limits = PublishLimits {
+            min_manifest_version: Some(2),
+            max_manifest_version: Some(3),
+            ..Default::default()
+        };
+        assert!(limits.check_manifest_version(1).is_some());
+        assert!(limits.check_manifest_version(2).is_none());
+        assert!(limits.check_manifest_version(4).is_some());
+        assert!(PublishLimits::default().check_manifest_version(9).is_none());
+    }
+}
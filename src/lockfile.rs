@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::encoding::json5;
+use crate::i18n::t;
+use crate::manifest::{GitSource, PathSource};
+use crate::project::Project;
+
+pub const LOCKFILE_VERSION: u32 = 1;
+
+/// Exact versions (or commits) installed for every dependency, stored in
+/// `vayload.lock` so later installs reproduce the same tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    /// Features `vk install --features` turned on, kept for later installs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub packages: Vec<LockedPackage>,
+    /// Versions `vk pin` holds packages at, which installs and updates never
+    /// move them from.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pins: BTreeMap<String, Pin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    pub version: String,
+    /// Why the package is held back, shown by `vk pin --list`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub id: String,
+    pub version: String,
+    /// Where the package came from. Registry packages omit it, or record
+    /// `vendor+<archive>` once `vk vendor` copied them into the project; git
+    /// dependencies record `git+<url>[?rev=|tag=|branch=<ref>]#<commit>` and
+    /// local ones `path+<path>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Hash of the installed files (see `utils::hash_dir`), checked by
+    /// `vk verify`. Local dependencies have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    /// Versions installed with it for its own registry dependencies.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
+    /// Set when the manifest `overrides` forced the version or source.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overridden: bool,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            features: Vec::new(),
+            packages: Vec::new(),
+            pins: BTreeMap::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Reads the project's lockfile, or an empty one when it does not exist.
+    pub fn load(project: &Project) -> Result<Self> {
+        let path = project.lockfile_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        json5::from_path(&path).map_err(|err| {
+            let context = match err {
+                json5::Error::Io(_) => t!("lock.read_failed"),
+                _ => t!("lock.parse_failed"),
+            };
+            anyhow::Error::new(err).context(context)
+        })
+    }
+
+    pub fn save(&mut self, project: &Project) -> Result<()> {
+        self.packages.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let path = project.lockfile_path();
+        let write = || -> Result<()> {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            json5::to_writer_pretty(&mut writer, self)?;
+            writer.flush()?;
+            Ok(())
+        };
+        write().with_context(|| t!("lock.write_failed"))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.id == id)
+    }
+
+    /// Inserts or replaces the entry for `package.id`.
+    pub fn upsert(&mut self, package: LockedPackage) {
+        self.remove(&package.id);
+        self.packages.push(package);
+    }
+
+    /// The version `id` is pinned at, if any.
+    pub fn pinned(&self, id: &str) -> Option<&str> {
+        self.pins.get(id).map(|pin| pin.version.as_str())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<LockedPackage> {
+        let index = self.packages.iter().position(|p| p.id == id)?;
+        Some(self.packages.remove(index))
+    }
+}
+
+impl LockedPackage {
+    pub fn git(id: &str, version: String, source: &GitSource, commit: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            version,
+            source: Some(format!("{}#{}", source.locator(), commit)),
+            checksum: None,
+            integrity: None,
+            dependencies: BTreeMap::new(),
+            overridden: false,
+        }
+    }
+
+    /// Local dependencies are never pinned; the entry only records what was
+    /// linked.
+    pub fn path(id: &str, version: String, source: &PathSource) -> Self {
+        Self {
+            id: id.to_string(),
+            version,
+            source: Some(format!("path+{}", source.path)),
+            checksum: None,
+            integrity: None,
+            dependencies: BTreeMap::new(),
+            overridden: false,
+        }
+    }
+
+    /// The archive of a vendored registry package, relative to the project
+    /// root.
+    pub fn vendored(&self) -> Option<&str> {
+        self.source.as_deref()?.strip_prefix("vendor+")
+    }
+
+    /// Whether the package comes from the registry, vendored or not.
+    pub fn is_registry(&self) -> bool {
+        self.source.is_none() || self.vendored().is_some()
+    }
+
+    /// The pinned commit, as long as the entry was locked for the same source.
+    pub fn git_commit(&self, source: &GitSource) -> Option<&str> {
+        let (locator, commit) = self.source.as_deref()?.rsplit_once('#')?;
+        (locator == source.locator()).then_some(commit)
+    }
+}
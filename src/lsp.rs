@@ -0,0 +1,447 @@
+//! The pieces of the Language Server Protocol `vk lsp` needs: JSON-RPC
+//! messages framed by `Content-Length` headers, positions in UTF-16 code
+//! units, and a tolerant reading of JSON5 documents that still works while
+//! they are being typed and do not parse.
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+/// Reads the next message, or `None` once the client closed the stream.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(io::Error::other)
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+pub fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// The LSP position of byte `offset` of `text`: a line and a column in
+/// UTF-16 code units.
+pub fn position(text: &str, offset: usize) -> Value {
+    let offset = floor_char_boundary(text, offset);
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+pub fn range(text: &str, span: &Range<usize>) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+/// The byte offset of an LSP position, clamped to the line and the text.
+pub fn offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start = if line == 0 {
+        0
+    } else {
+        match text.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        }
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || units >= character {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// A key of an object, with its value when that is a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// Keys of the objects around it, outermost first.
+    pub path: Vec<String>,
+    pub key: String,
+    pub key_span: Range<usize>,
+    pub value: Option<String>,
+    /// Span of the string value, without its quotes.
+    pub value_span: Option<Range<usize>>,
+}
+
+/// What is being typed at an offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Slot {
+    /// A key of the object at `path`, of which `prefix` is typed so far.
+    Key {
+        path: Vec<String>,
+        key: String,
+        prefix: String,
+    },
+    /// The value of `key` in the object at `path`.
+    Value {
+        path: Vec<String>,
+        key: String,
+        prefix: String,
+    },
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A string, with its span inside the quotes.
+    Str(String, Range<usize>),
+    /// An unquoted key, number or literal.
+    Word(String),
+    Punct(u8),
+}
+
+/// Tokens of a JSON5 document with their spans, skipping comments. Strings
+/// end at the end of their line when they are not closed yet.
+fn tokens(text: &str) -> Vec<(Token, Range<usize>)> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => i += 1,
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = text[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 2);
+            },
+            b @ (b'{' | b'}' | b'[' | b']' | b':' | b',') => {
+                tokens.push((Token::Punct(b), start..start + 1));
+                i += 1;
+            },
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                let mut value = String::new();
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' && i + 1 < bytes.len() {
+                        i += 1;
+                    }
+                    let c = text[i..].chars().next().unwrap_or_default();
+                    value.push(c);
+                    i += c.len_utf8();
+                }
+                let content = start + 1..i;
+                if i < bytes.len() && bytes[i] == quote {
+                    i += 1;
+                }
+                tokens.push((Token::Str(value, content), start..i));
+            },
+            _ => {
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !matches!(bytes[i], b'{' | b'}' | b'[' | b']' | b':' | b',' | b'"' | b'\'' | b'/')
+                {
+                    i += 1;
+                }
+                // A lone character no token starts with
+                i = i.max(start + text[start..].chars().next().map_or(1, char::len_utf8));
+                tokens.push((Token::Word(text[start..i].to_string()), start..i));
+            },
+        }
+    }
+    tokens
+}
+
+enum Frame {
+    Object {
+        /// Key the object is the value of.
+        key: Option<String>,
+        /// Key read last, whose value comes next once `colon` is set.
+        pending: Option<(String, Range<usize>)>,
+        colon: bool,
+    },
+    Array,
+}
+
+/// Follows the nesting of a document token by token.
+struct Walker {
+    frames: Vec<Frame>,
+    members: Vec<Member>,
+}
+
+impl Walker {
+    fn new() -> Self {
+        Walker { frames: Vec::new(), members: Vec::new() }
+    }
+
+    fn path(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .skip(1)
+            .filter_map(|frame| match frame {
+                Frame::Object { key, .. } => Some(key.clone().unwrap_or_default()),
+                Frame::Array => None,
+            })
+            .collect()
+    }
+
+    /// The key whose value starts at this point, if any.
+    fn value_key(&self) -> Option<String> {
+        match self.frames.last() {
+            Some(Frame::Object { pending: Some((key, _)), colon: true, .. }) => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    fn step(&mut self, token: &Token, span: &Range<usize>) {
+        match token {
+            Token::Punct(b'{') => {
+                let key = self.value_key();
+                self.frames.push(Frame::Object { key, pending: None, colon: false });
+            },
+            Token::Punct(b'[') => self.frames.push(Frame::Array),
+            Token::Punct(b'}' | b']') => {
+                self.frames.pop();
+            },
+            Token::Punct(b':') => {
+                if let Some(Frame::Object { colon, .. }) = self.frames.last_mut() {
+                    *colon = true;
+                }
+            },
+            Token::Punct(_) => {
+                if let Some(Frame::Object { pending, colon, .. }) = self.frames.last_mut() {
+                    *pending = None;
+                    *colon = false;
+                }
+            },
+            Token::Str(text, _) | Token::Word(text) => {
+                let path = self.path();
+                let Some(Frame::Object { pending, colon, .. }) = self.frames.last_mut() else {
+                    return;
+                };
+                match (pending.take(), *colon) {
+                    (Some((key, key_span)), true) => {
+                        let (value, value_span) = match token {
+                            Token::Str(value, content) => (Some(value.clone()), Some(content.clone())),
+                            _ => (None, None),
+                        };
+                        self.members.push(Member { path, key, key_span, value, value_span });
+                        *colon = false;
+                    },
+                    _ => {
+                        let key_span = match token {
+                            Token::Str(_, content) => content.clone(),
+                            _ => span.clone(),
+                        };
+                        *pending = Some((text.clone(), key_span));
+                    },
+                }
+            },
+        }
+    }
+}
+
+/// Every key with a scalar value in `text`, in order.
+pub fn members(text: &str) -> Vec<Member> {
+    let mut walker = Walker::new();
+    for (token, span) in tokens(text) {
+        // Keys of objects and arrays are recorded without a value
+        if matches!(token, Token::Punct(b'{' | b'['))
+            && let Some(Frame::Object { pending: Some((key, key_span)), colon: true, .. }) = walker.frames.last()
+        {
+            let member = Member {
+                path: walker.path(),
+                key: key.clone(),
+                key_span: key_span.clone(),
+                value: None,
+                value_span: None,
+            };
+            walker.members.push(member);
+        }
+        walker.step(&token, &span);
+    }
+    walker.members
+}
+
+/// What the text at byte `offset` is: a key or value being typed, or
+/// anything else.
+pub fn slot(text: &str, offset: usize) -> Slot {
+    let mut walker = Walker::new();
+    let mut current = None;
+    for (token, span) in tokens(text) {
+        let inside = match &token {
+            Token::Str(_, content) => content.start <= offset && offset <= content.end,
+            Token::Word(_) => span.start <= offset && offset <= span.end,
+            Token::Punct(_) => false,
+        };
+        if inside {
+            current = Some((token, span));
+            break;
+        }
+        if span.start >= offset {
+            break;
+        }
+        walker.step(&token, &span);
+    }
+
+    let (key, prefix) = match &current {
+        Some((Token::Str(value, content), _)) => {
+            (value.clone(), text[content.start..offset.min(content.end)].to_string())
+        },
+        Some((Token::Word(word), span)) => (word.clone(), text[span.start..offset].to_string()),
+        _ => (String::new(), String::new()),
+    };
+    let path = walker.path();
+    match walker.frames.last() {
+        Some(Frame::Object { pending: Some((name, _)), colon: true, .. }) => {
+            Slot::Value { path, key: name.clone(), prefix }
+        },
+        Some(Frame::Object { pending: None, .. }) => Slot::Key { path, key, prefix },
+        _ => Slot::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+    // the plugin
+    name: "hello",
+    engines: { lua: "5.1" },
+    dependencies: {
+        "ui": "^1.0.0",
+        'né': '2.x',
+        git_dep: { git: "https://example.com/x.git" },
+    },
+}"#;
+
+    #[test]
+    fn test_members() {
+        let members = members(MANIFEST);
+        let found: Vec<(String, String, Option<&str>)> =
+            members.iter().map(|m| (m.path.join("."), m.key.clone(), m.value.as_deref())).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("".to_string(), "name".to_string(), Some("hello")),
+                ("".to_string(), "engines".to_string(), None),
+                ("engines".to_string(), "lua".to_string(), Some("5.1")),
+                ("".to_string(), "dependencies".to_string(), None),
+                ("dependencies".to_string(), "ui".to_string(), Some("^1.0.0")),
+                ("dependencies".to_string(), "né".to_string(), Some("2.x")),
+                ("dependencies".to_string(), "git_dep".to_string(), None),
+                (
+                    "dependencies.git_dep".to_string(),
+                    "git".to_string(),
+                    Some("https://example.com/x.git")
+                ),
+            ]
+        );
+
+        let ui = &members[4];
+        assert_eq!(&MANIFEST[ui.key_span.clone()], "ui");
+        assert_eq!(&MANIFEST[ui.value_span.clone().unwrap()], "^1.0.0");
+    }
+
+    #[test]
+    fn test_slot() {
+        let text = "{\n  dependencies: {\n    \"u\n";
+        let dependencies = vec!["dependencies".to_string()];
+        assert_eq!(
+            slot(text, text.len() - 1),
+            Slot::Key {
+                path: dependencies.clone(),
+                key: "u".to_string(),
+                prefix: "u".to_string()
+            }
+        );
+
+        let text = "{ dependencies: { ui: \"^1.\n }, end: 1 }";
+        let at = text.find("^1.").unwrap() + 3;
+        assert_eq!(
+            slot(text, at),
+            Slot::Value {
+                path: dependencies.clone(),
+                key: "ui".to_string(),
+                prefix: "^1.".to_string()
+            }
+        );
+
+        let text = "{ dependencies: { ui: \"^1.0.0\", } }";
+        let at = text.find("ui").unwrap() + 1;
+        assert_eq!(
+            slot(text, at),
+            Slot::Key {
+                path: dependencies.clone(),
+                key: "ui".to_string(),
+                prefix: "u".to_string()
+            }
+        );
+        assert_eq!(
+            slot(text, text.find(", }").unwrap() + 2),
+            Slot::Key {
+                path: dependencies,
+                key: String::new(),
+                prefix: String::new()
+            }
+        );
+        assert_eq!(slot("[1, 2]", 2), Slot::Other);
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "{\n  \"né\": 1, \"x\": 2\n}";
+        let x = text.find("\"x\"").unwrap();
+        assert_eq!(position(text, x), json!({ "line": 1, "character": 11 }));
+        assert_eq!(offset(text, 1, 11), x);
+        assert_eq!(offset(text, 1, 99), text.rfind('\n').unwrap());
+        assert_eq!(offset(text, 9, 0), text.len());
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({ "id": 1 })).unwrap();
+        assert_eq!(out, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+
+        let mut input = io::Cursor::new(out);
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "id": 1 })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
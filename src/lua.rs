@@ -0,0 +1,774 @@
+use crate::i18n::t;
+
+/// A syntax error in a Lua source, with its 1-based line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Checks that `source` is syntactically valid Lua 5.4, reporting the first
+/// error the way `luac -p` would. Only the grammar is checked: undefined
+/// globals, misplaced `break`s or unknown `goto` labels are left to the host.
+pub fn check(source: &str) -> Result<(), SyntaxError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.block()?;
+    if parser.peek().kind != Kind::Eof {
+        return Err(parser.error(t!("lua.expected", token = "<eof>")));
+    }
+    Ok(())
+}
+
+/// What a Lua source refers to, for telling what a plugin does without
+/// running it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Outline {
+    /// Names and field chains read from them (`os.execute`, `load`), with
+    /// their line. Fields read from other expressions are left out.
+    pub names: Vec<(String, usize)>,
+    /// String literals without their quotes or brackets, escapes as written,
+    /// with their line.
+    pub strings: Vec<(String, usize)>,
+    /// Modules loaded with a literal name (`require "x"`, `require("x")`),
+    /// with their line.
+    pub requires: Vec<(String, usize)>,
+}
+
+/// Lists the names and strings of `source`, which must tokenize.
+pub fn outline(source: &str) -> Result<Outline, SyntaxError> {
+    let tokens = tokenize(source)?;
+    let mut outline = Outline::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        match token.kind {
+            Kind::String => outline.strings.push((unquote(&token.text).to_string(), token.line)),
+            Kind::Name if i == 0 || !matches!(tokens[i - 1].text.as_str(), "." | ":") => {
+                let mut name = token.text.clone();
+                while tokens.get(i + 1).is_some_and(|t| t.text == ".")
+                    && tokens.get(i + 2).is_some_and(|t| t.kind == Kind::Name)
+                {
+                    name.push('.');
+                    name.push_str(&tokens[i + 2].text);
+                    i += 2;
+                }
+                if name == "require" {
+                    let argument = match tokens.get(i + 1) {
+                        Some(t) if t.text == "(" => tokens.get(i + 2),
+                        next => next,
+                    };
+                    if let Some(module) = argument.filter(|t| t.kind == Kind::String) {
+                        outline.requires.push((unquote(&module.text).to_string(), token.line));
+                    }
+                }
+                outline.names.push((name, token.line));
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    Ok(outline)
+}
+
+/// The contents of a string token.
+fn unquote(text: &str) -> &str {
+    let Some((_, open)) = long_bracket(text.as_bytes(), 0) else {
+        return &text[1..text.len() - 1];
+    };
+    // The closing bracket is as long as the opening one, and a newline right
+    // after the opening one is not part of the string
+    let inner = &text[open..text.len() - open];
+    inner.strip_prefix("\r\n").or_else(|| inner.strip_prefix('\n')).unwrap_or(inner)
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in", "local", "nil",
+    "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Longest first, so `...` is not read as `..` and `.`.
+const SYMBOLS: &[&str] = &[
+    "...", "..", "::", "<<", ">>", "//", "==", "~=", "<=", ">=", "+", "-", "*", "/", "%", "^", "#", "&", "~", "|", "<",
+    ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+const BINARY_OPS: &[&str] = &[
+    "+", "-", "*", "/", "//", "%", "^", "..", "==", "~=", "<", "<=", ">", ">=", "and", "or", "&", "|", "~", "<<", ">>",
+];
+
+const UNARY_OPS: &[&str] = &["not", "-", "#", "~"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Name,
+    /// Keywords and symbols, told apart by their text.
+    Reserved,
+    Number,
+    String,
+    Eof,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: Kind,
+    text: String,
+    line: usize,
+}
+
+fn error(line: usize, message: String, near: &str) -> SyntaxError {
+    SyntaxError {
+        line,
+        message: t!("lua.near", message = message, token = near),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, SyntaxError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    // A first line starting with `#` (a shebang) is skipped, as `lua` does
+    if bytes.starts_with(b"#") {
+        while i < bytes.len() && bytes[i] != b'\n' {
+            i += 1;
+        }
+    }
+
+    while i < bytes.len() {
+        let start = i;
+        let start_line = line;
+        let kind = match bytes[i] {
+            b'\n' => {
+                line += 1;
+                i += 1;
+                continue;
+            },
+            b' ' | b'\t' | b'\r' | 0x0b | 0x0c => {
+                i += 1;
+                continue;
+            },
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i += 2;
+                if let Some((level, open)) = long_bracket(bytes, i) {
+                    i = skip_long(bytes, i + open, level, &mut line)
+                        .ok_or_else(|| error(start_line, t!("lua.unfinished_long_comment"), "<eof>"))?;
+                } else {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+                continue;
+            },
+            b'[' if long_bracket(bytes, i).is_some() => {
+                let (level, open) = long_bracket(bytes, i).unwrap_or_default();
+                i = skip_long(bytes, i + open, level, &mut line)
+                    .ok_or_else(|| error(start_line, t!("lua.unfinished_long_string"), "<eof>"))?;
+                Kind::String
+            },
+            quote @ (b'"' | b'\'') => {
+                i = skip_quoted(bytes, i, quote, &mut line)?;
+                Kind::String
+            },
+            b'0'..=b'9' => {
+                i = read_number(bytes, i, line)?;
+                Kind::Number
+            },
+            b'.' if bytes.get(i + 1).is_some_and(u8::is_ascii_digit) => {
+                i = read_number(bytes, i, line)?;
+                Kind::Number
+            },
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if KEYWORDS.contains(&&source[start..i]) {
+                    Kind::Reserved
+                } else {
+                    Kind::Name
+                }
+            },
+            _ => match SYMBOLS.iter().find(|s| bytes[i..].starts_with(s.as_bytes())) {
+                Some(symbol) => {
+                    i += symbol.len();
+                    Kind::Reserved
+                },
+                None => {
+                    let c = source[i..].chars().next().unwrap_or_default();
+                    return Err(error(line, t!("lua.unexpected_symbol"), &format!("'{}'", c)));
+                },
+            },
+        };
+
+        tokens.push(Token {
+            kind,
+            text: String::from_utf8_lossy(&bytes[start..i]).into_owned(),
+            line: start_line,
+        });
+    }
+
+    tokens.push(Token { kind: Kind::Eof, text: String::new(), line });
+    Ok(tokens)
+}
+
+/// The level and length of a long bracket (`[[`, `[==[`) opening at `i`.
+fn long_bracket(bytes: &[u8], i: usize) -> Option<(usize, usize)> {
+    if bytes.get(i) != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[i + 1..].iter().take_while(|&&b| b == b'=').count();
+    (bytes.get(i + 1 + level) == Some(&b'[')).then_some((level, level + 2))
+}
+
+/// Skips to just past the long bracket of `level` closing after `i`.
+fn skip_long(bytes: &[u8], mut i: usize, level: usize, line: &mut usize) -> Option<usize> {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => *line += 1,
+            b']' if bytes[i + 1..].iter().take_while(|&&b| b == b'=').count() == level
+                && bytes.get(i + 1 + level) == Some(&b']') =>
+            {
+                return Some(i + level + 2);
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Skips a quoted string opening at `start`, checking its escapes.
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8, line: &mut usize) -> Result<usize, SyntaxError> {
+    let near = |end: usize| format!("'{}'", String::from_utf8_lossy(&bytes[start..end.min(bytes.len())]));
+    let mut i = start + 1;
+
+    loop {
+        match bytes.get(i) {
+            None | Some(b'\n') | Some(b'\r') => {
+                return Err(error(*line, t!("lua.unfinished_string"), &near(i)));
+            },
+            Some(&c) if c == quote => return Ok(i + 1),
+            Some(b'\\') => {
+                i += 1;
+                match bytes.get(i) {
+                    Some(b'a' | b'b' | b'f' | b'n' | b'r' | b't' | b'v' | b'\\' | b'"' | b'\'') => i += 1,
+                    Some(b'\n') => {
+                        *line += 1;
+                        i += 1;
+                    },
+                    Some(b'z') => {
+                        i += 1;
+                        while let Some(c) = bytes.get(i).filter(|c| c.is_ascii_whitespace()) {
+                            if *c == b'\n' {
+                                *line += 1;
+                            }
+                            i += 1;
+                        }
+                    },
+                    Some(b'x') => {
+                        if !bytes.get(i + 1..i + 3).is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)) {
+                            return Err(error(*line, t!("lua.invalid_escape"), &near(i + 3)));
+                        }
+                        i += 3;
+                    },
+                    Some(b'u') => {
+                        let digits = bytes[i + 1..].iter().skip(1).take_while(|b| b.is_ascii_hexdigit()).count();
+                        if bytes.get(i + 1) != Some(&b'{') || digits == 0 || bytes.get(i + 2 + digits) != Some(&b'}') {
+                            return Err(error(*line, t!("lua.invalid_escape"), &near(i + 2 + digits)));
+                        }
+                        i += digits + 3;
+                    },
+                    Some(c) if c.is_ascii_digit() => {
+                        let digits = bytes[i..].iter().take(3).take_while(|b| b.is_ascii_digit()).count();
+                        let value: u32 = String::from_utf8_lossy(&bytes[i..i + digits]).parse().unwrap_or(0);
+                        if value > 255 {
+                            return Err(error(*line, t!("lua.invalid_escape"), &near(i + digits)));
+                        }
+                        i += digits;
+                    },
+                    _ => return Err(error(*line, t!("lua.invalid_escape"), &near(i + 1))),
+                }
+            },
+            Some(_) => i += 1,
+        }
+    }
+}
+
+/// Reads a numeral the way Lua's lexer does, then checks it converts.
+fn read_number(bytes: &[u8], start: usize, line: usize) -> Result<usize, SyntaxError> {
+    let hex = bytes[start] == b'0' && matches!(bytes.get(start + 1), Some(b'x' | b'X'));
+    let (mut i, exponent) = if hex {
+        (start + 2, [b'p', b'P'])
+    } else {
+        (start, [b'e', b'E'])
+    };
+
+    while let Some(&c) = bytes.get(i) {
+        if exponent.contains(&c) {
+            i += 1;
+            if matches!(bytes.get(i), Some(b'+' | b'-')) {
+                i += 1;
+            }
+        } else if c.is_ascii_hexdigit() || c == b'.' {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+
+    let text = String::from_utf8_lossy(&bytes[start..i]);
+    let valid = if hex {
+        is_hex_numeral(&text[2..])
+    } else {
+        text.parse::<f64>().is_ok()
+    };
+    if !valid {
+        return Err(error(line, t!("lua.malformed_number"), &format!("'{}'", text)));
+    }
+    Ok(i)
+}
+
+/// Hex digits with an optional fraction and binary exponent (`1F`, `a.8p-2`).
+fn is_hex_numeral(text: &str) -> bool {
+    let (mantissa, exponent) = match text.split_once(['p', 'P']) {
+        Some((m, e)) => (m, Some(e)),
+        None => (text, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits_ok = |s: &str| s.chars().all(|c| c.is_ascii_hexdigit());
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+        !e.is_empty() && e.chars().all(|c| c.is_ascii_digit())
+    });
+    !(int.is_empty() && frac.is_empty()) && digits_ok(int) && digits_ok(frac) && exponent_ok
+}
+
+/// What a suffixed expression turned out to be, which decides whether it
+/// can stand as a statement or be assigned to.
+#[derive(PartialEq, Eq)]
+enum Suffixed {
+    Variable,
+    Call,
+    Parenthesized,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) {
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+    }
+
+    fn check(&self, text: &str) -> bool {
+        let token = self.peek();
+        token.kind == Kind::Reserved && token.text == text
+    }
+
+    fn check_any(&self, texts: &[&str]) -> bool {
+        texts.iter().any(|t| self.check(t))
+    }
+
+    fn accept(&mut self, text: &str) -> bool {
+        let found = self.check(text);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn error(&self, message: String) -> SyntaxError {
+        let token = self.peek();
+        let near = match token.kind {
+            Kind::Eof => "<eof>".to_string(),
+            _ => format!("'{}'", token.text),
+        };
+        error(token.line, message, &near)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), SyntaxError> {
+        if self.accept(text) {
+            return Ok(());
+        }
+        Err(self.error(t!("lua.expected", token = text)))
+    }
+
+    /// Expects the token closing `opener`, naming the opening line when the
+    /// block spans several.
+    fn expect_closing(&mut self, text: &str, opener: &str, line: usize) -> Result<(), SyntaxError> {
+        if self.accept(text) {
+            return Ok(());
+        }
+        if self.peek().line == line {
+            return Err(self.error(t!("lua.expected", token = text)));
+        }
+        Err(self.error(t!("lua.expected_to_close", token = text, opener = opener, line = line)))
+    }
+
+    fn name(&mut self) -> Result<(), SyntaxError> {
+        if self.peek().kind != Kind::Name {
+            return Err(self.error(t!("lua.name_expected")));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn block_follows(&self) -> bool {
+        self.peek().kind == Kind::Eof || self.check_any(&["else", "elseif", "end", "until"])
+    }
+
+    fn block(&mut self) -> Result<(), SyntaxError> {
+        while !self.block_follows() {
+            if self.accept("return") {
+                if !self.block_follows() && !self.check(";") {
+                    self.expression_list()?;
+                }
+                self.accept(";");
+                return Ok(());
+            }
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), SyntaxError> {
+        let line = self.peek().line;
+        let keyword = if self.peek().kind == Kind::Reserved {
+            self.peek().text.clone()
+        } else {
+            String::new()
+        };
+
+        match keyword.as_str() {
+            ";" | "break" => self.advance(),
+            "goto" => {
+                self.advance();
+                self.name()?;
+            },
+            "::" => {
+                self.advance();
+                self.name()?;
+                self.expect("::")?;
+            },
+            "do" => {
+                self.advance();
+                self.block()?;
+                self.expect_closing("end", "do", line)?;
+            },
+            "while" => {
+                self.advance();
+                self.expression()?;
+                self.expect("do")?;
+                self.block()?;
+                self.expect_closing("end", "while", line)?;
+            },
+            "repeat" => {
+                self.advance();
+                self.block()?;
+                self.expect_closing("until", "repeat", line)?;
+                self.expression()?;
+            },
+            "if" => {
+                self.advance();
+                self.expression()?;
+                self.expect("then")?;
+                self.block()?;
+                while self.accept("elseif") {
+                    self.expression()?;
+                    self.expect("then")?;
+                    self.block()?;
+                }
+                if self.accept("else") {
+                    self.block()?;
+                }
+                self.expect_closing("end", "if", line)?;
+            },
+            "for" => {
+                self.advance();
+                self.name()?;
+                if self.accept("=") {
+                    self.expression()?;
+                    self.expect(",")?;
+                    self.expression()?;
+                    if self.accept(",") {
+                        self.expression()?;
+                    }
+                } else if self.check_any(&[",", "in"]) {
+                    while self.accept(",") {
+                        self.name()?;
+                    }
+                    self.expect("in")?;
+                    self.expression_list()?;
+                } else {
+                    return Err(self.error(t!("lua.for_expected")));
+                }
+                self.expect("do")?;
+                self.block()?;
+                self.expect_closing("end", "for", line)?;
+            },
+            "function" => {
+                self.advance();
+                self.name()?;
+                while self.accept(".") {
+                    self.name()?;
+                }
+                if self.accept(":") {
+                    self.name()?;
+                }
+                self.function_body(line)?;
+            },
+            "local" => {
+                self.advance();
+                if self.accept("function") {
+                    self.name()?;
+                    self.function_body(line)?;
+                } else {
+                    loop {
+                        self.name()?;
+                        // Attributes: `<const>`, `<close>`
+                        if self.accept("<") {
+                            self.name()?;
+                            self.expect(">")?;
+                        }
+                        if !self.accept(",") {
+                            break;
+                        }
+                    }
+                    if self.accept("=") {
+                        self.expression_list()?;
+                    }
+                }
+            },
+            _ => self.expression_statement()?,
+        }
+        Ok(())
+    }
+
+    /// A call, or an assignment to one or more variables.
+    fn expression_statement(&mut self) -> Result<(), SyntaxError> {
+        let first = self.suffixed()?;
+        if self.check_any(&["=", ","]) {
+            let mut target = first;
+            loop {
+                if target != Suffixed::Variable {
+                    return Err(self.error(t!("lua.syntax_error")));
+                }
+                if !self.accept(",") {
+                    break;
+                }
+                target = self.suffixed()?;
+            }
+            self.expect("=")?;
+            return self.expression_list();
+        }
+        if first != Suffixed::Call {
+            return Err(self.error(t!("lua.syntax_error")));
+        }
+        Ok(())
+    }
+
+    fn function_body(&mut self, line: usize) -> Result<(), SyntaxError> {
+        self.expect("(")?;
+        if !self.check(")") {
+            loop {
+                if self.accept("...") {
+                    break;
+                }
+                self.name()?;
+                if !self.accept(",") {
+                    break;
+                }
+            }
+        }
+        self.expect(")")?;
+        self.block()?;
+        self.expect_closing("end", "function", line)
+    }
+
+    fn expression_list(&mut self) -> Result<(), SyntaxError> {
+        self.expression()?;
+        while self.accept(",") {
+            self.expression()?;
+        }
+        Ok(())
+    }
+
+    /// Operands joined by binary operators; precedence does not matter for
+    /// checking syntax.
+    fn expression(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            while self.check_any(UNARY_OPS) {
+                self.advance();
+            }
+            self.simple_expression()?;
+            if !self.check_any(BINARY_OPS) {
+                return Ok(());
+            }
+            self.advance();
+        }
+    }
+
+    fn simple_expression(&mut self) -> Result<(), SyntaxError> {
+        let line = self.peek().line;
+        if matches!(self.peek().kind, Kind::Number | Kind::String) || self.check_any(&["nil", "true", "false", "..."]) {
+            self.advance();
+        } else if self.check("{") {
+            self.table()?;
+        } else if self.accept("function") {
+            self.function_body(line)?;
+        } else {
+            self.suffixed()?;
+        }
+        Ok(())
+    }
+
+    fn suffixed(&mut self) -> Result<Suffixed, SyntaxError> {
+        let line = self.peek().line;
+        let mut kind = if self.peek().kind == Kind::Name {
+            self.advance();
+            Suffixed::Variable
+        } else if self.accept("(") {
+            self.expression()?;
+            self.expect_closing(")", "(", line)?;
+            Suffixed::Parenthesized
+        } else {
+            return Err(self.error(t!("lua.unexpected_symbol")));
+        };
+
+        loop {
+            if self.accept(".") {
+                self.name()?;
+                kind = Suffixed::Variable;
+            } else if self.accept("[") {
+                self.expression()?;
+                self.expect("]")?;
+                kind = Suffixed::Variable;
+            } else if self.accept(":") {
+                self.name()?;
+                self.arguments()?;
+                kind = Suffixed::Call;
+            } else if self.check_any(&["(", "{"]) || self.peek().kind == Kind::String {
+                self.arguments()?;
+                kind = Suffixed::Call;
+            } else {
+                return Ok(kind);
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> Result<(), SyntaxError> {
+        let line = self.peek().line;
+        if self.peek().kind == Kind::String {
+            self.advance();
+        } else if self.check("{") {
+            self.table()?;
+        } else {
+            self.expect("(")?;
+            if !self.check(")") {
+                self.expression_list()?;
+            }
+            self.expect_closing(")", "(", line)?;
+        }
+        Ok(())
+    }
+
+    fn table(&mut self) -> Result<(), SyntaxError> {
+        let line = self.peek().line;
+        self.expect("{")?;
+        while !self.check("}") {
+            if self.accept("[") {
+                self.expression()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                self.expression()?;
+            } else if self.peek().kind == Kind::Name
+                && self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == Kind::Reserved && t.text == "=")
+            {
+                self.advance();
+                self.advance();
+                self.expression()?;
+            } else {
+                self.expression()?;
+            }
+            if !self.accept(",") && !self.accept(";") {
+                break;
+            }
+        }
+        self.expect_closing("}", "{", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_line(source: &str) -> usize {
+        check(source).expect_err("source should not parse").line
+    }
+
+    #[test]
+    fn test_valid_sources() {
+        let sources = [
+            "#!/usr/bin/env lua\nprint('hi')",
+            "local M = {}\nfunction M.greet(name, ...) return 'hi ' .. name end\nreturn M",
+            "local t <const> = { 1, 2; x = 3, ['y'] = -4.5e3, f = function() end, }",
+            "for i = 1, 10, 2 do if i % 2 == 0 then goto continue elseif i > 5 then break else end ::continue:: end",
+            "for k, v in pairs(t) do repeat local x = k until true end",
+            "a.b[c]:d 'str' {} ; (f)(); a, b.c = 1, 0x1Fp-2 // 3",
+            "local s = [==[\nlong ]] string\n]==] --[[ block\ncomment ]] x = \"esc \\x41\\u{48}\\65\\z\n  \\\"\"",
+            "while not done and #queue > 0 and ~mask & 1 << 2 >= 1 do done = true end",
+            "return",
+        ];
+        for source in sources {
+            assert_eq!(check(source), Ok(()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        let source = "local h = io.popen('ls')\nlocal s = [==[\nraw]==] .. x.y.z:w(os.getenv \"HOME\")";
+        let found = outline(source).unwrap();
+        let names: Vec<&str> = found.names.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["h", "io.popen", "s", "x.y.z", "os.getenv"]);
+        assert_eq!(
+            found.strings,
+            [("ls".to_string(), 1), ("raw".to_string(), 2), ("HOME".to_string(), 3)]
+        );
+
+        let outline =
+            outline("local http = require(\"vhost:http\")\nlocal fs = require 'vhost:fs'\nrequire(name)").unwrap();
+        assert_eq!(
+            outline.requires,
+            [("vhost:http".to_string(), 1), ("vhost:fs".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_errors_report_their_line() {
+        assert_eq!(error_line("local function f()\n  return 1\n"), 3);
+        assert_eq!(error_line("x = 1\ny = \nz = 2"), 3);
+        assert_eq!(error_line("if x then\n  y()\nelse\n  z(\nend"), 5);
+        assert_eq!(error_line("print('unterminated)\n"), 1);
+        assert_eq!(error_line("--[[ never\nclosed"), 1);
+        assert_eq!(error_line("\n\nx = 3..4"), 3);
+        assert_eq!(error_line("local s = '\\q'"), 1);
+    }
+
+    #[test]
+    fn test_statements_must_be_calls_or_assignments() {
+        assert!(check("f()").is_ok());
+        assert!(check("x").is_err());
+        assert!(check("f() = 1").is_err());
+        assert!(check("(a) = 1").is_err());
+        assert!(check("return 1\nx = 2").is_err());
+    }
+}
@@ -7,26 +7,31 @@ use clap::{
     },
 };
 use colored::Colorize;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-mod commands;
-mod config;
-mod encoding;
-mod http_client;
-mod manifest;
-mod pre;
-mod types;
-mod utils;
+use vayload_kit::{commands, config, dist_tag, error, host, i18n, manifest, policy, pre, reporter};
 
 #[cfg(feature = "full")]
-mod auth;
+use vayload_kit::credentials_manager::CredentialManager;
 #[cfg(feature = "full")]
-mod credentials_manager;
+use vayload_kit::{agent, auth, telemetry};
 
-#[cfg(feature = "full")]
-use crate::credentials_manager::{CredentialManager, RawCredentials};
-
-use crate::{config::AppConfig, http_client::HttpClient, manifest::PluginAccess};
+use vayload_kit::{
+    config::AppConfig,
+    http_capture::HttpCapture,
+    http_client::{HttpClient, MirrorEvent},
+    i18n::{Locale, t},
+    manifest::PluginAccess,
+    project::Project,
+    report::Recorder,
+    reporter::{OutputMode, Reporter},
+    resolver::Strategy,
+    store::StoreMode,
+    target::Target,
+    trust::Trust,
+};
 
 #[derive(Parser)]
 #[command(
@@ -37,6 +42,33 @@ use crate::{config::AppConfig, http_client::HttpClient, manifest::PluginAccess};
 struct AppCli {
     #[command(subcommand)]
     command: Commands,
+
+    #[arg(short, long, global = true, help = "Only print warnings, errors and command output")]
+    quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Output style (defaults to interactive on a terminal, plain otherwise)"
+    )]
+    reporter: Option<OutputMode>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "Record registry requests and responses to FILE (HAR), with credentials redacted"
+    )]
+    debug_http: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "MINUTES",
+        help = "While the registry is down or under maintenance, retry for up to MINUTES instead of failing"
+    )]
+    wait_for_registry: Option<u64>,
 }
 
 #[derive(Subcommand)]
@@ -45,6 +77,54 @@ enum Commands {
     Update {
         #[arg(help = "Optional package name to update. If omitted, updates all dependencies.")]
         package: Option<String>,
+
+        #[arg(long, help = "Show what would change without writing the manifest or lockfile")]
+        dry_run: bool,
+
+        #[arg(short, long, help = "Choose which updates to apply")]
+        interactive: bool,
+
+        #[arg(
+            long,
+            help = "Update to the newest release even across major versions, rewriting the range"
+        )]
+        latest: bool,
+
+        #[arg(long, value_name = "FILE", help = "Write a JSON summary of the run to FILE")]
+        report: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with = "interactive",
+            help = "Never prompt; print the changes, with their release notes, as JSON for bots"
+        )]
+        bot: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            requires = "bot",
+            help = "Commit the updates on vk-update/ branches, one per package or grouped, instead of the working tree"
+        )]
+        branches: Option<commands::update::BranchMode>,
+    },
+
+    #[command(about = "Hold a dependency at a version that installs and updates never move it from")]
+    Pin {
+        #[arg(
+            required_unless_present = "list",
+            help = "Package to pin, as <name>[@<version>] (defaults to the locked version)"
+        )]
+        package: Option<String>,
+
+        #[arg(long, conflicts_with = "package", help = "List the pinned packages")]
+        list: bool,
+
+        #[arg(long, requires = "package", help = "Remove the pin of the package")]
+        remove: bool,
+
+        #[arg(long, conflicts_with = "remove", help = "Why the package is pinned, shown by --list")]
+        reason: Option<String>,
     },
 
     #[command(about = "Publish a plugin to the registry")]
@@ -59,21 +139,472 @@ enum Commands {
         #[arg(short, long, value_parser = ["public", "private"], help = "Set package visibility")]
         access: Option<PluginAccess>,
 
+        #[arg(long, help = "Dist-tag to publish under (e.g. beta, next)", default_value = dist_tag::DEFAULT_TAG)]
+        tag: String,
+
         #[arg(long = "dry-run", help = "Simulate publishing without uploading")]
         dry_run: bool,
+
+        #[arg(long = "ignore-scripts", help = "Do not run the prepublish script")]
+        ignore_scripts: bool,
+
+        #[arg(
+            long = "allow-path-deps",
+            help = "Bundle path dependencies under vendor/ instead of refusing"
+        )]
+        allow_path_deps: bool,
+
+        #[arg(
+            long,
+            alias = "require-valid",
+            help = "Check the manifest and Lua sources before uploading"
+        )]
+        verify: bool,
+
+        #[arg(
+            long = "require-clean-audit",
+            help = "Audit the dependencies first and refuse to upload unless nothing is found"
+        )]
+        require_clean_audit: bool,
+
+        #[arg(
+            long = "require-tests",
+            help = "Run the tests first and refuse to upload unless they pass"
+        )]
+        require_tests: bool,
+
+        #[arg(
+            long = "force-republish",
+            help = "Replace the archive of an already published version, where the registry allows it"
+        )]
+        force_republish: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            value_name = "LEVEL",
+            help = "Bump the version at this level when it is already published"
+        )]
+        bump: Option<commands::publish::BumpLevel>,
+
+        #[arg(
+            long,
+            help = "Upload a provenance statement signed with the publisher key (VK_PUBLISHER_KEY)"
+        )]
+        provenance: bool,
     },
 
-    #[command(about = "Install a plugin")]
+    #[command(about = "Check the manifest and the syntax of the plugin's Lua sources")]
+    Validate {
+        #[arg(
+            long = "allow-path-deps",
+            help = "Bundle path dependencies under vendor/ instead of refusing"
+        )]
+        allow_path_deps: bool,
+    },
+
+    #[command(about = "Build the package archive without publishing it")]
+    Pack {
+        #[arg(short, long, help = "Output file (defaults to .vk/<name>-<version>.zip)")]
+        out: Option<String>,
+
+        #[arg(long, help = "Show what takes up space in the package and flag suspicious files")]
+        analyze: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "analyze",
+            help = "List the files that would be packaged without packing"
+        )]
+        list: bool,
+
+        #[arg(
+            long = "allow-path-deps",
+            help = "Bundle path dependencies under vendor/ instead of refusing"
+        )]
+        allow_path_deps: bool,
+    },
+
+    #[command(about = "Run the plugin's test files under tests/")]
+    Test {
+        #[arg(long, help = "Only run test files whose path contains this text")]
+        filter: Option<String>,
+
+        #[arg(long, help = "Command to run each test file with (e.g. lua5.4)")]
+        interpreter: Option<String>,
+
+        #[arg(long, value_name = "FILE", help = "Write a JUnit XML report")]
+        junit: Option<String>,
+
+        #[arg(long, help = "Collect line coverage when the runner supports it")]
+        coverage: bool,
+    },
+
+    #[command(about = "Run the plugin's benchmarks under benches/")]
+    Bench {
+        #[arg(long, help = "Only run bench files whose path contains this text")]
+        filter: Option<String>,
+
+        #[arg(long, help = "Command to run each bench file with (e.g. lua5.4)")]
+        interpreter: Option<String>,
+
+        #[arg(long, default_value_t = 3, help = "Untimed iterations before measuring")]
+        warmup: u32,
+
+        #[arg(long, default_value_t = 20, help = "Timed iterations")]
+        iterations: u32,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Compare against results saved with --save-baseline"
+        )]
+        baseline: Option<String>,
+
+        #[arg(long = "save-baseline", value_name = "FILE", help = "Save the results as a baseline")]
+        save_baseline: Option<String>,
+
+        #[arg(
+            long,
+            default_value_t = 10.0,
+            help = "Slowdown of the median, in percent, that fails the comparison"
+        )]
+        threshold: f64,
+    },
+
+    #[command(about = "Run a script defined in the manifest (lists them when no name is given)")]
+    RunScript {
+        #[arg(help = "Script name")]
+        name: Option<String>,
+
+        #[arg(last = true, help = "Extra arguments passed to the script")]
+        args: Vec<String>,
+    },
+
+    #[command(about = "Run a command in the project root with the VK_* environment")]
+    Exec {
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Command to run"
+        )]
+        command: Vec<String>,
+    },
+
+    #[command(about = "Manage the dist-tags of a package")]
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands,
+    },
+
+    #[command(about = "Manage the registry API tokens of the logged-in user")]
+    Token {
+        #[command(subcommand)]
+        command: TokenCommands,
+    },
+
+    #[command(about = "Install a plugin, or every dependency of the project when no name is given")]
     Install {
         #[arg(help = "Name of the plugin to install")]
+        package: Option<String>,
+
+        #[arg(
+            long,
+            help = "Target directory for installation (defaults to the project's install_dir)"
+        )]
+        dir: Option<String>,
+
+        #[arg(long, help = "Allow pre-release versions when resolving a range")]
+        pre: bool,
+
+        #[arg(long, help = "Do not run postinstall hooks of installed plugins")]
+        ignore_scripts: bool,
+
+        #[arg(long, help = "Install upgrades that ask for more permissions without asking")]
+        accept_permissions: bool,
+
+        #[arg(long, help = "Install registry packages from vendor/ without reaching the network")]
+        offline: bool,
+
+        #[arg(
+            long,
+            help = "Restore symlinks stored in packages (when they point inside the package)"
+        )]
+        allow_symlinks: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "offline",
+            help = "Only install packages published with verified provenance for the downloaded archive"
+        )]
+        require_provenance: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "offline",
+            help = "Only install packages of the trusted_publishers in config.toml, without asking about others"
+        )]
+        require_trusted: bool,
+
+        #[arg(
+            long,
+            help = "Show the files, permissions, hooks and red flags of each registry package and ask before installing it"
+        )]
+        inspect: bool,
+
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "FEATURES",
+            help = "Features to enable, comma-separated (kept in the lockfile for later installs)"
+        )]
+        features: Option<Vec<String>>,
+
+        #[arg(
+            long,
+            value_name = "OS[-ARCH]",
+            help = "Install target dependencies for another platform, e.g. linux-aarch64"
+        )]
+        target: Option<String>,
+
+        #[arg(
+            long,
+            alias = "host-version",
+            value_name = "VERSION",
+            help = "Version of the host runtime, instead of asking `vayload --version`"
+        )]
+        engine_version: Option<String>,
+
+        #[arg(long, help = "Only warn about plugins whose engines ranges exclude the host runtime")]
+        ignore_engines: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = Strategy::Highest,
+            help = "Pick the highest or the lowest version satisfying each range"
+        )]
+        resolution_strategy: Strategy,
+
+        #[arg(
+            short,
+            long,
+            value_name = "N",
+            help = "Packages to download and extract at once (defaults to the number of CPUs, 1 installs one by one)"
+        )]
+        jobs: Option<usize>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Share registry packages with other projects through the store in ~/.vayload-kit/store"
+        )]
+        store: Option<StoreMode>,
+
+        #[arg(long, value_name = "FILE", help = "Write a JSON summary of the run to FILE")]
+        report: Option<String>,
+    },
+
+    #[command(about = "Download the dependencies into vendor/ for offline installs")]
+    Vendor,
+
+    #[command(about = "Maintain the release notes in CHANGELOG.md")]
+    Changelog {
+        #[command(subcommand)]
+        command: ChangelogCommands,
+    },
+
+    #[command(about = "Format plugin.json5 and other JSON5 files, keeping their comments")]
+    Fmt {
+        #[arg(help = "Files or directories to format (defaults to the project's manifest)")]
+        paths: Vec<String>,
+
+        #[arg(long, help = "Fail if any file is not formatted, without changing it")]
+        check: bool,
+    },
+
+    #[command(about = "Check plugin.json5 for missing metadata, wildcard versions and other common problems")]
+    Lint {
+        #[arg(long, help = "Fix what can be fixed safely, keeping the manifest's comments")]
+        fix: bool,
+    },
+
+    #[command(about = "Export, import or check the lockfile")]
+    Lock {
+        #[command(subcommand)]
+        command: LockCommands,
+    },
+
+    #[command(about = "Check installed plugins against the hashes in the lockfile")]
+    Verify {
+        #[arg(long, help = "Reinstall plugins that are missing or modified")]
+        fix: bool,
+
+        #[arg(long, help = "Plugins directory of the project (defaults to its install_dir)")]
+        dir: Option<String>,
+    },
+
+    #[command(about = "Show what changed between two versions of a package")]
+    Diff {
+        #[arg(help = "Package name")]
         package: String,
 
-        #[arg(long, default_value = "./plugins", help = "Target directory for installation")]
-        dir: String,
+        #[arg(help = "Version to compare against (defaults to the latest); with two versions, the one to start from")]
+        from: Option<String>,
+
+        #[arg(help = "Version to compare to")]
+        to: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with = "to",
+            help = "Local directory to compare instead of the installed copy"
+        )]
+        local: Option<String>,
+
+        #[arg(long, help = "Plugins directory of the project (defaults to its install_dir)")]
+        dir: Option<String>,
     },
 
-    #[command(about = "Scan dependencies for known vulnerabilities")]
-    Audit,
+    #[command(about = "Show registry information about a package")]
+    Info {
+        #[arg(help = "Package name")]
+        package: String,
+
+        #[arg(long, help = "Include pre-release versions")]
+        pre: bool,
+    },
+
+    #[command(about = "Show a package's downloads, version adoption and dependents")]
+    Stats {
+        #[arg(help = "Package name")]
+        package: String,
+
+        #[arg(long, default_value_t = 30, help = "Number of days to cover")]
+        days: u32,
+
+        #[arg(long, help = "Print the registry's figures as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "List dependencies with newer versions available")]
+    Outdated {
+        #[arg(long, help = "Include pre-release versions")]
+        pre: bool,
+
+        #[arg(long, help = "Show the release notes of the newer versions")]
+        notes: bool,
+    },
+
+    #[command(about = "Follow registry events about your dependencies: new versions, advisories and yanks")]
+    WatchRegistry {
+        #[arg(long, help = "Print the events as JSON, one per line")]
+        json: bool,
+
+        #[arg(long, value_name = "URL", help = "Also post each event as JSON to this URL")]
+        webhook: Option<String>,
+    },
+
+    #[command(
+        alias = "migrate-manifest",
+        about = "Upgrade the manifest to the current schema, and a legacy plugin.json to plugin.json5"
+    )]
+    Migrate,
+
+    #[command(about = "Create plugin.json5 from an npm package.json or a legacy manifest")]
+    Import {
+        #[arg(default_value = "package.json", help = "File to import")]
+        file: String,
+
+        #[arg(long, help = "Overwrite an existing plugin.json5")]
+        force: bool,
+
+        #[arg(long, help = "Print the manifest instead of writing it")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Deploy the plugin to a host environment from the config")]
+    Deploy {
+        #[arg(help = "Environment to deploy to, a [deploy.<name>] table of the config")]
+        environment: String,
+
+        #[arg(long, help = "Go back to the version deployed before the current one")]
+        rollback: bool,
+
+        #[arg(long, help = "Bundle path dependencies into the archive")]
+        allow_path_deps: bool,
+
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            help = "How long to wait for the host to report the plugin healthy"
+        )]
+        timeout: Option<u64>,
+    },
+
+    #[command(about = "Show the log of a running host")]
+    Logs {
+        #[arg(short, long, help = "Keep printing lines as the host writes them")]
+        follow: bool,
+
+        #[arg(long, value_name = "NAME", help = "Only show the lines of this plugin")]
+        plugin: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "WHEN",
+            help = "Start from a duration ago (10m, 2h) or a timestamp"
+        )]
+        since: Option<String>,
+
+        #[arg(long, help = "Print the entries as JSON, one per line")]
+        json: bool,
+
+        #[arg(
+            long,
+            value_name = "URL|ENV",
+            help = "Host to read from, a URL or a deploy environment (defaults to the one started by `vk host start`)"
+        )]
+        host: Option<String>,
+    },
+
+    #[command(
+        about = "Report on the supply chain of the dependencies: vulnerabilities, licenses, permissions and releases"
+    )]
+    Audit {
+        #[arg(
+            long,
+            help = "Plugins directory checked against the permission policy (defaults to install_dir)"
+        )]
+        dir: Option<String>,
+
+        #[arg(long, help = "Check dependencies for known vulnerabilities")]
+        vulnerabilities: bool,
+
+        #[arg(long, help = "Check licenses against the policy")]
+        licenses: bool,
+
+        #[arg(long, help = "Check installed plugins against the permission policy")]
+        permissions: bool,
+
+        #[arg(long, help = "Check for locked versions that were yanked or deprecated")]
+        yanked: bool,
+
+        #[arg(long, help = "Check for packages without a recent release")]
+        unmaintained: bool,
+
+        #[arg(
+            long,
+            value_name = "MONTHS",
+            default_value_t = 12,
+            help = "Months without a release after which a package counts as unmaintained"
+        )]
+        stale_months: u32,
+
+        #[arg(long, value_name = "FILE", help = "Write a JSON summary of the run to FILE")]
+        report: Option<String>,
+    },
 
     #[command(about = "List installed dependencies")]
     List {
@@ -89,6 +620,29 @@ enum Commands {
 
         #[arg(long, help = "Directory to create the project in")]
         directory: Option<String>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Also write a CI pipeline that validates, audits and publishes the plugin"
+        )]
+        ci: Option<commands::init::CiProvider>,
+
+        #[arg(
+            long = "registry-url",
+            help = "Registry the CI pipeline publishes to (defaults to the configured one)"
+        )]
+        registry_url: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "SPDX",
+            help = "License of the plugin, written to LICENSE (defaults to MIT)"
+        )]
+        license: Option<String>,
+
+        #[arg(long = "no-git", help = "Do not make the project a git repository")]
+        no_git: bool,
     },
 
     #[cfg(feature = "full")]
@@ -99,6 +653,35 @@ enum Commands {
 
         #[arg(long, help = "Add as a development dependency")]
         dev: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "dev",
+            help = "Add as an optional dependency, installed when a feature enables it"
+        )]
+        optional: bool,
+
+        #[arg(long, help = "Allow a pre-release when picking the newest version")]
+        pre: bool,
+
+        #[arg(long, help = "Depend on a git repository instead of the registry")]
+        git: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with = "git",
+            help = "Depend on a local plugin directory (relative to the project root)"
+        )]
+        path: Option<String>,
+
+        #[arg(long, requires = "git", conflicts_with_all = ["branch", "tag"], help = "Git commit to pin")]
+        rev: Option<String>,
+
+        #[arg(long, requires = "git", conflicts_with = "tag", help = "Git branch to follow")]
+        branch: Option<String>,
+
+        #[arg(long, requires = "git", help = "Git tag to check out")]
+        tag: Option<String>,
     },
 
     #[cfg(feature = "full")]
@@ -106,11 +689,62 @@ enum Commands {
     Remove {
         #[arg(help = "Package name to remove")]
         package: String,
+
+        #[arg(
+            long,
+            help = "Directory the plugin is installed in (defaults to the project's install_dir)"
+        )]
+        dir: Option<String>,
+
+        #[arg(long, help = "Do not run the plugin's preremove hook")]
+        ignore_scripts: bool,
+    },
+
+    #[cfg(feature = "full")]
+    #[command(about = "Register this plugin for linking, or link a registered plugin into the project")]
+    Link {
+        #[arg(
+            help = "Registered plugin to link into the plugins directory (registers the current plugin when omitted)"
+        )]
+        name: Option<String>,
+
+        #[arg(long, help = "Plugins directory of the project (defaults to its install_dir)")]
+        dir: Option<String>,
+    },
+
+    #[cfg(feature = "full")]
+    #[command(about = "Remove a link created by `vk link`")]
+    Unlink {
+        #[arg(
+            help = "Linked plugin to remove from the plugins directory (unregisters the current plugin when omitted)"
+        )]
+        name: Option<String>,
+
+        #[arg(long, help = "Plugins directory of the project (defaults to its install_dir)")]
+        dir: Option<String>,
     },
 
     #[cfg(feature = "full")]
     #[command(about = "Clean cache and build artifacts")]
-    Clean,
+    Clean {
+        #[arg(long, help = "Remove the download cache in .vk/")]
+        cache: bool,
+
+        #[arg(long, help = "Remove archives written by `vk pack`")]
+        artifacts: bool,
+
+        #[arg(long, help = "Remove the lockfile (asks for confirmation)")]
+        lockfile: bool,
+
+        #[arg(long, help = "Remove the cache, archives and lockfile")]
+        all: bool,
+
+        #[arg(long, help = "Show what would be removed and how much space it takes")]
+        dry_run: bool,
+
+        #[arg(short, long, help = "Delete the lockfile without asking")]
+        yes: bool,
+    },
 
     #[cfg(feature = "full")]
     #[command(about = "Authenticate with the Vayload registry")]
@@ -129,6 +763,21 @@ enum Commands {
             help = "Authenticate using OAuth provider"
         )]
         oauth: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["username", "password", "oauth"],
+            help = "Refresh the session tokens without logging in again"
+        )]
+        refresh: bool,
+
+        #[arg(
+            long,
+            value_name = "ORG",
+            conflicts_with_all = ["username", "password", "oauth", "refresh"],
+            help = "Log in through the single sign-on of an organization"
+        )]
+        sso: Option<String>,
     },
 
     #[cfg(feature = "full")]
@@ -138,19 +787,222 @@ enum Commands {
     #[cfg(feature = "full")]
     #[command(about = "Logout and remove local credentials")]
     Logout,
+
+    #[cfg(feature = "full")]
+    #[command(about = "Manage the stored registry credentials")]
+    Auth {
+        #[command(subcommand)]
+        command: AuthSubcommands,
+    },
+
+    #[cfg(feature = "full")]
+    #[command(about = "Manage anonymous usage metrics")]
+    Telemetry {
+        #[command(subcommand)]
+        command: TelemetryCommands,
+    },
+
+    #[cfg(feature = "full")]
+    #[command(about = "Install, pin and run local Vayload host runtimes")]
+    Host {
+        #[command(subcommand)]
+        command: HostCommands,
+    },
+
+    #[cfg(feature = "full")]
+    #[command(about = "Run a language server for plugin.json5 over stdio, for editors")]
+    Lsp,
 }
 
-fn main() {
-    println!();
-    if let Err(err) = run() {
-        eprintln!("{} {}\n", "error:".red().bold(), err);
-        std::process::exit(1);
-    }
+#[cfg(feature = "full")]
+#[derive(Subcommand)]
+enum HostCommands {
+    #[command(about = "Download a host runtime for this platform")]
+    Install {
+        #[arg(help = "Version to install (defaults to the latest)")]
+        version: Option<String>,
+
+        #[arg(long, help = "Download again even if the version is installed")]
+        force: bool,
+    },
+
+    #[command(about = "List the installed host runtimes")]
+    List,
+
+    #[command(about = "Pin the host runtime version of the project")]
+    Pin {
+        #[arg(help = "Version to pin")]
+        version: String,
+
+        #[arg(long, help = "Set the default version, used outside pinned projects")]
+        global: bool,
+    },
+
+    #[command(about = "Start the project's host runtime in the background")]
+    Start {
+        #[arg(short, long, default_value_t = 8080, help = "Port to serve on")]
+        port: u16,
+
+        #[arg(long, help = "Plugins directory to serve")]
+        dir: Option<String>,
+    },
+
+    #[command(about = "Stop the host runtime started by `vk host start`")]
+    Stop,
+}
+
+#[cfg(feature = "full")]
+#[derive(Subcommand)]
+enum TelemetryCommands {
+    #[command(about = "Send anonymous usage metrics")]
+    On,
+
+    #[command(about = "Stop sending usage metrics and drop the unsent ones")]
+    Off,
+
+    #[command(about = "Show whether usage metrics are sent")]
+    Status,
+}
+
+#[cfg(feature = "full")]
+#[derive(Subcommand)]
+enum AuthSubcommands {
+    #[command(about = "Write the stored credentials to a passphrase-encrypted file")]
+    Export {
+        #[arg(short, long, help = "File to write")]
+        file: String,
+    },
+
+    #[command(about = "Store the credentials of a file written by `vk auth export`")]
+    Import {
+        #[arg(short, long, help = "File to read")]
+        file: String,
+    },
+
+    #[command(about = "Re-encrypt the stored credentials under a new key")]
+    RotateKey,
+
+    #[command(about = "Derive the credentials key from a passphrase instead of a key file")]
+    SetPassphrase,
+
+    #[command(about = "Go back to keeping the credentials key in a key file")]
+    RemovePassphrase,
+
+    #[command(about = "Forget the unlocked credentials key until the passphrase is entered again")]
+    Lock,
+
+    #[command(hide = true)]
+    Agent {
+        #[arg(long)]
+        socket: std::path::PathBuf,
+
+        #[arg(long)]
+        timeout: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChangelogCommands {
+    #[command(about = "Add the entry of a version, from the Unreleased section or the git commits since the last tag")]
+    Add {
+        #[arg(long, help = "Version of the entry (defaults to the manifest version)")]
+        version: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "REF",
+            help = "Collect the commits after REF instead of the latest tag"
+        )]
+        since: Option<String>,
+
+        #[arg(long, help = "Replace the entry if the changelog already has one")]
+        force: bool,
+
+        #[arg(long = "dry-run", help = "Print the entry instead of writing it")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Print the entry of a version")]
+    Show {
+        #[arg(help = "Version to show (defaults to the manifest version)")]
+        version: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LockCommands {
+    #[command(about = "Write the lockfile in another format")]
+    Export {
+        #[arg(long, value_enum, default_value = "json", help = "Format to write")]
+        format: commands::lock::ExportFormat,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "Write to FILE instead of the standard output"
+        )]
+        output: Option<String>,
+    },
+
+    #[command(about = "Pin dependencies at the versions of a package-lock.json or yarn.lock")]
+    Import {
+        #[arg(help = "npm or yarn lockfile to read")]
+        file: String,
+    },
+
+    #[command(about = "Fail when the lockfile is out of sync with the manifest")]
+    Check,
+}
+
+#[derive(Subcommand)]
+enum TagCommands {
+    #[command(about = "Point a dist-tag at a published version")]
+    Add {
+        #[arg(help = "Package and version, e.g. foo@1.2.0")]
+        package: String,
+
+        #[arg(help = "Tag name, e.g. beta")]
+        tag: String,
+    },
+
+    #[command(about = "Remove a dist-tag")]
+    Remove {
+        #[arg(help = "Package name")]
+        package: String,
+
+        #[arg(help = "Tag name")]
+        tag: String,
+    },
+
+    #[command(about = "List the dist-tags of a package")]
+    List {
+        #[arg(help = "Package name (defaults to the current project)")]
+        package: Option<String>,
+    },
+}
 
-    println!();
+#[derive(Subcommand)]
+enum TokenCommands {
+    #[command(about = "List the API tokens of the logged-in user")]
+    List,
 }
 
-fn run() -> Result<()> {
+impl Commands {
+    /// Whether the command's stdout is read by another program, so nothing
+    /// but what it writes there may go to it: the language server's
+    /// protocol, or the summary of `vk update --bot`.
+    fn owns_stdout(&self) -> bool {
+        match self {
+            #[cfg(feature = "full")]
+            Commands::Lsp => true,
+            Commands::Update { bot, .. } => *bot,
+            _ => false,
+        }
+    }
+}
+
+fn main() {
     use clap::CommandFactory;
 
     let orange = RgbColor(234, 88, 12);
@@ -165,51 +1017,528 @@ fn run() -> Result<()> {
         .invalid(AnsiColor::BrightRed.on_default());
 
     let matches = AppCli::command().styles(styles).get_matches();
+    let cli = match AppCli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(err) => err.exit(),
+    };
+
+    i18n::set_locale(Locale::from_env().unwrap_or(Locale::En));
+
+    let quiet = cli.quiet || cli.command.owns_stdout();
+    let mode = OutputMode::detect(cli.reporter, quiet);
+    let reporter: Arc<dyn Reporter> = Arc::from(reporter::new_reporter(mode));
+    let spaced = mode == OutputMode::Interactive;
+
+    if spaced {
+        println!();
+    }
+
+    let command_name = matches.subcommand_name().unwrap_or_default();
+    let options = RunOptions {
+        debug_http: cli.debug_http.as_deref(),
+        wait_for_registry: cli.wait_for_registry,
+    };
+    if let Err(err) = run(cli.command, command_name, &options, reporter.clone()) {
+        let (code, exit_code) = error::classify(&err);
+        reporter.failure(code, &error::render(&err));
+        if spaced {
+            eprintln!();
+        }
+        std::process::exit(exit_code);
+    }
+
+    if spaced {
+        println!();
+    }
+}
+
+/// Global options that change how the registry is talked to.
+struct RunOptions<'a> {
+    debug_http: Option<&'a Path>,
+    wait_for_registry: Option<u64>,
+}
 
-    let cli = AppCli::from_arg_matches(&matches)?;
+fn run(command: Commands, command_name: &str, options: &RunOptions, reporter: Arc<dyn Reporter>) -> Result<()> {
+    // Errors loading the config are in the environment's locale, as they
+    // leave `ui.locale` unknown
     let config = AppConfig::load()?;
+    if let Some(locale) = config.ui.locale.as_deref().and_then(Locale::parse) {
+        i18n::set_locale(locale);
+    }
+
+    let capture = options.debug_http.map(|_| Arc::new(HttpCapture::new()));
+    let mut http_client = setup_client(&config, command_name, capture.clone(), reporter.clone())?;
+    http_client.set_mirrors(config.server.mirrors.clone());
+    let mirror_reporter = reporter.clone();
+    http_client.set_mirror_fn(move |event| report_mirror(event, mirror_reporter.as_ref()));
+    if let Some(minutes) = options.wait_for_registry {
+        let wait_reporter = reporter.clone();
+        http_client.set_wait(Duration::from_secs(minutes * 60), move |err, delay| {
+            wait_reporter.warn(&t!("http.waiting", reason = err, seconds = delay.as_secs()))
+        });
+    }
+    let deprecation_reporter = reporter.clone();
+    http_client.set_deprecation_fn(move |notice| {
+        deprecation_reporter.warn(&t!(
+            "http.client_deprecated",
+            version = env!("CARGO_PKG_VERSION"),
+            notice = notice
+        ))
+    });
+
+    #[cfg(feature = "full")]
+    let started = std::time::Instant::now();
+    let result = dispatch(command, &config, &http_client, reporter.clone());
+
+    #[cfg(feature = "full")]
+    telemetry::after_command(
+        command_name,
+        started.elapsed(),
+        &result,
+        &config.telemetry_endpoint(),
+        reporter.as_ref(),
+    );
+
+    if let Err(err) = &result
+        && error::classify(err).1 == error::exit_code::REGISTRY_UNAVAILABLE
+        && let Some(url) = &config.server.status_page
+        && !error::render(err).contains(url.as_str())
+    {
+        reporter.info(&t!("http.status_page_hint", url = url.cyan()));
+    }
 
-    let http_client = setup_client(&config)?;
+    // Written whatever the outcome, as failures are what it is for
+    if let (Some(path), Some(capture)) = (options.debug_http, capture) {
+        match capture.write(path) {
+            Ok(()) => reporter.detail(&t!(
+                "http_capture.written",
+                count = capture.len(),
+                path = path.display()
+            )),
+            Err(err) => reporter.warn(&error::render(&err)),
+        }
+    }
+
+    result
+}
 
-    match cli.command {
-        Commands::Update { package } => {
-            pre::ensure_manifest_exists()?;
-            commands::update::update_dependencies(package.as_deref(), &http_client)?
+fn dispatch(
+    command: Commands,
+    config: &AppConfig,
+    http_client: &HttpClient,
+    reporter: Arc<dyn Reporter>,
+) -> Result<()> {
+    let r = reporter.as_ref();
+
+    match command {
+        Commands::Update { package, dry_run, interactive, latest, report, bot, branches } => {
+            let recorder = Recorder::new("update");
+            let result = open_project(r).and_then(|project| {
+                let options = commands::update::UpdateOptions {
+                    dry_run,
+                    interactive,
+                    latest,
+                    recorder: &recorder,
+                    bot,
+                    branches,
+                };
+                commands::update::update_dependencies(&project, package.as_deref(), &options, http_client, r)
+            });
+            finish_report(report.as_deref(), &recorder, result, r)?
         },
-        Commands::Install { package, dir } => {
-            pre::ensure_manifest_exists()?;
-            commands::install::install_plugin(&package, &dir, &http_client)?
+        Commands::Pin { package, list, remove, reason } => {
+            let project = open_project(r)?;
+            match package {
+                _ if list => commands::pin::list_pins(&project, r)?,
+                Some(package) if remove => commands::pin::unpin_package(&project, &package, r)?,
+                Some(package) => commands::pin::pin_package(&project, &package, reason.as_deref(), http_client, r)?,
+                None => unreachable!("clap requires a package without --list"),
+            }
         },
-        Commands::Publish { directory, access, dry_run } => {
-            commands::publish::publish_plugin(&directory, access, dry_run, &http_client)?
+        Commands::Install {
+            package,
+            dir,
+            pre,
+            ignore_scripts,
+            accept_permissions,
+            offline,
+            allow_symlinks,
+            require_provenance,
+            require_trusted,
+            inspect,
+            features,
+            target,
+            engine_version,
+            ignore_engines,
+            resolution_strategy,
+            jobs,
+            store,
+            report,
+        } => {
+            let recorder = Recorder::new("install");
+            let result = open_project(r).and_then(|project| {
+                let options = commands::install::InstallOptions {
+                    include_pre: pre,
+                    ignore_scripts,
+                    accept_permissions,
+                    offline,
+                    allow_symlinks: allow_symlinks || config.install.symlinks,
+                    require_provenance: require_provenance || config.install.require_provenance,
+                    strategy: resolution_strategy,
+                    features,
+                    target: Target::new(
+                        target.as_deref(),
+                        engine_version.as_deref().or(config.install.host_version.as_deref()),
+                        config.install.lua_version.as_deref(),
+                    )
+                    .map_err(|value| anyhow::anyhow!(t!("install.invalid_target", value = value)))?,
+                    ignore_engines: ignore_engines || config.install.ignore_engines,
+                    policy: policy::load(Some(&project))?,
+                    jobs: jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+                    store: store.unwrap_or(config.install.store),
+                    trust: Trust::new(&config.install, require_trusted),
+                    inspect: inspect || config.install.inspect,
+                    recorder: &recorder,
+                };
+                let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+                commands::install::install_plugin(&project, package.as_deref(), &dir, &options, http_client, r)
+            });
+            finish_report(report.as_deref(), &recorder, result, r)?
+        },
+        Commands::Vendor => {
+            let project = open_project(r)?;
+            commands::vendor::vendor_dependencies(&project, http_client, r)?
+        },
+        Commands::Changelog { command } => {
+            let project = open_project(r)?;
+            match command {
+                ChangelogCommands::Add { version, since, force, dry_run } => {
+                    let options = commands::changelog::AddOptions {
+                        version: version.as_deref(),
+                        since: since.as_deref(),
+                        force,
+                        dry_run,
+                    };
+                    commands::changelog::add_entry(&project, &options, r)?
+                },
+                ChangelogCommands::Show { version } => {
+                    commands::changelog::show_entry(&project, version.as_deref(), r)?
+                },
+            }
+        },
+        Commands::Fmt { paths, check } => {
+            let paths = if paths.is_empty() {
+                vec![open_project(r)?.manifest_path().to_path_buf()]
+            } else {
+                paths.into_iter().map(PathBuf::from).collect()
+            };
+            commands::fmt::format_files(&paths, check, r)?
+        },
+        Commands::Lint { fix } => {
+            let project = open_project(r)?;
+            commands::lint::lint_project(&project, fix, r)?
+        },
+        Commands::Lock { command } => {
+            let project = open_project(r)?;
+            match command {
+                LockCommands::Export { format, output } => {
+                    commands::lock::export_lockfile(&project, format, output.as_deref(), r)?
+                },
+                LockCommands::Import { file } => commands::lock::import_lockfile(&project, &file, r)?,
+                LockCommands::Check => commands::lock::check_lockfile(&project, r)?,
+            }
+        },
+        Commands::Verify { fix, dir } => {
+            let project = open_project(r)?;
+            let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+            commands::verify::verify_installs(&project, &dir, fix, config.install.symlinks, http_client, r)?
+        },
+        Commands::Diff { package, from, to, local, dir } => {
+            use commands::diff::DiffSide;
+
+            let (from, to) = match (from, to, local) {
+                (Some(from), Some(to), _) => (DiffSide::Published(Some(from)), DiffSide::Published(Some(to))),
+                (version, None, Some(local)) => (DiffSide::Local(local.into()), DiffSide::Published(version)),
+                (version, None, None) => {
+                    let project = open_project(r)?;
+                    let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+                    let installed = commands::diff::installed_dir(&project, &package, &dir)?;
+                    (DiffSide::Local(installed), DiffSide::Published(version))
+                },
+                (None, Some(_), _) => unreachable!("clap fills positional arguments in order"),
+            };
+            commands::diff::diff_package(&package, from, to, http_client, r)?
+        },
+        Commands::Info { package, pre } => commands::info::show_info(&package, pre, http_client, r)?,
+        Commands::Stats { package, days, json } => commands::stats::show_stats(&package, days, json, http_client, r)?,
+        Commands::Outdated { pre, notes } => {
+            let project = open_project(r)?;
+            commands::outdated::list_outdated(&project, pre, notes, http_client, r)?
+        },
+        Commands::WatchRegistry { json, webhook } => {
+            let project = open_project(r)?;
+            let options = commands::watch_registry::WatchOptions { json, webhook: webhook.as_deref() };
+            commands::watch_registry::watch_registry(&project, &options, http_client, r)?
+        },
+        Commands::Publish {
+            directory,
+            access,
+            tag,
+            dry_run,
+            ignore_scripts,
+            allow_path_deps,
+            verify,
+            require_clean_audit,
+            require_tests,
+            force_republish,
+            bump,
+            provenance,
+        } => {
+            let options = commands::publish::PublishOptions {
+                directory,
+                access,
+                tag,
+                dry_run,
+                ignore_scripts,
+                allow_path_deps,
+                verify,
+                require_clean_audit,
+                require_tests,
+                force_republish,
+                bump,
+                provenance,
+                limits: config.publish.clone(),
+                install_dir: config.install.dir.clone(),
+            };
+            commands::publish::publish_plugin(&options, http_client, r)?
+        },
+        Commands::Validate { allow_path_deps } => {
+            let project = open_project(r)?;
+            commands::validate::validate_package(&project, allow_path_deps, r)?
+        },
+        Commands::Pack { out, analyze, list, allow_path_deps } => {
+            let project = open_project(r)?;
+            let options = commands::pack::PackOptions { out, analyze, list, allow_path_deps };
+            commands::pack::pack_plugin(&project, &options, r)?
+        },
+        Commands::Test { filter, interpreter, junit, coverage } => {
+            let project = open_project(r)?;
+            let options = commands::test::TestOptions { filter, interpreter, junit, coverage };
+            commands::test::run_tests(&project, &options, r)?
+        },
+        Commands::Bench {
+            filter,
+            interpreter,
+            warmup,
+            iterations,
+            baseline,
+            save_baseline,
+            threshold,
+        } => {
+            let project = open_project(r)?;
+            let options = commands::bench::BenchOptions {
+                filter,
+                interpreter,
+                warmup,
+                iterations,
+                baseline,
+                save_baseline,
+                threshold,
+            };
+            commands::bench::run_benches(&project, &options, r)?
+        },
+        Commands::RunScript { name, args } => {
+            let project = open_project(r)?;
+            commands::run::run_script(&project, name.as_deref(), &args, r)?
+        },
+        Commands::Exec { command } => {
+            let project = open_project(r)?;
+            commands::run::exec_command(&project, &command, r)?
+        },
+        Commands::Tag { command } => match command {
+            TagCommands::Add { package, tag } => commands::tag::add_tag(&package, &tag, http_client, r)?,
+            TagCommands::Remove { package, tag } => commands::tag::remove_tag(&package, &tag, http_client, r)?,
+            TagCommands::List { package } => commands::tag::list_tags(package.as_deref(), http_client, r)?,
+        },
+        Commands::Token { command } => match command {
+            TokenCommands::List => commands::token::list_tokens(http_client, r)?,
         },
         Commands::List { depth } => {
-            pre::ensure_manifest_exists()?;
-            commands::list::list_dependencies(depth)?
+            let project = open_project(r)?;
+            commands::list::list_dependencies(&project, depth, r)?
         },
-        Commands::Audit => {
-            pre::ensure_manifest_exists()?;
-            commands::audit::audit_dependencies(&http_client)?
+        Commands::Migrate => commands::migrate::migrate_project(r)?,
+        Commands::Import { file, force, dry_run } => {
+            let options = commands::import::ImportOptions { force, dry_run };
+            commands::import::import_manifest(Path::new(&file), &options, http_client, r)?
+        },
+        Commands::Deploy { environment, rollback, allow_path_deps, timeout } => {
+            let project = open_project(r)?;
+            let target = deploy_target(config, &environment)?;
+            let options = commands::deploy::DeployOptions {
+                environment: &environment,
+                rollback,
+                allow_path_deps,
+                health_timeout: timeout.or(target.health_timeout).map(std::time::Duration::from_secs),
+            };
+            let host_client = host_client(config, &target.url, target.token.clone())?;
+            commands::deploy::deploy(&project, &options, &host_client, r)?
+        },
+        Commands::Logs { follow, plugin, since, json, host } => {
+            let host_client = match host {
+                Some(host) if !host.contains("://") => {
+                    let target = deploy_target(config, &host)?;
+                    host_client(config, &target.url, target.token.clone())?
+                },
+                Some(host) => host_client(config, &host, None)?,
+                None => {
+                    let project = open_project(r)?;
+                    let running = host::running(&project).ok_or_else(|| anyhow::anyhow!(t!("logs.no_host")))?;
+                    host_client(config, &running.url(), None)?
+                },
+            };
+            let options = commands::logs::LogsOptions {
+                follow,
+                plugin: plugin.as_deref(),
+                since: since.as_deref(),
+                json,
+            };
+            commands::logs::show_logs(&host_client, &options, r)?
+        },
+        Commands::Audit {
+            dir,
+            vulnerabilities,
+            licenses,
+            permissions,
+            yanked,
+            unmaintained,
+            stale_months,
+            report,
+        } => {
+            // Every section runs unless some are picked
+            let all = !(vulnerabilities || licenses || permissions || yanked || unmaintained);
+            let options = commands::audit::AuditOptions {
+                vulnerabilities: all || vulnerabilities,
+                licenses: all || licenses,
+                permissions: all || permissions,
+                yanked: all || yanked,
+                unmaintained: all || unmaintained,
+                stale_months,
+            };
+            let recorder = Recorder::new("audit");
+            let result = open_project(r).and_then(|project| {
+                let policy = policy::load(Some(&project))?;
+                let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+                commands::audit::audit_dependencies(
+                    &project,
+                    &dir,
+                    policy.as_ref(),
+                    &options,
+                    http_client,
+                    &recorder,
+                    r,
+                )
+                .map(|_| ())
+            });
+            finish_report(report.as_deref(), &recorder, result, r)?
         },
 
         #[cfg(feature = "full")]
         cmd @ (Commands::Add { .. }
         | Commands::Init { .. }
         | Commands::Remove { .. }
-        | Commands::Clean
+        | Commands::Link { .. }
+        | Commands::Unlink { .. }
+        | Commands::Clean { .. }
         | Commands::Login { .. }
         | Commands::Whoami
-        | Commands::Logout) => handle_full_commands(cmd, &http_client)?,
+        | Commands::Logout
+        | Commands::Auth { .. }
+        | Commands::Telemetry { .. }
+        | Commands::Host { .. }
+        | Commands::Lsp) => handle_full_commands(cmd, config, http_client, reporter)?,
     }
     Ok(())
 }
 
-fn setup_client(config: &AppConfig) -> Result<HttpClient> {
+/// Locates the enclosing project, mentioning it when it is not the current
+/// directory and nudging towards `vk migrate` for legacy manifests.
+fn open_project(reporter: &dyn Reporter) -> Result<Project> {
+    let project = pre::ensure_manifest_exists()?;
+    if std::env::current_dir().is_ok_and(|cwd| cwd != project.root()) {
+        reporter.detail(&t!("pre.project_root", path = project.root().display()));
+    }
+    if project.is_legacy() {
+        reporter.warn(&t!("project.legacy_manifest", file = project.manifest_filename()));
+    }
+    // Read as far as it goes: fields this vk does not know are ignored
+    if let Ok(version) = project.manifest_version()
+        && version > manifest::MANIFEST_VERSION
+    {
+        reporter.warn(&t!(
+            "project.newer_manifest",
+            file = project.manifest_filename(),
+            version = version,
+            supported = manifest::MANIFEST_VERSION
+        ));
+    }
+    Ok(project)
+}
+
+/// The `[deploy.<name>]` table of the config.
+fn deploy_target<'a>(config: &'a AppConfig, name: &str) -> Result<&'a config::DeployTarget> {
+    config.deploy.get(name).ok_or_else(|| {
+        let known = config.deploy.keys().cloned().collect::<Vec<_>>().join(", ");
+        anyhow::anyhow!(t!("deploy.unknown_environment", name = name, known = known))
+    })
+}
+
+/// A client for a Vayload host rather than the registry. Without a token of
+/// its own, it sends `VK_HOST_TOKEN` when set.
+fn host_client(config: &AppConfig, url: &str, token: Option<String>) -> Result<HttpClient> {
+    let mut client = HttpClient::new(url, config.http.timeouts())?;
+    if let Some(token) = token.or_else(|| std::env::var(commands::logs::HOST_TOKEN_VAR).ok()) {
+        client.set_auth_fn(move || Some(token.clone()));
+    }
+    Ok(client)
+}
+
+/// Writes the `--report` file, whatever the outcome of the command, and
+/// passes the outcome on.
+fn finish_report(path: Option<&str>, recorder: &Recorder, result: Result<()>, reporter: &dyn Reporter) -> Result<()> {
+    if let Some(path) = path {
+        match recorder.write(path, &result) {
+            Ok(()) => reporter.detail(&t!("report.written", path = path)),
+            // The command's own failure matters more than the report's
+            Err(err) if result.is_err() => reporter.warn(&error::render(&err)),
+            Err(err) => return Err(err),
+        }
+    }
+    result
+}
+
+fn report_mirror(event: MirrorEvent, reporter: &dyn Reporter) {
+    match event {
+        MirrorEvent::Failover { from, to, reason } => reporter.warn(&t!(
+            "http.mirror_failover",
+            from = from,
+            to = to.cyan(),
+            reason = reason
+        )),
+        MirrorEvent::Serving { url } => reporter.info(&t!("http.mirror_serving", url = url.cyan())),
+    }
+}
+
+fn setup_client(
+    config: &AppConfig,
+    command: &str,
+    capture: Option<Arc<HttpCapture>>,
+    reporter: Arc<dyn Reporter>,
+) -> Result<HttpClient> {
     #[cfg(feature = "full")]
     {
-        let km = Arc::new(CredentialManager::new()?);
-        let registry_url = config.server.registry_url.clone();
-        setup_interactive_http_client(registry_url, km)
+        let km = Arc::new(CredentialManager::new()?.with_agent_timeout(config.auth.passphrase_cache()));
+        setup_interactive_http_client(config, command, km, capture, reporter)
     }
 
     #[cfg(not(feature = "full"))]
@@ -219,31 +1548,82 @@ fn setup_client(config: &AppConfig) -> Result<HttpClient> {
         let token =
             std::env::var("VK_API_TOKEN").context("VK_API_TOKEN environment variable is required for CI/CD mode")?;
 
-        HttpClient::new_with_token(config.server.registry_url.clone(), token)
+        let _ = reporter;
+        let mut client = HttpClient::new_with_token(config.server.registry_url.clone(), token, config.http.timeouts())?;
+        client.set_command(command);
+        if let Some(capture) = capture {
+            client.set_capture(capture);
+        }
+        Ok(client)
     }
 }
 
 #[cfg(feature = "full")]
-fn handle_full_commands(command: Commands, client: &HttpClient) -> Result<()> {
-    let km = Arc::new(CredentialManager::new()?);
-    let auth_handler = auth::AuthCommands::new(km.clone(), client.clone());
+fn handle_full_commands(
+    command: Commands,
+    config: &AppConfig,
+    client: &HttpClient,
+    reporter: Arc<dyn Reporter>,
+) -> Result<()> {
+    let km = Arc::new(CredentialManager::new()?.with_agent_timeout(config.auth.passphrase_cache()));
+    let auth_handler = auth::AuthCommands::new(km.clone(), client.clone(), reporter.clone())
+        .with_callback_ports(config.auth.callback_ports()?);
+    let r = reporter.as_ref();
 
     match command {
-        Commands::Init { yes, directory } => commands::init::init_project(yes, &directory)?,
-        Commands::Add { package, dev } => {
-            pre::ensure_manifest_exists()?;
-            commands::add::add_dependency(&package, dev, client)?
+        Commands::Init { yes, directory, ci, registry_url, license, no_git } => {
+            let registry_url = registry_url.unwrap_or_else(|| client.base_url().to_string());
+            let options = commands::init::InitOptions {
+                yes,
+                directory,
+                ci,
+                registry_url,
+                license: license.unwrap_or_else(|| config.init.license.clone()),
+                git: config.init.git && !no_git,
+                user: config.user.clone(),
+            };
+            commands::init::init_project(&options, r)?
+        },
+        Commands::Add { package, dev, optional, pre, git, path, rev, branch, tag } => {
+            let project = open_project(r)?;
+            let source = match (git, path) {
+                (Some(git), _) => Some(manifest::Dependency::Git(manifest::GitSource { git, rev, branch, tag })),
+                (None, Some(path)) => Some(manifest::Dependency::Path(manifest::PathSource { path })),
+                (None, None) => None,
+            };
+            let section = match (dev, optional) {
+                (true, _) => commands::add::Section::DevDependencies,
+                (_, true) => commands::add::Section::OptionalDependencies,
+                _ => commands::add::Section::Dependencies,
+            };
+            commands::add::add_dependency(&project, &package, section, pre, source, client, r)?
+        },
+        Commands::Remove { package, dir, ignore_scripts } => {
+            let project = open_project(r)?;
+            let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+            commands::remove::remove_dependency(&project, &package, &dir, ignore_scripts, r)?
+        },
+        Commands::Link { name, dir } => {
+            let project = open_project(r)?;
+            let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+            commands::link::link(&project, name.as_deref(), &dir, r)?
         },
-        Commands::Remove { package } => {
-            pre::ensure_manifest_exists()?;
-            commands::remove::remove_dependency(&package)?
+        Commands::Unlink { name, dir } => {
+            let project = open_project(r)?;
+            let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+            commands::link::unlink(&project, name.as_deref(), &dir, r)?
         },
-        Commands::Clean => {
-            pre::ensure_manifest_exists()?;
-            commands::clean::clean_cache()?
+        Commands::Clean { cache, artifacts, lockfile, all, dry_run, yes } => {
+            let project = open_project(r)?;
+            let options = commands::clean::CleanOptions { cache, artifacts, lockfile, all, dry_run, yes };
+            commands::clean::clean_cache(&project, &options, r)?
         },
-        Commands::Login { username, password, oauth } => {
-            if let Some(o) = oauth {
+        Commands::Login { username, password, oauth, refresh, sso } => {
+            if refresh {
+                auth_handler.refresh_session()?;
+            } else if let Some(org) = sso {
+                auth_handler.login_with_sso(&org)?;
+            } else if let Some(o) = oauth {
                 auth_handler.login_with_oauth(&o)?;
             } else {
                 auth_handler.login_with_password(username, password)?;
@@ -251,42 +1631,105 @@ fn handle_full_commands(command: Commands, client: &HttpClient) -> Result<()> {
         },
         Commands::Whoami => auth_handler.whoami()?,
         Commands::Logout => auth_handler.logout()?,
+        Commands::Auth { command } => match command {
+            AuthSubcommands::Export { file } => auth_handler.export_credentials(&file, &config.server.registry_url)?,
+            AuthSubcommands::Import { file } => auth_handler.import_credentials(&file, &config.server.registry_url)?,
+            AuthSubcommands::RotateKey => auth_handler.rotate_key()?,
+            AuthSubcommands::SetPassphrase => auth_handler.set_passphrase()?,
+            AuthSubcommands::RemovePassphrase => auth_handler.remove_passphrase()?,
+            AuthSubcommands::Lock => auth_handler.lock()?,
+            AuthSubcommands::Agent { socket, timeout } => {
+                agent::serve(&socket, std::time::Duration::from_secs(timeout))?
+            },
+        },
+        Commands::Telemetry { command } => match command {
+            TelemetryCommands::On => commands::telemetry::set_enabled(true, r)?,
+            TelemetryCommands::Off => commands::telemetry::set_enabled(false, r)?,
+            TelemetryCommands::Status => commands::telemetry::show_status(&config.telemetry_endpoint(), r)?,
+        },
+        Commands::Host { command } => match command {
+            HostCommands::Install { version, force } => commands::host::install(version.as_deref(), force, client, r)?,
+            HostCommands::List => commands::host::list(Project::locate().ok().as_ref(), r)?,
+            HostCommands::Pin { version, global } => {
+                let project = if global { None } else { Some(open_project(r)?) };
+                commands::host::pin(project.as_ref(), &version, r)?
+            },
+            HostCommands::Start { port, dir } => {
+                let project = open_project(r)?;
+                let dir = project.plugins_dir(dir.as_deref(), config.install.dir.as_ref())?;
+                commands::host::start(&project, port, &dir, r)?
+            },
+            HostCommands::Stop => commands::host::stop(&open_project(r)?, r)?,
+        },
+        Commands::Lsp => commands::lsp::serve(client)?,
         _ => unreachable!(),
     }
     Ok(())
 }
 
 #[cfg(feature = "full")]
-fn setup_interactive_http_client(api_url: String, km: Arc<CredentialManager>) -> Result<HttpClient> {
-    let mut http_client = HttpClient::new(api_url)?;
+fn setup_interactive_http_client(
+    config: &AppConfig,
+    command: &str,
+    km: Arc<CredentialManager>,
+    capture: Option<Arc<HttpCapture>>,
+    reporter: Arc<dyn Reporter>,
+) -> Result<HttpClient> {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let mut http_client = HttpClient::new(config.server.registry_url.clone(), config.http.timeouts())?;
+    // Before the copy token refreshes go through, so those are identified
+    // and captured too
+    http_client.set_command(command);
+    if let Some(capture) = capture {
+        http_client.set_capture(capture);
+    }
     let fresh_client = http_client.clone();
+    let callback_ports = config.auth.callback_ports()?;
+    // Refreshes and login prompts happen one at a time, and the prompt at
+    // most once per command
+    let refreshing = Mutex::new(());
+    let prompted = AtomicBool::new(false);
+    let locked = AtomicBool::new(false);
 
     http_client.set_auth_fn(move || {
-        use crate::auth::OAuthDataResponse;
+        let _guard = refreshing.lock().unwrap_or_else(|e| e.into_inner());
 
-        if km.is_refresh_token_expired() {
+        if !km.has_credentials() || locked.load(Ordering::Relaxed) {
+            return None;
+        }
+        // Without the passphrase, requests go without credentials
+        if let Err(err) = km.unlock() {
+            reporter.warn(&error::render(&err));
+            locked.store(true, Ordering::Relaxed);
             return None;
         }
         if !km.is_access_token_expired() {
             return km.get_access_token().ok();
         }
+        if !km.is_refresh_token_expired() {
+            match auth::refresh_tokens(&km, &fresh_client) {
+                Ok(token) => return Some(token),
+                // The registry no longer takes the refresh token
+                Err(err) if matches!(error::classify(&err).0, "auth-expired" | "registry-rejected") => {},
+                Err(_) => return None,
+            }
+        }
 
-        let refresh_token = km.get_refresh_token().ok()?;
-        let response = fresh_client
-            .post::<OAuthDataResponse, _>(
-                "/auth/refresh-token",
-                &serde_json::json!({ "refresh_token": refresh_token }),
-            )
-            .ok()?;
-
-        km.store_tokens(RawCredentials::new(
-            response.access_token.clone(),
-            response.refresh_token.clone(),
-            response.expires_in as u64,
-        ))
-        .ok()?;
-
-        Some(response.access_token)
+        if prompted.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        let auth_handler = auth::AuthCommands::new(km.clone(), fresh_client.clone(), reporter.clone())
+            .with_callback_ports(callback_ports.clone());
+        match auth_handler.reauthenticate() {
+            Ok(true) => km.get_access_token().ok(),
+            Ok(false) => None,
+            Err(err) => {
+                reporter.warn(&error::render(&err));
+                None
+            },
+        }
     });
 
     Ok(http_client)
@@ -1,14 +1,69 @@
 /// The configuration of the plugin.
 /// This struct contains all the necessary information about the plugin.
 ///
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::i18n::t;
 
 pub const MANIFEST_FILENAME: &str = "plugin.json5";
 pub const VKIGNORE_FILENAME: &str = ".vkignore";
 
+/// The manifest schema this vk reads and writes. Manifests without a
+/// `manifest_version` are version 1; version 2 declares it, and drops the
+/// npm-style spellings of `DEPRECATED_FIELDS` some version 1 manifests carry.
+pub const MANIFEST_VERSION: u32 = 2;
+
+/// Fields of a manifest, in the order vk writes them.
+pub const MANIFEST_FIELDS: &[&str] = &[
+    "manifest_version",
+    "name",
+    "display_name",
+    "version",
+    "description",
+    "license",
+    "keywords",
+    "tags",
+    "homepage",
+    "repository",
+    "author",
+    "contributors",
+    "main",
+    "engines",
+    "dependencies",
+    "dev_dependencies",
+    "host_dependencies",
+    "optional_dependencies",
+    "target_dependencies",
+    "features",
+    "overrides",
+    "permissions",
+    "config",
+    "files",
+    "exclude",
+    "packaging",
+    "assets",
+    "scripts",
+    "hooks",
+    "install_dir",
+];
+
+/// npm-style spellings of manifest fields, which vk does not read, with the
+/// field it reads instead.
+pub const DEPRECATED_FIELDS: &[(&str, &str)] = &[
+    ("displayName", "display_name"),
+    ("devDependencies", "dev_dependencies"),
+    ("hostDependencies", "host_dependencies"),
+    ("optionalDependencies", "optional_dependencies"),
+    ("targetDependencies", "target_dependencies"),
+    ("installDir", "install_dir"),
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginManifest {
+    /// The schema the manifest is written in (see `MANIFEST_VERSION`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_version: Option<u32>,
     pub name: String,
     pub display_name: String,
     pub version: String,
@@ -23,18 +78,66 @@ pub struct PluginManifest {
     pub main: String,
     pub engines: Engines,
 
-    #[serde(default)]
-    pub dependencies: HashMap<String, String>,
-    pub dev_dependencies: Option<HashMap<String, String>>,
+    #[serde(default, serialize_with = "sorted")]
+    pub dependencies: HashMap<String, Dependency>,
+    #[serde(serialize_with = "sorted_opt")]
+    pub dev_dependencies: Option<HashMap<String, Dependency>>,
+    #[serde(serialize_with = "sorted_opt")]
     pub host_dependencies: Option<HashMap<String, String>>,
+    /// Dependencies only installed when a feature enables them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "sorted")]
+    pub optional_dependencies: HashMap<String, Dependency>,
+    /// Dependencies only installed on the hosts matching their conditions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub target_dependencies: Vec<TargetDependencies>,
+    /// Named sets of optional dependencies and other features, turned on
+    /// with `vk install --features`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Versions (or sources) forced on packages anywhere in the dependency
+    /// tree, whatever range the packages depending on them ask for: a
+    /// security pin, or a fork with a fix.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, Dependency>,
 
     pub permissions: Option<Permissions>,
     pub config: Option<PluginConfig>,
+
+    /// Paths (gitignore patterns, relative to the project root) to package.
+    /// When set, ignore files are not consulted; the manifest, README and
+    /// LICENSE are always included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+    /// Patterns never packaged, whatever `files` or the ignore files say.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// What the package archive keeps besides regular files.
+    #[serde(default, skip_serializing_if = "Packaging::is_default")]
+    pub packaging: Packaging,
+    /// Large files kept out of the archive and fetched by installs on their
+    /// own.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<Asset>,
+
+    /// Named shell commands run by `vk run-script` and lifecycle hooks
+    /// such as `prepublish`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scripts: BTreeMap<String, String>,
+
+    /// Lua scripts run by the host runtime in projects that install this
+    /// plugin, after the user has reviewed them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+
+    /// Where this project installs its dependencies, instead of `plugins/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_dir: Option<InstallDir>,
 }
 
 impl Default for PluginManifest {
     fn default() -> Self {
         Self {
+            manifest_version: Some(MANIFEST_VERSION),
             name: String::new(),
             display_name: String::new(),
             version: "0.1.0".into(),
@@ -51,19 +154,218 @@ impl Default for PluginManifest {
             dependencies: HashMap::new(),
             dev_dependencies: None,
             host_dependencies: None,
+            optional_dependencies: HashMap::new(),
+            target_dependencies: Vec::new(),
+            features: BTreeMap::new(),
+            overrides: BTreeMap::new(),
             permissions: Some(Permissions::default()),
             config: Some(PluginConfig::default()),
+            files: None,
+            exclude: Vec::new(),
+            packaging: Packaging::default(),
+            assets: Vec::new(),
+            scripts: BTreeMap::new(),
+            hooks: None,
+            install_dir: None,
         }
     }
 }
 
+/// Writes maps in key order so a manifest always serializes the same way.
+fn sorted<S: Serializer, V: Serialize>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+fn sorted_opt<S: Serializer, V: Serialize>(map: &Option<HashMap<String, V>>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.as_ref().map(|m| m.iter().collect::<BTreeMap<_, _>>()).serialize(serializer)
+}
+
+/// Only the schema version of a manifest, which still reads when the rest
+/// is in a schema vk does not know.
+#[derive(Debug, Deserialize)]
+pub struct SchemaVersion {
+    #[serde(default)]
+    pub manifest_version: Option<u32>,
+}
+
 impl PluginManifest {
+    /// Fails on a git dependency whose URL or ref git would take for an
+    /// option, naming the dependency.
+    pub fn check_git_sources(&self) -> Result<(), String> {
+        let sections = [Some(&self.dependencies), self.dev_dependencies.as_ref(), Some(&self.optional_dependencies)];
+        let targets = self.target_dependencies.iter().map(|target| Some(&target.dependencies));
+        for (name, dependency) in sections.into_iter().chain(targets).flatten().flatten() {
+            if let Dependency::Git(source) = dependency {
+                source.check().map_err(|value| t!("manifest.git_option", name = name, value = value))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The schema version, 1 for manifests that do not declare one.
+    pub fn schema_version(&self) -> u32 {
+        self.manifest_version.unwrap_or(1)
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.name = name.clone().to_lowercase().replace(" ", "-");
         self.display_name = name;
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packaging {
+    /// Store symlinks as links. They must point inside the package; links to
+    /// files are otherwise packaged as the file they point to, links to
+    /// directories are left out.
+    #[serde(default)]
+    pub symlinks: bool,
+    /// Keep directories with nothing packaged in them.
+    #[serde(default)]
+    pub empty_dirs: bool,
+}
+
+impl Packaging {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A file of the plugin left out of its archive, such as a model or a media
+/// file: uploaded next to the package by `vk publish`, or hosted elsewhere
+/// and named by its URL. Installs fetch it and check its hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// Where the file goes, relative to the plugin root.
+    pub path: String,
+    /// Where installs download it from. Without one, the file is uploaded
+    /// to the registry with the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// SHA-256 of the file. Required with `url`; filled in by `vk publish`
+    /// for uploaded files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Size in bytes, filled in by `vk publish`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// A plugins directory, relative to the project root: one path, or one per
+/// environment (`VK_ENV`) with a `default` for the others, e.g.
+/// `{ default: "plugins", production: "/srv/host/plugins" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InstallDir {
+    Path(String),
+    PerEnvironment(BTreeMap<String, String>),
+}
+
+impl InstallDir {
+    /// The directory for `environment`, falling back to the `default` entry.
+    pub fn resolve(&self, environment: Option<&str>) -> Option<&str> {
+        match self {
+            InstallDir::Path(path) => Some(path),
+            InstallDir::PerEnvironment(dirs) => {
+                environment.and_then(|env| dirs.get(env)).or_else(|| dirs.get("default")).map(String::as_str)
+            },
+        }
+    }
+}
+
+/// A dependency entry: a registry version range (`"^1.2.0"`, `"beta"`) or a
+/// detailed source such as `{ git: "https://...", rev: "abc123" }` or
+/// `{ path: "../my-lib" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(String),
+    Git(GitSource),
+    Path(PathSource),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitSource {
+    pub git: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// A plugin in a local directory, relative to the project root. Meant for
+/// developing several plugins together; it cannot be published as-is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathSource {
+    pub path: String,
+}
+
+impl Dependency {
+    /// The version range of a registry dependency.
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            Dependency::Version(version) => Some(version),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Dependency {
+    fn from(version: String) -> Self {
+        Dependency::Version(version)
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dependency::Version(version) => f.write_str(version),
+            Dependency::Git(source) => write!(f, "{}", source),
+            Dependency::Path(source) => write!(f, "path+{}", source.path),
+        }
+    }
+}
+
+impl GitSource {
+    /// Fails with the URL or ref that starts with `-`, which git would read
+    /// as an option (`--upload-pack=<command>` runs a command).
+    pub fn check(&self) -> Result<(), &str> {
+        let values = [Some(&self.git), self.rev.as_ref(), self.branch.as_ref(), self.tag.as_ref()];
+        match values.into_iter().flatten().find(|value| value.starts_with('-')) {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// The ref to check out, most specific first. `None` means the default branch.
+    pub fn reference(&self) -> Option<&str> {
+        self.rev.as_deref().or(self.tag.as_deref()).or(self.branch.as_deref())
+    }
+
+    /// Identifies the requested source in the lockfile, e.g.
+    /// `git+https://github.com/acme/plugin?branch=dev`, so a pin is dropped
+    /// when the manifest starts asking for something else.
+    pub fn locator(&self) -> String {
+        let selector = [("rev", &self.rev), ("tag", &self.tag), ("branch", &self.branch)]
+            .into_iter()
+            .find_map(|(kind, value)| value.as_ref().map(|v| format!("?{}={}", kind, v)))
+            .unwrap_or_default();
+        format!("git+{}{}", self.git, selector)
+    }
+}
+
+impl std::fmt::Display for GitSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "git+{}", self.git)?;
+        if let Some(reference) = self.reference() {
+            write!(f, "#{}", reference)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Repository {
     pub r#type: String,
@@ -88,6 +390,40 @@ impl Default for Engines {
     }
 }
 
+/// Dependencies that only apply where `when` holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetDependencies {
+    pub when: Condition,
+    #[serde(serialize_with = "sorted")]
+    pub dependencies: HashMap<String, Dependency>,
+}
+
+/// Where a set of dependencies applies. Every field given must match; an
+/// empty list matches anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Condition {
+    /// Operating systems, as Rust names them (`linux`, `macos`, `windows`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+    /// CPU architectures, as Rust names them (`x86_64`, `aarch64`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch: Vec<String>,
+    /// Range of host runtime versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+/// Lifecycle hooks of a plugin, as paths to Lua scripts inside it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Hooks {
+    /// Run after the plugin is installed into a project.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postinstall: Option<String>,
+    /// Run before the plugin is removed from a project.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preremove: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Permissions {
     pub filesystem: Option<FileSystemPermission>,
@@ -189,6 +525,7 @@ impl PluginAccess {
     }
 
     #[allow(unused)]
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s {
             "public" => Ok(PluginAccess::Public),
@@ -197,3 +534,47 @@ impl PluginAccess {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(url: &str, rev: Option<&str>) -> GitSource {
+        GitSource {
+            git: url.into(),
+            rev: rev.map(String::from),
+            branch: None,
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn test_git_sources_read_as_options() {
+        assert!(git("https://git.example.com/acme/ui.git", Some("v1.2.0")).check().is_ok());
+        assert!(git("git@git.example.com:acme/ui.git", Some("a1b2c3d")).check().is_ok());
+        assert_eq!(
+            git("--upload-pack=touch /tmp/x", None).check(),
+            Err("--upload-pack=touch /tmp/x")
+        );
+        assert_eq!(
+            git("https://git.example.com/acme/ui.git", Some("-oProxyCommand=x")).check(),
+            Err("-oProxyCommand=x")
+        );
+
+        let mut manifest = PluginManifest::default();
+        manifest.dependencies.insert(
+            "ui".into(),
+            Dependency::Git(git("https://git.example.com/acme/ui.git", None)),
+        );
+        assert!(manifest.check_git_sources().is_ok());
+
+        let mut dev = HashMap::new();
+        dev.insert(
+            "evil".into(),
+            Dependency::Git(git("https://git.example.com/acme/evil.git", Some("--output=x"))),
+        );
+        manifest.dev_dependencies = Some(dev);
+        let err = manifest.check_git_sources().unwrap_err();
+        assert!(err.contains("evil") && err.contains("--output=x"), "{}", err);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::i18n::t;
+use crate::manifest::{FileSystemPermission, FileSystemScope, Limits, NetworkPermission, Permissions};
+
+/// Human-readable lines summarizing what a plugin asks to be allowed to do,
+/// shown before running its hooks.
+pub fn describe(permissions: Option<&Permissions>) -> Vec<String> {
+    let Some(permissions) = permissions else {
+        return vec![t!("permissions.none")];
+    };
+
+    let mut lines = Vec::new();
+
+    if let Some(fs) = &permissions.filesystem {
+        lines.push(t!("permissions.filesystem", scope = scope_label(&fs.scope)));
+        if !fs.allow.is_empty() {
+            lines.push(format!("  {}", t!("permissions.fs_allow", paths = fs.allow.join(", "))));
+        }
+        if !fs.deny.is_empty() {
+            lines.push(format!("  {}", t!("permissions.fs_deny", paths = fs.deny.join(", "))));
+        }
+    }
+
+    if let Some(net) = &permissions.network {
+        if net.allow_outbound.is_empty() {
+            lines.push(t!("permissions.outbound_none"));
+        } else {
+            lines.push(t!("permissions.outbound", hosts = net.allow_outbound.join(", ")));
+        }
+        if net.allow_inbound {
+            lines.push(t!("permissions.inbound"));
+        }
+    }
+
+    if let Some(limits) = &permissions.limits {
+        lines.push(t!(
+            "permissions.limits",
+            memory = limits.max_memory_mb,
+            time = limits.max_execution_time_ms,
+            threads = limits.max_threads
+        ));
+    }
+
+    if lines.is_empty() {
+        lines.push(t!("permissions.none"));
+    }
+    lines
+}
+
+/// What `new` allows beyond `old`, one line per escalation: a wider
+/// filesystem scope or more allowed paths, new outbound hosts, inbound access
+/// and higher resource limits. Missing sections grant nothing, except limits,
+/// which fall back to the host defaults.
+pub fn escalations(old: Option<&Permissions>, new: Option<&Permissions>) -> Vec<String> {
+    let empty = Permissions::default();
+    let (old, new) = (old.unwrap_or(&empty), new.unwrap_or(&empty));
+    let mut lines = Vec::new();
+
+    let no_fs = FileSystemPermission::default();
+    let (old_fs, new_fs) = (
+        old.filesystem.as_ref().unwrap_or(&no_fs),
+        new.filesystem.as_ref().unwrap_or(&no_fs),
+    );
+    if scope_rank(&new_fs.scope) > scope_rank(&old_fs.scope) {
+        lines.push(t!("permissions.filesystem", scope = scope_label(&new_fs.scope)));
+    }
+    let paths: Vec<&str> = new_fs.allow.iter().filter(|p| !old_fs.allow.contains(p)).map(String::as_str).collect();
+    if !paths.is_empty() {
+        lines.push(t!("permissions.fs_allow_added", paths = paths.join(", ")));
+    }
+    let lifted: Vec<&str> = old_fs.deny.iter().filter(|p| !new_fs.deny.contains(p)).map(String::as_str).collect();
+    if !lifted.is_empty() {
+        lines.push(t!("permissions.fs_deny_lifted", paths = lifted.join(", ")));
+    }
+
+    let no_net = NetworkPermission::default();
+    let (old_net, new_net) = (
+        old.network.as_ref().unwrap_or(&no_net),
+        new.network.as_ref().unwrap_or(&no_net),
+    );
+    let hosts: Vec<&str> = new_net
+        .allow_outbound
+        .iter()
+        .filter(|h| !old_net.allow_outbound.contains(h))
+        .map(String::as_str)
+        .collect();
+    if !hosts.is_empty() {
+        lines.push(t!("permissions.outbound", hosts = hosts.join(", ")));
+    }
+    if new_net.allow_inbound && !old_net.allow_inbound {
+        lines.push(t!("permissions.inbound"));
+    }
+
+    let defaults = Limits::default();
+    let (old_lim, new_lim) = (
+        old.limits.as_ref().unwrap_or(&defaults),
+        new.limits.as_ref().unwrap_or(&defaults),
+    );
+    if new_lim.max_memory_mb > old_lim.max_memory_mb {
+        lines.push(t!(
+            "permissions.memory_raised",
+            from = old_lim.max_memory_mb,
+            to = new_lim.max_memory_mb
+        ));
+    }
+    if new_lim.max_execution_time_ms > old_lim.max_execution_time_ms {
+        lines.push(t!(
+            "permissions.time_raised",
+            from = old_lim.max_execution_time_ms,
+            to = new_lim.max_execution_time_ms
+        ));
+    }
+    if new_lim.max_threads > old_lim.max_threads {
+        lines.push(t!(
+            "permissions.threads_raised",
+            from = old_lim.max_threads,
+            to = new_lim.max_threads
+        ));
+    }
+
+    lines
+}
+
+/// Orders scopes from least to most access.
+pub fn scope_rank(scope: &FileSystemScope) -> u8 {
+    match scope {
+        FileSystemScope::None => 0,
+        FileSystemScope::ReadOnly => 1,
+        FileSystemScope::ReadWrite => 2,
+    }
+}
+
+pub fn scope_label(scope: &FileSystemScope) -> String {
+    match scope {
+        FileSystemScope::ReadOnly => t!("permissions.fs_read_only"),
+        FileSystemScope::ReadWrite => t!("permissions.fs_read_write"),
+        FileSystemScope::None => t!("permissions.fs_none"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permissions(scope: FileSystemScope, hosts: &[&str], memory: u32) -> Permissions {
+        Permissions::new(
+            FileSystemPermission { scope, allow: Vec::new(), deny: Vec::new() },
+            NetworkPermission::new(hosts.iter().map(|h| h.to_string()).collect(), false),
+            Limits { max_memory_mb: memory, ..Limits::default() },
+        )
+    }
+
+    #[test]
+    fn test_no_escalation() {
+        let old = permissions(FileSystemScope::ReadWrite, &["a.com", "b.com"], 256);
+        let new = permissions(FileSystemScope::ReadOnly, &["a.com"], 128);
+        assert!(escalations(Some(&old), Some(&new)).is_empty());
+        assert!(escalations(Some(&old), Some(&old)).is_empty());
+        assert!(escalations(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_escalations_are_listed() {
+        let old = permissions(FileSystemScope::ReadOnly, &["a.com"], 128);
+        let mut new = permissions(FileSystemScope::ReadWrite, &["a.com", "b.com"], 512);
+        new.network.as_mut().unwrap().allow_inbound = true;
+
+        let lines = escalations(Some(&old), Some(&new));
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().any(|l| l.contains("b.com") && !l.contains("a.com")));
+        assert!(lines.iter().any(|l| l.contains("512")));
+    }
+
+    #[test]
+    fn test_missing_permissions_grant_nothing() {
+        let new = permissions(FileSystemScope::None, &["a.com"], 128);
+        assert_eq!(escalations(None, Some(&new)).len(), 1);
+        assert!(escalations(Some(&new), None).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use globset::Glob;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::encoding::json5;
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::manifest::{FileSystemScope, Limits, Permissions};
+use crate::permissions::{scope_label, scope_rank};
+use crate::project::Project;
+use crate::reporter::Reporter;
+
+pub const POLICY_FILE: &str = "policy.json5";
+
+/// Ceilings on the permissions plugins may request, set by an organization
+/// for every project (`~/.vayload-kit/policy.json5`) or for one project
+/// (`.vk/policy.json5`, which takes precedence). `VK_POLICY` points at a
+/// policy file elsewhere.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    pub enforcement: Enforcement,
+    pub filesystem: FileSystemPolicy,
+    pub network: NetworkPolicy,
+    pub limits: LimitsPolicy,
+    pub licenses: LicensePolicy,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Enforcement {
+    /// Plugins exceeding the policy are rejected.
+    #[default]
+    Deny,
+    /// Violations are only reported.
+    Warn,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FileSystemPolicy {
+    /// Widest filesystem scope a plugin may request.
+    pub max_scope: Option<FileSystemScope>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NetworkPolicy {
+    /// `false` forbids plugins that accept inbound connections.
+    pub allow_inbound: Option<bool>,
+    /// Host patterns (`*.example.com`) plugins may connect to. Any host is
+    /// allowed when unset.
+    pub allowed_outbound: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LimitsPolicy {
+    pub max_memory_mb: Option<u32>,
+    pub max_execution_time_ms: Option<u64>,
+    pub max_threads: Option<u16>,
+}
+
+/// Licenses dependencies may carry, checked by `vk audit`. Entries are SPDX
+/// identifiers, compared without regard to case.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LicensePolicy {
+    /// Any license is allowed when unset.
+    pub allowed: Option<Vec<String>>,
+    pub denied: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// Whether a package under the SPDX `expression` can be used: one of its
+    /// `OR` alternatives must have every `AND` term allowed. Exceptions
+    /// (`WITH ...`) and parentheses are not considered.
+    pub fn permits(&self, expression: &str) -> bool {
+        let expression = expression.replace(['(', ')'], " ");
+        let words: Vec<&str> = expression.split_whitespace().collect();
+        words.split(|word| word.eq_ignore_ascii_case("or")).any(|alternative| {
+            alternative
+                .split(|word| word.eq_ignore_ascii_case("and"))
+                .all(|term| term.first().is_some_and(|id| self.allows(id)))
+        })
+    }
+
+    fn allows(&self, id: &str) -> bool {
+        let listed = |list: &[String]| list.iter().any(|l| l.eq_ignore_ascii_case(id));
+        !listed(&self.denied) && self.allowed.as_deref().is_none_or(listed)
+    }
+}
+
+/// Loads the policy that applies to `project`, if any.
+pub fn load(project: Option<&Project>) -> Result<Option<Policy>> {
+    let candidates = [
+        std::env::var_os("VK_POLICY").map(PathBuf::from),
+        project.map(|p| p.cache_dir().join(POLICY_FILE)),
+        dirs::home_dir().map(|home| home.join(".vayload-kit").join(POLICY_FILE)),
+    ];
+
+    let Some(path) = candidates.into_iter().flatten().find(|p| p.is_file()) else {
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&path).with_context(|| t!("common.read_failed", path = path.display()))?;
+    let policy = json5::from_str(&content).with_context(|| t!("policy.parse_failed", path = path.display()))?;
+    Ok(Some(policy))
+}
+
+impl Policy {
+    /// Everything in `permissions` the policy does not allow, one line each.
+    pub fn violations(&self, permissions: Option<&Permissions>) -> Vec<String> {
+        let empty = Permissions::default();
+        let permissions = permissions.unwrap_or(&empty);
+        let mut lines = Vec::new();
+
+        if let (Some(fs), Some(max)) = (&permissions.filesystem, &self.filesystem.max_scope)
+            && scope_rank(&fs.scope) > scope_rank(max)
+        {
+            lines.push(t!(
+                "policy.filesystem",
+                scope = scope_label(&fs.scope),
+                max = scope_label(max)
+            ));
+        }
+
+        if let Some(net) = &permissions.network {
+            if net.allow_inbound && self.network.allow_inbound == Some(false) {
+                lines.push(t!("policy.inbound"));
+            }
+            if let Some(allowed) = &self.network.allowed_outbound {
+                let patterns: Vec<_> = allowed
+                    .iter()
+                    .filter_map(|p| Glob::new(&p.to_ascii_lowercase()).ok())
+                    .map(|g| g.compile_matcher())
+                    .collect();
+                for host in &net.allow_outbound {
+                    if !patterns.iter().any(|p| p.is_match(host.to_ascii_lowercase())) {
+                        lines.push(t!("policy.outbound", host = host));
+                    }
+                }
+            }
+        }
+
+        let defaults = Limits::default();
+        let limits = permissions.limits.as_ref().unwrap_or(&defaults);
+        if let Some(max) = self.limits.max_memory_mb.filter(|max| limits.max_memory_mb > *max) {
+            lines.push(t!("policy.memory", value = limits.max_memory_mb, max = max));
+        }
+        if let Some(max) = self.limits.max_execution_time_ms.filter(|max| limits.max_execution_time_ms > *max) {
+            lines.push(t!("policy.time", value = limits.max_execution_time_ms, max = max));
+        }
+        if let Some(max) = self.limits.max_threads.filter(|max| limits.max_threads > *max) {
+            lines.push(t!("policy.threads", value = limits.max_threads, max = max));
+        }
+
+        lines
+    }
+
+    /// Reports how `plugin` exceeds the policy, failing when it is enforced.
+    pub fn check(&self, plugin: &str, permissions: Option<&Permissions>, reporter: &dyn Reporter) -> Result<()> {
+        let violations = self.violations(permissions);
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        reporter.warn(&t!("policy.exceeded", plugin = plugin.cyan()));
+        for line in &violations {
+            reporter.output(&format!("  {}", format!("- {}", line).red()));
+        }
+
+        match self.enforcement {
+            Enforcement::Deny => Err(VkError::PolicyViolation(plugin.to_string()).into()),
+            Enforcement::Warn => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{FileSystemPermission, NetworkPermission};
+
+    fn policy(source: &str) -> Policy {
+        json5::from_str(source).unwrap()
+    }
+
+    fn permissions(scope: FileSystemScope, hosts: &[&str], inbound: bool, memory: u32) -> Permissions {
+        Permissions::new(
+            FileSystemPermission { scope, ..FileSystemPermission::default() },
+            NetworkPermission::new(hosts.iter().map(|h| h.to_string()).collect(), inbound),
+            Limits { max_memory_mb: memory, ..Limits::default() },
+        )
+    }
+
+    #[test]
+    fn test_empty_policy_allows_everything() {
+        let p = permissions(FileSystemScope::ReadWrite, &["a.com"], true, 4096);
+        assert!(policy("{}").violations(Some(&p)).is_empty());
+    }
+
+    #[test]
+    fn test_ceilings() {
+        let policy = policy(
+            r#"{
+                filesystem: { max_scope: "read-only" },
+                network: { allow_inbound: false, allowed_outbound: ["*.example.com", "api.vayload.dev"] },
+                limits: { max_memory_mb: 256 },
+            }"#,
+        );
+
+        let ok = permissions(
+            FileSystemScope::ReadOnly,
+            &["cdn.example.com", "api.vayload.dev"],
+            false,
+            256,
+        );
+        assert!(policy.violations(Some(&ok)).is_empty());
+        assert!(policy.violations(None).is_empty());
+
+        let bad = permissions(FileSystemScope::ReadWrite, &["cdn.example.com", "evil.net"], true, 512);
+        let violations = policy.violations(Some(&bad));
+        assert_eq!(violations.len(), 4);
+        assert!(violations.iter().any(|v| v.contains("evil.net")));
+    }
+
+    #[test]
+    fn test_license_expressions() {
+        let licenses =
+            policy(r#"{ licenses: { allowed: ["MIT", "Apache-2.0", "BSD-3-Clause"], denied: ["GPL-3.0-only"] } }"#)
+                .licenses;
+        assert!(licenses.permits("MIT"));
+        assert!(licenses.permits("mit"));
+        assert!(licenses.permits("GPL-3.0-only OR MIT"));
+        assert!(licenses.permits("(Apache-2.0 AND BSD-3-Clause)"));
+        assert!(licenses.permits("Apache-2.0 WITH LLVM-exception"));
+        assert!(!licenses.permits("MIT AND GPL-3.0-only"));
+        assert!(!licenses.permits("MPL-2.0"));
+        assert!(!licenses.permits(""));
+
+        let denied_only = policy(r#"{ licenses: { denied: ["AGPL-3.0-only"] } }"#).licenses;
+        assert!(denied_only.permits("MPL-2.0"));
+        assert!(!denied_only.permits("AGPL-3.0-only"));
+    }
+
+    #[test]
+    fn test_enforcement_defaults_to_deny() {
+        assert_eq!(policy("{}").enforcement, Enforcement::Deny);
+        assert_eq!(policy(r#"{ enforcement: "warn" }"#).enforcement, Enforcement::Warn);
+    }
+}
@@ -1,21 +1,11 @@
 use anyhow::Result;
-use std::path::Path;
 
-use crate::manifest::MANIFEST_FILENAME;
+use crate::project::Project;
 
 /// This package contains the pre-run command for the vayload-kit commands;
 ///
-pub fn ensure_manifest_exists() -> Result<()> {
-    let manifest_path = Path::new(MANIFEST_FILENAME);
-
-    if !manifest_path.exists() {
-        anyhow::bail!(
-            "No {} found in the current directory.\n\
-             This command must be run inside a Vayload project.\n\
-             Run `vk init` to create a new project.",
-            MANIFEST_FILENAME
-        );
-    }
-
-    Ok(())
+/// Resolves the project the current directory belongs to. Every project
+/// command runs relative to its root.
+pub fn ensure_manifest_exists() -> Result<Project> {
+    Project::locate()
 }
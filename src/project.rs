@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::json5;
+use crate::error::VkError;
+use crate::i18n::t;
+use crate::manifest::{InstallDir, MANIFEST_FILENAME, MANIFEST_VERSION, PluginManifest, SchemaVersion};
+
+/// Manifest names used by older releases, still read until `vk migrate` is run.
+pub const LEGACY_MANIFEST_FILENAMES: &[&str] = &["plugin.json"];
+
+pub const LOCKFILE_NAME: &str = "vayload.lock";
+pub const CACHE_DIR: &str = ".vk";
+pub const VENDOR_DIR: &str = "vendor";
+pub const DEFAULT_PLUGINS_DIR: &str = "plugins";
+/// Names the environment whose `install_dir` applies, e.g. `production`.
+pub const ENVIRONMENT_VAR: &str = "VK_ENV";
+
+/// A Vayload project on disk: its root directory and the manifest found in it.
+///
+/// All path handling for project files goes through this type so commands
+/// never hard-code file names or assume the current directory is the root.
+#[derive(Debug, Clone)]
+pub struct Project {
+    root: PathBuf,
+    manifest_path: PathBuf,
+}
+
+impl Project {
+    /// Looks for a manifest in `dir` only, preferring the current format over
+    /// legacy names.
+    pub fn at(dir: &Path) -> Option<Self> {
+        std::iter::once(MANIFEST_FILENAME)
+            .chain(LEGACY_MANIFEST_FILENAMES.iter().copied())
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+            .map(|manifest_path| Self { root: dir.to_path_buf(), manifest_path })
+    }
+
+    /// Walks up from `start` to the first directory holding a manifest,
+    /// the same way cargo and git locate their project root.
+    pub fn discover(start: &Path) -> Option<Self> {
+        start.ancestors().find_map(Self::at)
+    }
+
+    /// Discovers the project enclosing the current directory.
+    pub fn locate() -> Result<Self> {
+        let current_dir = std::env::current_dir().with_context(|| t!("common.current_dir_failed"))?;
+
+        Self::discover(&current_dir)
+            .ok_or_else(|| VkError::ManifestMissing { file: MANIFEST_FILENAME.to_string() }.into())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    pub fn manifest_filename(&self) -> &str {
+        self.manifest_path.file_name().and_then(|n| n.to_str()).unwrap_or(MANIFEST_FILENAME)
+    }
+
+    /// Whether the manifest uses a name from an older release.
+    pub fn is_legacy(&self) -> bool {
+        self.manifest_filename() != MANIFEST_FILENAME
+    }
+
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.root.join(LOCKFILE_NAME)
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.root.join(CACHE_DIR)
+    }
+
+    /// Where the project's plugins live, relative to its root: `--dir` when
+    /// given, else the manifest's `install_dir`, else the configured one, else
+    /// `plugins`. Every command touching installed plugins resolves it here,
+    /// so they all agree.
+    pub fn plugins_dir(&self, flag: Option<&str>, configured: Option<&InstallDir>) -> Result<String> {
+        if let Some(dir) = flag {
+            return Ok(dir.to_string());
+        }
+
+        let environment = std::env::var(ENVIRONMENT_VAR).ok().filter(|e| !e.is_empty());
+        let manifest = self.read_manifest()?;
+        let dir = [manifest.install_dir.as_ref(), configured]
+            .into_iter()
+            .flatten()
+            .find_map(|dir| dir.resolve(environment.as_deref()))
+            .unwrap_or(DEFAULT_PLUGINS_DIR);
+        Ok(dir.to_string())
+    }
+
+    /// Where `vk vendor` keeps the archives of registry dependencies.
+    pub fn vendor_dir(&self) -> PathBuf {
+        self.root.join(VENDOR_DIR)
+    }
+
+    /// The schema version the manifest declares, read on its own so it is
+    /// known even when the rest of the manifest does not parse.
+    pub fn manifest_version(&self) -> Result<u32> {
+        let content = fs::read_to_string(&self.manifest_path).with_context(|| t!("manifest.read_failed"))?;
+        Ok(schema_version(&content))
+    }
+
+    pub fn read_manifest(&self) -> Result<PluginManifest> {
+        let content = fs::read_to_string(&self.manifest_path).with_context(|| t!("manifest.read_failed"))?;
+        let manifest: PluginManifest = json5::from_str(&content)
+            .map_err(|e| match schema_version(&content) {
+                // A newer schema is the likely reason it does not parse
+                version if version > MANIFEST_VERSION => {
+                    VkError::ManifestUnsupported { version, supported: MANIFEST_VERSION }
+                },
+                _ => VkError::ManifestInvalid(e.to_string()),
+            })
+            .with_context(|| t!("manifest.parse_failed"))?;
+        manifest.check_git_sources().map_err(VkError::ManifestInvalid)?;
+        Ok(manifest)
+    }
+
+    /// Writes the manifest back to the file it was read from. Legacy manifests
+    /// are plain JSON, so they are kept in that format. Manifests in a newer
+    /// schema are not written, as the fields vk does not know would be lost.
+    pub fn write_manifest(&self, manifest: &PluginManifest) -> Result<()> {
+        let version = manifest.schema_version();
+        if version > MANIFEST_VERSION {
+            return Err(VkError::ManifestUnsupported { version, supported: MANIFEST_VERSION }.into());
+        }
+        let content = self.render_manifest(manifest)?;
+        fs::write(&self.manifest_path, content).with_context(|| t!("manifest.write_failed"))
+    }
+
+    /// Serializes `manifest` in the format of the project's manifest file.
+    pub fn render_manifest(&self, manifest: &PluginManifest) -> Result<String> {
+        Ok(if self.is_legacy() {
+            serde_json::to_string_pretty(manifest)?
+        } else {
+            json5::to_string_pretty(manifest)?
+        })
+    }
+}
+
+/// The `manifest_version` of the manifest `content`, 1 when it has none.
+fn schema_version(content: &str) -> u32 {
+    json5::from_str::<SchemaVersion>(content).ok().and_then(|s| s.manifest_version).unwrap_or(1)
+}
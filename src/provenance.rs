@@ -0,0 +1,297 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::error::VkError;
+use crate::git::{self, BuildInfo};
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::lockfile::Lockfile;
+use crate::manifest::PluginManifest;
+use crate::project::Project;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+
+/// Environment variable holding the publisher key (base64), the secret the
+/// registry knows the publisher by and checks provenance signatures with.
+pub const PUBLISHER_KEY_VAR: &str = "VK_PUBLISHER_KEY";
+const MIN_KEY_LEN: usize = 32;
+
+const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+const BUILD_TYPE: &str = "https://vayload.dev/vk/publish@v1";
+
+/// A signed statement, in the DSSE envelope format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+    /// The statement, base64-encoded.
+    pub payload: String,
+    pub signatures: Vec<Signature>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    pub keyid: String,
+    /// HMAC-SHA256 of the envelope's pre-authentication encoding, base64.
+    pub sig: String,
+}
+
+/// What the registry keeps for a published version.
+#[derive(Debug, Deserialize)]
+pub struct Attestation {
+    /// Whether the registry checked the signature against the publisher's key.
+    verified: bool,
+    envelope: Envelope,
+}
+
+/// The publisher key, from `VK_PUBLISHER_KEY`.
+pub fn publisher_key() -> Result<Vec<u8>> {
+    let encoded = std::env::var(PUBLISHER_KEY_VAR)
+        .ok()
+        .filter(|k| !k.trim().is_empty())
+        .with_context(|| t!("provenance.key_missing", var = PUBLISHER_KEY_VAR))?;
+    let key = STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .filter(|k| k.len() >= MIN_KEY_LEN)
+        .with_context(|| t!("provenance.key_invalid", var = PUBLISHER_KEY_VAR, bytes = MIN_KEY_LEN))?;
+    Ok(key)
+}
+
+/// Short identifier of a key, so the registry knows which one signed.
+pub fn key_id(key: &[u8]) -> String {
+    hex::encode(&Sha256::digest(key)[..4])
+}
+
+/// Name of a package archive in provenance statements.
+fn subject_name(id: &str, version: &str) -> String {
+    format!("{}-{}.zip", id, version)
+}
+
+/// Builds the SLSA provenance statement of a package archive: the builder,
+/// the source repository and commit, the locked dependencies the package was
+/// built with, and the archive digest.
+pub fn statement(
+    project: &Project,
+    manifest: &PluginManifest,
+    archive: &[u8],
+    build_info: Option<&BuildInfo>,
+) -> Result<Value> {
+    let repository = git::run(project.root(), &["remote", "get-url", "origin"])
+        .ok()
+        .or_else(|| manifest.repository.as_ref().map(|r| r.url.clone()));
+
+    let mut materials = Vec::new();
+    if let (Some(repository), Some(info)) = (&repository, build_info) {
+        materials.push(json!({
+            "uri": format!("git+{}@{}", repository, info.tag.as_deref().unwrap_or(&info.commit)),
+            "digest": { "gitCommit": info.commit },
+        }));
+    }
+    for package in Lockfile::load(project)?.packages {
+        let uri = package.source.clone().unwrap_or_else(|| format!("vk:{}@{}", package.id, package.version));
+        let mut material = json!({ "uri": uri });
+        if let Some(checksum) = &package.checksum {
+            material["digest"] = json!({ "sha256": checksum });
+        }
+        materials.push(material);
+    }
+
+    let (builder, invocation) = builder();
+    let mut run_details = json!({ "builder": { "id": builder } });
+    if let Some(invocation) = invocation {
+        run_details["metadata"] = json!({ "invocationId": invocation });
+    }
+
+    Ok(json!({
+        "_type": STATEMENT_TYPE,
+        "subject": [{
+            "name": subject_name(&manifest.name, &manifest.version),
+            "digest": { "sha256": hex::encode(Sha256::digest(archive)) },
+        }],
+        "predicateType": PREDICATE_TYPE,
+        "predicate": {
+            "buildDefinition": {
+                "buildType": BUILD_TYPE,
+                "externalParameters": {
+                    "package": manifest.name,
+                    "version": manifest.version,
+                    "repository": repository,
+                    "ref": build_info.map(|i| i.tag.clone().unwrap_or_else(|| i.commit.clone())),
+                },
+                "internalParameters": { "vk": env!("CARGO_PKG_VERSION") },
+                "resolvedDependencies": materials,
+            },
+            "runDetails": run_details,
+        },
+    }))
+}
+
+/// Who is building: the CI job when running in GitHub Actions or GitLab CI,
+/// else the local machine. Returns the builder id and the run, if known.
+pub fn builder() -> (String, Option<String>) {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+    if var("GITHUB_ACTIONS").as_deref() == Some("true") {
+        let server = var("GITHUB_SERVER_URL").unwrap_or_else(|| "https://github.com".to_string());
+        let repository = var("GITHUB_REPOSITORY").unwrap_or_default();
+        let id = match var("GITHUB_WORKFLOW_REF") {
+            Some(workflow) => format!("{}/{}", server, workflow),
+            None => format!("{}/{}/actions", server, repository),
+        };
+        let run = var("GITHUB_RUN_ID").map(|run| {
+            let attempt = var("GITHUB_RUN_ATTEMPT").unwrap_or_else(|| "1".to_string());
+            format!("{}/{}/actions/runs/{}/attempts/{}", server, repository, run, attempt)
+        });
+        return (id, run);
+    }
+    if var("GITLAB_CI").is_some() {
+        let id = format!(
+            "{}/{}/-/runners/{}",
+            var("CI_SERVER_URL").unwrap_or_default(),
+            var("CI_PROJECT_PATH").unwrap_or_default(),
+            var("CI_RUNNER_ID").unwrap_or_default()
+        );
+        return (id, var("CI_JOB_URL"));
+    }
+    (
+        format!("https://vayload.dev/vk/local@v{}", env!("CARGO_PKG_VERSION")),
+        None,
+    )
+}
+
+/// Whether the builder is a developer machine rather than a CI job.
+pub fn is_local_builder(id: &str) -> bool {
+    id.starts_with("https://vayload.dev/vk/local@")
+}
+
+/// Signs `statement` with the publisher key.
+pub fn sign(statement: &Value, key: &[u8]) -> Result<Envelope> {
+    let payload = serde_json::to_vec(statement)?;
+    let sig = hmac_sha256(key, &pae(PAYLOAD_TYPE, &payload));
+    Ok(Envelope {
+        payload_type: PAYLOAD_TYPE.to_string(),
+        payload: STANDARD.encode(&payload),
+        signatures: vec![Signature { keyid: key_id(key), sig: STANDARD.encode(sig) }],
+    })
+}
+
+/// Checks that a version was published with provenance the registry
+/// verified, and that it describes this very archive.
+pub fn verify(
+    id: &str,
+    version: &str,
+    archive: &[u8],
+    http_client: &HttpClient,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let package = format!("{}@{}", id, version);
+    let attestation = match RegistryClient::new(http_client).attestation(id, version) {
+        Ok(attestation) => attestation,
+        Err(ClientError::Api { status: 404, .. } | ClientError::Status { status: 404, .. }) => {
+            return Err(VkError::ProvenanceMissing(package).into());
+        },
+        Err(err) => return Err(err.into()),
+    };
+    if !attestation.verified {
+        return Err(VkError::ProvenanceInvalid { package, reason: t!("provenance.unverified") }.into());
+    }
+
+    let statement = check_statement(&attestation.envelope, &subject_name(id, version), archive)
+        .map_err(|reason| VkError::ProvenanceInvalid { package, reason })?;
+    let builder = statement["predicate"]["runDetails"]["builder"]["id"].as_str().unwrap_or("?");
+    reporter.success(&t!("provenance.verified", builder = builder.bright_black()));
+    Ok(())
+}
+
+/// Decodes the statement of an envelope and checks that its subject is the
+/// archive. Returns the statement, or why it does not hold.
+fn check_statement(envelope: &Envelope, name: &str, archive: &[u8]) -> Result<Value, String> {
+    if envelope.payload_type != PAYLOAD_TYPE {
+        return Err(t!("provenance.wrong_type", kind = envelope.payload_type));
+    }
+    let statement: Value = STANDARD
+        .decode(&envelope.payload)
+        .ok()
+        .and_then(|payload| serde_json::from_slice(&payload).ok())
+        .ok_or_else(|| t!("provenance.unreadable"))?;
+    if statement["_type"] != STATEMENT_TYPE || statement["predicateType"] != PREDICATE_TYPE {
+        return Err(t!("provenance.unreadable"));
+    }
+
+    let digest = hex::encode(Sha256::digest(archive));
+    let subjects = statement["subject"].as_array().cloned().unwrap_or_default();
+    let matches = subjects
+        .iter()
+        .any(|s| s["name"] == name && s["digest"]["sha256"].as_str().is_some_and(|d| d.eq_ignore_ascii_case(&digest)));
+    if !matches {
+        return Err(t!("provenance.wrong_subject", name = name, digest = digest));
+    }
+    Ok(statement)
+}
+
+/// DSSE pre-authentication encoding: what the signature covers.
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut encoded = format!("DSSEv1 {} {} {} ", payload_type.len(), payload_type, payload.len()).into_bytes();
+    encoded.extend_from_slice(payload);
+    encoded
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new().chain_update(block.map(|b| b ^ 0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(block.map(|b| b ^ 0x5c)).chain_update(inner).finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_pae() {
+        assert_eq!(
+            pae("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+        );
+    }
+
+    #[test]
+    fn test_check_statement() {
+        let archive = b"archive";
+        let statement = json!({
+            "_type": STATEMENT_TYPE,
+            "subject": [{ "name": "hello-1.0.0.zip", "digest": { "sha256": hex::encode(Sha256::digest(archive)) } }],
+            "predicateType": PREDICATE_TYPE,
+            "predicate": {},
+        });
+        let envelope = sign(&statement, &[7; 32]).unwrap();
+        assert_eq!(envelope.signatures[0].keyid, key_id(&[7; 32]));
+
+        assert!(check_statement(&envelope, "hello-1.0.0.zip", archive).is_ok());
+        assert!(check_statement(&envelope, "hello-1.0.1.zip", archive).is_err());
+        assert!(check_statement(&envelope, "hello-1.0.0.zip", b"tampered").is_err());
+    }
+}
@@ -0,0 +1,529 @@
+//! A registry without a server: package archives plus an `index.json5`
+//! describing them, in a shared directory (`file:///srv/vk-registry`), a git
+//! repository (`git+https://git.example.com/team/registry.git`) or an S3
+//! bucket (`s3://vk-plugins/registry`):
+//!
+//! ```text
+//! index.json5
+//! hello/hello-1.0.0.zip
+//! hello/hello-1.1.0.zip
+//! ```
+//!
+//! A git registry is cloned under [`config::registries_dir`] and brought up
+//! to date once per run; publishing commits there and pushes. Buckets are
+//! read and written directly, see [`Bucket`].
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::s3::{Bucket, Object, Precondition};
+use super::{PackageDetails, PackageVersion, Published, SearchResult, Upload, VERSION_EXISTS};
+use crate::config;
+use crate::encoding::json5::{self, DateTime};
+use crate::git::{self, BuildInfo};
+use crate::http_client::{ClientError, Download, HttpClient};
+use crate::i18n::t;
+use crate::manifest::{Dependency, MANIFEST_FILENAME, PluginManifest, TargetDependencies};
+use crate::provenance::Attestation;
+use crate::resolver;
+use crate::types::{ApiError, ErrorResponse};
+use crate::utils::{read_zip_file, sha256_hex};
+
+pub const INDEX_FILENAME: &str = "index.json5";
+/// Held while the index is rewritten, so concurrent publishes to a shared
+/// directory do not drop each other's entries.
+const LOCK_FILENAME: &str = ".index.lock";
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Times a bucket update starts over because another one got there first.
+const MAX_CONFLICTS: u32 = 5;
+
+const FILE_SCHEME: &str = "file://";
+const GIT_SCHEME: &str = "git+";
+
+/// Remotes already brought up to date by this run.
+static SYNCED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default)]
+    packages: BTreeMap<String, IndexedPackage>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexedPackage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
+    #[serde(default)]
+    dist_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    versions: Vec<IndexedVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedVersion {
+    version: String,
+    /// SHA-256 of the archive.
+    checksum: String,
+    published_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    changelog: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    build_info: Option<BuildInfo>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    target_dependencies: Vec<TargetDependencies>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    yanked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deprecated: Option<String>,
+}
+
+impl IndexedPackage {
+    /// The version the `latest` tag points at, or the newest release.
+    fn latest(&self) -> Option<&str> {
+        if let Some(latest) = self.dist_tags.get("latest") {
+            return Some(latest);
+        }
+        self.versions
+            .iter()
+            .filter_map(|v| Some((resolver::parse_version(&v.version)?, v.version.as_str())))
+            .filter(|(parsed, _)| parsed.pre.is_empty())
+            .max()
+            .map(|(_, version)| version)
+    }
+}
+
+/// Where the files of an index registry are.
+enum Storage {
+    /// A directory, for git registries a checkout of `remote`. The root of
+    /// a checkout is unknown without a home directory.
+    Directory {
+        root: Option<PathBuf>,
+        remote: Option<String>,
+    },
+    Bucket(Box<Bucket>),
+}
+
+/// Registry kept as files: an index and the archives it lists.
+pub struct IndexRegistry {
+    storage: Storage,
+}
+
+impl IndexRegistry {
+    /// The registry `url` names when it is a `file://`, `git+` or `s3://`
+    /// URL. Buckets are reached over the connections of `http`.
+    pub fn for_url(url: &str, http: &HttpClient) -> Option<Self> {
+        let storage = if let Some(path) = url.strip_prefix(FILE_SCHEME) {
+            Storage::Directory { root: Some(PathBuf::from(path)), remote: None }
+        } else if let Some(remote) = url.strip_prefix(GIT_SCHEME) {
+            let root = config::registries_dir().ok().map(|dir| dir.join(&sha256_hex(remote.as_bytes())[..16]));
+            Storage::Directory { root, remote: Some(remote.to_string()) }
+        } else {
+            Storage::Bucket(Box::new(Bucket::for_url(url, http)?))
+        };
+        Some(IndexRegistry { storage })
+    }
+
+    pub fn package(&self, id: &str) -> Result<PackageDetails, ClientError> {
+        let (index, _) = self.load()?;
+        let package = index.packages.get(id).ok_or_else(|| not_found(id))?;
+        Ok(PackageDetails {
+            name: id.to_string(),
+            latest_version: package.latest().unwrap_or_default().to_string(),
+            description: package.description.clone(),
+            license: package.license.clone(),
+            homepage: package.homepage.clone(),
+        })
+    }
+
+    pub fn versions(&self, id: &str) -> Result<Vec<PackageVersion>, ClientError> {
+        let (mut index, _) = self.load()?;
+        let package = index.packages.remove(id).ok_or_else(|| not_found(id))?;
+        Ok(package
+            .versions
+            .into_iter()
+            .map(|v| PackageVersion {
+                version: v.version,
+                build_info: v.build_info,
+                published_at: Some(v.published_at),
+                changelog: v.changelog,
+                dependencies: v.dependencies,
+                target_dependencies: v.target_dependencies,
+                yanked: v.yanked,
+                deprecated: v.deprecated,
+            })
+            .collect())
+    }
+
+    pub fn dist_tags(&self, id: &str) -> Result<BTreeMap<String, String>, ClientError> {
+        let (mut index, _) = self.load()?;
+        Ok(index.packages.remove(id).ok_or_else(|| not_found(id))?.dist_tags)
+    }
+
+    /// Points `tag` at `version`, or removes it when `None`.
+    pub fn set_dist_tag(&self, id: &str, tag: &str, version: Option<&str>) -> Result<(), ClientError> {
+        let message = match version {
+            Some(version) => format!("Tag {}@{} as {}", id, version, tag),
+            None => format!("Remove the {} tag of {}", tag, id),
+        };
+        self.update(&message, |index| {
+            let package = index.packages.get_mut(id).ok_or_else(|| not_found(id))?;
+            match version {
+                Some(version) if !package.versions.iter().any(|v| v.version == version) => {
+                    Err(not_found(&format!("{}@{}", id, version)))
+                },
+                Some(version) => {
+                    package.dist_tags.insert(tag.to_string(), version.to_string());
+                    Ok(())
+                },
+                None => {
+                    package.dist_tags.remove(tag);
+                    Ok(())
+                },
+            }
+        })
+    }
+
+    /// Indexes have no provenance: it is the registry server that checks
+    /// attestations against the publisher's key.
+    pub fn attestation(&self, id: &str, version: &str) -> Result<Attestation, ClientError> {
+        Err(not_found(&format!("{}@{}", id, version)))
+    }
+
+    /// The archive of `id` at `version`, the latest release when `None`,
+    /// answered as the registry server would.
+    pub fn download(&self, id: &str, version: Option<&str>) -> Result<Download, ClientError> {
+        let (index, _) = self.load()?;
+        let package = index.packages.get(id).ok_or_else(|| not_found(id))?;
+        let version = version.or_else(|| package.latest()).ok_or_else(|| not_found(id))?;
+        let entry = package
+            .versions
+            .iter()
+            .find(|v| v.version == version)
+            .ok_or_else(|| not_found(&format!("{}@{}", id, version)))?;
+        let Object { data, .. } =
+            self.read(&archive_key(id, version))?.ok_or_else(|| not_found(&format!("{}@{}", id, version)))?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Plugin-Version", header_value(version)?);
+        headers.insert("X-Checksum", header_value(&entry.checksum)?);
+        let response = http::Response::builder()
+            .header(CONTENT_TYPE, "application/zip")
+            .body(data)
+            .map_err(io::Error::other)?;
+        Ok(Download { headers, response: response.into() })
+    }
+
+    /// Adds the archive and its entry, read from the manifest inside it.
+    pub fn publish(&self, upload: &Upload) -> Result<Published, ClientError> {
+        let manifest = read_zip_file(upload.data, MANIFEST_FILENAME)
+            .ok()
+            .flatten()
+            .and_then(|m| json5::from_slice::<PluginManifest>(&m).ok())
+            .filter(|m| m.name == upload.name && m.version == upload.version)
+            .ok_or_else(|| ClientError::Status { status: 400, message: t!("registry.index_bad_archive") })?;
+        let checksum = sha256_hex(upload.data);
+        let package = format!("{}@{}", upload.name, upload.version);
+
+        self.update(&format!("Publish {}", package), |index| {
+            let entry = index.packages.entry(upload.name.to_string()).or_default();
+            if let Some(existing) = entry.versions.iter().find(|v| v.version == upload.version)
+                && !upload.force
+            {
+                return Err(version_exists(&package, &existing.checksum));
+            }
+            entry.versions.retain(|v| v.version != upload.version);
+
+            self.write(&archive_key(upload.name, upload.version), upload.data)?;
+
+            entry.description = Some(manifest.description.clone()).filter(|d| !d.is_empty());
+            entry.license = Some(manifest.license.clone()).filter(|l| !l.is_empty());
+            entry.homepage = manifest.homepage.clone();
+            entry.versions.push(IndexedVersion {
+                version: upload.version.to_string(),
+                checksum: checksum.clone(),
+                published_at: DateTime::now(),
+                changelog: upload.notes.map(String::from),
+                build_info: upload.build_info.cloned(),
+                dependencies: manifest.dependencies.clone().into_iter().collect(),
+                target_dependencies: manifest.target_dependencies.clone(),
+                yanked: false,
+                deprecated: None,
+            });
+            entry.dist_tags.insert(upload.tag.to_string(), upload.version.to_string());
+            Ok(())
+        })?;
+
+        Ok(Published { name: upload.name.to_string(), slug: upload.name.to_string() })
+    }
+
+    /// Packages whose name or description contains `query`, those matching
+    /// by name first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ClientError> {
+        let query = query.to_lowercase();
+        let (index, _) = self.load()?;
+        let mut results: Vec<(bool, SearchResult)> = index
+            .packages
+            .iter()
+            .filter_map(|(name, package)| {
+                let by_name = name.to_lowercase().contains(&query);
+                let by_description = package.description.as_ref().is_some_and(|d| d.to_lowercase().contains(&query));
+                (by_name || by_description).then(|| {
+                    let result = SearchResult {
+                        name: name.clone(),
+                        latest_version: package.latest().map(String::from),
+                        description: package.description.clone(),
+                    };
+                    (!by_name, result)
+                })
+            })
+            .collect();
+        results.sort_by_key(|(by_description, _)| *by_description);
+        Ok(results.into_iter().take(limit).map(|(_, result)| result).collect())
+    }
+
+    /// The index, from an up-to-date checkout for git registries, with the
+    /// ETag of its version for buckets. A registry without one yet is empty.
+    fn load(&self) -> Result<(Index, Option<String>), ClientError> {
+        let Some(Object { data, etag }) = self.read(INDEX_FILENAME)? else {
+            return Ok((Index::default(), None));
+        };
+        let index = json5::from_slice(&data)
+            .map_err(|err| io::Error::other(t!("registry.index_invalid", file = INDEX_FILENAME, error = err)))?;
+        Ok((index, etag))
+    }
+
+    /// Applies `change` to the index and writes it back. Directories are
+    /// locked meanwhile and git registries then commit it with `message` and
+    /// push.
+    fn update(
+        &self,
+        message: &str,
+        change: impl FnMut(&mut Index) -> Result<(), ClientError>,
+    ) -> Result<(), ClientError> {
+        let (root, remote) = match &self.storage {
+            Storage::Directory { remote, .. } => (self.checkout()?, remote),
+            Storage::Bucket(bucket) => return self.update_bucket(bucket, change),
+        };
+        let mut change = change;
+        fs::create_dir_all(root)?;
+        let _lock = IndexLock::acquire(root)?;
+
+        let (mut index, _) = self.load()?;
+        change(&mut index)?;
+        self.write(
+            INDEX_FILENAME,
+            json5::to_string_pretty(&index).map_err(io::Error::other)?.as_bytes(),
+        )?;
+
+        if remote.is_some() {
+            let unlocked = format!(":!{}", LOCK_FILENAME);
+            let pushed = git::run(root, &["add", "-A", "--", ".", &unlocked])
+                .and_then(|_| git::run(root, &["commit", "-q", "-m", message]))
+                .and_then(|_| git::run(root, &["push", "-q", "origin", "HEAD"]));
+            if let Err(err) = pushed {
+                // Start from the remote again next time rather than from a
+                // commit it refused
+                SYNCED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                return Err(io::Error::other(format!("{:#}", err)).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Buckets cannot be locked: the index is only replaced if it is still
+    /// the version `change` was applied to, and the change is made again on
+    /// the new one otherwise.
+    fn update_bucket(
+        &self,
+        bucket: &Bucket,
+        mut change: impl FnMut(&mut Index) -> Result<(), ClientError>,
+    ) -> Result<(), ClientError> {
+        let mut conflicts = 0;
+        loop {
+            let (mut index, etag) = self.load()?;
+            change(&mut index)?;
+            let data = json5::to_string_pretty(&index).map_err(io::Error::other)?.into_bytes();
+            let precondition = match &etag {
+                Some(etag) => Precondition::Matches(etag),
+                None => Precondition::Absent,
+            };
+            match bucket.put(INDEX_FILENAME, data, precondition) {
+                Err(ClientError::Status { status: 409 | 412, .. }) if conflicts < MAX_CONFLICTS => conflicts += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// The file at `key`, with an ETag for buckets, or `None` when there is
+    /// none.
+    fn read(&self, key: &str) -> Result<Option<Object>, ClientError> {
+        match &self.storage {
+            Storage::Directory { .. } => match fs::read(self.checkout()?.join(key)) {
+                Ok(data) => Ok(Some(Object { data, etag: None })),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            Storage::Bucket(bucket) => bucket.get(key),
+        }
+    }
+
+    /// Replaces the file at `key` at once, so readers never see part of it.
+    fn write(&self, key: &str, data: &[u8]) -> Result<(), ClientError> {
+        match &self.storage {
+            Storage::Directory { .. } => {
+                let path = self.checkout()?.join(key);
+                let dir = path.parent().unwrap_or(Path::new("."));
+                fs::create_dir_all(dir)?;
+                let mut file = tempfile::NamedTempFile::new_in(dir)?;
+                io::Write::write_all(&mut file, data)?;
+                file.persist(&path).map_err(|e| e.error)?;
+                Ok(())
+            },
+            Storage::Bucket(bucket) => bucket.put(key, data.to_vec(), Precondition::Any),
+        }
+    }
+
+    /// The root of a directory registry. Git registries are cloned or
+    /// brought up to date with the remote the first time this run needs it.
+    fn checkout(&self) -> Result<&Path, ClientError> {
+        let Storage::Directory { root, remote } = &self.storage else {
+            unreachable!("buckets have no checkout");
+        };
+        let root = root.as_deref().ok_or_else(|| io::Error::other(t!("common.no_home")))?;
+        let Some(remote) = remote else {
+            return Ok(root);
+        };
+        let mut synced = SYNCED.lock().unwrap_or_else(|e| e.into_inner());
+        if synced.contains(remote) {
+            return Ok(root);
+        }
+
+        let result = if root.join(".git").is_dir() {
+            // An empty remote has nothing to fetch yet
+            match git::run(root, &["fetch", "-q", "origin", "HEAD"]) {
+                Ok(_) => git::run(root, &["reset", "-q", "--hard", "FETCH_HEAD"]).map(drop),
+                Err(_) if git::run(root, &["ls-remote", "--heads", "origin"]).is_ok_and(|h| h.is_empty()) => Ok(()),
+                Err(err) => Err(err),
+            }
+        } else {
+            let parent = root.parent().unwrap_or(Path::new("."));
+            fs::create_dir_all(parent)?;
+            git::run(parent, &["clone", "-q", remote, &root.to_string_lossy()]).map(drop)
+        };
+        result.map_err(|err| io::Error::other(format!("{:#}", err)))?;
+
+        synced.insert(remote.clone());
+        Ok(root)
+    }
+}
+
+/// Where the archive of `id` at `version` is kept.
+fn archive_key(id: &str, version: &str) -> String {
+    format!("{}/{}-{}.zip", id, id, version)
+}
+
+/// Exclusive hold on the index of a registry, released on drop.
+struct IndexLock(PathBuf);
+
+impl IndexLock {
+    fn acquire(root: &Path) -> io::Result<Self> {
+        let path = root.join(LOCK_FILENAME);
+        let start = Instant::now();
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(IndexLock(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && start.elapsed() < LOCK_TIMEOUT => {
+                    thread::sleep(Duration::from_millis(100));
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(io::Error::other(t!("registry.index_locked", path = path.display())));
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+/// The answer of a registry server for what an index does not offer.
+pub fn unsupported(what: &str) -> ClientError {
+    ClientError::Status {
+        status: 501,
+        message: t!("registry.index_unsupported", what = what),
+    }
+}
+
+fn not_found(what: &str) -> ClientError {
+    ClientError::Status {
+        status: 404,
+        message: t!("registry.index_not_found", what = what),
+    }
+}
+
+/// The answer of a registry server that already has the version.
+fn version_exists(package: &str, checksum: &str) -> ClientError {
+    let message = t!("registry.index_version_exists", package = package);
+    let error = ApiError {
+        message: message.clone(),
+        code: VERSION_EXISTS.to_string(),
+        sub_code: None,
+        details: Some(serde_json::json!({ "checksum": checksum })),
+    };
+    ClientError::Api {
+        status: 409,
+        message,
+        payload: Box::new(ErrorResponse { error, meta: None }),
+    }
+}
+
+fn header_value(value: &str) -> io::Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> IndexedVersion {
+        IndexedVersion {
+            version: version.to_string(),
+            checksum: String::new(),
+            published_at: DateTime::from_unix(0, 0),
+            changelog: None,
+            build_info: None,
+            dependencies: BTreeMap::new(),
+            target_dependencies: Vec::new(),
+            yanked: false,
+            deprecated: None,
+        }
+    }
+
+    #[test]
+    fn test_latest() {
+        let mut package = IndexedPackage {
+            versions: vec![version("1.2.0"), version("1.10.0"), version("2.0.0-beta.1")],
+            ..Default::default()
+        };
+        assert_eq!(package.latest(), Some("1.10.0"));
+
+        package.dist_tags.insert("latest".to_string(), "1.2.0".to_string());
+        assert_eq!(package.latest(), Some("1.2.0"));
+    }
+}
@@ -0,0 +1,477 @@
+//! Typed access to the registry API. Every endpoint path and response model
+//! lives here, so commands ask for what they need (`versions`, `publish`...)
+//! and never build URLs themselves.
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+
+use crate::encoding::json5::DateTime;
+use crate::git::BuildInfo;
+use crate::http_client::{ClientError, Download, HttpClient, UploadProgress};
+use crate::limits::PublishLimits;
+use crate::manifest::{Dependency, PluginAccess, TargetDependencies};
+use crate::provenance::Attestation;
+use crate::utils::sha256_hex;
+
+mod index;
+mod s3;
+
+pub use index::IndexRegistry;
+
+/// Header the registry deduplicates uploads by: a retried upload with the
+/// same key gets the first one's answer instead of publishing twice.
+const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+/// Error code the registry answers with when the version is already published.
+pub const VERSION_EXISTS: &str = "version_exists";
+
+/// Prefix of every endpoint. The registry serves its current API at the
+/// root; a versioned API would only change this.
+const API_BASE: &str = "";
+
+fn endpoint(path: impl Display) -> String {
+    format!("{}{}", API_BASE, path)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackageDetails {
+    pub name: String,
+    #[serde(rename = "latestVersion")]
+    pub latest_version: String,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+}
+
+/// A published version, as the versions listing describes it.
+#[derive(Debug, Deserialize)]
+pub struct PackageVersion {
+    pub version: String,
+    #[serde(default, alias = "buildInfo")]
+    pub build_info: Option<BuildInfo>,
+    #[serde(default, alias = "publishedAt")]
+    pub published_at: Option<DateTime>,
+    /// Release notes, from the changelog of the plugin.
+    #[serde(default, alias = "releaseNotes")]
+    pub changelog: Option<String>,
+    /// What the version's manifest declares.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub target_dependencies: Vec<TargetDependencies>,
+    /// Withdrawn by its publisher; installs no longer pick it.
+    #[serde(default)]
+    pub yanked: bool,
+    /// Why the publisher advises against this version, when they do.
+    #[serde(default)]
+    pub deprecated: Option<String>,
+}
+
+/// The package a publish created or updated.
+#[derive(Debug, Deserialize)]
+pub struct Published {
+    pub name: String,
+    pub slug: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Advisories {
+    vulnerabilities: Vec<Advisory>,
+}
+
+/// A known vulnerability of a package.
+#[derive(Debug, Deserialize)]
+pub struct Advisory {
+    pub id: String,
+    pub title: String,
+    pub severity: String,
+    pub description: Option<String>,
+    pub patched_versions: Option<String>,
+}
+
+/// Usage of a package over a period.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Stats {
+    /// Downloads per day, oldest first.
+    #[serde(default)]
+    pub downloads: Vec<DailyDownloads>,
+    /// Downloads over the period, per version.
+    #[serde(default)]
+    pub versions: BTreeMap<String, u64>,
+    /// Published packages depending on this one.
+    #[serde(default)]
+    pub dependents: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyDownloads {
+    pub date: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchResult {
+    pub name: String,
+    #[serde(default, rename = "latestVersion")]
+    pub latest_version: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Owner {
+    pub username: String,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// What the logged-in user may do with a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Maintainer,
+    #[serde(other)]
+    None,
+}
+
+/// The role of the logged-in user on a package, with who to ask for more.
+#[derive(Debug, Deserialize)]
+pub struct PackageRole {
+    pub role: Role,
+    #[serde(default)]
+    pub owners: Vec<String>,
+}
+
+/// An API token of the logged-in user. The secret is only shown once, when
+/// the token is created, so it is not part of the listing.
+#[derive(Debug, Deserialize)]
+pub struct Token {
+    pub id: String,
+    pub name: String,
+    #[serde(default, alias = "createdAt")]
+    pub created_at: Option<DateTime>,
+    #[serde(default, alias = "lastUsedAt")]
+    pub last_used_at: Option<DateTime>,
+}
+
+/// What a publish sends.
+pub struct Upload<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub data: &'a [u8],
+    /// Release notes of the version, from the changelog.
+    pub notes: Option<&'a str>,
+    pub build_info: Option<&'a BuildInfo>,
+    /// Signed provenance statement (a DSSE envelope, as JSON).
+    pub provenance: Option<&'a str>,
+    pub access: PluginAccess,
+    pub tag: &'a str,
+    pub force: bool,
+}
+
+impl Upload<'_> {
+    /// The idempotency key of the upload. It only depends on what is sent,
+    /// and archives are reproducible, so re-running a publish that failed
+    /// after the upload reached the registry sends the same key again.
+    fn idempotency_key(&self, checksum: &str) -> String {
+        let request = format!(
+            "{}@{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.name,
+            self.version,
+            checksum,
+            self.access.as_str(),
+            self.tag,
+            self.force,
+            self.notes.map(|notes| sha256_hex(notes.as_bytes())).unwrap_or_default(),
+            self.provenance.is_some()
+        );
+        sha256_hex(request.as_bytes())[..32].to_string()
+    }
+
+    /// The multipart form the HTTP API takes, with the archive's upload
+    /// tracked by `progress`.
+    fn form(&self, progress: &UploadProgress) -> Result<Form, ClientError> {
+        let mut form = Form::new()
+            .part(
+                "file",
+                progress
+                    .part(self.data.to_vec())
+                    .file_name(format!("{}.zip", self.name))
+                    .mime_str("application/zip")?,
+            )
+            .part("access", Part::bytes(self.access.as_str().to_string().into_bytes()))
+            .text("tag", self.tag.to_string());
+        if self.force {
+            form = form.text("force", "true");
+        }
+        if let Some(notes) = self.notes {
+            form = form.text("changelog", notes.to_string());
+        }
+        if let Some(info) = self.build_info {
+            form = form.text("build_info", serde_json::to_string(info)?);
+        }
+        if let Some(provenance) = self.provenance {
+            form = form.text("provenance", provenance.to_string());
+        }
+        Ok(form)
+    }
+}
+
+/// Where the packages are.
+enum Backend<'a> {
+    /// A registry server, over the connection of an [`HttpClient`].
+    Http(&'a HttpClient),
+    /// Archives and an index in a directory, git repository or bucket.
+    Index(IndexRegistry),
+}
+
+/// The registry API. The backend follows the registry URL: `file://`, `git+`
+/// and `s3://` URLs name an [`IndexRegistry`], anything else a registry
+/// server.
+pub struct RegistryClient<'a> {
+    backend: Backend<'a>,
+}
+
+impl<'a> RegistryClient<'a> {
+    pub fn new(http: &'a HttpClient) -> Self {
+        let backend = match IndexRegistry::for_url(http.base_url(), http) {
+            Some(index) => Backend::Index(index),
+            None => Backend::Http(http),
+        };
+        RegistryClient { backend }
+    }
+
+    pub fn package(&self, id: &str) -> Result<PackageDetails, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint(format_args!("/packages/{}", id))),
+            Backend::Index(index) => index.package(id),
+        }
+    }
+
+    /// Every published version of `id`, in registry order.
+    pub fn versions(&self, id: &str) -> Result<Vec<PackageVersion>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get_all(&endpoint(format_args!("/packages/{}/versions", id))),
+            Backend::Index(index) => index.versions(id),
+        }
+    }
+
+    pub fn stats(&self, id: &str, days: u32) -> Result<Stats, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint(format_args!("/packages/{}/stats?days={}", id, days))),
+            Backend::Index(_) => Err(index::unsupported("stats")),
+        }
+    }
+
+    /// The `tag -> version` map of a package.
+    pub fn dist_tags(&self, id: &str) -> Result<BTreeMap<String, String>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint(format_args!("/packages/{}/dist-tags", id))),
+            Backend::Index(index) => index.dist_tags(id),
+        }
+    }
+
+    pub fn set_dist_tag(&self, id: &str, tag: &str, version: &str) -> Result<(), ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let path = endpoint(format_args!("/packages/{}/dist-tags/{}", id, tag));
+                http.put::<serde_json::Value, _>(&path, &serde_json::json!({ "version": version }))?;
+                Ok(())
+            },
+            Backend::Index(index) => index.set_dist_tag(id, tag, Some(version)),
+        }
+    }
+
+    pub fn remove_dist_tag(&self, id: &str, tag: &str) -> Result<(), ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                http.delete::<serde_json::Value>(&endpoint(format_args!("/packages/{}/dist-tags/{}", id, tag)))?;
+                Ok(())
+            },
+            Backend::Index(index) => index.set_dist_tag(id, tag, None),
+        }
+    }
+
+    /// The provenance attestation published with `id` at `version`.
+    pub fn attestation(&self, id: &str, version: &str) -> Result<Attestation, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint(format_args!(
+                "/packages/{}/versions/{}/provenance",
+                id, version
+            ))),
+            Backend::Index(index) => index.attestation(id, version),
+        }
+    }
+
+    /// Known vulnerabilities of `id`.
+    pub fn advisories(&self, id: &str) -> Result<Vec<Advisory>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                Ok(http.get::<Advisories>(&endpoint(format_args!("/audit/{}", id)))?.vulnerabilities)
+            },
+            Backend::Index(_) => Err(index::unsupported("audit")),
+        }
+    }
+
+    /// Starts downloading the archive of `id` at `version`, the latest
+    /// release when `None`.
+    pub fn download(&self, id: &str, version: Option<&str>) -> Result<Download, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let mut path = endpoint(format_args!("/plugins/{}/download", id));
+                if let Some(version) = version {
+                    path.push_str(&format!("?version={}", version));
+                }
+                http.download(&path)
+            },
+            Backend::Index(index) => index.download(id, version),
+        }
+    }
+
+    /// Starts downloading a patch from the archive of `id` at `from` to the
+    /// one at `to`, the latest release when `None`.
+    pub fn delta(&self, id: &str, from: &str, to: Option<&str>) -> Result<Download, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let mut path = endpoint(format_args!("/plugins/{}/delta?from={}", id, from));
+                if let Some(to) = to {
+                    path.push_str(&format!("&to={}", to));
+                }
+                http.download(&path)
+            },
+            Backend::Index(_) => Err(index::unsupported("delta")),
+        }
+    }
+
+    /// Starts downloading an asset published with `id` at `version`.
+    pub fn asset(&self, id: &str, version: &str, path: &str) -> Result<Download, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.download(&endpoint(format_args!(
+                "/plugins/{}/assets/{}?version={}",
+                id, path, version
+            ))),
+            Backend::Index(_) => Err(index::unsupported("assets")),
+        }
+    }
+
+    /// Publishes a package archive along with its metadata.
+    pub fn publish(&self, upload: &Upload) -> Result<Published, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let progress = UploadProgress::default();
+                let form = upload.form(&progress)?;
+                let key = upload.idempotency_key(&sha256_hex(upload.data));
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    IDEMPOTENCY_HEADER,
+                    HeaderValue::from_str(&key).map_err(io::Error::other)?,
+                );
+                http.post_multipart(&endpoint("/plugins/publish"), form, headers, &progress)
+            },
+            Backend::Index(index) => index.publish(upload),
+        }
+    }
+
+    /// Uploads an asset of the version of `id` being published.
+    pub fn upload_asset(&self, id: &str, form: Form, progress: &UploadProgress) -> Result<(), ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let path = endpoint(format_args!("/plugins/{}/assets", id));
+                http.post_multipart::<serde_json::Value>(&path, form, HeaderMap::new(), progress)?;
+                Ok(())
+            },
+            Backend::Index(_) => Err(index::unsupported("assets")),
+        }
+    }
+
+    /// The publish limits the registry advertises. An index has none.
+    pub fn limits(&self) -> Result<PublishLimits, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint("/limits")),
+            Backend::Index(_) => Ok(PublishLimits::default()),
+        }
+    }
+
+    /// Packages matching `query`, best matches first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => {
+                let query = url_encode(query);
+                http.get_paginated(&endpoint(format_args!("/packages?q={}&limit={}", query, limit)))
+                    .take(limit)
+                    .collect()
+            },
+            Backend::Index(index) => index.search(query, limit),
+        }
+    }
+
+    /// Users allowed to publish `id`.
+    pub fn owners(&self, id: &str) -> Result<Vec<Owner>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get_all(&endpoint(format_args!("/packages/{}/owners", id))),
+            Backend::Index(_) => Err(index::unsupported("owners")),
+        }
+    }
+
+    /// The role of the logged-in user on `id`.
+    pub fn role(&self, id: &str) -> Result<PackageRole, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get(&endpoint(format_args!("/packages/{}/role", id))),
+            Backend::Index(_) => Err(index::unsupported("roles")),
+        }
+    }
+
+    /// API tokens of the logged-in user.
+    pub fn tokens(&self) -> Result<Vec<Token>, ClientError> {
+        match &self.backend {
+            Backend::Http(http) => http.get_all(&endpoint("/tokens")),
+            Backend::Index(_) => Err(index::unsupported("tokens")),
+        }
+    }
+}
+
+/// `s` percent-encoded for a query string.
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(url_encode("http client"), "http%20client");
+        assert_eq!(url_encode("a&b=c/ñ"), "a%26b%3Dc%2F%C3%B1");
+        assert_eq!(url_encode("lua-utils_2.0~x"), "lua-utils_2.0~x");
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let upload = |force| Upload {
+            name: "hello",
+            version: "1.0.0",
+            data: b"",
+            notes: None,
+            build_info: None,
+            provenance: None,
+            access: PluginAccess::default(),
+            tag: "latest",
+            force,
+        };
+
+        let key = upload(false).idempotency_key("abc");
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, upload(false).idempotency_key("abc"));
+        assert_ne!(key, upload(false).idempotency_key("abd"));
+        assert_ne!(key, upload(true).idempotency_key("abc"));
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::error;
+use crate::i18n::t;
+
+/// Collects what `install`, `update` and `audit` did, for the JSON file
+/// written by `--report <file>` that CI steps archive and other tools read.
+pub struct Recorder {
+    command: &'static str,
+    started: Instant,
+    actions: RefCell<Actions>,
+}
+
+#[derive(Default)]
+struct Actions {
+    installed: Vec<PackageEntry>,
+    updated: Vec<PackageEntry>,
+    skipped: Vec<PackageEntry>,
+    vulnerabilities: Vec<VulnerabilityEntry>,
+    findings: Vec<FindingEntry>,
+    score: Option<u32>,
+    bytes_downloaded: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PackageEntry {
+    pub id: String,
+    /// Version before the command, for updates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Why the package was skipped or how it changed, as a stable code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VulnerabilityEntry {
+    pub package: String,
+    pub version: String,
+    pub id: String,
+    pub severity: String,
+    pub title: String,
+}
+
+/// A problem `vk audit` found outside the advisories.
+#[derive(Debug, Serialize)]
+pub struct FindingEntry {
+    /// The audit section that found it, as a stable code.
+    pub section: &'static str,
+    pub package: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    command: &'a str,
+    success: bool,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ReportError>,
+    duration_ms: u64,
+    bytes_downloaded: u64,
+    installed: &'a [PackageEntry],
+    updated: &'a [PackageEntry],
+    skipped: &'a [PackageEntry],
+    vulnerabilities: &'a [VulnerabilityEntry],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    findings: &'a [FindingEntry],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ReportError {
+    code: &'static str,
+    message: String,
+}
+
+impl Recorder {
+    pub fn new(command: &'static str) -> Self {
+        Recorder {
+            command,
+            started: Instant::now(),
+            actions: RefCell::default(),
+        }
+    }
+
+    pub fn installed(&self, entry: PackageEntry) {
+        self.actions.borrow_mut().installed.push(entry);
+    }
+
+    pub fn updated(&self, entry: PackageEntry) {
+        self.actions.borrow_mut().updated.push(entry);
+    }
+
+    pub fn skipped(&self, id: &str, reason: &'static str) {
+        let entry = PackageEntry {
+            id: id.to_string(),
+            reason: Some(reason),
+            ..PackageEntry::default()
+        };
+        self.actions.borrow_mut().skipped.push(entry);
+    }
+
+    pub fn vulnerability(&self, entry: VulnerabilityEntry) {
+        self.actions.borrow_mut().vulnerabilities.push(entry);
+    }
+
+    pub fn finding(&self, entry: FindingEntry) {
+        self.actions.borrow_mut().findings.push(entry);
+    }
+
+    pub fn score(&self, score: u32) {
+        self.actions.borrow_mut().score = Some(score);
+    }
+
+    pub fn downloaded(&self, bytes: usize) {
+        self.actions.borrow_mut().bytes_downloaded += bytes as u64;
+    }
+
+    /// Writes the report for a command that ended with `outcome`. Entries are
+    /// sorted so reports of identical runs only differ in their durations.
+    pub fn write(&self, path: &str, outcome: &Result<()>) -> Result<()> {
+        let mut guard = self.actions.borrow_mut();
+        let actions = &mut *guard;
+        for list in [&mut actions.installed, &mut actions.updated, &mut actions.skipped] {
+            list.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        actions.vulnerabilities.sort_by(|a, b| (&a.package, &a.id).cmp(&(&b.package, &b.id)));
+        actions.findings.sort_by(|a, b| (a.section, &a.package).cmp(&(b.section, &b.package)));
+
+        let (exit_code, error) = match outcome {
+            Ok(()) => (0, None),
+            Err(err) => {
+                let (code, exit_code) = error::classify(err);
+                (
+                    exit_code,
+                    Some(ReportError { code, message: strip_ansi(&error::render(err)) }),
+                )
+            },
+        };
+
+        let report = Report {
+            command: self.command,
+            success: outcome.is_ok(),
+            exit_code,
+            error,
+            duration_ms: millis(self.started.elapsed()),
+            bytes_downloaded: actions.bytes_downloaded,
+            installed: &actions.installed,
+            updated: &actions.updated,
+            skipped: &actions.skipped,
+            vulnerabilities: &actions.vulnerabilities,
+            findings: &actions.findings,
+            score: actions.score,
+        };
+
+        fs::write(path, serde_json::to_string_pretty(&report)? + "\n")
+            .with_context(|| t!("common.write_failed", path = path))
+    }
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// Drops the terminal color codes messages carry in interactive mode.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a letter
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[36mhello\x1b[0m@\x1b[1;33m1.0.0\x1b[0m"), "hello@1.0.0");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+}
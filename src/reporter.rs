@@ -0,0 +1,510 @@
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How user-facing output is rendered.
+///
+/// `Interactive` is used when stdout is a terminal, `Plain` is the line-based
+/// fallback for CI logs and pipes, `Quiet` only keeps warnings, errors and
+/// command output, and `Json` emits one JSON object per event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputMode {
+    Interactive,
+    Plain,
+    Quiet,
+    Json,
+}
+
+impl OutputMode {
+    /// Resolves the effective mode from the CLI flags and the environment.
+    pub fn detect(requested: Option<OutputMode>, quiet: bool) -> Self {
+        if quiet {
+            return OutputMode::Quiet;
+        }
+
+        match requested {
+            Some(mode) => mode,
+            None if std::io::stdout().is_terminal() => OutputMode::Interactive,
+            None => OutputMode::Plain,
+        }
+    }
+
+    fn use_color(&self) -> bool {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return false;
+        }
+
+        match self {
+            OutputMode::Interactive => true,
+            OutputMode::Quiet => std::io::stdout().is_terminal(),
+            OutputMode::Plain | OutputMode::Json => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Headline of a command ("Installing foo")
+    Title,
+    /// Intermediate step of a command
+    Step,
+    /// Secondary details such as file listings
+    Detail,
+    Info,
+    Success,
+    Warn,
+    Error,
+    /// Final message of a successful command
+    Done,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Title => "title",
+            Level::Step => "step",
+            Level::Detail => "detail",
+            Level::Info => "info",
+            Level::Success => "success",
+            Level::Warn => "warn",
+            Level::Error => "error",
+            Level::Done => "done",
+        }
+    }
+}
+
+/// Sink for every user-facing message printed by the commands.
+///
+/// Commands never print directly; they receive a `&dyn Reporter` and the
+/// implementation decides how (or whether) a message is rendered.
+pub trait Reporter: Send + Sync {
+    fn emit(&self, level: Level, message: &str);
+
+    /// Prints the primary output of a command (lists, tables, user info).
+    /// Unlike status messages it is kept in quiet mode.
+    fn output(&self, line: &str);
+
+    fn progress(&self, total: Option<u64>, message: &str) -> Progress;
+
+    fn mode(&self) -> OutputMode;
+
+    fn title(&self, message: &str) {
+        self.emit(Level::Title, message);
+    }
+
+    fn step(&self, message: &str) {
+        self.emit(Level::Step, message);
+    }
+
+    fn detail(&self, message: &str) {
+        self.emit(Level::Detail, message);
+    }
+
+    fn info(&self, message: &str) {
+        self.emit(Level::Info, message);
+    }
+
+    fn success(&self, message: &str) {
+        self.emit(Level::Success, message);
+    }
+
+    fn warn(&self, message: &str) {
+        self.emit(Level::Warn, message);
+    }
+
+    fn error(&self, message: &str) {
+        self.emit(Level::Error, message);
+    }
+
+    fn done(&self, message: &str) {
+        self.emit(Level::Done, message);
+    }
+
+    /// Reports the error that terminated the command along with its stable code.
+    fn failure(&self, code: &str, message: &str) {
+        self.error(&format!("[{}] {}", code, message));
+    }
+
+    /// Whether interactive prompts may be shown to the user.
+    fn is_interactive(&self) -> bool {
+        self.mode() == OutputMode::Interactive && std::io::stdin().is_terminal()
+    }
+
+    /// Asks a yes/no question that defaults to no. Answers no without asking
+    /// when prompts cannot be shown, including builds without prompt support.
+    fn confirm(&self, prompt: &str) -> anyhow::Result<bool> {
+        #[cfg(feature = "full")]
+        if self.is_interactive() {
+            use anyhow::Context;
+
+            return dialoguer::Confirm::new()
+                .with_prompt(prompt)
+                .default(false)
+                .interact()
+                .with_context(|| crate::i18n::t!("init.read_input_failed"));
+        }
+
+        let _ = prompt;
+        Ok(false)
+    }
+}
+
+/// Builds the reporter for the given mode and configures global color output.
+pub fn new_reporter(mode: OutputMode) -> Box<dyn Reporter> {
+    colored::control::set_override(mode.use_color());
+
+    match mode {
+        OutputMode::Interactive => Box::new(InteractiveReporter),
+        OutputMode::Plain => Box::new(PlainReporter),
+        OutputMode::Quiet => Box::new(QuietReporter),
+        OutputMode::Json => Box::new(JsonReporter),
+    }
+}
+
+pub struct InteractiveReporter;
+
+impl Reporter for InteractiveReporter {
+    fn failure(&self, code: &str, message: &str) {
+        eprintln!("{} {}", format!("error[{}]:", code).red().bold(), message);
+    }
+
+    fn emit(&self, level: Level, message: &str) {
+        match level {
+            Level::Title => println!("{}", message.bold().cyan()),
+            Level::Step => println!("{} {}", "→".cyan(), message),
+            Level::Detail => println!("  {}", message.bright_black()),
+            Level::Info => println!("{} {}", "ℹ".bright_blue(), message),
+            Level::Success => println!("{} {}", "✓".green(), message),
+            Level::Warn => eprintln!("{} {}", "⚠".yellow(), message.yellow()),
+            Level::Error => eprintln!("{} {}", "error:".red().bold(), message),
+            Level::Done => println!("{} {}", "✅".green(), message.green().bold()),
+        }
+    }
+
+    fn output(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn progress(&self, total: Option<u64>, message: &str) -> Progress {
+        let pb = match total {
+            Some(size) => {
+                let pb = ProgressBar::new(size);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{msg} [{bar:30.cyan/blue}] {percent}% ({bytes}/{total_bytes}) {elapsed}")
+                        .unwrap()
+                        .progress_chars("█░"),
+                );
+                pb
+            },
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg} {bytes} {elapsed}").unwrap());
+                pb
+            },
+        };
+        pb.set_message(message.to_string());
+
+        Progress { inner: ProgressInner::Bar(pb) }
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::Interactive
+    }
+}
+
+pub struct PlainReporter;
+
+impl Reporter for PlainReporter {
+    fn failure(&self, code: &str, message: &str) {
+        eprintln!("error[{}]: {}", code, message);
+    }
+
+    fn emit(&self, level: Level, message: &str) {
+        match level {
+            Level::Title => println!("==> {}", message),
+            Level::Step => println!("--> {}", message),
+            Level::Detail => println!("    {}", message),
+            Level::Info => println!("[info] {}", message),
+            Level::Success => println!("[ok] {}", message),
+            Level::Warn => eprintln!("[warn] {}", message),
+            Level::Error => eprintln!("error: {}", message),
+            Level::Done => println!("[done] {}", message),
+        }
+    }
+
+    fn output(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn progress(&self, total: Option<u64>, message: &str) -> Progress {
+        println!("--> {}", message);
+        Progress::lines(total, message)
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::Plain
+    }
+}
+
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {
+    fn failure(&self, code: &str, message: &str) {
+        eprintln!("{} {}", format!("error[{}]:", code).red().bold(), message);
+    }
+
+    fn emit(&self, level: Level, message: &str) {
+        match level {
+            Level::Warn => eprintln!("{} {}", "warning:".yellow(), message),
+            Level::Error => eprintln!("{} {}", "error:".red().bold(), message),
+            _ => {},
+        }
+    }
+
+    fn output(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn progress(&self, _total: Option<u64>, _message: &str) -> Progress {
+        Progress::hidden()
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::Quiet
+    }
+}
+
+pub struct JsonReporter;
+
+impl JsonReporter {
+    fn print(&self, event: &str, message: &str) {
+        println!("{}", serde_json::json!({ "event": event, "message": message }));
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn failure(&self, code: &str, message: &str) {
+        println!(
+            "{}",
+            serde_json::json!({ "event": "error", "code": code, "message": message })
+        );
+    }
+
+    fn emit(&self, level: Level, message: &str) {
+        self.print(level.as_str(), message);
+    }
+
+    fn output(&self, line: &str) {
+        self.print("output", line);
+    }
+
+    fn progress(&self, total: Option<u64>, message: &str) -> Progress {
+        println!(
+            "{}",
+            serde_json::json!({ "event": "progress", "message": message, "total": total })
+        );
+        Progress::hidden()
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::Json
+    }
+}
+
+/// What a [`CallbackReporter`] hands its callback.
+#[derive(Debug, Clone, Copy)]
+pub enum Report<'a> {
+    Message(Level, &'a str),
+    /// A line of the command's primary output.
+    Output(&'a str),
+    /// `current` of `total` units (bytes, mostly) of the work `message`
+    /// describes are done; `done` once it is over.
+    Progress {
+        message: &'a str,
+        current: u64,
+        total: Option<u64>,
+        done: bool,
+    },
+}
+
+type Callback = Arc<dyn Fn(Report) + Send + Sync>;
+
+/// Hands every message and progress update to a callback, for programs
+/// running the commands through the library. Messages come as plain text:
+/// creating one turns colors off. Prompts are answered no.
+pub struct CallbackReporter {
+    callback: Callback,
+}
+
+impl CallbackReporter {
+    pub fn new(callback: impl Fn(Report) + Send + Sync + 'static) -> Self {
+        colored::control::set_override(false);
+        Self { callback: Arc::new(callback) }
+    }
+}
+
+impl Reporter for CallbackReporter {
+    fn emit(&self, level: Level, message: &str) {
+        (self.callback)(Report::Message(level, message));
+    }
+
+    fn output(&self, line: &str) {
+        (self.callback)(Report::Output(line));
+    }
+
+    fn progress(&self, total: Option<u64>, message: &str) -> Progress {
+        (self.callback)(Report::Progress { message, current: 0, total, done: false });
+        Progress {
+            inner: ProgressInner::Callback {
+                message: message.to_string(),
+                total,
+                current: AtomicU64::new(0),
+                callback: self.callback.clone(),
+            },
+        }
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::Plain
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+enum Deferred {
+    Emit(Level, String),
+    Output(String),
+}
+
+/// Holds the messages of work running in the background, so they can be
+/// shown with the rest of the output once the command gets to that work.
+/// Progress is hidden and prompts are answered no.
+pub struct DeferredReporter {
+    mode: OutputMode,
+    messages: Mutex<Vec<Deferred>>,
+}
+
+impl DeferredReporter {
+    pub fn new(mode: OutputMode) -> Self {
+        Self { mode, messages: Mutex::new(Vec::new()) }
+    }
+
+    /// Shows the held messages through `reporter`, in the order they came.
+    pub fn replay(self, reporter: &dyn Reporter) {
+        for message in self.messages.into_inner().unwrap() {
+            match message {
+                Deferred::Emit(level, message) => reporter.emit(level, &message),
+                Deferred::Output(line) => reporter.output(&line),
+            }
+        }
+    }
+}
+
+impl Reporter for DeferredReporter {
+    fn emit(&self, level: Level, message: &str) {
+        self.messages.lock().unwrap().push(Deferred::Emit(level, message.to_string()));
+    }
+
+    fn output(&self, line: &str) {
+        self.messages.lock().unwrap().push(Deferred::Output(line.to_string()));
+    }
+
+    fn progress(&self, _total: Option<u64>, _message: &str) -> Progress {
+        Progress::hidden()
+    }
+
+    fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Handle to an in-flight progress indicator.
+///
+/// Depending on the reporter it renders an animated bar, prints a line every
+/// 25% (non-TTY fallback) or stays silent.
+pub struct Progress {
+    inner: ProgressInner,
+}
+
+enum ProgressInner {
+    Bar(ProgressBar),
+    Lines(Mutex<LineProgress>),
+    Callback {
+        message: String,
+        total: Option<u64>,
+        current: AtomicU64,
+        callback: Callback,
+    },
+    Hidden,
+}
+
+struct LineProgress {
+    message: String,
+    total: Option<u64>,
+    current: u64,
+    last_quarter: u64,
+}
+
+impl Progress {
+    pub fn hidden() -> Self {
+        Self { inner: ProgressInner::Hidden }
+    }
+
+    fn lines(total: Option<u64>, message: &str) -> Self {
+        Self {
+            inner: ProgressInner::Lines(Mutex::new(LineProgress {
+                message: message.to_string(),
+                total,
+                current: 0,
+                last_quarter: 0,
+            })),
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        match &self.inner {
+            ProgressInner::Bar(pb) => pb.inc(delta),
+            ProgressInner::Lines(state) => {
+                let mut state = state.lock().unwrap();
+                state.current += delta;
+
+                if let Some(total) = state.total.filter(|t| *t > 0) {
+                    let quarter = (state.current * 4 / total).min(4);
+                    if quarter > state.last_quarter && quarter < 4 {
+                        state.last_quarter = quarter;
+                        println!("    {} {}%", state.message, quarter * 25);
+                    }
+                }
+            },
+            ProgressInner::Callback { message, total, current, callback } => {
+                let current = current.fetch_add(delta, Ordering::Relaxed) + delta;
+                callback(Report::Progress { message, current, total: *total, done: false });
+            },
+            ProgressInner::Hidden => {},
+        }
+    }
+
+    pub fn finish(self) {
+        match self.inner {
+            ProgressInner::Bar(pb) => pb.finish_and_clear(),
+            ProgressInner::Lines(state) => {
+                let state = state.into_inner().unwrap();
+                println!("    {} 100%", state.message);
+            },
+            ProgressInner::Callback { message, total, current, callback } => {
+                let current = current.into_inner();
+                callback(Report::Progress { message: &message, current, total, done: true });
+            },
+            ProgressInner::Hidden => {},
+        }
+    }
+}
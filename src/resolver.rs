@@ -0,0 +1,497 @@
+use semver::{Comparator, Op, Version, VersionReq};
+use std::collections::{BTreeMap, HashMap};
+
+/// Which of the versions satisfying a range the resolver picks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    /// The newest one.
+    #[default]
+    Highest,
+    /// The oldest one, to check that the lower bounds of the ranges hold.
+    Minimal,
+}
+
+/// Parses a dependency constraint. `*` and an empty string accept any
+/// release; a bare version such as `1.2.0` is treated as `^1.2.0`.
+pub fn parse_req(spec: &str) -> Option<VersionReq> {
+    let spec = spec.trim();
+    if spec.is_empty() || spec == "*" {
+        return Some(VersionReq::STAR);
+    }
+    VersionReq::parse(spec).ok()
+}
+
+/// Parses a registry version, tolerating a leading `v`.
+pub fn parse_version(version: &str) -> Option<Version> {
+    Version::parse(version.trim().trim_start_matches('v')).ok()
+}
+
+/// Whether `version` satisfies `req`.
+///
+/// Pre-releases are excluded unless the constraint itself names a pre-release
+/// of the same `major.minor.patch` (`^1.2.0-rc.1` accepts `1.2.0-rc.2`), or
+/// `include_pre` is set, in which case pre-releases of versions inside the
+/// range are accepted too (`^1.2.0` accepts `1.3.0-beta.1`, not `1.2.0-rc.1`).
+pub fn matches(req: &VersionReq, version: &Version, include_pre: bool) -> bool {
+    if req.matches(version) {
+        return true;
+    }
+    if !include_pre || version.pre.is_empty() {
+        return false;
+    }
+
+    let release = Version::new(version.major, version.minor, version.patch);
+    req.matches(&release) && !req.comparators.iter().any(|c| is_release_lower_bound(c, &release))
+}
+
+/// A pre-release sorts below its release, so it falls outside a range whose
+/// lower bound is exactly that release.
+fn is_release_lower_bound(comparator: &Comparator, release: &Version) -> bool {
+    matches!(
+        comparator.op,
+        Op::Exact | Op::Caret | Op::Tilde | Op::GreaterEq | Op::Wildcard
+    ) && comparator.pre.is_empty()
+        && comparator.major == release.major
+        && comparator.minor.unwrap_or(0) == release.minor
+        && comparator.patch.unwrap_or(0) == release.patch
+}
+
+/// Picks the newest version satisfying `req`.
+pub fn max_satisfying<'a, I>(versions: I, req: &VersionReq, include_pre: bool) -> Option<Version>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    versions.into_iter().filter_map(parse_version).filter(|v| matches(req, v, include_pre)).max()
+}
+
+/// Picks the version satisfying `req` that `strategy` prefers.
+pub fn pick<'a, I>(versions: I, req: &VersionReq, include_pre: bool, strategy: Strategy) -> Option<Version>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let satisfying = versions.into_iter().filter_map(parse_version).filter(|v| matches(req, v, include_pre));
+    match strategy {
+        Strategy::Highest => satisfying.max(),
+        Strategy::Minimal => satisfying.min(),
+    }
+}
+
+/// Newest version overall; pre-releases only count when `include_pre` is set.
+pub fn latest<'a, I>(versions: I, include_pre: bool) -> Option<Version>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    versions.into_iter().filter_map(parse_version).filter(|v| include_pre || v.pre.is_empty()).max()
+}
+
+/// Sorts version strings by semver precedence (oldest first), so `1.2.0-rc.1`
+/// lands before `1.2.0` and `1.10.0` after `1.9.0`. Unparseable entries keep
+/// their relative order at the start.
+pub fn sort_versions(versions: &mut [String]) {
+    versions.sort_by_cached_key(|v| parse_version(v));
+}
+
+/// A published version of a package and the ranges it depends on.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub version: Version,
+    pub dependencies: BTreeMap<String, String>,
+}
+
+/// A package on the way from the manifest to a requirement, and the range it
+/// was itself required with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependent {
+    pub id: String,
+    pub version: Version,
+    pub range: String,
+}
+
+/// A range some package must satisfy. `via` lists the packages that led to
+/// it, starting with a dependency of the manifest; it is empty when the
+/// manifest itself asks for the range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub range: String,
+    pub via: Vec<Dependent>,
+}
+
+/// Why resolution failed: the requirements on `package` no published version
+/// satisfies together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub package: String,
+    pub requirements: Vec<Requirement>,
+    pub available: Vec<Version>,
+}
+
+/// The version picked for a package, and those picked for its dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub version: Version,
+    pub dependencies: BTreeMap<String, Version>,
+}
+
+#[derive(Debug)]
+pub enum Unsolvable {
+    Conflict(Conflict),
+    /// The versions of a package could not be fetched.
+    Failed(anyhow::Error),
+}
+
+/// Gives up backtracking after this many picks and reports the first
+/// conflict found.
+const MAX_PICKS: usize = 10_000;
+
+/// Picks a version of every package reachable from `roots` (the ranges the
+/// manifest declares) such that all the ranges on each package hold.
+///
+/// Versions in `preferred` (the lockfile) are tried first, then the rest in
+/// `strategy` order, backtracking when a pick leaves some package without a
+/// version. When nothing works, the conflict found with the preferred picks
+/// is reported, as the one closest to what the user asked for. Ranges that
+/// are not semver (dist-tags) are not checked.
+///
+/// A range in `overrides` replaces every range on that package, wherever it
+/// appears in the tree.
+pub fn solve<F>(
+    roots: &BTreeMap<String, String>,
+    preferred: &BTreeMap<String, Version>,
+    overrides: &BTreeMap<String, String>,
+    strategy: Strategy,
+    include_pre: bool,
+    fetch: F,
+) -> Result<BTreeMap<String, Resolved>, Unsolvable>
+where
+    F: FnMut(&str) -> anyhow::Result<Vec<Candidate>>,
+{
+    let mut solver = Solver {
+        preferred,
+        overrides,
+        strategy,
+        include_pre,
+        fetch,
+        cache: HashMap::new(),
+        picks: 0,
+        conflict: None,
+    };
+
+    let mut state = State::default();
+    for (id, range) in roots {
+        state.require(id, Requirement { range: range.clone(), via: Vec::new() });
+    }
+
+    match solver.step(state)? {
+        Some(solution) => Ok(solution),
+        None => Err(Unsolvable::Conflict(
+            solver.conflict.expect("a failed resolution records its conflict"),
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    picked: BTreeMap<String, Candidate>,
+    requirements: BTreeMap<String, Vec<Requirement>>,
+    /// Packages still to pick, in the order they were first required.
+    pending: Vec<String>,
+}
+
+impl State {
+    fn require(&mut self, id: &str, requirement: Requirement) {
+        let requirements = self.requirements.entry(id.to_string()).or_default();
+        if requirements.is_empty() && !self.picked.contains_key(id) {
+            self.pending.push(id.to_string());
+        }
+        requirements.push(requirement);
+    }
+}
+
+struct Solver<'a, F> {
+    preferred: &'a BTreeMap<String, Version>,
+    overrides: &'a BTreeMap<String, String>,
+    strategy: Strategy,
+    include_pre: bool,
+    fetch: F,
+    cache: HashMap<String, Vec<Candidate>>,
+    picks: usize,
+    conflict: Option<Conflict>,
+}
+
+impl<F> Solver<'_, F>
+where
+    F: FnMut(&str) -> anyhow::Result<Vec<Candidate>>,
+{
+    fn step(&mut self, mut state: State) -> Result<Option<BTreeMap<String, Resolved>>, Unsolvable> {
+        if state.pending.is_empty() {
+            return Ok(Some(solution(&state.picked)));
+        }
+        let id = state.pending.remove(0);
+
+        if !self.cache.contains_key(&id) {
+            let candidates = (self.fetch)(&id).map_err(Unsolvable::Failed)?;
+            self.cache.insert(id.clone(), candidates);
+        }
+        let requirements = match self.overrides.get(&id) {
+            Some(range) => vec![Requirement { range: range.clone(), via: Vec::new() }],
+            None => state.requirements[&id].clone(),
+        };
+        let candidates = self.ordered(&id, &requirements);
+
+        if candidates.is_empty() {
+            self.fail(&id, requirements);
+            return Ok(None);
+        }
+
+        for candidate in candidates {
+            self.picks += 1;
+            if self.picks > MAX_PICKS {
+                break;
+            }
+
+            let via = match requirements.first() {
+                Some(requirement) => {
+                    let mut via = requirement.via.clone();
+                    via.push(Dependent {
+                        id: id.clone(),
+                        version: candidate.version.clone(),
+                        range: requirement.range.clone(),
+                    });
+                    via
+                },
+                None => Vec::new(),
+            };
+
+            let mut next = state.clone();
+            next.picked.insert(id.clone(), candidate.clone());
+
+            let mut clash = None;
+            for (dep, range) in &candidate.dependencies {
+                let requirement = Requirement { range: range.clone(), via: via.clone() };
+                if let Some(picked) = next.picked.get(dep)
+                    && !self.overrides.contains_key(dep)
+                    && !self.allows(dep, &requirement, &picked.version)
+                {
+                    let mut requirements = next.requirements[dep].clone();
+                    requirements.push(requirement);
+                    clash = Some((dep.clone(), requirements));
+                    break;
+                }
+                next.require(dep, requirement);
+            }
+            if let Some((dep, requirements)) = clash {
+                self.fail(&dep, requirements);
+                continue;
+            }
+
+            if let Some(solution) = self.step(next)? {
+                return Ok(Some(solution));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The versions of `id` every requirement allows, best first.
+    fn ordered(&self, id: &str, requirements: &[Requirement]) -> Vec<Candidate> {
+        let mut candidates: Vec<Candidate> = self.cache[id]
+            .iter()
+            .filter(|c| requirements.iter().all(|r| self.allows(id, r, &c.version)))
+            .cloned()
+            .collect();
+        candidates.sort_by(|a, b| match self.strategy {
+            Strategy::Highest => b.version.cmp(&a.version),
+            Strategy::Minimal => a.version.cmp(&b.version),
+        });
+        if let Some(preferred) = self.preferred.get(id)
+            && let Some(index) = candidates.iter().position(|c| c.version == *preferred)
+        {
+            let candidate = candidates.remove(index);
+            candidates.insert(0, candidate);
+        }
+        candidates
+    }
+
+    fn allows(&self, id: &str, requirement: &Requirement, version: &Version) -> bool {
+        let Some(req) = parse_req(&requirement.range) else {
+            return true;
+        };
+        // A locked pre-release stays while its range still covers it
+        let include_pre = self.include_pre || self.preferred.get(id) == Some(version);
+        matches(&req, version, include_pre)
+    }
+
+    fn fail(&mut self, id: &str, requirements: Vec<Requirement>) {
+        if self.conflict.is_none() {
+            let mut available: Vec<Version> =
+                self.cache.get(id).into_iter().flatten().map(|c| c.version.clone()).collect();
+            available.sort();
+            self.conflict = Some(Conflict { package: id.to_string(), requirements, available });
+        }
+    }
+}
+
+fn solution(picked: &BTreeMap<String, Candidate>) -> BTreeMap<String, Resolved> {
+    picked
+        .iter()
+        .map(|(id, candidate)| {
+            let dependencies = candidate
+                .dependencies
+                .keys()
+                .filter_map(|dep| Some((dep.clone(), picked.get(dep)?.version.clone())))
+                .collect();
+            (
+                id.clone(),
+                Resolved { version: candidate.version.clone(), dependencies },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(s: &str) -> VersionReq {
+        parse_req(s).unwrap()
+    }
+
+    fn v(s: &str) -> Version {
+        parse_version(s).unwrap()
+    }
+
+    #[test]
+    fn test_pre_releases_excluded_by_default() {
+        assert!(matches(&req("^1.2.0"), &v("1.4.0"), false));
+        assert!(!matches(&req("^1.2.0"), &v("1.3.0-rc.1"), false));
+        assert!(!matches(&req("~1.2.0"), &v("1.2.1-beta.1"), false));
+        assert!(!matches(&req("*"), &v("2.0.0-alpha"), false));
+    }
+
+    #[test]
+    fn test_pre_release_constraint_allows_same_release_line() {
+        assert!(matches(&req("^1.2.0-rc.1"), &v("1.2.0-rc.2"), false));
+        assert!(matches(&req("^1.2.0-rc.1"), &v("1.2.0"), false));
+        assert!(!matches(&req("^1.2.0-rc.1"), &v("1.3.0-rc.1"), false));
+    }
+
+    #[test]
+    fn test_include_pre() {
+        assert!(matches(&req("^1.2.0"), &v("1.3.0-rc.1"), true));
+        assert!(!matches(&req("^1.2.0"), &v("1.2.0-rc.1"), true));
+        assert!(!matches(&req("^1.2.0"), &v("2.0.0-rc.1"), true));
+        assert!(matches(&req("*"), &v("2.0.0-alpha"), true));
+    }
+
+    #[test]
+    fn test_max_satisfying() {
+        let versions = ["1.2.0", "1.3.0", "1.4.0-rc.1", "2.0.0"];
+        assert_eq!(max_satisfying(versions, &req("^1.2"), false), Some(v("1.3.0")));
+        assert_eq!(max_satisfying(versions, &req("^1.2"), true), Some(v("1.4.0-rc.1")));
+        assert_eq!(latest(["1.0.0", "2.0.0-rc.1"], false), Some(v("1.0.0")));
+    }
+
+    #[test]
+    fn test_sort_versions() {
+        let mut versions: Vec<String> =
+            ["1.10.0", "1.2.0", "1.2.0-rc.1", "1.2.0-alpha", "1.9.0"].iter().map(|s| s.to_string()).collect();
+        sort_versions(&mut versions);
+        assert_eq!(versions, ["1.2.0-alpha", "1.2.0-rc.1", "1.2.0", "1.9.0", "1.10.0"]);
+    }
+
+    fn registry(id: &str) -> anyhow::Result<Vec<Candidate>> {
+        let entries: &[(&str, &[(&str, &str)])] = match id {
+            "app" => &[("1.0.0", &[("hello", "^1.0.0")]), ("2.0.0", &[("hello", "^2.0.0")])],
+            "hello" => &[("1.0.0", &[]), ("1.1.0", &[]), ("2.0.0", &[])],
+            "ui" => &[("1.0.0", &[("hello", "~1.0.0")])],
+            _ => anyhow::bail!("unknown package {}", id),
+        };
+        Ok(entries
+            .iter()
+            .map(|(version, deps)| Candidate {
+                version: v(version),
+                dependencies: deps.iter().map(|(d, r)| (d.to_string(), r.to_string())).collect(),
+            })
+            .collect())
+    }
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(id, range)| (id.to_string(), range.to_string())).collect()
+    }
+
+    fn resolve(
+        roots: &[(&str, &str)],
+        locked: &[(&str, &str)],
+        overrides: &[(&str, &str)],
+        strategy: Strategy,
+    ) -> Result<BTreeMap<String, Resolved>, Unsolvable> {
+        let locked = locked.iter().map(|(id, version)| (id.to_string(), v(version))).collect();
+        solve(&map(roots), &locked, &map(overrides), strategy, false, registry)
+    }
+
+    #[test]
+    fn test_solve() {
+        let solved = resolve(&[("app", "*")], &[], &[], Strategy::Highest).unwrap();
+        assert_eq!(solved["app"].version, v("2.0.0"));
+        assert_eq!(solved["app"].dependencies["hello"], v("2.0.0"));
+
+        let solved = resolve(&[("app", "*")], &[], &[], Strategy::Minimal).unwrap();
+        assert_eq!(
+            (&solved["app"].version, &solved["hello"].version),
+            (&v("1.0.0"), &v("1.0.0"))
+        );
+
+        // Backtracks to the app that works with the hello the manifest asks for
+        let solved = resolve(&[("app", "*"), ("hello", "^1.1")], &[], &[], Strategy::Highest).unwrap();
+        assert_eq!(
+            (&solved["app"].version, &solved["hello"].version),
+            (&v("1.0.0"), &v("1.1.0"))
+        );
+
+        let solved = resolve(&[("hello", "^1.0")], &[("hello", "1.0.0")], &[], Strategy::Highest).unwrap();
+        assert_eq!(solved["hello"].version, v("1.0.0"));
+    }
+
+    #[test]
+    fn test_solve_overrides() {
+        // ui asks for ~1.0.0, the override wins
+        let solved = resolve(
+            &[("app", "^2"), ("ui", "^1")],
+            &[],
+            &[("hello", "=2.0.0")],
+            Strategy::Highest,
+        )
+        .unwrap();
+        assert_eq!(solved["hello"].version, v("2.0.0"));
+        assert_eq!(solved["ui"].dependencies["hello"], v("2.0.0"));
+
+        let Err(Unsolvable::Conflict(conflict)) = resolve(&[("ui", "^1")], &[], &[("hello", "^3")], Strategy::Highest)
+        else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(
+            conflict.requirements,
+            [Requirement { range: "^3".into(), via: Vec::new() }]
+        );
+    }
+
+    #[test]
+    fn test_solve_conflict() {
+        let Err(Unsolvable::Conflict(conflict)) = resolve(&[("app", "^2"), ("ui", "^1")], &[], &[], Strategy::Highest)
+        else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflict.package, "hello");
+        assert_eq!(conflict.available.len(), 3);
+        let ranges: Vec<_> = conflict.requirements.iter().map(|r| r.range.as_str()).collect();
+        assert_eq!(ranges, ["^2.0.0", "~1.0.0"]);
+        assert_eq!(
+            conflict.requirements[1].via,
+            [Dependent { id: "ui".into(), version: v("1.0.0"), range: "^1".into() }]
+        );
+
+        assert!(matches!(
+            resolve(&[("missing", "*")], &[], &[], Strategy::Highest),
+            Err(Unsolvable::Failed(_))
+        ));
+    }
+}
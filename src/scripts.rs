@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::ffi::OsStr;
+use std::path::{Component, Path};
+use std::process::{Command, ExitStatus};
+use walkdir::WalkDir;
+
+use crate::error::VkError;
+use crate::host;
+use crate::i18n::t;
+use crate::manifest::{Hooks, PluginManifest};
+use crate::permissions;
+use crate::project::Project;
+use crate::reporter::Reporter;
+use crate::store;
+
+/// Environment variable naming the host runtime program.
+pub const HOST_RUNTIME_VAR: &str = "VK_HOST_RUNTIME";
+
+/// Lua files of the plugin that vk runs itself, one process per file. The
+/// manifest script of the same name, if any, runs them instead of the host
+/// runtime and receives the file path as argument.
+#[derive(Clone, Copy)]
+pub enum Suite {
+    Test,
+    Bench,
+}
+
+impl Suite {
+    fn name(self) -> &'static str {
+        match self {
+            Suite::Test => "test",
+            Suite::Bench => "bench",
+        }
+    }
+
+    fn file_variable(self) -> &'static str {
+        match self {
+            Suite::Test => "VK_TEST_FILE",
+            Suite::Bench => "VK_BENCH_FILE",
+        }
+    }
+
+    /// Directory holding the suite's files.
+    pub fn dir(self) -> &'static str {
+        match self {
+            Suite::Test => "tests",
+            Suite::Bench => "benches",
+        }
+    }
+
+    /// The suite's Lua files, relative to the project root with `/`
+    /// separators, in a stable order. Files starting with `_` are helpers
+    /// loaded by the others and are not run on their own.
+    pub fn files(self, project: &Project) -> Vec<String> {
+        let mut files: Vec<String> = WalkDir::new(project.root().join(self.dir()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy();
+                name.ends_with(".lua") && !name.starts_with('_')
+            })
+            .filter_map(|e| {
+                let relative = e.path().strip_prefix(project.root()).ok()?;
+                Some(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+/// Lifecycle hooks a plugin can declare for the projects that install it.
+#[derive(Clone, Copy)]
+pub enum Hook {
+    PostInstall,
+    PreRemove,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PostInstall => "postinstall",
+            Hook::PreRemove => "preremove",
+        }
+    }
+
+    fn script(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Hook::PostInstall => hooks.postinstall.as_deref(),
+            Hook::PreRemove => hooks.preremove.as_deref(),
+        }
+    }
+}
+
+/// Builds a command that runs `line` through the platform shell.
+fn shell(line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(line);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        command
+    }
+}
+
+/// Quotes an argument appended to a script line so the shell passes it through
+/// verbatim.
+fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Prepares `command` to run inside the project: the project root as cwd and
+/// the `VK_*` variables describing the package. A managed host runtime
+/// comes first in `PATH`, so scripts calling `vayload` get the pinned one.
+pub fn configure(command: &mut Command, project: &Project, manifest: &PluginManifest) {
+    command
+        .current_dir(project.root())
+        .env("VK_PROJECT_ROOT", project.root())
+        .env("VK_MANIFEST_PATH", project.manifest_path())
+        .env("VK_PACKAGE_NAME", &manifest.name)
+        .env("VK_PACKAGE_VERSION", &manifest.version)
+        .env("VK_VERSION", env!("CARGO_PKG_VERSION"));
+
+    if std::env::var_os(HOST_RUNTIME_VAR).is_none()
+        && let Some(runtime) = host::managed_runtime(Some(project))
+        && let Some(dir) = runtime.parent()
+    {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let paths = std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path));
+        if let Ok(path) = std::env::join_paths(paths) {
+            command.env("PATH", path);
+        }
+        command.env(HOST_RUNTIME_VAR, runtime);
+    }
+}
+
+fn wait(mut command: Command, name: &str) -> Result<()> {
+    let status = command.status().with_context(|| t!("scripts.spawn_failed", name = name))?;
+
+    if !status.success() {
+        return Err(VkError::ScriptFailed { name: name.to_string(), status: status.code() }.into());
+    }
+    Ok(())
+}
+
+/// Runs the script `name` from the manifest, appending `args` to it.
+pub fn run_script(project: &Project, manifest: &PluginManifest, name: &str, args: &[String]) -> Result<()> {
+    let Some(script) = manifest.scripts.get(name) else {
+        return Err(VkError::ScriptNotFound(name.to_string()).into());
+    };
+
+    let line = std::iter::once(script.clone()).chain(args.iter().map(|a| quote(a))).collect::<Vec<_>>().join(" ");
+
+    let mut command = shell(&line);
+    configure(&mut command, project, manifest);
+    command.env("VK_SCRIPT", name);
+
+    wait(command, name)
+}
+
+/// Runs a lifecycle hook if the manifest defines it. Returns whether it ran.
+pub fn run_lifecycle(project: &Project, manifest: &PluginManifest, name: &str) -> Result<bool> {
+    if !manifest.scripts.contains_key(name) {
+        return Ok(false);
+    }
+    run_script(project, manifest, name, &[])?;
+    Ok(true)
+}
+
+/// Runs an arbitrary program with the project environment.
+pub fn exec(project: &Project, manifest: &PluginManifest, program: &str, args: &[String]) -> Result<()> {
+    let mut command = Command::new(program);
+    command.args(args);
+    configure(&mut command, project, manifest);
+
+    wait(command, program)
+}
+
+/// Runs one file of `suite`: through `interpreter` when given, else through
+/// the manifest script named after the suite (`test`, `bench`), else in the
+/// host runtime (`vayload test <file>`) under the plugin's own permissions.
+/// `env` is passed to the runner along with the project variables.
+pub fn run_suite_file(
+    project: &Project,
+    manifest: &PluginManifest,
+    suite: Suite,
+    file: &str,
+    interpreter: Option<&str>,
+    env: &[(&str, &OsStr)],
+) -> Result<ExitStatus> {
+    let runner = interpreter.or_else(|| manifest.scripts.get(suite.name()).map(String::as_str));
+
+    let (mut command, name) = match runner {
+        Some(runner) => (shell(&format!("{} {}", runner, quote(file))), runner.to_string()),
+        None => {
+            let runtime = host_runtime();
+            let mut command = Command::new(&runtime);
+            command
+                .arg(suite.name())
+                .arg(file)
+                .env("VK_PERMISSIONS", serde_json::to_string(&manifest.permissions)?);
+            (command, runtime)
+        },
+    };
+    configure(&mut command, project, manifest);
+    command.env(suite.file_variable(), file).envs(env.iter().copied());
+
+    match command.status() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && runner.is_none() => {
+            Err(e).context(t!("scripts.runtime_missing", runtime = name, script = suite.name()))
+        },
+        Err(e) => Err(e).with_context(|| t!("scripts.spawn_failed", name = name)),
+        Ok(status) => Ok(status),
+    }
+}
+
+/// Program that runs plugin hooks and tests: the Vayload host runtime, which
+/// sandboxes the script according to `VK_PERMISSIONS`. `VK_HOST_RUNTIME`
+/// overrides it; otherwise the runtime managed by `vk host` for the current
+/// project is used, else `vayload` from `PATH`.
+pub fn host_runtime() -> String {
+    if let Ok(runtime) = std::env::var(HOST_RUNTIME_VAR) {
+        return runtime;
+    }
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| host::managed_runtime_at(&dir))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "vayload".to_string())
+}
+
+/// Runs `hook` of the plugin installed at `plugin_dir`, if it declares one.
+/// The hook and the plugin's permissions are shown first and nothing runs
+/// without the user's confirmation.
+pub fn run_plugin_hook(
+    project: &Project,
+    plugin_dir: &Path,
+    hook: Hook,
+    ignore_scripts: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let Some(manifest) = Project::at(plugin_dir).and_then(|p| p.read_manifest().ok()) else {
+        return Ok(());
+    };
+    let Some(script) = manifest.hooks.as_ref().and_then(|h| hook.script(h)) else {
+        return Ok(());
+    };
+    let plugin = format!("{}@{}", manifest.name, manifest.version);
+
+    if ignore_scripts {
+        reporter.detail(&t!("hooks.ignored", hook = hook.name(), plugin = plugin.cyan()));
+        return Ok(());
+    }
+    // Linked installs share their files with every project using the store
+    if store::is_shared(plugin_dir) {
+        reporter.warn(&t!("hooks.shared_skipped", hook = hook.name(), plugin = plugin.cyan()));
+        return Ok(());
+    }
+
+    // Hooks come from third-party plugins; keep them inside the plugin
+    let relative = Path::new(script);
+    if relative.is_absolute() || relative.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(VkError::ManifestInvalid(t!("hooks.outside_plugin", plugin = plugin, script = script)).into());
+    }
+
+    reporter.warn(&t!(
+        "hooks.declared",
+        plugin = plugin.cyan(),
+        hook = hook.name().bold(),
+        script = script.bright_black()
+    ));
+    reporter.output(&format!("  {}", t!("hooks.permissions")));
+    for line in permissions::describe(manifest.permissions.as_ref()) {
+        reporter.output(&format!("    {}", line));
+    }
+
+    if !reporter.is_interactive() {
+        reporter.warn(&t!("hooks.needs_confirmation"));
+        return Ok(());
+    }
+    if !reporter.confirm(&t!("hooks.confirm"))? {
+        reporter.detail(&t!("hooks.skipped", hook = hook.name(), plugin = plugin.cyan()));
+        return Ok(());
+    }
+
+    let runtime = host_runtime();
+    let mut command = Command::new(&runtime);
+    command
+        .arg("hook")
+        .arg(script)
+        .current_dir(plugin_dir)
+        .env("VK_HOOK", hook.name())
+        .env("VK_PERMISSIONS", serde_json::to_string(&manifest.permissions)?)
+        .env("VK_PROJECT_ROOT", project.root())
+        .env("VK_PACKAGE_NAME", &manifest.name)
+        .env("VK_PACKAGE_VERSION", &manifest.version)
+        .env("VK_VERSION", env!("CARGO_PKG_VERSION"));
+
+    reporter.step(&t!("hooks.running", hook = hook.name(), plugin = plugin.cyan()));
+
+    let name = format!("{} ({})", hook.name(), manifest.name);
+    match command.status() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            reporter.warn(&t!("hooks.runtime_missing", runtime = runtime, hook = hook.name()));
+            Ok(())
+        },
+        Err(e) => Err(e).with_context(|| t!("scripts.spawn_failed", name = name)),
+        Ok(status) if !status.success() => Err(VkError::ScriptFailed { name, status: status.code() }.into()),
+        Ok(_) => Ok(()),
+    }
+}
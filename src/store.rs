@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config;
+use crate::i18n::t;
+use crate::utils::{clear_readonly, copy_dir, hash_dir, remove_installed, symlink_dir};
+
+/// How installs use the shared store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum StoreMode {
+    /// Every install extracts its own copy; the store is not used.
+    #[default]
+    Off,
+    /// Packages are extracted into the store once and copied from there.
+    Copy,
+    /// Files are hard links to the store's, so each is on disk once.
+    Hardlink,
+    /// The plugin directory is a symlink to the store's.
+    Symlink,
+}
+
+/// Extracted registry packages shared by every project on the machine, at
+/// `<name>/<version>`, each next to the checksum of the archive it came
+/// from and the hash of its files. An entry is only used for the archive
+/// with that checksum, and while its files still hash the same. They are
+/// read-only, since linked installs share them.
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    pub fn open() -> Result<Self> {
+        Ok(Store { root: config::store_dir()? })
+    }
+
+    fn entry(&self, id: &str, version: &str) -> PathBuf {
+        self.root.join(id).join(version)
+    }
+
+    fn checksum_path(&self, id: &str, version: &str) -> PathBuf {
+        self.root.join(id).join(format!("{}.checksum", version))
+    }
+
+    fn integrity_path(&self, id: &str, version: &str) -> PathBuf {
+        self.root.join(id).join(format!("{}.integrity", version))
+    }
+
+    /// The stored copy of `id` at `version`, if it was extracted from the
+    /// archive with `checksum` and nothing changed it since.
+    pub fn get(&self, id: &str, version: &str, checksum: Option<&str>) -> Option<PathBuf> {
+        let stored = fs::read_to_string(self.checksum_path(id, version)).ok()?;
+        let entry = self.entry(id, version);
+        if Some(stored.trim()) != checksum || !entry.is_dir() {
+            return None;
+        }
+        let integrity = fs::read_to_string(self.integrity_path(id, version)).ok()?;
+        (hash_dir(&entry).ok()?.as_str() == integrity.trim()).then_some(entry)
+    }
+
+    /// Moves the plugin just installed at `installed` into the store, unless
+    /// it holds that archive already, and puts it back as `mode` asks.
+    /// Returns the mode used.
+    pub fn adopt(
+        &self,
+        id: &str,
+        version: &str,
+        checksum: &str,
+        installed: &Path,
+        mode: StoreMode,
+    ) -> Result<StoreMode> {
+        let entry = match self.get(id, version, Some(checksum)) {
+            Some(entry) => entry,
+            None => self.add(id, version, checksum, installed)?,
+        };
+        remove_installed(installed).with_context(|| t!("install.remove_old_failed"))?;
+        link(&entry, installed, mode)
+    }
+
+    fn add(&self, id: &str, version: &str, checksum: &str, installed: &Path) -> Result<PathBuf> {
+        let entry = self.entry(id, version);
+        let parent = entry.parent().unwrap_or(&self.root);
+        fs::create_dir_all(parent).with_context(|| t!("common.write_failed", path = parent.display()))?;
+
+        // Other installs may be storing the same version meanwhile
+        let partial = parent.join(format!(".{}.{}.partial", version, std::process::id()));
+        remove_installed(&partial).ok();
+        if fs::rename(installed, &partial).is_err() {
+            // Across filesystems the plugin has to be copied
+            copy_dir(installed, &partial)?;
+        }
+        seal(&partial).with_context(|| t!("common.write_failed", path = partial.display()))?;
+        let integrity = hash_dir(&partial)?;
+
+        remove_installed(&self.checksum_path(id, version)).ok();
+        remove_installed(&self.integrity_path(id, version)).ok();
+        remove_installed(&entry).with_context(|| t!("common.write_failed", path = entry.display()))?;
+        if let Err(err) = fs::rename(&partial, &entry) {
+            remove_installed(&partial).ok();
+            if !entry.is_dir() {
+                return Err(err).with_context(|| t!("common.write_failed", path = entry.display()));
+            }
+        }
+        let checksum_path = self.checksum_path(id, version);
+        fs::write(&checksum_path, checksum)
+            .with_context(|| t!("common.write_failed", path = checksum_path.display()))?;
+        let integrity_path = self.integrity_path(id, version);
+        fs::write(&integrity_path, integrity)
+            .with_context(|| t!("common.write_failed", path = integrity_path.display()))?;
+        Ok(entry)
+    }
+}
+
+/// Puts the stored `entry` at `dest` the way `mode` asks, falling back to
+/// hard links and then to a copy where the filesystem refuses. Returns the
+/// mode used.
+pub fn link(entry: &Path, dest: &Path, mode: StoreMode) -> Result<StoreMode> {
+    if mode == StoreMode::Symlink && symlink_dir(entry, dest).is_ok() {
+        return Ok(StoreMode::Symlink);
+    }
+    if matches!(mode, StoreMode::Symlink | StoreMode::Hardlink) && hard_link_dir(entry, dest).is_ok() {
+        return Ok(StoreMode::Hardlink);
+    }
+    remove_installed(dest).with_context(|| t!("install.remove_old_failed"))?;
+    copy_dir(entry, dest)?;
+    // Copies belong to the project, unlike the stored files
+    for file in WalkDir::new(dest) {
+        clear_readonly(file?.path()).with_context(|| t!("common.write_failed", path = dest.display()))?;
+    }
+    Ok(StoreMode::Copy)
+}
+
+/// Whether the plugin at `path` is linked to the store, so that writing into
+/// it would change the package for every project using it.
+pub fn is_shared(path: &Path) -> bool {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    if meta.file_type().is_symlink() {
+        let root = config::store_dir().and_then(|dir| Ok(fs::canonicalize(dir)?));
+        let (Ok(root), Ok(target)) = (root, fs::canonicalize(path)) else {
+            return false;
+        };
+        return target.starts_with(root);
+    }
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .any(|entry| entry.metadata().is_ok_and(|meta| is_hard_linked(&meta)))
+}
+
+#[cfg(unix)]
+fn is_hard_linked(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink() > 1
+}
+
+/// Windows has no stable link count; stored files are the read-only ones.
+#[cfg(not(unix))]
+fn is_hard_linked(meta: &fs::Metadata) -> bool {
+    meta.permissions().readonly()
+}
+
+/// Makes the files under `dir` read-only.
+fn seal(dir: &Path) -> std::io::Result<()> {
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let mut permissions = entry.metadata()?.permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(entry.path(), permissions)?;
+    }
+    Ok(())
+}
+
+/// Recreates the directories of `src` at `dest`, with hard links to its files.
+fn hard_link_dir(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let target = dest.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::hard_link(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store { root: dir.path().join("store") };
+        let installed = dir.path().join("plugins").join("ui");
+        fs::create_dir_all(&installed).unwrap();
+        fs::write(installed.join("init.lua"), "return {}").unwrap();
+
+        let mode = store.adopt("ui", "1.0.0", "abc", &installed, StoreMode::Hardlink).unwrap();
+        assert_eq!(mode, StoreMode::Hardlink);
+        assert_eq!(fs::read_to_string(installed.join("init.lua")).unwrap(), "return {}");
+        assert!(is_shared(&installed));
+
+        let entry = store.get("ui", "1.0.0", Some("abc")).unwrap();
+        assert!(entry.join("init.lua").is_file());
+        assert!(fs::metadata(entry.join("init.lua")).unwrap().permissions().readonly());
+        // Another archive under the same version is not the same package
+        assert!(store.get("ui", "1.0.0", Some("def")).is_none());
+        assert!(store.get("ui", "1.0.0", None).is_none());
+
+        // An entry changed after it was stored is not used again
+        clear_readonly(&entry.join("init.lua")).unwrap();
+        fs::write(entry.join("init.lua"), "return { edited = true }").unwrap();
+        assert!(store.get("ui", "1.0.0", Some("abc")).is_none());
+
+        // Copies are the project's own to change
+        let copied = dir.path().join("plugins").join("copied");
+        assert_eq!(link(&entry, &copied, StoreMode::Copy).unwrap(), StoreMode::Copy);
+        assert!(!fs::metadata(copied.join("init.lua")).unwrap().permissions().readonly());
+        assert!(!is_shared(&copied));
+    }
+}
@@ -0,0 +1,177 @@
+use semver::Version;
+use std::cell::OnceCell;
+use std::process::Command;
+
+use crate::manifest::Condition;
+use crate::resolver;
+use crate::scripts;
+
+/// The environment plugins are installed for: this machine, unless
+/// `vk install --target` or `--engine-version` say otherwise.
+#[derive(Debug, Default)]
+pub struct Target {
+    /// Operating system, as Rust names it (`linux`, `macos`, `windows`).
+    pub os: String,
+    /// CPU architecture, as Rust names it (`x86_64`, `aarch64`).
+    pub arch: String,
+    runtime: OnceCell<Runtime>,
+}
+
+/// The engines a plugin declares ranges for in `engines`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Host,
+    Lua,
+}
+
+impl Engine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Host => "host",
+            Engine::Lua => "lua",
+        }
+    }
+}
+
+/// Versions of the host runtime and of the Lua it embeds.
+#[derive(Debug, Clone, Default)]
+struct Runtime {
+    host: Option<Version>,
+    lua: Option<Version>,
+}
+
+impl Target {
+    pub fn local() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            runtime: OnceCell::new(),
+        }
+    }
+
+    /// This machine, with the OS and architecture of `platform`
+    /// (`<os>[-<arch>]`) and the engine versions given. An engine left out is
+    /// asked to the host runtime.
+    pub fn new(platform: Option<&str>, host: Option<&str>, lua: Option<&str>) -> Result<Self, String> {
+        let mut target = Self::local();
+        if let Some(platform) = platform {
+            let (os, arch) = platform.split_once('-').unwrap_or((platform, &target.arch));
+            if os.is_empty() || arch.is_empty() {
+                return Err(platform.to_string());
+            }
+            target.os = os.to_string();
+            target.arch = arch.to_string();
+        }
+        let parse = |version: Option<&str>| match version {
+            Some(version) => parse_loose(version).map(Some).ok_or_else(|| version.to_string()),
+            None => Ok(None),
+        };
+        let (host, lua) = (parse(host)?, parse(lua)?);
+        if host.is_some() || lua.is_some() {
+            let detected = if host.is_none() || lua.is_none() {
+                detect()
+            } else {
+                Runtime::default()
+            };
+            target.runtime = OnceCell::from(Runtime { host: host.or(detected.host), lua: lua.or(detected.lua) });
+        }
+        Ok(target)
+    }
+
+    /// Version of `engine`, as `vayload --version` reports it unless given.
+    /// None when the runtime is not installed or says nothing vk understands.
+    pub fn version(&self, engine: Engine) -> Option<&Version> {
+        let runtime = self.runtime.get_or_init(detect);
+        match engine {
+            Engine::Host => runtime.host.as_ref(),
+            Engine::Lua => runtime.lua.as_ref(),
+        }
+    }
+
+    /// Whether every part of `condition` holds here. A condition on the host
+    /// version does not hold when the version is unknown.
+    pub fn satisfies(&self, condition: &Condition) -> bool {
+        let listed = |names: &[String], value: &str| names.is_empty() || names.iter().any(|n| n == value);
+        listed(&condition.os, &self.os)
+            && listed(&condition.arch, &self.arch)
+            && condition.host.as_deref().is_none_or(|range| self.supports(Engine::Host, range) == Some(true))
+    }
+
+    /// Whether the version of `engine` is in `range`, or None when it is
+    /// unknown. Ranges that do not parse are not checked.
+    pub fn supports(&self, engine: Engine, range: &str) -> Option<bool> {
+        let Some(req) = resolver::parse_req(range) else {
+            return Some(true);
+        };
+        self.version(engine).map(|version| resolver::matches(&req, version, true))
+    }
+}
+
+/// Asks the host runtime for its version, e.g. `vayload 2.3.0 (Lua 5.4.6)`:
+/// the first version is the host's, the one after `Lua` the engine's.
+fn detect() -> Runtime {
+    let Ok(output) = Command::new(scripts::host_runtime()).arg("--version").output() else {
+        return Runtime::default();
+    };
+    parse_runtime(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_runtime(output: &str) -> Runtime {
+    let words: Vec<&str> =
+        output.split_whitespace().map(|w| w.trim_matches(|c| c == '(' || c == ')' || c == ',')).collect();
+    let lua_at = words.iter().position(|w| w.eq_ignore_ascii_case("lua"));
+    Runtime {
+        host: words.iter().take(lua_at.unwrap_or(words.len())).find_map(|w| resolver::parse_version(w)),
+        lua: lua_at.and_then(|i| words.get(i + 1)).and_then(|w| parse_loose(w)),
+    }
+}
+
+/// Parses a version that may leave out the minor and patch numbers (`5.4`).
+fn parse_loose(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    let parts = version.split('.').count();
+    resolver::parse_version(version)
+        .or_else(|| resolver::parse_version(&format!("{}{}", version, ".0".repeat(3usize.saturating_sub(parts)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_satisfies() {
+        let target = Target::new(Some("windows-aarch64"), Some("2.3.0"), Some("5.4")).unwrap();
+        let condition = |os: &[&str], arch: &[&str], host: Option<&str>| Condition {
+            os: os.iter().map(|s| s.to_string()).collect(),
+            arch: arch.iter().map(|s| s.to_string()).collect(),
+            host: host.map(String::from),
+        };
+
+        assert!(target.satisfies(&condition(&[], &[], None)));
+        assert!(target.satisfies(&condition(&["linux", "windows"], &["aarch64"], Some(">=2.0"))));
+        assert!(!target.satisfies(&condition(&["linux"], &[], None)));
+        assert!(!target.satisfies(&condition(&[], &["x86_64"], None)));
+        assert!(!target.satisfies(&condition(&[], &[], Some("^3"))));
+
+        assert_eq!(target.supports(Engine::Lua, "5.1"), Some(true));
+        assert_eq!(target.supports(Engine::Lua, "~5.1"), Some(false));
+
+        assert_eq!(
+            Target::new(Some("macos"), None, None).unwrap().arch,
+            std::env::consts::ARCH
+        );
+        assert!(Target::new(Some("-x86_64"), None, None).is_err());
+        assert!(Target::new(None, Some("two"), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_runtime() {
+        let runtime = parse_runtime("vayload 2.3.0 (Lua 5.4.6)\n");
+        assert_eq!(runtime.host, resolver::parse_version("2.3.0"));
+        assert_eq!(runtime.lua, resolver::parse_version("5.4.6"));
+
+        let runtime = parse_runtime("Lua 5.1, vayload v1.0.0");
+        assert_eq!(runtime.lua, resolver::parse_version("5.1.0"));
+        assert_eq!(runtime.host, None);
+    }
+}
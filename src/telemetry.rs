@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::error;
+use crate::i18n::t;
+use crate::reporter::Reporter;
+
+const SETTINGS_FILE: &str = "telemetry.json";
+const QUEUE_FILE: &str = "telemetry-queue.jsonl";
+
+/// Queued events are sent once there are this many, or once the oldest is a
+/// day old.
+const BATCH_SIZE: usize = 20;
+const BATCH_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Events kept while the endpoint cannot be reached; older ones are dropped.
+const QUEUE_LIMIT: usize = 200;
+const SEND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Whether usage metrics may be sent, kept in `~/.vayload-kit/telemetry.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Settings {
+    pub enabled: bool,
+    /// Whether the first-run question was answered, so it is asked once.
+    pub asked: bool,
+}
+
+/// One command run. Nothing in it identifies the user, the machine or the
+/// project.
+#[derive(Debug, Serialize, Deserialize)]
+struct Event {
+    command: String,
+    duration_ms: u64,
+    /// `success`, or the error code the command failed with.
+    outcome: String,
+    version: String,
+    timestamp: u64,
+}
+
+/// The environment variable turning telemetry off whatever the settings say:
+/// `DO_NOT_TRACK=1` or `VK_TELEMETRY=off`.
+pub fn disabled_by_env() -> Option<&'static str> {
+    if std::env::var("DO_NOT_TRACK").is_ok_and(|v| !v.is_empty() && v != "0") {
+        return Some("DO_NOT_TRACK");
+    }
+    if std::env::var("VK_TELEMETRY").is_ok_and(|v| matches!(v.to_ascii_lowercase().as_str(), "0" | "off" | "false")) {
+        return Some("VK_TELEMETRY");
+    }
+    None
+}
+
+pub fn load_settings() -> Settings {
+    config::data_dir()
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(SETTINGS_FILE)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_settings(settings: &Settings) -> Result<()> {
+    let dir = config::data_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(SETTINGS_FILE);
+    fs::write(&path, serde_json::to_string_pretty(settings)? + "\n")
+        .with_context(|| t!("common.write_failed", path = path.display()))
+}
+
+fn queue_path() -> Result<PathBuf> {
+    Ok(config::data_dir()?.join(QUEUE_FILE))
+}
+
+fn read_queue() -> Vec<Event> {
+    queue_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Events recorded but not sent yet.
+pub fn queued() -> usize {
+    read_queue().len()
+}
+
+/// Drops the events not sent yet.
+pub fn clear_queue() -> Result<()> {
+    match queue_path() {
+        Ok(path) if path.exists() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+/// Records a finished command and sends the queued events when a batch is
+/// due. The first interactive run asks whether to take part; until then, and
+/// in CI, nothing is recorded. Telemetry never makes a command fail.
+pub fn after_command(command: &str, duration: Duration, outcome: &Result<()>, endpoint: &str, reporter: &dyn Reporter) {
+    if command.is_empty() || command == "telemetry" || disabled_by_env().is_some() {
+        return;
+    }
+
+    let mut settings = load_settings();
+    if !settings.asked {
+        if !reporter.is_interactive() {
+            return;
+        }
+        reporter.info(&t!("telemetry.consent_details"));
+        settings.enabled = reporter.confirm(&t!("telemetry.consent_prompt")).unwrap_or(false);
+        settings.asked = true;
+        if save_settings(&settings).is_err() {
+            return;
+        }
+        reporter.detail(&t!("telemetry.consent_change"));
+    }
+    if !settings.enabled {
+        return;
+    }
+
+    let event = Event {
+        command: command.to_string(),
+        duration_ms: duration.as_millis() as u64,
+        outcome: match outcome {
+            Ok(()) => "success".to_string(),
+            Err(err) => error::classify(err).0.to_string(),
+        },
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        timestamp: now(),
+    };
+    if enqueue(&event).is_ok() {
+        let _ = flush_if_due(endpoint);
+    }
+}
+
+fn enqueue(event: &Event) -> Result<()> {
+    let path = queue_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+
+    let events = read_queue();
+    if events.len() > QUEUE_LIMIT {
+        write_queue(&events[events.len() - QUEUE_LIMIT..])?;
+    }
+    Ok(())
+}
+
+fn write_queue(events: &[Event]) -> Result<()> {
+    let path = queue_path()?;
+    let mut content = String::new();
+    for event in events {
+        content.push_str(&serde_json::to_string(event)?);
+        content.push('\n');
+    }
+    Ok(fs::write(path, content)?)
+}
+
+fn flush_if_due(endpoint: &str) -> Result<()> {
+    let events = read_queue();
+    let oldest = events.iter().map(|e| e.timestamp).min();
+    let due = events.len() >= BATCH_SIZE || oldest.is_some_and(|t| now().saturating_sub(t) >= BATCH_MAX_AGE.as_secs());
+    if !due {
+        return Ok(());
+    }
+
+    reqwest::blocking::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()?
+        .post(endpoint)
+        .json(&serde_json::json!({ "events": events }))
+        .send()?
+        .error_for_status()?;
+    clear_queue()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
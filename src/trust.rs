@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, AppInstall};
+use crate::encoding::json5;
+use crate::error::VkError;
+use crate::http_client::{ClientError, HttpClient};
+use crate::i18n::t;
+use crate::registry::RegistryClient;
+use crate::reporter::Reporter;
+
+/// Publishers approved at the install prompt, in `~/.vayload-kit`.
+pub const APPROVED_FILE: &str = "publishers.json5";
+
+/// Whose packages installs accept, going by the owners the registry reports
+/// for each package: the `trusted_publishers` and `blocked_publishers` of
+/// `config.toml`, plus the publishers approved when first installing one of
+/// their packages.
+#[derive(Debug)]
+pub struct Trust {
+    trusted: Vec<String>,
+    blocked: Vec<String>,
+    /// Refuse packages of publishers nobody trusted instead of asking.
+    require_trusted: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict<'a> {
+    Trusted,
+    Blocked(&'a str),
+    Unknown,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Approved {
+    #[serde(default)]
+    publishers: BTreeSet<String>,
+}
+
+impl Trust {
+    /// The trust settings of `install`, or `None` when nothing is configured
+    /// and every publisher is accepted.
+    pub fn new(install: &AppInstall, require_trusted: bool) -> Option<Self> {
+        let require_trusted = require_trusted || install.require_trusted;
+        if install.trusted_publishers.is_empty() && install.blocked_publishers.is_empty() && !require_trusted {
+            return None;
+        }
+        Some(Trust {
+            trusted: install.trusted_publishers.clone(),
+            blocked: install.blocked_publishers.clone(),
+            require_trusted,
+        })
+    }
+
+    /// Checks who publishes `id` before it is installed, asking whether to
+    /// trust publishers seen for the first time.
+    pub fn check(&self, id: &str, http_client: &HttpClient, reporter: &dyn Reporter) -> Result<()> {
+        let owners: Vec<String> = match RegistryClient::new(http_client).owners(id) {
+            Ok(owners) => owners.into_iter().map(|owner| owner.username).collect(),
+            // Index registries do not track ownership
+            Err(ClientError::Status { status: 501, .. }) if !self.require_trusted => {
+                reporter.detail(&t!("trust.owners_unknown", package = id.cyan()));
+                return Ok(());
+            },
+            Err(err) => return Err(VkError::package_lookup(err, id)),
+        };
+
+        let path = approved_path()?;
+        let mut approved = load_approved(&path)?;
+        let publishers = owners.join(", ");
+        match self.verdict(&owners, &approved.publishers) {
+            Verdict::Trusted => Ok(()),
+            Verdict::Blocked(publisher) => {
+                Err(VkError::PublisherBlocked { package: id.to_string(), publisher: publisher.to_string() }.into())
+            },
+            Verdict::Unknown if self.require_trusted => {
+                Err(VkError::PublisherNotTrusted { package: id.to_string(), publishers }.into())
+            },
+            Verdict::Unknown => {
+                reporter.warn(&t!(
+                    "trust.unknown_publisher",
+                    package = id.cyan(),
+                    publishers = publishers.yellow()
+                ));
+                if !reporter.confirm(&t!("trust.confirm"))? {
+                    return Err(VkError::PublisherNotTrusted { package: id.to_string(), publishers }.into());
+                }
+                approved.publishers.extend(owners);
+                save_approved(&path, &approved)?;
+                reporter.detail(&t!("trust.approved", path = path.display().to_string().bright_black()));
+                Ok(())
+            },
+        }
+    }
+
+    /// A blocked owner rules the package out even when another owner is
+    /// trusted.
+    fn verdict<'a>(&self, owners: &'a [String], approved: &BTreeSet<String>) -> Verdict<'a> {
+        let listed = |list: &[String], owner: &str| list.iter().any(|p| p.eq_ignore_ascii_case(owner));
+        if let Some(owner) = owners.iter().find(|owner| listed(&self.blocked, owner)) {
+            return Verdict::Blocked(owner);
+        }
+        let trusted = owners
+            .iter()
+            .any(|owner| listed(&self.trusted, owner) || (!self.require_trusted && approved.contains(owner)));
+        if trusted { Verdict::Trusted } else { Verdict::Unknown }
+    }
+}
+
+fn approved_path() -> Result<PathBuf> {
+    Ok(config::data_dir()?.join(APPROVED_FILE))
+}
+
+fn load_approved(path: &Path) -> Result<Approved> {
+    if !path.is_file() {
+        return Ok(Approved::default());
+    }
+    let content = fs::read_to_string(path).with_context(|| t!("common.read_failed", path = path.display()))?;
+    json5::from_str(&content).with_context(|| t!("trust.parse_failed", path = path.display()))
+}
+
+fn save_approved(path: &Path, approved: &Approved) -> Result<()> {
+    let write = || -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, json5::to_string_pretty(approved)? + "\n")?;
+        Ok(())
+    };
+    write().with_context(|| t!("common.write_failed", path = path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict() {
+        let trust = Trust {
+            trusted: vec!["Vayload".to_string()],
+            blocked: vec!["mallory".to_string()],
+            require_trusted: false,
+        };
+        let owners = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let approved = BTreeSet::from(["alice".to_string()]);
+
+        assert_eq!(trust.verdict(&owners(&["vayload"]), &approved), Verdict::Trusted);
+        assert_eq!(trust.verdict(&owners(&["alice"]), &approved), Verdict::Trusted);
+        assert_eq!(trust.verdict(&owners(&["bob"]), &approved), Verdict::Unknown);
+        assert_eq!(
+            trust.verdict(&owners(&["vayload", "mallory"]), &approved),
+            Verdict::Blocked("mallory")
+        );
+
+        // Approvals at the prompt do not count once trust is required
+        let strict = Trust { require_trusted: true, ..trust };
+        assert_eq!(strict.verdict(&owners(&["alice"]), &approved), Verdict::Unknown);
+        assert_eq!(strict.verdict(&owners(&["vayload"]), &approved), Verdict::Trusted);
+    }
+}
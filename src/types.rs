@@ -15,6 +15,8 @@ pub struct DownloadMeta {
     pub id: String,
     pub version: String,
     pub checksum: Option<String>,
+    /// Hash of the archive, when it was computed as the archive arrived.
+    pub sha256: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +30,8 @@ pub struct JsonResponse<T> {
 #[derive(Debug, Deserialize)]
 pub struct JsonResponseMeta {
     pub request_id: Option<String>,
+    /// Set on a page of a list that has more, to ask for the next.
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
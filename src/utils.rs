@@ -1,69 +1,103 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use globset::{Glob, GlobSet, GlobSetBuilder};
 use sha2::{Digest, Sha256};
-use std::fs::{self, File, read_to_string};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::{DirEntry, IntoIter as WalkDirIter, WalkDir};
 use zip::write::{FileOptions, SimpleFileOptions};
 use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
+use crate::assets;
+use crate::i18n::t;
+use crate::ignore::IgnoreRules;
 use crate::manifest::VKIGNORE_FILENAME;
+use crate::reporter::Reporter;
 
+/// Walks a directory, skipping whatever its ignore files exclude.
 pub struct FilteredWalker {
     root: PathBuf,
     walker: WalkDirIter,
-    builder: GlobSetBuilder,
-    ignore_set: Option<GlobSet>,
+    rules: IgnoreRules,
+    /// Names of the ignore files read from every directory on the way down,
+    /// in increasing precedence.
+    ignore_files: Vec<String>,
+    /// Rules that take precedence over every ignore file.
+    overrides: IgnoreRules,
+    /// When set, only files matched here (directly or through a parent
+    /// directory) are yielded, and ignore files are not read.
+    allow: Option<IgnoreRules>,
 }
 
 impl FilteredWalker {
     pub fn new<P: AsRef<Path>>(root: P) -> Self {
-        let mut builder = GlobSetBuilder::new();
+        let mut rules = IgnoreRules::default();
 
         // core ignore patterns
-        let default_ignores = [".git/**", ".svn/**", ".hg/**", ".vk/**", ".vkcache/**"];
-
-        for pattern in default_ignores.iter() {
-            builder.add(Glob::new(pattern).expect("Error creando patrón default"));
+        for pattern in [".git/", ".svn/", ".hg/", "/.vk/", "/.vkcache/"] {
+            rules.add_line(pattern, "");
         }
 
         Self {
             root: root.as_ref().to_path_buf(),
-            walker: WalkDir::new(&root).into_iter(),
-            builder,
-            ignore_set: None,
+            walker: WalkDir::new(&root).sort_by_file_name().into_iter(),
+            rules,
+            ignore_files: Vec::new(),
+            overrides: IgnoreRules::default(),
+            allow: None,
         }
     }
 
-    pub fn add_ignore_file(&mut self, filename: &Path) -> &mut Self {
-        let full_path = self.root.join(filename);
-        if let Ok(content) = read_to_string(full_path) {
-            for line in content.lines() {
-                let line = line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
+    /// Restricts the walk to files matching `patterns`, relative to the root.
+    /// A pattern naming a directory selects everything inside it.
+    pub fn only(&mut self, patterns: &[String]) -> &mut Self {
+        let mut allow = IgnoreRules::default();
+        for pattern in patterns {
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(rest) => ("!", rest),
+                None => ("", pattern.as_str()),
+            };
+            allow.add_line(&format!("{}/{}", negated, pattern.trim_start_matches('/')), "");
+        }
+        self.allow = Some(allow);
+        self
+    }
 
-                let pattern = if line.ends_with('/') {
-                    format!("**/{}/**", line.trim_end_matches('/'))
-                } else {
-                    format!("**/{}", line)
-                };
+    /// Ignores `patterns` (gitignore syntax) regardless of the ignore files.
+    pub fn exclude(&mut self, patterns: &[String]) -> &mut Self {
+        for pattern in patterns {
+            self.overrides.add_line(pattern, "");
+        }
+        self
+    }
 
-                if let Ok(glob) = Glob::new(&pattern) {
-                    self.builder.add(glob);
-                }
+    /// Whether the allowlist selects `path` or one of its parent directories,
+    /// the closest match deciding.
+    fn is_allowed(allow: &IgnoreRules, path: &str) -> bool {
+        if let Some(decision) = allow.decision(path, false) {
+            return decision;
+        }
+        let mut current = path;
+        while let Some((parent, _)) = current.rsplit_once('/') {
+            if let Some(decision) = allow.decision(parent, true) {
+                return decision;
             }
+            current = parent;
         }
+        false
+    }
+
+    /// Reads an ignore file with this name in every directory walked, like
+    /// git does for nested `.gitignore` files. Later names take precedence.
+    pub fn add_ignore_file_name(&mut self, name: &str) -> &mut Self {
+        self.ignore_files.push(name.to_string());
         self
     }
 
     #[allow(unused)]
     pub fn add_pattern(&mut self, pattern: &str) -> &mut Self {
-        if let Ok(glob) = Glob::new(pattern) {
-            self.builder.add(glob);
-        }
+        self.rules.add_line(pattern, "");
         self
     }
 }
@@ -72,27 +106,47 @@ impl Iterator for FilteredWalker {
     type Item = DirEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.ignore_set.is_none() {
-            self.ignore_set = Some(self.builder.build().expect("Error compilando patrones"));
-        }
-
-        let ignore_set = self.ignore_set.as_ref().unwrap();
-
         loop {
-            let entry = self.walker.next()?;
-
-            match entry {
-                Ok(e) => {
-                    if e.depth() > 0 && ignore_set.is_match(e.path()) {
-                        if e.file_type().is_dir() {
-                            self.walker.skip_current_dir();
-                        }
-                        continue;
-                    }
-                    return Some(e);
-                },
+            let entry = match self.walker.next()? {
+                Ok(e) => e,
                 Err(_) => continue,
+            };
+
+            let is_dir = entry.file_type().is_dir();
+            let Ok(relative) = entry.path().strip_prefix(&self.root) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+
+            if entry.depth() > 0 {
+                let ignored = self
+                    .overrides
+                    .decision(&relative, is_dir)
+                    .unwrap_or_else(|| self.rules.is_ignored(&relative, is_dir));
+
+                if ignored {
+                    if is_dir {
+                        self.walker.skip_current_dir();
+                    }
+                    continue;
+                }
+
+                // Directories stay open: a file deeper down may still be selected
+                let allowed = is_dir || self.allow.as_ref().is_none_or(|allow| Self::is_allowed(allow, &relative));
+                if !allowed {
+                    continue;
+                }
+            }
+
+            // Entries are yielded before their contents, so the rules of a
+            // directory are loaded before anything inside it is checked
+            if is_dir && self.allow.is_none() {
+                for name in &self.ignore_files {
+                    self.rules.add_file(&entry.path().join(name), &relative);
+                }
             }
+
+            return Some(entry);
         }
     }
 }
@@ -101,109 +155,391 @@ impl Iterator for FilteredWalker {
 // (Future: could be increased up to 250 MB for larger packages)
 const LIMIT_SIZE: usize = 25 * 1024 * 1024; // 25MB
 
-/// Creates a ZIP archive of the given directory.
-/// Returns a tuple of (ZIP bytes, SHA256 checksum).
-/// Respects .vkignore and .gitignore files, and enforces the size limit.
-pub fn create_zip(dir: &Path) -> Result<(Vec<u8>, String)> {
-    // Preallocate 10MB for the ZIP buffer for better performance
-    let cursor = std::io::Cursor::new(Vec::with_capacity(10 * 1024 * 1024));
-    let mut zip = ZipWriter::new(cursor);
+/// Deflate level used for every package, so archives do not change with the
+/// zip library defaults.
+const ARCHIVE_COMPRESSION_LEVEL: i64 = 6;
+
+/// Files that are always packaged when the manifest lists `files`.
+const ALWAYS_PACKAGED: &[&str] = &["plugin.json5", "plugin.json", "README*", "LICENSE*", "LICENCE*"];
+
+/// What goes into a package archive besides the files of its directory.
+#[derive(Default)]
+pub struct ArchiveSpec {
+    /// Manifest `files`: when set, only these paths are packaged and ignore
+    /// files are not consulted.
+    pub files: Option<Vec<String>>,
+    /// Manifest `exclude`: left out even when the ignore files or `files`
+    /// would include them.
+    pub exclude: Vec<String>,
+    /// Replaces the content of the files at these archive paths, adding the
+    /// ones the sources do not have.
+    pub replace: Vec<(String, Vec<u8>)>,
+    /// Directories added under an archive prefix (vendored path dependencies).
+    pub directories: Vec<(PathBuf, String)>,
+    /// Store symlinks as links instead of the content they point to. Links
+    /// to directories are otherwise left out.
+    pub symlinks: bool,
+    /// Keep directories with nothing packaged in them.
+    pub empty_dirs: bool,
+}
 
-    let options: SimpleFileOptions = FileOptions::default().compression_method(CompressionMethod::Deflated);
+/// What an archive entry holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    /// A link, with its target relative to the link's directory.
+    Symlink(String),
+    Dir,
+}
 
-    let vkignore = dir.join(VKIGNORE_FILENAME);
-    let gitignore = dir.join(".gitignore");
+/// A file selected for packaging.
+pub struct PackageFile {
+    pub path: PathBuf,
+    /// Path inside the archive, always with `/` separators.
+    pub name: String,
+    pub size: u64,
+    pub executable: bool,
+    pub kind: EntryKind,
+}
 
-    let mut walker = FilteredWalker::new(dir);
-    let mut total_size: usize = 0;
+/// A package archive and what went into it.
+pub struct PackedArchive {
+    pub data: Vec<u8>,
+    pub entries: Vec<PackedEntry>,
+}
 
-    // Load ignore rules if the files exist
-    if vkignore.exists() {
-        walker.add_ignore_file(&vkignore);
+pub struct PackedEntry {
+    /// Path inside the archive, always with `/` separators.
+    pub name: String,
+    pub size: u64,
+    pub compressed: u64,
+}
+
+impl PackedArchive {
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
     }
+}
 
-    if gitignore.exists() {
-        walker.add_ignore_file(&gitignore);
+/// Lists the files a package built from `dir` with `spec` would contain, in
+/// archive order, with the size they will have in the archive.
+pub fn package_files(dir: &Path, spec: &ArchiveSpec) -> Result<Vec<PackageFile>> {
+    let mut files = Vec::new();
+
+    collect_files(dir, "", spec.files.as_deref(), &spec.exclude, spec, &mut files)?;
+    for (source, prefix) in &spec.directories {
+        collect_files(source, prefix, None, &[], spec, &mut files)?;
     }
 
-    println!(
-        "\n{} Preparing package from: {}",
-        "📦".bold().blue(),
-        dir.display().to_string().bright_black()
-    );
-    println!("{}", "-".repeat(80));
-    println!("{:<2} {:<80} {:>10}", "", "File", "Size");
-    println!("{}", "-".repeat(80));
+    // A directory is only empty when nothing else was packaged under it
+    let names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+    files.retain(|f| f.kind != EntryKind::Dir || !names.iter().any(|n| n != &f.name && n.starts_with(&f.name)));
+
+    for file in &mut files {
+        if let Some((_, content)) = spec.replace.iter().find(|(target, _)| *target == file.name) {
+            file.size = content.len() as u64;
+        }
+    }
+    for (target, content) in &spec.replace {
+        if !files.iter().any(|f| f.name == *target) {
+            files.push(PackageFile {
+                path: dir.join(target),
+                name: target.clone(),
+                size: content.len() as u64,
+                executable: false,
+                kind: EntryKind::File,
+            });
+        }
+    }
+
+    // Archive order must not depend on the filesystem
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    only: Option<&[String]>,
+    exclude: &[String],
+    spec: &ArchiveSpec,
+    files: &mut Vec<PackageFile>,
+) -> Result<()> {
+    let mut walker = FilteredWalker::new(dir);
+    walker.exclude(exclude);
+
+    match only {
+        Some(only) => {
+            let patterns: Vec<String> =
+                ALWAYS_PACKAGED.iter().map(|p| p.to_string()).chain(only.iter().cloned()).collect();
+            walker.only(&patterns);
+        },
+        // .vkignore rules come last so they can override .gitignore
+        None => {
+            walker.add_ignore_file_name(".gitignore").add_ignore_file_name(VKIGNORE_FILENAME);
+        },
+    }
 
     for entry in walker {
         let path = entry.path();
 
         // Protect against directory traversal attacks
-        if path.starts_with(dir) {
-            let file_size = path.metadata()?.len() as usize;
-
-            // Enforce maximum ZIP size limit
-            if total_size + file_size > LIMIT_SIZE {
-                return Err(anyhow::anyhow!(
-                    "{} ZIP file size limit exceeded ({} bytes)",
-                    "⚠".yellow(),
-                    LIMIT_SIZE
-                ));
-            }
+        if !path.starts_with(dir) || entry.depth() == 0 {
+            continue;
+        }
 
-            if path.is_file() {
-                let name = path.strip_prefix(dir)?.to_str().context("invalid path")?;
-
-                // Add file to ZIP
-                zip.start_file(name, options)?;
-                let mut file = File::open(path)?;
-                std::io::copy(&mut file, &mut zip)?;
-                total_size += file_size;
-
-                println!(
-                    "{} {:<80} {:>10}",
-                    "✓".green(),
-                    name,
-                    format_bytes(file_size).bright_black()
-                );
+        let relative =
+            path.strip_prefix(dir)?.to_str().with_context(|| t!("pack.invalid_path", path = path.display()))?;
+        let name = format!("{}{}", prefix, relative.replace('\\', "/"));
+
+        if spec.symlinks && entry.path_is_symlink() {
+            let target = fs::read_link(path)?.to_string_lossy().replace('\\', "/");
+            if !link_stays_inside(relative, &target) {
+                return Err(anyhow::anyhow!(t!(
+                    "pack.symlink_outside",
+                    path = name,
+                    target = target
+                )));
             }
+            files.push(PackageFile {
+                path: path.to_path_buf(),
+                name,
+                size: target.len() as u64,
+                executable: false,
+                kind: EntryKind::Symlink(target),
+            });
+        } else if path.is_file() {
+            let metadata = path.metadata()?;
+            files.push(PackageFile {
+                path: path.to_path_buf(),
+                name,
+                size: metadata.len(),
+                executable: is_executable(&metadata),
+                kind: EntryKind::File,
+            });
+        } else if spec.empty_dirs && entry.file_type().is_dir() {
+            files.push(PackageFile {
+                path: path.to_path_buf(),
+                name: name + "/",
+                size: 0,
+                executable: false,
+                kind: EntryKind::Dir,
+            });
         }
     }
 
+    Ok(())
+}
+
+fn entry_options() -> SimpleFileOptions {
+    FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .compression_level(Some(ARCHIVE_COMPRESSION_LEVEL))
+        .last_modified_time(zip::DateTime::DEFAULT)
+}
+
+/// Compresses the regular files among `files` on a pool of threads, each
+/// writing its own in-memory archive. Returns every worker's archive with the
+/// indices (into `files`) of the entries it holds, in the order they were
+/// written.
+fn compress_parallel(files: &[PackageFile], spec: &ArchiveSpec) -> Result<Vec<(Vec<u8>, Vec<usize>)>> {
+    let options = entry_options();
+
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(files.len()).max(1);
+    let next = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<(Vec<u8>, Vec<usize>)> {
+                    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+                    let mut indices = Vec::new();
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else { break };
+
+                        // Links and directories have nothing to compress
+                        if file.kind != EntryKind::File {
+                            continue;
+                        }
+
+                        let mode = if file.executable { 0o755 } else { 0o644 };
+                        zip.start_file(file.name.as_str(), options.unix_permissions(mode))?;
+                        match spec.replace.iter().find(|(target, _)| *target == file.name) {
+                            Some((_, content)) => zip.write_all(content)?,
+                            None => {
+                                let mut source = File::open(&file.path)?;
+                                std::io::copy(&mut source, &mut zip)?;
+                            },
+                        }
+                        indices.push(index);
+                    }
+
+                    Ok((zip.finish()?.into_inner(), indices))
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().expect("compression worker panicked")).collect()
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Creates a ZIP archive of the given directory as described by `spec`.
+/// Respects .vkignore and .gitignore files, and enforces the size limit.
+///
+/// The output is reproducible: entries are sorted, timestamps are fixed to
+/// the ZIP epoch (1980-01-01), permissions are reduced to 644/755 and the
+/// compression level is pinned, so the same sources always give the same
+/// checksum.
+pub fn create_zip(dir: &Path, spec: &ArchiveSpec, reporter: &dyn Reporter) -> Result<PackedArchive> {
+    reporter.step(&t!("pack.preparing", path = dir.display().to_string().bright_black()));
+    for (source, prefix) in &spec.directories {
+        reporter.step(&t!(
+            "pack.vendoring",
+            path = source.display().to_string().bright_black(),
+            prefix = prefix
+        ));
+    }
+
+    let files = package_files(dir, spec)?;
+
+    // Enforce maximum ZIP size limit
+    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    if total_size > LIMIT_SIZE as u64 {
+        return Err(anyhow::anyhow!(t!("pack.size_limit", limit = LIMIT_SIZE)));
+    }
     if total_size == 0 {
-        return Err(anyhow::anyhow!("{} No files to include in the package", "⚠".yellow()));
+        return Err(anyhow::anyhow!(t!("pack.empty")));
+    }
+    for file in assets::large_binaries(&files) {
+        reporter.warn(&t!(
+            "pack.large_binary",
+            path = file.name,
+            size = format_bytes(file.size as usize)
+        ));
+    }
+
+    let chunks = compress_parallel(&files, spec)?;
+
+    // Preallocate 10MB for the ZIP buffer for better performance
+    let cursor = std::io::Cursor::new(Vec::with_capacity(10 * 1024 * 1024));
+    let mut zip = ZipWriter::new(cursor);
+    let mut entries = Vec::with_capacity(files.len());
+
+    // Copy the compressed entries back in archive order
+    let mut located = vec![(0, 0); files.len()];
+    let mut archives = Vec::with_capacity(chunks.len());
+    for (worker, (data, indices)) in chunks.into_iter().enumerate() {
+        for (position, index) in indices.into_iter().enumerate() {
+            located[index] = (worker, position);
+        }
+        archives.push(ZipArchive::new(std::io::Cursor::new(data))?);
+    }
+
+    for (file, (worker, position)) in files.into_iter().zip(located) {
+        // Written here: raw copies would lose the entry type
+        let compressed = match &file.kind {
+            EntryKind::Symlink(target) => {
+                zip.add_symlink(file.name.as_str(), target, entry_options())?;
+                file.size
+            },
+            EntryKind::Dir => {
+                zip.add_directory(file.name.as_str(), entry_options().unix_permissions(0o755))?;
+                0
+            },
+            EntryKind::File => {
+                let entry = archives[worker].by_index_raw(position)?;
+                let compressed = entry.compressed_size();
+                zip.raw_copy_file(entry)?;
+                compressed
+            },
+        };
+
+        reporter.detail(&format!("{:<70} {:>10}", file.name, format_bytes(file.size as usize)));
+        entries.push(PackedEntry { name: file.name, size: file.size, compressed });
     }
 
     let cursor = zip.finish()?;
-    let buffer = cursor.into_inner();
+    let data = cursor.into_inner();
 
-    println!("{}", "-".repeat(80));
-    println!(
-        "{} Original size: {}, Compressed size: {}",
-        "ℹ".bright_blue(),
-        format_bytes(total_size).bright_black(),
-        format_bytes(buffer.len()).bright_black()
-    );
+    reporter.info(&t!(
+        "pack.sizes",
+        original = format_bytes(total_size as usize).bright_black(),
+        compressed = format_bytes(data.len()).bright_black()
+    ));
 
-    let mut hasher = Sha256::new();
-    hasher.update(&buffer);
-    let checksum = hex::encode(hasher.finalize());
+    let checksum = sha256_hex(&data);
 
-    println!("{} SHA256 checksum: {}", "🔑".bright_black(), checksum);
+    reporter.info(&t!("pack.checksum", checksum = checksum));
+
+    Ok(PackedArchive { data, entries })
+}
 
-    Ok((buffer, checksum))
+/// Reads one file of a zip archive into memory, `None` when it is missing.
+pub fn read_zip_file(data: &[u8], name: &str) -> Result<Option<Vec<u8>>> {
+    let mut archive = ZipArchive::new(std::io::Cursor::new(data))?;
+    let Ok(mut file) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut content = Vec::new();
+    std::io::Read::read_to_end(&mut file, &mut content)?;
+    Ok(Some(content))
 }
 
-pub fn extract_zip(data: &[u8], dest_dir: &Path) -> Result<()> {
+/// Extracts an archive into `dest_dir`. Entries escaping it are skipped. On
+/// Windows, names NTFS rejects are rewritten (see `sanitize_windows_name`)
+/// and paths past the 260 character limit are written through `\\?\`.
+///
+/// Symlinks are only restored with `symlinks`, and only when they resolve to
+/// something inside `dest_dir`, through any other link; otherwise they are
+/// left out and their names returned.
+/// Where links cannot be created, their target is copied instead.
+pub fn extract_zip(data: &[u8], dest_dir: &Path, symlinks: bool) -> Result<Vec<String>> {
     let cursor = std::io::Cursor::new(data);
     let mut archive = ZipArchive::new(cursor)?;
+    let dest_dir = long_path(dest_dir)?;
+    let mut links = Vec::new();
+    let mut skipped = Vec::new();
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
-        let outpath = match file.enclosed_name() {
-            Some(path) => dest_dir.join(path),
-            None => continue,
+        let Some(name) = file.enclosed_name() else {
+            continue;
         };
+        let mut outpath = dest_dir.clone();
+        for component in name.components() {
+            let component = component.as_os_str().to_string_lossy();
+            if cfg!(windows) {
+                outpath.push(sanitize_windows_name(&component));
+            } else {
+                outpath.push(component.as_ref());
+            }
+        }
+
+        if file.is_symlink() {
+            let mut target = String::new();
+            std::io::Read::read_to_string(&mut file, &mut target)?;
+            let relative = name.to_string_lossy().replace('\\', "/");
+            if symlinks && link_stays_inside(&relative, &target) {
+                links.push((relative, outpath, target));
+            } else {
+                skipped.push(relative);
+            }
+            continue;
+        }
 
         if file.is_dir() {
             fs::create_dir_all(&outpath)?;
@@ -211,22 +547,281 @@ pub fn extract_zip(data: &[u8], dest_dir: &Path) -> Result<()> {
             if let Some(parent) = outpath.parent() {
                 fs::create_dir_all(parent)?;
             }
+            // Extracting over an earlier copy must be able to replace read-only files
+            clear_readonly(&outpath)?;
             let mut outfile = File::create(&outpath)?;
             std::io::copy(&mut file, &mut outfile)?;
         }
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Some(mode) = file.unix_mode() {
+        if let Some(mode) = file.unix_mode() {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
                 fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
             }
+            // Only the owner's write bit has a Windows equivalent
+            #[cfg(windows)]
+            if !file.is_dir() && mode & 0o200 == 0 {
+                let mut permissions = fs::metadata(&outpath)?.permissions();
+                permissions.set_readonly(true);
+                fs::set_permissions(&outpath, permissions)?;
+            }
+            #[cfg(not(any(unix, windows)))]
+            let _ = mode;
+        }
+    }
+
+    // Links go last so the copies made where links are unavailable find their target
+    let mut restored = Vec::new();
+    for (relative, link, target) in links {
+        // Each link is only checked on its own, so one placed through another
+        // (`a -> .`, then `a/b/c -> ../..`) would land elsewhere than checked
+        if through_link(&dest_dir, &link) {
+            skipped.push(relative);
+            continue;
+        }
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        remove_installed(&link)?;
+        restore_symlink(&link, &target)?;
+        restored.push((relative, link));
+    }
+
+    // Targets may also go through other links; once all are in place, what
+    // each resolves to is checked for real
+    let root = match restored.is_empty() {
+        true => dest_dir.clone(),
+        false => dest_dir.canonicalize()?,
+    };
+    for (relative, link) in restored {
+        if !link.canonicalize().is_ok_and(|resolved| resolved.starts_with(&root)) {
+            remove_installed(&link)?;
+            skipped.push(relative);
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Whether a directory between `root` and `path` is a symlink.
+fn through_link(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != root && dir.starts_with(root))
+        .any(|dir| fs::symlink_metadata(dir).is_ok_and(|m| m.file_type().is_symlink()))
+}
+
+/// Whether a symlink at `link` (relative to the package root, `/`-separated)
+/// pointing at `target` resolves inside the package.
+pub fn link_stays_inside(link: &str, target: &str) -> bool {
+    if target.is_empty() || target.starts_with(['/', '\\']) || target.contains(':') {
+        return false;
+    }
+
+    let mut depth = link.split('/').filter(|p| !p.is_empty()).count() as isize - 1;
+    for part in target.split(['/', '\\']) {
+        match part {
+            "" | "." => {},
+            ".." => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+            },
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+fn restore_symlink(link: &Path, target: &str) -> Result<()> {
+    #[cfg(unix)]
+    return Ok(std::os::unix::fs::symlink(target, link)?);
+
+    // Creating links needs developer mode or elevation on Windows
+    #[cfg(windows)]
+    {
+        let target = target.replace('/', "\\");
+        let resolved = link.parent().unwrap_or(link).join(&target);
+        let created = if resolved.is_dir() {
+            std::os::windows::fs::symlink_dir(&target, link)
+        } else {
+            std::os::windows::fs::symlink_file(&target, link)
+        };
+        if created.is_err() {
+            if resolved.is_dir() {
+                copy_dir(&resolved, link)?;
+            } else {
+                fs::copy(&resolved, link)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Characters NTFS does not allow in file names, besides control characters.
+const NTFS_RESERVED_CHARS: &str = "<>:\"/\\|?*";
+/// Device names Windows reserves, with or without an extension.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Rewrites a file name so Windows can create it: reserved and control
+/// characters become `_`, trailing dots and spaces are dropped and device
+/// names such as `con.lua` get a `_` after their stem (`con_.lua`).
+pub fn sanitize_windows_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || NTFS_RESERVED_CHARS.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    if sanitized.is_empty() {
+        return "_".to_string();
+    }
+
+    let stem_len = sanitized.find('.').unwrap_or(sanitized.len());
+    if WINDOWS_RESERVED_NAMES.iter().any(|r| sanitized[..stem_len].trim_end().eq_ignore_ascii_case(r)) {
+        sanitized.insert(stem_len, '_');
+    }
+    sanitized
+}
+
+/// Makes an existing file writable so it can be replaced or removed.
+pub fn clear_readonly(path: &Path) -> std::io::Result<()> {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !meta.is_file() || !meta.permissions().readonly() {
+        return Ok(());
+    }
+
+    let mut permissions = meta.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+/// The absolute form of `path`, with the `\\?\` prefix on Windows so it
+/// may exceed 260 characters.
+fn long_path(path: &Path) -> std::io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    #[cfg(windows)]
+    {
+        let text = path.as_os_str().to_string_lossy();
+        if !text.starts_with(r"\\?\") {
+            return Ok(match text.strip_prefix(r"\\") {
+                Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+                None => PathBuf::from(format!(r"\\?\{}", text)),
+            });
+        }
+    }
+    Ok(path)
+}
+
+/// Points `dest` at `src` with a directory symlink, copying the tree instead
+/// when the platform refuses (e.g. Windows without developer mode).
+/// Returns whether a link was created.
+pub fn link_or_copy_dir(src: &Path, dest: &Path) -> Result<bool> {
+    if symlink_dir(src, dest).is_ok() {
+        return Ok(true);
+    }
+
+    copy_dir(src, dest)?;
+    Ok(false)
+}
+
+pub fn symlink_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(src, dest);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_dir(src, dest);
+}
+
+/// Recursively copies `src` into `dest`, leaving out version control data.
+pub fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    let walker = WalkDir::new(src).into_iter().filter_entry(|e| e.file_name() != ".git");
+
+    for entry in walker {
+        let entry = entry?;
+        let target = dest.join(entry.path().strip_prefix(src)?);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target).with_context(|| t!("common.write_failed", path = target.display()))?;
+        } else {
+            fs::copy(entry.path(), &target).with_context(|| t!("common.write_failed", path = target.display()))?;
         }
     }
 
     Ok(())
 }
 
+/// Removes an installed plugin directory. Links are removed without touching
+/// the directory they point to.
+pub fn remove_installed(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::remove_file(path).or_else(|_| fs::remove_dir(path)),
+        Ok(_) => {
+            // Windows refuses to delete read-only files
+            if cfg!(windows) {
+                for entry in WalkDir::new(path).into_iter().flatten() {
+                    clear_readonly(entry.path())?;
+                }
+            }
+            fs::remove_dir_all(long_path(path)?)
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Hashes a directory tree: every file's path relative to `dir` and its
+/// contents, in path order, so the result does not depend on the filesystem
+/// or on timestamps. Symlinks count with their target.
+pub fn hash_dir(dir: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let content = if entry.file_type().is_file() {
+            fs::read(entry.path()).with_context(|| t!("common.read_failed", path = entry.path().display()))?
+        } else if entry.path_is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            format!("-> {}", target.to_string_lossy().replace('\\', "/")).into_bytes()
+        } else {
+            continue;
+        };
+
+        let relative = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
 pub fn parse_package(spec: &str) -> (String, Option<String>) {
     match spec.split_once('@') {
         Some((id, version)) => (id.to_string(), Some(version.to_string())),
@@ -246,3 +841,96 @@ pub fn format_bytes(bytes: usize) -> String {
         format!("{} B", bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_windows_name() {
+        assert_eq!(sanitize_windows_name("init.lua"), "init.lua");
+        assert_eq!(sanitize_windows_name("a:b|c?.lua"), "a_b_c_.lua");
+        assert_eq!(sanitize_windows_name("notes. "), "notes");
+        assert_eq!(sanitize_windows_name("con.lua"), "con_.lua");
+        assert_eq!(sanitize_windows_name("COM1"), "COM1_");
+        assert_eq!(sanitize_windows_name("console.lua"), "console.lua");
+        assert_eq!(sanitize_windows_name(".."), "_");
+    }
+
+    #[test]
+    fn test_extract_zip_replaces_read_only_files() {
+        let archive = |content: &[u8]| {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+            zip.start_file("src/init.lua", SimpleFileOptions::default().unix_permissions(0o444)).unwrap();
+            zip.write_all(content).unwrap();
+            zip.finish().unwrap().into_inner()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        extract_zip(&archive(b"first"), dir.path(), false).unwrap();
+        extract_zip(&archive(b"again"), dir.path(), false).unwrap();
+        assert_eq!(fs::read(dir.path().join("src/init.lua")).unwrap(), b"again");
+
+        remove_installed(dir.path()).unwrap();
+        assert!(!dir.path().exists());
+    }
+
+    #[test]
+    fn test_link_stays_inside() {
+        assert!(link_stays_inside("init.lua", "src/init.lua"));
+        assert!(link_stays_inside("lib/current", "../vendor/lib"));
+        assert!(link_stays_inside("a/b/link", "../../c"));
+        assert!(!link_stays_inside("a/b/link", "../../../c"));
+        assert!(!link_stays_inside("link", "../outside"));
+        assert!(!link_stays_inside("link", "/etc/passwd"));
+        assert!(!link_stays_inside("link", "C:\\Windows"));
+        assert!(!link_stays_inside("link", "src/../../x"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_and_empty_dirs_roundtrip() {
+        let source = tempfile::tempdir().unwrap();
+        fs::create_dir_all(source.path().join("src")).unwrap();
+        fs::create_dir_all(source.path().join("data/cache")).unwrap();
+        fs::write(source.path().join("src/init.lua"), "return {}").unwrap();
+        std::os::unix::fs::symlink("src/init.lua", source.path().join("main.lua")).unwrap();
+
+        let spec = ArchiveSpec { symlinks: true, empty_dirs: true, ..Default::default() };
+        let packed = create_zip(source.path(), &spec, &crate::reporter::QuietReporter).unwrap();
+        let names: Vec<_> = packed.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["data/cache/", "main.lua", "src/init.lua"]);
+
+        let skipped = tempfile::tempdir().unwrap();
+        assert_eq!(extract_zip(&packed.data, skipped.path(), false).unwrap(), ["main.lua"]);
+        assert!(skipped.path().join("data/cache").is_dir());
+        assert!(!skipped.path().join("main.lua").exists());
+
+        let restored = tempfile::tempdir().unwrap();
+        assert!(extract_zip(&packed.data, restored.path(), true).unwrap().is_empty());
+        let link = restored.path().join("main.lua");
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("src/init.lua"));
+        assert_eq!(fs::read_to_string(link).unwrap(), "return {}");
+
+        std::os::unix::fs::symlink("../../etc", source.path().join("escape")).unwrap();
+        assert!(create_zip(source.path(), &spec, &crate::reporter::QuietReporter).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_chained_symlinks_stay_inside() {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, target) in [("a", "."), ("a/b/c", "../.."), ("d", "a/a/a/.."), ("e", "missing")] {
+            zip.add_symlink(name, target, SimpleFileOptions::default()).unwrap();
+        }
+        let data = zip.finish().unwrap().into_inner();
+
+        let parent = tempfile::tempdir().unwrap();
+        let dest = parent.path().join("plugin");
+        let skipped = extract_zip(&data, &dest, true).unwrap();
+        assert_eq!(skipped, ["a/b/c", "d", "e"]);
+        assert_eq!(fs::read_link(dest.join("a")).unwrap(), Path::new("."));
+        assert!(!dest.join("b").exists() && !dest.join("d").exists());
+        assert_eq!(fs::read_dir(parent.path()).unwrap().count(), 1);
+    }
+}